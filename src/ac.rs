@@ -0,0 +1,765 @@
+//! AC analysis
+//!
+//! Small-signal analysis of a linear circuit at a single frequency.
+//! Independent sources are driven by their AC specification, not by
+//! their DC value, so a DC bias source with no AC specification is a
+//! short circuit (voltage source) or open circuit (current source)
+//! in this analysis.
+//!
+//! Nonlinear components are linearised about the DC operating
+//! point, which is solved first if the circuit contains any.
+//! Thyristors and IGBTs are in their off state. An AC sweep
+//! ([ac_sweep]) solves the circuit at frequencies spaced by decade,
+//! octave or linearly, as in a SPICE `.AC` analysis.
+
+use std::f64::consts::PI;
+use std::fmt;
+
+use num::Complex;
+
+use crate::circuit::Circuit;
+use crate::component::{AcSpec, Component, NOMINAL_TEMPERATURE};
+use crate::dc::{explain, solve_elaborated_options, try_solve_elaborated, DcOptions};
+use crate::error::{invalid, no_instance, EsimError};
+use crate::mna::Mna;
+use crate::node::NodeNames;
+use crate::solver::linearise_transistor;
+use crate::sparse::CachedSolver;
+
+/// Node voltages and edge currents of an AC solution
+type Phasors = (Vec<Complex<f64>>, Vec<Complex<f64>>);
+
+pub struct LinearAcAnalysis {
+    /// The elaborated circuit
+    circuit: Circuit,
+    /// Node voltages at the DC operating point
+    dc_voltages: Vec<f64>,
+    /// Edge currents at the DC operating point
+    dc_currents: Vec<f64>,
+    /// Warnings of solving the operating point
+    warnings: Vec<String>,
+}
+
+impl LinearAcAnalysis {
+    pub fn new(circuit: &Circuit) -> Self {
+        Self::with_options(circuit, &DcOptions::default())
+    }
+
+    /// The analysis of a circuit, solving its operating point (if it
+    /// is nonlinear) with options
+    pub fn with_options(circuit: &Circuit, options: &DcOptions) -> Self {
+        let circuit = circuit.elaborate();
+        let mut warnings = Vec::new();
+        let (dc_voltages, dc_currents) = if nonlinear(&circuit) {
+            solve_elaborated_options(&circuit, options, &mut warnings)
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        Self {
+            circuit,
+            dc_voltages,
+            dc_currents,
+            warnings,
+        }
+    }
+
+    /// The analysis of a circuit as for [LinearAcAnalysis::with_options],
+    /// returning an error if the circuit fails its check (see
+    /// [Circuit::validate]), or its operating point cannot be solved
+    /// or does not converge
+    pub fn try_new(circuit: &Circuit, options: &DcOptions) -> Result<Self, EsimError> {
+        circuit.validate()?;
+        let elaborated = circuit.elaborate();
+        let (dc_voltages, dc_currents) = if nonlinear(&elaborated) {
+            try_solve_elaborated(&elaborated, options).map_err(|error| explain(circuit, error))?
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        Ok(Self {
+            circuit: elaborated,
+            dc_voltages,
+            dc_currents,
+            warnings: Vec::new(),
+        })
+    }
+
+    /// Warnings of solving the operating point, such as a Newton
+    /// iteration that did not converge
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// The elaborated circuit
+    pub(crate) fn circuit(&self) -> &Circuit {
+        &self.circuit
+    }
+
+    /// Component of the instance of the elaborated circuit with a
+    /// name, or an error if there is none
+    pub(crate) fn component(&self, name: &str) -> Result<&Component, EsimError> {
+        self.circuit
+            .instances()
+            .iter()
+            .find(|i| i.name == name)
+            .map(|i| &i.component)
+            .ok_or_else(|| no_instance(name))
+    }
+
+    /// Component of the independent source of the elaborated circuit
+    /// with a name, which is the input of an analysis, or an error if
+    /// there is no such source
+    pub(crate) fn input_source(&self, name: &str) -> Result<&Component, EsimError> {
+        match self.component(name)? {
+            source @ (Component::IndependentVoltageSource { .. }
+            | Component::IndependentCurrentSource { .. }) => Ok(source),
+            _ => Err(invalid(format!("{name} is not an independent source"))),
+        }
+    }
+
+    /// The output nodes of an analysis, which must be in the circuit
+    /// (ground is node 0)
+    pub(crate) fn check_output(&self, (pos, neg): (usize, usize)) -> Result<(), EsimError> {
+        match [pos, neg]
+            .into_iter()
+            .find(|node| *node > self.circuit.num_voltage_nodes())
+        {
+            Some(node) => Err(invalid(format!("No output node {node}"))),
+            None => Ok(()),
+        }
+    }
+
+    /// Voltage of a node at the DC operating point
+    pub(crate) fn dc_voltage(&self, node: usize) -> f64 {
+        if node == 0 {
+            0.0
+        } else {
+            self.dc_voltages[node - 1]
+        }
+    }
+
+    /// Assemble the MNA system at the frequency (in Hz), with the
+    /// independent sources driven by their AC specifications, or set
+    /// to zero if sources is false
+    pub(crate) fn assemble(&self, frequency: f64, sources: bool) -> Mna<Complex<f64>> {
+        let omega = 2.0 * PI * frequency;
+        let excitation = |ac: AcSpec| {
+            if sources {
+                ac.phasor()
+            } else {
+                Complex::new(0.0, 0.0)
+            }
+        };
+        let mut mna = Mna::new();
+        for instance in self.circuit.instances() {
+            match instance.component {
+                Component::Resistor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    resistance,
+                } => mna.add_resistor(term_1, term_2, current_edge, resistance.into()),
+                Component::Thermistor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    model,
+                    temperature,
+                } => {
+                    let resistance = model.resistance(temperature.unwrap_or(NOMINAL_TEMPERATURE));
+                    mna.add_resistor(term_1, term_2, current_edge, resistance.into())
+                }
+                Component::Capacitor {
+                    term_1,
+                    term_2,
+                    capacitance,
+                } => mna.add_admittance(term_1, term_2, Complex::new(0.0, omega * capacitance)),
+                Component::Inductor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    inductance,
+                } => {
+                    // The impedance is stamped in the same way as a group 2 resistance
+                    let impedance = Complex::new(0.0, omega * inductance);
+                    mna.add_resistor(term_1, term_2, Some(current_edge), impedance)
+                }
+                Component::SaturableInductor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    ref curve,
+                } => {
+                    let inductance = curve.inductance(self.dc_currents[current_edge]);
+                    let impedance = Complex::new(0.0, omega * inductance);
+                    mna.add_resistor(term_1, term_2, Some(current_edge), impedance)
+                }
+                Component::Crystal { .. }
+                | Component::Urc { .. }
+                | Component::Supercapacitor { .. }
+                | Component::SemiconductorResistor { .. }
+                | Component::SemiconductorCapacitor { .. } => {
+                    unreachable!("Macromodels are expanded by elaborate()")
+                }
+                Component::Diode { .. }
+                | Component::Photodiode { .. }
+                | Component::TunnelDiode { .. }
+                | Component::SchottkyDiode { .. }
+                | Component::Table { .. }
+                | Component::Compact { .. } => {
+                    let (anode, cathode, junction) = instance.component.junction().unwrap();
+                    let voltage = self.dc_voltage(anode) - self.dc_voltage(cathode);
+                    let (conductance, _) = junction.linearise(voltage);
+                    mna.add_admittance(anode, cathode, conductance.into())
+                }
+                Component::Bjt { .. } | Component::Mosfet { .. } => {
+                    // Each current is a transconductance from each of the
+                    // control voltages, at their DC values
+                    let (nodes, transistor) = instance.component.transistor().unwrap();
+                    let at = transistor.controls().map(|(pos, neg)| {
+                        self.dc_voltage(nodes[pos]) - self.dc_voltage(nodes[neg])
+                    });
+                    for branch in linearise_transistor(nodes, transistor, at) {
+                        for (control, derivative) in branch.controls.iter().zip(branch.derivatives)
+                        {
+                            mna.add_voltage_controlled_current_source(
+                                branch.output,
+                                *control,
+                                derivative.into(),
+                            );
+                        }
+                    }
+                }
+                Component::NPort {
+                    ref ports,
+                    ref data,
+                } => mna.add_admittance_matrix(ports, &data.admittance(frequency)),
+                Component::Fuse {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    params,
+                } => mna.add_resistor(term_1, term_2, Some(current_edge), params.r_on.into()),
+                Component::Relay {
+                    coil_pos,
+                    coil_neg,
+                    contact_1,
+                    contact_2,
+                    coil_edge,
+                    contact_edge,
+                    params,
+                } => {
+                    let impedance = Complex::new(params.r_coil, omega * params.l_coil);
+                    mna.add_resistor(coil_pos, coil_neg, Some(coil_edge), impedance);
+                    mna.add_resistor(
+                        contact_1,
+                        contact_2,
+                        Some(contact_edge),
+                        params.r_off.into(),
+                    );
+                }
+                Component::Thyristor {
+                    anode,
+                    cathode,
+                    gate,
+                    current_edge,
+                    params,
+                    ..
+                } => {
+                    mna.add_resistor(anode, cathode, Some(current_edge), params.r_off.into());
+                    mna.add_resistor(gate, cathode, None, params.r_gate.into());
+                }
+                Component::Battery {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    ref model,
+                    ..
+                } => mna.add_resistor(term_pos, term_neg, Some(current_edge), model.r_int.into()),
+                Component::LogicGate { .. } | Component::AdcBridge { .. } => {}
+                Component::DacBridge {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    family,
+                    ..
+                } => mna.add_resistor(term_pos, term_neg, Some(current_edge), family.r_out.into()),
+                Component::IndependentVoltageSource {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    ac,
+                    ..
+                } => mna.add_independent_voltage_source(
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    excitation(ac),
+                ),
+                Component::CurrentProbe {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                } => mna.add_independent_voltage_source(
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    Complex::new(0.0, 0.0),
+                ),
+                Component::Igbt {
+                    collector,
+                    emitter,
+                    current_edge,
+                    params,
+                    ..
+                } => mna.add_resistor(collector, emitter, Some(current_edge), params.r_off.into()),
+                Component::MeasurementProbe {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    ..
+                } => {
+                    if let Some(current_edge) = current_edge {
+                        mna.add_independent_voltage_source(
+                            term_pos,
+                            term_neg,
+                            current_edge,
+                            Complex::new(0.0, 0.0),
+                        )
+                    }
+                }
+                Component::IndependentCurrentSource {
+                    term_pos,
+                    term_neg,
+                    ac,
+                    ..
+                } => mna.add_independent_current_source(term_pos, term_neg, excitation(ac)),
+                Component::VoltageControlledVoltageSource { .. }
+                | Component::VoltageControlledCurrentSource { .. }
+                | Component::CurrentControlledCurrentSource { .. }
+                | Component::CurrentControlledVoltageSource { .. } => {
+                    mna.add_controlled_source(&instance.component)
+                }
+            }
+        }
+        mna
+    }
+
+    /// Returns complex node voltages and edge currents at the
+    /// frequency (in Hz)
+    pub fn solve(&self, frequency: f64) -> (Vec<Complex<f64>>, Vec<Complex<f64>>) {
+        self.assemble(frequency, true).solve()
+    }
+
+    /// Solve at the frequency as for [LinearAcAnalysis::solve],
+    /// returning an error if the system cannot be solved
+    pub fn try_solve(&self, frequency: f64) -> Result<Phasors, EsimError> {
+        self.assemble(frequency, true).try_solve()
+    }
+
+    /// Solve at the frequency (in Hz) with each source that has an AC
+    /// specification driving the circuit on its own, giving the
+    /// contribution of each source to every node voltage and edge
+    /// current. The matrix is factorized once for all the sources, and
+    /// the contributions sum to the solution with all the sources.
+    pub fn superposition(&self, frequency: f64) -> Superposition {
+        let mut mna = self.assemble(frequency, false);
+        let mut sources = Vec::new();
+        for instance in self.circuit.instances() {
+            match instance.component {
+                Component::IndependentVoltageSource {
+                    current_edge, ac, ..
+                } if ac != AcSpec::default() => {
+                    mna.next_rhs();
+                    mna.add_series_voltage(current_edge, ac.phasor());
+                }
+                Component::IndependentCurrentSource {
+                    term_pos,
+                    term_neg,
+                    ac,
+                    ..
+                } if ac != AcSpec::default() => {
+                    mna.next_rhs();
+                    mna.add_independent_current_source(term_pos, term_neg, ac.phasor());
+                }
+                _ => continue,
+            }
+            sources.push(instance.name.clone());
+        }
+        // The first right-hand side is from the assembly, and is zero
+        let contributions = sources
+            .into_iter()
+            .zip(mna.solve_columns().into_iter().skip(1))
+            .map(|(source, (voltages, currents))| SourceContribution {
+                source,
+                voltages,
+                currents,
+            })
+            .collect();
+        Superposition {
+            frequency,
+            contributions,
+        }
+    }
+}
+
+/// Transfer to the output of an adjoint solution (see
+/// [Mna::solve_adjoint]) from a unit current injected into a node
+pub(crate) fn node_transfer(
+    (nodes, _): &(Vec<Complex<f64>>, Vec<Complex<f64>>),
+    node: usize,
+) -> Complex<f64> {
+    match node {
+        0 => Complex::new(0.0, 0.0),
+        n => nodes[n - 1],
+    }
+}
+
+/// Transfer to the output of an adjoint solution from the value of an
+/// independent source, or None if the component is not one
+pub(crate) fn source_transfer(
+    adjoint: &(Vec<Complex<f64>>, Vec<Complex<f64>>),
+    source: &Component,
+) -> Option<Complex<f64>> {
+    match *source {
+        Component::IndependentVoltageSource { current_edge, .. } => Some(adjoint.1[current_edge]),
+        // A current source drives current out of its positive terminal
+        // and into its negative terminal
+        Component::IndependentCurrentSource {
+            term_pos, term_neg, ..
+        } => Some(node_transfer(adjoint, term_neg) - node_transfer(adjoint, term_pos)),
+        _ => None,
+    }
+}
+
+/// Node voltages and edge currents due to one source
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceContribution {
+    /// Name of the source
+    pub source: String,
+    pub voltages: Vec<Complex<f64>>,
+    pub currents: Vec<Complex<f64>>,
+}
+
+impl SourceContribution {
+    /// Voltage of a node (zero for ground)
+    pub fn voltage(&self, node: usize) -> Complex<f64> {
+        if node == 0 {
+            Complex::new(0.0, 0.0)
+        } else {
+            self.voltages[node - 1]
+        }
+    }
+}
+
+/// Contribution of each AC source to the solution at one frequency
+#[derive(Debug, Clone, PartialEq)]
+pub struct Superposition {
+    pub frequency: f64,
+    pub contributions: Vec<SourceContribution>,
+}
+
+impl Superposition {
+    /// Voltage of a node with all the sources
+    pub fn voltage(&self, node: usize) -> Complex<f64> {
+        self.contributions.iter().map(|c| c.voltage(node)).sum()
+    }
+
+    /// Current in an edge with all the sources
+    pub fn current(&self, edge: usize) -> Complex<f64> {
+        self.contributions.iter().map(|c| c.currents[edge]).sum()
+    }
+
+    /// The contribution of each source to a node voltage, largest
+    /// first
+    pub fn breakdown(&self, node: usize) -> Vec<(&str, Complex<f64>)> {
+        let mut breakdown: Vec<(&str, Complex<f64>)> = self
+            .contributions
+            .iter()
+            .map(|c| (c.source.as_str(), c.voltage(node)))
+            .collect();
+        breakdown.sort_by(|a, b| b.1.norm().total_cmp(&a.1.norm()));
+        breakdown
+    }
+}
+
+impl fmt::Display for Superposition {
+    /// A table of the contribution of each source to each node
+    /// voltage, largest first, with its share of the sum of the
+    /// contribution magnitudes
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<8} {:<12} {:>12} {:>10} {:>8}",
+            "node", "source", "magnitude", "phase", "share"
+        )?;
+        let num_nodes = self.contributions.first().map_or(0, |c| c.voltages.len());
+        for node in 1..=num_nodes {
+            let breakdown = self.breakdown(node);
+            let sum: f64 = breakdown.iter().map(|(_, v)| v.norm()).sum();
+            for (source, v) in breakdown {
+                let share = if sum > 0.0 { v.norm() / sum } else { 0.0 };
+                writeln!(
+                    f,
+                    "{:<8} {:<12} {:>12.4e} {:>10.2} {:>7.1}%",
+                    format!("v({node})"),
+                    source,
+                    v.norm(),
+                    v.arg().to_degrees(),
+                    100.0 * share
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How the frequencies of an AC sweep are spaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variation {
+    /// A number of points per decade
+    #[default]
+    Decade,
+    /// A number of points per octave
+    Octave,
+    /// A number of points in all, equally spaced
+    Linear,
+}
+
+/// The frequencies of an AC sweep, as in SPICE `.AC DEC 10 1 1meg`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AcSweep {
+    pub variation: Variation,
+    /// Points per decade or octave, or in all
+    pub points: usize,
+    pub start: f64,
+    pub stop: f64,
+}
+
+impl AcSweep {
+    /// Panics if there are no points, or the frequencies are not
+    /// positive and increasing
+    pub fn new(variation: Variation, points: usize, start: f64, stop: f64) -> Self {
+        assert!(points > 0, "An AC sweep needs at least one point");
+        assert!(
+            start > 0.0 && stop >= start,
+            "AC sweep frequencies must be positive and increasing"
+        );
+        Self {
+            variation,
+            points,
+            start,
+            stop,
+        }
+    }
+
+    /// The swept frequencies (in Hz). The stop frequency is included
+    /// if it is within a small fraction of a step of the last step.
+    pub fn frequencies(&self) -> Vec<f64> {
+        let ratio = match self.variation {
+            Variation::Decade => 10.0,
+            Variation::Octave => 2.0,
+            Variation::Linear => {
+                if self.points == 1 {
+                    return vec![self.start];
+                }
+                let step = (self.stop - self.start) / (self.points - 1) as f64;
+                return (0..self.points)
+                    .map(|n| self.start + n as f64 * step)
+                    .collect();
+            }
+        };
+        let steps = (self.stop / self.start).log(ratio) * self.points as f64;
+        let num_steps = (steps + 1e-9).floor() as usize;
+        (0..=num_steps)
+            .map(|n| self.start * ratio.powf(n as f64 / self.points as f64))
+            .collect()
+    }
+}
+
+/// The small-signal solution at each frequency of an AC sweep
+#[derive(Debug, Clone)]
+pub struct AcSweepResult {
+    pub frequencies: Vec<f64>,
+    /// Node voltages at each frequency
+    pub voltages: Vec<Vec<Complex<f64>>>,
+    /// Edge currents at each frequency
+    pub currents: Vec<Vec<Complex<f64>>>,
+    /// Warnings of solving the operating point
+    pub warnings: Vec<String>,
+    nodes: NodeNames,
+}
+
+impl AcSweepResult {
+    /// Voltage of a node at each frequency (zero for ground)
+    pub fn voltage(&self, node: usize) -> Vec<Complex<f64>> {
+        self.voltages
+            .iter()
+            .map(|v| {
+                if node == 0 {
+                    Complex::new(0.0, 0.0)
+                } else {
+                    v[node - 1]
+                }
+            })
+            .collect()
+    }
+
+    /// Voltage of a node by name (see [crate::node]) at each
+    /// frequency, if there is a node with the name in the circuit
+    pub fn node_voltage(&self, name: &str) -> Option<Vec<Complex<f64>>> {
+        self.nodes
+            .get(name)
+            .filter(|node| *node <= self.voltages.first().map_or(0, Vec::len))
+            .map(|node| self.voltage(node))
+    }
+
+    /// Current in an edge at each frequency
+    pub fn current(&self, edge: usize) -> Vec<Complex<f64>> {
+        self.currents.iter().map(|i| i[edge]).collect()
+    }
+}
+
+/// Solve a circuit at each frequency of an AC sweep, linearised about
+/// its operating point (which is solved once)
+pub fn ac_sweep(circuit: &Circuit, sweep: &AcSweep) -> AcSweepResult {
+    ac_sweep_options(circuit, sweep, &DcOptions::default())
+}
+
+/// Sweep the frequency as for [ac_sweep], solving the operating point
+/// with options
+pub fn ac_sweep_options(circuit: &Circuit, sweep: &AcSweep, options: &DcOptions) -> AcSweepResult {
+    sweep_frequencies(&LinearAcAnalysis::with_options(circuit, options), sweep)
+        .unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Sweep the frequency as for [ac_sweep_options], returning an error
+/// if the circuit fails its check, or the operating point or the
+/// system at a frequency cannot be solved (see
+/// [LinearAcAnalysis::try_new])
+pub fn try_ac_sweep(
+    circuit: &Circuit,
+    sweep: &AcSweep,
+    options: &DcOptions,
+) -> Result<AcSweepResult, EsimError> {
+    sweep_frequencies(&LinearAcAnalysis::try_new(circuit, options)?, sweep)
+        .map_err(|error| explain(circuit, error))
+}
+
+fn sweep_frequencies(
+    analysis: &LinearAcAnalysis,
+    sweep: &AcSweep,
+) -> Result<AcSweepResult, EsimError> {
+    let frequencies = sweep.frequencies();
+    // The matrix has the same structure at every frequency, so it is
+    // refactorized with the ordering of the first
+    let mut solver = CachedSolver::default();
+    let (voltages, currents) = frequencies
+        .iter()
+        .map(|f| analysis.assemble(*f, true).try_solve_with(&mut solver))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .unzip();
+    Ok(AcSweepResult {
+        frequencies,
+        voltages,
+        currents,
+        warnings: analysis.warnings.clone(),
+        nodes: analysis.circuit.node_names().clone(),
+    })
+}
+
+/// Whether an elaborated circuit has components that are linearised
+/// about the operating point
+fn nonlinear(circuit: &Circuit) -> bool {
+    circuit.instances().iter().any(|instance| {
+        matches!(instance.component, Component::SaturableInductor { .. })
+            || instance.component.junction().is_some()
+            || instance.component.transistor().is_some()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CircuitBuilder;
+    use crate::component::{Model, MosfetModel};
+    use crate::noise::try_noise;
+    use crate::transfer_function::try_transfer_function;
+
+    fn divider() -> Circuit {
+        CircuitBuilder::new()
+            .vsource("V1", "in", "0", 1.0)
+            .resistor("R1", "in", "out", 1e3)
+            .resistor("R2", "out", "0", 1e3)
+            .capacitor("C1", "out", "0", 1e-6)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn try_ac_sweep_matches_the_sweep() {
+        let circuit = divider();
+        let sweep = AcSweep::new(Variation::Decade, 5, 10.0, 1e4);
+        let options = DcOptions::default();
+        let result = try_ac_sweep(&circuit, &sweep, &options).unwrap();
+        let expected = ac_sweep_options(&circuit, &sweep, &options);
+        assert_eq!(result.frequencies, expected.frequencies);
+        assert_eq!(result.voltages, expected.voltages);
+    }
+
+    #[test]
+    fn sweep_voltages_are_looked_up_by_name() {
+        let circuit = divider();
+        let sweep = AcSweep::new(Variation::Decade, 5, 10.0, 1e4);
+        let result = ac_sweep(&circuit, &sweep);
+        let out = circuit.node_names().get("out").unwrap();
+        assert_eq!(result.node_voltage("OUT"), Some(result.voltage(out)));
+        assert_eq!(result.node_voltage("gnd"), Some(result.voltage(0)));
+        assert_eq!(result.node_voltage("missing"), None);
+    }
+
+    #[test]
+    fn common_source_gain_is_the_transconductance_times_the_load() {
+        let model = MosfetModel {
+            vto: 1.0,
+            kp: 1e-3,
+            ..Default::default()
+        };
+        let circuit = CircuitBuilder::new()
+            .model("n", Model::Mosfet(model))
+            .vsource("V1", "vdd", "0", 10.0)
+            .vsource("VG", "g", "0", 3.0)
+            .with_ac(1.0, 0.0)
+            .resistor("RD", "vdd", "d", 1e3)
+            .mosfet("M1", "d", "g", "0", "n")
+            .build()
+            .unwrap();
+        let sweep = AcSweep::new(Variation::Decade, 1, 1e3, 1e3);
+        let result = ac_sweep(&circuit, &sweep);
+        // gm = 1m (3 - 1) = 2 mS into 1k
+        let gain = result.node_voltage("d").unwrap()[0];
+        assert!((gain - Complex::new(-2.0, 0.0)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn try_ac_sweep_rejects_an_invalid_circuit() {
+        let mut circuit = divider();
+        circuit.add_component("V2", circuit.instances()[0].component.clone());
+        let sweep = AcSweep::new(Variation::Decade, 5, 10.0, 1e4);
+        let result = try_ac_sweep(&circuit, &sweep, &DcOptions::default());
+        assert!(matches!(result, Err(EsimError::Assembly { .. })));
+    }
+
+    #[test]
+    fn inputs_and_outputs_are_checked() {
+        let circuit = divider();
+        let out = circuit.node_names().get("out").unwrap();
+        assert!(try_transfer_function(&circuit, (out, 0), "V1").is_ok());
+        let resistor = try_transfer_function(&circuit, (out, 0), "R1");
+        assert!(matches!(resistor, Err(EsimError::Invalid { .. })));
+        let missing = try_noise(&circuit, (out, 0), "V9", &[1e3]);
+        assert!(matches!(missing, Err(EsimError::Invalid { .. })));
+        let node = try_noise(&circuit, (9, 0), "V1", &[1e3]);
+        assert!(matches!(node, Err(EsimError::Invalid { .. })));
+    }
+}
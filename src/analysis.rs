@@ -0,0 +1,30 @@
+//! Analyses
+//!
+//! Each analysis is a module of its own at the top of the crate; they
+//! are gathered here under one path (such as `analysis::transient`)
+//! for discovery. The transient analysis steps the circuit from its
+//! operating point by an integration method (backward Euler,
+//! trapezoidal or Gear), at a fixed time step or with the step
+//! controlled by the local truncation error. The small-signal analyses
+//! (such as noise and pole-zero) linearise the circuit about its
+//! operating point, as the AC analysis does, and the periodic ones
+//! (PSS, periodic AC and harmonic balance) solve for a steady state.
+
+pub use crate::ac;
+pub use crate::dc;
+pub use crate::fourier;
+pub use crate::harmonic_balance;
+pub use crate::loop_gain;
+pub use crate::monte_carlo;
+pub use crate::noise;
+pub use crate::pac;
+pub use crate::pole_zero;
+pub use crate::power;
+pub use crate::pss;
+pub use crate::sensitivity;
+pub use crate::step;
+pub use crate::sweep;
+pub use crate::tdr;
+pub use crate::transfer_function;
+pub use crate::transient;
+pub use crate::two_port;
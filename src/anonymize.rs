@@ -0,0 +1,177 @@
+//! Anonymizing netlists for sharing
+//!
+//! A netlist that shows a solver problem can be shared without
+//! revealing the design it came from: components are renamed by type
+//! and position (`R1`, `R2`, ...), nodes are renumbered in the order
+//! they first appear (ground stays 0), and comments, blank lines and
+//! commands are dropped (continuation lines are joined), and the
+//! subcircuits are expanded. Model cards are kept, renamed in order
+//! (`MODEL1`, `MODEL2`, ...), with their parameters. The circuit is otherwise unchanged, so a
+//! numerical failure is reproduced exactly.
+//!
+//! Values can also be disguised, at the risk of changing the failure:
+//! perturbed by a random relative amount, or rounded to a number of
+//! logarithmic buckets per decade. Perturbations are drawn from their
+//! own random stream (see [crate::rng]), so a seed reproduces them.
+
+use std::collections::HashMap;
+
+use rand::Rng;
+
+use crate::netlist::{
+    find_model, flatten, parse_netlist, touchstone_file, Dialect, NetlistError, Statement,
+};
+use crate::rng::{RngStreams, ANONYMIZE};
+use crate::value::{normalize, parse_spice_value};
+
+/// How component values are disguised
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ValueTreatment {
+    /// Values are kept exactly as written
+    #[default]
+    Keep,
+    /// Each value is scaled by a random factor in 1 ± relative
+    Perturb { relative: f64 },
+    /// Each value is rounded to the nearest of the given number of
+    /// logarithmically spaced values per decade
+    Bucket { per_decade: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AnonymizeOptions {
+    pub values: ValueTreatment,
+    /// Master seed of the perturbations
+    pub seed: u64,
+}
+
+/// An anonymized netlist, with the renaming, so that the owner of the
+/// original can relate results back to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnonymizedNetlist {
+    pub text: String,
+    /// Original and new name of each component
+    pub instances: Vec<(String, String)>,
+    /// Original and new number of each node
+    pub nodes: Vec<(usize, usize)>,
+}
+
+/// The value of a token, if it is one (keywords such as `DC` are not)
+fn token_value(token: &str) -> Option<f64> {
+    parse_spice_value(&normalize(token).text).ok()
+}
+
+fn bucket(value: f64, per_decade: usize) -> f64 {
+    if value == 0.0 || per_decade == 0 {
+        return value;
+    }
+    let n = per_decade as f64;
+    value.signum() * 10f64.powf((value.abs().log10() * n).round() / n)
+}
+
+/// Anonymize a netlist. The netlist is checked by parsing it first.
+pub fn anonymize(
+    text: &str,
+    options: &AnonymizeOptions,
+) -> Result<AnonymizedNetlist, NetlistError> {
+    parse_netlist(text)?;
+    let mut rng = RngStreams::new(options.seed).stream(ANONYMIZE);
+    // The commands other than the model cards are dropped along with
+    // the comments
+    let (cards, statements): (Vec<Statement>, Vec<Statement>) = flatten(text, None, Dialect::Esim)?
+        .0
+        .into_iter()
+        .filter(|statement| {
+            statement.kind() != '.' || statement.name.eq_ignore_ascii_case(".MODEL")
+        })
+        .partition(|statement| statement.kind() == '.');
+    let models: HashMap<String, String> = cards
+        .iter()
+        .enumerate()
+        .map(|(index, card)| {
+            (
+                card.tokens[1].to_ascii_lowercase(),
+                format!("MODEL{}", index + 1),
+            )
+        })
+        .collect();
+    let mut out_lines: Vec<String> = cards
+        .iter()
+        .map(|card| {
+            let name = &models[&card.tokens[1].to_ascii_lowercase()];
+            format!(
+                ".MODEL {name} {}",
+                card.tokens[2..].join(" ").to_ascii_uppercase()
+            )
+        })
+        .collect();
+    // The components are renamed first, since a current-controlled
+    // source can name a component on a later line
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    let instances: Vec<(String, String)> = statements
+        .iter()
+        .map(|statement| {
+            let kind = statement.kind();
+            let count = counts.entry(kind).or_insert(0);
+            *count += 1;
+            (statement.name.clone(), format!("{kind}{count}"))
+        })
+        .collect();
+    let mut nodes: Vec<(usize, usize)> = vec![(0, 0)];
+    for (statement, (_, name)) in statements.iter().zip(&instances) {
+        let line = statement.tokens.join(" ");
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let kind = name.chars().next().unwrap();
+        let num_nodes = match kind {
+            'E' | 'G' => 4,
+            'S' => touchstone_file(&statement.tokens) - 1,
+            _ => 2,
+        };
+        let mut out = vec![name.clone()];
+        for (index, token) in tokens.iter().enumerate().skip(1) {
+            if index <= num_nodes {
+                // The netlist has been parsed, so the nodes are numbers
+                let node: usize = token.parse().unwrap();
+                let renamed = match nodes.iter().find(|(original, _)| *original == node) {
+                    Some((_, renamed)) => *renamed,
+                    None => {
+                        nodes.push((node, nodes.len()));
+                        nodes.len() - 1
+                    }
+                };
+                out.push(renamed.to_string());
+                continue;
+            }
+            if kind == 'D' && index == 3 {
+                let model = find_model(&statement.name, token, |name| models.contains_key(name));
+                // The netlist has been parsed, so the model is defined
+                out.push(models[&model.unwrap()].clone());
+                continue;
+            }
+            if matches!(kind, 'F' | 'H') && index == 3 {
+                let (_, renamed) = instances
+                    .iter()
+                    .find(|(original, _)| original.eq_ignore_ascii_case(token))
+                    .unwrap();
+                out.push(renamed.clone());
+                continue;
+            }
+            let token = match (token_value(token), options.values) {
+                (Some(value), ValueTreatment::Perturb { relative }) => {
+                    format!("{:e}", value * (1.0 + relative * rng.gen_range(-1.0..=1.0)))
+                }
+                (Some(value), ValueTreatment::Bucket { per_decade }) => {
+                    format!("{:e}", bucket(value, per_decade))
+                }
+                (Some(_), ValueTreatment::Keep) => token.to_string(),
+                (None, _) => token.to_ascii_uppercase(),
+            };
+            out.push(token);
+        }
+        out_lines.push(out.join(" "));
+    }
+    Ok(AnonymizedNetlist {
+        text: out_lines.join("\n") + "\n",
+        instances,
+        nodes,
+    })
+}
@@ -0,0 +1,435 @@
+//! Building circuits by name
+//!
+//! A [CircuitBuilder] adds components to a circuit one call at a time,
+//! with the nodes given by name (see [crate::node]) and the values as
+//! numbers or in engineering notation (see [IntoValue]):
+//!
+//! ```text
+//! let circuit = CircuitBuilder::new()
+//!     .vsource("V1", "in", "0", 5.0)
+//!     .resistor("R1", "in", "out", "1k")
+//!     .capacitor("C1", "out", "gnd", 100e-9)
+//!     .build()?;
+//! ```
+//!
+//! The builder numbers the nodes and the current edges, and resolves
+//! the components controlling the current-controlled sources and the
+//! models used by the diodes and transistors when the circuit is
+//! built, so they can be
+//! added in any order. The first error (such as an invalid value or a
+//! duplicate name) is kept, and returned when the circuit is built.
+
+use std::fmt;
+
+use crate::circuit::Circuit;
+use crate::component::{AcSpec, BjtModel, Component, DiodeModel, Model, MosfetModel};
+use crate::library;
+use crate::value::IntoValue;
+use crate::waveform::Waveform;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildError {
+    /// Name of the instance the error is in
+    pub instance: String,
+    pub message: String,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.instance, self.message)
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Builds a circuit from components with named nodes
+#[derive(Debug, Clone, Default)]
+pub struct CircuitBuilder {
+    circuit: Circuit,
+    /// Current-controlled sources, with the name of the component
+    /// that controls each
+    controlled: Vec<(String, String)>,
+    /// Diodes and transistors that use a model, with the name of the
+    /// model
+    uses: Vec<(String, String)>,
+    error: Option<BuildError>,
+}
+
+impl CircuitBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The node with a name, as numbered in the circuit being built.
+    /// Panics if the name is a number given to another name (see
+    /// [Circuit::try_node]).
+    pub fn node(&mut self, name: &str) -> usize {
+        self.circuit.node(name)
+    }
+
+    /// A node of an instance, or ground (keeping the error) if it is
+    /// a number given to another name
+    fn terminal(&mut self, instance: &str, node: &str) -> usize {
+        self.circuit.try_node(node).unwrap_or_else(|error| {
+            self.fail(instance, error.to_string());
+            0
+        })
+    }
+
+    /// Keep an error, unless there is one already
+    fn fail(&mut self, instance: &str, message: String) {
+        self.error.get_or_insert(BuildError {
+            instance: instance.to_string(),
+            message,
+        });
+    }
+
+    /// A value of an instance, or zero (keeping the error) if it is
+    /// invalid
+    fn value(&mut self, instance: &str, value: impl IntoValue) -> f64 {
+        value.into_value().unwrap_or_else(|error| {
+            self.fail(instance, error.to_string());
+            0.0
+        })
+    }
+
+    /// Add a component, giving it the next free current edges
+    pub fn component(mut self, name: &str, mut component: Component) -> Self {
+        if self.circuit.instances().iter().any(|i| i.name == name) {
+            self.fail(name, String::from("duplicate component name"));
+            return self;
+        }
+        let mut next_edge = self.circuit.num_current_edges();
+        let mut edge = || {
+            next_edge += 1;
+            next_edge - 1
+        };
+        if let Component::Relay { coil_edge, .. } = &mut component {
+            *coil_edge = edge();
+        }
+        if let Some(current_edge) = component.current_edge_mut() {
+            *current_edge = edge();
+        }
+        self.circuit.add_component(name, component);
+        self
+    }
+
+    pub fn resistor(
+        mut self,
+        name: &str,
+        term_1: &str,
+        term_2: &str,
+        resistance: impl IntoValue,
+    ) -> Self {
+        let component = Component::Resistor {
+            term_1: self.terminal(name, term_1),
+            term_2: self.terminal(name, term_2),
+            current_edge: None,
+            resistance: self.value(name, resistance),
+        };
+        self.component(name, component)
+    }
+
+    pub fn capacitor(
+        mut self,
+        name: &str,
+        term_1: &str,
+        term_2: &str,
+        capacitance: impl IntoValue,
+    ) -> Self {
+        let component = Component::Capacitor {
+            term_1: self.terminal(name, term_1),
+            term_2: self.terminal(name, term_2),
+            capacitance: self.value(name, capacitance),
+        };
+        self.component(name, component)
+    }
+
+    pub fn inductor(
+        mut self,
+        name: &str,
+        term_1: &str,
+        term_2: &str,
+        inductance: impl IntoValue,
+    ) -> Self {
+        let component = Component::Inductor {
+            term_1: self.terminal(name, term_1),
+            term_2: self.terminal(name, term_2),
+            current_edge: 0,
+            inductance: self.value(name, inductance),
+        };
+        self.component(name, component)
+    }
+
+    /// Add a diode with the default model
+    pub fn diode(mut self, name: &str, anode: &str, cathode: &str) -> Self {
+        let component = Component::Diode {
+            anode: self.terminal(name, anode),
+            cathode: self.terminal(name, cathode),
+            model: DiodeModel::default(),
+        };
+        self.component(name, component)
+    }
+
+    /// Add a diode that uses a model of the circuit by name (see
+    /// [CircuitBuilder::model]), or a part of the [library] if no
+    /// model has the name
+    pub fn diode_model(self, name: &str, anode: &str, cathode: &str, model: &str) -> Self {
+        let mut builder = self.diode(name, anode, cathode);
+        builder.uses.push((name.to_string(), model.to_string()));
+        builder
+    }
+
+    /// Add a bipolar transistor that uses a model of the circuit by
+    /// name, or a part of the [library] if no model has the name
+    pub fn bjt(
+        mut self,
+        name: &str,
+        collector: &str,
+        base: &str,
+        emitter: &str,
+        model: &str,
+    ) -> Self {
+        let component = Component::Bjt {
+            collector: self.terminal(name, collector),
+            base: self.terminal(name, base),
+            emitter: self.terminal(name, emitter),
+            model: BjtModel::default(),
+        };
+        self.uses.push((name.to_string(), model.to_string()));
+        self.component(name, component)
+    }
+
+    /// Add a MOSFET (of the default width and length) that uses a
+    /// model as for [CircuitBuilder::bjt]
+    pub fn mosfet(
+        mut self,
+        name: &str,
+        drain: &str,
+        gate: &str,
+        source: &str,
+        model: &str,
+    ) -> Self {
+        let component = Component::Mosfet {
+            drain: self.terminal(name, drain),
+            gate: self.terminal(name, gate),
+            source: self.terminal(name, source),
+            model: MosfetModel::default(),
+        };
+        self.uses.push((name.to_string(), model.to_string()));
+        self.component(name, component)
+    }
+
+    /// Define a model, which instances can use by name
+    pub fn model(mut self, name: &str, model: Model) -> Self {
+        self.circuit.add_model(name, model);
+        self
+    }
+
+    /// Add an independent voltage source with a DC value
+    pub fn vsource(
+        mut self,
+        name: &str,
+        term_pos: &str,
+        term_neg: &str,
+        voltage: impl IntoValue,
+    ) -> Self {
+        let component = Component::IndependentVoltageSource {
+            term_pos: self.terminal(name, term_pos),
+            term_neg: self.terminal(name, term_neg),
+            current_edge: 0,
+            voltage: self.value(name, voltage),
+            ac: AcSpec::default(),
+            waveform: None,
+        };
+        self.component(name, component)
+    }
+
+    /// Add an independent current source with a DC value
+    pub fn isource(
+        mut self,
+        name: &str,
+        term_pos: &str,
+        term_neg: &str,
+        current: impl IntoValue,
+    ) -> Self {
+        let component = Component::IndependentCurrentSource {
+            term_pos: self.terminal(name, term_pos),
+            term_neg: self.terminal(name, term_neg),
+            current: self.value(name, current),
+            ac: AcSpec::default(),
+            waveform: None,
+        };
+        self.component(name, component)
+    }
+
+    /// The AC specification and waveform of the independent source
+    /// added last, keeping an error if it is not one
+    fn last_source(&mut self) -> Option<(&mut AcSpec, &mut Option<Waveform>)> {
+        let last = self.circuit.instances().last();
+        if !matches!(
+            last.map(|i| &i.component),
+            Some(Component::IndependentVoltageSource { .. })
+                | Some(Component::IndependentCurrentSource { .. })
+        ) {
+            let name = last.map_or(String::new(), |i| i.name.clone());
+            self.fail(&name, String::from("not an independent source"));
+            return None;
+        }
+        match &mut self.circuit.instances_mut().last_mut()?.component {
+            Component::IndependentVoltageSource { ac, waveform, .. }
+            | Component::IndependentCurrentSource { ac, waveform, .. } => Some((ac, waveform)),
+            _ => None,
+        }
+    }
+
+    /// Give the independent source added last an AC excitation, with
+    /// the phase in degrees
+    pub fn with_ac(mut self, magnitude: f64, phase: f64) -> Self {
+        if let Some((ac, _)) = self.last_source() {
+            *ac = AcSpec::new(magnitude, phase);
+        }
+        self
+    }
+
+    /// Give the independent source added last a transient waveform
+    pub fn with_waveform(mut self, waveform: Waveform) -> Self {
+        if let Some((_, slot)) = self.last_source() {
+            *slot = Some(waveform);
+        }
+        self
+    }
+
+    /// Add a voltage-controlled voltage source (E)
+    pub fn vcvs(
+        mut self,
+        name: &str,
+        term_pos: &str,
+        term_neg: &str,
+        ctrl_pos: &str,
+        ctrl_neg: &str,
+        gain: impl IntoValue,
+    ) -> Self {
+        let component = Component::VoltageControlledVoltageSource {
+            term_pos: self.terminal(name, term_pos),
+            term_neg: self.terminal(name, term_neg),
+            ctrl_pos: self.terminal(name, ctrl_pos),
+            ctrl_neg: self.terminal(name, ctrl_neg),
+            current_edge: 0,
+            gain: self.value(name, gain),
+        };
+        self.component(name, component)
+    }
+
+    /// Add a voltage-controlled current source (G)
+    pub fn vccs(
+        mut self,
+        name: &str,
+        term_pos: &str,
+        term_neg: &str,
+        ctrl_pos: &str,
+        ctrl_neg: &str,
+        transconductance: impl IntoValue,
+    ) -> Self {
+        let component = Component::VoltageControlledCurrentSource {
+            term_pos: self.terminal(name, term_pos),
+            term_neg: self.terminal(name, term_neg),
+            ctrl_pos: self.terminal(name, ctrl_pos),
+            ctrl_neg: self.terminal(name, ctrl_neg),
+            transconductance: self.value(name, transconductance),
+        };
+        self.component(name, component)
+    }
+
+    /// Add a current-controlled current source (F), controlled by the
+    /// current of a component with a current edge, by name
+    pub fn cccs(
+        mut self,
+        name: &str,
+        term_pos: &str,
+        term_neg: &str,
+        control: &str,
+        gain: impl IntoValue,
+    ) -> Self {
+        let component = Component::CurrentControlledCurrentSource {
+            term_pos: self.terminal(name, term_pos),
+            term_neg: self.terminal(name, term_neg),
+            ctrl_edge: 0,
+            gain: self.value(name, gain),
+        };
+        self.controlled
+            .push((name.to_string(), control.to_string()));
+        self.component(name, component)
+    }
+
+    /// Add a current-controlled voltage source (H), controlled by the
+    /// current of a component with a current edge, by name
+    pub fn ccvs(
+        mut self,
+        name: &str,
+        term_pos: &str,
+        term_neg: &str,
+        control: &str,
+        transresistance: impl IntoValue,
+    ) -> Self {
+        let component = Component::CurrentControlledVoltageSource {
+            term_pos: self.terminal(name, term_pos),
+            term_neg: self.terminal(name, term_neg),
+            ctrl_edge: 0,
+            current_edge: 0,
+            transresistance: self.value(name, transresistance),
+        };
+        self.controlled
+            .push((name.to_string(), control.to_string()));
+        self.component(name, component)
+    }
+
+    /// The circuit, or the first error in building it
+    pub fn build(mut self) -> Result<Circuit, BuildError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        let error = |instance: &str, message: String| BuildError {
+            instance: instance.to_string(),
+            message,
+        };
+        for (name, control) in &self.controlled {
+            let ctrl_edge = self
+                .circuit
+                .instances()
+                .iter()
+                .find(|i| i.name == *control)
+                .ok_or_else(|| error(name, format!("no component named '{control}'")))?
+                .component
+                .current_edge()
+                .ok_or_else(|| error(name, format!("'{control}' has no current edge")))?;
+            let instance = self
+                .circuit
+                .instances_mut()
+                .iter_mut()
+                .find(|i| i.name == *name)
+                .unwrap();
+            *instance.component.ctrl_edge_mut().unwrap() = ctrl_edge;
+        }
+        for (name, model) in &self.uses {
+            if self.circuit.model(model).is_none() {
+                if let Some(part) = library::model(model) {
+                    self.circuit.add_model(model, part);
+                }
+            }
+            if self.circuit.model(model).is_none() {
+                return Err(error(name, format!("no model named '{model}'")));
+            }
+            if self.circuit.try_use_model(name, model).is_err() {
+                let instance = self.circuit.instances().iter().find(|i| i.name == *name);
+                let kind = match instance.map(|i| &i.component) {
+                    Some(Component::Bjt { .. }) => "bipolar transistor",
+                    Some(Component::Mosfet { .. }) => "MOSFET",
+                    _ => "diode",
+                };
+                return Err(error(name, format!("'{model}' is not a {kind} model")));
+            }
+        }
+        Ok(self.circuit)
+    }
+}
@@ -0,0 +1,555 @@
+//! Characterization benches for model cards
+//!
+//! Each bench builds the standard test circuit for one device from
+//! its model card, runs the analyses that measure the device's
+//! characteristic curve, and returns the curve. This checks a model
+//! card as the simulator sees it (after elaboration), rather than by
+//! evaluating the model equations directly.
+//!
+//! The benches are for the devices the simulator has models for: the
+//! diodes, resistive and inductive two-terminal components, the
+//! crystal, and the transistors. A MOSFET has output and transfer
+//! curves, and both kinds of transistor have a transition frequency
+//! (fT): the frequency at which the small-signal current gain from the
+//! input (base or gate) to the shorted output (collector or drain)
+//! falls to one.
+
+use std::f64::consts::PI;
+
+use crate::ac::LinearAcAnalysis;
+use crate::circuit::Circuit;
+use crate::component::{
+    AcSpec, BjtModel, Component, CrystalParams, DiodeModel, MosfetModel, SaturationCurve,
+    SchottkyModel, SemiconductorResistorModel, ThermistorModel, TunnelDiodeModel,
+};
+use crate::dc::operating_point;
+
+/// Test current used by the resistance benches
+const TEST_CURRENT: f64 = 1e-3;
+
+/// Frequency at which the incremental inductance is measured
+const INDUCTANCE_TEST_FREQUENCY: f64 = 1e3;
+
+/// Resistance that biases the gate of a MOSFET in the fT bench, large
+/// enough that it takes almost none of the AC gate current
+const GATE_BIAS_RESISTANCE: f64 = 1e12;
+
+/// Highest frequency the fT benches search up to
+const MAX_TRANSITION_FREQUENCY: f64 = 1e15;
+
+/// A characteristic curve: a quantity measured at each value of a
+/// swept variable
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+    pub x_name: String,
+    pub x: Vec<f64>,
+    pub y_name: String,
+    pub y: Vec<f64>,
+}
+
+impl Curve {
+    fn new(x_name: &str, x: Vec<f64>, y_name: &str, y: Vec<f64>) -> Self {
+        Self {
+            x_name: x_name.to_string(),
+            x,
+            y_name: y_name.to_string(),
+            y,
+        }
+    }
+}
+
+/// Resistance of a two-terminal resistive component, measured by
+/// forcing the test current through it
+fn measure_resistance(component: Component) -> f64 {
+    let mut circuit = Circuit::new();
+    circuit.add_component("dut", component);
+    circuit.add_component(
+        "itest",
+        Component::IndependentCurrentSource {
+            term_pos: 0,
+            term_neg: 1,
+            current: TEST_CURRENT,
+            ac: AcSpec::default(),
+            waveform: None,
+        },
+    );
+    operating_point(&circuit).voltage(1) / TEST_CURRENT
+}
+
+/// Current into the first terminal (node 1) of a two-terminal
+/// component at each voltage across it
+fn sweep_current(component: Component, voltages: &[f64]) -> Vec<f64> {
+    voltages
+        .iter()
+        .map(|voltage| {
+            let mut circuit = Circuit::new();
+            circuit.add_component("dut", component.clone());
+            circuit.add_component(
+                "vtest",
+                Component::IndependentVoltageSource {
+                    term_pos: 1,
+                    term_neg: 0,
+                    current_edge: 0,
+                    voltage: *voltage,
+                    ac: AcSpec::default(),
+                    waveform: None,
+                },
+            );
+            // The source current flows from its positive terminal into
+            // the source, so it is minus the current into the component
+            -operating_point(&circuit).currents[0]
+        })
+        .collect()
+}
+
+/// Current against forward voltage of a diode
+pub fn diode_iv(model: DiodeModel, voltages: &[f64]) -> Curve {
+    let diode = Component::Diode {
+        anode: 1,
+        cathode: 0,
+        model,
+    };
+    Curve::new(
+        "voltage",
+        voltages.to_vec(),
+        "current",
+        sweep_current(diode, voltages),
+    )
+}
+
+/// Current against forward voltage of a tunnel diode, including the
+/// negative resistance region between the peak and the valley
+pub fn tunnel_diode_iv(model: TunnelDiodeModel, voltages: &[f64]) -> Curve {
+    let diode = Component::TunnelDiode {
+        anode: 1,
+        cathode: 0,
+        model,
+    };
+    Curve::new(
+        "voltage",
+        voltages.to_vec(),
+        "current",
+        sweep_current(diode, voltages),
+    )
+}
+
+/// Current against voltage of a Schottky diode, including its
+/// reverse leakage
+pub fn schottky_iv(model: SchottkyModel, voltages: &[f64]) -> Curve {
+    let diode = Component::SchottkyDiode {
+        anode: 1,
+        cathode: 0,
+        model,
+    };
+    Curve::new(
+        "voltage",
+        voltages.to_vec(),
+        "current",
+        sweep_current(diode, voltages),
+    )
+}
+
+/// Output current against voltage of a solar cell (or photodiode) at
+/// an irradiance. The current is positive when the cell delivers
+/// power, so the curve runs from the short-circuit current at zero
+/// volts down through the open-circuit voltage.
+pub fn solar_cell_iv(
+    model: DiodeModel,
+    responsivity: f64,
+    irradiance: f64,
+    voltages: &[f64],
+) -> Curve {
+    let cell = Component::Photodiode {
+        anode: 1,
+        cathode: 0,
+        model,
+        responsivity,
+        irradiance,
+    };
+    let current = sweep_current(cell, voltages)
+        .into_iter()
+        .map(|i| -i)
+        .collect();
+    Curve::new("voltage", voltages.to_vec(), "current", current)
+}
+
+/// Resistance against temperature (degrees Celsius) of a thermistor
+pub fn thermistor_resistance(model: ThermistorModel, temperatures: &[f64]) -> Curve {
+    let resistance = temperatures
+        .iter()
+        .map(|temperature| {
+            measure_resistance(Component::Thermistor {
+                term_1: 1,
+                term_2: 0,
+                current_edge: None,
+                model,
+                temperature: Some(*temperature),
+            })
+        })
+        .collect();
+    Curve::new(
+        "temperature",
+        temperatures.to_vec(),
+        "resistance",
+        resistance,
+    )
+}
+
+/// Resistance against drawn length of a semiconductor resistor of a
+/// width (or the model default width) at a temperature
+pub fn semiconductor_resistance(
+    model: SemiconductorResistorModel,
+    width: Option<f64>,
+    temperature: f64,
+    lengths: &[f64],
+) -> Curve {
+    let resistance = lengths
+        .iter()
+        .map(|length| {
+            measure_resistance(Component::SemiconductorResistor {
+                term_1: 1,
+                term_2: 0,
+                current_edge: None,
+                model,
+                length: *length,
+                width,
+                temperature: Some(temperature),
+            })
+        })
+        .collect();
+    Curve::new("length", lengths.to_vec(), "resistance", resistance)
+}
+
+/// Incremental inductance against bias current of a saturable
+/// inductor, measured by AC analysis about each bias point
+pub fn inductance_vs_current(curve: &SaturationCurve, currents: &[f64]) -> Curve {
+    let omega = 2.0 * PI * INDUCTANCE_TEST_FREQUENCY;
+    let inductance = currents
+        .iter()
+        .map(|current| {
+            let mut circuit = Circuit::new();
+            circuit.add_component(
+                "dut",
+                Component::SaturableInductor {
+                    term_1: 1,
+                    term_2: 0,
+                    current_edge: 0,
+                    curve: curve.clone(),
+                },
+            );
+            circuit.add_component(
+                "ibias",
+                Component::IndependentCurrentSource {
+                    term_pos: 0,
+                    term_neg: 1,
+                    current: *current,
+                    ac: AcSpec::new(1.0, 0.0),
+                    waveform: None,
+                },
+            );
+            let (voltages, _) = LinearAcAnalysis::new(&circuit).solve(INDUCTANCE_TEST_FREQUENCY);
+            voltages[0].norm() / omega
+        })
+        .collect();
+    Curve::new("current", currents.to_vec(), "inductance", inductance)
+}
+
+/// Impedance magnitude against frequency of a crystal, measured by
+/// driving it with a unit AC current
+pub fn crystal_impedance(params: CrystalParams, frequencies: &[f64]) -> Curve {
+    let mut circuit = Circuit::new();
+    circuit.add_component(
+        "dut",
+        Component::Crystal {
+            term_1: 1,
+            term_2: 0,
+            params,
+        },
+    );
+    circuit.add_component(
+        "itest",
+        Component::IndependentCurrentSource {
+            term_pos: 0,
+            term_neg: 1,
+            current: 0.0,
+            ac: AcSpec::new(1.0, 0.0),
+            waveform: None,
+        },
+    );
+    let analysis = LinearAcAnalysis::new(&circuit);
+    let impedance = frequencies
+        .iter()
+        .map(|frequency| analysis.solve(*frequency).0[0].norm())
+        .collect();
+    Curve::new("frequency", frequencies.to_vec(), "impedance", impedance)
+}
+
+/// A voltage source from a node to ground
+fn voltage_source(node: usize, current_edge: usize, voltage: f64) -> Component {
+    Component::IndependentVoltageSource {
+        term_pos: node,
+        term_neg: 0,
+        current_edge,
+        voltage,
+        ac: AcSpec::default(),
+        waveform: None,
+    }
+}
+
+/// Drain current of a MOSFET (drain node 1, gate node 2, source
+/// grounded) with its gate and drain held at voltages
+fn drain_current(model: MosfetModel, vgs: f64, vds: f64) -> f64 {
+    let mut circuit = Circuit::new();
+    circuit.add_component(
+        "dut",
+        Component::Mosfet {
+            drain: 1,
+            gate: 2,
+            source: 0,
+            model,
+        },
+    );
+    circuit.add_component("vd", voltage_source(1, 0, vds));
+    circuit.add_component("vg", voltage_source(2, 1, vgs));
+    // The source current flows from the drain into the source
+    -operating_point(&circuit).currents[0]
+}
+
+/// Drain current against drain-source voltage of a MOSFET at a
+/// gate-source voltage
+pub fn mosfet_output(model: MosfetModel, vgs: f64, drain_voltages: &[f64]) -> Curve {
+    let current = drain_voltages
+        .iter()
+        .map(|vds| drain_current(model, vgs, *vds))
+        .collect();
+    Curve::new(
+        "drain voltage",
+        drain_voltages.to_vec(),
+        "drain current",
+        current,
+    )
+}
+
+/// Drain current against gate-source voltage of a MOSFET at a
+/// drain-source voltage
+pub fn mosfet_transfer(model: MosfetModel, vds: f64, gate_voltages: &[f64]) -> Curve {
+    let current = gate_voltages
+        .iter()
+        .map(|vgs| drain_current(model, *vgs, vds))
+        .collect();
+    Curve::new(
+        "gate voltage",
+        gate_voltages.to_vec(),
+        "drain current",
+        current,
+    )
+}
+
+/// The frequency at which a current gain, falling with frequency,
+/// is one, found by bisection on a log scale, or NaN if the gain is
+/// not above one at 1 Hz or not below it by the highest frequency
+fn unity_gain_frequency(gain: impl Fn(f64) -> f64) -> f64 {
+    let mut low = 1.0;
+    if gain(low) <= 1.0 {
+        return f64::NAN;
+    }
+    let mut high = 10.0;
+    while gain(high) > 1.0 {
+        if high >= MAX_TRANSITION_FREQUENCY {
+            return f64::NAN;
+        }
+        low = high;
+        high *= 10.0;
+    }
+    while high / low > 1.0 + 1e-9 {
+        let middle = (low * high).sqrt();
+        if gain(middle) > 1.0 {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    (low * high).sqrt()
+}
+
+/// Transition frequency against base current of a bipolar transistor
+/// at a collector-emitter voltage. The base is driven by the bias
+/// current with a unit AC current, and the collector is held (and AC
+/// shorted) by a voltage source.
+pub fn bjt_ft(model: BjtModel, vce: f64, base_currents: &[f64]) -> Curve {
+    let ft = base_currents
+        .iter()
+        .map(|current| {
+            let mut circuit = Circuit::new();
+            circuit.add_component(
+                "dut",
+                Component::Bjt {
+                    collector: 1,
+                    base: 2,
+                    emitter: 0,
+                    model,
+                },
+            );
+            circuit.add_component("vce", voltage_source(1, 0, vce));
+            circuit.add_component(
+                "ib",
+                Component::IndependentCurrentSource {
+                    term_pos: 0,
+                    term_neg: 2,
+                    current: *current,
+                    ac: AcSpec::new(1.0, 0.0),
+                    waveform: None,
+                },
+            );
+            let analysis = LinearAcAnalysis::new(&circuit);
+            unity_gain_frequency(|frequency| analysis.solve(frequency).1[0].norm())
+        })
+        .collect();
+    Curve::new(
+        "base current",
+        base_currents.to_vec(),
+        "transition frequency",
+        ft,
+    )
+}
+
+/// Transition frequency against gate-source voltage of a MOSFET at a
+/// drain-source voltage. The gate is biased through a large resistor
+/// and driven by a unit AC current, and the drain is held (and AC
+/// shorted) by a voltage source.
+pub fn mosfet_ft(model: MosfetModel, vds: f64, gate_voltages: &[f64]) -> Curve {
+    let ft = gate_voltages
+        .iter()
+        .map(|vgs| {
+            let mut circuit = Circuit::new();
+            circuit.add_component(
+                "dut",
+                Component::Mosfet {
+                    drain: 1,
+                    gate: 2,
+                    source: 0,
+                    model,
+                },
+            );
+            circuit.add_component("vd", voltage_source(1, 0, vds));
+            circuit.add_component("vg", voltage_source(3, 1, *vgs));
+            circuit.add_component(
+                "rbias",
+                Component::Resistor {
+                    term_1: 3,
+                    term_2: 2,
+                    current_edge: None,
+                    resistance: GATE_BIAS_RESISTANCE,
+                },
+            );
+            circuit.add_component(
+                "ig",
+                Component::IndependentCurrentSource {
+                    term_pos: 0,
+                    term_neg: 2,
+                    current: 0.0,
+                    ac: AcSpec::new(1.0, 0.0),
+                    waveform: None,
+                },
+            );
+            let analysis = LinearAcAnalysis::new(&circuit);
+            unity_gain_frequency(|frequency| {
+                let (voltages, currents) = analysis.solve(frequency);
+                // The gate current is the drive less what the bias
+                // resistor takes
+                let gate = 1.0 - voltages[1] / GATE_BIAS_RESISTANCE;
+                (currents[0] / gate).norm()
+            })
+        })
+        .collect();
+    Curve::new(
+        "gate voltage",
+        gate_voltages.to_vec(),
+        "transition frequency",
+        ft,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::THERMAL_VOLTAGE;
+
+    #[test]
+    fn the_diode_bench_follows_the_diode_equation() {
+        let model = DiodeModel::default();
+        let curve = diode_iv(model, &[0.5, 0.6]);
+        assert_eq!(curve.x, vec![0.5, 0.6]);
+        for (voltage, current) in curve.x.iter().zip(&curve.y) {
+            let expected = model.is * ((voltage / THERMAL_VOLTAGE).exp() - 1.0);
+            assert!((current / expected - 1.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn the_inductance_bench_measures_the_slope_of_the_curve() {
+        let curve = SaturationCurve::Arctan {
+            l0: 10e-3,
+            l_sat: 2e-3,
+            i_sat: 1.0,
+        };
+        let measured = inductance_vs_current(&curve, &[0.0, 1.0, 3.0]);
+        for (current, inductance) in measured.x.iter().zip(&measured.y) {
+            assert!((inductance / curve.inductance(*current) - 1.0).abs() < 1e-6);
+        }
+    }
+
+    fn mosfet() -> MosfetModel {
+        MosfetModel {
+            vto: 1.0,
+            kp: 1e-3,
+            cgso: 5e-8,
+            cgdo: 2e-8,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn the_mosfet_benches_follow_the_square_law() {
+        let model = mosfet();
+        // Linear region, edge of saturation and saturation at vgs = 3
+        let output = mosfet_output(model, 3.0, &[1.0, 2.0, 4.0]);
+        assert_eq!(output.y_name, "drain current");
+        for (current, expected) in output.y.iter().zip([1.5e-3, 2e-3, 2e-3]) {
+            assert!((current - expected).abs() < 1e-9, "{current}");
+        }
+        let transfer = mosfet_transfer(model, 5.0, &[0.5, 2.0, 3.0]);
+        for (current, expected) in transfer.y.iter().zip([0.0, 0.5e-3, 2e-3]) {
+            assert!((current - expected).abs() < 1e-9, "{current}");
+        }
+    }
+
+    #[test]
+    fn the_ft_benches_find_the_unity_current_gain() {
+        // With the output shorted, the current gain is
+        // (gm - jw Cbc) / (jw (Cbe + Cbc)), which is one at
+        // w = gm / sqrt(C^2 - Cbc^2)
+        let unity = |gm: f64, input: f64, feedback: f64| {
+            let total = input + feedback;
+            gm / (total * total - feedback * feedback).sqrt() / (2.0 * PI)
+        };
+        let model = BjtModel {
+            cje: 10e-12,
+            cjc: 1e-12,
+            ..Default::default()
+        };
+        let curve = bjt_ft(model, 5.0, &[10e-6]);
+        // The collector current is the gain times the base current
+        let gm = 1e-3 / THERMAL_VOLTAGE;
+        let expected = unity(gm, 10e-12, 1e-12);
+        assert!((curve.y[0] / expected - 1.0).abs() < 1e-3, "{}", curve.y[0]);
+
+        // 5 pF and 2 pF of overlap at the default width, and
+        // gm = kp (vgs - vto) = 2 mS
+        let curve = mosfet_ft(mosfet(), 5.0, &[3.0, 0.5]);
+        let expected = unity(2e-3, 5e-12, 2e-12);
+        assert!((curve.y[0] / expected - 1.0).abs() < 1e-3, "{}", curve.y[0]);
+        // A transistor that is off has no gain
+        assert!(curve.y[1].is_nan());
+    }
+}
@@ -0,0 +1,1171 @@
+//! Circuit description
+//!
+//! A circuit is a list of named components, independent of any
+//! analysis. Analyses read the circuit to assemble their own
+//! MNA systems. A circuit also holds parameters (see
+//! [crate::expression]), and the values of instances that are
+//! expressions of them, which follow them when they are set, and
+//! named device models (as defined by `.MODEL` cards), which the
+//! instances that use them follow in the same way, and names for its
+//! nodes (see [crate::node]).
+//!
+//! Macromodel components (such as crystals) are not stamped
+//! directly. Instead, the circuit is elaborated first, which
+//! replaces them with the primitive components of their equivalent
+//! circuits. Components whose values come from a model (such as
+//! semiconductor resistors) are replaced by plain components with
+//! the computed values. Voltage probes are kept, and their loading
+//! is added as plain components, as are the gate capacitance of
+//! IGBTs and the series resistance of diodes. Elaboration also warns about loops of ideal voltage
+//! sources (including parallel sources), which would make the MNA
+//! matrix singular. The electrical rule check ([Circuit::check])
+//! finds these and the other connectivity problems (such as floating
+//! nodes) before any analysis is run.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use crate::component::{
+    BjtModel, Component, DiodeModel, Junction, Model, MosfetModel, SchottkyModel,
+    NOMINAL_TEMPERATURE,
+};
+use crate::error::{invalid, no_instance, EsimError};
+use crate::expression::{Expression, ExpressionError, Parameters};
+use crate::netlist::{parse_netlist, parse_netlist_file, NetlistError};
+use crate::node::NodeNames;
+use crate::topology::{self, voltage_loops, Violation};
+use crate::transient::ComponentChange;
+
+/// A named component in a circuit
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub name: String,
+    pub component: Component,
+}
+
+/// Allocates internal nodes and current edges while elaborating
+struct Elaboration {
+    circuit: Circuit,
+    next_node: usize,
+    next_edge: usize,
+}
+
+impl Elaboration {
+    fn node(&mut self) -> usize {
+        self.next_node += 1;
+        self.next_node
+    }
+
+    fn edge(&mut self) -> usize {
+        self.next_edge += 1;
+        self.next_edge - 1
+    }
+
+    /// Add a component of a macromodel, named after its parent instance
+    fn add(&mut self, parent: &str, name: &str, component: Component) {
+        self.circuit
+            .add_component(&format!("{parent}.{name}"), component);
+    }
+
+    /// The internal node behind a series resistance from a terminal, or
+    /// the terminal if there is no resistance
+    fn series(&mut self, parent: &str, name: &str, terminal: usize, resistance: f64) -> usize {
+        if resistance <= 0.0 {
+            return terminal;
+        }
+        let node = self.node();
+        self.add(parent, name, series_resistor(terminal, node, resistance));
+        node
+    }
+
+    /// Add a capacitor of a macromodel, unless it has no capacitance
+    fn capacitor(
+        &mut self,
+        parent: &str,
+        name: &str,
+        term_1: usize,
+        term_2: usize,
+        capacitance: f64,
+    ) {
+        if capacitance > 0.0 {
+            self.add(
+                parent,
+                name,
+                Component::Capacitor {
+                    term_1,
+                    term_2,
+                    capacitance,
+                },
+            );
+        }
+    }
+}
+
+fn series_resistor(term_1: usize, term_2: usize, resistance: f64) -> Component {
+    Component::Resistor {
+        term_1,
+        term_2,
+        current_edge: None,
+        resistance,
+    }
+}
+
+/// Names of the components that differ between two versions of a
+/// circuit
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CircuitDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Components whose type, nodes or values changed
+    pub changed: Vec<String>,
+}
+
+impl CircuitDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Circuit {
+    instances: Vec<Instance>,
+    parameters: Parameters,
+    /// Instances whose values are expressions of the parameters
+    expressions: Vec<(String, Expression)>,
+    models: BTreeMap<String, Model>,
+    /// Instances that use a model, with the name of the model
+    model_instances: Vec<(String, String)>,
+    nodes: NodeNames,
+    /// Temperature (degrees Celsius), if it is not the nominal one
+    temperature: Option<f64>,
+    /// Warnings of parsing the circuit from a netlist
+    warnings: Vec<String>,
+}
+
+impl Circuit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_component(&mut self, name: &str, component: Component) {
+        self.instances.push(Instance {
+            name: name.to_string(),
+            component,
+        });
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    pub fn instances_mut(&mut self) -> &mut [Instance] {
+        &mut self.instances
+    }
+
+    /// The node with a name, giving a new name the node after the
+    /// highest in use (see [crate::node] for the names of ground and
+    /// the numbered nodes). Panics if the name is a number that has
+    /// been given to another name (see [Circuit::try_node]).
+    pub fn node(&mut self, name: &str) -> usize {
+        self.try_node(name)
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// The node with a name, as for [Circuit::node], returning an error
+    /// if the name is a number that has been given to another name
+    pub fn try_node(&mut self, name: &str) -> Result<usize, EsimError> {
+        if let Ok(number) = name.parse::<usize>() {
+            if let Some(other) = self.nodes.name(number) {
+                return Err(invalid(format!(
+                    "node {number} is the node named '{other}'"
+                )));
+            }
+        }
+        if let Some(node) = self.nodes.get(name) {
+            return Ok(node);
+        }
+        let node = self.num_voltage_nodes().max(self.nodes.highest()) + 1;
+        self.nodes.insert(name, node);
+        Ok(node)
+    }
+
+    /// Names of the nodes
+    pub fn node_names(&self) -> &NodeNames {
+        &self.nodes
+    }
+
+    pub(crate) fn set_node_names(&mut self, nodes: NodeNames) {
+        self.nodes = nodes;
+    }
+
+    /// Temperature of the circuit (degrees Celsius), as set by a
+    /// `.TEMP` line, which is that of the devices that do not have their
+    /// own (27 degrees Celsius unless it is set)
+    pub fn temperature(&self) -> f64 {
+        self.temperature.unwrap_or(NOMINAL_TEMPERATURE)
+    }
+
+    pub fn set_temperature(&mut self, temperature: f64) {
+        self.temperature = Some(temperature);
+    }
+
+    /// Warnings of parsing the circuit from a netlist, such as a value
+    /// that was normalized or a model that is not supported and so was
+    /// ignored, with their locations
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub(crate) fn set_warnings(&mut self, warnings: Vec<String>) {
+        self.warnings = warnings;
+    }
+
+    /// Parameters of the circuit (as defined by `.PARAM` lines)
+    pub fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+
+    pub(crate) fn set_parameters(&mut self, parameters: Parameters) {
+        self.parameters = parameters;
+    }
+
+    /// Give the value of an instance (as set by
+    /// [ComponentChange::Value]) by an expression of the parameters,
+    /// which is evaluated now and again whenever a parameter is set.
+    /// Fails if there is no instance with the name, or its value
+    /// cannot be set.
+    pub fn set_value_expression(
+        &mut self,
+        instance: &str,
+        expression: Expression,
+    ) -> Result<(), ExpressionError> {
+        self.set_value(instance, self.parameters.evaluate(&expression)?)?;
+        self.expressions
+            .retain(|(name, _)| name.as_str() != instance);
+        self.expressions.push((instance.to_string(), expression));
+        Ok(())
+    }
+
+    /// Instances whose values are expressions of the parameters, with
+    /// the expressions
+    pub fn value_expressions(&self) -> &[(String, Expression)] {
+        &self.expressions
+    }
+
+    /// Set a parameter to a value, and evaluate the values of the
+    /// instances that depend on it again. On an error, the instances
+    /// are not changed.
+    pub fn set_parameter(&mut self, name: &str, value: f64) -> Result<(), ExpressionError> {
+        let mut parameters = self.parameters.clone();
+        parameters.set(name, value);
+        let values = self
+            .expressions
+            .iter()
+            .map(|(instance, expression)| Ok((instance.clone(), parameters.evaluate(expression)?)))
+            .collect::<Result<Vec<_>, ExpressionError>>()?;
+        for (instance, value) in values {
+            self.set_value(&instance, value)?;
+        }
+        self.parameters = parameters;
+        Ok(())
+    }
+
+    fn set_value(&mut self, name: &str, value: f64) -> Result<(), ExpressionError> {
+        let fail = |message: String| ExpressionError { message };
+        let instance = self
+            .instances
+            .iter_mut()
+            .find(|i| i.name == name)
+            .ok_or_else(|| fail(format!("No instance named {name}")))?;
+        instance.component = ComponentChange::Value(value)
+            .apply(&instance.component)
+            .ok_or_else(|| fail(format!("Cannot set the value of instance {name}")))?;
+        Ok(())
+    }
+
+    /// Define a model (names are not case sensitive), replacing any
+    /// model with the name, in which case the instances that use it
+    /// take its new parameters. Panics if such an instance cannot use
+    /// the new model (see [Circuit::try_add_model]).
+    pub fn add_model(&mut self, name: &str, model: Model) {
+        self.try_add_model(name, model)
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Define a model as for [Circuit::add_model], returning an error,
+    /// and changing nothing, if an instance that uses a model with the
+    /// name cannot use the new model
+    pub fn try_add_model(&mut self, name: &str, model: Model) -> Result<(), EsimError> {
+        let name = name.to_ascii_lowercase();
+        for (instance, _) in self.model_instances.iter().filter(|(_, m)| *m == name) {
+            let mut component = self.instance_component(instance)?.clone();
+            if !model.apply(&mut component) {
+                return Err(invalid(format!(
+                    "Instance {instance} cannot use model {name}"
+                )));
+            }
+        }
+        self.models.insert(name.clone(), model);
+        self.apply_model(&name);
+        Ok(())
+    }
+
+    /// Component of an instance by name
+    fn instance_component(&self, name: &str) -> Result<&Component, EsimError> {
+        self.instances
+            .iter()
+            .find(|i| i.name == name)
+            .map(|i| &i.component)
+            .ok_or_else(|| no_instance(name))
+    }
+
+    /// A model by name
+    pub fn model(&self, name: &str) -> Option<&Model> {
+        self.models.get(&name.to_ascii_lowercase())
+    }
+
+    /// The models with their names (in lower case), in order of name
+    pub fn models(&self) -> impl Iterator<Item = (&str, &Model)> {
+        self.models
+            .iter()
+            .map(|(name, model)| (name.as_str(), model))
+    }
+
+    /// The name of the model an instance uses, if it uses one
+    pub fn instance_model(&self, instance: &str) -> Option<&str> {
+        self.model_instances
+            .iter()
+            .find(|(name, _)| name == instance)
+            .map(|(_, model)| model.as_str())
+    }
+
+    /// Make an instance use a model, giving it the parameters of the
+    /// model now and whenever they are changed. Panics if there is no
+    /// instance or model with the name, or the instance is not a
+    /// device of the type of the model (see [Circuit::try_use_model]).
+    pub fn use_model(&mut self, instance: &str, model: &str) {
+        self.try_use_model(instance, model)
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Make an instance use a model as for [Circuit::use_model],
+    /// returning an error, and changing nothing, if it cannot
+    pub fn try_use_model(&mut self, instance: &str, model: &str) -> Result<(), EsimError> {
+        let model = model.to_ascii_lowercase();
+        let card = *self
+            .models
+            .get(&model)
+            .ok_or_else(|| invalid(format!("No model named {model}")))?;
+        let index = self
+            .instances
+            .iter()
+            .position(|i| i.name == instance)
+            .ok_or_else(|| no_instance(instance))?;
+        let mut component = self.instances[index].component.clone();
+        if !card.apply(&mut component) {
+            return Err(invalid(format!(
+                "Instance {instance} cannot use model {model}"
+            )));
+        }
+        self.instances[index].component = component;
+        self.model_instances.retain(|(name, _)| name != instance);
+        self.model_instances.push((instance.to_string(), model));
+        Ok(())
+    }
+
+    /// Set a parameter of a model (as for [Model::parameter_mut]), and
+    /// so of every instance that uses it. Returns false, changing
+    /// nothing, if there is no model with the name or it has no such
+    /// parameter.
+    pub fn set_model_parameter(&mut self, model: &str, parameter: &str, value: f64) -> bool {
+        let model = model.to_ascii_lowercase();
+        let Some(card) = self.models.get_mut(&model) else {
+            return false;
+        };
+        let Some(slot) = card.parameter_mut(parameter) else {
+            return false;
+        };
+        *slot = value;
+        self.apply_model(&model);
+        true
+    }
+
+    /// Give the instances that use a model its parameters. Those that
+    /// cannot use it keep their own (the callers check that they can).
+    fn apply_model(&mut self, model: &str) {
+        let card = self.models[model];
+        for (instance, _) in self.model_instances.iter().filter(|(_, m)| m == model) {
+            if let Some(instance) = self.instances.iter_mut().find(|i| i.name == *instance) {
+                card.apply(&mut instance.component);
+            }
+        }
+    }
+
+    /// Number of voltage nodes excluding ground (the highest node index)
+    pub fn num_voltage_nodes(&self) -> usize {
+        self.instances
+            .iter()
+            .flat_map(|instance| instance.component.terminals())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Number of current edges (one more than the highest edge index)
+    pub fn num_current_edges(&self) -> usize {
+        self.instances
+            .iter()
+            .flat_map(|instance| instance.component.current_edges())
+            .map(|e| e + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Replace the circuit with one parsed from a netlist, changing
+    /// only the components that differ, and return the differences
+    ///
+    /// Components that are in both versions keep their current edges
+    /// (new components take the lowest free edges, and the edges are
+    /// only renumbered to close the gaps left by removed components),
+    /// so results indexed by edge stay comparable between reloads. If
+    /// nothing changed, the circuit is left exactly as it was. On a
+    /// parse error, the circuit is not changed.
+    pub fn reload(&mut self, text: &str) -> Result<CircuitDiff, NetlistError> {
+        Ok(self.replace(parse_netlist(text)?))
+    }
+
+    /// Reload the circuit (as by [Circuit::reload]) from a netlist
+    /// file, whose included files are found from its directory
+    pub fn reload_file(&mut self, path: &Path) -> Result<CircuitDiff, NetlistError> {
+        Ok(self.replace(parse_netlist_file(path)?))
+    }
+
+    /// Replace the circuit with a new version, as for a reload
+    fn replace(&mut self, mut circuit: Circuit) -> CircuitDiff {
+        let old: HashMap<&str, &Component> = self
+            .instances
+            .iter()
+            .map(|instance| (instance.name.as_str(), &instance.component))
+            .collect();
+
+        // Keep the old edges, then give the remaining edges the lowest
+        // free ones, then close any gaps (keeping the order). The edges
+        // of a component (such as the coil and contact edges of a
+        // relay) are by their position in its edges.
+        let mut used = BTreeMap::new();
+        let mut unassigned = Vec::new();
+        for (index, instance) in circuit.instances.iter().enumerate() {
+            let old_edges = old
+                .get(instance.name.as_str())
+                .map(|c| c.current_edges())
+                .unwrap_or_default();
+            for position in 0..instance.component.current_edges().len() {
+                match old_edges.get(position) {
+                    Some(edge) => {
+                        used.insert(*edge, (index, position));
+                    }
+                    None => unassigned.push((index, position)),
+                }
+            }
+        }
+        let mut edge = 0;
+        for edge_of in unassigned {
+            while used.contains_key(&edge) {
+                edge += 1;
+            }
+            used.insert(edge, edge_of);
+        }
+        let mut renumbered = HashMap::new();
+        for (new_edge, (index, position)) in used.into_values().enumerate() {
+            let mut edges = circuit.instances[index].component.current_edges_mut();
+            let edge = &mut *edges[position];
+            renumbered.insert(*edge, new_edge);
+            *edge = new_edge;
+        }
+        // Current-controlled sources follow the edges that control them
+        for instance in &mut circuit.instances {
+            if let Some(ctrl_edge) = instance.component.ctrl_edge_mut() {
+                if let Some(new_edge) = renumbered.get(ctrl_edge) {
+                    *ctrl_edge = *new_edge;
+                }
+            }
+        }
+
+        let mut diff = CircuitDiff::default();
+        for instance in &circuit.instances {
+            match old.get(instance.name.as_str()) {
+                None => diff.added.push(instance.name.clone()),
+                Some(component) => {
+                    let mut component = (*component).clone();
+                    for (old_edge, new_edge) in component
+                        .current_edges_mut()
+                        .into_iter()
+                        .zip(instance.component.current_edges())
+                    {
+                        *old_edge = new_edge;
+                    }
+                    if component != instance.component {
+                        diff.changed.push(instance.name.clone());
+                    }
+                }
+            }
+        }
+        for instance in &self.instances {
+            if !circuit.instances.iter().any(|i| i.name == instance.name) {
+                diff.removed.push(instance.name.clone());
+            }
+        }
+        if !diff.is_empty() {
+            self.instances = circuit.instances;
+        }
+        self.parameters = circuit.parameters;
+        self.expressions = circuit.expressions;
+        self.models = circuit.models;
+        self.model_instances = circuit.model_instances;
+        self.nodes = circuit.nodes;
+        self.temperature = circuit.temperature;
+        self.warnings = circuit.warnings;
+        diff
+    }
+
+    /// Expand macromodel components into primitive components
+    ///
+    /// Internal nodes and current edges are numbered after the highest
+    /// node and edge used in the circuit, so the elaborated circuit
+    /// keeps all the original nodes and edges. Loops of ideal voltage
+    /// sources and inductors are left in, and are found by
+    /// [Circuit::check] (or broken by [Circuit::break_voltage_loops]).
+    pub fn elaborate(&self) -> Circuit {
+        self.expand()
+    }
+
+    /// Run the electrical rule check on the elaborated circuit (see
+    /// [crate::topology::check]), returning the problems that would
+    /// make the circuit fail to solve. There are none if it is empty.
+    pub fn check(&self) -> Vec<Violation> {
+        topology::check(&self.expand())
+    }
+
+    /// Check that the circuit can be assembled into an MNA system,
+    /// returning an error for the first of: two instances with the same
+    /// name, two instances with the same current edge, or a branch
+    /// (such as a resistor, or the output of a controlled source) with
+    /// both terminals on the same node. The analyses panic on these, or
+    /// give wrong results without warning. The files of the source
+    /// waveforms are read through too (see [crate::waveform::PwlFile]),
+    /// returning an error if one cannot be read or parsed, and the
+    /// saturation curves are checked (see
+    /// [SaturationCurve::check](crate::component::SaturationCurve::check)).
+    pub fn validate(&self) -> Result<(), EsimError> {
+        let mut names = HashSet::new();
+        let mut edges = HashMap::new();
+        for instance in &self.instances {
+            let name = &instance.name;
+            let fail = |message: String| Err(EsimError::Assembly { message });
+            if !names.insert(name) {
+                return fail(format!("Two instances are named {name}"));
+            }
+            for edge in instance.component.current_edges() {
+                if let Some(other) = edges.insert(edge, name) {
+                    return fail(format!(
+                        "Instances {other} and {name} share current edge {edge}"
+                    ));
+                }
+            }
+            let component = &instance.component;
+            let terminals = component.terminals();
+            let branches = match *component {
+                Component::NPort { ref ports, .. } => ports.clone(),
+                Component::Relay {
+                    coil_pos,
+                    coil_neg,
+                    contact_1,
+                    contact_2,
+                    ..
+                } => vec![(coil_pos, coil_neg), (contact_1, contact_2)],
+                Component::Igbt {
+                    collector, emitter, ..
+                }
+                | Component::Bjt {
+                    collector, emitter, ..
+                } => vec![(collector, emitter)],
+                Component::Mosfet { drain, source, .. } => vec![(drain, source)],
+                _ if terminals.len() >= 2 => vec![(terminals[0], terminals[1])],
+                _ => Vec::new(),
+            };
+            if let Some((node, _)) = branches.iter().find(|(n1, n2)| n1 == n2) {
+                return fail(format!("{name} has both terminals on node {node}"));
+            }
+            if let Component::IndependentVoltageSource {
+                waveform: Some(waveform),
+                ..
+            }
+            | Component::IndependentCurrentSource {
+                waveform: Some(waveform),
+                ..
+            } = component
+            {
+                waveform.check()?;
+            }
+            if let Component::SaturableInductor { curve, .. } = component {
+                if let Err(error) = curve.check() {
+                    return fail(format!("{name}: {error}"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Elaborate the circuit, and break every loop of ideal voltage
+    /// sources and inductors by inserting a small resistance in series
+    /// with the branch that closes the loop. Returns the elaborated
+    /// circuit and the names of the inserted resistors.
+    pub fn break_voltage_loops(&self, resistance: f64) -> (Circuit, Vec<String>) {
+        let mut circuit = self.expand();
+        let mut inserted = Vec::new();
+        for path in voltage_loops(&circuit) {
+            let index = *path.last().unwrap();
+            let node = circuit.num_voltage_nodes() + 1;
+            let terminal = match &mut circuit.instances[index].component {
+                Component::IndependentVoltageSource { term_pos: t, .. }
+                | Component::CurrentProbe { term_pos: t, .. }
+                | Component::MeasurementProbe { term_pos: t, .. }
+                | Component::Inductor { term_1: t, .. }
+                | Component::SaturableInductor { term_1: t, .. } => std::mem::replace(t, node),
+                _ => unreachable!("Only voltage branches can close a voltage loop"),
+            };
+            let name = format!("{}.rs", circuit.instances[index].name);
+            circuit.add_component(
+                &name,
+                Component::Resistor {
+                    term_1: terminal,
+                    term_2: node,
+                    current_edge: None,
+                    resistance,
+                },
+            );
+            inserted.push(name);
+        }
+        (circuit, inserted)
+    }
+
+    fn expand(&self) -> Circuit {
+        let mut elab = Elaboration {
+            circuit: Circuit::new(),
+            next_node: self.num_voltage_nodes(),
+            next_edge: self.num_current_edges(),
+        };
+        elab.circuit.nodes = self.nodes.clone();
+        elab.circuit.temperature = self.temperature;
+        for instance in &self.instances {
+            let name = &instance.name;
+            // Diodes, transistors and thermistors without their own
+            // temperature are at that of the circuit
+            let mut component = instance.component.clone();
+            match &mut component {
+                Component::Diode { model, .. } | Component::Photodiode { model, .. } => {
+                    model.temp = Some(model.temp.unwrap_or(self.temperature()));
+                }
+                Component::SchottkyDiode { model, .. } => {
+                    model.temp = Some(model.temp.unwrap_or(self.temperature()));
+                }
+                Component::TunnelDiode { model, .. } => {
+                    model.temp = Some(model.temp.unwrap_or(self.temperature()));
+                }
+                Component::Bjt { model, .. } => {
+                    model.temp = Some(model.temp.unwrap_or(self.temperature()));
+                }
+                Component::Mosfet { model, .. } => {
+                    model.temp = Some(model.temp.unwrap_or(self.temperature()));
+                }
+                Component::Thermistor { temperature, .. } => {
+                    *temperature = Some(temperature.unwrap_or(self.temperature()));
+                }
+                _ => {}
+            }
+            match component {
+                Component::Crystal {
+                    term_1,
+                    term_2,
+                    params,
+                } => {
+                    let (a, b) = (elab.node(), elab.node());
+                    let current_edge = elab.edge();
+                    elab.add(
+                        name,
+                        "rm",
+                        Component::Resistor {
+                            term_1,
+                            term_2: a,
+                            current_edge: None,
+                            resistance: params.motional_resistance(),
+                        },
+                    );
+                    elab.add(
+                        name,
+                        "lm",
+                        Component::Inductor {
+                            term_1: a,
+                            term_2: b,
+                            current_edge,
+                            inductance: params.motional_inductance(),
+                        },
+                    );
+                    elab.add(
+                        name,
+                        "cm",
+                        Component::Capacitor {
+                            term_1: b,
+                            term_2,
+                            capacitance: params.cm,
+                        },
+                    );
+                    elab.add(
+                        name,
+                        "c0",
+                        Component::Capacitor {
+                            term_1,
+                            term_2,
+                            capacitance: params.c0,
+                        },
+                    );
+                }
+                Component::Supercapacitor {
+                    term_1,
+                    term_2,
+                    params,
+                } => {
+                    let a = elab.node();
+                    elab.add(name, "esr", series_resistor(term_1, a, params.esr));
+                    elab.add(
+                        name,
+                        "c",
+                        Component::Capacitor {
+                            term_1: a,
+                            term_2,
+                            capacitance: params.capacitance,
+                        },
+                    );
+                    elab.add(
+                        name,
+                        "rleak",
+                        series_resistor(a, term_2, params.leakage_resistance()),
+                    );
+                }
+                Component::SemiconductorResistor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    model,
+                    length,
+                    width,
+                    temperature,
+                } => elab.circuit.add_component(
+                    name,
+                    Component::Resistor {
+                        term_1,
+                        term_2,
+                        current_edge,
+                        resistance: model.resistance(
+                            length,
+                            width,
+                            temperature.unwrap_or(self.temperature()),
+                        ),
+                    },
+                ),
+                Component::SemiconductorCapacitor {
+                    term_1,
+                    term_2,
+                    model,
+                    length,
+                    width,
+                    temperature,
+                } => elab.circuit.add_component(
+                    name,
+                    Component::Capacitor {
+                        term_1,
+                        term_2,
+                        capacitance: model.capacitance(
+                            length,
+                            width,
+                            temperature.unwrap_or(self.temperature()),
+                        ),
+                    },
+                ),
+                Component::Urc {
+                    term_1,
+                    term_2,
+                    term_cap,
+                    model,
+                    length,
+                    lumps,
+                } => {
+                    let lumps = model.lumps(length, lumps.unwrap_or(model.num_lumps(length)));
+                    // Each lump is a T-section, so the resistor between two
+                    // lump nodes is half of each neighbouring lump
+                    let mut previous = (term_1, 0.0);
+                    for (k, (r, c)) in lumps.into_iter().enumerate() {
+                        let node = elab.node();
+                        elab.add(
+                            name,
+                            &format!("r{k}"),
+                            Component::Resistor {
+                                term_1: previous.0,
+                                term_2: node,
+                                current_edge: None,
+                                resistance: previous.1 + r / 2.0,
+                            },
+                        );
+                        elab.add(
+                            name,
+                            &format!("c{k}"),
+                            Component::Capacitor {
+                                term_1: node,
+                                term_2: term_cap,
+                                capacitance: c,
+                            },
+                        );
+                        previous = (node, r / 2.0);
+                    }
+                    elab.add(
+                        name,
+                        "rend",
+                        Component::Resistor {
+                            term_1: previous.0,
+                            term_2,
+                            current_edge: None,
+                            resistance: previous.1,
+                        },
+                    );
+                }
+                Component::MeasurementProbe {
+                    term_pos,
+                    term_neg,
+                    current_edge: None,
+                    params,
+                } => {
+                    elab.circuit.add_component(name, instance.component.clone());
+                    if let Some(resistance) = params.r_in {
+                        elab.add(
+                            name,
+                            "rin",
+                            Component::Resistor {
+                                term_1: term_pos,
+                                term_2: term_neg,
+                                current_edge: None,
+                                resistance,
+                            },
+                        );
+                    }
+                    if let Some(capacitance) = params.c_in {
+                        elab.add(
+                            name,
+                            "cin",
+                            Component::Capacitor {
+                                term_1: term_pos,
+                                term_2: term_neg,
+                                capacitance,
+                            },
+                        );
+                    }
+                }
+                Component::Diode {
+                    anode,
+                    cathode,
+                    model,
+                } if model.rs > 0.0 => {
+                    let node = elab.node();
+                    elab.add(name, "rs", series_resistor(anode, node, model.rs));
+                    elab.circuit.add_component(
+                        name,
+                        Component::Diode {
+                            anode: node,
+                            cathode,
+                            model: DiodeModel { rs: 0.0, ..model },
+                        },
+                    );
+                }
+                Component::SchottkyDiode {
+                    anode,
+                    cathode,
+                    model,
+                } if model.rs > 0.0 => {
+                    let node = elab.node();
+                    elab.add(name, "rs", series_resistor(anode, node, model.rs));
+                    elab.circuit.add_component(
+                        name,
+                        Component::SchottkyDiode {
+                            anode: node,
+                            cathode,
+                            model: SchottkyModel { rs: 0.0, ..model },
+                        },
+                    );
+                }
+                Component::Photodiode {
+                    anode,
+                    cathode,
+                    model,
+                    responsivity,
+                    irradiance,
+                } if model.rs > 0.0 => {
+                    let node = elab.node();
+                    elab.add(name, "rs", series_resistor(anode, node, model.rs));
+                    elab.circuit.add_component(
+                        name,
+                        Component::Photodiode {
+                            anode: node,
+                            cathode,
+                            model: DiodeModel { rs: 0.0, ..model },
+                            responsivity,
+                            irradiance,
+                        },
+                    );
+                }
+                Component::Igbt {
+                    gate,
+                    emitter,
+                    params,
+                    ..
+                } => {
+                    elab.circuit.add_component(name, component.clone());
+                    elab.add(
+                        name,
+                        "cge",
+                        Component::Capacitor {
+                            term_1: gate,
+                            term_2: emitter,
+                            capacitance: params.c_ge,
+                        },
+                    );
+                }
+                Component::Compact {
+                    anode,
+                    cathode,
+                    ref model,
+                } if model.capacitance() != 0.0 => {
+                    let capacitance = model.capacitance();
+                    elab.circuit.add_component(name, component.clone());
+                    elab.add(
+                        name,
+                        "ddt",
+                        Component::Capacitor {
+                            term_1: anode,
+                            term_2: cathode,
+                            capacitance,
+                        },
+                    );
+                }
+                Component::Bjt {
+                    collector,
+                    base,
+                    emitter,
+                    model,
+                } => {
+                    let collector = elab.series(name, "rc", collector, model.rc);
+                    let base = elab.series(name, "rb", base, model.rb);
+                    let emitter = elab.series(name, "re", emitter, model.re);
+                    elab.circuit.add_component(
+                        name,
+                        Component::Bjt {
+                            collector,
+                            base,
+                            emitter,
+                            model: BjtModel {
+                                rb: 0.0,
+                                rc: 0.0,
+                                re: 0.0,
+                                cje: 0.0,
+                                cjc: 0.0,
+                                ..model
+                            },
+                        },
+                    );
+                    elab.capacitor(name, "cje", base, emitter, model.cje);
+                    elab.capacitor(name, "cjc", base, collector, model.cjc);
+                }
+                Component::Mosfet {
+                    drain,
+                    gate,
+                    source,
+                    model,
+                } => {
+                    let drain = elab.series(name, "rd", drain, model.rd);
+                    let source = elab.series(name, "rs", source, model.rs);
+                    elab.circuit.add_component(
+                        name,
+                        Component::Mosfet {
+                            drain,
+                            gate,
+                            source,
+                            model: MosfetModel {
+                                rd: 0.0,
+                                rs: 0.0,
+                                cgso: 0.0,
+                                cgdo: 0.0,
+                                ..model
+                            },
+                        },
+                    );
+                    elab.capacitor(name, "cgs", gate, source, model.cgso * model.width);
+                    elab.capacitor(name, "cgd", gate, drain, model.cgdo * model.width);
+                }
+                component => elab.circuit.add_component(name, component),
+            }
+        }
+        elab.circuit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CircuitBuilder;
+    use crate::component::{AcSpec, ThermistorModel};
+    use crate::waveform::{Interpolation, OutOfRange, PwlFile, Waveform};
+
+    #[test]
+    fn a_number_given_to_a_name_is_reserved() {
+        let mut circuit = Circuit::new();
+        let out = circuit.node("out");
+        assert_eq!(circuit.node("OUT"), out);
+        assert_eq!(circuit.node_names().name(out), Some("out"));
+        let number = out.to_string();
+        assert!(matches!(
+            circuit.try_node(&number),
+            Err(EsimError::Invalid { .. })
+        ));
+        let built = CircuitBuilder::new()
+            .resistor("R1", "out", "0", 1e3)
+            .resistor("R2", "1", "0", 1e3)
+            .build();
+        assert_eq!(built.unwrap_err().instance, "R2");
+    }
+
+    #[test]
+    fn replace_keeps_every_edge_of_a_component() {
+        let relay = |coil_edge, contact_edge| Component::Relay {
+            coil_pos: 1,
+            coil_neg: 0,
+            contact_1: 1,
+            contact_2: 2,
+            coil_edge,
+            contact_edge,
+            params: Default::default(),
+        };
+        let source = |current_edge, voltage| Component::IndependentVoltageSource {
+            term_pos: 1,
+            term_neg: 0,
+            current_edge,
+            voltage,
+            ac: AcSpec::default(),
+            waveform: None,
+        };
+        let mut circuit = Circuit::new();
+        circuit.add_component("V1", source(0, 1.0));
+        circuit.add_component("K1", relay(1, 2));
+        // The circuit with a new voltage, numbered the other way round
+        let mut reloaded = Circuit::new();
+        reloaded.add_component("K1", relay(0, 1));
+        reloaded.add_component("V1", source(2, 2.0));
+        let diff = circuit.replace(reloaded);
+        assert_eq!(diff.changed, ["V1"]);
+        let edges: Vec<Vec<usize>> = circuit
+            .instances()
+            .iter()
+            .map(|instance| instance.component.current_edges())
+            .collect();
+        assert_eq!(edges, [vec![1, 2], vec![0]]);
+    }
+
+    #[test]
+    fn elaboration_gives_the_circuit_temperature_to_devices_without_their_own() {
+        let mut circuit = Circuit::new();
+        circuit.set_temperature(85.0);
+        let model = SchottkyModel::default();
+        circuit.add_component(
+            "D1",
+            Component::SchottkyDiode {
+                anode: 1,
+                cathode: 0,
+                model,
+            },
+        );
+        let model = SchottkyModel {
+            temp: Some(-40.0),
+            ..model
+        };
+        circuit.add_component(
+            "D2",
+            Component::SchottkyDiode {
+                anode: 1,
+                cathode: 0,
+                model,
+            },
+        );
+        circuit.add_component(
+            "R1",
+            Component::Thermistor {
+                term_1: 1,
+                term_2: 0,
+                current_edge: None,
+                model: ThermistorModel::Beta {
+                    r0: 10e3,
+                    t0: 25.0,
+                    beta: 3950.0,
+                },
+                temperature: None,
+            },
+        );
+        let elaborated = circuit.elaborate();
+        let temperatures: Vec<_> = elaborated
+            .instances()
+            .iter()
+            .map(|instance| match instance.component {
+                Component::SchottkyDiode { model, .. } => model.temp,
+                Component::Thermistor { temperature, .. } => temperature,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(temperatures, [Some(85.0), Some(-40.0), Some(85.0)]);
+    }
+
+    #[test]
+    fn try_use_model_reports_unknown_names() {
+        let mut circuit = CircuitBuilder::new()
+            .vsource("V1", "in", "0", 1.0)
+            .resistor("R1", "in", "a", 1e3)
+            .diode("D1", "a", "0")
+            .build()
+            .unwrap();
+        let model = circuit.try_use_model("D1", "nomodel");
+        assert!(matches!(model, Err(EsimError::Invalid { .. })));
+        circuit.add_model("fast", Model::Diode(Default::default()));
+        let instance = circuit.try_use_model("D9", "fast");
+        assert!(matches!(instance, Err(EsimError::Invalid { .. })));
+        assert!(circuit.try_use_model("D1", "fast").is_ok());
+    }
+
+    #[test]
+    fn validate_reads_the_waveform_files() {
+        let mut circuit = CircuitBuilder::new()
+            .resistor("R1", "in", "0", 1e3)
+            .build()
+            .unwrap();
+        let file = PwlFile::new(
+            "no/such/stimulus.csv",
+            Interpolation::Linear,
+            OutOfRange::Hold,
+        );
+        circuit.add_component(
+            "V1",
+            Component::IndependentVoltageSource {
+                term_pos: 1,
+                term_neg: 0,
+                current_edge: 0,
+                voltage: 0.0,
+                ac: AcSpec::default(),
+                waveform: Some(Waveform::PwlFile(file)),
+            },
+        );
+        assert!(matches!(circuit.validate(), Err(EsimError::Io { .. })));
+    }
+}
@@ -0,0 +1,796 @@
+//! Circuit components
+//!
+//! Components hold the values of a circuit element for every
+//! analysis. For example, an independent source carries both its
+//! DC value, its AC (small-signal) specification and its transient
+//! waveform, and each analysis picks out the value that applies to it.
+
+use std::sync::Arc;
+
+use num::Complex;
+
+use crate::waveform::Waveform;
+
+pub use self::battery::BatteryModel;
+pub use self::bjt::BjtModel;
+pub use self::compact::CompactModel;
+pub use self::crystal::{CrystalParams, DEFAULT_CAPACITANCE_RATIO};
+pub use self::digital::{LogicFamily, LogicGate};
+pub use self::diode::{
+    thermal_voltage, DiodeModel, ELECTRON_CHARGE, NOMINAL_TEMPERATURE, THERMAL_VOLTAGE,
+};
+pub use self::fuse::FuseParams;
+pub use self::igbt::IgbtParams;
+pub use self::junction::Junction;
+pub use self::limiting::{fetlim, limexp, limvds, pnjlim};
+pub use self::model::Model;
+pub use self::mosfet::{MosfetModel, DEFAULT_SIZE};
+pub use self::probe::ProbeParams;
+pub use self::relay::RelayParams;
+pub use self::saturation::SaturationCurve;
+pub use self::schottky::SchottkyModel;
+pub use self::semiconductor::{SemiconductorCapacitorModel, SemiconductorResistorModel};
+pub use self::supercap::SupercapParams;
+pub use self::table::{TableModel, TableQuantity};
+pub use self::thermistor::ThermistorModel;
+pub use self::thyristor::{ThyristorKind, ThyristorParams};
+pub use self::touchstone::{NetworkParameter, TouchstoneData};
+pub use self::transistor::{Polarity, Transistor, TransistorCurrent};
+pub use self::tunnel_diode::TunnelDiodeModel;
+pub use self::urc::UrcModel;
+
+mod battery;
+mod bjt;
+mod compact;
+mod crystal;
+mod digital;
+mod diode;
+mod fuse;
+mod igbt;
+mod junction;
+mod limiting;
+mod model;
+mod mosfet;
+mod probe;
+mod relay;
+mod saturation;
+mod schottky;
+mod semiconductor;
+mod supercap;
+mod table;
+mod thermistor;
+mod thyristor;
+mod touchstone;
+mod transistor;
+mod tunnel_diode;
+mod urc;
+
+/// Small-signal excitation of an independent source
+///
+/// This is the SPICE `AC <magnitude> <phase>` specification, where
+/// the phase is in degrees. It is independent of the DC value of the
+/// source. A source with the default (zero) specification does not
+/// excite the circuit in AC analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AcSpec {
+    pub magnitude: f64,
+    /// Phase in degrees
+    pub phase: f64,
+}
+
+impl AcSpec {
+    pub fn new(magnitude: f64, phase: f64) -> Self {
+        Self { magnitude, phase }
+    }
+
+    /// The complex amplitude of the excitation
+    pub fn phasor(&self) -> Complex<f64> {
+        Complex::from_polar(self.magnitude, self.phase.to_radians())
+    }
+}
+
+/// Component type
+///
+/// Components are either in group 1 (their currents are eliminated),
+/// or group 2 (their currents are kept in the solution). Group 2
+/// components store the index of their current edge.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Component {
+    /// Fixed resistor (group1 or group2)
+    Resistor {
+        term_1: usize,
+        term_2: usize,
+        current_edge: Option<usize>,
+        resistance: f64,
+    },
+    /// Thermistor (group1 or group2)
+    ///
+    /// The resistance is given by the model at the ambient
+    /// temperature (degrees Celsius), which is that of the circuit if
+    /// the thermistor has none of its own (it is set to that of the
+    /// circuit when the circuit is elaborated).
+    Thermistor {
+        term_1: usize,
+        term_2: usize,
+        current_edge: Option<usize>,
+        model: ThermistorModel,
+        temperature: Option<f64>,
+    },
+    /// Semiconductor resistor (group1 or group2)
+    ///
+    /// The resistance is computed by the model from the drawn length
+    /// and width (or the model default width) at the temperature
+    /// (degrees Celsius, that of the circuit if the resistor has none
+    /// of its own), and the component becomes a plain resistor when the
+    /// circuit is elaborated.
+    SemiconductorResistor {
+        term_1: usize,
+        term_2: usize,
+        current_edge: Option<usize>,
+        model: SemiconductorResistorModel,
+        length: f64,
+        width: Option<f64>,
+        temperature: Option<f64>,
+    },
+    /// Capacitor (group1)
+    Capacitor {
+        term_1: usize,
+        term_2: usize,
+        capacitance: f64,
+    },
+    /// Semiconductor capacitor (group1)
+    ///
+    /// The capacitance is computed by the model from the drawn length
+    /// and width (or the model default width) at the temperature
+    /// (degrees Celsius, that of the circuit if the capacitor has none
+    /// of its own), and the component becomes a plain capacitor when
+    /// the circuit is elaborated.
+    SemiconductorCapacitor {
+        term_1: usize,
+        term_2: usize,
+        model: SemiconductorCapacitorModel,
+        length: f64,
+        width: Option<f64>,
+        temperature: Option<f64>,
+    },
+    /// Inductor (group2)
+    Inductor {
+        term_1: usize,
+        term_2: usize,
+        current_edge: usize,
+        inductance: f64,
+    },
+    /// Inductor with a saturating core (group2)
+    ///
+    /// This is a short circuit at DC. In AC analysis, it has the
+    /// incremental inductance of the curve at the DC current.
+    SaturableInductor {
+        term_1: usize,
+        term_2: usize,
+        current_edge: usize,
+        curve: SaturationCurve,
+    },
+    /// Quartz crystal (macromodel)
+    ///
+    /// Expanded into its motional RLC branch and shunt capacitance
+    /// when the circuit is elaborated.
+    Crystal {
+        term_1: usize,
+        term_2: usize,
+        params: CrystalParams,
+    },
+    /// Supercapacitor (macromodel)
+    ///
+    /// Expanded into its ESR in series with the capacitance and
+    /// leakage resistance (in parallel) when the circuit is
+    /// elaborated. The ESR is at term_1.
+    Supercapacitor {
+        term_1: usize,
+        term_2: usize,
+        params: SupercapParams,
+    },
+    /// Uniform distributed RC line (macromodel)
+    ///
+    /// The resistance runs between term_1 and term_2, and the
+    /// capacitance is distributed between the line and term_cap. The
+    /// line is expanded into a ladder of RC lumps when the circuit is
+    /// elaborated; if the number of lumps is not given, it is chosen
+    /// by the model.
+    Urc {
+        term_1: usize,
+        term_2: usize,
+        term_cap: usize,
+        model: UrcModel,
+        length: f64,
+        lumps: Option<usize>,
+    },
+    /// Junction diode (group1, nonlinear)
+    ///
+    /// The series resistance of the model is added as a plain
+    /// resistor when the circuit is elaborated.
+    Diode {
+        anode: usize,
+        cathode: usize,
+        model: DiodeModel,
+    },
+    /// Photodiode or solar cell (group1, nonlinear)
+    ///
+    /// A diode with a photocurrent of responsivity (A per W/m^2)
+    /// times irradiance (W/m^2), flowing from the cathode to the
+    /// anode through the device.
+    Photodiode {
+        anode: usize,
+        cathode: usize,
+        model: DiodeModel,
+        responsivity: f64,
+        irradiance: f64,
+    },
+    /// Tunnel diode (group1, nonlinear)
+    ///
+    /// A diode with a region of negative differential resistance
+    /// between its peak and valley voltages.
+    TunnelDiode {
+        anode: usize,
+        cathode: usize,
+        model: TunnelDiodeModel,
+    },
+    /// Schottky diode (group1, nonlinear)
+    ///
+    /// A metal-semiconductor diode, with a low forward voltage and a
+    /// reverse leakage that rises with reverse voltage.
+    SchottkyDiode {
+        anode: usize,
+        cathode: usize,
+        model: SchottkyModel,
+    },
+    /// Lookup-table device (group1, nonlinear)
+    ///
+    /// A device whose current or resistance is interpolated from a
+    /// table of values at voltages from term_1 to term_2.
+    Table {
+        term_1: usize,
+        term_2: usize,
+        model: TableModel,
+    },
+    /// Compact model (group1, nonlinear)
+    ///
+    /// A two-terminal junction defined outside the crate, such as a
+    /// compiled Verilog-A model.
+    Compact {
+        anode: usize,
+        cathode: usize,
+        model: CompactModel,
+    },
+    /// N-port from measured network parameters (group1)
+    ///
+    /// Each port is a (positive, negative) pair of nodes, with the
+    /// current of the port into the positive node. In AC analysis, the
+    /// N-port is its admittance matrix, interpolated from the data at
+    /// each frequency. The data has no time-domain model, so in DC and
+    /// transient analysis the N-port is the real part of its admittance
+    /// at the lowest frequency of the data. The data is shared between
+    /// copies of the component.
+    NPort {
+        ports: Vec<(usize, usize)>,
+        data: Arc<TouchstoneData>,
+    },
+    /// Fuse or circuit breaker (group2)
+    ///
+    /// Intact in DC and AC analysis. In transient analysis, the fuse
+    /// integrates i²t from the current and opens as an event when the
+    /// rating is reached.
+    Fuse {
+        term_1: usize,
+        term_2: usize,
+        current_edge: usize,
+        params: FuseParams,
+    },
+    /// Relay (group2)
+    ///
+    /// The coil (a resistance in series with an inductance) is
+    /// between coil_pos and coil_neg, with its current on coil_edge,
+    /// and the contacts are between contact_1 and contact_2, with
+    /// their current on contact_edge. The contacts are normally open.
+    /// They are open in DC and AC analysis, and close and open as
+    /// events in transient analysis.
+    Relay {
+        coil_pos: usize,
+        coil_neg: usize,
+        contact_1: usize,
+        contact_2: usize,
+        coil_edge: usize,
+        contact_edge: usize,
+        params: RelayParams,
+    },
+    /// Thyristor, SCR or TRIAC (group2)
+    ///
+    /// The anode and cathode are MT2 and MT1 for a TRIAC, and the
+    /// current edge is the current from anode to cathode. The
+    /// thyristor is off in DC and AC analysis, and switches on and
+    /// off as events during transient analysis.
+    Thyristor {
+        anode: usize,
+        cathode: usize,
+        gate: usize,
+        current_edge: usize,
+        kind: ThyristorKind,
+        params: ThyristorParams,
+    },
+    /// IGBT (group2)
+    ///
+    /// The current edge is the collector current. The gate
+    /// capacitance is added as a plain capacitor when the circuit is
+    /// elaborated. The IGBT is off in DC and AC analysis, and
+    /// switches on and off as events during transient analysis.
+    Igbt {
+        collector: usize,
+        gate: usize,
+        emitter: usize,
+        current_edge: usize,
+        params: IgbtParams,
+    },
+    /// Bipolar junction transistor (group1, nonlinear)
+    ///
+    /// The series resistances and junction capacitances of the model
+    /// are added as plain resistors and capacitors when the circuit is
+    /// elaborated.
+    Bjt {
+        collector: usize,
+        base: usize,
+        emitter: usize,
+        model: BjtModel,
+    },
+    /// MOSFET (group1, nonlinear)
+    ///
+    /// The series resistances and gate overlap capacitances of the
+    /// model are added as plain resistors and capacitors when the
+    /// circuit is elaborated.
+    Mosfet {
+        drain: usize,
+        gate: usize,
+        source: usize,
+        model: MosfetModel,
+    },
+    /// Battery (group2)
+    ///
+    /// The open-circuit voltage at the state of charge (from 0 to 1)
+    /// in series with the internal resistance. The current edge is the
+    /// charging current, from term_pos through the battery to
+    /// term_neg. The state of charge is the initial one in transient
+    /// analysis, where it is integrated from the current, and the
+    /// battery is its internal resistance in AC analysis.
+    Battery {
+        term_pos: usize,
+        term_neg: usize,
+        current_edge: usize,
+        model: BatteryModel,
+        soc: f64,
+    },
+    /// Digital logic gate
+    ///
+    /// Reads and drives logic nets rather than analog nodes, so it
+    /// only takes part in transient analysis, where its output changes
+    /// the delay after its inputs.
+    LogicGate { gate: LogicGate, delay: f64 },
+    /// Analog-to-digital bridge
+    ///
+    /// Drives a logic net from the voltage from term_pos to term_neg,
+    /// which it does not load.
+    AdcBridge {
+        term_pos: usize,
+        term_neg: usize,
+        net: usize,
+        family: LogicFamily,
+    },
+    /// Digital-to-analog bridge (group2)
+    ///
+    /// The output level of a logic net (in series with the output
+    /// resistance) from term_pos to term_neg, with the current edge
+    /// from term_pos through the bridge to term_neg. The output is low
+    /// in DC analysis, and is its output resistance in AC analysis.
+    DacBridge {
+        term_pos: usize,
+        term_neg: usize,
+        current_edge: usize,
+        net: usize,
+        family: LogicFamily,
+    },
+    /// Independent voltage source (group2)
+    IndependentVoltageSource {
+        term_pos: usize,
+        term_neg: usize,
+        current_edge: usize,
+        voltage: f64,
+        ac: AcSpec,
+        /// Transient waveform (the DC value if there is none)
+        waveform: Option<Waveform>,
+    },
+    /// Current probe (group2)
+    ///
+    /// A zero volt source (ammeter) whose only purpose is to keep the
+    /// current through it in the solution. The current flows from
+    /// term_pos, through the probe, to term_neg.
+    CurrentProbe {
+        term_pos: usize,
+        term_neg: usize,
+        current_edge: usize,
+    },
+    /// Measurement probe (group2 for a current probe)
+    ///
+    /// A voltage probe (with no current edge) measures the voltage
+    /// from term_pos to term_neg, and is replaced by its input
+    /// resistance and capacitance between the terminals when the
+    /// circuit is elaborated. A current probe is a zero volt source
+    /// like a current probe element. The measured signal is seen
+    /// through the bandwidth and averaging of the probe in transient
+    /// analysis.
+    MeasurementProbe {
+        term_pos: usize,
+        term_neg: usize,
+        current_edge: Option<usize>,
+        params: ProbeParams,
+    },
+    /// Independent current source (group1)
+    ///
+    /// The current flows out of term_pos, through the source, and
+    /// into term_neg.
+    IndependentCurrentSource {
+        term_pos: usize,
+        term_neg: usize,
+        current: f64,
+        ac: AcSpec,
+        /// Transient waveform (the DC value if there is none)
+        waveform: Option<Waveform>,
+    },
+    /// Voltage-controlled voltage source (group2)
+    ///
+    /// The voltage from term_pos to term_neg is the gain times the
+    /// voltage from ctrl_pos to ctrl_neg, which draw no current.
+    VoltageControlledVoltageSource {
+        term_pos: usize,
+        term_neg: usize,
+        ctrl_pos: usize,
+        ctrl_neg: usize,
+        current_edge: usize,
+        gain: f64,
+    },
+    /// Voltage-controlled current source (group1)
+    ///
+    /// The current, which flows out of term_pos, through the source,
+    /// and into term_neg, is the transconductance times the voltage
+    /// from ctrl_pos to ctrl_neg, which draw no current.
+    VoltageControlledCurrentSource {
+        term_pos: usize,
+        term_neg: usize,
+        ctrl_pos: usize,
+        ctrl_neg: usize,
+        transconductance: f64,
+    },
+    /// Current-controlled current source (group1)
+    ///
+    /// The current, which flows out of term_pos, through the source,
+    /// and into term_neg, is the gain times the current of another
+    /// component's current edge.
+    CurrentControlledCurrentSource {
+        term_pos: usize,
+        term_neg: usize,
+        ctrl_edge: usize,
+        gain: f64,
+    },
+    /// Current-controlled voltage source (group2)
+    ///
+    /// The voltage from term_pos to term_neg is the transresistance
+    /// times the current of another component's current edge.
+    CurrentControlledVoltageSource {
+        term_pos: usize,
+        term_neg: usize,
+        ctrl_edge: usize,
+        current_edge: usize,
+        transresistance: f64,
+    },
+}
+
+impl Component {
+    /// The nodes the component is connected to
+    pub fn terminals(&self) -> Vec<usize> {
+        match *self {
+            Self::Resistor { term_1, term_2, .. }
+            | Self::Thermistor { term_1, term_2, .. }
+            | Self::SemiconductorResistor { term_1, term_2, .. }
+            | Self::Capacitor { term_1, term_2, .. }
+            | Self::SemiconductorCapacitor { term_1, term_2, .. }
+            | Self::Inductor { term_1, term_2, .. }
+            | Self::SaturableInductor { term_1, term_2, .. }
+            | Self::Crystal { term_1, term_2, .. }
+            | Self::Supercapacitor { term_1, term_2, .. }
+            | Self::Fuse { term_1, term_2, .. }
+            | Self::Table { term_1, term_2, .. } => vec![term_1, term_2],
+            Self::Diode { anode, cathode, .. }
+            | Self::Photodiode { anode, cathode, .. }
+            | Self::TunnelDiode { anode, cathode, .. }
+            | Self::SchottkyDiode { anode, cathode, .. }
+            | Self::Compact { anode, cathode, .. } => {
+                vec![anode, cathode]
+            }
+            Self::Urc {
+                term_1,
+                term_2,
+                term_cap,
+                ..
+            } => vec![term_1, term_2, term_cap],
+            Self::Thyristor {
+                anode,
+                cathode,
+                gate,
+                ..
+            } => vec![anode, cathode, gate],
+            Self::Igbt {
+                collector,
+                gate,
+                emitter,
+                ..
+            } => vec![collector, gate, emitter],
+            Self::Bjt {
+                collector,
+                base,
+                emitter,
+                ..
+            } => vec![collector, base, emitter],
+            Self::Mosfet {
+                drain,
+                gate,
+                source,
+                ..
+            } => vec![drain, gate, source],
+            Self::Relay {
+                coil_pos,
+                coil_neg,
+                contact_1,
+                contact_2,
+                ..
+            } => vec![coil_pos, coil_neg, contact_1, contact_2],
+            Self::NPort { ref ports, .. } => ports.iter().flat_map(|(p, n)| [*p, *n]).collect(),
+            Self::LogicGate { .. } => Vec::new(),
+            Self::IndependentVoltageSource {
+                term_pos, term_neg, ..
+            }
+            | Self::CurrentProbe {
+                term_pos, term_neg, ..
+            }
+            | Self::MeasurementProbe {
+                term_pos, term_neg, ..
+            }
+            | Self::Battery {
+                term_pos, term_neg, ..
+            }
+            | Self::AdcBridge {
+                term_pos, term_neg, ..
+            }
+            | Self::DacBridge {
+                term_pos, term_neg, ..
+            }
+            | Self::IndependentCurrentSource {
+                term_pos, term_neg, ..
+            }
+            | Self::CurrentControlledCurrentSource {
+                term_pos, term_neg, ..
+            }
+            | Self::CurrentControlledVoltageSource {
+                term_pos, term_neg, ..
+            } => {
+                vec![term_pos, term_neg]
+            }
+            Self::VoltageControlledVoltageSource {
+                term_pos,
+                term_neg,
+                ctrl_pos,
+                ctrl_neg,
+                ..
+            }
+            | Self::VoltageControlledCurrentSource {
+                term_pos,
+                term_neg,
+                ctrl_pos,
+                ctrl_neg,
+                ..
+            } => vec![term_pos, term_neg, ctrl_pos, ctrl_neg],
+        }
+    }
+
+    /// Return the current edge, if this element has a current (for a
+    /// relay, which has two, this is the contact current)
+    pub fn current_edge(&self) -> Option<usize> {
+        match *self {
+            Self::Resistor { current_edge, .. }
+            | Self::Thermistor { current_edge, .. }
+            | Self::SemiconductorResistor { current_edge, .. }
+            | Self::MeasurementProbe { current_edge, .. } => current_edge,
+            Self::Inductor { current_edge, .. }
+            | Self::SaturableInductor { current_edge, .. }
+            | Self::Fuse { current_edge, .. }
+            | Self::Relay {
+                contact_edge: current_edge,
+                ..
+            }
+            | Self::Thyristor { current_edge, .. }
+            | Self::Igbt { current_edge, .. }
+            | Self::Battery { current_edge, .. }
+            | Self::DacBridge { current_edge, .. }
+            | Self::IndependentVoltageSource { current_edge, .. }
+            | Self::CurrentProbe { current_edge, .. }
+            | Self::VoltageControlledVoltageSource { current_edge, .. }
+            | Self::CurrentControlledVoltageSource { current_edge, .. } => Some(current_edge),
+            Self::Capacitor { .. }
+            | Self::SemiconductorCapacitor { .. }
+            | Self::Crystal { .. }
+            | Self::Supercapacitor { .. }
+            | Self::Urc { .. }
+            | Self::Diode { .. }
+            | Self::Photodiode { .. }
+            | Self::TunnelDiode { .. }
+            | Self::SchottkyDiode { .. }
+            | Self::Table { .. }
+            | Self::Compact { .. }
+            | Self::Bjt { .. }
+            | Self::Mosfet { .. }
+            | Self::NPort { .. }
+            | Self::LogicGate { .. }
+            | Self::AdcBridge { .. }
+            | Self::IndependentCurrentSource { .. }
+            | Self::VoltageControlledCurrentSource { .. }
+            | Self::CurrentControlledCurrentSource { .. } => None,
+        }
+    }
+
+    /// All the current edges of this element
+    pub fn current_edges(&self) -> Vec<usize> {
+        match *self {
+            Self::Relay {
+                coil_edge,
+                contact_edge,
+                ..
+            } => vec![coil_edge, contact_edge],
+            _ => self.current_edge().into_iter().collect(),
+        }
+    }
+
+    /// Mutable reference to the current edge, if this element has a
+    /// current
+    pub(crate) fn current_edge_mut(&mut self) -> Option<&mut usize> {
+        match self {
+            Self::Resistor { current_edge, .. }
+            | Self::Thermistor { current_edge, .. }
+            | Self::SemiconductorResistor { current_edge, .. }
+            | Self::MeasurementProbe { current_edge, .. } => current_edge.as_mut(),
+            Self::Inductor { current_edge, .. }
+            | Self::SaturableInductor { current_edge, .. }
+            | Self::Fuse { current_edge, .. }
+            | Self::Relay {
+                contact_edge: current_edge,
+                ..
+            }
+            | Self::Thyristor { current_edge, .. }
+            | Self::Igbt { current_edge, .. }
+            | Self::Battery { current_edge, .. }
+            | Self::DacBridge { current_edge, .. }
+            | Self::IndependentVoltageSource { current_edge, .. }
+            | Self::CurrentProbe { current_edge, .. }
+            | Self::VoltageControlledVoltageSource { current_edge, .. }
+            | Self::CurrentControlledVoltageSource { current_edge, .. } => Some(current_edge),
+            Self::Capacitor { .. }
+            | Self::SemiconductorCapacitor { .. }
+            | Self::Crystal { .. }
+            | Self::Supercapacitor { .. }
+            | Self::Urc { .. }
+            | Self::Diode { .. }
+            | Self::Photodiode { .. }
+            | Self::TunnelDiode { .. }
+            | Self::SchottkyDiode { .. }
+            | Self::Table { .. }
+            | Self::Compact { .. }
+            | Self::Bjt { .. }
+            | Self::Mosfet { .. }
+            | Self::NPort { .. }
+            | Self::LogicGate { .. }
+            | Self::AdcBridge { .. }
+            | Self::IndependentCurrentSource { .. }
+            | Self::VoltageControlledCurrentSource { .. }
+            | Self::CurrentControlledCurrentSource { .. } => None,
+        }
+    }
+
+    /// Mutable references to all the current edges of this element (in
+    /// the order of [Component::current_edges])
+    pub(crate) fn current_edges_mut(&mut self) -> Vec<&mut usize> {
+        match self {
+            Self::Relay {
+                coil_edge,
+                contact_edge,
+                ..
+            } => vec![coil_edge, contact_edge],
+            _ => self.current_edge_mut().into_iter().collect(),
+        }
+    }
+
+    /// Mutable reference to the current edge that controls this
+    /// element, if it is a current-controlled source
+    pub(crate) fn ctrl_edge_mut(&mut self) -> Option<&mut usize> {
+        match self {
+            Self::CurrentControlledCurrentSource { ctrl_edge, .. }
+            | Self::CurrentControlledVoltageSource { ctrl_edge, .. } => Some(ctrl_edge),
+            _ => None,
+        }
+    }
+
+    /// The anode, cathode and model of the junction, if this element
+    /// has one
+    pub fn junction(&self) -> Option<(usize, usize, &dyn Junction)> {
+        match self {
+            Self::Diode {
+                anode,
+                cathode,
+                model,
+            }
+            | Self::Photodiode {
+                anode,
+                cathode,
+                model,
+                ..
+            } => Some((*anode, *cathode, model)),
+            Self::TunnelDiode {
+                anode,
+                cathode,
+                model,
+            } => Some((*anode, *cathode, model)),
+            Self::SchottkyDiode {
+                anode,
+                cathode,
+                model,
+            } => Some((*anode, *cathode, model)),
+            Self::Table {
+                term_1,
+                term_2,
+                model,
+            } => Some((*term_1, *term_2, model)),
+            Self::Compact {
+                anode,
+                cathode,
+                model,
+            } => Some((*anode, *cathode, model)),
+            _ => None,
+        }
+    }
+
+    /// The nodes of the terminals (as numbered by the model) and the
+    /// model of the transistor, if this element is one
+    pub fn transistor(&self) -> Option<([usize; 3], &dyn Transistor)> {
+        match self {
+            Self::Bjt {
+                collector,
+                base,
+                emitter,
+                model,
+            } => Some(([*collector, *base, *emitter], model)),
+            Self::Mosfet {
+                drain,
+                gate,
+                source,
+                model,
+            } => Some(([*drain, *gate, *source], model)),
+            _ => None,
+        }
+    }
+
+    /// A parameter of the model of the component by name, if it has a
+    /// model with the parameter
+    pub fn model_parameter_mut(&mut self, name: &str) -> Option<&mut f64> {
+        match self {
+            Self::Diode { model, .. } | Self::Photodiode { model, .. } => model.parameter_mut(name),
+            Self::TunnelDiode { model, .. } => model.parameter_mut(name),
+            Self::SchottkyDiode { model, .. } => model.parameter_mut(name),
+            Self::Bjt { model, .. } => model.parameter_mut(name),
+            Self::Mosfet { model, .. } => model.parameter_mut(name),
+            _ => None,
+        }
+    }
+}
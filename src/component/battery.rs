@@ -0,0 +1,56 @@
+//! Battery model
+//!
+//! The battery is its open-circuit voltage (OCV) in series with its
+//! internal resistance. The OCV is looked up from the state of charge
+//! (SOC), which is the fraction of the capacity that is stored. In
+//! transient analysis, the SOC is integrated from the battery current.
+
+use crate::waveform::pwl_value;
+
+/// Typical OCV of a lithium-ion cell, as (SOC, voltage) points
+const LITHIUM_ION_OCV: [(f64, f64); 9] = [
+    (0.0, 3.0),
+    (0.05, 3.4),
+    (0.1, 3.5),
+    (0.2, 3.6),
+    (0.4, 3.7),
+    (0.6, 3.8),
+    (0.8, 3.95),
+    (0.9, 4.05),
+    (1.0, 4.2),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatteryModel {
+    /// Charge stored when full (coulombs, which is 3600 times the
+    /// capacity in amp-hours)
+    pub capacity: f64,
+    /// Internal resistance
+    pub r_int: f64,
+    /// Open-circuit voltage as (SOC, voltage) points sorted by SOC,
+    /// interpolated linearly
+    pub ocv: Vec<(f64, f64)>,
+}
+
+impl BatteryModel {
+    /// A single lithium-ion cell, from its capacity in amp-hours
+    pub fn lithium_ion(capacity_ah: f64) -> Self {
+        Self {
+            capacity: 3600.0 * capacity_ah,
+            r_int: 0.05,
+            ocv: LITHIUM_ION_OCV.to_vec(),
+        }
+    }
+
+    /// Open-circuit voltage at a state of charge
+    pub fn open_circuit_voltage(&self, soc: f64) -> f64 {
+        pwl_value(&self.ocv, soc)
+    }
+
+    /// State of charge after a charging current (negative when
+    /// discharging) has flowed for a time, limited to between empty
+    /// and full
+    pub fn integrate(&self, soc: f64, current: f64, time: f64) -> f64 {
+        (soc + current * time / self.capacity).clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,239 @@
+//! Bipolar junction transistor model
+//!
+//! The transistor follows the transport form of the Ebers-Moll model,
+//! as the SPICE Gummel-Poon model does without high-level injection:
+//! the forward and reverse diffusion currents
+//! $I_F = I_s (e^{V_{BE} / n_f V_t} - 1)$ and
+//! $I_R = I_s (e^{V_{BC} / n_r V_t} - 1)$ give a collector-emitter
+//! transport current $(I_F - I_R)(1 - V_{BC} / V_{AF})$, reduced by the
+//! Early effect, and base currents $I_F / \beta_F$ (to the emitter)
+//! and $I_R / \beta_R$ (to the collector). The saturation current
+//! follows the temperature as in SPICE:
+//! $I_s(T) = I_s (T/T_{nom})^{X_{ti}} e^{(T/T_{nom} - 1) E_g / V_t}$.
+//! A transistor is at the temperature of the circuit unless it has its
+//! own.
+//!
+//! The base-emitter and base-collector voltages are limited as pn
+//! junctions (see [super::pnjlim]). The series resistances and the
+//! (constant) junction capacitances are added as plain resistors and
+//! capacitors when the circuit is elaborated.
+
+use super::diode::{kelvin, thermal_voltage, GMIN, NOMINAL_TEMPERATURE};
+use super::limiting::{limexp, pnjlim};
+use super::transistor::{Polarity, Transistor, TransistorCurrent};
+
+/// Terminals of a bipolar transistor
+const COLLECTOR: usize = 0;
+const BASE: usize = 1;
+const EMITTER: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BjtModel {
+    /// NPN or PNP
+    pub polarity: Polarity,
+    /// Transport saturation current
+    pub is: f64,
+    /// Forward current gain
+    pub bf: f64,
+    /// Reverse current gain
+    pub br: f64,
+    /// Forward emission coefficient
+    pub nf: f64,
+    /// Reverse emission coefficient
+    pub nr: f64,
+    /// Forward Early voltage (zero for none)
+    pub vaf: f64,
+    /// Base resistance
+    pub rb: f64,
+    /// Collector resistance
+    pub rc: f64,
+    /// Emitter resistance
+    pub re: f64,
+    /// Base-emitter capacitance
+    pub cje: f64,
+    /// Base-collector capacitance
+    pub cjc: f64,
+    /// Energy gap (eV) of the temperature dependence of `is`
+    pub eg: f64,
+    /// Exponent of the temperature dependence of `is`
+    pub xti: f64,
+    /// Temperature the parameters are given at (degrees Celsius)
+    pub tnom: f64,
+    /// Temperature of the instance using the model (degrees Celsius),
+    /// if it has its own, as for [super::DiodeModel::temp]
+    pub temp: Option<f64>,
+}
+
+impl Default for BjtModel {
+    /// The SPICE defaults of an NPN transistor
+    fn default() -> Self {
+        Self {
+            polarity: Polarity::N,
+            is: 1e-16,
+            bf: 100.0,
+            br: 1.0,
+            nf: 1.0,
+            nr: 1.0,
+            vaf: 0.0,
+            rb: 0.0,
+            rc: 0.0,
+            re: 0.0,
+            cje: 0.0,
+            cjc: 0.0,
+            eg: 1.11,
+            xti: 3.0,
+            tnom: NOMINAL_TEMPERATURE,
+            temp: None,
+        }
+    }
+}
+
+impl BjtModel {
+    /// The temperature of the transistor (degrees Celsius)
+    pub fn temperature(&self) -> f64 {
+        self.temp.unwrap_or(NOMINAL_TEMPERATURE)
+    }
+
+    /// The saturation current at the temperature of the transistor
+    fn is(&self) -> f64 {
+        let ratio = kelvin(self.temperature()) / kelvin(self.tnom);
+        if ratio == 1.0 {
+            return self.is;
+        }
+        let vt = thermal_voltage(self.temperature());
+        self.is * ratio.powf(self.xti) * ((ratio - 1.0) * self.eg / vt).exp()
+    }
+
+    /// The diffusion current and its derivative of a junction at a
+    /// voltage, with an emission coefficient
+    fn diffusion(&self, voltage: f64, n: f64) -> (f64, f64) {
+        let vt = n * thermal_voltage(self.temperature());
+        let is = self.is();
+        let (e, de) = limexp(voltage / vt);
+        (is * (e - 1.0) + GMIN * voltage, is * de / vt + GMIN)
+    }
+
+    /// A parameter by its SPICE name (in any case), if there is one
+    pub fn parameter_mut(&mut self, name: &str) -> Option<&mut f64> {
+        match name.to_ascii_lowercase().as_str() {
+            "is" => Some(&mut self.is),
+            "bf" => Some(&mut self.bf),
+            "br" => Some(&mut self.br),
+            "nf" => Some(&mut self.nf),
+            "nr" => Some(&mut self.nr),
+            "vaf" | "va" => Some(&mut self.vaf),
+            "rb" => Some(&mut self.rb),
+            "rc" => Some(&mut self.rc),
+            "re" => Some(&mut self.re),
+            "cje" => Some(&mut self.cje),
+            "cjc" => Some(&mut self.cjc),
+            "eg" => Some(&mut self.eg),
+            "xti" => Some(&mut self.xti),
+            "tnom" => Some(&mut self.tnom),
+            _ => None,
+        }
+    }
+}
+
+impl Transistor for BjtModel {
+    /// The base-emitter and base-collector voltages
+    fn controls(&self) -> [(usize, usize); 2] {
+        [(BASE, EMITTER), (BASE, COLLECTOR)]
+    }
+
+    fn limit(&self, new: [f64; 2], old: [f64; 2]) -> [f64; 2] {
+        let sign = self.polarity.sign();
+        let is = self.is();
+        let vt = thermal_voltage(self.temperature());
+        let limit = |new: f64, old: f64, n: f64| sign * pnjlim(n * vt, is, sign * new, sign * old);
+        [
+            limit(new[0], old[0], self.nf),
+            limit(new[1], old[1], self.nr),
+        ]
+    }
+
+    fn currents(&self, voltages: [f64; 2]) -> Vec<TransistorCurrent> {
+        // A PNP transistor is an NPN one with every voltage and current
+        // reversed, so the derivatives are the same
+        let sign = self.polarity.sign();
+        let (vbe, vbc) = (sign * voltages[0], sign * voltages[1]);
+        let (i_f, g_f) = self.diffusion(vbe, self.nf);
+        let (i_r, g_r) = self.diffusion(vbc, self.nr);
+        let (early, d_early) = if self.vaf > 0.0 {
+            (1.0 - vbc / self.vaf, -1.0 / self.vaf)
+        } else {
+            (1.0, 0.0)
+        };
+        vec![
+            TransistorCurrent {
+                terminals: (COLLECTOR, EMITTER),
+                current: sign * (i_f - i_r) * early,
+                derivatives: [g_f * early, -g_r * early + (i_f - i_r) * d_early],
+            },
+            TransistorCurrent {
+                terminals: (BASE, EMITTER),
+                current: sign * i_f / self.bf,
+                derivatives: [g_f / self.bf, 0.0],
+            },
+            TransistorCurrent {
+                terminals: (BASE, COLLECTOR),
+                current: sign * i_r / self.br,
+                derivatives: [0.0, g_r / self.br],
+            },
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::THERMAL_VOLTAGE;
+
+    #[test]
+    fn forward_active_currents_follow_the_gain() {
+        let model = BjtModel::default();
+        let currents = model.currents([0.7, -5.0]);
+        let (collector, base) = (currents[0].current, currents[1].current);
+        assert!((collector / base / model.bf - 1.0).abs() < 1e-4);
+        // The collector current is the saturation current times the
+        // exponential of the base-emitter voltage
+        let expected = model.is * (0.7 / THERMAL_VOLTAGE).exp();
+        assert!((collector / expected - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn derivatives_match_the_currents() {
+        let model = BjtModel {
+            vaf: 50.0,
+            ..Default::default()
+        };
+        for polarity in [Polarity::N, Polarity::P] {
+            let model = BjtModel { polarity, ..model };
+            let sign = polarity.sign();
+            let voltages = [sign * 0.65, sign * -2.0];
+            let currents = model.currents(voltages);
+            for control in 0..2 {
+                let mut moved = voltages;
+                moved[control] += 1e-7;
+                for (at, next) in currents.iter().zip(model.currents(moved)) {
+                    let numeric = (next.current - at.current) / 1e-7;
+                    let analytic = at.derivatives[control];
+                    assert!((numeric - analytic).abs() <= 1e-4 * analytic.abs() + 1e-12);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pnp_currents_are_reversed() {
+        let npn = BjtModel::default();
+        let pnp = BjtModel {
+            polarity: Polarity::P,
+            ..npn
+        };
+        let (n, p) = (npn.currents([0.7, -1.0]), pnp.currents([-0.7, 1.0]));
+        for (n, p) in n.iter().zip(&p) {
+            assert_eq!(n.current, -p.current);
+        }
+    }
+}
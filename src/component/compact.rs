@@ -0,0 +1,63 @@
+//! Externally defined compact models
+//!
+//! A compact model is a two-terminal junction whose characteristic is
+//! computed outside the circuit, such as a Verilog-A model compiled by
+//! OpenVAF and loaded through its OSDI interface (see [crate::osdi]),
+//! or one compiled from a subset of Verilog-A by this crate (see
+//! [crate::veriloga]). The model is shared between copies of the
+//! component. A capacitance of the model is added in parallel with the
+//! junction when the circuit is elaborated.
+
+use std::fmt;
+use std::sync::Arc;
+
+use super::Junction;
+
+#[derive(Clone)]
+pub struct CompactModel {
+    /// Name of the model, for reports
+    pub name: String,
+    junction: Arc<dyn Junction + Send + Sync>,
+}
+
+impl CompactModel {
+    pub fn new(name: &str, junction: impl Junction + Send + Sync + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            junction: Arc::new(junction),
+        }
+    }
+}
+
+impl fmt::Debug for CompactModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CompactModel")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Two compact models are equal if they are copies of the same model
+impl PartialEq for CompactModel {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.junction, &other.junction)
+    }
+}
+
+impl Junction for CompactModel {
+    fn evaluate(&self, voltage: f64) -> (f64, f64) {
+        self.junction.evaluate(voltage)
+    }
+
+    fn limit_voltage(&self, new: f64, old: f64) -> f64 {
+        self.junction.limit_voltage(new, old)
+    }
+
+    fn noise(&self, current: f64, frequency: f64) -> f64 {
+        self.junction.noise(current, frequency)
+    }
+
+    fn capacitance(&self) -> f64 {
+        self.junction.capacitance()
+    }
+}
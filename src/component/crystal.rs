@@ -0,0 +1,85 @@
+//! Quartz crystal parameters
+//!
+//! The crystal is modelled by the standard Butterworth-Van Dyke
+//! equivalent circuit: a motional branch (series Rm, Lm and Cm) in
+//! parallel with the shunt (holder) capacitance C0. The motional
+//! capacitance is given by the datasheet, or else from the ratio
+//! C0/Cm, which is 250 for a typical crystal unless it is given.
+
+use std::f64::consts::PI;
+
+/// Typical ratio C0/Cm between the shunt and motional capacitances of
+/// a fundamental-mode AT-cut crystal
+pub const DEFAULT_CAPACITANCE_RATIO: f64 = 250.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrystalParams {
+    /// Series resonant frequency (Hz)
+    pub fs: f64,
+    /// Quality factor of the motional branch
+    pub q: f64,
+    /// Shunt capacitance
+    pub c0: f64,
+    /// Motional capacitance
+    pub cm: f64,
+}
+
+impl CrystalParams {
+    /// Make crystal parameters from the series resonant frequency, the
+    /// quality factor and the shunt capacitance. The motional
+    /// capacitance is set from the typical C0/Cm ratio
+    /// ([DEFAULT_CAPACITANCE_RATIO], 250), unless it is given (see
+    /// [CrystalParams::with_motional_capacitance] and
+    /// [CrystalParams::with_capacitance_ratio]).
+    pub fn new(fs: f64, q: f64, c0: f64) -> Self {
+        Self {
+            fs,
+            q,
+            c0,
+            cm: c0 / DEFAULT_CAPACITANCE_RATIO,
+        }
+    }
+
+    /// The parameters with the motional capacitance from the datasheet
+    pub fn with_motional_capacitance(self, cm: f64) -> Self {
+        Self { cm, ..self }
+    }
+
+    /// The parameters with the motional capacitance set from a ratio
+    /// C0/Cm other than the typical one
+    pub fn with_capacitance_ratio(self, ratio: f64) -> Self {
+        Self {
+            cm: self.c0 / ratio,
+            ..self
+        }
+    }
+
+    pub fn motional_inductance(&self) -> f64 {
+        1.0 / ((2.0 * PI * self.fs).powi(2) * self.cm)
+    }
+
+    pub fn motional_resistance(&self) -> f64 {
+        2.0 * PI * self.fs * self.motional_inductance() / self.q
+    }
+
+    /// Parallel (anti-)resonant frequency (Hz)
+    pub fn parallel_resonance(&self) -> f64 {
+        self.fs * (1.0 + self.cm / self.c0).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_motional_capacitance_can_be_given() {
+        let params = CrystalParams::new(10e6, 1e5, 5e-12);
+        assert_eq!(params.cm, 5e-12 / DEFAULT_CAPACITANCE_RATIO);
+        assert_eq!(params.with_motional_capacitance(25e-15).cm, 25e-15);
+        let ratio = params.with_capacitance_ratio(400.0);
+        assert_eq!(ratio.cm, 5e-12 / 400.0);
+        let expected = 10e6 * (1.0 + 1.0 / 400.0_f64).sqrt();
+        assert!((ratio.parallel_resonance() - expected).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,111 @@
+//! Digital primitives and analog/digital bridges
+//!
+//! Logic gates read and drive logic nets, which are numbered from 0
+//! separately from the analog nodes, and which are either high or
+//! low. Gates have no analog terminals; they are connected to the
+//! analog circuit through bridges. An ADC bridge drives a logic net
+//! from an analog voltage, with the input thresholds of a logic family
+//! giving hysteresis, and a DAC bridge drives an analog node from a
+//! logic net, as the output level of the family behind its output
+//! resistance.
+
+/// Input thresholds and output levels of a logic family
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogicFamily {
+    /// Highest input voltage read as low
+    pub vil: f64,
+    /// Lowest input voltage read as high
+    pub vih: f64,
+    /// Output voltage when low
+    pub v_low: f64,
+    /// Output voltage when high
+    pub v_high: f64,
+    /// Output resistance
+    pub r_out: f64,
+}
+
+impl Default for LogicFamily {
+    /// 5 V CMOS
+    fn default() -> Self {
+        Self {
+            vil: 1.5,
+            vih: 3.5,
+            v_low: 0.0,
+            v_high: 5.0,
+            r_out: 100.0,
+        }
+    }
+}
+
+impl LogicFamily {
+    /// Logic level read from an input voltage, given the previous
+    /// level (which is kept between the thresholds)
+    pub fn input_level(&self, level: bool, voltage: f64) -> bool {
+        if voltage >= self.vih {
+            true
+        } else if voltage <= self.vil {
+            false
+        } else {
+            level
+        }
+    }
+
+    /// Output voltage at a logic level
+    pub fn output_voltage(&self, level: bool) -> f64 {
+        if level {
+            self.v_high
+        } else {
+            self.v_low
+        }
+    }
+}
+
+/// A logic function, with the logic nets it reads and drives
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogicGate {
+    Inverter {
+        input: usize,
+        output: usize,
+    },
+    Nand {
+        inputs: Vec<usize>,
+        output: usize,
+    },
+    /// D flip-flop, which copies d to q on the rising edge of the
+    /// clock
+    Dff {
+        d: usize,
+        clock: usize,
+        q: usize,
+    },
+}
+
+impl LogicGate {
+    /// Logic nets read by the gate
+    pub fn inputs(&self) -> Vec<usize> {
+        match self {
+            Self::Inverter { input, .. } => vec![*input],
+            Self::Nand { inputs, .. } => inputs.clone(),
+            Self::Dff { d, clock, .. } => vec![*d, *clock],
+        }
+    }
+
+    /// Logic net driven by the gate
+    pub fn output(&self) -> usize {
+        match *self {
+            Self::Inverter { output, .. } | Self::Nand { output, .. } => output,
+            Self::Dff { q, .. } => q,
+        }
+    }
+
+    /// New output level from the levels of the logic nets, and the
+    /// clock level before the change (for a flip-flop), or None if the
+    /// output does not change
+    pub fn evaluate(&self, nets: &[bool], clock_before: bool) -> Option<bool> {
+        match self {
+            Self::Inverter { input, .. } => Some(!nets[*input]),
+            Self::Nand { inputs, .. } => Some(!inputs.iter().all(|net| nets[*net])),
+            Self::Dff { d, clock, .. } => (nets[*clock] && !clock_before).then_some(nets[*d]),
+        }
+    }
+}
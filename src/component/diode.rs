@@ -0,0 +1,140 @@
+//! Junction diode model
+//!
+//! The junction follows the Shockley equation
+//! $I = I_s (e^{V / n V_t} - 1)$, with a series resistance that is
+//! added as a plain resistor when the circuit is elaborated. The
+//! thermal voltage $V_t = kT/q$ is taken at the temperature $T$ of the
+//! diode, and the saturation current, given at the nominal temperature
+//! $T_{nom}$, follows it as in SPICE:
+//! $I_s(T) = I_s (T/T_{nom})^{X_{ti}/n} e^{(T/T_{nom} - 1) E_g / n V_t}$.
+//! A diode is at the temperature of the circuit (see
+//! [crate::Circuit::temperature]) unless it has its own.
+//!
+//! In noise analysis, the junction has shot noise, and flicker noise
+//! $K_f I^{A_f} / f$ as in SPICE.
+//!
+//! A photodiode (or solar cell) is a diode with a photocurrent in
+//! parallel with the junction, flowing from the cathode to the anode,
+//! which is proportional to the irradiance.
+
+use super::limiting::{limexp, pnjlim};
+use super::Junction;
+
+/// Thermal voltage kT/q at 27 degrees Celsius
+pub const THERMAL_VOLTAGE: f64 = 0.025852;
+
+/// The temperature (degrees Celsius) models are given at, unless they
+/// say otherwise, and circuits are at, unless they are set otherwise
+pub const NOMINAL_TEMPERATURE: f64 = 27.0;
+
+/// Thermal voltage at a temperature (degrees Celsius), in proportion
+/// to [THERMAL_VOLTAGE] at the nominal temperature
+pub fn thermal_voltage(temperature: f64) -> f64 {
+    THERMAL_VOLTAGE * kelvin(temperature) / kelvin(NOMINAL_TEMPERATURE)
+}
+
+pub(super) fn kelvin(celsius: f64) -> f64 {
+    celsius + 273.15
+}
+
+/// Elementary charge (C)
+pub const ELECTRON_CHARGE: f64 = 1.602176634e-19;
+
+/// Conductance in parallel with each junction, which keeps the
+/// matrix non-singular when the junction is strongly reverse biased
+pub(crate) const GMIN: f64 = 1e-12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiodeModel {
+    /// Saturation current
+    pub is: f64,
+    /// Emission coefficient
+    pub n: f64,
+    /// Series resistance
+    pub rs: f64,
+    /// Flicker noise coefficient
+    pub kf: f64,
+    /// Flicker noise exponent
+    pub af: f64,
+    /// Energy gap (eV) of the temperature dependence of `is`
+    pub eg: f64,
+    /// Exponent of the temperature dependence of `is`
+    pub xti: f64,
+    /// Temperature the parameters are given at (degrees Celsius)
+    pub tnom: f64,
+    /// Temperature of the instance using the model (degrees Celsius),
+    /// if it has its own, as set by `temp=` on its netlist line. This
+    /// is not a parameter of the card, and is kept when the card of an
+    /// instance changes. When the circuit is elaborated, it is set to
+    /// the temperature of the circuit if there is none.
+    pub temp: Option<f64>,
+}
+
+impl Default for DiodeModel {
+    fn default() -> Self {
+        Self {
+            is: 1e-14,
+            n: 1.0,
+            rs: 0.0,
+            kf: 0.0,
+            af: 1.0,
+            eg: 1.11,
+            xti: 3.0,
+            tnom: NOMINAL_TEMPERATURE,
+            temp: None,
+        }
+    }
+}
+
+impl DiodeModel {
+    /// The temperature of the diode (degrees Celsius)
+    pub fn temperature(&self) -> f64 {
+        self.temp.unwrap_or(NOMINAL_TEMPERATURE)
+    }
+
+    fn vt(&self) -> f64 {
+        self.n * thermal_voltage(self.temperature())
+    }
+
+    /// The saturation current at the temperature of the diode
+    fn is(&self) -> f64 {
+        let ratio = kelvin(self.temperature()) / kelvin(self.tnom);
+        if ratio == 1.0 {
+            return self.is;
+        }
+        self.is * ratio.powf(self.xti / self.n) * ((ratio - 1.0) * self.eg / self.vt()).exp()
+    }
+
+    /// A parameter by its SPICE name (in any case), if there is one
+    pub fn parameter_mut(&mut self, name: &str) -> Option<&mut f64> {
+        match name.to_ascii_lowercase().as_str() {
+            "is" => Some(&mut self.is),
+            "n" => Some(&mut self.n),
+            "rs" => Some(&mut self.rs),
+            "kf" => Some(&mut self.kf),
+            "af" => Some(&mut self.af),
+            "eg" => Some(&mut self.eg),
+            "xti" => Some(&mut self.xti),
+            "tnom" => Some(&mut self.tnom),
+            _ => None,
+        }
+    }
+}
+
+impl Junction for DiodeModel {
+    fn evaluate(&self, voltage: f64) -> (f64, f64) {
+        let (is, vt) = (self.is(), self.vt());
+        let (e, de) = limexp(voltage / vt);
+        (is * (e - 1.0) + GMIN * voltage, is * de / vt + GMIN)
+    }
+
+    /// The SPICE pnjlim algorithm, which stops the exponential from
+    /// overflowing
+    fn limit_voltage(&self, new: f64, old: f64) -> f64 {
+        pnjlim(self.vt(), self.is(), new, old)
+    }
+
+    fn noise(&self, current: f64, frequency: f64) -> f64 {
+        2.0 * ELECTRON_CHARGE * current.abs() + self.kf * current.abs().powf(self.af) / frequency
+    }
+}
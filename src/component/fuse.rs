@@ -0,0 +1,37 @@
+//! Fuse (or circuit breaker) model
+//!
+//! The fuse is a small resistance until the integral of the square
+//! of its current (i²t) reaches the rating, and then it opens to a
+//! large resistance. It does not close again. The heating is not
+//! modelled beyond i²t, so there is no cooling between overloads.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FuseParams {
+    /// Melting (or trip) i²t (A²s)
+    pub i2t: f64,
+    /// Resistance when intact
+    pub r_on: f64,
+    /// Resistance when open
+    pub r_off: f64,
+}
+
+impl FuseParams {
+    /// Fuse with an i²t rating, and typical intact and open
+    /// resistances
+    pub fn new(i2t: f64) -> Self {
+        Self {
+            i2t,
+            r_on: 0.01,
+            r_off: 1e9,
+        }
+    }
+
+    /// Resistance when intact or open
+    pub fn resistance(&self, open: bool) -> f64 {
+        if open {
+            self.r_off
+        } else {
+            self.r_on
+        }
+    }
+}
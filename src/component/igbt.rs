@@ -0,0 +1,56 @@
+//! IGBT behavioral model
+//!
+//! The collector to emitter path is a switch, with a small on
+//! resistance and a large off resistance, which is on while the gate
+//! to emitter voltage is above the threshold. The gate is the input
+//! capacitance to the emitter.
+//!
+//! At turn-off, the stored charge in the drift region of a real IGBT
+//! keeps a tail current flowing after the MOSFET channel has closed.
+//! This is approximated by a current that starts at a fraction of the
+//! current before turn-off and decays exponentially.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IgbtParams {
+    /// Gate threshold voltage
+    pub vth: f64,
+    /// Collector to emitter resistance when on
+    pub r_on: f64,
+    /// Collector to emitter resistance when off
+    pub r_off: f64,
+    /// Gate to emitter capacitance
+    pub c_ge: f64,
+    /// Tail current at turn-off, as a fraction of the on current
+    pub tail_fraction: f64,
+    /// Time constant of the tail current decay
+    pub tail_time: f64,
+}
+
+impl Default for IgbtParams {
+    fn default() -> Self {
+        Self {
+            vth: 5.5,
+            r_on: 0.05,
+            r_off: 1e8,
+            c_ge: 2e-9,
+            tail_fraction: 0.2,
+            tail_time: 1e-6,
+        }
+    }
+}
+
+impl IgbtParams {
+    /// Collector to emitter resistance in the on or off state
+    pub fn resistance(&self, on: bool) -> f64 {
+        if on {
+            self.r_on
+        } else {
+            self.r_off
+        }
+    }
+
+    /// Tail current a time after turn-off from a collector current
+    pub fn tail_current(&self, current: f64, time: f64) -> f64 {
+        self.tail_fraction * current * (-time / self.tail_time).exp()
+    }
+}
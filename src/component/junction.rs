@@ -0,0 +1,39 @@
+//! Nonlinear junctions
+//!
+//! A junction is a two-terminal element whose current is a nonlinear
+//! function of the voltage across it. Circuits with junctions are
+//! solved by Newton iteration, with each junction replaced by its
+//! conductance and an equivalent current source at the junction
+//! voltage of the previous iteration.
+
+use super::diode::ELECTRON_CHARGE;
+
+pub trait Junction {
+    /// Junction current and conductance at a junction voltage
+    fn evaluate(&self, voltage: f64) -> (f64, f64);
+
+    /// Limit the change in junction voltage between Newton iterations,
+    /// so that the iteration does not diverge from a poor guess
+    fn limit_voltage(&self, new: f64, old: f64) -> f64;
+
+    /// Conductance and equivalent current of the junction linearised
+    /// at a voltage, so that the current near the voltage is
+    /// approximately conductance * v + current
+    fn linearise(&self, voltage: f64) -> (f64, f64) {
+        let (current, conductance) = self.evaluate(voltage);
+        (conductance, current - conductance * voltage)
+    }
+
+    /// Spectral density (in A²/Hz) of the noise current of the
+    /// junction at a (DC) junction current and a frequency, which is
+    /// the shot noise unless the model adds more
+    fn noise(&self, current: f64, _frequency: f64) -> f64 {
+        2.0 * ELECTRON_CHARGE * current.abs()
+    }
+
+    /// Capacitance (F) in parallel with the junction, which is none
+    /// unless the model has one
+    fn capacitance(&self) -> f64 {
+        0.0
+    }
+}
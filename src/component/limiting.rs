@@ -0,0 +1,175 @@
+//! Device voltage limiting
+//!
+//! Newton iteration from a poor guess can give a device a voltage far
+//! from the solution, at which an exponential overflows, or the
+//! linearisation points the next iteration further away. These are
+//! the SPICE helpers that limit the change in a device voltage from
+//! one iteration to the next, given the voltage of the last: [pnjlim]
+//! for pn junctions (as in diodes and bipolar transistors), which is
+//! for the [super::Junction]s and for the devices of the
+//! [crate::solver], which keep their own last voltages, and [fetlim]
+//! and [limvds] for the gate-source and drain-source voltages of
+//! MOSFETs. [limexp] continues an exponential as a straight line
+//! beyond a large argument, so that it cannot overflow at any voltage.
+
+/// Argument above which [limexp] is linear, where the exponential
+/// is about 5.5e34
+const MAX_EXPONENT: f64 = 80.0;
+
+/// The exponential of x and its derivative, continued linearly (with
+/// the same slope) above an argument of 80
+pub fn limexp(x: f64) -> (f64, f64) {
+    if x <= MAX_EXPONENT {
+        let e = x.exp();
+        (e, e)
+    } else {
+        let e = MAX_EXPONENT.exp();
+        (e * (1.0 + x - MAX_EXPONENT), e)
+    }
+}
+
+/// Limit the change in the voltage of a junction with a thermal
+/// voltage and saturation current (SPICE `DEVpnjlim`): above the
+/// critical voltage, where the current is growing fastest, a step of
+/// more than two thermal voltages is cut to the logarithm of the step
+pub fn pnjlim(vt: f64, is: f64, new: f64, old: f64) -> f64 {
+    let vcrit = vt * (vt / (std::f64::consts::SQRT_2 * is)).ln();
+    if new > vcrit && (new - old).abs() > 2.0 * vt {
+        if old > 0.0 {
+            let arg = 1.0 + (new - old) / vt;
+            if arg > 0.0 {
+                old + vt * arg.ln()
+            } else {
+                vcrit
+            }
+        } else {
+            vt * (new / vt).ln()
+        }
+    } else {
+        new
+    }
+}
+
+/// Limit the change in the gate-source voltage of a FET with a
+/// threshold voltage (SPICE `DEVfetlim`): a FET that is on is not
+/// turned off in one step, nor one that is off turned fully on, and
+/// large steps are cut in proportion to the distance from the
+/// threshold
+pub fn fetlim(new: f64, old: f64, vto: f64) -> f64 {
+    let vtsthi = (2.0 * (old - vto)).abs() + 2.0;
+    let vtstlo = (old - vto).abs() + 1.0;
+    let vtox = vto + 3.5;
+    let delta = new - old;
+    if old >= vto {
+        if old >= vtox {
+            if delta <= 0.0 {
+                // Going off
+                if new >= vtox {
+                    if -delta > vtstlo {
+                        return old - vtstlo;
+                    }
+                    new
+                } else {
+                    new.max(vto + 2.0)
+                }
+            } else if delta >= vtsthi {
+                // Staying on
+                old + vtsthi
+            } else {
+                new
+            }
+        } else if delta <= 0.0 {
+            // In the middle region, decreasing
+            new.max(vto - 0.5)
+        } else {
+            // In the middle region, increasing
+            new.min(vto + 4.0)
+        }
+    } else if delta <= 0.0 {
+        // Off, and going further off
+        if -delta > vtsthi {
+            old - vtsthi
+        } else {
+            new
+        }
+    } else if new <= vto + 0.5 {
+        // Off, and turning on
+        if delta > vtstlo {
+            old + vtstlo
+        } else {
+            new
+        }
+    } else {
+        vto + 0.5
+    }
+}
+
+/// Limit the change in the drain-source voltage of a FET (SPICE
+/// `DEVlimvds`): it can at most triple (plus two volts) in one step
+/// from above 3.5 V, and is kept between -0.5 and 4 V from below
+pub fn limvds(new: f64, old: f64) -> f64 {
+    if old >= 3.5 {
+        if new > old {
+            new.min(3.0 * old + 2.0)
+        } else if new < 3.5 {
+            new.max(2.0)
+        } else {
+            new
+        }
+    } else if new > old {
+        new.min(4.0)
+    } else {
+        new.max(-0.5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VT: f64 = 0.025852;
+    const IS: f64 = 1e-14;
+
+    #[test]
+    fn limexp_is_continuous_and_finite() {
+        let (below, _) = limexp(MAX_EXPONENT - 1e-9);
+        let (above, slope) = limexp(MAX_EXPONENT + 1e-9);
+        assert!((above - below).abs() / below < 1e-6);
+        assert_eq!(slope, MAX_EXPONENT.exp());
+        assert!(limexp(1e4).0.is_finite());
+    }
+
+    #[test]
+    fn pnjlim_cuts_large_forward_steps() {
+        // A small step, or one below the critical voltage, is kept
+        assert_eq!(pnjlim(VT, IS, 0.61, 0.6), 0.61);
+        assert_eq!(pnjlim(VT, IS, -5.0, 0.6), -5.0);
+        // A large step above it grows with the logarithm of the step
+        let limited = pnjlim(VT, IS, 5.0, 0.6);
+        assert!(limited > 0.6 && limited < 0.8);
+    }
+
+    #[test]
+    fn fetlim_turns_a_fet_on_and_off_in_steps() {
+        // From off, a step on stops just above the threshold
+        assert_eq!(fetlim(10.0, 0.0, 2.0), 2.5);
+        // From the middle region, it goes to at most 4 V above it
+        assert_eq!(fetlim(10.0, 2.5, 2.0), 6.0);
+        // From fully on, a large step is cut in proportion to the
+        // distance from the threshold
+        assert_eq!(fetlim(100.0, 6.0, 2.0), 16.0);
+        // A FET that is fully on is not turned off in one step
+        assert_eq!(fetlim(0.0, 6.0, 2.0), 4.0);
+        // Small steps are kept
+        assert_eq!(fetlim(5.9, 6.0, 2.0), 5.9);
+    }
+
+    #[test]
+    fn limvds_bounds_drain_steps() {
+        assert_eq!(limvds(10.0, 0.0), 4.0);
+        assert_eq!(limvds(-3.0, 0.0), -0.5);
+        assert_eq!(limvds(100.0, 4.0), 14.0);
+        assert_eq!(limvds(0.0, 4.0), 2.0);
+        assert_eq!(limvds(3.8, 4.0), 3.8);
+    }
+}
@@ -0,0 +1,96 @@
+//! Device models shared by instances
+//!
+//! A model is a named set of device parameters, as defined by a SPICE
+//! `.MODEL` card, which any number of instances of the device use. The
+//! circuit keeps its models by name (see
+//! [Circuit::add_model](crate::circuit::Circuit::add_model)), and
+//! gives each instance that uses one the parameters of the model, so
+//! changing a parameter of the model changes it for all of them.
+
+use super::{
+    BjtModel, Component, DiodeModel, MosfetModel, SchottkyModel, SemiconductorCapacitorModel,
+    SemiconductorResistorModel, TunnelDiodeModel,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Model {
+    /// Junction diode (type `D`), also used by photodiodes
+    Diode(DiodeModel),
+    Schottky(SchottkyModel),
+    TunnelDiode(TunnelDiodeModel),
+    /// Semiconductor resistor (type `R`)
+    SemiconductorResistor(SemiconductorResistorModel),
+    /// Semiconductor capacitor (type `C`)
+    SemiconductorCapacitor(SemiconductorCapacitorModel),
+    /// Bipolar junction transistor (type `NPN` or `PNP`)
+    Bjt(BjtModel),
+    /// MOSFET (type `NMOS` or `PMOS`)
+    Mosfet(MosfetModel),
+}
+
+impl Model {
+    /// A parameter by its name (in any case), if there is one
+    pub fn parameter_mut(&mut self, name: &str) -> Option<&mut f64> {
+        match self {
+            Self::Diode(model) => model.parameter_mut(name),
+            Self::Schottky(model) => model.parameter_mut(name),
+            Self::TunnelDiode(model) => model.parameter_mut(name),
+            Self::SemiconductorResistor(model) => model.parameter_mut(name),
+            Self::SemiconductorCapacitor(model) => model.parameter_mut(name),
+            Self::Bjt(model) => model.parameter_mut(name),
+            Self::Mosfet(model) => model.parameter_mut(name),
+        }
+    }
+
+    /// Give a component the parameters of the model, returning false
+    /// (and leaving it unchanged) if it is not a device of the type of
+    /// the model
+    pub fn apply(&self, component: &mut Component) -> bool {
+        match (self, component) {
+            (Self::Diode(card), Component::Diode { model, .. })
+            | (Self::Diode(card), Component::Photodiode { model, .. }) => {
+                // The temperature is the instance's own
+                *model = DiodeModel {
+                    temp: model.temp,
+                    ..*card
+                }
+            }
+            (Self::Schottky(card), Component::SchottkyDiode { model, .. }) => {
+                *model = SchottkyModel {
+                    temp: model.temp,
+                    ..*card
+                }
+            }
+            (Self::TunnelDiode(card), Component::TunnelDiode { model, .. }) => {
+                *model = TunnelDiodeModel {
+                    temp: model.temp,
+                    ..*card
+                }
+            }
+            (Self::SemiconductorResistor(card), Component::SemiconductorResistor { model, .. }) => {
+                *model = *card
+            }
+            (
+                Self::SemiconductorCapacitor(card),
+                Component::SemiconductorCapacitor { model, .. },
+            ) => *model = *card,
+            (Self::Bjt(card), Component::Bjt { model, .. }) => {
+                *model = BjtModel {
+                    temp: model.temp,
+                    ..*card
+                }
+            }
+            (Self::Mosfet(card), Component::Mosfet { model, .. }) => {
+                // So are the width and length
+                *model = MosfetModel {
+                    width: model.width,
+                    length: model.length,
+                    temp: model.temp,
+                    ..*card
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,255 @@
+//! MOSFET model
+//!
+//! The transistor follows the SPICE level 1 (Shichman-Hodges) model,
+//! without the body effect: with a gate overdrive
+//! $V_{ov} = V_{GS} - V_{to}$, the drain current is zero in cutoff
+//! ($V_{ov} \le 0$), $\beta (V_{ov} V_{DS} - V_{DS}^2 / 2)(1 + \lambda V_{DS})$
+//! in the linear region ($V_{DS} < V_{ov}$), and
+//! $\beta V_{ov}^2 (1 + \lambda V_{DS}) / 2$ in saturation, where
+//! $\beta = K_P W / L$. The drain and source swap when $V_{DS}$ is
+//! negative. The transconductance falls with the temperature as
+//! $K_P (T / T_{nom})^{-3/2}$, and a transistor is at the temperature
+//! of the circuit unless it has its own.
+//!
+//! The gate-source voltage is limited about the threshold (see
+//! [super::fetlim]) and the drain-source voltage in steps (see
+//! [super::limvds]), as in SPICE. The series resistances and the
+//! (constant) gate overlap capacitances are added as plain resistors
+//! and capacitors when the circuit is elaborated. The bulk is not
+//! modelled, and is taken to be at the source.
+
+use super::diode::{kelvin, GMIN, NOMINAL_TEMPERATURE};
+use super::limiting::{fetlim, limvds};
+use super::transistor::{Polarity, Transistor, TransistorCurrent};
+
+/// Terminals of a MOSFET
+const DRAIN: usize = 0;
+const GATE: usize = 1;
+const SOURCE: usize = 2;
+
+/// Drawn width and length of a MOSFET that does not give them (m)
+pub const DEFAULT_SIZE: f64 = 100e-6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MosfetModel {
+    /// N-channel or P-channel
+    pub polarity: Polarity,
+    /// Threshold voltage (negative for an enhancement P-channel device)
+    pub vto: f64,
+    /// Transconductance parameter (A/V²)
+    pub kp: f64,
+    /// Channel length modulation (1/V)
+    pub lambda: f64,
+    /// Drain resistance
+    pub rd: f64,
+    /// Source resistance
+    pub rs: f64,
+    /// Gate-source overlap capacitance per width (F/m)
+    pub cgso: f64,
+    /// Gate-drain overlap capacitance per width (F/m)
+    pub cgdo: f64,
+    /// Temperature the parameters are given at (degrees Celsius)
+    pub tnom: f64,
+    /// Drawn width of the instance using the model (m), which is not
+    /// a parameter of the card, and is kept when the card changes
+    pub width: f64,
+    /// Drawn length of the instance using the model (m), as for the
+    /// width
+    pub length: f64,
+    /// Temperature of the instance using the model (degrees Celsius),
+    /// if it has its own, as for [super::DiodeModel::temp]
+    pub temp: Option<f64>,
+}
+
+impl Default for MosfetModel {
+    /// The SPICE defaults of an N-channel transistor
+    fn default() -> Self {
+        Self {
+            polarity: Polarity::N,
+            vto: 0.0,
+            kp: 2e-5,
+            lambda: 0.0,
+            rd: 0.0,
+            rs: 0.0,
+            cgso: 0.0,
+            cgdo: 0.0,
+            tnom: NOMINAL_TEMPERATURE,
+            width: DEFAULT_SIZE,
+            length: DEFAULT_SIZE,
+            temp: None,
+        }
+    }
+}
+
+impl MosfetModel {
+    /// The temperature of the transistor (degrees Celsius)
+    pub fn temperature(&self) -> f64 {
+        self.temp.unwrap_or(NOMINAL_TEMPERATURE)
+    }
+
+    /// The threshold voltage of the N-channel transistor the device is
+    /// modelled as
+    fn threshold(&self) -> f64 {
+        self.polarity.sign() * self.vto
+    }
+
+    /// $K_P W / L$ at the temperature of the transistor
+    fn beta(&self) -> f64 {
+        let ratio = kelvin(self.temperature()) / kelvin(self.tnom);
+        self.kp * self.width / self.length * ratio.powf(-1.5)
+    }
+
+    /// The drain current of an N-channel transistor in normal mode
+    /// ($V_{DS} \ge 0$), and its derivatives by $V_{GS}$ and $V_{DS}$
+    fn drain_current(&self, vgs: f64, vds: f64) -> (f64, [f64; 2]) {
+        let overdrive = vgs - self.threshold();
+        if overdrive <= 0.0 {
+            return (0.0, [0.0, 0.0]);
+        }
+        let beta = self.beta();
+        let modulation = 1.0 + self.lambda * vds;
+        if vds < overdrive {
+            let shape = overdrive * vds - vds * vds / 2.0;
+            (
+                beta * shape * modulation,
+                [
+                    beta * vds * modulation,
+                    beta * (overdrive - vds) * modulation + beta * shape * self.lambda,
+                ],
+            )
+        } else {
+            let shape = overdrive * overdrive / 2.0;
+            (
+                beta * shape * modulation,
+                [beta * overdrive * modulation, beta * shape * self.lambda],
+            )
+        }
+    }
+
+    /// A parameter by its SPICE name (in any case), if there is one
+    pub fn parameter_mut(&mut self, name: &str) -> Option<&mut f64> {
+        match name.to_ascii_lowercase().as_str() {
+            "vto" | "vt0" => Some(&mut self.vto),
+            "kp" => Some(&mut self.kp),
+            "lambda" => Some(&mut self.lambda),
+            "rd" => Some(&mut self.rd),
+            "rs" => Some(&mut self.rs),
+            "cgso" => Some(&mut self.cgso),
+            "cgdo" => Some(&mut self.cgdo),
+            "tnom" => Some(&mut self.tnom),
+            _ => None,
+        }
+    }
+}
+
+impl Transistor for MosfetModel {
+    /// The gate-source and drain-source voltages
+    fn controls(&self) -> [(usize, usize); 2] {
+        [(GATE, SOURCE), (DRAIN, SOURCE)]
+    }
+
+    /// The SPICE level 1 limiting: the gate voltage is limited about
+    /// the threshold from the source, or from the drain if the
+    /// transistor was in reverse mode
+    fn limit(&self, new: [f64; 2], old: [f64; 2]) -> [f64; 2] {
+        let sign = self.polarity.sign();
+        let (vgs, vds) = (sign * new[0], sign * new[1]);
+        let (vgs_old, vds_old) = (sign * old[0], sign * old[1]);
+        let vgd = vgs - vds;
+        let von = self.threshold();
+        let (vgs, vds) = if vds_old >= 0.0 {
+            let vgs = fetlim(vgs, vgs_old, von);
+            (vgs, limvds(vgs - vgd, vds_old))
+        } else {
+            let vgd = fetlim(vgd, vgs_old - vds_old, von);
+            let vds = -limvds(vgd - vgs, -vds_old);
+            (vgd + vds, vds)
+        };
+        [sign * vgs, sign * vds]
+    }
+
+    fn currents(&self, voltages: [f64; 2]) -> Vec<TransistorCurrent> {
+        // A P-channel transistor is an N-channel one with every voltage
+        // and current reversed, so the derivatives are the same
+        let sign = self.polarity.sign();
+        let (vgs, vds) = (sign * voltages[0], sign * voltages[1]);
+        let (current, derivatives) = if vds >= 0.0 {
+            self.drain_current(vgs, vds)
+        } else {
+            // In reverse mode the drain is the source, at -vds from it
+            let (current, [gm, gds]) = self.drain_current(vgs - vds, -vds);
+            (-current, [-gm, gm + gds])
+        };
+        vec![TransistorCurrent {
+            terminals: (DRAIN, SOURCE),
+            current: sign * (current + GMIN * vds),
+            derivatives: [derivatives[0], derivatives[1] + GMIN],
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> MosfetModel {
+        MosfetModel {
+            vto: 1.0,
+            kp: 1e-3,
+            lambda: 0.02,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn drain_current_is_square_law_in_saturation() {
+        let model = model();
+        let current = model.currents([3.0, 5.0])[0].current;
+        // beta (vgs - vto)^2 / 2 (1 + lambda vds)
+        let expected = 1e-3 * 4.0 / 2.0 * 1.1;
+        assert!((current - expected).abs() < 1e-9);
+        assert!(model.currents([0.5, 5.0])[0].current.abs() < 1e-9);
+    }
+
+    #[test]
+    fn derivatives_match_the_currents() {
+        for polarity in [Polarity::N, Polarity::P] {
+            let model = MosfetModel {
+                polarity,
+                vto: polarity.sign(),
+                ..model()
+            };
+            let sign = polarity.sign();
+            // Saturation, linear region and reverse mode
+            for voltages in [[3.0, 5.0], [3.0, 0.5], [3.0, -0.5]] {
+                let voltages = voltages.map(|v| sign * v);
+                let at = model.currents(voltages)[0];
+                for control in 0..2 {
+                    let mut moved = voltages;
+                    moved[control] += 1e-7;
+                    let numeric = (model.currents(moved)[0].current - at.current) / 1e-7;
+                    let analytic = at.derivatives[control];
+                    assert!((numeric - analytic).abs() <= 1e-4 * analytic.abs() + 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn drain_and_source_swap_in_reverse_mode() {
+        let model = model();
+        // With the drain 1 V below the source and the gate 3 V above
+        // the drain, the current is that of vgs = 3, vds = 1 reversed
+        let forward = model.currents([3.0, 1.0])[0].current;
+        let reverse = model.currents([2.0, -1.0])[0].current;
+        assert!((forward + reverse).abs() < 1e-12);
+    }
+
+    #[test]
+    fn limiting_holds_the_gate_near_the_threshold() {
+        let model = model();
+        let [vgs, vds] = model.limit([10.0, 10.0], [0.0, 0.0]);
+        assert_eq!(vgs, 1.5);
+        assert_eq!(vds, 1.5);
+    }
+}
@@ -0,0 +1,71 @@
+//! Measurement probe parameters
+//!
+//! A real probe loads the circuit and does not show the signal
+//! exactly: its bandwidth is finite, and oscilloscopes often average
+//! the measurement. The probe response is modelled as a first-order
+//! low-pass filter at the bandwidth, followed by a running average
+//! over a time window.
+
+use std::f64::consts::PI;
+
+use num::Complex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ProbeParams {
+    /// Input resistance (voltage probes only; none for no loading)
+    pub r_in: Option<f64>,
+    /// Input capacitance (voltage probes only; none for no loading)
+    pub c_in: Option<f64>,
+    /// -3 dB bandwidth (Hz; none for unlimited)
+    pub bandwidth: Option<f64>,
+    /// Length of the running average (seconds; none for no averaging)
+    pub averaging_window: Option<f64>,
+}
+
+impl ProbeParams {
+    /// Frequency response of the probe (the averaging is not included)
+    pub fn response(&self, frequency: f64) -> Complex<f64> {
+        match self.bandwidth {
+            Some(bandwidth) => 1.0 / Complex::new(1.0, frequency / bandwidth),
+            None => Complex::new(1.0, 0.0),
+        }
+    }
+
+    /// The signal seen through the probe, from the signal at the probe
+    /// terminals sampled at increasing (not necessarily uniform) times
+    pub fn measure(&self, time: &[f64], signal: &[f64]) -> Vec<f64> {
+        let mut filtered = signal.to_vec();
+        if let Some(bandwidth) = self.bandwidth {
+            // Exact step response of the low-pass filter over each
+            // interval, holding the input at the end of the interval
+            for k in 1..filtered.len() {
+                let a = (-2.0 * PI * bandwidth * (time[k] - time[k - 1])).exp();
+                filtered[k] = a * filtered[k - 1] + (1.0 - a) * signal[k];
+            }
+        }
+        let Some(window) = self.averaging_window else {
+            return filtered;
+        };
+        // Trapezoidal running integral, averaged over the window (or
+        // over the time so far, at the start)
+        let mut integral = vec![0.0; filtered.len()];
+        for k in 1..filtered.len() {
+            integral[k] =
+                integral[k - 1] + 0.5 * (filtered[k] + filtered[k - 1]) * (time[k] - time[k - 1]);
+        }
+        let mut start = 0;
+        (0..filtered.len())
+            .map(|k| {
+                while time[k] - time[start] > window {
+                    start += 1;
+                }
+                let span = time[k] - time[start];
+                if span > 0.0 {
+                    (integral[k] - integral[start]) / span
+                } else {
+                    filtered[k]
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,76 @@
+//! Relay model
+//!
+//! The coil is a resistance in series with an inductance, and the
+//! contacts are a switch with a small closed resistance and a large
+//! open resistance. The coil is energized when its current reaches
+//! the pull-in current, and stays energized until the current falls
+//! below the drop-out current (which is lower, giving hysteresis).
+//! The contacts close after the operate time once the coil is
+//! energized, and open after the release time once it is not.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RelayParams {
+    /// Coil resistance
+    pub r_coil: f64,
+    /// Coil inductance
+    pub l_coil: f64,
+    /// Coil current at which the relay operates
+    pub pull_in: f64,
+    /// Coil current below which the relay releases
+    pub drop_out: f64,
+    /// Time from the coil being energized to the contacts closing
+    pub operate_time: f64,
+    /// Time from the coil being de-energized to the contacts opening
+    pub release_time: f64,
+    /// Contact resistance when closed
+    pub r_on: f64,
+    /// Contact resistance when open
+    pub r_off: f64,
+}
+
+impl Default for RelayParams {
+    /// A typical 12 V signal relay
+    fn default() -> Self {
+        Self {
+            r_coil: 400.0,
+            l_coil: 0.2,
+            pull_in: 22e-3,
+            drop_out: 3e-3,
+            operate_time: 5e-3,
+            release_time: 2e-3,
+            r_on: 0.05,
+            r_off: 1e9,
+        }
+    }
+}
+
+impl RelayParams {
+    /// Contact resistance when closed or open
+    pub fn resistance(&self, closed: bool) -> f64 {
+        if closed {
+            self.r_on
+        } else {
+            self.r_off
+        }
+    }
+
+    /// Whether the coil is energized at a coil current, given whether
+    /// it was energized before
+    pub fn energized(&self, energized: bool, current: f64) -> bool {
+        let threshold = if energized {
+            self.drop_out
+        } else {
+            self.pull_in
+        };
+        current.abs() >= threshold
+    }
+
+    /// Delay before the contacts follow the coil
+    pub fn delay(&self, energized: bool) -> f64 {
+        if energized {
+            self.operate_time
+        } else {
+            self.release_time
+        }
+    }
+}
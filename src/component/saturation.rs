@@ -0,0 +1,124 @@
+//! Core saturation curves for nonlinear inductors
+//!
+//! A saturation curve gives the flux linkage as a function of the
+//! inductor current. The incremental inductance is the slope of the
+//! curve, which falls as the core saturates.
+
+use crate::error::{invalid, EsimError};
+
+/// Flux linkage versus current relationship of a saturable inductor
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaturationCurve {
+    /// $\Phi(i) = L_{sat} i + (L_0 - L_{sat}) I_{sat} \arctan(i / I_{sat})$
+    Arctan {
+        /// Inductance around zero current
+        l0: f64,
+        /// Inductance of the fully saturated core
+        l_sat: f64,
+        /// Current at which the incremental inductance is halfway
+        /// between `l0` and `l_sat`
+        i_sat: f64,
+    },
+    /// Piecewise-linear flux linkage, given as (current, flux) points
+    /// sorted by current. Outside the points, the first and last
+    /// segments are extended. There must be at least two points (see
+    /// [SaturationCurve::check]).
+    Pwl { points: Vec<(f64, f64)> },
+}
+
+impl SaturationCurve {
+    /// Check the curve, returning an error if it is a PWL curve with
+    /// fewer than two points or with currents that do not increase,
+    /// or an arctan curve whose saturation current is not positive
+    pub fn check(&self) -> Result<(), EsimError> {
+        match self {
+            Self::Arctan { i_sat, .. } if *i_sat <= 0.0 || !i_sat.is_finite() => Err(invalid(
+                format!("Saturation current {i_sat} must be positive"),
+            )),
+            Self::Pwl { points } if points.len() < 2 => Err(invalid(format!(
+                "Saturation curve needs at least two points, but has {}",
+                points.len()
+            ))),
+            Self::Pwl { points } if points.windows(2).any(|w| w[1].0 <= w[0].0) => Err(invalid(
+                String::from("Saturation curve currents must increase"),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Index of the segment of the PWL curve that contains the current
+    /// (which is a curve of at least two points)
+    fn pwl_segment(points: &[(f64, f64)], current: f64) -> usize {
+        let n = points[1..points.len() - 1]
+            .iter()
+            .take_while(|(i, _)| *i < current)
+            .count();
+        n.min(points.len() - 2)
+    }
+
+    /// Flux linkage at the current
+    pub fn flux(&self, current: f64) -> f64 {
+        match self {
+            Self::Arctan { l0, l_sat, i_sat } => {
+                l_sat * current + (l0 - l_sat) * i_sat * (current / i_sat).atan()
+            }
+            // A curve of fewer than two points (which fails its check)
+            // is flat
+            Self::Pwl { points } if points.len() < 2 => points.first().map_or(0.0, |p| p.1),
+            Self::Pwl { points } => {
+                let k = Self::pwl_segment(points, current);
+                let (i1, phi1) = points[k];
+                phi1 + self.inductance(current) * (current - i1)
+            }
+        }
+    }
+
+    /// Incremental inductance (slope of the flux curve) at the current
+    pub fn inductance(&self, current: f64) -> f64 {
+        match self {
+            Self::Arctan { l0, l_sat, i_sat } => {
+                l_sat + (l0 - l_sat) / (1.0 + (current / i_sat).powi(2))
+            }
+            Self::Pwl { points } if points.len() < 2 => 0.0,
+            Self::Pwl { points } => {
+                let k = Self::pwl_segment(points, current);
+                let ((i1, phi1), (i2, phi2)) = (points[k], points[k + 1]);
+                (phi2 - phi1) / (i2 - i1)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_inductance_at_the_saturation_current_is_halfway() {
+        let curve = SaturationCurve::Arctan {
+            l0: 10e-3,
+            l_sat: 2e-3,
+            i_sat: 1.5,
+        };
+        assert!(curve.check().is_ok());
+        assert!((curve.inductance(1.5) - 6e-3).abs() < 1e-15);
+    }
+
+    #[test]
+    fn a_pwl_curve_needs_two_points_of_increasing_current() {
+        let short = SaturationCurve::Pwl {
+            points: vec![(0.0, 0.0)],
+        };
+        assert!(matches!(short.check(), Err(EsimError::Invalid { .. })));
+        assert_eq!(short.inductance(1.0), 0.0);
+        let unsorted = SaturationCurve::Pwl {
+            points: vec![(1.0, 1e-3), (0.0, 0.0)],
+        };
+        assert!(matches!(unsorted.check(), Err(EsimError::Invalid { .. })));
+        let curve = SaturationCurve::Pwl {
+            points: vec![(0.0, 0.0), (1.0, 1e-3), (2.0, 1.5e-3)],
+        };
+        assert!(curve.check().is_ok());
+        assert!((curve.flux(1.5) - 1.25e-3).abs() < 1e-15);
+    }
+}
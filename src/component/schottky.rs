@@ -0,0 +1,113 @@
+//! Schottky diode model
+//!
+//! A metal-semiconductor junction, whose saturation current is set by
+//! the barrier height through thermionic emission,
+//! $I_s = A A^{**} T^2 e^{-\phi_B / V_t}$, rather than by minority
+//! carrier diffusion. The low barrier gives a forward voltage well
+//! below that of a pn junction, and a saturation current many orders
+//! of magnitude higher.
+//!
+//! The reverse leakage also rises with reverse voltage, as the image
+//! force lowers the barrier by $\beta \sqrt{V_R}$, so the saturation
+//! current is multiplied by $e^{\beta \sqrt{V_R} / V_t}$ under
+//! reverse bias. As for the junction diode, the series resistance is
+//! added as a plain resistor when the circuit is elaborated, and the
+//! diode is at the temperature of the circuit unless it has its own.
+
+use super::diode::{kelvin, thermal_voltage, GMIN, NOMINAL_TEMPERATURE};
+use super::limiting::{limexp, pnjlim};
+use super::Junction;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SchottkyModel {
+    /// Barrier height (V)
+    pub phi_b: f64,
+    /// Junction area (m²)
+    pub area: f64,
+    /// Effective Richardson constant (A/m²K²)
+    pub richardson: f64,
+    /// Emission coefficient
+    pub n: f64,
+    /// Barrier lowering coefficient (√V)
+    pub lowering: f64,
+    /// Series resistance
+    pub rs: f64,
+    /// Temperature of the instance using the model (degrees Celsius),
+    /// if it has its own, as set by `temp=` on its netlist line. As for
+    /// [super::DiodeModel::temp], it is kept when the card changes, and
+    /// set to the temperature of the circuit when it is elaborated.
+    pub temp: Option<f64>,
+}
+
+impl Default for SchottkyModel {
+    /// A small-signal silicon Schottky diode
+    fn default() -> Self {
+        Self {
+            phi_b: 0.6,
+            area: 1e-8,
+            richardson: 1.12e6,
+            n: 1.05,
+            lowering: 0.02,
+            rs: 0.0,
+            temp: None,
+        }
+    }
+}
+
+impl SchottkyModel {
+    /// The temperature of the diode (degrees Celsius)
+    pub fn temperature(&self) -> f64 {
+        self.temp.unwrap_or(NOMINAL_TEMPERATURE)
+    }
+
+    fn vt(&self) -> f64 {
+        self.n * thermal_voltage(self.temperature())
+    }
+
+    /// A parameter by its name (in any case), if there is one
+    pub fn parameter_mut(&mut self, name: &str) -> Option<&mut f64> {
+        match name.to_ascii_lowercase().as_str() {
+            "phi_b" => Some(&mut self.phi_b),
+            "area" => Some(&mut self.area),
+            "richardson" => Some(&mut self.richardson),
+            "n" => Some(&mut self.n),
+            "lowering" => Some(&mut self.lowering),
+            "rs" => Some(&mut self.rs),
+            _ => None,
+        }
+    }
+
+    /// Saturation current at zero bias, at the temperature of the diode
+    pub fn saturation_current(&self) -> f64 {
+        let temperature = self.temperature();
+        self.area
+            * self.richardson
+            * kelvin(temperature).powi(2)
+            * (-self.phi_b / thermal_voltage(temperature)).exp()
+    }
+}
+
+impl Junction for SchottkyModel {
+    fn evaluate(&self, voltage: f64) -> (f64, f64) {
+        let is = self.saturation_current();
+        let (e, de) = limexp(voltage / self.vt());
+        // Saturation current with the barrier lowered by the reverse
+        // voltage, and its derivative
+        let vt = thermal_voltage(self.temperature());
+        let (is, dis) = if voltage < 0.0 {
+            let root = (-voltage).sqrt();
+            let is = is * (self.lowering * root / vt).exp();
+            (is, -is * self.lowering / (2.0 * root * vt))
+        } else {
+            (is, 0.0)
+        };
+        (
+            is * (e - 1.0) + GMIN * voltage,
+            is * de / self.vt() + dis * (e - 1.0) + GMIN,
+        )
+    }
+
+    fn limit_voltage(&self, new: f64, old: f64) -> f64 {
+        pnjlim(self.vt(), self.saturation_current(), new, old)
+    }
+}
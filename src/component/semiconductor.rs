@@ -0,0 +1,138 @@
+//! Semiconductor resistor and capacitor models
+//!
+//! These are the SPICE `R` and `C` model types, where the value of a
+//! component is computed from its drawn length and width, rather
+//! than given directly. Temperatures are in degrees Celsius.
+
+/// Default SPICE nominal temperature (degrees Celsius)
+pub const DEFAULT_TNOM: f64 = 27.0;
+
+/// Default drawn width (m)
+const DEFAULT_WIDTH: f64 = 10e-6;
+
+/// Temperature scaling $1 + TC_1 \Delta T + TC_2 \Delta T^2$
+fn temperature_factor(tc1: f64, tc2: f64, tnom: f64, temperature: f64) -> f64 {
+    let dt = temperature - tnom;
+    1.0 + tc1 * dt + tc2 * dt * dt
+}
+
+/// Semiconductor resistor model card
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SemiconductorResistorModel {
+    /// Sheet resistance (Ohm per square)
+    pub rsh: f64,
+    /// Default width, if the instance has none (m)
+    pub defw: f64,
+    /// Narrowing of the width due to side etching (m)
+    pub narrow: f64,
+    /// Shortening of the length due to side etching (m)
+    pub short: f64,
+    /// First order temperature coefficient (1/C)
+    pub tc1: f64,
+    /// Second order temperature coefficient (1/C^2)
+    pub tc2: f64,
+    /// Temperature at which the parameters were measured (C)
+    pub tnom: f64,
+}
+
+impl Default for SemiconductorResistorModel {
+    fn default() -> Self {
+        Self {
+            rsh: 0.0,
+            defw: DEFAULT_WIDTH,
+            narrow: 0.0,
+            short: 0.0,
+            tc1: 0.0,
+            tc2: 0.0,
+            tnom: DEFAULT_TNOM,
+        }
+    }
+}
+
+impl SemiconductorResistorModel {
+    /// A parameter by its name (in any case), if there is one
+    pub fn parameter_mut(&mut self, name: &str) -> Option<&mut f64> {
+        match name.to_ascii_lowercase().as_str() {
+            "rsh" => Some(&mut self.rsh),
+            "defw" => Some(&mut self.defw),
+            "narrow" => Some(&mut self.narrow),
+            "short" => Some(&mut self.short),
+            "tc1" => Some(&mut self.tc1),
+            "tc2" => Some(&mut self.tc2),
+            "tnom" => Some(&mut self.tnom),
+            _ => None,
+        }
+    }
+
+    /// Resistance of an instance with the drawn length and width (the
+    /// model default width is used if there is none), at the
+    /// temperature
+    pub fn resistance(&self, length: f64, width: Option<f64>, temperature: f64) -> f64 {
+        let width = width.unwrap_or(self.defw);
+        let squares = (length - self.short) / (width - self.narrow);
+        self.rsh * squares * temperature_factor(self.tc1, self.tc2, self.tnom, temperature)
+    }
+}
+
+/// Semiconductor capacitor model card
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SemiconductorCapacitorModel {
+    /// Junction bottom capacitance (F/m^2)
+    pub cj: f64,
+    /// Junction sidewall capacitance (F/m)
+    pub cjsw: f64,
+    /// Default width, if the instance has none (m)
+    pub defw: f64,
+    /// Narrowing of the width due to side etching (m)
+    pub narrow: f64,
+    /// Shortening of the length due to side etching (m)
+    pub short: f64,
+    /// First order temperature coefficient (1/C)
+    pub tc1: f64,
+    /// Second order temperature coefficient (1/C^2)
+    pub tc2: f64,
+    /// Temperature at which the parameters were measured (C)
+    pub tnom: f64,
+}
+
+impl Default for SemiconductorCapacitorModel {
+    fn default() -> Self {
+        Self {
+            cj: 0.0,
+            cjsw: 0.0,
+            defw: DEFAULT_WIDTH,
+            narrow: 0.0,
+            short: 0.0,
+            tc1: 0.0,
+            tc2: 0.0,
+            tnom: DEFAULT_TNOM,
+        }
+    }
+}
+
+impl SemiconductorCapacitorModel {
+    /// A parameter by its name (in any case), if there is one
+    pub fn parameter_mut(&mut self, name: &str) -> Option<&mut f64> {
+        match name.to_ascii_lowercase().as_str() {
+            "cj" => Some(&mut self.cj),
+            "cjsw" => Some(&mut self.cjsw),
+            "defw" => Some(&mut self.defw),
+            "narrow" => Some(&mut self.narrow),
+            "short" => Some(&mut self.short),
+            "tc1" => Some(&mut self.tc1),
+            "tc2" => Some(&mut self.tc2),
+            "tnom" => Some(&mut self.tnom),
+            _ => None,
+        }
+    }
+
+    /// Capacitance of an instance with the drawn length and width (the
+    /// model default width is used if there is none), at the
+    /// temperature
+    pub fn capacitance(&self, length: f64, width: Option<f64>, temperature: f64) -> f64 {
+        let length = length - self.short;
+        let width = width.unwrap_or(self.defw) - self.narrow;
+        let value = self.cj * length * width + 2.0 * self.cjsw * (length + width);
+        value * temperature_factor(self.tc1, self.tc2, self.tnom, temperature)
+    }
+}
@@ -0,0 +1,26 @@
+//! Supercapacitor parameters
+//!
+//! The supercapacitor is its main capacitance in series with the
+//! equivalent series resistance (ESR), with a leakage resistance in
+//! parallel with the capacitance. The leakage resistance is set from
+//! the leakage current at the rated voltage, as given in datasheets.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SupercapParams {
+    /// Main capacitance
+    pub capacitance: f64,
+    /// Equivalent series resistance
+    pub esr: f64,
+    /// Rated voltage
+    pub rated_voltage: f64,
+    /// Leakage current at the rated voltage
+    pub leakage_current: f64,
+}
+
+impl SupercapParams {
+    /// Resistance in parallel with the capacitance that draws the
+    /// leakage current at the rated voltage
+    pub fn leakage_resistance(&self) -> f64 {
+        self.rated_voltage / self.leakage_current
+    }
+}
@@ -0,0 +1,153 @@
+//! Lookup-table device
+//!
+//! A two-terminal device whose characteristic is a table of points
+//! (such as a measured I–V curve), interpolated linearly between the
+//! points. The table gives either the current or the resistance as a
+//! function of the voltage across the device.
+//!
+//! Outside the table, a current is extrapolated along the first or
+//! last segment (so the device keeps a conductance there), and a
+//! resistance holds its first or last value.
+//!
+//! Tables can be read from files in the same format as PWL stimulus
+//! files: each line holds a voltage and a value, separated by a
+//! comma, semicolon or spaces. Blank lines, comment lines starting
+//! with `#`, `*` or `;`, and a header line before the first point are
+//! skipped.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::diode::GMIN;
+use super::Junction;
+use crate::error::{self, EsimError};
+use crate::value::parse_spice_value;
+
+/// The quantity the table gives as a function of voltage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TableQuantity {
+    #[default]
+    Current,
+    Resistance,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableModel {
+    /// (voltage, value) points, in increasing order of voltage
+    pub points: Vec<(f64, f64)>,
+    pub quantity: TableQuantity,
+}
+
+/// Error for a table that cannot be used
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl TableModel {
+    /// Panics if there are fewer than two points or the voltages are
+    /// not increasing (see [TableModel::try_new])
+    pub fn new(points: Vec<(f64, f64)>, quantity: TableQuantity) -> Self {
+        Self::try_new(points, quantity).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// The table of the points, or an error if there are fewer than two
+    /// or the voltages are not increasing
+    pub fn try_new(points: Vec<(f64, f64)>, quantity: TableQuantity) -> Result<Self, EsimError> {
+        if points.len() < 2 {
+            return Err(error::invalid(String::from(
+                "A table needs at least two points",
+            )));
+        }
+        if !points.windows(2).all(|p| p[1].0 > p[0].0) {
+            return Err(error::invalid(String::from(
+                "Table voltages must be increasing",
+            )));
+        }
+        Ok(Self { points, quantity })
+    }
+
+    /// Read a table from a file
+    pub fn from_file(path: impl AsRef<Path>, quantity: TableQuantity) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mut points: Vec<(f64, f64)> = Vec::new();
+        for (index, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(['#', '*', ';']) {
+                continue;
+            }
+            let fields: Vec<&str> = line
+                .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+                .filter(|s| !s.is_empty())
+                .collect();
+            let point = match fields[..] {
+                [voltage, value, ..] => parse_spice_value(voltage)
+                    .and_then(|voltage| Ok((voltage, parse_spice_value(value)?)))
+                    .map_err(|error| error.to_string()),
+                _ => Err(String::from("expected a voltage and a value")),
+            };
+            let location = || format!("Table file {} line {}", path.display(), index + 1);
+            match point {
+                Ok(point) => {
+                    if points.last().is_some_and(|last| point.0 <= last.0) {
+                        let message = "voltages must be increasing";
+                        return Err(invalid(format!("{}: {message}", location())));
+                    }
+                    points.push(point);
+                }
+                // A header line before the first point
+                Err(_) if points.is_empty() => continue,
+                Err(error) => return Err(invalid(format!("{}: {error}", location()))),
+            }
+        }
+        if points.len() < 2 {
+            let message = "a table needs at least two points";
+            return Err(invalid(format!("Table file {}: {message}", path.display())));
+        }
+        Ok(Self { points, quantity })
+    }
+
+    /// Interpolated value and its slope at a voltage, extrapolating
+    /// along the end segments
+    fn interpolate(&self, voltage: f64) -> (f64, f64) {
+        let last = self.points.len() - 2;
+        let k = self
+            .points
+            .iter()
+            .skip(1)
+            .position(|(v, _)| *v > voltage)
+            .unwrap_or(last);
+        let ((v1, y1), (v2, y2)) = (self.points[k], self.points[k + 1]);
+        let slope = (y2 - y1) / (v2 - v1);
+        (y1 + slope * (voltage - v1), slope)
+    }
+}
+
+impl Junction for TableModel {
+    fn evaluate(&self, voltage: f64) -> (f64, f64) {
+        let (current, conductance) = match self.quantity {
+            TableQuantity::Current => self.interpolate(voltage),
+            TableQuantity::Resistance => {
+                let (first, last) = (self.points[0], self.points[self.points.len() - 1]);
+                let (resistance, slope) = if voltage <= first.0 {
+                    (first.1, 0.0)
+                } else if voltage >= last.0 {
+                    (last.1, 0.0)
+                } else {
+                    self.interpolate(voltage)
+                };
+                (
+                    voltage / resistance,
+                    (resistance - voltage * slope) / resistance.powi(2),
+                )
+            }
+        };
+        (current + GMIN * voltage, conductance + GMIN)
+    }
+
+    /// The characteristic is piecewise linear, so Newton iteration
+    /// needs no limiting
+    fn limit_voltage(&self, new: f64, _old: f64) -> f64 {
+        new
+    }
+}
@@ -0,0 +1,47 @@
+//! Thermistor resistance models
+//!
+//! Temperatures are given in degrees Celsius and converted to kelvin
+//! internally.
+
+/// Offset between degrees Celsius and kelvin
+const ZERO_CELSIUS: f64 = 273.15;
+
+/// Resistance versus temperature relationship of a thermistor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThermistorModel {
+    /// Beta equation $R = R_0 \exp(\beta (1/T - 1/T_0))$
+    ///
+    /// A positive beta gives an NTC thermistor, and a negative beta
+    /// gives a PTC thermistor.
+    Beta {
+        /// Resistance at the reference temperature
+        r0: f64,
+        /// Reference temperature (degrees Celsius)
+        t0: f64,
+        /// Beta constant (kelvin)
+        beta: f64,
+    },
+    /// Steinhart-Hart equation $1/T = A + B \ln R + C (\ln R)^3$
+    SteinhartHart { a: f64, b: f64, c: f64 },
+}
+
+impl ThermistorModel {
+    /// Resistance at the temperature (degrees Celsius)
+    pub fn resistance(&self, temperature: f64) -> f64 {
+        let t = temperature + ZERO_CELSIUS;
+        match *self {
+            Self::Beta { r0, t0, beta } => {
+                r0 * (beta * (1.0 / t - 1.0 / (t0 + ZERO_CELSIUS))).exp()
+            }
+            Self::SteinhartHart { a, b, c } => {
+                if c == 0.0 {
+                    return ((1.0 / t - a) / b).exp();
+                }
+                // Invert the cubic in ln(R)
+                let y = (a - 1.0 / t) / (2.0 * c);
+                let x = ((b / (3.0 * c)).powi(3) + y * y).sqrt();
+                ((x - y).cbrt() - (x + y).cbrt()).exp()
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Thyristor (SCR and TRIAC) behavioral model
+//!
+//! The main terminals are a switch, with a small on resistance and a
+//! large off resistance, and the gate is a resistance to the cathode
+//! (MT1 for a TRIAC). The switch turns on when the gate current
+//! reaches the trigger current (in the forward direction only for an
+//! SCR), and latches: it stays on after the gate current is removed,
+//! until the main current falls below the holding current.
+
+/// Type of thyristor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThyristorKind {
+    /// Silicon controlled rectifier: triggered by a positive gate
+    /// current while the anode is positive, and conducts from anode
+    /// to cathode
+    Scr,
+    /// Triggered by a gate current of either sign, and conducts in
+    /// both directions
+    Triac,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThyristorParams {
+    /// Gate trigger current
+    pub igt: f64,
+    /// Holding current
+    pub ih: f64,
+    /// Main terminal resistance when on
+    pub r_on: f64,
+    /// Main terminal resistance when off
+    pub r_off: f64,
+    /// Gate to cathode (MT1) resistance
+    pub r_gate: f64,
+}
+
+impl Default for ThyristorParams {
+    fn default() -> Self {
+        Self {
+            igt: 5e-3,
+            ih: 10e-3,
+            r_on: 0.05,
+            r_off: 1e8,
+            r_gate: 100.0,
+        }
+    }
+}
+
+impl ThyristorParams {
+    /// Resistance between the main terminals in the on or off state
+    pub fn resistance(&self, on: bool) -> f64 {
+        if on {
+            self.r_on
+        } else {
+            self.r_off
+        }
+    }
+
+    /// The next state of the switch, given its state and the main
+    /// (anode to cathode) voltage and current and the gate current
+    /// of the last solution
+    pub fn next_state(
+        &self,
+        kind: ThyristorKind,
+        on: bool,
+        voltage: f64,
+        current: f64,
+        gate_current: f64,
+    ) -> bool {
+        let (triggered, holding) = match kind {
+            ThyristorKind::Scr => (
+                gate_current >= self.igt && voltage > 0.0,
+                current >= self.ih,
+            ),
+            ThyristorKind::Triac => (gate_current.abs() >= self.igt, current.abs() >= self.ih),
+        };
+        triggered || (on && holding)
+    }
+}
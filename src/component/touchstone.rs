@@ -0,0 +1,310 @@
+//! Measured N-port data from Touchstone files
+//!
+//! A Touchstone (`.sNp`) file holds the network parameters of an
+//! N-port (such as a measured filter or connector) at a list of
+//! frequencies. The file starts with an option line
+//!
+//! ```text
+//! # <unit> <parameter> <format> R <z0>
+//! ```
+//!
+//! giving the frequency unit (`HZ`, `KHZ`, `MHZ` or `GHZ`), the
+//! parameter (`S`, `Y` or `Z`), the format of each complex value (`DB`
+//! or `MA`, a magnitude and an angle in degrees, or `RI`, real and
+//! imaginary parts) and the reference impedance, which default to `GHZ
+//! S MA R 50`. Each frequency is followed by the N² values, row by
+//! row, except in a two-port file, where the order is 11, 21, 12, 22.
+//! The values can run over several lines. Y and Z parameters are
+//! normalized to the reference impedance. Comments start with `!`, and
+//! the noise parameters of a two-port file (whose first frequency is
+//! not above the last frequency of the network data) are ignored.
+//! Touchstone 2 keywords are not supported.
+//!
+//! The parameters are interpolated linearly between the frequencies,
+//! and held outside them. The N-port is stamped as its admittance
+//! matrix, which is found from S parameters as
+//! $Y = (I + S)^{-1} (I - S) / z_0$.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use num::Complex;
+
+use crate::error::{self, EsimError};
+
+/// The network parameters of a Touchstone file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NetworkParameter {
+    #[default]
+    Scattering,
+    Admittance,
+    Impedance,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TouchstoneData {
+    pub parameter: NetworkParameter,
+    /// Reference impedance of the ports (of the S parameters)
+    pub z0: f64,
+    /// (frequency, matrix) points, in increasing order of frequency,
+    /// with each matrix by row then column. Y and Z parameters are not
+    /// normalized.
+    pub points: Vec<(f64, Vec<Complex<f64>>)>,
+}
+
+/// Error for a file that cannot be used
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Solve A X = B for square matrices by Gaussian elimination with
+/// partial pivoting, where B is given by row then column
+fn solve(mut a: Vec<Complex<f64>>, mut b: Vec<Complex<f64>>, n: usize) -> Vec<Complex<f64>> {
+    for k in 0..n {
+        let pivot = (k..n)
+            .max_by(|i, j| a[i * n + k].norm().total_cmp(&a[j * n + k].norm()))
+            .unwrap();
+        for column in 0..n {
+            a.swap(k * n + column, pivot * n + column);
+            b.swap(k * n + column, pivot * n + column);
+        }
+        for row in k + 1..n {
+            let factor = a[row * n + k] / a[k * n + k];
+            for column in 0..n {
+                let (ak, bk) = (a[k * n + column], b[k * n + column]);
+                a[row * n + column] -= factor * ak;
+                b[row * n + column] -= factor * bk;
+            }
+        }
+    }
+    for k in (0..n).rev() {
+        for column in 0..n {
+            let sum: Complex<f64> = (k + 1..n).map(|j| a[k * n + j] * b[j * n + column]).sum();
+            b[k * n + column] = (b[k * n + column] - sum) / a[k * n + k];
+        }
+    }
+    b
+}
+
+impl TouchstoneData {
+    /// Panics if there are no points, the frequencies are not
+    /// increasing, or the matrices are not square and of one size (see
+    /// [TouchstoneData::try_new])
+    pub fn new(
+        parameter: NetworkParameter,
+        z0: f64,
+        points: Vec<(f64, Vec<Complex<f64>>)>,
+    ) -> Self {
+        Self::try_new(parameter, z0, points).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// The data of the points, or an error if there are none, the
+    /// frequencies are not increasing, or the matrices are not square
+    /// and of one size
+    pub fn try_new(
+        parameter: NetworkParameter,
+        z0: f64,
+        points: Vec<(f64, Vec<Complex<f64>>)>,
+    ) -> Result<Self, EsimError> {
+        let fail = |message: &str| Err(error::invalid(message.to_string()));
+        let Some((_, first)) = points.first() else {
+            return fail("N-port data needs at least one point");
+        };
+        if !points.windows(2).all(|p| p[1].0 > p[0].0) {
+            return fail("N-port frequencies must be increasing");
+        }
+        let size = first.len();
+        let n = (size as f64).sqrt().round() as usize;
+        if n == 0 || n * n != size || points.iter().any(|(_, m)| m.len() != size) {
+            return fail("N-port matrices must be square and of one size");
+        }
+        Ok(Self {
+            parameter,
+            z0,
+            points,
+        })
+    }
+
+    /// Read the data of an N-port from the text of a Touchstone file
+    pub fn parse(text: &str, num_ports: usize) -> Result<Self, String> {
+        let mut scale = 1e9;
+        let mut parameter = NetworkParameter::Scattering;
+        let mut format = "MA";
+        let mut z0 = 50.0;
+        let record = 1 + 2 * num_ports * num_ports;
+        let mut numbers: Vec<f64> = Vec::new();
+        let mut points: Vec<(f64, Vec<Complex<f64>>)> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: &str| format!("line {}: {message}", index + 1);
+            let line = line.split('!').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(options) = line.strip_prefix('#') {
+                let options = options.to_ascii_uppercase();
+                let mut tokens = options.split_whitespace();
+                while let Some(token) = tokens.next() {
+                    match token {
+                        "HZ" => scale = 1.0,
+                        "KHZ" => scale = 1e3,
+                        "MHZ" => scale = 1e6,
+                        "GHZ" => scale = 1e9,
+                        "S" => parameter = NetworkParameter::Scattering,
+                        "Y" => parameter = NetworkParameter::Admittance,
+                        "Z" => parameter = NetworkParameter::Impedance,
+                        "DB" => format = "DB",
+                        "MA" => format = "MA",
+                        "RI" => format = "RI",
+                        "R" => {
+                            z0 = tokens
+                                .next()
+                                .and_then(|z0| z0.parse().ok())
+                                .ok_or_else(|| error("expected a reference impedance"))?
+                        }
+                        "H" | "G" => return Err(error("H and G parameters are not supported")),
+                        _ => return Err(error(&format!("unknown option '{token}'"))),
+                    }
+                }
+                continue;
+            }
+            if line.starts_with('[') {
+                return Err(error("Touchstone 2 keywords are not supported"));
+            }
+            let values: Vec<f64> = line
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| error("expected numbers"))?;
+            // Noise parameters follow the network data of a two-port
+            if numbers.is_empty()
+                && num_ports == 2
+                && values.len() == 5
+                && points.last().is_some_and(|(f, _)| values[0] * scale <= *f)
+            {
+                break;
+            }
+            numbers.extend(values);
+            while numbers.len() >= record {
+                let frequency = numbers[0] * scale;
+                let mut matrix: Vec<Complex<f64>> = numbers[1..record]
+                    .chunks(2)
+                    .map(|pair| match format {
+                        "RI" => Complex::new(pair[0], pair[1]),
+                        "DB" => {
+                            Complex::from_polar(10f64.powf(pair[0] / 20.0), pair[1].to_radians())
+                        }
+                        _ => Complex::from_polar(pair[0], pair[1].to_radians()),
+                    })
+                    .collect();
+                if num_ports == 2 {
+                    matrix.swap(1, 2);
+                }
+                if points.last().is_some_and(|(f, _)| frequency <= *f) {
+                    return Err(error("frequencies must be increasing"));
+                }
+                points.push((frequency, matrix));
+                numbers.drain(..record);
+            }
+        }
+        if !numbers.is_empty() {
+            return Err(String::from("incomplete data at the end"));
+        }
+        if points.is_empty() {
+            return Err(String::from("no data"));
+        }
+        for (_, matrix) in &mut points {
+            for value in matrix {
+                match parameter {
+                    NetworkParameter::Scattering => {}
+                    NetworkParameter::Admittance => *value /= z0,
+                    NetworkParameter::Impedance => *value *= z0,
+                }
+            }
+        }
+        Ok(Self::new(parameter, z0, points))
+    }
+
+    /// Read the data of an N-port from a Touchstone file, with the
+    /// number of ports from its extension (`.s2p` for two)
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let num_ports = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase)
+            .and_then(|extension| {
+                extension
+                    .strip_prefix('s')?
+                    .strip_suffix('p')?
+                    .parse::<usize>()
+                    .ok()
+            })
+            .filter(|n| *n > 0)
+            .ok_or_else(|| {
+                let message = "the extension does not give the number of ports";
+                invalid(format!("Touchstone file {}: {message}", path.display()))
+            })?;
+        Self::parse(&fs::read_to_string(path)?, num_ports)
+            .map_err(|error| invalid(format!("Touchstone file {}: {error}", path.display())))
+    }
+
+    pub fn num_ports(&self) -> usize {
+        (self.points[0].1.len() as f64).sqrt().round() as usize
+    }
+
+    /// The parameters at a frequency, interpolated linearly between the
+    /// points, and held outside them
+    pub fn parameters(&self, frequency: f64) -> Vec<Complex<f64>> {
+        let (first, last) = (&self.points[0], &self.points[self.points.len() - 1]);
+        if frequency <= first.0 {
+            return first.1.clone();
+        }
+        if frequency >= last.0 {
+            return last.1.clone();
+        }
+        let k = self
+            .points
+            .iter()
+            .position(|(f, _)| *f > frequency)
+            .unwrap()
+            - 1;
+        let ((f1, m1), (f2, m2)) = (&self.points[k], &self.points[k + 1]);
+        let t = (frequency - f1) / (f2 - f1);
+        m1.iter().zip(m2).map(|(a, b)| a + (b - a) * t).collect()
+    }
+
+    /// The admittance matrix at a frequency, by row then column
+    pub fn admittance(&self, frequency: f64) -> Vec<Complex<f64>> {
+        let n = self.num_ports();
+        let matrix = self.parameters(frequency);
+        let identity = |k: usize| {
+            if k / n == k % n {
+                Complex::new(1.0, 0.0)
+            } else {
+                Complex::new(0.0, 0.0)
+            }
+        };
+        match self.parameter {
+            NetworkParameter::Admittance => matrix,
+            NetworkParameter::Impedance => solve(matrix, (0..n * n).map(identity).collect(), n),
+            NetworkParameter::Scattering => {
+                let plus = matrix.iter().enumerate().map(|(k, s)| identity(k) + s);
+                let minus = matrix.iter().enumerate().map(|(k, s)| identity(k) - s);
+                solve(plus.collect(), minus.collect(), n)
+                    .into_iter()
+                    .map(|y| y / self.z0)
+                    .collect()
+            }
+        }
+    }
+
+    /// The conductance matrix used in DC and transient analysis: the
+    /// real part of the admittance at the lowest frequency
+    pub fn conductance(&self) -> Vec<f64> {
+        self.admittance(self.points[0].0)
+            .iter()
+            .map(|y| y.re)
+            .collect()
+    }
+}
@@ -0,0 +1,55 @@
+//! Three-terminal transistors
+//!
+//! A transistor is a device whose currents are nonlinear functions of
+//! two control voltages between its terminals (base-emitter and
+//! base-collector for a bipolar transistor, gate-source and
+//! drain-source for a MOSFET). As for the [super::Junction]s, circuits
+//! with transistors are solved by Newton iteration: each current is
+//! replaced by its value and its derivatives by the control voltages of
+//! the previous iteration, each limited from the one before, so that
+//! the iteration does not diverge from a poor guess.
+//!
+//! The terminals of a transistor are numbered 0 to 2 (collector, base
+//! and emitter, or drain, gate and source), and the control voltages
+//! and currents are between pairs of them.
+
+/// Polarity of a transistor: NPN or N-channel, PNP or P-channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polarity {
+    N,
+    P,
+}
+
+impl Polarity {
+    /// The sign the voltages and currents of the device are multiplied
+    /// by, so that a P device is modelled as an N device
+    pub fn sign(&self) -> f64 {
+        match self {
+            Self::N => 1.0,
+            Self::P => -1.0,
+        }
+    }
+}
+
+/// A current of a transistor at its control voltages: the current
+/// (out of the first terminal, through the device, and into the
+/// second) and its derivative by each control voltage
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransistorCurrent {
+    pub terminals: (usize, usize),
+    pub current: f64,
+    pub derivatives: [f64; 2],
+}
+
+pub trait Transistor {
+    /// The terminals each control voltage is from and to
+    fn controls(&self) -> [(usize, usize); 2];
+
+    /// Limit the change in the control voltages between Newton
+    /// iterations
+    fn limit(&self, new: [f64; 2], old: [f64; 2]) -> [f64; 2];
+
+    /// The currents of the device at the control voltages, which are
+    /// the same number (between the same terminals) at every voltage
+    fn currents(&self, voltages: [f64; 2]) -> Vec<TransistorCurrent>;
+}
@@ -0,0 +1,97 @@
+//! Tunnel diode model
+//!
+//! The current is the sum of the tunnelling current
+//! $I_p (V / V_p) e^{1 - V / V_p}$, which peaks at the peak current
+//! $I_p$ at the peak voltage $V_p$ and then falls, and the diffusion
+//! current of a junction diode, $I_s (e^{V / n V_t} - 1)$, which
+//! takes over at higher voltages. Between the peak and the valley
+//! (where the falling tunnelling current meets the rising diffusion
+//! current), the differential resistance is negative.
+//!
+//! Newton iteration can jump between the branches of the
+//! characteristic, so the change in voltage between iterations is
+//! limited to a fraction of the peak voltage, as well as by the
+//! junction limit of the diffusion current.
+//!
+//! The thermal voltage of the diffusion current is taken at the
+//! temperature of the diode, which is that of the circuit unless it
+//! has its own.
+
+use super::diode::{thermal_voltage, GMIN, NOMINAL_TEMPERATURE};
+use super::limiting::{limexp, pnjlim};
+use super::Junction;
+
+/// Largest change in voltage between Newton iterations, as a multiple
+/// of the peak voltage
+const MAX_STEP: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TunnelDiodeModel {
+    /// Peak current
+    pub ip: f64,
+    /// Peak voltage
+    pub vp: f64,
+    /// Saturation current of the diffusion current
+    pub is: f64,
+    /// Emission coefficient of the diffusion current
+    pub n: f64,
+    /// Temperature of the instance using the model (degrees Celsius),
+    /// if it has its own, as set by `temp=` on its netlist line. As for
+    /// [super::DiodeModel::temp], it is kept when the card changes, and
+    /// set to the temperature of the circuit when it is elaborated.
+    pub temp: Option<f64>,
+}
+
+impl Default for TunnelDiodeModel {
+    /// A small germanium tunnel diode
+    fn default() -> Self {
+        Self {
+            ip: 1e-3,
+            vp: 0.065,
+            is: 1e-12,
+            n: 1.0,
+            temp: None,
+        }
+    }
+}
+
+impl TunnelDiodeModel {
+    /// The temperature of the diode (degrees Celsius)
+    pub fn temperature(&self) -> f64 {
+        self.temp.unwrap_or(NOMINAL_TEMPERATURE)
+    }
+
+    fn vt(&self) -> f64 {
+        self.n * thermal_voltage(self.temperature())
+    }
+
+    /// A parameter by its name (in any case), if there is one
+    pub fn parameter_mut(&mut self, name: &str) -> Option<&mut f64> {
+        match name.to_ascii_lowercase().as_str() {
+            "ip" => Some(&mut self.ip),
+            "vp" => Some(&mut self.vp),
+            "is" => Some(&mut self.is),
+            "n" => Some(&mut self.n),
+            _ => None,
+        }
+    }
+}
+
+impl Junction for TunnelDiodeModel {
+    fn evaluate(&self, voltage: f64) -> (f64, f64) {
+        let x = voltage / self.vp;
+        let tunnel = self.ip * x * (1.0 - x).exp();
+        let tunnel_conductance = self.ip / self.vp * (1.0 - x) * (1.0 - x).exp();
+        let (e, de) = limexp(voltage / self.vt());
+        (
+            tunnel + self.is * (e - 1.0) + GMIN * voltage,
+            tunnel_conductance + self.is * de / self.vt() + GMIN,
+        )
+    }
+
+    fn limit_voltage(&self, new: f64, old: f64) -> f64 {
+        let step = MAX_STEP * self.vp;
+        let new = new.clamp(old - step, old + step);
+        pnjlim(self.vt(), self.is, new, old)
+    }
+}
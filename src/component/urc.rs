@@ -0,0 +1,62 @@
+//! Uniform distributed RC line model
+//!
+//! The line is approximated by a ladder of RC lumps. The lumps are
+//! smallest at the two ends of the line and grow geometrically by the
+//! factor K towards the middle, where the line voltage varies most
+//! slowly. The lumps add up to the total resistance and capacitance
+//! of the line.
+
+use std::f64::consts::PI;
+
+/// Distributed RC line model card (the SPICE `URC` model)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UrcModel {
+    /// Propagation constant (ratio between neighbouring lumps)
+    pub k: f64,
+    /// Maximum frequency of interest, used to choose the number of
+    /// lumps (Hz)
+    pub fmax: f64,
+    /// Resistance per unit length (Ohm/m)
+    pub rperl: f64,
+    /// Capacitance per unit length (F/m)
+    pub cperl: f64,
+}
+
+impl Default for UrcModel {
+    fn default() -> Self {
+        Self {
+            k: 2.0,
+            fmax: 1e9,
+            rperl: 1000.0,
+            cperl: 1e-15,
+        }
+    }
+}
+
+impl UrcModel {
+    /// Number of lumps needed for a line of the length (m), using the
+    /// SPICE rule based on the maximum frequency
+    pub fn num_lumps(&self, length: f64) -> usize {
+        let k = self.k;
+        let wnorm = 2.0 * PI * self.fmax * self.rperl * self.cperl * length * length;
+        if wnorm < 35.0 {
+            return 3;
+        }
+        let lumps = (wnorm * ((k - 1.0) / k).powi(2)).ln() / k.ln();
+        lumps.max(3.0) as usize
+    }
+
+    /// Resistance and capacitance of each lump of a line of the length
+    /// (m), divided into the number of lumps
+    pub fn lumps(&self, length: f64, num_lumps: usize) -> Vec<(f64, f64)> {
+        let weights: Vec<f64> = (0..num_lumps)
+            .map(|i| self.k.powi(i.min(num_lumps - 1 - i) as i32))
+            .collect();
+        let total: f64 = weights.iter().sum();
+        let (r0, c0) = (self.rperl * length, self.cperl * length);
+        weights
+            .iter()
+            .map(|w| (r0 * w / total, c0 * w / total))
+            .collect()
+    }
+}
@@ -0,0 +1,421 @@
+//! Cross-checking against ngspice
+//!
+//! A netlist is simulated by esim and by ngspice (run in batch mode
+//! as a subprocess), and the node voltages of each analysis are
+//! compared. For a transient analysis, the ngspice waveforms (which
+//! are at its own time points) are interpolated at the esim time
+//! points. Each compared signal passes if every difference is within
+//! the tolerances, and the report gives the largest difference of
+//! each signal and where it occurred. The magnitudes of the node
+//! voltages are compared in an AC analysis, and the scale of a DC
+//! sweep is the swept value.
+//!
+//! The netlist is passed to ngspice as it is, apart from the `G2`
+//! flags and `#` comments, which are not SPICE, and its `.END` line,
+//! which goes after the control commands. esim integrates by the
+//! method and step control of the transient options (backward Euler
+//! with a fixed step unless they say otherwise), and ngspice by the
+//! trapezoidal rule with its own step control, so the differences
+//! show up in transient results, and the tolerances there should
+//! allow for the time step. Each node is compared by its name in the
+//! netlist (as `v(out)`), or by its number if it has none; the
+//! internal nodes of subcircuits are named as ngspice names them
+//! (such as `x1.mid`).
+//!
+//! A circuit built without a netlist can be cross-checked with
+//! [cross_check_circuit], which passes ngspice the circuit as written
+//! by [write_netlist]. Its nodes are written by name, or by number if
+//! they have none, and are compared in the same way.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::ac::{ac_sweep, Variation};
+use crate::circuit::Circuit;
+use crate::dc::operating_point;
+use crate::netlist::{parse_netlist, write_netlist, NetlistError, WriteError};
+use crate::sweep::dc_sweep;
+use crate::transient::TransientAnalysis;
+use crate::watch::Analysis;
+use crate::waveform::pwl_value;
+
+/// Number of decks written, so that each has its own files
+static NUM_DECKS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossCheckOptions {
+    /// The ngspice executable
+    pub ngspice: PathBuf,
+    pub reltol: f64,
+    pub abstol: f64,
+}
+
+impl Default for CrossCheckOptions {
+    fn default() -> Self {
+        Self {
+            ngspice: PathBuf::from("ngspice"),
+            reltol: 1e-3,
+            abstol: 1e-6,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CrossCheckError {
+    Netlist(NetlistError),
+    /// The circuit cannot be written as a SPICE netlist
+    Write(WriteError),
+    /// ngspice could not be run, or its output could not be read
+    Ngspice(io::Error),
+}
+
+impl fmt::Display for CrossCheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Netlist(error) => write!(f, "{error}"),
+            Self::Write(error) => write!(f, "{error}"),
+            Self::Ngspice(error) => write!(f, "ngspice: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for CrossCheckError {}
+
+impl From<NetlistError> for CrossCheckError {
+    fn from(error: NetlistError) -> Self {
+        Self::Netlist(error)
+    }
+}
+
+impl From<WriteError> for CrossCheckError {
+    fn from(error: WriteError) -> Self {
+        Self::Write(error)
+    }
+}
+
+impl From<io::Error> for CrossCheckError {
+    fn from(error: io::Error) -> Self {
+        Self::Ngspice(error)
+    }
+}
+
+/// Largest difference between esim and ngspice in one signal
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// Analysis, such as `op` or `tran`
+    pub analysis: String,
+    /// Signal, such as `v(out)`, or `v(2)` for a node with no name
+    pub signal: String,
+    /// Point of the scale (such as the time) of the largest difference
+    /// (zero for an operating point)
+    pub time: f64,
+    pub esim: f64,
+    pub ngspice: f64,
+    /// Whether every difference in the signal is within the
+    /// tolerances
+    pub passed: bool,
+}
+
+impl Divergence {
+    pub fn difference(&self) -> f64 {
+        (self.esim - self.ngspice).abs()
+    }
+}
+
+/// Divergence of every compared signal
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossCheckReport {
+    pub divergences: Vec<Divergence>,
+}
+
+impl CrossCheckReport {
+    /// Whether every signal is within the tolerances
+    pub fn passed(&self) -> bool {
+        self.divergences.iter().all(|d| d.passed)
+    }
+
+    /// The signals that are not within the tolerances
+    pub fn failures(&self) -> Vec<&Divergence> {
+        self.divergences.iter().filter(|d| !d.passed).collect()
+    }
+}
+
+impl fmt::Display for CrossCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<6} {:<10} {:>12} {:>14} {:>14} {:>12}",
+            "", "signal", "time", "esim", "ngspice", "difference"
+        )?;
+        for d in &self.divergences {
+            writeln!(
+                f,
+                "{:<6} {:<10} {:>12.5e} {:>14.6e} {:>14.6e} {:>12.3e} {}",
+                d.analysis,
+                d.signal,
+                d.time,
+                d.esim,
+                d.ngspice,
+                d.difference(),
+                if d.passed { "ok" } else { "FAIL" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The netlist as SPICE lines, without its `.END` line, so that
+/// control commands can be added
+fn spice_lines(netlist: &str) -> Vec<String> {
+    netlist
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#') && !line.eq_ignore_ascii_case(".end"))
+        .map(|line| {
+            line.split_whitespace()
+                .filter(|token| !token.eq_ignore_ascii_case("G2"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+/// Run an analysis in ngspice, returning the scale (time, frequency
+/// or swept value, or a single zero for an operating point) and the
+/// voltage (or its magnitude) of each node (by its name in the
+/// netlist) at each point of the scale
+fn run_ngspice(
+    netlist: &str,
+    analysis: &Analysis,
+    nodes: &[String],
+    options: &CrossCheckOptions,
+) -> io::Result<(Vec<f64>, Vec<Vec<f64>>)> {
+    let id = format!(
+        "esim-{}-{}",
+        std::process::id(),
+        NUM_DECKS.fetch_add(1, Ordering::Relaxed)
+    );
+    let deck_path = env::temp_dir().join(format!("{id}.cir"));
+    let data_path = env::temp_dir().join(format!("{id}.data"));
+    let command = match analysis {
+        Analysis::OperatingPoint => String::from("op"),
+        Analysis::DcSweep { instance, range } => format!(
+            "dc {instance} {:e} {:e} {:e}",
+            range.start, range.stop, range.step
+        ),
+        Analysis::Ac(sweep) => {
+            let variation = match sweep.variation {
+                Variation::Decade => "dec",
+                Variation::Octave => "oct",
+                Variation::Linear => "lin",
+            };
+            format!(
+                "ac {variation} {} {:e} {:e}",
+                sweep.points, sweep.start, sweep.stop
+            )
+        }
+        Analysis::Transient(options) => {
+            format!("tran {:e} {:e}", options.time_step, options.stop_time)
+        }
+    };
+    // The magnitudes are compared in an AC analysis
+    let vector = match analysis {
+        Analysis::Ac(_) => "vm",
+        _ => "v",
+    };
+    let vectors: Vec<String> = nodes
+        .iter()
+        .map(|node| format!("{vector}({node})"))
+        .collect();
+    let mut deck = vec![String::from("* esim cross-check")];
+    deck.extend(spice_lines(netlist));
+    deck.extend([
+        String::from(".control"),
+        String::from("set wr_singlescale"),
+        command,
+        format!("wrdata {} {}", data_path.display(), vectors.join(" ")),
+        String::from("quit"),
+        String::from(".endc"),
+        String::from(".end"),
+    ]);
+    fs::write(&deck_path, deck.join("\n") + "\n")?;
+    let output = Command::new(&options.ngspice)
+        .arg("-b")
+        .arg(&deck_path)
+        .output();
+    let _ = fs::remove_file(&deck_path);
+    let output = output?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let data = fs::read_to_string(&data_path);
+    let _ = fs::remove_file(&data_path);
+
+    // With a single scale, each line is the scale and then the
+    // value of each vector
+    let mut scale = Vec::new();
+    let mut values = vec![Vec::new(); nodes.len()];
+    for line in data?.lines() {
+        let numbers: Vec<f64> = line
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| io::Error::other(format!("cannot read output line: {line}")))?;
+        if numbers.len() != nodes.len() + 1 {
+            return Err(io::Error::other(format!("cannot read output line: {line}")));
+        }
+        scale.push(numbers[0]);
+        for (values, value) in values.iter_mut().zip(&numbers[1..]) {
+            values.push(*value);
+        }
+    }
+    Ok((scale, values))
+}
+
+/// Run an analysis in esim, returning the points of the scale, as for
+/// [run_ngspice], and the voltage of each node at each point
+fn run_esim(circuit: &Circuit, analysis: &Analysis, nodes: &[usize]) -> (Vec<f64>, Vec<Vec<f64>>) {
+    match analysis {
+        Analysis::OperatingPoint => {
+            let solution = operating_point(circuit);
+            let values = nodes.iter().map(|n| vec![solution.voltage(*n)]).collect();
+            (vec![0.0], values)
+        }
+        Analysis::DcSweep { instance, range } => {
+            let result = dc_sweep(circuit, instance, range);
+            let values = nodes.iter().map(|n| result.voltage(*n)).collect();
+            (result.values, values)
+        }
+        Analysis::Ac(sweep) => {
+            let result = ac_sweep(circuit, sweep);
+            let values = nodes
+                .iter()
+                .map(|n| result.voltage(*n).iter().map(|v| v.norm()).collect())
+                .collect();
+            (result.frequencies, values)
+        }
+        Analysis::Transient(options) => {
+            let result = TransientAnalysis::new(circuit, *options).run();
+            let values = nodes.iter().map(|n| result.voltage(*n)).collect();
+            (result.time, values)
+        }
+    }
+}
+
+/// Compare the node voltages of a netlist from esim and ngspice in
+/// each analysis (all the nodes of the circuit if none are given)
+pub fn cross_check(
+    netlist: &str,
+    analyses: &[Analysis],
+    nodes: &[usize],
+    options: &CrossCheckOptions,
+) -> Result<CrossCheckReport, CrossCheckError> {
+    let circuit = parse_netlist(netlist)?;
+    compare(&circuit, netlist, true, analyses, nodes, options)
+}
+
+/// Compare the node voltages of a circuit from esim and ngspice in
+/// each analysis, passing ngspice the circuit as written by
+/// [write_netlist] (all the nodes of the circuit if none are given)
+pub fn cross_check_circuit(
+    circuit: &Circuit,
+    analyses: &[Analysis],
+    nodes: &[usize],
+    options: &CrossCheckOptions,
+) -> Result<CrossCheckReport, CrossCheckError> {
+    let netlist = write_netlist(circuit)?;
+    compare(circuit, &netlist, true, analyses, nodes, options)
+}
+
+/// Name of a node in the netlist of a circuit: its name, if the
+/// netlist uses the names of the circuit and it has one, or else its
+/// number
+fn node_name(circuit: &Circuit, node: usize, named: bool) -> String {
+    match circuit.node_names().name(node) {
+        Some(name) if named => name.to_string(),
+        _ => node.to_string(),
+    }
+}
+
+/// Compare the node voltages of a circuit from esim with those of its
+/// netlist from ngspice, in which the nodes are named if named
+fn compare(
+    circuit: &Circuit,
+    netlist: &str,
+    named: bool,
+    analyses: &[Analysis],
+    nodes: &[usize],
+    options: &CrossCheckOptions,
+) -> Result<CrossCheckReport, CrossCheckError> {
+    let nodes: Vec<usize> = if nodes.is_empty() {
+        (1..=circuit.num_voltage_nodes()).collect()
+    } else {
+        nodes.to_vec()
+    };
+    let names: Vec<String> = nodes
+        .iter()
+        .map(|node| node_name(circuit, *node, named))
+        .collect();
+    let mut divergences = Vec::new();
+    for analysis in analyses {
+        let (time, esim) = run_esim(circuit, analysis, &nodes);
+        let (scale, ngspice) = run_ngspice(netlist, analysis, &names, options)?;
+        let name = analysis.name();
+        for ((node, esim), ngspice) in names.iter().zip(esim).zip(ngspice) {
+            let points: Vec<(f64, f64)> = scale.iter().copied().zip(ngspice).collect();
+            let mut divergence = Divergence {
+                analysis: name.to_string(),
+                signal: format!("v({node})"),
+                time: 0.0,
+                esim: 0.0,
+                ngspice: 0.0,
+                passed: true,
+            };
+            let mut largest = -1.0;
+            for (t, value) in time.iter().zip(esim) {
+                let reference = pwl_value(&points, *t);
+                let difference = (value - reference).abs();
+                if difference > options.abstol + options.reltol * reference.abs() {
+                    divergence.passed = false;
+                }
+                if difference > largest {
+                    largest = difference;
+                    divergence.time = *t;
+                    divergence.esim = value;
+                    divergence.ngspice = reference;
+                }
+            }
+            divergences.push(divergence);
+        }
+    }
+    Ok(CrossCheckReport { divergences })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spice_lines_drop_the_end_and_esim_syntax() {
+        let netlist = "# esim comment\nV1 in 0 G2 1\nR1 in out 1k\n.end\n";
+        assert_eq!(spice_lines(netlist), ["V1 in 0 1", "R1 in out 1k"]);
+    }
+
+    #[test]
+    fn nodes_are_compared_by_name() {
+        let circuit = parse_netlist("V1 in 0 1\nR1 in 2 1k\nR2 2 0 1k\n").unwrap();
+        let input = circuit.node_names().get("in").unwrap();
+        assert_eq!(node_name(&circuit, input, true), "in");
+        assert_eq!(node_name(&circuit, input, false), input.to_string());
+        assert_eq!(node_name(&circuit, 2, true), "2");
+    }
+}
@@ -1,42 +1,908 @@
 //! DC analysis
+//!
+//! Circuits with nonlinear components (such as diodes) are solved by
+//! Newton iteration (see [crate::solver]): each iteration solves the
+//! circuit with the junctions linearised about the voltages of the
+//! previous one, until the junction voltages settle.
+//!
+//! As a convergence aid, the Newton iterates of chosen nodes can be
+//! clamped to physically plausible ranges (such as 0 to 5 V for a
+//! logic node). The clamps only steer the iteration: once it settles,
+//! it is continued without them, so the solution is that of the
+//! unclamped circuit.
+//!
+//! Nodesets (SPICE `.NODESET`) give approximate voltages of nodes.
+//! The iteration is first solved with each of these nodes held near
+//! its voltage, and then continued from there with the nodes
+//! released. Like the clamps, they only choose where the iteration
+//! starts, which decides the state found in a circuit with more than
+//! one (such as a latch).
+//!
+//! If Newton iteration does not converge, the operating point is
+//! found by gmin stepping (unless the options prevent it), as in SPICE:
+//! a conductance from every node to ground is reduced in steps from a
+//! large value, each solved from the last, and then removed. If that
+//! fails too, it is found by pseudo-transient continuation, which
+//! integrates the circuit to its steady state with a capacitor from
+//! every node to ground, for circuits (such as latches and ring
+//! oscillators) whose iteration does not settle by either.
 
+use std::collections::HashMap;
+
+use crate::circuit::Circuit;
+use crate::component::{AcSpec, Component, NOMINAL_TEMPERATURE};
+use crate::error::EsimError;
+use crate::evaluation::{linearise_junctions, JunctionCache};
 use crate::mna::Mna;
-use csuperlu::c::value_type::ValueType;
+use crate::node::NodeNames;
+use crate::solver::{transistor_branches, Iterate, Linearisation, NonlinearSolver};
+use crate::sparse::{CachedSolver, ValueType};
 use num;
 
 pub struct LinearDcAnalysis<P: ValueType + num::Float> {
     mna: Mna<P>,
 }
 
+impl<P: ValueType + num::Float> Default for LinearDcAnalysis<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<P: ValueType + num::Float> LinearDcAnalysis<P> {
     pub fn new() -> Self {
-	Self {
-	    mna: Mna::new(),
-	}
+        Self { mna: Mna::new() }
     }
 
     pub fn add_resistor(
-	&mut self,
-	term_1: usize,
-	term_2: usize,
-	current_edge: Option<usize>,
-	resistance: P,
+        &mut self,
+        term_1: usize,
+        term_2: usize,
+        current_edge: Option<usize>,
+        resistance: P,
     ) {
-	self.mna.add_resistor(term_1, term_2, current_edge, resistance);
+        self.mna
+            .add_resistor(term_1, term_2, current_edge, resistance);
     }
 
     pub fn add_independent_voltage_source(
-	&mut self,
-	term_pos: usize,
-	term_neg: usize,
-	current_edge: usize,
-	voltage: P,
+        &mut self,
+        term_pos: usize,
+        term_neg: usize,
+        current_edge: usize,
+        voltage: P,
     ) {
-	self.mna.add_independent_voltage_source(term_pos, term_neg, current_edge, voltage);
+        self.mna
+            .add_independent_voltage_source(term_pos, term_neg, current_edge, voltage);
+    }
+
+    pub fn add_series_voltage(&mut self, current_edge: usize, voltage: P) {
+        self.mna.add_series_voltage(current_edge, voltage);
+    }
+
+    pub fn add_admittance(&mut self, term_1: usize, term_2: usize, admittance: P) {
+        self.mna.add_admittance(term_1, term_2, admittance);
+    }
+
+    pub fn add_independent_current_source(&mut self, term_pos: usize, term_neg: usize, current: P) {
+        self.mna
+            .add_independent_current_source(term_pos, term_neg, current);
+    }
+
+    pub fn add_admittance_matrix(&mut self, ports: &[(usize, usize)], admittance: &[P]) {
+        self.mna.add_admittance_matrix(ports, admittance);
     }
 
     pub fn solve(self) -> (Vec<P>, Vec<P>) {
-	self.mna.solve()
+        self.mna.solve()
+    }
+
+    /// Solve as for [LinearDcAnalysis::solve], returning an error
+    /// instead of panicking if the system cannot be solved
+    pub fn try_solve(self) -> Result<(Vec<P>, Vec<P>), EsimError> {
+        self.mna.try_solve()
+    }
+
+    /// Solve as for [LinearDcAnalysis::try_solve] with a solver kept
+    /// between systems of the same structure (see [CachedSolver])
+    pub fn try_solve_with(
+        self,
+        solver: &mut CachedSolver<P>,
+    ) -> Result<(Vec<P>, Vec<P>), EsimError> {
+        self.mna.try_solve_with(solver)
+    }
+
+    pub(crate) fn mna_mut(&mut self) -> &mut Mna<P> {
+        &mut self.mna
+    }
+}
+
+impl<P: ValueType + num::Float + From<f64>> LinearDcAnalysis<P> {
+    pub fn add_controlled_source(&mut self, component: &Component) {
+        self.mna.add_controlled_source(component);
+    }
+}
+
+impl LinearDcAnalysis<f64> {
+    /// Assemble the DC system for a circuit
+    ///
+    /// Sources take their DC values (their AC specifications are
+    /// ignored), capacitors are open circuits, inductors are short
+    /// circuits, fuses are intact, relays are open, thyristors and
+    /// IGBTs are off, and batteries are at their initial state of
+    /// charge. Junctions and transistors are linearised at zero volts.
+    /// The circuit is elaborated first.
+    pub fn from_circuit(circuit: &Circuit) -> Self {
+        let circuit = circuit.elaborate();
+        let junctions = linearise_junctions(&circuit, &[]);
+        let transistors = transistor_branches(&circuit, &[]);
+        Linearisation::new(&junctions, &[], &transistors).dc(&circuit)
+    }
+
+    /// Assemble the DC system for an elaborated circuit, with the
+    /// linearisation (conductance and equivalent current) of each
+    /// junction, indexed by instance
+    pub(crate) fn linearised(circuit: &Circuit, linearised: &[Option<(f64, f64)>]) -> Self {
+        let mut dc = Self::new();
+        for (index, instance) in circuit.instances().iter().enumerate() {
+            match instance.component {
+                Component::Resistor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    resistance,
+                } => dc.add_resistor(term_1, term_2, current_edge, resistance),
+                Component::Thermistor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    model,
+                    temperature,
+                } => {
+                    let resistance = model.resistance(temperature.unwrap_or(NOMINAL_TEMPERATURE));
+                    dc.add_resistor(term_1, term_2, current_edge, resistance)
+                }
+                Component::Capacitor { .. } => {}
+                Component::Inductor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    ..
+                }
+                | Component::SaturableInductor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    ..
+                } => dc.add_resistor(term_1, term_2, Some(current_edge), 0.0),
+                Component::Crystal { .. }
+                | Component::Urc { .. }
+                | Component::Supercapacitor { .. }
+                | Component::SemiconductorResistor { .. }
+                | Component::SemiconductorCapacitor { .. } => {
+                    unreachable!("Macromodels are expanded by elaborate()")
+                }
+                Component::Diode { .. }
+                | Component::Photodiode { .. }
+                | Component::TunnelDiode { .. }
+                | Component::SchottkyDiode { .. }
+                | Component::Table { .. }
+                | Component::Compact { .. } => {
+                    let (anode, cathode, _) = instance.component.junction().unwrap();
+                    let (conductance, current) = linearised[index].unwrap();
+                    dc.add_admittance(anode, cathode, conductance);
+                    dc.add_independent_current_source(anode, cathode, current);
+                    if let Component::Photodiode {
+                        responsivity,
+                        irradiance,
+                        ..
+                    } = instance.component
+                    {
+                        dc.add_independent_current_source(
+                            cathode,
+                            anode,
+                            responsivity * irradiance,
+                        );
+                    }
+                }
+                // The transistors are added with the device stamps (see
+                // crate::solver)
+                Component::Bjt { .. } | Component::Mosfet { .. } => {}
+                Component::NPort {
+                    ref ports,
+                    ref data,
+                } => dc.add_admittance_matrix(ports, &data.conductance()),
+                Component::Fuse {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    params,
+                } => dc.add_resistor(term_1, term_2, Some(current_edge), params.r_on),
+                Component::Relay {
+                    coil_pos,
+                    coil_neg,
+                    contact_1,
+                    contact_2,
+                    coil_edge,
+                    contact_edge,
+                    params,
+                } => {
+                    dc.add_resistor(coil_pos, coil_neg, Some(coil_edge), params.r_coil);
+                    dc.add_resistor(contact_1, contact_2, Some(contact_edge), params.r_off);
+                }
+                Component::Thyristor {
+                    anode,
+                    cathode,
+                    gate,
+                    current_edge,
+                    params,
+                    ..
+                } => {
+                    dc.add_resistor(anode, cathode, Some(current_edge), params.r_off);
+                    dc.add_resistor(gate, cathode, None, params.r_gate);
+                }
+                Component::Battery {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    ref model,
+                    soc,
+                } => {
+                    dc.add_resistor(term_pos, term_neg, Some(current_edge), model.r_int);
+                    dc.add_series_voltage(current_edge, model.open_circuit_voltage(soc));
+                }
+                Component::LogicGate { .. } | Component::AdcBridge { .. } => {}
+                Component::DacBridge {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    family,
+                    ..
+                } => {
+                    dc.add_resistor(term_pos, term_neg, Some(current_edge), family.r_out);
+                    dc.add_series_voltage(current_edge, family.output_voltage(false));
+                }
+                Component::IndependentVoltageSource {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    voltage,
+                    ..
+                } => dc.add_independent_voltage_source(term_pos, term_neg, current_edge, voltage),
+                Component::CurrentProbe {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                } => dc.add_independent_voltage_source(term_pos, term_neg, current_edge, 0.0),
+                Component::Igbt {
+                    collector,
+                    emitter,
+                    current_edge,
+                    params,
+                    ..
+                } => dc.add_resistor(collector, emitter, Some(current_edge), params.r_off),
+                Component::MeasurementProbe {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    ..
+                } => {
+                    if let Some(current_edge) = current_edge {
+                        dc.add_independent_voltage_source(term_pos, term_neg, current_edge, 0.0)
+                    }
+                }
+                Component::IndependentCurrentSource {
+                    term_pos,
+                    term_neg,
+                    current,
+                    ..
+                } => dc.add_independent_current_source(term_pos, term_neg, current),
+                Component::VoltageControlledVoltageSource { .. }
+                | Component::VoltageControlledCurrentSource { .. }
+                | Component::CurrentControlledCurrentSource { .. }
+                | Component::CurrentControlledVoltageSource { .. } => {
+                    dc.add_controlled_source(&instance.component)
+                }
+            }
+        }
+        dc
+    }
+}
+
+/// Convergence settings of Newton iteration: it has converged when
+/// every junction voltage changes by less than `reltol` times its
+/// magnitude plus `vntol` in an iteration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NewtonOptions {
+    pub reltol: f64,
+    /// Absolute tolerance of the junction voltages (V)
+    pub vntol: f64,
+    /// Largest number of iterations (SPICE `ITL1` in DC analysis and
+    /// `ITL4` at each time point of a transient analysis)
+    pub max_iterations: usize,
+}
+
+impl Default for NewtonOptions {
+    fn default() -> Self {
+        Self {
+            reltol: 1e-3,
+            vntol: 1e-6,
+            max_iterations: 100,
+        }
+    }
+}
+
+impl NewtonOptions {
+    pub(crate) fn converged(&self, new: f64, old: f64) -> bool {
+        (new - old).abs() <= self.reltol * new.abs().max(old.abs()) + self.vntol
+    }
+}
+
+/// The warning of a Newton iteration that did not converge
+pub(crate) fn not_converged(options: &NewtonOptions) -> String {
+    format!(
+        "Newton iteration did not converge after {} iterations",
+        options.max_iterations
+    )
+}
+
+/// Options for solving the operating point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DcOptions {
+    /// Whether to retry by gmin stepping if Newton iteration does not
+    /// converge
+    pub gmin_stepping: bool,
+    /// Conductance from each node to ground in the first gmin step
+    pub gmin_start: f64,
+    /// Conductance in the last gmin step, after which it is removed
+    pub gmin: f64,
+    /// Number of gmin steps, with the conductance reduced by the same
+    /// ratio in each
+    pub gmin_steps: usize,
+    /// Whether to retry by pseudo-transient continuation if gmin
+    /// stepping does not converge either
+    pub pseudo_transient: bool,
+    /// Largest number of pseudo-transient steps
+    pub pseudo_transient_steps: usize,
+    /// Convergence settings of each Newton iteration
+    pub newton: NewtonOptions,
+}
+
+impl Default for DcOptions {
+    /// gmin stepping from 1 mS to the SPICE gmin of 1 pS, a decade at a
+    /// time, then up to 200 pseudo-transient steps
+    fn default() -> Self {
+        Self {
+            gmin_stepping: true,
+            gmin_start: 1e-3,
+            gmin: 1e-12,
+            gmin_steps: 10,
+            pseudo_transient: true,
+            pseudo_transient_steps: 200,
+            newton: NewtonOptions::default(),
+        }
+    }
+}
+
+/// Conductance of the capacitor from each node to ground over the
+/// first pseudo-transient step (its capacitance over the step), and
+/// the factors by which the step is lengthened after a step that
+/// converges, and shortened after one that does not
+const PSEUDO_TRANSIENT_CONDUCTANCE: f64 = 1.0;
+const STEP_GROWTH: f64 = 2.0;
+const STEP_CUT: f64 = 8.0;
+
+/// Solve an elaborated circuit by Newton iteration, and if that does
+/// not converge, with the aids allowed by the options: first gmin
+/// stepping, then pseudo-transient continuation. The junction voltages
+/// start from their values on entry, and are left at their final
+/// values. The last solution tried is returned if none converges,
+/// with a warning. Panics if the circuit cannot be solved.
+pub(crate) fn newton_aided(
+    circuit: &Circuit,
+    junctions: &mut Vec<f64>,
+    cache: &mut JunctionCache,
+    options: &DcOptions,
+    warnings: &mut Vec<String>,
+) -> (Vec<f64>, Vec<f64>) {
+    let iterate = try_newton_aided(circuit, junctions, cache, options)
+        .unwrap_or_else(|error| panic!("{error}"));
+    if !iterate.converged {
+        warnings.push(not_converged(&options.newton));
+    }
+    iterate.solution
+}
+
+/// Solve an elaborated circuit as for [newton_aided], returning the
+/// last iterate tried, or an error if a linear system could not be
+/// solved
+fn try_newton_aided(
+    circuit: &Circuit,
+    junctions: &mut Vec<f64>,
+    cache: &mut JunctionCache,
+    options: &DcOptions,
+) -> Result<Iterate, EsimError> {
+    let start = junctions.clone();
+    let linear = &mut CachedSolver::default();
+    let mut iterate = newton_dc(circuit, junctions, cache, linear, options)?;
+    if !iterate.converged && options.gmin_stepping {
+        junctions.clone_from(&start);
+        iterate = gmin_stepping(circuit, junctions, cache, linear, options)?;
+    }
+    if !iterate.converged && options.pseudo_transient {
+        junctions.clone_from(&start);
+        iterate = pseudo_transient(circuit, junctions, cache, linear, options)?;
+    }
+    Ok(iterate)
+}
+
+/// Newton iteration of the DC system of an elaborated circuit (see
+/// [NonlinearSolver::iterate]), solving each linear system with the
+/// solver kept through the analysis
+fn newton_dc(
+    circuit: &Circuit,
+    junctions: &mut Vec<f64>,
+    cache: &mut JunctionCache,
+    linear: &mut CachedSolver<f64>,
+    options: &DcOptions,
+) -> Result<Iterate, EsimError> {
+    NonlinearSolver::from(options.newton).iterate(
+        circuit,
+        &[],
+        junctions,
+        cache,
+        &mut [],
+        |linearisation| linearisation.dc(circuit).try_solve_with(linear),
+    )
+}
+
+/// Solve an elaborated circuit by gmin stepping: a conductance from
+/// every node to ground makes the iteration converge more easily, and
+/// is reduced in steps, each solved from the solution of the step
+/// before, until it is removed for the last solve. Returns the last
+/// iterate.
+fn gmin_stepping(
+    circuit: &Circuit,
+    junctions: &mut Vec<f64>,
+    cache: &mut JunctionCache,
+    linear: &mut CachedSolver<f64>,
+    options: &DcOptions,
+) -> Result<Iterate, EsimError> {
+    let steps = options.gmin_steps.max(1);
+    let ratio = (options.gmin / options.gmin_start).powf(1.0 / (steps - 1).max(1) as f64);
+    for step in 0..steps {
+        let gmin = options.gmin_start * ratio.powi(step as i32);
+        let mut stepped = circuit.clone();
+        for node in 1..=circuit.num_voltage_nodes() {
+            stepped.add_component(
+                &format!("gmin({node})"),
+                Component::Resistor {
+                    term_1: node,
+                    term_2: 0,
+                    current_edge: None,
+                    resistance: 1.0 / gmin,
+                },
+            );
+        }
+        newton_dc(&stepped, junctions, cache, linear, options)?;
+    }
+    newton_dc(circuit, junctions, cache, linear, options)
+}
+
+/// Solve an elaborated circuit by pseudo-transient continuation: with
+/// a capacitor from every node to ground, the circuit is integrated
+/// by backward Euler from zero volts, with the step lengthened while
+/// each converges, until the node voltages settle, and then solved
+/// from there without the capacitors. Each step is a Newton solve with
+/// the capacitors replaced by their companion models (a conductance in
+/// parallel with a current source, as a held node is). Returns the
+/// last iterate.
+fn pseudo_transient(
+    circuit: &Circuit,
+    junctions: &mut Vec<f64>,
+    cache: &mut JunctionCache,
+    linear: &mut CachedSolver<f64>,
+    options: &DcOptions,
+) -> Result<Iterate, EsimError> {
+    let mut voltages = vec![0.0; circuit.num_voltage_nodes()];
+    let mut conductance = PSEUDO_TRANSIENT_CONDUCTANCE;
+    for _ in 0..options.pseudo_transient_steps {
+        let mut stepped = circuit.clone();
+        for (node, voltage) in (1..).zip(&voltages) {
+            let name = format!("ptran({node})");
+            stepped.add_component(
+                &name,
+                Component::Resistor {
+                    term_1: node,
+                    term_2: 0,
+                    current_edge: None,
+                    resistance: 1.0 / conductance,
+                },
+            );
+            stepped.add_component(
+                &name,
+                Component::IndependentCurrentSource {
+                    term_pos: 0,
+                    term_neg: node,
+                    current: conductance * voltage,
+                    ac: AcSpec::default(),
+                    waveform: None,
+                },
+            );
+        }
+        let saved = junctions.clone();
+        let step = newton_dc(&stepped, junctions, cache, linear, options)?;
+        if !step.converged {
+            junctions.clone_from(&saved);
+            conductance *= STEP_CUT;
+            continue;
+        }
+        let (next, _) = step.solution;
+        let settled = next
+            .iter()
+            .zip(&voltages)
+            .all(|(new, old)| options.newton.converged(*new, *old));
+        voltages = next;
+        conductance /= STEP_GROWTH;
+        if settled {
+            let mut trial = junctions.clone();
+            let iterate = newton_dc(circuit, &mut trial, cache, linear, options)?;
+            if iterate.converged {
+                *junctions = trial;
+                return Ok(iterate);
+            }
+        }
+    }
+    newton_dc(circuit, junctions, cache, linear, options)
+}
+
+fn node_voltage(voltages: &[f64], node: usize) -> f64 {
+    if node == 0 {
+        0.0
+    } else {
+        voltages[node - 1]
+    }
+}
+
+/// Conductance of the Norton equivalent holding a node at a voltage
+/// (as ngspice holds initial conditions)
+const HOLD_CONDUCTANCE: f64 = 1e10;
+
+/// Hold nodes of an elaborated circuit near voltages, each by a large
+/// conductance to ground in parallel with a current source. The
+/// components are added after the others, which keep their indices.
+pub(crate) fn hold_nodes(circuit: &mut Circuit, nodes: &[(usize, f64)]) {
+    for (node, voltage) in nodes {
+        let name = format!("hold({node})");
+        circuit.add_component(
+            &name,
+            Component::Resistor {
+                term_1: *node,
+                term_2: 0,
+                current_edge: None,
+                resistance: 1.0 / HOLD_CONDUCTANCE,
+            },
+        );
+        circuit.add_component(
+            &name,
+            Component::IndependentCurrentSource {
+                term_pos: 0,
+                term_neg: *node,
+                current: HOLD_CONDUCTANCE * voltage,
+                ac: AcSpec::default(),
+                waveform: None,
+            },
+        );
+    }
+}
+
+/// Solve the DC operating point of an elaborated circuit, returning
+/// the node voltages and edge currents, and adding its warnings
+pub(crate) fn solve_elaborated(
+    circuit: &Circuit,
+    warnings: &mut Vec<String>,
+) -> (Vec<f64>, Vec<f64>) {
+    solve_elaborated_options(circuit, &DcOptions::default(), warnings)
+}
+
+/// Solve the DC operating point of an elaborated circuit with options
+pub(crate) fn solve_elaborated_options(
+    circuit: &Circuit,
+    options: &DcOptions,
+    warnings: &mut Vec<String>,
+) -> (Vec<f64>, Vec<f64>) {
+    newton_aided(
+        circuit,
+        &mut Vec::new(),
+        &mut JunctionCache::default(),
+        options,
+        warnings,
+    )
+}
+
+/// Solve the DC operating point of an elaborated circuit with the
+/// nodes of the nodesets (node and voltage) first held near their
+/// voltages, then released. The junction voltages start from their
+/// values on entry, and are left at their final values. An iteration
+/// that does not converge adds a warning, and one whose linear system
+/// cannot be solved is an error.
+pub(crate) fn solve_nodesets(
+    circuit: &Circuit,
+    junctions: &mut Vec<f64>,
+    nodesets: &[(usize, f64)],
+    options: &DcOptions,
+    warnings: &mut Vec<String>,
+) -> Result<(Vec<f64>, Vec<f64>), EsimError> {
+    let mut cache = JunctionCache::default();
+    if !nodesets.is_empty() {
+        let mut held = circuit.clone();
+        hold_nodes(&mut held, nodesets);
+        let linear = &mut CachedSolver::default();
+        let iterate = newton_dc(&held, junctions, &mut cache, linear, options)?;
+        if !iterate.converged {
+            warnings.push(not_converged(&options.newton));
+        }
+    }
+    let iterate = try_newton_aided(circuit, junctions, &mut cache, options)?;
+    if !iterate.converged {
+        warnings.push(not_converged(&options.newton));
+    }
+    Ok(iterate.solution)
+}
+
+/// A range that the Newton iterates of a node voltage are clamped to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeClamp {
+    pub node: usize,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl NodeClamp {
+    /// Panics if the node is ground or the range is empty
+    pub fn new(node: usize, min: f64, max: f64) -> Self {
+        assert!(node != 0, "Cannot clamp the ground node");
+        assert!(min <= max, "Clamp range must not be empty");
+        Self { node, min, max }
+    }
+}
+
+/// How often a node clamp was active during Newton iteration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClampActivity {
+    pub clamp: NodeClamp,
+    /// Number of iterations in which the clamp changed the iterate
+    pub iterations: usize,
+}
+
+/// Solution of the DC analysis of a circuit
+#[derive(Debug, Clone)]
+pub struct DcSolution {
+    /// Node voltages, excluding ground (node n is at index n-1)
+    pub voltages: Vec<f64>,
+    /// Edge currents
+    pub currents: Vec<f64>,
+    /// Current edges of the current probes, by name
+    probes: HashMap<String, usize>,
+    nodes: NodeNames,
+    /// Warnings of the solve, such as a Newton iteration that did not
+    /// converge
+    pub warnings: Vec<String>,
+}
+
+impl DcSolution {
+    /// Voltage of a node (zero for ground)
+    pub fn voltage(&self, node: usize) -> f64 {
+        node_voltage(&self.voltages, node)
+    }
+
+    /// Voltage of a node by name (see [crate::node]), if there is a
+    /// node with the name in the circuit
+    pub fn node_voltage(&self, name: &str) -> Option<f64> {
+        self.nodes
+            .get(name)
+            .filter(|node| *node <= self.voltages.len())
+            .map(|node| self.voltage(node))
+    }
+
+    /// Current through a current probe, from its positive to its
+    /// negative terminal
+    pub fn probe_current(&self, name: &str) -> Option<f64> {
+        self.probes.get(name).map(|e| self.currents[*e])
+    }
+}
+
+/// Attach the current probes and node names of an elaborated circuit
+/// to its solution, with the warnings of solving it
+pub(crate) fn dc_solution(
+    circuit: &Circuit,
+    (voltages, currents): (Vec<f64>, Vec<f64>),
+    warnings: Vec<String>,
+) -> DcSolution {
+    let probes = circuit
+        .instances()
+        .iter()
+        .filter_map(|instance| match instance.component {
+            Component::CurrentProbe { current_edge, .. } => {
+                Some((instance.name.clone(), current_edge))
+            }
+            _ => None,
+        })
+        .collect();
+    DcSolution {
+        voltages,
+        currents,
+        probes,
+        nodes: circuit.node_names().clone(),
+        warnings,
+    }
+}
+
+/// Solve the DC operating point of a circuit. If the iteration does
+/// not converge, the last solution tried is given with a warning (see
+/// [DcSolution::warnings]).
+pub fn operating_point(circuit: &Circuit) -> DcSolution {
+    operating_point_options(circuit, &DcOptions::default())
+}
+
+/// Solve the DC operating point of a circuit with options, such as
+/// those of gmin stepping
+pub fn operating_point_options(circuit: &Circuit, options: &DcOptions) -> DcSolution {
+    let elaborated = circuit.elaborate();
+    let mut warnings = Vec::new();
+    let solution = solve_elaborated_options(&elaborated, options, &mut warnings);
+    dc_solution(&elaborated, solution, warnings)
+}
+
+/// Solve the DC operating point of a circuit with options, returning
+/// an error instead of panicking or warning if it cannot be solved:
+/// the circuit is checked first (see [Circuit::validate]), a failed
+/// solve is reported as the topology problems of the circuit that
+/// explain it (see [crate::topology]), if there are any, and an
+/// iteration that does not converge as a convergence error
+pub fn try_operating_point(
+    circuit: &Circuit,
+    options: &DcOptions,
+) -> Result<DcSolution, EsimError> {
+    circuit.validate()?;
+    let elaborated = circuit.elaborate();
+    try_solve_elaborated(&elaborated, options)
+        .map(|solution| dc_solution(&elaborated, solution, Vec::new()))
+        .map_err(|error| explain(circuit, error))
+}
+
+/// Solve the DC operating point of an elaborated circuit with options,
+/// returning an error if a linear system cannot be solved or the
+/// iteration does not converge
+pub(crate) fn try_solve_elaborated(
+    circuit: &Circuit,
+    options: &DcOptions,
+) -> Result<(Vec<f64>, Vec<f64>), EsimError> {
+    let iterate = try_newton_aided(
+        circuit,
+        &mut Vec::new(),
+        &mut JunctionCache::default(),
+        options,
+    )?;
+    if !iterate.converged {
+        return Err(EsimError::Convergence {
+            iterations: options.newton.max_iterations,
+        });
+    }
+    Ok(iterate.solution)
+}
+
+/// The error of a fallible analysis of a circuit, with a failed solve
+/// reported as the topology problems of the circuit that explain it
+/// (see [crate::topology]), if there are any
+pub(crate) fn explain(circuit: &Circuit, error: EsimError) -> EsimError {
+    match error {
+        EsimError::Solve { message } => {
+            let violations = circuit.check();
+            if violations.is_empty() {
+                EsimError::Solve { message }
+            } else {
+                EsimError::Topology(violations)
+            }
+        }
+        error => error,
+    }
+}
+
+/// Solve the DC operating point of a circuit from nodesets, given as
+/// the node and its approximate voltage. Panics if a node is ground
+/// or not in the circuit.
+pub fn operating_point_nodesets(circuit: &Circuit, nodesets: &[(usize, f64)]) -> DcSolution {
+    let elaborated = circuit.elaborate();
+    for (node, _) in nodesets {
+        assert!(
+            *node != 0 && *node <= elaborated.num_voltage_nodes(),
+            "No node {node} for a nodeset"
+        );
+    }
+    let mut warnings = Vec::new();
+    let solution = solve_nodesets(
+        &elaborated,
+        &mut Vec::new(),
+        nodesets,
+        &DcOptions::default(),
+        &mut warnings,
+    )
+    .unwrap_or_else(|error| panic!("{error}"));
+    dc_solution(&elaborated, solution, warnings)
+}
+
+/// Solve the DC operating point of a circuit with the Newton iterates
+/// of some nodes clamped to ranges. When the clamped iteration
+/// settles, it is continued without the clamps to give the solution.
+/// Returns the solution and the clamps that were active.
+pub fn operating_point_clamped(
+    circuit: &Circuit,
+    clamps: &[NodeClamp],
+) -> (DcSolution, Vec<ClampActivity>) {
+    let elaborated = circuit.elaborate();
+    let mut activity: Vec<ClampActivity> = clamps
+        .iter()
+        .map(|clamp| ClampActivity {
+            clamp: *clamp,
+            iterations: 0,
+        })
+        .collect();
+    let mut junctions = Vec::new();
+    let mut cache = JunctionCache::default();
+    let solver = NonlinearSolver::from(NewtonOptions::default());
+    let mut linear = CachedSolver::default();
+    let mut warnings = Vec::new();
+    let mut newton = |clamps: &mut [ClampActivity]| {
+        let iterate = solver
+            .iterate(
+                &elaborated,
+                &[],
+                &mut junctions,
+                &mut cache,
+                clamps,
+                |linearisation| linearisation.dc(&elaborated).try_solve_with(&mut linear),
+            )
+            .unwrap_or_else(|error| panic!("{error}"));
+        if !iterate.converged {
+            warnings.push(not_converged(&solver.options.newton));
+        }
+        iterate.solution
+    };
+    newton(&mut activity);
+    let solution = newton(&mut []);
+    activity.retain(|activity| activity.iterations > 0);
+    (dc_solution(&elaborated, solution, warnings), activity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CircuitBuilder;
+
+    #[test]
+    fn an_iteration_that_does_not_converge_is_a_warning() {
+        let circuit = CircuitBuilder::new()
+            .vsource("V1", "in", "0", 5.0)
+            .resistor("R1", "in", "a", 1e3)
+            .diode("D1", "a", "0")
+            .build()
+            .unwrap();
+        let solution = operating_point(&circuit);
+        assert!(solution.warnings.is_empty());
+        let options = DcOptions {
+            gmin_stepping: false,
+            pseudo_transient: false,
+            newton: NewtonOptions {
+                max_iterations: 1,
+                ..NewtonOptions::default()
+            },
+            ..DcOptions::default()
+        };
+        let solution = operating_point_options(&circuit, &options);
+        assert_eq!(
+            solution.warnings,
+            ["Newton iteration did not converge after 1 iterations"]
+        );
     }
-    
 }
@@ -0,0 +1,232 @@
+//! Debugging the Newton iteration of a transient analysis
+//!
+//! A transient analysis can be run under a [Debugger] (see
+//! [TransientAnalysis::debug](crate::transient::TransientAnalysis::debug)),
+//! which is paused when one of a list of [Breakpoint]s is hit, such as
+//! a time being reached, a node voltage crossing a level, or a time
+//! point taking too many Newton iterations. While paused, the debugger
+//! is called after every Newton iteration with the state of the
+//! iteration ([NewtonState]): the new iterate and the change in each
+//! node voltage from the previous one, the row of the Jacobian (the
+//! MNA matrix of the linearised circuit) for a node, and the stamp of
+//! each device. It then either steps to the next iteration, or
+//! continues until the next breakpoint is hit.
+//!
+//! Each breakpoint is hit when its condition becomes true, rather than
+//! at every iteration while it is, so a node above a level pauses the
+//! analysis once each time it crosses the level. Debugging does not
+//! change the solution.
+
+use std::fmt;
+
+use crate::mna::Mna;
+
+/// A condition that pauses a debugged analysis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Breakpoint {
+    /// At the first time point at or after a time
+    Time(f64),
+    /// When an iterate of a node voltage goes above a level
+    Above { node: usize, voltage: f64 },
+    /// When an iterate of a node voltage goes below a level
+    Below { node: usize, voltage: f64 },
+    /// When a time point takes more than a number of Newton iterations
+    Iterations(usize),
+}
+
+impl Breakpoint {
+    fn condition(&self, state: &NewtonState) -> bool {
+        match *self {
+            Self::Time(time) => state.time >= time,
+            Self::Above { node, voltage } => state.voltage(node) > voltage,
+            Self::Below { node, voltage } => state.voltage(node) < voltage,
+            Self::Iterations(iterations) => state.iteration > iterations,
+        }
+    }
+}
+
+/// What a paused debugger does next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Pause again after the next Newton iteration
+    Step,
+    /// Run until the next breakpoint is hit
+    Continue,
+}
+
+/// A debugger of the Newton iteration, which is called at each
+/// iteration while the analysis is paused
+pub trait Debugger {
+    fn paused(&mut self, state: &NewtonState) -> Command;
+}
+
+impl<F: FnMut(&NewtonState) -> Command> Debugger for F {
+    fn paused(&mut self, state: &NewtonState) -> Command {
+        self(state)
+    }
+}
+
+/// An unknown of the MNA system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Unknown {
+    /// Voltage of a node (from 1, as ground is not an unknown)
+    Node(usize),
+    /// Current in an edge
+    Edge(usize),
+}
+
+impl fmt::Display for Unknown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Node(node) => write!(f, "v({node})"),
+            Self::Edge(edge) => write!(f, "i({edge})"),
+        }
+    }
+}
+
+/// Entries added to the MNA system, by the unknown of each row and
+/// column. The row of a node is its current equation (KCL), and the
+/// row of an edge is its branch equation.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Stamp {
+    /// Entries of the matrix, by row and column, in order
+    pub matrix: Vec<(Unknown, Unknown, f64)>,
+    /// Non-zero entries of the right-hand side, in order
+    pub rhs: Vec<(Unknown, f64)>,
+}
+
+impl Stamp {
+    pub(crate) fn from_mna(mna: &Mna<f64>) -> Self {
+        let num_voltage_nodes = mna.num_voltage_nodes();
+        let unknown = |index: usize| {
+            if index < num_voltage_nodes {
+                Unknown::Node(index + 1)
+            } else {
+                Unknown::Edge(index - num_voltage_nodes)
+            }
+        };
+        let mut matrix: Vec<(Unknown, Unknown, f64)> = mna
+            .entries()
+            .into_iter()
+            .map(|((row, column), value)| (unknown(row), unknown(column), value))
+            .collect();
+        matrix.sort_by_key(|(row, column, _)| (*row, *column));
+        let rhs = mna
+            .rhs()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, value)| *value != 0.0)
+            .map(|(index, value)| (unknown(index), value))
+            .collect();
+        Self { matrix, rhs }
+    }
+
+    /// Entries in the row of an unknown, by column
+    pub fn row(&self, row: Unknown) -> Vec<(Unknown, f64)> {
+        self.matrix
+            .iter()
+            .filter(|(r, _, _)| *r == row)
+            .map(|(_, column, value)| (*column, *value))
+            .collect()
+    }
+}
+
+/// The state of a Newton iteration of a time point
+pub struct NewtonState<'a> {
+    pub time: f64,
+    /// Iteration at the time point, from one
+    pub iteration: usize,
+    /// The breakpoint (by index) hit at this iteration, if any
+    pub breakpoint: Option<usize>,
+    /// The new iterate of the node voltages (node n at index n-1) and
+    /// edge currents
+    pub voltages: &'a [f64],
+    pub currents: &'a [f64],
+    /// The node voltages of the previous iterate (or time point, at
+    /// the first iteration)
+    pub(crate) previous: &'a [f64],
+    /// The system solved for the iterate
+    pub(crate) system: &'a Stamp,
+    /// Stamp of an instance by name, in the system
+    pub(crate) stamp: &'a dyn Fn(&str) -> Option<Stamp>,
+}
+
+impl NewtonState<'_> {
+    /// Voltage of a node in the iterate (zero for ground)
+    pub fn voltage(&self, node: usize) -> f64 {
+        match node {
+            0 => 0.0,
+            n => self.voltages.get(n - 1).copied().unwrap_or(0.0),
+        }
+    }
+
+    /// Change in the voltage of a node from the previous iterate
+    pub fn delta(&self, node: usize) -> f64 {
+        match node {
+            0 => 0.0,
+            n => self.voltage(n) - self.previous.get(n - 1).copied().unwrap_or(0.0),
+        }
+    }
+
+    /// The node whose voltage changed most from the previous iterate,
+    /// and the change
+    pub fn largest_delta(&self) -> Option<(usize, f64)> {
+        (1..=self.voltages.len())
+            .map(|node| (node, self.delta(node)))
+            .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+    }
+
+    /// The system solved for the iterate
+    pub fn system(&self) -> &Stamp {
+        self.system
+    }
+
+    /// Row of the Jacobian for the current equation of a node, by
+    /// column
+    pub fn jacobian_row(&self, node: usize) -> Vec<(Unknown, f64)> {
+        self.system.row(Unknown::Node(node))
+    }
+
+    /// Stamp of an instance in the system, if there is an instance
+    /// with the name
+    pub fn stamp(&self, instance: &str) -> Option<Stamp> {
+        (self.stamp)(instance)
+    }
+}
+
+/// A debugger with its breakpoints, during an analysis
+pub(crate) struct DebugSession<'a> {
+    breakpoints: &'a [Breakpoint],
+    debugger: &'a mut dyn Debugger,
+    /// Whether the condition of each breakpoint held at the last
+    /// iteration
+    conditions: Vec<bool>,
+    paused: bool,
+}
+
+impl<'a> DebugSession<'a> {
+    pub(crate) fn new(breakpoints: &'a [Breakpoint], debugger: &'a mut dyn Debugger) -> Self {
+        Self {
+            breakpoints,
+            debugger,
+            conditions: vec![false; breakpoints.len()],
+            paused: false,
+        }
+    }
+
+    /// Check the breakpoints at an iteration, and call the debugger if
+    /// it is paused
+    pub(crate) fn iteration(&mut self, mut state: NewtonState) {
+        for (index, breakpoint) in self.breakpoints.iter().enumerate() {
+            let condition = breakpoint.condition(&state);
+            if condition && !self.conditions[index] && state.breakpoint.is_none() {
+                state.breakpoint = Some(index);
+                self.paused = true;
+            }
+            self.conditions[index] = condition;
+        }
+        if self.paused {
+            self.paused = self.debugger.paused(&state) == Command::Step;
+        }
+    }
+}
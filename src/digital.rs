@@ -0,0 +1,147 @@
+//! Event-driven digital simulation
+//!
+//! The logic nets of a circuit are simulated by events: a change of
+//! a net at some time is an event, which makes every gate reading the
+//! net evaluate its output, and schedule a change of its output net
+//! the gate's delay later. All the nets start low, and every gate is
+//! evaluated once at time zero.
+//!
+//! In a mixed-signal transient analysis, the logic is advanced to each
+//! time point of the analog solution: ADC bridges drive their nets
+//! from the analog voltages, the events up to the time point are
+//! processed, and DAC bridges take their levels from their nets. Logic
+//! events are therefore resolved to the time step.
+
+use crate::circuit::Circuit;
+use crate::component::{Component, LogicGate};
+
+/// Largest number of events processed at one time point, which stops
+/// a loop of gates with no delay from oscillating forever
+const MAX_EVENTS: usize = 10_000;
+
+/// A scheduled change of a logic net, by the instance driving it
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LogicEvent {
+    time: f64,
+    net: usize,
+    level: bool,
+    driver: usize,
+}
+
+/// The levels of the logic nets of a circuit, and their scheduled
+/// changes
+#[derive(Debug, Clone)]
+pub struct LogicSimulator {
+    nets: Vec<bool>,
+    /// Scheduled changes, in the order they were scheduled
+    pending: Vec<LogicEvent>,
+    /// Warnings of the events processed, such as events that did not
+    /// settle
+    warnings: Vec<String>,
+}
+
+/// Logic nets connected to a component
+fn logic_nets(component: &Component) -> Vec<usize> {
+    match component {
+        Component::LogicGate { gate, .. } => {
+            let mut nets = gate.inputs();
+            nets.push(gate.output());
+            nets
+        }
+        Component::AdcBridge { net, .. } | Component::DacBridge { net, .. } => vec![*net],
+        _ => Vec::new(),
+    }
+}
+
+impl LogicSimulator {
+    pub fn new(circuit: &Circuit) -> Self {
+        let num_nets = circuit
+            .instances()
+            .iter()
+            .flat_map(|instance| logic_nets(&instance.component))
+            .max()
+            .map_or(0, |net| net + 1);
+        let mut simulator = Self {
+            nets: vec![false; num_nets],
+            pending: Vec::new(),
+            warnings: Vec::new(),
+        };
+        for (index, instance) in circuit.instances().iter().enumerate() {
+            if let Component::LogicGate { gate, delay } = &instance.component {
+                if let Some(level) = gate.evaluate(&simulator.nets, false) {
+                    simulator.schedule(*delay, gate.output(), level, index);
+                }
+            }
+        }
+        simulator
+    }
+
+    /// Whether the logic circuit has any nets
+    pub fn is_empty(&self) -> bool {
+        self.nets.is_empty()
+    }
+
+    /// Warnings of the events processed so far
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Level of a logic net
+    pub fn level(&self, net: usize) -> bool {
+        self.nets[net]
+    }
+
+    /// Schedule a change of a net, driven by an instance (by index)
+    pub fn schedule(&mut self, time: f64, net: usize, level: bool, driver: usize) {
+        self.pending.push(LogicEvent {
+            time,
+            net,
+            level,
+            driver,
+        });
+    }
+
+    /// Process the scheduled changes up to a time. Returns the changes
+    /// of level, as (time, instance index of the driver, new level).
+    pub fn advance(&mut self, circuit: &Circuit, until: f64) -> Vec<(f64, usize, bool)> {
+        let mut changes = Vec::new();
+        for _ in 0..MAX_EVENTS {
+            // The earliest event, or the first scheduled of several at
+            // the same time
+            let next = self
+                .pending
+                .iter()
+                .enumerate()
+                .filter(|(_, event)| event.time <= until)
+                .min_by(|(_, a), (_, b)| a.time.total_cmp(&b.time))
+                .map(|(k, _)| k);
+            let Some(k) = next else {
+                return changes;
+            };
+            let event = self.pending.remove(k);
+            if self.nets[event.net] == event.level {
+                continue;
+            }
+            self.nets[event.net] = event.level;
+            changes.push((event.time, event.driver, event.level));
+            for (index, instance) in circuit.instances().iter().enumerate() {
+                if let Component::LogicGate { gate, delay } = &instance.component {
+                    if !gate.inputs().contains(&event.net) {
+                        continue;
+                    }
+                    let clock_before = match gate {
+                        LogicGate::Dff { clock, .. } if *clock == event.net => !event.level,
+                        LogicGate::Dff { clock, .. } => self.nets[*clock],
+                        _ => false,
+                    };
+                    if let Some(level) = gate.evaluate(&self.nets, clock_before) {
+                        self.schedule(event.time + delay, gate.output(), level, index);
+                    }
+                }
+            }
+        }
+        self.warnings
+            .push(format!("time {until}: logic events did not settle"));
+        changes
+    }
+}
@@ -0,0 +1,73 @@
+//! Errors of the analyses
+//!
+//! Each analysis has a fallible version, named after it with a `try_`
+//! prefix (such as [crate::dc::try_operating_point],
+//! [crate::transient::TransientAnalysis::try_run] and
+//! [crate::ac::try_ac_sweep]), which checks the circuit first (see
+//! [crate::circuit::Circuit::validate]) and returns an [EsimError] for
+//! a circuit it cannot solve or an argument the circuit does not
+//! have, so that a library that takes its circuits from its users can
+//! report the problem and go on. The versions without the prefix
+//! panic instead, which is the simplest behaviour for a program that
+//! builds its own circuits.
+
+use std::fmt;
+
+use crate::topology::Violation;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EsimError {
+    /// A component that cannot be stamped into the MNA matrix, such as
+    /// one with both terminals on the same node, or a circuit with two
+    /// instances of the same name or current edge
+    Assembly { message: String },
+    /// Problems with the connectivity of the circuit (see
+    /// [crate::topology]) that made it fail to solve
+    Topology(Vec<Violation>),
+    /// Newton iteration that did not converge, even with the aids of
+    /// the options (gmin stepping and pseudo-transient continuation)
+    Convergence { iterations: usize },
+    /// A linear system that could not be solved, such as one with a
+    /// singular matrix
+    Solve { message: String },
+    /// An argument that does not fit the circuit, such as the name of
+    /// an instance it does not have, or a change its instance cannot
+    /// take
+    Invalid { message: String },
+    /// A file that could not be read
+    Io { path: String, message: String },
+    /// The contents of a file that could not be parsed
+    Parse { message: String },
+}
+
+impl fmt::Display for EsimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Assembly { message } => write!(f, "Cannot assemble the circuit: {message}"),
+            Self::Topology(violations) => {
+                let violations: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                write!(f, "Circuit topology error: {}", violations.join("; "))
+            }
+            Self::Convergence { iterations } => write!(
+                f,
+                "Newton iteration did not converge after {iterations} iterations"
+            ),
+            Self::Solve { message } => write!(f, "Failed to solve system: {message}"),
+            Self::Invalid { message } => write!(f, "{message}"),
+            Self::Io { path, message } => write!(f, "Cannot read {path}: {message}"),
+            Self::Parse { message } => write!(f, "Parse error: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for EsimError {}
+
+/// The error of an argument that does not fit the circuit
+pub(crate) fn invalid(message: String) -> EsimError {
+    EsimError::Invalid { message }
+}
+
+/// The error of an instance name the circuit does not have
+pub(crate) fn no_instance(name: &str) -> EsimError {
+    invalid(format!("No instance named {name}"))
+}
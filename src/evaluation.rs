@@ -0,0 +1,274 @@
+//! Parallel evaluation of nonlinear devices
+//!
+//! In each Newton iteration, every junction is linearised at its
+//! junction voltage, and in a circuit with many diodes (or compact
+//! models) this dominates the time to assemble the system. The
+//! junctions are therefore evaluated on several threads: each thread
+//! takes a contiguous range of the instances and fills its own buffer
+//! of linearisations, and the buffers are merged in instance order
+//! before anything is added to the matrix. The stamps are then added
+//! in the same order as if they had been evaluated on one thread, so
+//! the results are identical bit for bit whatever the number of
+//! threads.
+//!
+//! Small circuits are evaluated on the calling thread, since starting
+//! threads costs more than the evaluation.
+//!
+//! A junction whose voltage (and so current) has changed by less than
+//! a tolerance since it was last evaluated is bypassed, as in SPICE:
+//! its previous linearisation is used again without evaluating the
+//! model. The last evaluations are kept from one Newton iteration to
+//! the next, and from one time point (or sweep point) to the next, so
+//! the junctions that are idle, such as reverse biased diodes, are
+//! rarely evaluated. As safeguards, a junction is only bypassed if
+//! both its voltage and the change in its current predicted by its
+//! conductance are within tolerances a tenth of those of the Newton
+//! iteration, so the error is well below that of the iteration
+//! itself; and the evaluations are discarded when components are
+//! changed during a transient analysis. Bypassing can be turned off,
+//! and the number of junctions bypassed is counted in the [Profile].
+
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+use crate::circuit::Circuit;
+
+/// Fewest junctions each thread is given
+const MIN_JUNCTIONS_PER_THREAD: usize = 256;
+
+/// Bypass tolerances
+const BYPASS_RELTOL: f64 = 1e-4;
+const BYPASS_VNTOL: f64 = 1e-7;
+const BYPASS_ABSTOL: f64 = 1e-13;
+
+/// Number of threads evaluating the devices (zero for one per
+/// available core)
+static NUM_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+static BYPASS: AtomicBool = AtomicBool::new(true);
+
+/// Counters of the profile
+static ITERATIONS: AtomicUsize = AtomicUsize::new(0);
+static EVALUATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYPASSED: AtomicUsize = AtomicUsize::new(0);
+
+/// Set the number of threads evaluating the devices (zero for one
+/// per available core, which is the default)
+pub fn set_num_threads(num_threads: usize) {
+    NUM_THREADS.store(num_threads, Ordering::Relaxed);
+}
+
+/// Number of threads evaluating the devices
+pub fn num_threads() -> usize {
+    match NUM_THREADS.load(Ordering::Relaxed) {
+        0 => thread::available_parallelism().map_or(1, usize::from),
+        n => n,
+    }
+}
+
+/// Turn bypassing of unchanged junctions on (the default) or off
+pub fn set_bypass(enabled: bool) {
+    BYPASS.store(enabled, Ordering::Relaxed);
+}
+
+pub fn bypass() -> bool {
+    BYPASS.load(Ordering::Relaxed)
+}
+
+/// Counts of the device evaluation in the Newton iterations since
+/// the profile was last reset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Profile {
+    /// Newton iterations (each a linear solve)
+    pub iterations: usize,
+    /// Junctions evaluated
+    pub evaluations: usize,
+    /// Junctions bypassed, whose previous linearisation was used
+    pub bypassed: usize,
+}
+
+impl Profile {
+    /// Fraction of the junction linearisations that were bypassed
+    pub fn bypass_fraction(&self) -> f64 {
+        let total = self.evaluations + self.bypassed;
+        if total == 0 {
+            0.0
+        } else {
+            self.bypassed as f64 / total as f64
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} Newton iterations, {} junction evaluations, {} bypassed ({:.1}%)",
+            self.iterations,
+            self.evaluations,
+            self.bypassed,
+            100.0 * self.bypass_fraction()
+        )
+    }
+}
+
+/// The counts since the profile was last reset
+pub fn profile() -> Profile {
+    Profile {
+        iterations: ITERATIONS.load(Ordering::Relaxed),
+        evaluations: EVALUATIONS.load(Ordering::Relaxed),
+        bypassed: BYPASSED.load(Ordering::Relaxed),
+    }
+}
+
+pub fn reset_profile() {
+    ITERATIONS.store(0, Ordering::Relaxed);
+    EVALUATIONS.store(0, Ordering::Relaxed);
+    BYPASSED.store(0, Ordering::Relaxed);
+}
+
+/// Evaluate the instances of a circuit, on several threads if it has
+/// enough junctions, with the results in instance order
+fn evaluate<T: Send>(
+    circuit: &Circuit,
+    evaluate_range: impl Fn(Range<usize>) -> Vec<T> + Sync,
+) -> Vec<T> {
+    let instances = circuit.instances();
+    let num_junctions = instances
+        .iter()
+        .filter(|i| i.component.junction().is_some())
+        .count();
+    let num_threads = num_threads().min(num_junctions / MIN_JUNCTIONS_PER_THREAD);
+    if num_threads <= 1 {
+        return evaluate_range(0..instances.len());
+    }
+    let chunk = instances.len().div_ceil(num_threads);
+    let buffers: Vec<Vec<T>> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..instances.len())
+            .step_by(chunk)
+            .map(|start| {
+                let end = (start + chunk).min(instances.len());
+                let evaluate_range = &evaluate_range;
+                scope.spawn(move || evaluate_range(start..end))
+            })
+            .collect();
+        // Joined in order, so the buffers are merged in instance order
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Device evaluation panicked"))
+            .collect()
+    });
+    buffers.into_iter().flatten().collect()
+}
+
+/// Linearisation (conductance and equivalent current) of each
+/// junction of an elaborated circuit at the junction voltages (indexed
+/// by instance, and zero if missing). Instances without a junction
+/// are None.
+pub(crate) fn linearise_junctions(circuit: &Circuit, junctions: &[f64]) -> Vec<Option<(f64, f64)>> {
+    let instances = circuit.instances();
+    evaluate(circuit, |range| {
+        range
+            .map(|index| {
+                let voltage = junctions.get(index).copied().unwrap_or(0.0);
+                instances[index]
+                    .component
+                    .junction()
+                    .map(|(_, _, junction)| junction.linearise(voltage))
+            })
+            .collect()
+    })
+}
+
+/// A junction at the voltage it was last evaluated at
+#[derive(Debug, Clone, Copy)]
+struct Evaluated {
+    voltage: f64,
+    /// Junction current at the voltage
+    current: f64,
+    conductance: f64,
+}
+
+impl Evaluated {
+    /// Whether the linearisation can be used at another voltage
+    fn bypass(&self, voltage: f64) -> bool {
+        let change = voltage - self.voltage;
+        change.abs() <= BYPASS_RELTOL * voltage.abs().max(self.voltage.abs()) + BYPASS_VNTOL
+            && (self.conductance * change).abs()
+                <= BYPASS_RELTOL * self.current.abs() + BYPASS_ABSTOL
+    }
+
+    fn linearisation(&self) -> (f64, f64) {
+        (
+            self.conductance,
+            self.current - self.conductance * self.voltage,
+        )
+    }
+}
+
+/// The junctions of a circuit as last evaluated, for bypassing. The
+/// cache must be cleared if a junction is changed.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct JunctionCache {
+    evaluated: Vec<Option<Evaluated>>,
+    linearised: Vec<Option<(f64, f64)>>,
+    /// Control voltages of the transistors of the circuit as last
+    /// limited (by instance), which the next iteration limits from
+    pub transistors: Vec<[f64; 2]>,
+}
+
+impl JunctionCache {
+    /// Linearise the junctions of an elaborated circuit at the junction
+    /// voltages (indexed by instance), as for [linearise_junctions],
+    /// bypassing those that have not changed
+    pub(crate) fn linearise(
+        &mut self,
+        circuit: &Circuit,
+        junctions: &[f64],
+    ) -> &[Option<(f64, f64)>] {
+        let instances = circuit.instances();
+        let allow_bypass = bypass() && self.evaluated.len() == instances.len();
+        let previous = &self.evaluated;
+        // Each junction as evaluated, and whether it was bypassed
+        let results = evaluate(circuit, |range| {
+            range
+                .map(|index| {
+                    let (_, _, junction) = instances[index].component.junction()?;
+                    let voltage = junctions.get(index).copied().unwrap_or(0.0);
+                    match previous.get(index).copied().flatten() {
+                        Some(evaluated) if allow_bypass && evaluated.bypass(voltage) => {
+                            Some((evaluated, true))
+                        }
+                        _ => {
+                            let (current, conductance) = junction.evaluate(voltage);
+                            let evaluated = Evaluated {
+                                voltage,
+                                current,
+                                conductance,
+                            };
+                            Some((evaluated, false))
+                        }
+                    }
+                })
+                .collect()
+        });
+        let bypassed = results.iter().flatten().filter(|(_, b)| *b).count();
+        let evaluations = results.iter().flatten().count() - bypassed;
+        ITERATIONS.fetch_add(1, Ordering::Relaxed);
+        EVALUATIONS.fetch_add(evaluations, Ordering::Relaxed);
+        BYPASSED.fetch_add(bypassed, Ordering::Relaxed);
+        self.evaluated = results.iter().map(|r| r.map(|(e, _)| e)).collect();
+        self.linearised = self
+            .evaluated
+            .iter()
+            .map(|e| e.as_ref().map(Evaluated::linearisation))
+            .collect();
+        &self.linearised
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.evaluated.clear();
+    }
+}
@@ -0,0 +1,962 @@
+//! Parameter expressions
+//!
+//! An expression gives a value in terms of named parameters, as in the
+//! `{R1*2 + 50}` of a netlist. It has the operators `+`, `-`, `*`, `/`
+//! and `^` (or `**`) for powers, with the usual precedence (powers
+//! first, and right-associative), the comparisons `==`, `!=`, `<`,
+//! `<=`, `>` and `>=`, and the logical operators `&&`, `||` and `!`
+//! (with a lower precedence than arithmetic, and giving 1 for true and
+//! 0 for false, with any value other than 0 being true), parentheses,
+//! numbers in the notation
+//! of [parse_spice_value] (so `2k` is 2000), parameters by name, the
+//! constant `pi`, and the functions
+//!
+//! - of one argument: `abs`, `sqrt`, `exp`, `ln`, `log` (natural, as in
+//!   SPICE), `log10`, `sin`, `cos`, `tan`, `asin`, `acos`, `atan`,
+//!   `sinh`, `cosh`, `tanh`, `floor`, `ceil`, `int` (towards zero) and
+//!   `sgn`
+//! - of two arguments: `min`, `max`, `pow` and `atan2`
+//!
+//! Names are not case sensitive. A [Parameters] table holds an
+//! expression for each parameter, which can use the others (in any
+//! order, but not in a loop). The expressions are evaluated when a
+//! value is needed, so changing a parameter changes the values of the
+//! parameters that depend on it.
+//!
+//! The table also holds user-defined functions (see [Function]), as
+//! given by the `.FUNC` lines of a netlist:
+//!
+//! ```text
+//! db(x) = 20*log10(x)
+//! par(a, b) = a*b/(a + b)
+//! ```
+//!
+//! The body of a function is an expression of its arguments, the
+//! parameters and the other functions (but not, directly or
+//! indirectly, itself), evaluated when the function is called. A
+//! function cannot have the name of a built-in one. A call of a
+//! function that is not built in is checked when it is evaluated,
+//! since the function may be defined after the expression is parsed.
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::fmt;
+
+use crate::value::{format_number, parse_spice_value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpressionError {
+    pub message: String,
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+fn error(message: String) -> ExpressionError {
+    ExpressionError { message }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(f64),
+    /// A parameter, by name (in lower case)
+    Parameter(String),
+    Negate(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+    /// A function, by name (in lower case), with its arguments
+    Call(String, Vec<Expression>),
+}
+
+/// Number of arguments of each function
+const FUNCTIONS: [(&str, usize); 23] = [
+    ("abs", 1),
+    ("sqrt", 1),
+    ("exp", 1),
+    ("ln", 1),
+    ("log", 1),
+    ("log10", 1),
+    ("sin", 1),
+    ("cos", 1),
+    ("tan", 1),
+    ("asin", 1),
+    ("acos", 1),
+    ("atan", 1),
+    ("sinh", 1),
+    ("cosh", 1),
+    ("tanh", 1),
+    ("floor", 1),
+    ("ceil", 1),
+    ("int", 1),
+    ("sgn", 1),
+    ("min", 2),
+    ("max", 2),
+    ("pow", 2),
+    ("atan2", 2),
+];
+
+/// The value of a truth: 1 if it is true, and 0 if not
+fn truth(value: bool) -> f64 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+pub(crate) fn is_builtin(function: &str) -> bool {
+    FUNCTIONS.iter().any(|(name, _)| *name == function)
+}
+
+fn call(function: &str, args: &[f64]) -> f64 {
+    match (function, args) {
+        ("abs", [x]) => x.abs(),
+        ("sqrt", [x]) => x.sqrt(),
+        ("exp", [x]) => x.exp(),
+        ("ln" | "log", [x]) => x.ln(),
+        ("log10", [x]) => x.log10(),
+        ("sin", [x]) => x.sin(),
+        ("cos", [x]) => x.cos(),
+        ("tan", [x]) => x.tan(),
+        ("asin", [x]) => x.asin(),
+        ("acos", [x]) => x.acos(),
+        ("atan", [x]) => x.atan(),
+        ("sinh", [x]) => x.sinh(),
+        ("cosh", [x]) => x.cosh(),
+        ("tanh", [x]) => x.tanh(),
+        ("floor", [x]) => x.floor(),
+        ("ceil", [x]) => x.ceil(),
+        ("int", [x]) => x.trunc(),
+        ("sgn", [x]) if *x == 0.0 => 0.0,
+        ("sgn", [x]) => x.signum(),
+        ("min", [x, y]) => x.min(*y),
+        ("max", [x, y]) => x.max(*y),
+        ("pow", [x, y]) => x.powf(*y),
+        ("atan2", [y, x]) => y.atan2(*x),
+        _ => unreachable!("Functions are checked when parsed"),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(char),
+}
+
+fn lex(text: &str) -> Result<Vec<Token>, ExpressionError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut k = 0;
+    while k < chars.len() {
+        let c = chars[k];
+        let start = k;
+        if c.is_whitespace() {
+            k += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            while k < chars.len() && (chars[k].is_ascii_digit() || chars[k] == '.') {
+                k += 1;
+            }
+            // An exponent, if the letter is followed by digits
+            if k < chars.len() && matches!(chars[k], 'e' | 'E') {
+                let digits = match chars.get(k + 1) {
+                    Some('+' | '-') => k + 2,
+                    _ => k + 1,
+                };
+                if chars.get(digits).is_some_and(char::is_ascii_digit) {
+                    k = digits;
+                    while k < chars.len() && chars[k].is_ascii_digit() {
+                        k += 1;
+                    }
+                }
+            }
+            // A scale suffix and unit
+            while k < chars.len() && chars[k].is_ascii_alphabetic() {
+                k += 1;
+            }
+            let number: String = chars[start..k].iter().collect();
+            let value = parse_spice_value(&number).map_err(|e| error(e.to_string()))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() || c == '_' {
+            // Names can have dots, as the parameters of subcircuit
+            // instances do
+            while k < chars.len() && (chars[k].is_alphanumeric() || matches!(chars[k], '_' | '.')) {
+                k += 1;
+            }
+            let name: String = chars[start..k].iter().collect();
+            tokens.push(Token::Name(name.to_lowercase()));
+        } else if c == '*' && chars.get(k + 1) == Some(&'*') {
+            tokens.push(Token::Symbol('^'));
+            k += 2;
+        } else if let Some(symbol) = chars
+            .get(k + 1)
+            .and_then(|next| two_character_symbol(c, *next))
+        {
+            tokens.push(Token::Symbol(symbol));
+            k += 2;
+        } else if "+-*/^(),<>!".contains(c) {
+            tokens.push(Token::Symbol(c));
+            k += 1;
+        } else {
+            return Err(error(format!("unexpected '{c}' in expression '{text}'")));
+        }
+    }
+    Ok(tokens)
+}
+
+/// The symbol standing for a two-character operator, if the characters
+/// are one (`=` for `==`, `#` for `!=`, `{` for `<=`, `}` for `>=`,
+/// `&` for `&&` and `|` for `||`)
+fn two_character_symbol(first: char, second: char) -> Option<char> {
+    Some(match (first, second) {
+        ('=', '=') => '=',
+        ('!', '=') => '#',
+        ('<', '=') => '{',
+        ('>', '=') => '}',
+        ('&', '&') => '&',
+        ('|', '|') => '|',
+        _ => return None,
+    })
+}
+
+/// Recursive descent parser, from the lowest precedence
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    /// Consume a symbol if it is next
+    fn symbol(&mut self, symbol: char) -> bool {
+        let found = self.peek() == Some(&Token::Symbol(symbol));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ExpressionError> {
+        if self.symbol(symbol) {
+            Ok(())
+        } else {
+            Err(error(format!("expected '{symbol}'")))
+        }
+    }
+
+    fn or(&mut self) -> Result<Expression, ExpressionError> {
+        let mut lhs = self.and()?;
+        while self.symbol('|') {
+            lhs = Expression::Binary(Operator::Or, Box::new(lhs), Box::new(self.and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Expression, ExpressionError> {
+        let mut lhs = self.comparison()?;
+        while self.symbol('&') {
+            lhs = Expression::Binary(Operator::And, Box::new(lhs), Box::new(self.comparison()?));
+        }
+        Ok(lhs)
+    }
+
+    /// A comparison of two sums, or a sum (comparisons do not chain)
+    fn comparison(&mut self) -> Result<Expression, ExpressionError> {
+        let lhs = self.sum()?;
+        let operators = [
+            ('=', Operator::Equal),
+            ('#', Operator::NotEqual),
+            ('<', Operator::Less),
+            ('{', Operator::LessEqual),
+            ('>', Operator::Greater),
+            ('}', Operator::GreaterEqual),
+        ];
+        for (symbol, operator) in operators {
+            if self.symbol(symbol) {
+                return Ok(Expression::Binary(
+                    operator,
+                    Box::new(lhs),
+                    Box::new(self.sum()?),
+                ));
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn sum(&mut self) -> Result<Expression, ExpressionError> {
+        let mut lhs = self.product()?;
+        loop {
+            let operator = if self.symbol('+') {
+                Operator::Add
+            } else if self.symbol('-') {
+                Operator::Subtract
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expression::Binary(operator, Box::new(lhs), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expression, ExpressionError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let operator = if self.symbol('*') {
+                Operator::Multiply
+            } else if self.symbol('/') {
+                Operator::Divide
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expression::Binary(operator, Box::new(lhs), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression, ExpressionError> {
+        if self.symbol('!') {
+            // Logical not, as a comparison with zero
+            let operand = self.unary()?;
+            Ok(Expression::Binary(
+                Operator::Equal,
+                Box::new(operand),
+                Box::new(Expression::Number(0.0)),
+            ))
+        } else if self.symbol('-') {
+            Ok(Expression::Negate(Box::new(self.unary()?)))
+        } else if self.symbol('+') {
+            self.unary()
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<Expression, ExpressionError> {
+        let base = self.primary()?;
+        if self.symbol('^') {
+            let exponent = self.unary()?;
+            Ok(Expression::Binary(
+                Operator::Power,
+                Box::new(base),
+                Box::new(exponent),
+            ))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expression, ExpressionError> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| error(String::from("unexpected end of expression")))?;
+        self.position += 1;
+        match token {
+            Token::Number(value) => Ok(Expression::Number(value)),
+            Token::Name(name) if self.symbol('(') => {
+                let mut args = vec![self.or()?];
+                while self.symbol(',') {
+                    args.push(self.or()?);
+                }
+                self.expect(')')?;
+                match FUNCTIONS.iter().find(|(function, _)| *function == name) {
+                    // A user-defined function
+                    None => Ok(Expression::Call(name, args)),
+                    Some((_, count)) if *count != args.len() => Err(error(format!(
+                        "{name} takes {count} argument{}, not {}",
+                        if *count == 1 { "" } else { "s" },
+                        args.len()
+                    ))),
+                    Some(_) => Ok(Expression::Call(name, args)),
+                }
+            }
+            Token::Name(name) => Ok(Expression::Parameter(name)),
+            Token::Symbol('(') => {
+                let inner = self.or()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            Token::Symbol(symbol) => Err(error(format!("unexpected '{symbol}'"))),
+        }
+    }
+}
+
+/// Parse an expression
+pub fn parse_expression(text: &str) -> Result<Expression, ExpressionError> {
+    let mut parser = Parser {
+        tokens: lex(text)?,
+        position: 0,
+    };
+    let expression = parser.or()?;
+    match parser.peek() {
+        None => Ok(expression),
+        Some(_) => Err(error(format!("unexpected text in expression '{text}'"))),
+    }
+}
+
+/// A user-defined function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// Names of the arguments (in lower case)
+    pub arguments: Vec<String>,
+    /// An expression of the arguments, the parameters and other
+    /// functions
+    pub body: Expression,
+}
+
+/// Parse the definition of a function, as in `db(x) = 20*log10(x)`,
+/// returning its name (in lower case). The body can be in braces
+/// instead of after an equals sign, as in `db(x) {20*log10(x)}`.
+pub fn parse_function(text: &str) -> Result<(String, Function), ExpressionError> {
+    let (name, rest) = text
+        .split_once('(')
+        .ok_or_else(|| error(format!("expected the arguments of function '{text}'")))?;
+    let name = name.trim().to_lowercase();
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(error(format!("invalid function name '{name}'")));
+    }
+    if is_builtin(&name) {
+        return Err(error(format!("function '{name}' is built in")));
+    }
+    let (arguments, body) = rest
+        .split_once(')')
+        .ok_or_else(|| error(format!("expected ')' after the arguments of '{name}'")))?;
+    if arguments.trim().is_empty() {
+        return Err(error(format!("function '{name}' has no arguments")));
+    }
+    let mut names: Vec<String> = Vec::new();
+    for argument in arguments.split(',').map(str::trim) {
+        if argument.is_empty() || !argument.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(error(format!("invalid argument '{argument}' of '{name}'")));
+        }
+        let argument = argument.to_lowercase();
+        if names.contains(&argument) {
+            return Err(error(format!(
+                "argument '{argument}' of '{name}' is repeated"
+            )));
+        }
+        names.push(argument);
+    }
+    let body = body.trim();
+    let body = match body.strip_prefix('=') {
+        Some(body) => body.trim(),
+        None => body,
+    };
+    let body = body
+        .strip_prefix('{')
+        .and_then(|body| body.strip_suffix('}'))
+        .unwrap_or(body);
+    let body = parse_expression(body).map_err(|e| error(format!("function '{name}': {e}")))?;
+    Ok((
+        name,
+        Function {
+            arguments: names,
+            body,
+        },
+    ))
+}
+
+impl Expression {
+    /// The same expression with each parameter renamed
+    pub(crate) fn rename(&self, rename: &impl Fn(&str) -> String) -> Expression {
+        match self {
+            Self::Number(value) => Self::Number(*value),
+            Self::Parameter(name) => Self::Parameter(rename(name)),
+            Self::Negate(inner) => Self::Negate(Box::new(inner.rename(rename))),
+            Self::Binary(operator, lhs, rhs) => Self::Binary(
+                *operator,
+                Box::new(lhs.rename(rename)),
+                Box::new(rhs.rename(rename)),
+            ),
+            Self::Call(function, args) => Self::Call(
+                function.clone(),
+                args.iter().map(|arg| arg.rename(rename)).collect(),
+            ),
+        }
+    }
+
+    /// The same expression with the values of the named parameters in
+    /// place of them
+    pub(crate) fn bind(&self, names: &[String], values: &[f64]) -> Expression {
+        match self {
+            Self::Parameter(name) => match names.iter().position(|n| n == name) {
+                Some(index) => Self::Number(values[index]),
+                None => self.clone(),
+            },
+            Self::Number(_) => self.clone(),
+            Self::Negate(inner) => Self::Negate(Box::new(inner.bind(names, values))),
+            Self::Binary(operator, lhs, rhs) => Self::Binary(
+                *operator,
+                Box::new(lhs.bind(names, values)),
+                Box::new(rhs.bind(names, values)),
+            ),
+            Self::Call(function, args) => Self::Call(
+                function.clone(),
+                args.iter().map(|arg| arg.bind(names, values)).collect(),
+            ),
+        }
+    }
+    /// The parameters the expression uses, in the order they appear
+    pub(crate) fn parameters(&self) -> Vec<String> {
+        match self {
+            Self::Number(_) => Vec::new(),
+            Self::Parameter(name) => vec![name.clone()],
+            Self::Negate(inner) => inner.parameters(),
+            Self::Binary(_, lhs, rhs) => [lhs.parameters(), rhs.parameters()].concat(),
+            Self::Call(_, args) => args.iter().flat_map(Expression::parameters).collect(),
+        }
+    }
+
+    /// Whether the expression uses a parameter
+    pub(crate) fn depends_on(&self, name: &str) -> bool {
+        match self {
+            Self::Number(_) => false,
+            Self::Parameter(parameter) => parameter == name,
+            Self::Negate(inner) => inner.depends_on(name),
+            Self::Binary(_, lhs, rhs) => lhs.depends_on(name) || rhs.depends_on(name),
+            Self::Call(_, args) => args.iter().any(|arg| arg.depends_on(name)),
+        }
+    }
+
+    /// The derivative of the expression with respect to a parameter,
+    /// as an expression, or an error if it calls a function that is
+    /// not built in. The comparisons and the functions that are
+    /// piecewise constant (such as `floor` and `sgn`) have a derivative
+    /// of zero.
+    pub(crate) fn derivative(&self, name: &str) -> Result<Expression, ExpressionError> {
+        if !self.depends_on(name) {
+            return Ok(Self::Number(0.0));
+        }
+        Ok(match self {
+            Self::Number(_) => Self::Number(0.0),
+            Self::Parameter(_) => Self::Number(1.0),
+            Self::Negate(inner) => negate(inner.derivative(name)?),
+            Self::Binary(operator, lhs, rhs) => {
+                let (u, w) = (lhs.as_ref().clone(), rhs.as_ref().clone());
+                let (du, dw) = (lhs.derivative(name)?, rhs.derivative(name)?);
+                match operator {
+                    Operator::Add => binary(Operator::Add, du, dw),
+                    Operator::Subtract => binary(Operator::Subtract, du, dw),
+                    Operator::Multiply => binary(
+                        Operator::Add,
+                        binary(Operator::Multiply, du, w),
+                        binary(Operator::Multiply, u, dw),
+                    ),
+                    Operator::Divide => binary(
+                        Operator::Divide,
+                        binary(
+                            Operator::Subtract,
+                            binary(Operator::Multiply, du, w.clone()),
+                            binary(Operator::Multiply, u, dw),
+                        ),
+                        binary(Operator::Power, w, Self::Number(2.0)),
+                    ),
+                    Operator::Power => power_derivative(u, w, du, dw, rhs.depends_on(name)),
+                    _ => Self::Number(0.0),
+                }
+            }
+            Self::Call(function, args) => {
+                let x = args[0].clone();
+                let dx = args[0].derivative(name)?;
+                let call =
+                    |function: &str, x: Expression| Self::Call(function.to_string(), vec![x]);
+                let chain = |outer: Expression| binary(Operator::Multiply, outer, dx.clone());
+                let square = |x: Expression| binary(Operator::Power, x, Self::Number(2.0));
+                let one_minus_square =
+                    |x: Expression| binary(Operator::Subtract, Self::Number(1.0), square(x));
+                match function.as_str() {
+                    "abs" => chain(call("sgn", x)),
+                    "sqrt" => binary(
+                        Operator::Divide,
+                        dx.clone(),
+                        binary(Operator::Multiply, Self::Number(2.0), self.clone()),
+                    ),
+                    "exp" => chain(self.clone()),
+                    "ln" | "log" => binary(Operator::Divide, dx.clone(), x),
+                    "log10" => binary(
+                        Operator::Divide,
+                        dx.clone(),
+                        binary(Operator::Multiply, x, Self::Number(std::f64::consts::LN_10)),
+                    ),
+                    "sin" => chain(call("cos", x)),
+                    "cos" => negate(chain(call("sin", x))),
+                    "tan" => binary(Operator::Divide, dx.clone(), square(call("cos", x))),
+                    "asin" => binary(
+                        Operator::Divide,
+                        dx.clone(),
+                        call("sqrt", one_minus_square(x)),
+                    ),
+                    "acos" => negate(binary(
+                        Operator::Divide,
+                        dx.clone(),
+                        call("sqrt", one_minus_square(x)),
+                    )),
+                    "atan" => binary(
+                        Operator::Divide,
+                        dx.clone(),
+                        binary(Operator::Add, Self::Number(1.0), square(x)),
+                    ),
+                    "sinh" => chain(call("cosh", x)),
+                    "cosh" => chain(call("sinh", x)),
+                    "tanh" => chain(one_minus_square(self.clone())),
+                    "floor" | "ceil" | "int" | "sgn" => Self::Number(0.0),
+                    "min" | "max" => {
+                        let y = args[1].clone();
+                        let dy = args[1].derivative(name)?;
+                        let operator = match function.as_str() {
+                            "min" => Operator::Less,
+                            _ => Operator::Greater,
+                        };
+                        // The derivative of the argument that is taken
+                        let first = Self::Binary(operator, Box::new(x.clone()), Box::new(y));
+                        binary(
+                            Operator::Add,
+                            binary(Operator::Multiply, first.clone(), dx.clone()),
+                            binary(
+                                Operator::Multiply,
+                                binary(Operator::Subtract, Self::Number(1.0), first),
+                                dy,
+                            ),
+                        )
+                    }
+                    "pow" => power_derivative(
+                        x,
+                        args[1].clone(),
+                        dx.clone(),
+                        args[1].derivative(name)?,
+                        args[1].depends_on(name),
+                    ),
+                    "atan2" => {
+                        // atan2(y, x), with x the second argument
+                        let (y, dy) = (x, dx.clone());
+                        let x = args[1].clone();
+                        let dx = args[1].derivative(name)?;
+                        binary(
+                            Operator::Divide,
+                            binary(
+                                Operator::Subtract,
+                                binary(Operator::Multiply, x.clone(), dy),
+                                binary(Operator::Multiply, y.clone(), dx),
+                            ),
+                            binary(Operator::Add, square(x), square(y)),
+                        )
+                    }
+                    _ => return Err(error(format!("cannot differentiate function '{function}'"))),
+                }
+            }
+        })
+    }
+}
+
+/// Negation of an expression, folding numbers
+fn negate(expression: Expression) -> Expression {
+    match expression {
+        Expression::Number(value) => Expression::Number(-value),
+        expression => Expression::Negate(Box::new(expression)),
+    }
+}
+
+/// A binary operation, folding numbers and the operations with zero
+/// and one that do nothing, so that derivatives stay small
+fn binary(operator: Operator, lhs: Expression, rhs: Expression) -> Expression {
+    use Expression::Number;
+    match (operator, &lhs, &rhs) {
+        (Operator::Add, Number(a), Number(b)) => Number(a + b),
+        (Operator::Subtract, Number(a), Number(b)) => Number(a - b),
+        (Operator::Multiply, Number(a), Number(b)) => Number(a * b),
+        (Operator::Add, Number(zero), _) if *zero == 0.0 => rhs,
+        (Operator::Add | Operator::Subtract, _, Number(zero)) if *zero == 0.0 => lhs,
+        (Operator::Subtract, Number(zero), _) if *zero == 0.0 => negate(rhs),
+        (Operator::Multiply, Number(zero), _) | (Operator::Multiply, _, Number(zero))
+            if *zero == 0.0 =>
+        {
+            Number(0.0)
+        }
+        (Operator::Multiply, Number(one), _) if *one == 1.0 => rhs,
+        (Operator::Multiply | Operator::Divide | Operator::Power, _, Number(one))
+            if *one == 1.0 =>
+        {
+            lhs
+        }
+        (Operator::Divide, Number(zero), _) if *zero == 0.0 => Number(0.0),
+        _ => Expression::Binary(operator, Box::new(lhs), Box::new(rhs)),
+    }
+}
+
+/// The derivative of u^w, from the derivatives of u and w
+fn power_derivative(
+    u: Expression,
+    w: Expression,
+    du: Expression,
+    dw: Expression,
+    variable_exponent: bool,
+) -> Expression {
+    let power = binary(Operator::Power, u.clone(), w.clone());
+    if variable_exponent {
+        // u^w (w' ln u + w u'/u)
+        binary(
+            Operator::Multiply,
+            power,
+            binary(
+                Operator::Add,
+                binary(
+                    Operator::Multiply,
+                    dw,
+                    Expression::Call(String::from("ln"), vec![u.clone()]),
+                ),
+                binary(Operator::Divide, binary(Operator::Multiply, w, du), u),
+            ),
+        )
+    } else {
+        // w u^(w - 1) u'
+        binary(
+            Operator::Multiply,
+            binary(
+                Operator::Multiply,
+                w.clone(),
+                binary(
+                    Operator::Power,
+                    u,
+                    binary(Operator::Subtract, w, Expression::Number(1.0)),
+                ),
+            ),
+            du,
+        )
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Self::Add => "+",
+            Self::Subtract => "-",
+            Self::Multiply => "*",
+            Self::Divide => "/",
+            Self::Power => "^",
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::Less => "<",
+            Self::LessEqual => "<=",
+            Self::Greater => ">",
+            Self::GreaterEqual => ">=",
+            Self::And => "&&",
+            Self::Or => "||",
+        };
+        write!(f, "{symbol}")
+    }
+}
+
+impl fmt::Display for Expression {
+    /// The expression as text that parses back to it, with each
+    /// operation inside another in parentheses
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operand = |expression: &Expression| match expression {
+            Expression::Binary(..) | Expression::Negate(_) => format!("({expression})"),
+            Expression::Number(value) if *value < 0.0 => format!("({expression})"),
+            _ => expression.to_string(),
+        };
+        match self {
+            Self::Number(value) => write!(f, "{}", format_number(*value)),
+            Self::Parameter(name) => write!(f, "{name}"),
+            Self::Negate(inner) => write!(f, "-{}", operand(inner)),
+            Self::Binary(operator, lhs, rhs) => {
+                write!(f, "{} {operator} {}", operand(lhs), operand(rhs))
+            }
+            Self::Call(function, args) => {
+                let args: Vec<String> = args.iter().map(Expression::to_string).collect();
+                write!(f, "{function}({})", args.join(", "))
+            }
+        }
+    }
+}
+
+/// Named parameters, each given by an expression, and user-defined
+/// functions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Parameters {
+    expressions: BTreeMap<String, Expression>,
+    functions: BTreeMap<String, Function>,
+}
+
+impl Parameters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define a parameter, replacing any previous definition
+    pub fn define(&mut self, name: &str, expression: Expression) {
+        self.expressions.insert(name.to_lowercase(), expression);
+    }
+
+    /// Define a parameter as a number
+    pub fn set(&mut self, name: &str, value: f64) {
+        self.define(name, Expression::Number(value));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.expressions.contains_key(&name.to_lowercase())
+    }
+
+    /// Names of the parameters, in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.expressions.keys().map(String::as_str)
+    }
+
+    /// The parameters with their expressions, in alphabetical order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Expression)> {
+        self.expressions
+            .iter()
+            .map(|(name, expression)| (name.as_str(), expression))
+    }
+
+    /// Define a function, replacing any previous definition, or return
+    /// an error if a function of the name is built in
+    pub fn define_function(
+        &mut self,
+        name: &str,
+        function: Function,
+    ) -> Result<(), ExpressionError> {
+        let name = name.to_lowercase();
+        if is_builtin(&name) {
+            return Err(error(format!("function '{name}' is built in")));
+        }
+        self.functions.insert(name, function);
+        Ok(())
+    }
+
+    /// A user-defined function by name
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.get(&name.to_lowercase())
+    }
+
+    /// The user-defined functions, in alphabetical order
+    pub fn functions(&self) -> impl Iterator<Item = (&str, &Function)> {
+        self.functions
+            .iter()
+            .map(|(name, function)| (name.as_str(), function))
+    }
+
+    /// Value of a parameter
+    pub fn value(&self, name: &str) -> Result<f64, ExpressionError> {
+        self.parameter(&name.to_lowercase(), &mut Vec::new())
+    }
+
+    /// Value of an expression of the parameters
+    pub fn evaluate(&self, expression: &Expression) -> Result<f64, ExpressionError> {
+        self.evaluate_from(expression, &mut Vec::new())
+    }
+
+    /// Value of a parameter, with the parameters being evaluated
+    fn parameter(&self, name: &str, stack: &mut Vec<String>) -> Result<f64, ExpressionError> {
+        let Some(expression) = self.expressions.get(name) else {
+            return match name {
+                "pi" => Ok(PI),
+                _ => Err(error(format!("unknown parameter '{name}'"))),
+            };
+        };
+        if stack.iter().any(|n| n == name) {
+            return Err(error(format!("parameter '{name}' depends on itself")));
+        }
+        stack.push(name.to_string());
+        let value = self.evaluate_from(expression, stack);
+        stack.pop();
+        value
+    }
+
+    /// Value of a user-defined function at its arguments, with the
+    /// parameters and functions being evaluated (functions as their
+    /// name followed by `()`)
+    fn call(
+        &self,
+        name: &str,
+        args: &[f64],
+        stack: &mut Vec<String>,
+    ) -> Result<f64, ExpressionError> {
+        let function = self
+            .functions
+            .get(name)
+            .ok_or_else(|| error(format!("unknown function '{name}'")))?;
+        if function.arguments.len() != args.len() {
+            let count = function.arguments.len();
+            return Err(error(format!(
+                "{name} takes {count} argument{}, not {}",
+                if count == 1 { "" } else { "s" },
+                args.len()
+            )));
+        }
+        let key = format!("{name}()");
+        if stack.contains(&key) {
+            return Err(error(format!("function '{name}' calls itself")));
+        }
+        stack.push(key);
+        let value = self.evaluate_from(&function.body.bind(&function.arguments, args), stack);
+        stack.pop();
+        value
+    }
+
+    fn evaluate_from(
+        &self,
+        expression: &Expression,
+        stack: &mut Vec<String>,
+    ) -> Result<f64, ExpressionError> {
+        Ok(match expression {
+            Expression::Number(value) => *value,
+            Expression::Parameter(name) => self.parameter(name, stack)?,
+            Expression::Negate(inner) => -self.evaluate_from(inner, stack)?,
+            Expression::Binary(operator, lhs, rhs) => {
+                let (lhs, rhs) = (
+                    self.evaluate_from(lhs, stack)?,
+                    self.evaluate_from(rhs, stack)?,
+                );
+                match operator {
+                    Operator::Add => lhs + rhs,
+                    Operator::Subtract => lhs - rhs,
+                    Operator::Multiply => lhs * rhs,
+                    Operator::Divide => lhs / rhs,
+                    Operator::Power => lhs.powf(rhs),
+                    Operator::Equal => truth(lhs == rhs),
+                    Operator::NotEqual => truth(lhs != rhs),
+                    Operator::Less => truth(lhs < rhs),
+                    Operator::LessEqual => truth(lhs <= rhs),
+                    Operator::Greater => truth(lhs > rhs),
+                    Operator::GreaterEqual => truth(lhs >= rhs),
+                    Operator::And => truth(lhs != 0.0 && rhs != 0.0),
+                    Operator::Or => truth(lhs != 0.0 || rhs != 0.0),
+                }
+            }
+            Expression::Call(function, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.evaluate_from(arg, stack))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if is_builtin(function) {
+                    call(function, &args)
+                } else {
+                    self.call(function, &args, stack)?
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,238 @@
+//! Fault injection and random fault campaigns
+//!
+//! A fault is a defect in one component of a circuit: an open
+//! circuit, a short circuit, or drift of the component's value by a
+//! factor. A faulty circuit is the original circuit with the faulty
+//! component replaced (by a large or small resistance for an open or
+//! a short), so it keeps the same nodes and current edges.
+//!
+//! A fault campaign samples faults at random from the fault space of
+//! a circuit (all the faults of all the components that can fail),
+//! and runs a test on the fault-free circuit and on each faulty
+//! circuit. A test is any function that simulates the circuit with
+//! its test stimulus and returns the measured values. A fault is
+//! detected if any measured value differs from the fault-free value
+//! by more than the tolerance. Independent sources and probes are the
+//! test equipment, so they are not part of the fault space.
+
+use rand::seq::SliceRandom;
+
+use crate::circuit::Circuit;
+use crate::component::Component;
+use crate::rng::{RngStreams, FAULT_CAMPAIGN};
+
+/// Resistance of an open-circuit fault
+const OPEN_RESISTANCE: f64 = 1e9;
+
+/// Resistance of a short-circuit fault
+const SHORT_RESISTANCE: f64 = 1e-3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FaultKind {
+    Open,
+    Short,
+    /// The component value (resistance, capacitance or inductance)
+    /// is multiplied by the factor
+    Drift(f64),
+}
+
+/// A fault in a named component instance
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    pub instance: String,
+    pub kind: FaultKind,
+}
+
+/// Whether a component can have open and short faults
+fn can_fail(component: &Component) -> bool {
+    match component {
+        Component::IndependentVoltageSource { .. }
+        | Component::IndependentCurrentSource { .. }
+        | Component::CurrentProbe { .. }
+        | Component::MeasurementProbe { .. }
+        | Component::CurrentControlledCurrentSource { .. }
+        | Component::CurrentControlledVoltageSource { .. } => false,
+        component => component.terminals().len() == 2,
+    }
+}
+
+/// Whether a component has a value that can drift
+fn can_drift(component: &Component) -> bool {
+    matches!(
+        component,
+        Component::Resistor { .. } | Component::Capacitor { .. } | Component::Inductor { .. }
+    )
+}
+
+/// The component with a fault, or None if it cannot have the fault
+pub(crate) fn faulty_component(component: &Component, kind: FaultKind) -> Option<Component> {
+    let resistance = match kind {
+        FaultKind::Open if can_fail(component) => OPEN_RESISTANCE,
+        FaultKind::Short if can_fail(component) => SHORT_RESISTANCE,
+        FaultKind::Drift(factor) => {
+            let mut component = component.clone();
+            match component {
+                Component::Resistor {
+                    ref mut resistance, ..
+                } => *resistance *= factor,
+                Component::Capacitor {
+                    ref mut capacitance,
+                    ..
+                } => *capacitance *= factor,
+                Component::Inductor {
+                    ref mut inductance, ..
+                } => *inductance *= factor,
+                _ => return None,
+            }
+            return Some(component);
+        }
+        _ => return None,
+    };
+    let terminals = component.terminals();
+    Some(Component::Resistor {
+        term_1: terminals[0],
+        term_2: terminals[1],
+        current_edge: component.current_edge(),
+        resistance,
+    })
+}
+
+impl Fault {
+    pub fn new(instance: &str, kind: FaultKind) -> Self {
+        Self {
+            instance: instance.to_string(),
+            kind,
+        }
+    }
+
+    /// The circuit with the fault injected, or None if the circuit
+    /// has no such instance or the instance cannot have the fault
+    pub fn apply(&self, circuit: &Circuit) -> Option<Circuit> {
+        let mut circuit = circuit.clone();
+        let instance = circuit
+            .instances_mut()
+            .iter_mut()
+            .find(|instance| instance.name == self.instance)?;
+        instance.component = faulty_component(&instance.component, self.kind)?;
+        Some(circuit)
+    }
+}
+
+/// All the faults of a circuit: an open and a short for every
+/// component that can fail, and a drift by each of the factors for
+/// every component with a value
+pub fn fault_space(circuit: &Circuit, drift_factors: &[f64]) -> Vec<Fault> {
+    let mut faults = Vec::new();
+    for instance in circuit.instances() {
+        if can_fail(&instance.component) {
+            faults.push(Fault::new(&instance.name, FaultKind::Open));
+            faults.push(Fault::new(&instance.name, FaultKind::Short));
+        }
+        if can_drift(&instance.component) {
+            for factor in drift_factors {
+                faults.push(Fault::new(&instance.name, FaultKind::Drift(*factor)));
+            }
+        }
+    }
+    faults
+}
+
+/// Options for a random fault campaign
+#[derive(Debug, Clone, PartialEq)]
+pub struct CampaignOptions {
+    /// Number of faults sampled (all of them if the fault space is
+    /// smaller)
+    pub num_faults: usize,
+    /// Master seed of the random number streams, so a campaign can
+    /// be repeated
+    pub seed: u64,
+    /// Factors by which component values drift
+    pub drift_factors: Vec<f64>,
+    /// Relative tolerance for detecting a fault
+    pub reltol: f64,
+    /// Absolute tolerance for detecting a fault
+    pub abstol: f64,
+}
+
+impl CampaignOptions {
+    pub fn new(num_faults: usize, seed: u64) -> Self {
+        Self {
+            num_faults,
+            seed,
+            drift_factors: vec![0.5, 2.0],
+            reltol: 0.05,
+            abstol: 1e-6,
+        }
+    }
+}
+
+/// Outcome of a fault campaign
+#[derive(Debug, Clone, PartialEq)]
+pub struct CampaignResult {
+    /// Number of faults in the fault space
+    pub fault_space_size: usize,
+    /// The sampled faults
+    pub faults: Vec<Fault>,
+    /// Whether the test detected each sampled fault
+    pub detected: Vec<bool>,
+}
+
+impl CampaignResult {
+    /// Fraction of the sampled faults that were detected
+    pub fn coverage(&self) -> f64 {
+        if self.faults.is_empty() {
+            return 1.0;
+        }
+        let detected = self.detected.iter().filter(|d| **d).count();
+        detected as f64 / self.faults.len() as f64
+    }
+
+    /// The sampled faults that the test did not detect
+    pub fn undetected(&self) -> Vec<&Fault> {
+        self.faults
+            .iter()
+            .zip(&self.detected)
+            .filter(|(_, detected)| !**detected)
+            .map(|(fault, _)| fault)
+            .collect()
+    }
+}
+
+/// Whether a measurement of a faulty circuit differs from the
+/// fault-free measurement by more than the tolerances
+fn differs(golden: &[f64], measured: &[f64], options: &CampaignOptions) -> bool {
+    golden.len() != measured.len()
+        || golden.iter().zip(measured).any(|(g, m)| {
+            !m.is_finite() || (m - g).abs() > options.abstol + options.reltol * g.abs()
+        })
+}
+
+/// Sample faults at random from the fault space of a circuit, and
+/// find which of them a test detects
+pub fn run_campaign(
+    circuit: &Circuit,
+    options: &CampaignOptions,
+    test: impl Fn(&Circuit) -> Vec<f64>,
+) -> CampaignResult {
+    let space = fault_space(circuit, &options.drift_factors);
+    let mut rng = RngStreams::new(options.seed).stream(FAULT_CAMPAIGN);
+    let faults: Vec<Fault> = space
+        .choose_multiple(&mut rng, options.num_faults)
+        .cloned()
+        .collect();
+    let golden = test(circuit);
+    let detected = faults
+        .iter()
+        .map(|fault| {
+            let faulty = fault
+                .apply(circuit)
+                .expect("Faults in the fault space can be applied");
+            differs(&golden, &test(&faulty), options)
+        })
+        .collect();
+    CampaignResult {
+        fault_space_size: space.len(),
+        faults,
+        detected,
+    }
+}
@@ -0,0 +1,331 @@
+//! Fourier analysis
+//!
+//! The DC component, fundamental and harmonics of a transient
+//! waveform, as for a SPICE `.FOUR` line, with the total harmonic
+//! distortion. As in ngspice, the last period of the waveform (which
+//! is assumed to be periodic by then) is interpolated linearly onto a
+//! uniform grid, and each harmonic is found by correlation with a sine
+//! and cosine at its frequency. The phase is that of a sine, so a sine
+//! wave starting at zero has a phase of zero, and the normalised
+//! magnitudes and phases are relative to the fundamental. The results
+//! print in the format of ngspice.
+
+use std::f64::consts::PI;
+use std::fmt;
+
+use crate::error::{invalid, EsimError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FourierOptions {
+    /// Number of harmonics, counting the DC component
+    pub num_harmonics: usize,
+    /// Number of points the last period is interpolated onto
+    pub grid_size: usize,
+}
+
+impl Default for FourierOptions {
+    /// The ngspice defaults
+    fn default() -> Self {
+        Self {
+            num_harmonics: 10,
+            grid_size: 200,
+        }
+    }
+}
+
+/// One harmonic of a waveform
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Harmonic {
+    /// Multiple of the fundamental (zero for the DC component)
+    pub number: usize,
+    pub frequency: f64,
+    pub magnitude: f64,
+    /// Phase in degrees
+    pub phase: f64,
+    /// Magnitude relative to the fundamental
+    pub normalized_magnitude: f64,
+    /// Phase relative to the fundamental, in degrees
+    pub normalized_phase: f64,
+}
+
+/// Harmonics of a waveform at a fundamental frequency
+#[derive(Debug, Clone, PartialEq)]
+pub struct FourierResult {
+    /// Name of the waveform, such as "v(2)"
+    pub name: String,
+    pub fundamental: f64,
+    /// The harmonics, from the DC component
+    pub harmonics: Vec<Harmonic>,
+    /// Total harmonic distortion, in percent
+    pub thd: f64,
+    pub grid_size: usize,
+}
+
+impl FourierResult {
+    /// The DC component
+    pub fn dc(&self) -> f64 {
+        self.harmonics.first().map_or(0.0, |h| h.magnitude)
+    }
+}
+
+impl fmt::Display for FourierResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Fourier analysis for {}:", self.name)?;
+        writeln!(
+            f,
+            "  No. Harmonics: {}, THD: {} %, Gridsize: {}, Interpolation Degree: 1",
+            self.harmonics.len(),
+            significant(self.thd),
+            self.grid_size
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<12} {:<12} {:<12} {:<12} {:<12} Norm. Phase",
+            "Harmonic", "Frequency", "Magnitude", "Phase", "Norm. Mag"
+        )?;
+        writeln!(
+            f,
+            "{:<12} {:<12} {:<12} {:<12} {:<12} -----------",
+            "--------", "---------", "---------", "-----", "---------"
+        )?;
+        for harmonic in &self.harmonics {
+            writeln!(
+                f,
+                " {:<11} {:<12} {:<12} {:<12} {:<12} {}",
+                harmonic.number,
+                significant(harmonic.frequency),
+                significant(harmonic.magnitude),
+                significant(harmonic.phase),
+                significant(harmonic.normalized_magnitude),
+                significant(harmonic.normalized_phase)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A value to six significant figures, as by the `%g` format of C
+fn significant(value: f64) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return format!("{value}");
+    }
+    let exponent = value.abs().log10().floor() as i32;
+    if (-5..6).contains(&exponent) {
+        let decimals = (5 - exponent).max(0) as usize;
+        let text = format!("{value:.decimals$}");
+        if text.contains('.') {
+            text.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            text
+        }
+    } else {
+        let text = format!("{value:.5e}");
+        let (mantissa, exponent) = text.split_once('e').expect("Exponent format");
+        let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+        let exponent: i32 = exponent.parse().expect("Exponent format");
+        format!(
+            "{mantissa}e{}{:02}",
+            if exponent < 0 { '-' } else { '+' },
+            exponent.abs()
+        )
+    }
+}
+
+/// Value of a waveform at a time, by linear interpolation
+fn interpolate(time: &[f64], values: &[f64], t: f64) -> f64 {
+    let index = time.partition_point(|x| *x < t).clamp(1, time.len() - 1);
+    let (t0, t1) = (time[index - 1], time[index]);
+    let (v0, v1) = (values[index - 1], values[index]);
+    if t1 == t0 {
+        v1
+    } else {
+        v0 + (v1 - v0) * (t - t0) / (t1 - t0)
+    }
+}
+
+/// Fourier analysis of the last period of a waveform (with values at
+/// increasing times) at a fundamental frequency (in Hz). Panics if
+/// the waveform is shorter than a period (see [try_fourier]).
+pub fn fourier(
+    name: &str,
+    time: &[f64],
+    values: &[f64],
+    fundamental: f64,
+    options: &FourierOptions,
+) -> FourierResult {
+    try_fourier(name, time, values, fundamental, options).unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Fourier analysis as for [fourier], returning an error if the
+/// fundamental frequency is not positive, the grid is empty, the
+/// times and values differ in length, or the waveform is shorter than
+/// a period
+pub fn try_fourier(
+    name: &str,
+    time: &[f64],
+    values: &[f64],
+    fundamental: f64,
+    options: &FourierOptions,
+) -> Result<FourierResult, EsimError> {
+    if fundamental.is_nan() || fundamental <= 0.0 {
+        return Err(invalid(
+            "Fundamental frequency must be positive".to_string(),
+        ));
+    }
+    if options.grid_size == 0 {
+        return Err(invalid("Fourier grid must have a point".to_string()));
+    }
+    if time.len() != values.len() {
+        return Err(invalid(format!(
+            "Waveform {name} has {} times but {} values",
+            time.len(),
+            values.len()
+        )));
+    }
+    let period = 1.0 / fundamental;
+    let (start, stop) = match (time.first(), time.last()) {
+        (Some(start), Some(stop)) if stop - start >= period * (1.0 - 1e-9) => (*start, *stop),
+        _ => {
+            return Err(invalid(
+                "Waveform must be at least a period long for Fourier analysis".to_string(),
+            ))
+        }
+    };
+    let first = (stop - period).max(start);
+    let grid_size = options.grid_size;
+    let grid: Vec<f64> = (0..grid_size)
+        .map(|j| interpolate(time, values, first + period * j as f64 / grid_size as f64))
+        .collect();
+    // Correlation with the sine and cosine of each harmonic
+    let correlations: Vec<(f64, f64)> = (0..options.num_harmonics)
+        .map(|n| {
+            grid.iter().enumerate().fold((0.0, 0.0), |(s, c), (j, v)| {
+                let angle = 2.0 * PI * (n * j) as f64 / grid_size as f64;
+                (s + v * angle.sin(), c + v * angle.cos())
+            })
+        })
+        .collect();
+    let mut harmonics: Vec<Harmonic> = correlations
+        .iter()
+        .enumerate()
+        .map(|(n, (s, c))| {
+            let (magnitude, phase) = if n == 0 {
+                (c / grid_size as f64, 0.0)
+            } else {
+                let (s, c) = (2.0 * s / grid_size as f64, 2.0 * c / grid_size as f64);
+                (s.hypot(c), c.atan2(s).to_degrees())
+            };
+            Harmonic {
+                number: n,
+                frequency: n as f64 * fundamental,
+                magnitude,
+                phase,
+                normalized_magnitude: 0.0,
+                normalized_phase: 0.0,
+            }
+        })
+        .collect();
+    if let Some(&Harmonic {
+        magnitude, phase, ..
+    }) = harmonics.get(1)
+    {
+        for harmonic in harmonics.iter_mut().skip(1) {
+            harmonic.normalized_magnitude = harmonic.magnitude / magnitude;
+            harmonic.normalized_phase = harmonic.phase - phase;
+        }
+    }
+    let thd = 100.0
+        * harmonics
+            .iter()
+            .skip(2)
+            .map(|h| h.normalized_magnitude.powi(2))
+            .sum::<f64>()
+            .sqrt();
+    Ok(FourierResult {
+        name: name.to_string(),
+        fundamental,
+        harmonics,
+        thd,
+        grid_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples of a waveform over two periods of 1 ms
+    fn sampled(wave: impl Fn(f64) -> f64) -> (Vec<f64>, Vec<f64>) {
+        let time: Vec<f64> = (0..=2000).map(|i| i as f64 * 1e-6).collect();
+        let values = time.iter().map(|t| wave(*t)).collect();
+        (time, values)
+    }
+
+    #[test]
+    fn harmonics_and_distortion_are_found() {
+        let omega = 2.0 * PI * 1e3;
+        let (time, values) = sampled(|t| 0.5 + (omega * t).sin() + 0.1 * (3.0 * omega * t).sin());
+        let result = fourier("v(out)", &time, &values, 1e3, &FourierOptions::default());
+        assert_eq!(result.harmonics.len(), 10);
+        assert!((result.dc() - 0.5).abs() < 1e-9);
+        let magnitudes: Vec<f64> = result.harmonics.iter().map(|h| h.magnitude).collect();
+        assert!((magnitudes[1] - 1.0).abs() < 1e-9);
+        assert!((magnitudes[3] - 0.1).abs() < 1e-9);
+        assert!(magnitudes[2] < 1e-9 && magnitudes[4] < 1e-9);
+        assert_eq!(result.harmonics[3].frequency, 3e3);
+        assert!((result.harmonics[3].normalized_magnitude - 0.1).abs() < 1e-9);
+        // The third harmonic is the only distortion
+        assert!((result.thd - 10.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn phase_is_that_of_a_sine() {
+        let omega = 2.0 * PI * 1e3;
+        let (time, values) = sampled(|t| (omega * t).cos() + 0.2 * (2.0 * omega * t).sin());
+        let result = fourier("v(1)", &time, &values, 1e3, &FourierOptions::default());
+        // A cosine leads a sine by 90 degrees
+        assert!((result.harmonics[1].phase - 90.0).abs() < 1e-6);
+        assert!(result.harmonics[2].phase.abs() < 1e-6);
+        assert!((result.harmonics[2].normalized_phase + 90.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn results_print_in_the_format_of_ngspice() {
+        let omega = 2.0 * PI * 1e3;
+        let (time, values) = sampled(|t| (omega * t).sin() + 0.1 * (3.0 * omega * t).sin());
+        let options = FourierOptions {
+            num_harmonics: 4,
+            ..Default::default()
+        };
+        let text = fourier("v(out)", &time, &values, 1e3, &options).to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "Fourier analysis for v(out):");
+        assert_eq!(
+            lines[1],
+            "  No. Harmonics: 4, THD: 10 %, Gridsize: 200, Interpolation Degree: 1"
+        );
+        assert!(lines[3].starts_with("Harmonic     Frequency    Magnitude"));
+        assert_eq!(lines.len(), 9);
+        let third: Vec<&str> = lines[8].split_whitespace().collect();
+        assert_eq!(&third[..3], ["3", "3000", "0.1"]);
+    }
+
+    #[test]
+    fn numbers_are_written_to_six_significant_figures() {
+        assert_eq!(significant(0.0), "0");
+        assert_eq!(significant(1234.5678), "1234.57");
+        assert_eq!(significant(-0.000123456789), "-0.000123457");
+        assert_eq!(significant(1.5e-7), "1.5e-07");
+        assert_eq!(significant(2e6), "2e+06");
+    }
+
+    #[test]
+    fn a_waveform_shorter_than_a_period_is_an_error() {
+        let (time, values) = sampled(|t| t);
+        let options = FourierOptions::default();
+        assert!(try_fourier("v(1)", &time, &values, 100.0, &options).is_err());
+        assert!(try_fourier("v(1)", &time, &values, 0.0, &options).is_err());
+        assert!(try_fourier("v(1)", &time, &values[1..], 1e3, &options).is_err());
+    }
+}
@@ -0,0 +1,485 @@
+//! Harmonic balance analysis
+//!
+//! The periodic steady state of a circuit driven at a fundamental
+//! frequency, solved in the frequency domain with the voltages and
+//! currents truncated to a number of harmonics, rather than by a
+//! transient analysis run until the start-up transient has decayed
+//! (which takes a long time in circuits with high Q or long time
+//! constants, such as tuned amplifiers).
+//!
+//! The linear part of the circuit is solved at each harmonic as in AC
+//! analysis. The junctions are the nonlinear part: in each Newton
+//! iteration, their voltages are transformed to the time domain by an
+//! FFT on a grid over one period, their currents and conductances are
+//! found at each time point (with the voltage limited as in the DC
+//! analysis), and these are transformed back to the frequency domain,
+//! where the conductance couples each harmonic to the others. The
+//! other nonlinear components are linearised about the DC operating
+//! point, as in AC analysis.
+//!
+//! Each independent source is driven by its transient waveform (or its
+//! DC value if it has none) over the first period, which must be
+//! periodic at the fundamental. Two tones (such as the RF and LO inputs
+//! of a mixer) are analysed together if their frequencies are both
+//! harmonics of the fundamental, so with the fundamental the largest
+//! common divisor of their frequencies.
+//!
+//! The harmonics of a node voltage or edge current are phasors, as in
+//! AC analysis, so the waveform is
+//! $x(t) = \operatorname{Re} \sum_k X_k e^{j k \omega t}$, with $X_0$
+//! the (real) DC component.
+
+use std::f64::consts::PI;
+
+use num::Complex;
+
+use crate::ac::LinearAcAnalysis;
+use crate::circuit::Circuit;
+use crate::component::{Component, Junction};
+use crate::node::NodeNames;
+use crate::sparse::{plus_equals, solve, SparseMat};
+
+/// Newton iteration settings, as in the DC analysis
+const MAX_NEWTON_ITERATIONS: usize = 100;
+const RELTOL: f64 = 1e-3;
+const VNTOL: f64 = 1e-6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HarmonicBalanceOptions {
+    /// Fundamental frequency, in Hz
+    pub fundamental: f64,
+    /// Number of harmonics above the DC component
+    pub num_harmonics: usize,
+}
+
+impl HarmonicBalanceOptions {
+    pub fn new(fundamental: f64, num_harmonics: usize) -> Self {
+        Self {
+            fundamental,
+            num_harmonics,
+        }
+    }
+
+    /// Number of time points in a period, which is a power of two at
+    /// least twice the number of harmonics of the conductance of a
+    /// junction (twice those of the solution), so that they do not
+    /// alias
+    fn grid_size(&self) -> usize {
+        (4 * self.num_harmonics + 2).next_power_of_two()
+    }
+}
+
+/// Periodic steady state of a circuit, by harmonic
+#[derive(Debug, Clone, PartialEq)]
+pub struct HarmonicBalanceResult {
+    pub fundamental: f64,
+    /// Phasor of each harmonic (from the DC component) of each node
+    /// voltage, by node (from node 1)
+    pub voltages: Vec<Vec<Complex<f64>>>,
+    /// Phasor of each harmonic of each edge current, by edge
+    pub currents: Vec<Vec<Complex<f64>>>,
+    /// Warnings of the analysis, such as a Newton iteration that did
+    /// not converge
+    pub warnings: Vec<String>,
+    nodes: NodeNames,
+}
+
+impl HarmonicBalanceResult {
+    /// Harmonics of the voltage of a node (from the DC component)
+    pub fn voltage(&self, node: usize) -> Vec<Complex<f64>> {
+        match node {
+            0 => vec![Complex::new(0.0, 0.0); self.num_harmonics() + 1],
+            n => self.voltages[n - 1].clone(),
+        }
+    }
+
+    /// Harmonics of the voltage of a node by name (see [crate::node]),
+    /// if there is a node with the name in the circuit
+    pub fn node_voltage(&self, name: &str) -> Option<Vec<Complex<f64>>> {
+        self.nodes
+            .get(name)
+            .filter(|node| *node <= self.voltages.len())
+            .map(|node| self.voltage(node))
+    }
+
+    /// Harmonics of the current of an edge (from the DC component)
+    pub fn current(&self, edge: usize) -> Vec<Complex<f64>> {
+        self.currents[edge].clone()
+    }
+
+    /// Number of harmonics above the DC component
+    pub fn num_harmonics(&self) -> usize {
+        self.voltages.first().map_or(0, |v| v.len() - 1)
+    }
+
+    /// The waveform of a node voltage over one period, at a number of
+    /// equally spaced times from zero. Returns the times and values.
+    pub fn waveform(&self, node: usize, num_points: usize) -> (Vec<f64>, Vec<f64>) {
+        let harmonics = self.voltage(node);
+        let period = 1.0 / self.fundamental;
+        (0..num_points)
+            .map(|n| {
+                let time = period * n as f64 / num_points as f64;
+                let phase = 2.0 * PI * self.fundamental * time;
+                let value = harmonics
+                    .iter()
+                    .enumerate()
+                    .map(|(k, x)| (x * Complex::from_polar(1.0, k as f64 * phase)).re)
+                    .sum::<f64>();
+                (time, value)
+            })
+            .unzip()
+    }
+
+    /// Total harmonic distortion of a node voltage, in percent
+    pub fn thd(&self, node: usize) -> f64 {
+        let harmonics = self.voltage(node);
+        let distortion: f64 = harmonics.iter().skip(2).map(|x| x.norm_sqr()).sum();
+        100.0 * distortion.sqrt() / harmonics[1].norm()
+    }
+}
+
+/// In-place radix-2 FFT of a power-of-two number of values, or the
+/// inverse (without the scaling by the number of values)
+fn fft(values: &mut [Complex<f64>], inverse: bool) {
+    let size = values.len();
+    let bits = size.trailing_zeros();
+    for i in 0..size {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut length = 2;
+    while length <= size {
+        let root = Complex::from_polar(1.0, sign * 2.0 * PI / length as f64);
+        for chunk in values.chunks_mut(length) {
+            let (lower, upper) = chunk.split_at_mut(length / 2);
+            let mut twiddle = Complex::new(1.0, 0.0);
+            for (a, b) in lower.iter_mut().zip(upper) {
+                let t = *b * twiddle;
+                *b = *a - t;
+                *a += t;
+                twiddle *= root;
+            }
+        }
+        length *= 2;
+    }
+}
+
+/// Fourier coefficients (from harmonic -max to max) of a real waveform
+/// sampled over a period
+fn spectrum(samples: &[f64], max: usize) -> Vec<Complex<f64>> {
+    let size = samples.len();
+    let mut values: Vec<Complex<f64>> = samples.iter().map(|x| Complex::new(*x, 0.0)).collect();
+    fft(&mut values, false);
+    (-(max as isize)..=max as isize)
+        .map(|k| values[k.rem_euclid(size as isize) as usize] / size as f64)
+        .collect()
+}
+
+/// A junction, with the conductance stamped for it by the AC analysis
+struct HbJunction<'a> {
+    anode: usize,
+    cathode: usize,
+    model: &'a dyn Junction,
+    conductance: f64,
+}
+
+/// Add an admittance between two nodes to the block of the Jacobian
+/// coupling two harmonics
+pub(crate) fn add_admittance(
+    matrix: &mut SparseMat<Complex<f64>>,
+    (row, column): (usize, usize),
+    (term_1, term_2): (usize, usize),
+    admittance: Complex<f64>,
+) {
+    for (node_1, node_2, sign) in [
+        (term_1, term_1, 1.0),
+        (term_2, term_2, 1.0),
+        (term_1, term_2, -1.0),
+        (term_2, term_1, -1.0),
+    ] {
+        if node_1 != 0 && node_2 != 0 {
+            plus_equals(
+                matrix,
+                row + node_1 - 1,
+                column + node_2 - 1,
+                sign * admittance,
+            );
+        }
+    }
+}
+
+/// Solve the periodic steady state of a circuit by harmonic balance
+pub fn harmonic_balance(
+    circuit: &Circuit,
+    options: &HarmonicBalanceOptions,
+) -> HarmonicBalanceResult {
+    let analysis = LinearAcAnalysis::new(circuit);
+    let max = options.num_harmonics;
+    let grid_size = options.grid_size();
+    let period = 1.0 / options.fundamental;
+    let times: Vec<f64> = (0..grid_size)
+        .map(|n| period * n as f64 / grid_size as f64)
+        .collect();
+    // Spectrum of each independent source (empty for the other
+    // instances)
+    let spectra: Vec<Vec<Complex<f64>>> = analysis
+        .circuit()
+        .instances()
+        .iter()
+        .map(|instance| match instance.component {
+            Component::IndependentVoltageSource {
+                voltage: value,
+                ref waveform,
+                ..
+            }
+            | Component::IndependentCurrentSource {
+                current: value,
+                ref waveform,
+                ..
+            } => {
+                let samples: Vec<f64> = match waveform {
+                    Some(waveform) => times.iter().map(|t| waveform.value(*t)).collect(),
+                    None => vec![value; grid_size],
+                };
+                spectrum(&samples, max)
+            }
+            _ => Vec::new(),
+        })
+        .collect();
+
+    // The linear system at each harmonic (from -max), with the sources
+    let linear: Vec<_> = (-(max as isize)..=max as isize)
+        .enumerate()
+        .map(|(index, k)| {
+            let mut mna = analysis.assemble(k as f64 * options.fundamental, false);
+            for (instance, spectrum) in analysis.circuit().instances().iter().zip(&spectra) {
+                match instance.component {
+                    Component::IndependentVoltageSource { current_edge, .. } => {
+                        mna.add_series_voltage(current_edge, spectrum[index])
+                    }
+                    Component::IndependentCurrentSource {
+                        term_pos, term_neg, ..
+                    } => mna.add_independent_current_source(term_pos, term_neg, spectrum[index]),
+                    Component::Photodiode {
+                        anode,
+                        cathode,
+                        responsivity,
+                        irradiance,
+                        ..
+                    } if k == 0 => mna.add_independent_current_source(
+                        cathode,
+                        anode,
+                        (responsivity * irradiance).into(),
+                    ),
+                    _ => {}
+                }
+            }
+            (mna.entries(), mna.rhs(), mna.num_voltage_nodes())
+        })
+        .collect();
+    let size = linear[0].1.len();
+    let num_voltage_nodes = linear[0].2;
+
+    let junctions: Vec<HbJunction> = analysis
+        .circuit()
+        .instances()
+        .iter()
+        .filter_map(|instance| {
+            let (anode, cathode, model) = instance.component.junction()?;
+            let voltage = analysis.dc_voltage(anode) - analysis.dc_voltage(cathode);
+            Some(HbJunction {
+                anode,
+                cathode,
+                model,
+                conductance: model.linearise(voltage).0,
+            })
+        })
+        .collect();
+
+    // Solve with the junctions linearised at their voltage at each time
+    // point, returning the solution by harmonic (from -max)
+    let solve_linearised = |voltages: &[Vec<f64>]| -> Vec<Vec<Complex<f64>>> {
+        let blocks = 2 * max + 1;
+        let mut matrix = SparseMat::new(blocks * size, blocks * size);
+        let mut rhs = Vec::with_capacity(blocks * size);
+        for (block, (entries, block_rhs, _)) in linear.iter().enumerate() {
+            for ((row, column), value) in entries {
+                plus_equals(
+                    &mut matrix,
+                    block * size + row,
+                    block * size + column,
+                    *value,
+                );
+            }
+            rhs.extend_from_slice(block_rhs);
+        }
+        for (junction, voltages) in junctions.iter().zip(voltages) {
+            let (conductances, currents): (Vec<f64>, Vec<f64>) = voltages
+                .iter()
+                .map(|v| {
+                    let (conductance, current) = junction.model.linearise(*v);
+                    (conductance - junction.conductance, current)
+                })
+                .unzip();
+            let conductances = spectrum(&conductances, 2 * max);
+            let currents = spectrum(&currents, max);
+            let terminals = (junction.anode, junction.cathode);
+            for row in 0..blocks {
+                for column in 0..blocks {
+                    let admittance = conductances[2 * max + row - column];
+                    add_admittance(
+                        &mut matrix,
+                        (row * size, column * size),
+                        terminals,
+                        admittance,
+                    );
+                }
+                if junction.anode != 0 {
+                    rhs[row * size + junction.anode - 1] -= currents[row];
+                }
+                if junction.cathode != 0 {
+                    rhs[row * size + junction.cathode - 1] += currents[row];
+                }
+            }
+        }
+        solve(matrix, rhs)
+            .chunks(size)
+            .map(|block| block.to_vec())
+            .collect()
+    };
+
+    // Voltage of each junction at each time point
+    let junction_voltages = |solution: &[Vec<Complex<f64>>]| -> Vec<Vec<f64>> {
+        junctions
+            .iter()
+            .map(|junction| {
+                let mut values = vec![Complex::new(0.0, 0.0); grid_size];
+                for (k, block) in (-(max as isize)..).zip(solution) {
+                    let node = |node: usize| match node {
+                        0 => Complex::new(0.0, 0.0),
+                        n => block[n - 1],
+                    };
+                    values[k.rem_euclid(grid_size as isize) as usize] =
+                        node(junction.anode) - node(junction.cathode);
+                }
+                fft(&mut values, true);
+                values.iter().map(|v| v.re).collect()
+            })
+            .collect()
+    };
+
+    let mut voltages: Vec<Vec<f64>> = junctions
+        .iter()
+        .map(|junction| {
+            let voltage =
+                analysis.dc_voltage(junction.anode) - analysis.dc_voltage(junction.cathode);
+            vec![voltage; grid_size]
+        })
+        .collect();
+    let mut solution = solve_linearised(&voltages);
+    let mut converged = junctions.is_empty();
+    for _ in 0..MAX_NEWTON_ITERATIONS {
+        if converged {
+            break;
+        }
+        converged = true;
+        let new_voltages = junction_voltages(&solution);
+        for ((junction, old), new) in junctions.iter().zip(&mut voltages).zip(new_voltages) {
+            for (old, new) in old.iter_mut().zip(new) {
+                let new = junction.model.limit_voltage(new, *old);
+                if (new - *old).abs() > RELTOL * new.abs().max(old.abs()) + VNTOL {
+                    converged = false;
+                }
+                *old = new;
+            }
+        }
+        if !converged {
+            solution = solve_linearised(&voltages);
+        }
+    }
+    let mut warnings = Vec::new();
+    if !converged {
+        warnings.push(format!(
+            "Harmonic balance did not converge after {MAX_NEWTON_ITERATIONS} iterations"
+        ));
+    }
+
+    // Phasors from the coefficients of the non-negative harmonics
+    let phasors = |index: usize| -> Vec<Complex<f64>> {
+        solution[max..]
+            .iter()
+            .enumerate()
+            .map(|(k, block)| {
+                if k == 0 {
+                    block[index]
+                } else {
+                    2.0 * block[index]
+                }
+            })
+            .collect()
+    };
+    HarmonicBalanceResult {
+        fundamental: options.fundamental,
+        voltages: (0..num_voltage_nodes).map(phasors).collect(),
+        currents: (num_voltage_nodes..size).map(phasors).collect(),
+        warnings,
+        nodes: circuit.node_names().clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CircuitBuilder;
+    use crate::component::THERMAL_VOLTAGE;
+    use crate::waveform::Waveform;
+
+    /// Modified Bessel function of the first kind, by its series
+    fn bessel_i(order: u32, x: f64) -> f64 {
+        let mut term = (x / 2.0).powi(order as i32) / (1..=order).product::<u32>() as f64;
+        let mut sum = 0.0;
+        for m in 1..30 {
+            sum += term;
+            term *= (x / 2.0).powi(2) / (m * (m + order)) as f64;
+        }
+        sum
+    }
+
+    #[test]
+    fn diode_current_harmonics_follow_the_bessel_functions() {
+        // With v = V0 + Va sin(wt) across a diode, the current is
+        // Is e^(V0/Vt) e^(a sin(wt)) - Is, whose harmonics have
+        // magnitudes 2 Is e^(V0/Vt) I_k(a), with a = Va / Vt
+        let (bias, amplitude) = (0.6, 20e-3);
+        let circuit = CircuitBuilder::new()
+            .vsource("V1", "a", "0", bias)
+            .with_waveform(Waveform::Sin {
+                offset: bias,
+                amplitude,
+                frequency: 1e3,
+                delay: 0.0,
+                damping: 0.0,
+            })
+            .diode("D1", "a", "0")
+            .build()
+            .unwrap();
+        let result = harmonic_balance(&circuit, &HarmonicBalanceOptions::new(1e3, 8));
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+        let current = result.current(0);
+        let a = amplitude / THERMAL_VOLTAGE;
+        let scale = 1e-14 * (bias / THERMAL_VOLTAGE).exp();
+        let dc = scale * bessel_i(0, a) - 1e-14;
+        assert!((current[0].norm() / dc - 1.0).abs() < 1e-6);
+        for k in 1..4 {
+            let expected = 2.0 * scale * bessel_i(k, a);
+            assert!(
+                (current[k as usize].norm() / expected - 1.0).abs() < 1e-6,
+                "harmonic {k}"
+            );
+        }
+    }
+}
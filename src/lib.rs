@@ -1,3 +1,92 @@
-pub mod mna;
+//! Circuit simulation
+//!
+//! A [Circuit] is built from instances of [Component]s connected
+//! between numbered nodes (node 0 is ground), which can be named (see
+//! [node]), either directly, with a [CircuitBuilder], or by
+//! parsing a netlist with [parse_netlist], and then analysed: its DC
+//! operating point ([operating_point]), DC sweeps ([dc_sweep]), AC
+//! response ([LinearAcAnalysis]), small-signal DC transfer function
+//! ([transfer_function()]), noise ([noise()]), poles and zeros
+//! ([pole_zero()]) and transient response ([TransientAnalysis]), along
+//! with statistical, fault and other analyses in their modules.
+//!
+//! The most common items are re-exported here, so the module layout
+//! need not be known to get started, and all of them (with the traits)
+//! can be imported at once from the [prelude].
+
+pub mod ac;
+pub mod analysis;
+pub mod anonymize;
+pub mod builder;
+pub mod characterize;
+pub mod circuit;
+pub mod component;
+pub mod crosscheck;
 pub mod dc;
+pub mod debugger;
+pub mod digital;
+pub mod error;
+pub mod evaluation;
+pub mod expression;
+pub mod fault;
+pub mod fourier;
+pub mod harmonic_balance;
+pub mod library;
+pub mod loading;
+pub mod loop_gain;
+pub mod measure;
+pub(crate) mod mna;
+pub mod monte_carlo;
+pub mod netlist;
+pub mod node;
+pub mod noise;
+pub mod options;
+#[cfg(feature = "osdi")]
+pub mod osdi;
+pub mod pac;
+pub mod plan;
+pub mod pole_fit;
+pub mod pole_zero;
+pub mod power;
+pub mod prelude;
+pub mod pss;
+pub mod rawfile;
+pub mod rng;
+pub mod schema;
+pub mod sensitivity;
+pub mod shell;
+pub mod solver;
 pub mod sparse;
+pub mod statistics;
+pub mod step;
+pub mod sweep;
+pub mod tdr;
+pub mod topology;
+pub mod transfer_function;
+pub mod transient;
+pub mod two_port;
+pub mod value;
+pub mod veriloga;
+pub mod watch;
+pub mod waveform;
+
+pub use crate::ac::LinearAcAnalysis;
+pub use crate::builder::{BuildError, CircuitBuilder};
+pub use crate::circuit::Circuit;
+pub use crate::component::{AcSpec, Component};
+pub use crate::dc::{operating_point, try_operating_point, DcSolution};
+pub use crate::error::EsimError;
+pub use crate::netlist::{
+    parse_netlist, parse_netlist_file, write_netlist, NetlistError, WriteError,
+};
+pub use crate::noise::{noise, try_noise, NoiseResult};
+pub use crate::pole_zero::{pole_zero, try_pole_zero, PoleZeroResult};
+pub use crate::sweep::{
+    dc_sweep, dc_sweep_nested, try_dc_sweep, try_dc_sweep_nested, DcSweepResult, SweepRange,
+};
+pub use crate::transfer_function::{transfer_function, try_transfer_function, TransferFunction};
+pub use crate::transient::{
+    IntegrationMethod, StepControl, TransientAnalysis, TransientOptions, TransientResult,
+};
+pub use crate::value::{parse_value, IntoValue, ValueError};
+pub use crate::waveform::Waveform;
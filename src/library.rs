@@ -0,0 +1,213 @@
+//! Built-in library of common parts
+//!
+//! Models of a few common parts are built in, so circuits using real
+//! parts can be simulated without finding their models first. A
+//! netlist uses them by name, as if they were defined in it, unless it
+//! defines a model or subcircuit of the same name itself:
+//!
+//! ```text
+//! D1 in out 1N4148
+//! Q1 c b e 2N2222
+//! X1 inp inn vcc vee out TL081
+//! ```
+//!
+//! and a [crate::CircuitBuilder] uses the diode and transistor models
+//! in the same way (see [crate::CircuitBuilder::diode_model]). The
+//! parts are
+//!
+//! - `1N4148`: small-signal switching diode
+//! - `1N4007`: 1 A, 1000 V rectifier diode
+//! - `2N2222`: general-purpose NPN transistor
+//! - `2N7000`: small-signal N-channel enhancement MOSFET
+//! - `TL081`: JFET-input op-amp, with ports (non-inverting input,
+//!   inverting input, positive supply, negative supply, output)
+//!
+//! The op-amp is a macromodel: a high input resistance, a
+//! transconductance stage into a resistor and capacitor, giving a gain
+//! of 200 V/mV and a dominant pole at 15 Hz (a gain-bandwidth product of
+//! 3 MHz), and an output resistance of 75 Ω. Its output swings to
+//! within about 1.5 V of the supplies, where it is clamped by diodes,
+//! but its slew rate is not limited.
+//!
+//! The transistors have the junction and overlap capacitances of their
+//! data sheets, but only the level of detail of the models in this
+//! crate: the 2N2222 has no high-level injection, and the 2N7000 is a
+//! level 1 MOSFET with its capacitances at the default width.
+
+use crate::component::{
+    BjtModel, DiodeModel, Model, MosfetModel, Polarity, DEFAULT_SIZE, NOMINAL_TEMPERATURE,
+};
+
+/// What a part of the library is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Contents {
+    /// A model card, used by name by a device of its type
+    Model(Model),
+    /// The text of a `.SUBCKT` definition, used by name by an `X` line
+    Subcircuit(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Part {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub contents: Contents,
+}
+
+const TL081: &str = "\
+.SUBCKT TL081 inp inn vcc vee out
+RIN inp inn 1T
+G1 0 gain inp inn 200u
+R1 gain 0 1G
+C1 gain 0 10.6n
+VCP vcc cp 2.1
+DCP gain cp
+VCN cn vee 2.1
+DCN cn gain
+E1 buf 0 gain 0 1
+ROUT buf out 75
+.ENDS TL081
+";
+
+/// The parts of the library
+pub const PARTS: [Part; 5] = [
+    Part {
+        name: "1N4148",
+        description: "Small-signal switching diode",
+        contents: Contents::Model(Model::Diode(DiodeModel {
+            is: 2.52e-9,
+            n: 1.752,
+            rs: 0.568,
+            kf: 0.0,
+            af: 1.0,
+            eg: 1.11,
+            xti: 3.0,
+            tnom: NOMINAL_TEMPERATURE,
+            temp: None,
+        })),
+    },
+    Part {
+        name: "1N4007",
+        description: "1 A, 1000 V rectifier diode",
+        contents: Contents::Model(Model::Diode(DiodeModel {
+            is: 7.03e-9,
+            n: 1.808,
+            rs: 0.0342,
+            kf: 0.0,
+            af: 1.0,
+            eg: 1.11,
+            xti: 3.0,
+            tnom: NOMINAL_TEMPERATURE,
+            temp: None,
+        })),
+    },
+    Part {
+        name: "2N2222",
+        description: "General-purpose NPN transistor",
+        contents: Contents::Model(Model::Bjt(BjtModel {
+            polarity: Polarity::N,
+            is: 14.34e-15,
+            bf: 255.9,
+            br: 6.092,
+            nf: 1.0,
+            nr: 1.0,
+            vaf: 74.03,
+            rb: 10.0,
+            rc: 1.0,
+            re: 0.0,
+            cje: 22.01e-12,
+            cjc: 7.306e-12,
+            eg: 1.11,
+            xti: 3.0,
+            tnom: NOMINAL_TEMPERATURE,
+            temp: None,
+        })),
+    },
+    Part {
+        name: "2N7000",
+        description: "Small-signal N-channel enhancement MOSFET",
+        contents: Contents::Model(Model::Mosfet(MosfetModel {
+            polarity: Polarity::N,
+            vto: 2.0,
+            kp: 0.1,
+            lambda: 0.02,
+            rd: 1.0,
+            rs: 0.5,
+            cgso: 150e-9,
+            cgdo: 50e-9,
+            tnom: NOMINAL_TEMPERATURE,
+            width: DEFAULT_SIZE,
+            length: DEFAULT_SIZE,
+            temp: None,
+        })),
+    },
+    Part {
+        name: "TL081",
+        description: "JFET-input op-amp",
+        contents: Contents::Subcircuit(TL081),
+    },
+];
+
+/// A part by its name (in any case)
+pub fn part(name: &str) -> Option<&'static Part> {
+    PARTS
+        .iter()
+        .find(|part| part.name.eq_ignore_ascii_case(name))
+}
+
+/// The model card of a part by its name (in any case), if it has one
+pub fn model(name: &str) -> Option<Model> {
+    match part(name)?.contents {
+        Contents::Model(model) => Some(model),
+        Contents::Subcircuit(_) => None,
+    }
+}
+
+/// The `.SUBCKT` definitions of the parts that are subcircuits
+pub(crate) fn subcircuits() -> impl Iterator<Item = (&'static str, &'static str)> {
+    PARTS.iter().filter_map(|part| match part.contents {
+        Contents::Subcircuit(text) => Some((part.name, text)),
+        Contents::Model(_) => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dc::operating_point;
+    use crate::netlist::parse_netlist;
+
+    #[test]
+    fn parts_are_found_by_name_in_any_case() {
+        assert!(matches!(model("1n4148"), Some(Model::Diode(_))));
+        assert_eq!(model("TL081"), None);
+        assert_eq!(part("tl081").map(|part| part.name), Some("TL081"));
+        assert!(matches!(model("2n2222"), Some(Model::Bjt(_))));
+        assert!(matches!(model("2N7000"), Some(Model::Mosfet(_))));
+        assert_eq!(part("2N3904"), None);
+    }
+
+    #[test]
+    fn the_op_amp_follows_its_input() {
+        let circuit = parse_netlist(
+            "V1 in 0 1\nVCC vcc 0 15\nVEE vee 0 -15\nX1 in out vcc vee out TL081\nR1 out 0 10k\n",
+        )
+        .unwrap();
+        let output = operating_point(&circuit).node_voltage("out").unwrap();
+        assert!((output - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn the_transistors_switch_on() {
+        let circuit = parse_netlist(
+            "VCC vcc 0 5\nRB vcc b 100k\nRC vcc c 1k\nQ1 c b 0 2N2222\n\
+             RD vcc d 1k\nM1 d vcc 0 0 2N7000\n",
+        )
+        .unwrap();
+        let solution = operating_point(&circuit);
+        // The base current of about 43 uA saturates the transistor
+        assert!(solution.node_voltage("c").unwrap() < 0.2);
+        // As does a gate 3 V above the threshold
+        assert!(solution.node_voltage("d").unwrap() < 0.1);
+    }
+}
@@ -0,0 +1,103 @@
+//! Node loading report
+//!
+//! Assembles the nodal conductance (G) and capacitance (C) matrices
+//! of a circuit and reports, for every node, the total conductance
+//! and capacitance attached to it (the diagonal entry) and the part
+//! of that which goes directly to ground (the row sum). This is
+//! useful for finding unexpectedly heavily loaded nets.
+//!
+//! The circuit is elaborated first, so the report includes the
+//! internal nodes of macromodels. All resistive elements are included in G, whether or not their
+//! currents are kept in the MNA solution. Inductors and sources do
+//! not contribute.
+
+use crate::circuit::Circuit;
+use crate::component::{Component, NOMINAL_TEMPERATURE};
+use crate::sparse::{plus_equals, SparseMat};
+
+/// Loading seen at one node
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeLoading {
+    pub node: usize,
+    /// Sum of all capacitances connected to the node
+    pub total_capacitance: f64,
+    /// Capacitance between the node and ground
+    pub capacitance_to_ground: f64,
+    /// Sum of all conductances connected to the node
+    pub total_conductance: f64,
+    /// Conductance between the node and ground
+    pub conductance_to_ground: f64,
+}
+
+/// Accumulate an admittance between two nodes into a nodal matrix
+/// (the ground node, n = 0, is not stored)
+fn stamp(mat: &mut SparseMat<f64>, n1: usize, n2: usize, y: f64) {
+    if n1 != 0 {
+        plus_equals(mat, n1 - 1, n1 - 1, y);
+    }
+    if n2 != 0 {
+        plus_equals(mat, n2 - 1, n2 - 1, y);
+    }
+    if n1 != 0 && n2 != 0 {
+        plus_equals(mat, n1 - 1, n2 - 1, -y);
+        plus_equals(mat, n2 - 1, n1 - 1, -y);
+    }
+}
+
+/// Diagonal entries and row sums of a nodal matrix
+fn diagonal_and_row_sums(mat: &SparseMat<f64>, num_nodes: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut diagonal = vec![0.0; num_nodes];
+    let mut row_sums = vec![0.0; num_nodes];
+    for ((row, col), value) in mat.non_zero_vals().iter() {
+        if row == col {
+            diagonal[*row] += *value;
+        }
+        row_sums[*row] += *value;
+    }
+    (diagonal, row_sums)
+}
+
+/// Report the loading of every node (excluding ground) in the circuit
+pub fn node_loading(circuit: &Circuit) -> Vec<NodeLoading> {
+    let circuit = circuit.elaborate();
+    let num_nodes = circuit.num_voltage_nodes();
+    let mut g = SparseMat::empty();
+    let mut c = SparseMat::empty();
+    for instance in circuit.instances() {
+        match instance.component {
+            Component::Resistor {
+                term_1,
+                term_2,
+                resistance,
+                ..
+            } => stamp(&mut g, term_1, term_2, 1.0 / resistance),
+            Component::Thermistor {
+                term_1,
+                term_2,
+                model,
+                temperature,
+                ..
+            } => {
+                let resistance = model.resistance(temperature.unwrap_or(NOMINAL_TEMPERATURE));
+                stamp(&mut g, term_1, term_2, 1.0 / resistance)
+            }
+            Component::Capacitor {
+                term_1,
+                term_2,
+                capacitance,
+            } => stamp(&mut c, term_1, term_2, capacitance),
+            _ => {}
+        }
+    }
+    let (total_conductance, conductance_to_ground) = diagonal_and_row_sums(&g, num_nodes);
+    let (total_capacitance, capacitance_to_ground) = diagonal_and_row_sums(&c, num_nodes);
+    (0..num_nodes)
+        .map(|k| NodeLoading {
+            node: k + 1,
+            total_capacitance: total_capacitance[k],
+            capacitance_to_ground: capacitance_to_ground[k],
+            total_conductance: total_conductance[k],
+            conductance_to_ground: conductance_to_ground[k],
+        })
+        .collect()
+}
@@ -0,0 +1,214 @@
+//! Loop gain and stability analysis
+//!
+//! The loop gain of a feedback loop, from which follow the phase
+//! margin and gain margin, found without breaking the loop (which
+//! would upset its operating point and loading) by the method of Tian
+//! et al. A probe (a zero volt source or a current probe) is placed in
+//! the loop, with its positive terminal on the side returning from the
+//! loop and its negative terminal on the side going into it. Two AC
+//! solutions are found, from one factorization: one with a voltage
+//! injected by the probe, giving the voltage loop gain
+//! $T_v = -v_+ / v_-$, and one with a current injected into the
+//! probe's negative terminal, giving the current loop gain
+//! $T_i = -i_+ / i_-$ from the currents arriving from the positive side
+//! and leaving into the negative side. These combine to the loop gain
+//! $T = (T_v T_i - 1) / (T_v + T_i + 2)$, which is exact whichever
+//! direction signals cross the probe, for either sense of the loop.
+//!
+//! The loop gain of a negative feedback loop is positive at low
+//! frequency, as in the closed loop gain $A / (1 + T)$. The phase
+//! margin is 180° more than its phase at the crossover frequency,
+//! where its magnitude falls through 0 dB, and the gain margin is the
+//! amount its magnitude is below 0 dB where its phase falls through
+//! -180°. The margins are interpolated between the frequencies of the
+//! analysis, which should be closely spaced around them.
+
+use std::fmt;
+
+use num::Complex;
+
+use crate::ac::LinearAcAnalysis;
+use crate::circuit::Circuit;
+use crate::component::Component;
+use crate::dc::{explain, DcOptions};
+use crate::error::{invalid, EsimError};
+
+/// Loop gain of a feedback loop at each frequency
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopGainResult {
+    pub frequencies: Vec<f64>,
+    pub loop_gain: Vec<Complex<f64>>,
+}
+
+/// The frequency at which a quantity, which is linear in the logarithm
+/// of the frequency between the frequencies, first falls through a
+/// value, with the index of the frequency after it
+fn falling_crossing(frequencies: &[f64], values: &[f64], value: f64) -> Option<(f64, usize)> {
+    (1..values.len()).find_map(|i| {
+        let (before, after) = (values[i - 1] - value, values[i] - value);
+        (before >= 0.0 && after < 0.0).then(|| {
+            let fraction = before / (before - after);
+            let (f0, f1) = (frequencies[i - 1].ln(), frequencies[i].ln());
+            ((f0 + fraction * (f1 - f0)).exp(), i)
+        })
+    })
+}
+
+impl LoopGainResult {
+    /// Magnitude of the loop gain in decibels at each frequency
+    pub fn magnitude_db(&self) -> Vec<f64> {
+        self.loop_gain
+            .iter()
+            .map(|t| 20.0 * t.norm().log10())
+            .collect()
+    }
+
+    /// Phase of the loop gain in degrees at each frequency, unwrapped
+    /// so that it changes continuously, from within 180° of zero
+    pub fn phase(&self) -> Vec<f64> {
+        let mut phase: Vec<f64> = Vec::with_capacity(self.loop_gain.len());
+        for t in &self.loop_gain {
+            let wrapped = t.arg().to_degrees();
+            let unwrapped = match phase.last() {
+                None => wrapped,
+                Some(previous) => wrapped + 360.0 * ((previous - wrapped) / 360.0).round(),
+            };
+            phase.push(unwrapped);
+        }
+        phase
+    }
+
+    /// The frequency at which the magnitude of the loop gain first
+    /// falls through 0 dB, if it does
+    pub fn crossover_frequency(&self) -> Option<f64> {
+        falling_crossing(&self.frequencies, &self.magnitude_db(), 0.0).map(|(f, _)| f)
+    }
+
+    /// Phase margin in degrees, if the magnitude of the loop gain falls
+    /// through 0 dB
+    pub fn phase_margin(&self) -> Option<f64> {
+        let (frequency, index) = falling_crossing(&self.frequencies, &self.magnitude_db(), 0.0)?;
+        Some(180.0 + self.interpolate(&self.phase(), frequency, index))
+    }
+
+    /// The frequency at which the phase of the loop gain first falls
+    /// through -180°, if it does
+    pub fn phase_crossover_frequency(&self) -> Option<f64> {
+        falling_crossing(&self.frequencies, &self.phase(), -180.0).map(|(f, _)| f)
+    }
+
+    /// Gain margin in decibels, if the phase of the loop gain falls
+    /// through -180°
+    pub fn gain_margin(&self) -> Option<f64> {
+        let (frequency, index) = falling_crossing(&self.frequencies, &self.phase(), -180.0)?;
+        Some(-self.interpolate(&self.magnitude_db(), frequency, index))
+    }
+
+    /// Value of a quantity at a frequency between the frequency at an
+    /// index and the one before it, interpolated in the logarithm of
+    /// the frequency
+    fn interpolate(&self, values: &[f64], frequency: f64, index: usize) -> f64 {
+        let fraction = (frequency.ln() - self.frequencies[index - 1].ln())
+            / (self.frequencies[index].ln() - self.frequencies[index - 1].ln());
+        values[index - 1] + fraction * (values[index] - values[index - 1])
+    }
+
+    /// Whether the loop is stable by its margins: the phase margin and
+    /// gain margin are positive where they exist
+    pub fn is_stable(&self) -> bool {
+        self.phase_margin().is_none_or(|m| m > 0.0) && self.gain_margin().is_none_or(|m| m > 0.0)
+    }
+}
+
+impl fmt::Display for LoopGainResult {
+    /// The margins, and the frequencies at which they are found
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.crossover_frequency(), self.phase_margin()) {
+            (Some(frequency), Some(margin)) => {
+                writeln!(f, "Phase margin: {margin:.2} deg at {frequency:.6e} Hz")?
+            }
+            _ => writeln!(f, "Phase margin: none (no 0 dB crossover)")?,
+        }
+        match (self.phase_crossover_frequency(), self.gain_margin()) {
+            (Some(frequency), Some(margin)) => {
+                writeln!(f, "Gain margin: {margin:.2} dB at {frequency:.6e} Hz")
+            }
+            _ => writeln!(f, "Gain margin: none (no -180 deg crossover)"),
+        }
+    }
+}
+
+/// Loop gain of the feedback loop through a probe (a zero volt
+/// independent voltage source or a current probe, by instance name),
+/// at each frequency (in Hz). Panics if there is no such instance, or
+/// it is not a probe (see [try_loop_gain]).
+pub fn loop_gain(circuit: &Circuit, probe: &str, frequencies: &[f64]) -> LoopGainResult {
+    loop_gain_with(&LinearAcAnalysis::new(circuit), probe, frequencies)
+        .unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Loop gain as for [loop_gain], returning an error if the circuit
+/// fails its check (see [Circuit::validate]), its operating point
+/// cannot be solved, or there is no such probe
+pub fn try_loop_gain(
+    circuit: &Circuit,
+    probe: &str,
+    frequencies: &[f64],
+) -> Result<LoopGainResult, EsimError> {
+    let analysis = LinearAcAnalysis::try_new(circuit, &DcOptions::default())?;
+    loop_gain_with(&analysis, probe, frequencies).map_err(|error| explain(circuit, error))
+}
+
+fn loop_gain_with(
+    analysis: &LinearAcAnalysis,
+    probe: &str,
+    frequencies: &[f64],
+) -> Result<LoopGainResult, EsimError> {
+    let (term_pos, term_neg, current_edge) = match *analysis.component(probe)? {
+        Component::IndependentVoltageSource {
+            term_pos,
+            term_neg,
+            current_edge,
+            ..
+        }
+        | Component::CurrentProbe {
+            term_pos,
+            term_neg,
+            current_edge,
+        } => (term_pos, term_neg, current_edge),
+        _ => {
+            return Err(invalid(format!(
+                "Instance {probe} is not a voltage source or current probe"
+            )))
+        }
+    };
+    let node = |voltages: &[Complex<f64>], node: usize| match node {
+        0 => Complex::new(0.0, 0.0),
+        n => voltages[n - 1],
+    };
+    let loop_gain = frequencies
+        .iter()
+        .map(|frequency| {
+            let mut mna = analysis.assemble(*frequency, false);
+            mna.next_rhs();
+            mna.add_series_voltage(current_edge, Complex::new(1.0, 0.0));
+            mna.next_rhs();
+            mna.add_independent_current_source(0, term_neg, Complex::new(1.0, 0.0));
+            // The first right-hand side is from the assembly, and is zero
+            let solutions = mna.try_solve_columns()?;
+            let (voltages, _) = &solutions[1];
+            let voltage_gain = -node(voltages, term_pos) / node(voltages, term_neg);
+            // The edge current flows from the positive side, through the
+            // probe, to the negative side, where the injected current
+            // joins it
+            let (_, currents) = &solutions[2];
+            let arriving = currents[current_edge];
+            let current_gain = -arriving / (arriving + 1.0);
+            Ok((voltage_gain * current_gain - 1.0) / (voltage_gain + current_gain + 2.0))
+        })
+        .collect::<Result<_, EsimError>>()?;
+    Ok(LoopGainResult {
+        frequencies: frequencies.to_vec(),
+        loop_gain,
+    })
+}
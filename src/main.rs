@@ -1,15 +1,300 @@
-use crate::dc::LinearDcAnalysis;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::exit;
 
-mod mna;
-mod dc;
-mod sparse;
+use libesim::anonymize::{anonymize, AnonymizeOptions, ValueTreatment};
+use libesim::measure::parse_measurements;
+use libesim::netlist::{
+    check_netlist_file, parse_analyses_file, parse_netlist_file_dialect, parse_options_file,
+    Dialect,
+};
+use libesim::rawfile::{write_raw, RawPlot};
+use libesim::shell::Shell;
+use libesim::transient::TransientOptions;
+use libesim::value::{normalize, parse_value};
+use libesim::watch::{watch, Analysis, WatchOptions};
+
+const USAGE: &str =
+    "Usage: esim run <netlist> [--ngspice | --ltspice] [--output <file.raw | file.csv>]
+       esim check <netlist> [--ngspice | --ltspice]
+       esim shell [netlist] [--ngspice | --ltspice]
+       esim watch <netlist> [--tran <time step> <stop time>] [--profile]
+       esim anonymize <netlist> [--perturb <relative> | --bucket <per decade>] [--seed <seed>]";
+
+fn usage() -> ! {
+    eprintln!("{USAGE}");
+    exit(1);
+}
+
+fn value(text: Option<&String>) -> f64 {
+    let text = text.unwrap_or_else(|| usage());
+    for change in normalize(text).changes {
+        eprintln!("Warning: value '{text}': {change}");
+    }
+    parse_value(text).unwrap_or_else(|error| {
+        eprintln!("{error}");
+        exit(1);
+    })
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{message}");
+    exit(1);
+}
+
+fn warn(warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("Warning: {warning}");
+    }
+}
+
+/// Name of the plot of an analysis in a raw file, as in ngspice
+fn plot_name(analysis: &Analysis) -> &'static str {
+    match analysis {
+        Analysis::OperatingPoint => "Operating Point",
+        Analysis::DcSweep { .. } => "DC transfer characteristic",
+        Analysis::Ac(_) => "AC Analysis",
+        Analysis::Transient(_) => "Transient Analysis",
+    }
+}
+
+/// Run the analyses of a netlist (an operating point if it has none)
+/// with its options, writing the results to a raw or CSV file, or to
+/// stdout, and the warnings of the netlist and the analyses to stderr. A SPICE deck can be run without a dialect, since its title
+/// line is found by the parser (see [libesim::netlist]). The
+/// `.MEASURE` lines are measured on the transient analyses, and
+/// printed to stdout after the results, failing if any cannot be made.
+fn run(netlist: &str, dialect: Dialect, output: Option<&String>) {
+    let path = Path::new(netlist);
+    let circuit = parse_netlist_file_dialect(path, dialect).unwrap_or_else(|error| fail(error));
+    warn(circuit.warnings());
+    let mut analyses = parse_analyses_file(path, dialect).unwrap_or_else(|error| fail(error));
+    let options = parse_options_file(path, dialect).unwrap_or_else(|error| fail(error));
+    if analyses.is_empty() {
+        analyses.push(Analysis::OperatingPoint);
+    }
+    let text = std::fs::read_to_string(path).unwrap_or_else(|error| fail(error));
+    let measurements = parse_measurements(&text, &circuit)
+        .unwrap_or_else(|error| fail(format!("{netlist}: {error}")));
+    if !measurements.is_empty()
+        && !analyses
+            .iter()
+            .any(|analysis| matches!(analysis, Analysis::Transient(_)))
+    {
+        fail(format!(
+            "{netlist}: the .MEASURE lines need a transient analysis (.TRAN)"
+        ));
+    }
+    let mut plots = Vec::new();
+    let mut measured = Vec::new();
+    for analysis in &mut analyses {
+        *analysis = analysis
+            .for_circuit(&circuit)
+            .unwrap_or_else(|error| fail(format!("{netlist}: {error}")));
+        let (mut dataset, results) = analysis.run_measured(&circuit, &options, &measurements);
+        measured.extend(measurements.iter().zip(results));
+        warn(&dataset.warnings);
+        dataset.name_signals(&circuit);
+        plots.push(RawPlot {
+            title: netlist.to_string(),
+            plot_name: plot_name(analysis).to_string(),
+            dataset,
+        });
+    }
+    let write = |path: &Path, text: String| {
+        std::fs::write(path, text)
+            .unwrap_or_else(|error| fail(format!("{}: {error}", path.display())));
+    };
+    match output {
+        Some(output) if output.to_ascii_lowercase().ends_with(".raw") => {
+            write(Path::new(output), write_raw(&plots))
+        }
+        // A CSV file holds one analysis, so each has its own file if
+        // there are several, as in out.tran.csv
+        Some(output) if output.to_ascii_lowercase().ends_with(".csv") => {
+            for (analysis, plot) in analyses.iter().zip(&plots) {
+                let path = Path::new(output);
+                let path = if plots.len() > 1 {
+                    path.with_extension(format!("{}.csv", analysis.name()))
+                } else {
+                    path.to_path_buf()
+                };
+                write(&path, plot.dataset.to_csv());
+            }
+        }
+        Some(_) => usage(),
+        None => {
+            for plot in &plots {
+                println!("* {}", plot.plot_name);
+                match plot.dataset.axis {
+                    Some(_) => print!("{}", plot.dataset.to_csv()),
+                    None => {
+                        for signal in &plot.dataset.signals {
+                            println!("{} = {:e}", signal.label(), signal.real[0]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let mut failed = false;
+    for (measurement, result) in measured {
+        match result {
+            Ok(value) => println!("{} = {value:e}", measurement.name),
+            Err(error) => {
+                eprintln!("{netlist}: {error}");
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
+
+/// Run shell commands from stdin until `quit` or the end of the input
+fn shell(netlist: Option<&String>, dialect: Dialect) {
+    let mut shell = Shell::new(dialect);
+    let mut execute = |line: &str| match shell.execute(line) {
+        Ok(output) if output.is_empty() => {}
+        Ok(output) => println!("{output}"),
+        Err(error) => eprintln!("{error}"),
+    };
+    if let Some(netlist) = netlist {
+        execute(&format!("source {netlist}"));
+    }
+    let stdin = io::stdin();
+    loop {
+        print!("esim> ");
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        if matches!(line.trim(), "quit" | "exit") {
+            break;
+        }
+        execute(&line);
+    }
+}
 
 fn main() {
-    let mut dc = LinearDcAnalysis::new();
-    dc.add_resistor(1, 0, None, 50.0);
-    dc.add_resistor(2, 1, None, 50.0);
-    dc.add_independent_voltage_source(2, 0, 0, 5.0);
-    let (voltages, currents) = dc.solve();
-    println!("{:?}", voltages);
-    println!("{:?}", currents);   
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("run") => {
+            let netlist = args.get(1).unwrap_or_else(|| usage());
+            let mut dialect = Dialect::Esim;
+            let mut output = None;
+            let mut rest = args[2..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--ngspice" => dialect = Dialect::Ngspice,
+                    "--ltspice" => dialect = Dialect::Ltspice,
+                    "--output" => output = Some(rest.next().unwrap_or_else(|| usage())),
+                    _ => usage(),
+                }
+            }
+            run(netlist, dialect, output);
+        }
+        Some("shell") => {
+            let mut netlist = None;
+            let mut dialect = Dialect::Esim;
+            for arg in &args[1..] {
+                match arg.as_str() {
+                    "--ngspice" => dialect = Dialect::Ngspice,
+                    "--ltspice" => dialect = Dialect::Ltspice,
+                    _ if netlist.is_none() && !arg.starts_with("--") => netlist = Some(arg),
+                    _ => usage(),
+                }
+            }
+            shell(netlist, dialect);
+        }
+        Some("check") => {
+            let netlist = args.get(1).unwrap_or_else(|| usage());
+            let mut dialect = Dialect::Esim;
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "--ngspice" => dialect = Dialect::Ngspice,
+                    "--ltspice" => dialect = Dialect::Ltspice,
+                    _ => usage(),
+                }
+            }
+            let path = Path::new(netlist);
+            let diagnostics = check_netlist_file(path, dialect);
+            if !diagnostics.is_empty() {
+                print!("{diagnostics}");
+                exit(1);
+            }
+            // The electrical rules, on a netlist that parses
+            let circuit =
+                parse_netlist_file_dialect(path, dialect).unwrap_or_else(|error| fail(error));
+            let violations = circuit.check();
+            if violations.is_empty() {
+                println!("{netlist}: no errors");
+            } else {
+                for violation in violations {
+                    println!("{netlist}: {violation}");
+                }
+                exit(1);
+            }
+        }
+        Some("watch") => {
+            let netlist = args.get(1).unwrap_or_else(|| usage());
+            let mut options = WatchOptions::default();
+            let mut rest = args[2..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--tran" => {
+                        let time_step = value(rest.next());
+                        let stop_time = value(rest.next());
+                        options
+                            .analyses
+                            .push(Analysis::Transient(TransientOptions::new(
+                                time_step, stop_time,
+                            )));
+                    }
+                    "--profile" => options.profile = true,
+                    _ => usage(),
+                }
+            }
+            if let Err(error) = watch(Path::new(netlist), &options) {
+                eprintln!("{netlist}: {error}");
+                exit(1);
+            }
+        }
+        Some("anonymize") => {
+            let netlist = args.get(1).unwrap_or_else(|| usage());
+            let mut options = AnonymizeOptions::default();
+            let mut rest = args[2..].iter();
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--perturb" => {
+                        options.values = ValueTreatment::Perturb {
+                            relative: value(rest.next()),
+                        }
+                    }
+                    "--bucket" => {
+                        options.values = ValueTreatment::Bucket {
+                            per_decade: value(rest.next()) as usize,
+                        }
+                    }
+                    "--seed" => options.seed = value(rest.next()) as u64,
+                    _ => usage(),
+                }
+            }
+            let text = std::fs::read_to_string(netlist).unwrap_or_else(|error| {
+                eprintln!("{netlist}: {error}");
+                exit(1);
+            });
+            match anonymize(&text, &options) {
+                Ok(anonymized) => print!("{}", anonymized.text),
+                Err(error) => {
+                    eprintln!("{netlist}: {error}");
+                    exit(1);
+                }
+            }
+        }
+        _ => usage(),
+    }
 }
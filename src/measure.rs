@@ -0,0 +1,864 @@
+//! Measurements of transient waveforms
+//!
+//! A measurement reduces the waveforms of a transient analysis to one
+//! number, as a SPICE `.MEASURE` line does: the delay from a trigger
+//! to a target, the value of a signal when another crosses a level,
+//! the time of a crossing, or the average, RMS, minimum, maximum,
+//! peak-to-peak value or integral of a signal over an interval.
+//! Measurements are built directly, or parsed from the `.MEASURE` (or
+//! `.MEAS`) lines of a netlist by [parse_measurements]:
+//!
+//! ```text
+//! .MEASURE TRAN delay TRIG v(1) VAL=0.5 RISE=1 TARG v(2) VAL=0.5 RISE=1
+//! .MEASURE TRAN vout FIND v(2) WHEN v(1)=0.5 CROSS=2
+//! .MEASURE TRAN vend FIND v(2) AT=1m
+//! .MEASURE TRAN tcross WHEN v(2)=2.5 FALL=LAST
+//! .MEASURE TRAN ripple PP v(out) FROM=1m TO=2m
+//! .MEASURE TRAN power AVG v(out)*i(R1)
+//! .MEASURE TRAN gain PARAM='vend/vin'
+//! ```
+//!
+//! The signals are node voltages `v(n)` (by number or name),
+//! differences `v(n,m)` and currents `i(e)` (by edge number, or by the
+//! name of a component with a current), or expressions of them (see
+//! [crate::expression]), such as `v(out)-v(in)` or `'v(out) * 2'`,
+//! which can use the parameters of the circuit. A `PARAM` measurement
+//! is an expression of the measurements before it and the parameters.
+//! A crossing is the first
+//! (or nth, or last) time after the delay `TD` that the signal rises
+//! through, falls through or crosses the value, interpolated linearly
+//! between time points; with no `RISE`, `FALL` or `CROSS` it is the
+//! first crossing. The statistics are over the whole analysis unless
+//! `FROM` or `TO` is given, with the signal interpolated linearly, so
+//! the average and RMS are of the interpolated waveform rather than of
+//! the time points.
+
+use std::fmt;
+
+use crate::circuit::Circuit;
+use crate::expression::{parse_expression, Expression, Parameters};
+use crate::netlist::NetlistError;
+use crate::transient::TransientResult;
+use crate::value::parse_value;
+
+/// A measured signal
+#[derive(Debug, Clone, PartialEq)]
+pub enum Signal {
+    /// Voltage of a node
+    Voltage(usize),
+    /// Voltage of the first node relative to the second
+    Difference(usize, usize),
+    /// Current of an edge
+    Current(usize),
+    /// An expression of the signals, in which each signal is the
+    /// parameter named by its text (as `v(3)`)
+    Expression(Expression, Vec<Signal>),
+}
+
+impl Signal {
+    /// The signal at every time point of a result, or an error message
+    /// if it has no such edge or the expression cannot be evaluated
+    fn values(&self, result: &TransientResult) -> Result<Vec<f64>, String> {
+        let missing = || format!("no signal {self}");
+        match self {
+            Self::Voltage(node) => Ok(result.voltage(*node)),
+            Self::Difference(pos, neg) => Ok(result
+                .voltage(*pos)
+                .iter()
+                .zip(result.voltage(*neg))
+                .map(|(pos, neg)| pos - neg)
+                .collect()),
+            Self::Current(edge) => match result.currents.first() {
+                Some(currents) if currents.len() > *edge => Ok(result.current(*edge)),
+                _ => Err(missing()),
+            },
+            Self::Expression(expression, signals) => {
+                let names: Vec<String> = signals.iter().map(Signal::to_string).collect();
+                let values = signals
+                    .iter()
+                    .map(|signal| signal.values(result))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let parameters = Parameters::new();
+                (0..result.time.len())
+                    .map(|k| {
+                        let point: Vec<f64> = values.iter().map(|v| v[k]).collect();
+                        parameters
+                            .evaluate(&expression.bind(&names, &point))
+                            .map_err(|error| format!("{self}: {error}"))
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Voltage(node) => write!(f, "v({node})"),
+            Self::Difference(pos, neg) => write!(f, "v({pos},{neg})"),
+            Self::Current(edge) => write!(f, "i({edge})"),
+            Self::Expression(expression, _) => write!(f, "{expression}"),
+        }
+    }
+}
+
+/// Direction of a crossing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Rise,
+    Fall,
+    /// Either direction
+    Cross,
+}
+
+/// Which crossing of a value counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Occurrence {
+    /// The nth crossing, from one
+    Nth(usize),
+    Last,
+}
+
+/// A time in a measurement
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// When a signal crosses a value after a delay
+    When {
+        signal: Signal,
+        value: f64,
+        edge: Edge,
+        occurrence: Occurrence,
+        delay: f64,
+    },
+    /// At a fixed time
+    At(f64),
+}
+
+impl Condition {
+    /// A condition met at the first crossing of a value in either
+    /// direction
+    pub fn crossing(signal: Signal, value: f64) -> Self {
+        Self::When {
+            signal,
+            value,
+            edge: Edge::Cross,
+            occurrence: Occurrence::Nth(1),
+            delay: 0.0,
+        }
+    }
+
+    /// The time the condition is met, or an error message if it is not
+    fn time(&self, result: &TransientResult) -> Result<f64, String> {
+        let (signal, value, edge, occurrence, delay) = match *self {
+            Self::When {
+                ref signal,
+                value,
+                edge,
+                occurrence,
+                delay,
+            } => (signal, value, edge, occurrence, delay),
+            Self::At(time) => return Ok(time),
+        };
+        let values = signal.values(result)?;
+        let mut crossings = result
+            .time
+            .windows(2)
+            .zip(values.windows(2))
+            .filter_map(|(t, v)| {
+                let (before, after) = (v[0] - value, v[1] - value);
+                let rising = before < 0.0 && after >= 0.0;
+                let falling = before > 0.0 && after <= 0.0;
+                let counts = match edge {
+                    Edge::Rise => rising,
+                    Edge::Fall => falling,
+                    Edge::Cross => rising || falling,
+                };
+                counts.then(|| t[0] + (t[1] - t[0]) * before / (before - after))
+            })
+            .filter(|time| *time >= delay);
+        match occurrence {
+            Occurrence::Nth(n) => crossings.nth(n.saturating_sub(1)),
+            Occurrence::Last => crossings.next_back(),
+        }
+        .ok_or_else(|| format!("{signal} does not reach {value}"))
+    }
+}
+
+/// A statistic of a signal over an interval
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Statistic {
+    Average,
+    Rms,
+    Min,
+    Max,
+    PeakToPeak,
+    Integral,
+}
+
+/// What a measurement measures
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeasureKind {
+    /// Time from the trigger to the target
+    Delay {
+        trigger: Condition,
+        target: Condition,
+    },
+    /// Value of a signal at a time
+    Find { signal: Signal, at: Condition },
+    /// Time a condition is met
+    When(Condition),
+    /// Statistic of a signal from a time to a time (the start and end
+    /// of the analysis if None)
+    Statistic {
+        statistic: Statistic,
+        signal: Signal,
+        from: Option<f64>,
+        to: Option<f64>,
+    },
+    /// An expression of the measurements before it, each the parameter
+    /// named by the name of the measurement (in lower case)
+    Param(Expression),
+}
+
+/// A named measurement
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub name: String,
+    pub kind: MeasureKind,
+}
+
+/// A measurement that could not be made, such as one whose trigger
+/// never happens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeasureError {
+    /// Name of the measurement
+    pub name: String,
+    pub message: String,
+}
+
+impl fmt::Display for MeasureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Measurement {}: {}", self.name, self.message)
+    }
+}
+
+impl std::error::Error for MeasureError {}
+
+/// Value of a waveform at a time, by linear interpolation (and the end
+/// values outside it)
+fn interpolate(time: &[f64], values: &[f64], t: f64) -> f64 {
+    let index = time.partition_point(|x| *x < t).clamp(1, time.len() - 1);
+    let (t0, t1) = (time[index - 1], time[index]);
+    let (v0, v1) = (values[index - 1], values[index]);
+    if t1 == t0 || t <= t0 {
+        if t <= t0 {
+            v0
+        } else {
+            v1
+        }
+    } else {
+        v0 + (v1 - v0) * ((t - t0) / (t1 - t0)).min(1.0)
+    }
+}
+
+/// Statistic of a waveform from a time to a time
+fn statistic(
+    statistic: Statistic,
+    time: &[f64],
+    values: &[f64],
+    from: f64,
+    to: f64,
+) -> Result<f64, String> {
+    if to <= from {
+        return Err(format!("empty interval from {from} to {to}"));
+    }
+    // The points in the interval, with its ends interpolated
+    let points: Vec<(f64, f64)> = [(from, interpolate(time, values, from))]
+        .into_iter()
+        .chain(
+            time.iter()
+                .copied()
+                .zip(values.iter().copied())
+                .filter(|(t, _)| *t > from && *t < to),
+        )
+        .chain([(to, interpolate(time, values, to))])
+        .collect();
+    let integral = |f: fn(f64) -> f64| -> f64 {
+        points
+            .windows(2)
+            .map(|p| 0.5 * (f(p[0].1) + f(p[1].1)) * (p[1].0 - p[0].0))
+            .sum()
+    };
+    let min = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let max = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    Ok(match statistic {
+        Statistic::Average => integral(|v| v) / (to - from),
+        // The square is integrated exactly between the points, as the
+        // waveform is linear between them
+        Statistic::Rms => {
+            let squares: f64 = points
+                .windows(2)
+                .map(|p| {
+                    let (a, b) = (p[0].1, p[1].1);
+                    (a * a + a * b + b * b) / 3.0 * (p[1].0 - p[0].0)
+                })
+                .sum();
+            (squares / (to - from)).sqrt()
+        }
+        Statistic::Min => min,
+        Statistic::Max => max,
+        Statistic::PeakToPeak => max - min,
+        Statistic::Integral => integral(|v| v),
+    })
+}
+
+impl Measurement {
+    pub fn new(name: &str, kind: MeasureKind) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+        }
+    }
+
+    /// Make the measurement on the result of a transient analysis. A
+    /// `PARAM` measurement of other measurements is made by
+    /// [measure_all].
+    pub fn evaluate(&self, result: &TransientResult) -> Result<f64, MeasureError> {
+        self.evaluate_after(result, &[], &[])
+    }
+
+    /// Make the measurement, with the values of the measurements before
+    /// it by name
+    fn evaluate_after(
+        &self,
+        result: &TransientResult,
+        names: &[String],
+        values: &[f64],
+    ) -> Result<f64, MeasureError> {
+        self.measure(result, names, values)
+            .map_err(|message| MeasureError {
+                name: self.name.clone(),
+                message,
+            })
+    }
+
+    fn measure(
+        &self,
+        result: &TransientResult,
+        names: &[String],
+        values: &[f64],
+    ) -> Result<f64, String> {
+        let time = &result.time;
+        if let MeasureKind::Param(expression) = &self.kind {
+            return Parameters::new()
+                .evaluate(&expression.bind(names, values))
+                .map_err(|error| error.to_string());
+        }
+        if time.is_empty() {
+            return Err("no time points".to_string());
+        }
+        match &self.kind {
+            MeasureKind::Delay { trigger, target } => {
+                Ok(target.time(result)? - trigger.time(result)?)
+            }
+            MeasureKind::Find { signal, at } => {
+                let at = at.time(result)?;
+                Ok(interpolate(time, &signal.values(result)?, at))
+            }
+            MeasureKind::When(condition) => condition.time(result),
+            MeasureKind::Statistic {
+                statistic: kind,
+                signal,
+                from,
+                to,
+            } => {
+                let from = from.unwrap_or(time[0]);
+                let to = to.unwrap_or(time[time.len() - 1]);
+                statistic(*kind, time, &signal.values(result)?, from, to)
+            }
+            MeasureKind::Param(_) => unreachable!("PARAM measurements are made above"),
+        }
+    }
+}
+
+/// Make each measurement on the result of a transient analysis, with
+/// the `PARAM` measurements of the ones before them
+pub fn measure_all(
+    measurements: &[Measurement],
+    result: &TransientResult,
+) -> Vec<Result<f64, MeasureError>> {
+    let (mut names, mut values) = (Vec::new(), Vec::new());
+    measurements
+        .iter()
+        .map(|measurement| {
+            let value = measurement.evaluate_after(result, &names, &values);
+            if let Ok(value) = value {
+                names.push(measurement.name.to_lowercase());
+                values.push(value);
+            }
+            value
+        })
+        .collect()
+}
+
+/// Tokens of a measurement line, with each `=` a token of its own and
+/// no spaces inside the parentheses of a signal, or inside an
+/// expression in quotes or braces
+fn tokenize(text: &str) -> Vec<String> {
+    let mut normalized = String::new();
+    let mut depth = 0;
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth -= 1,
+            '\'' => quoted = !quoted,
+            _ => {}
+        }
+        match c {
+            '=' if depth == 0 && !quoted => normalized.push_str(" = "),
+            c if c.is_whitespace() && (depth > 0 || quoted) => {}
+            c => normalized.push(c),
+        }
+    }
+    normalized.split_whitespace().map(str::to_string).collect()
+}
+
+/// Parser state for a measurement line
+struct Parser<'a> {
+    line: usize,
+    tokens: &'a [String],
+    index: usize,
+    /// The circuit whose node names, currents and parameters are used
+    circuit: &'a Circuit,
+    /// The names of the measurements before the line (in lower case)
+    measurements: &'a [String],
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> NetlistError {
+        NetlistError::new(None, self.line, message.to_string())
+    }
+
+    fn peek(&self) -> Option<String> {
+        self.tokens.get(self.index).map(|t| t.to_ascii_uppercase())
+    }
+
+    fn next(&mut self, what: &str) -> Result<&str, NetlistError> {
+        let token = self
+            .tokens
+            .get(self.index)
+            .ok_or_else(|| self.error(&format!("missing {what}")))?;
+        self.index += 1;
+        Ok(token)
+    }
+
+    fn value(&mut self) -> Result<f64, NetlistError> {
+        let token = self.next("value")?.to_string();
+        parse_value(&token).map_err(|error| self.error(&error.to_string()))
+    }
+
+    /// A keyword followed by `=` and a value, if the next token is one
+    /// of the keywords
+    fn assignment(&mut self, keywords: &[&str]) -> Option<String> {
+        let keyword = self.peek()?;
+        (keywords.contains(&keyword.as_str())
+            && self.tokens.get(self.index + 1).map(String::as_str) == Some("="))
+        .then(|| {
+            self.index += 2;
+            keyword
+        })
+    }
+
+    /// An expression, without the quotes or braces around it
+    fn expression(&mut self, what: &str) -> Result<Expression, NetlistError> {
+        let token = self.next(what)?.to_string();
+        let text = token
+            .strip_prefix('\'')
+            .and_then(|t| t.strip_suffix('\''))
+            .or_else(|| token.strip_prefix('{').and_then(|t| t.strip_suffix('}')))
+            .unwrap_or(&token);
+        parse_expression(text)
+            .map_err(|error| self.error(&format!("invalid {what} '{token}': {error}")))
+    }
+
+    /// A signal, or an expression of signals
+    fn signal(&mut self) -> Result<Signal, NetlistError> {
+        let expression = self.expression("signal")?;
+        let mut signals = Vec::new();
+        let expression = self.resolve(&expression, &mut signals)?;
+        match (expression, signals.as_slice()) {
+            (Expression::Parameter(_), [signal]) => Ok(signal.clone()),
+            (expression, []) => Err(self.error(&format!("no signal in '{expression}'"))),
+            (expression, _) => Ok(Signal::Expression(expression, signals)),
+        }
+    }
+
+    /// The expression with each signal in it the parameter named by the
+    /// signal (which is added to the signals), and each parameter of
+    /// the circuit its value
+    fn resolve(
+        &self,
+        expression: &Expression,
+        signals: &mut Vec<Signal>,
+    ) -> Result<Expression, NetlistError> {
+        Ok(match expression {
+            Expression::Number(_) => expression.clone(),
+            Expression::Parameter(name) => Expression::Number(
+                self.circuit
+                    .parameters()
+                    .value(name)
+                    .map_err(|error| self.error(&error.to_string()))?,
+            ),
+            Expression::Negate(inner) => {
+                Expression::Negate(Box::new(self.resolve(inner, signals)?))
+            }
+            Expression::Binary(operator, lhs, rhs) => Expression::Binary(
+                *operator,
+                Box::new(self.resolve(lhs, signals)?),
+                Box::new(self.resolve(rhs, signals)?),
+            ),
+            Expression::Call(function, args) if function == "v" || function == "i" => {
+                let signal = self.probe(function, args)?;
+                let name = signal.to_string();
+                if !signals.contains(&signal) {
+                    signals.push(signal);
+                }
+                Expression::Parameter(name)
+            }
+            Expression::Call(function, args) => Expression::Call(
+                function.clone(),
+                args.iter()
+                    .map(|arg| self.resolve(arg, signals))
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+
+    /// The signal of a `v(...)` or `i(...)`, from its nodes (by number
+    /// or name) or its edge (by number, or the name of a component)
+    fn probe(&self, function: &str, args: &[Expression]) -> Result<Signal, NetlistError> {
+        let error = || {
+            let args: Vec<String> = args.iter().map(Expression::to_string).collect();
+            self.error(&format!("invalid signal '{function}({})'", args.join(",")))
+        };
+        let number = |arg: &Expression| match arg {
+            Expression::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None,
+        };
+        let node = |arg: &Expression| match arg {
+            Expression::Parameter(name) => self.circuit.node_names().get(name),
+            arg => number(arg),
+        };
+        let edge = |arg: &Expression| match arg {
+            Expression::Parameter(name) => self
+                .circuit
+                .instances()
+                .iter()
+                .find(|instance| instance.name.eq_ignore_ascii_case(name))
+                .and_then(|instance| instance.component.current_edge()),
+            arg => number(arg),
+        };
+        match (function, args) {
+            ("v", [n]) => Ok(Signal::Voltage(node(n).ok_or_else(error)?)),
+            ("v", [pos, neg]) => Ok(Signal::Difference(
+                node(pos).ok_or_else(error)?,
+                node(neg).ok_or_else(error)?,
+            )),
+            ("i", [e]) => Ok(Signal::Current(edge(e).ok_or_else(error)?)),
+            _ => Err(error()),
+        }
+    }
+
+    /// A `PARAM=` expression of the measurements before it and the
+    /// parameters of the circuit
+    fn param(&mut self) -> Result<Expression, NetlistError> {
+        let expression = self.expression("expression")?;
+        let bound: Vec<String> = self
+            .circuit
+            .parameters()
+            .names()
+            .filter(|name| !self.measurements.iter().any(|m| m == name))
+            .map(str::to_string)
+            .collect();
+        let values = bound
+            .iter()
+            .map(|name| self.circuit.parameters().value(name))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| self.error(&error.to_string()))?;
+        let expression = expression.bind(&bound, &values);
+        match expression
+            .parameters()
+            .into_iter()
+            .find(|name| !self.measurements.contains(name))
+        {
+            Some(name) => Err(self.error(&format!("unknown measurement '{name}'"))),
+            None => Ok(expression),
+        }
+    }
+
+    /// The delay and crossing options of a condition
+    fn crossing(&mut self, signal: Signal, value: f64) -> Result<Condition, NetlistError> {
+        let mut edge = Edge::Cross;
+        let mut occurrence = Occurrence::Nth(1);
+        let mut delay = 0.0;
+        while let Some(keyword) = self.assignment(&["TD", "RISE", "FALL", "CROSS"]) {
+            if keyword == "TD" {
+                delay = self.value()?;
+                continue;
+            }
+            edge = match keyword.as_str() {
+                "RISE" => Edge::Rise,
+                "FALL" => Edge::Fall,
+                _ => Edge::Cross,
+            };
+            let token = self.next("count")?.to_string();
+            occurrence = if token.eq_ignore_ascii_case("LAST") {
+                Occurrence::Last
+            } else {
+                match token.parse() {
+                    Ok(n) if n > 0 => Occurrence::Nth(n),
+                    _ => return Err(self.error(&format!("invalid count '{token}'"))),
+                }
+            };
+        }
+        Ok(Condition::When {
+            signal,
+            value,
+            edge,
+            occurrence,
+            delay,
+        })
+    }
+
+    /// A trigger or target: `signal VAL=value ...` or `AT=time`
+    fn trigger(&mut self) -> Result<Condition, NetlistError> {
+        if self.assignment(&["AT"]).is_some() {
+            return Ok(Condition::At(self.value()?));
+        }
+        let signal = self.signal()?;
+        if self.assignment(&["VAL"]).is_none() {
+            return Err(self.error("expected VAL="));
+        }
+        let value = self.value()?;
+        self.crossing(signal, value)
+    }
+
+    /// A `WHEN signal=value ...` condition (after the keyword)
+    fn when(&mut self) -> Result<Condition, NetlistError> {
+        let signal = self.signal()?;
+        if self.next("'='")? != "=" {
+            return Err(self.error("expected '=' after the signal"));
+        }
+        let value = self.value()?;
+        self.crossing(signal, value)
+    }
+
+    fn kind(&mut self) -> Result<MeasureKind, NetlistError> {
+        let keyword = self
+            .peek()
+            .ok_or_else(|| self.error("missing measurement"))?;
+        self.index += 1;
+        let statistic = match keyword.as_str() {
+            "TRIG" => {
+                let trigger = self.trigger()?;
+                if self.peek().as_deref() != Some("TARG") {
+                    return Err(self.error("expected TARG"));
+                }
+                self.index += 1;
+                let target = self.trigger()?;
+                return Ok(MeasureKind::Delay { trigger, target });
+            }
+            "FIND" => {
+                let signal = self.signal()?;
+                let at = if self.assignment(&["AT"]).is_some() {
+                    Condition::At(self.value()?)
+                } else if self.peek().as_deref() == Some("WHEN") {
+                    self.index += 1;
+                    self.when()?
+                } else {
+                    return Err(self.error("expected WHEN or AT="));
+                };
+                return Ok(MeasureKind::Find { signal, at });
+            }
+            "WHEN" => return Ok(MeasureKind::When(self.when()?)),
+            "PARAM" => {
+                if self.next("'='")? != "=" {
+                    return Err(self.error("expected '=' after PARAM"));
+                }
+                return Ok(MeasureKind::Param(self.param()?));
+            }
+            "AVG" => Statistic::Average,
+            "RMS" => Statistic::Rms,
+            "MIN" => Statistic::Min,
+            "MAX" => Statistic::Max,
+            "PP" => Statistic::PeakToPeak,
+            "INTEG" => Statistic::Integral,
+            _ => return Err(self.error(&format!("unknown measurement '{keyword}'"))),
+        };
+        let signal = self.signal()?;
+        let (mut from, mut to) = (None, None);
+        while let Some(keyword) = self.assignment(&["FROM", "TO"]) {
+            let value = Some(self.value()?);
+            if keyword == "FROM" {
+                from = value;
+            } else {
+                to = value;
+            }
+        }
+        Ok(MeasureKind::Statistic {
+            statistic,
+            signal,
+            from,
+            to,
+        })
+    }
+}
+
+/// Parse the `.MEASURE` lines of the netlist of a circuit, ignoring the
+/// other lines. Only transient (`TRAN`) measurements are supported.
+pub fn parse_measurements(text: &str, circuit: &Circuit) -> Result<Vec<Measurement>, NetlistError> {
+    let mut measurements: Vec<Measurement> = Vec::new();
+    for (index, text) in text.lines().enumerate() {
+        let tokens = tokenize(text.trim());
+        match tokens.first() {
+            Some(first) if is_measure(first) => {}
+            _ => continue,
+        }
+        let names: Vec<String> = measurements
+            .iter()
+            .map(|measurement| measurement.name.to_lowercase())
+            .collect();
+        let mut parser = Parser {
+            line: index + 1,
+            tokens: &tokens,
+            index: 1,
+            circuit,
+            measurements: &names,
+        };
+        let analysis = parser.next("analysis")?.to_string();
+        if !analysis.eq_ignore_ascii_case("TRAN") {
+            return Err(parser.error(&format!(
+                "unsupported analysis '{analysis}' (only TRAN measurements are supported)"
+            )));
+        }
+        let name = parser.next("name")?.to_string();
+        let kind = parser.kind()?;
+        if let Some(token) = tokens.get(parser.index) {
+            return Err(parser.error(&format!("unexpected '{token}'")));
+        }
+        measurements.push(Measurement { name, kind });
+    }
+    Ok(measurements)
+}
+
+/// Whether the first token of a line starts a measurement
+pub(crate) fn is_measure(token: &str) -> bool {
+    token.eq_ignore_ascii_case(".MEASURE") || token.eq_ignore_ascii_case(".MEAS")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netlist::parse_netlist;
+    use crate::transient::{TransientAnalysis, TransientOptions};
+
+    /// The measurements of a netlist, by name, on its transient analysis
+    /// from 0 to 1 ms
+    fn measure(netlist: &str) -> Vec<(String, f64)> {
+        let circuit = parse_netlist(netlist).unwrap();
+        let measurements = parse_measurements(netlist, &circuit).unwrap();
+        let result = TransientAnalysis::new(&circuit, TransientOptions::new(1e-6, 1e-3)).run();
+        measurements
+            .iter()
+            .zip(measure_all(&measurements, &result))
+            .map(|(measurement, value)| (measurement.name.clone(), value.unwrap()))
+            .collect()
+    }
+
+    /// A ramp from 0 to 1 V over the analysis, halved at `out`, and a
+    /// 1 V, 1 kHz sine about 0.5 V
+    const NETLIST: &str = "V1 in 0 PWL(0 0 1m 1)
+R1 in out 1k
+R2 out 0 1k
+V2 ac 0 SIN(0.5 1 1k)
+R3 ac 0 1k
+.PARAM two=2
+";
+
+    fn assert_measures(lines: &str, expected: &[(&str, f64)]) {
+        let measured = measure(&format!("{NETLIST}{lines}"));
+        assert_eq!(measured.len(), expected.len());
+        for ((name, value), (expected_name, expected)) in measured.iter().zip(expected) {
+            assert_eq!(name, expected_name);
+            assert!(
+                (value - expected).abs() < 1e-6 * expected.abs().max(1.0),
+                "{name}: {value} is not {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn delay_is_from_the_trigger_to_the_target() {
+        assert_measures(
+            ".MEAS TRAN rise TRIG v(in) VAL=0.2 RISE=1 TARG v(in) VAL=0.8 RISE=1\n\
+             .MEAS TRAN fall TRIG v(ac) VAL=0.5 FALL=1 TARG v(ac) VAL=0.5 FALL=LAST\n",
+            &[("rise", 0.6e-3), ("fall", 0.0)],
+        );
+    }
+
+    #[test]
+    fn find_is_the_value_when_another_signal_crosses() {
+        assert_measures(
+            ".MEAS TRAN half FIND v(out) WHEN v(in)=0.5\n\
+             .MEAS TRAN quarter FIND v(out,0) AT=0.5m\n\
+             .MEASURE TRAN crossing WHEN v(ac)=1.5 RISE=1\n",
+            &[("half", 0.25), ("quarter", 0.25), ("crossing", 0.25e-3)],
+        );
+    }
+
+    #[test]
+    fn average_and_rms_are_of_the_waveform() {
+        // The sine has an average of its offset, and an RMS of
+        // sqrt(offset^2 + amplitude^2 / 2) over whole periods
+        let measured = measure(&format!(
+            "{NETLIST}.MEAS TRAN avg AVG v(ac)\n.MEAS TRAN rms RMS v(ac) FROM=0 TO=1m\n\
+             .MEAS TRAN ramp AVG v(in) FROM=0.2m TO=0.6m\n"
+        ));
+        assert!((measured[0].1 - 0.5).abs() < 1e-6, "{measured:?}");
+        assert!(
+            (measured[1].1 - 0.75f64.sqrt()).abs() < 1e-4,
+            "{measured:?}"
+        );
+        assert!((measured[2].1 - 0.4).abs() < 1e-9, "{measured:?}");
+    }
+
+    #[test]
+    fn signals_are_expressions_of_named_nodes_and_currents() {
+        let measured = measure(&format!(
+            "{NETLIST}.MEAS TRAN doubled FIND 'v(out) * two' AT=0.5m\n\
+             .MEAS TRAN difference FIND v(in)-v(out) AT=1m\n\
+             .MEAS TRAN current FIND i(V1) AT=1m\n\
+             .MEAS TRAN ratio PARAM='doubled/difference + two'\n"
+        ));
+        let values: Vec<f64> = measured.iter().map(|(_, value)| *value).collect();
+        assert!((values[0] - 0.5).abs() < 1e-9, "{measured:?}");
+        assert!((values[1] - 0.5).abs() < 1e-9, "{measured:?}");
+        assert!((values[2].abs() - 0.5e-3).abs() < 1e-12, "{measured:?}");
+        assert!((values[3] - 3.0).abs() < 1e-9, "{measured:?}");
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        let circuit = parse_netlist(NETLIST).unwrap();
+        for line in [
+            ".MEAS TRAN x FIND v(nowhere) AT=1m",
+            ".MEAS TRAN x FIND v(out)*gain AT=1m",
+            ".MEAS TRAN x PARAM='later*2'",
+            ".MEAS TRAN x AVG two",
+        ] {
+            assert!(parse_measurements(line, &circuit).is_err(), "{line}");
+        }
+    }
+}
@@ -1,35 +1,84 @@
-use csuperlu::c::value_type::ValueType;
-
 use std::ops;
 
-use crate::sparse::solve;
+use crate::component::Component;
+use crate::error::EsimError;
+use crate::sparse::{transpose, try_solve, CachedSolver, ValueType};
 
 use self::{mna_matrix::MnaMatrix, mna_rhs::MnaRhs};
 
 mod mna_matrix;
 mod mna_rhs;
 
-pub struct Mna<P: ValueType + ops::Neg<Output=P>> {
+/// Node voltages and edge currents
+type Solution<P> = (Vec<P>, Vec<P>);
+
+/// Scalar of an MNA system, which is real (f64) in DC and transient
+/// analyses and complex (Complex<f64>) in AC analyses, so that they
+/// share the same stamps
+pub trait Scalar: ValueType + ops::Neg<Output = Self> {}
+
+impl<P: ValueType + ops::Neg<Output = P>> Scalar for P {}
+
+pub struct Mna<P: Scalar> {
     matrix: MnaMatrix<P>,
     rhs: MnaRhs<P>,
+    /// Earlier right-hand sides, for solve_columns
+    previous_rhs: Vec<MnaRhs<P>>,
+}
+
+impl<P: Scalar> Default for Mna<P> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl<P: ValueType + ops::Neg<Output=P>> Mna<P> {
+impl<P: Scalar> Mna<P> {
     pub fn new() -> Self {
         Self {
             matrix: MnaMatrix::new(),
             rhs: MnaRhs::new(),
+            previous_rhs: Vec::new(),
+        }
+    }
+
+    /// Start a new right-hand side, so that the sources added from
+    /// now on are solved separately from the earlier ones by
+    /// solve_columns
+    pub fn next_rhs(&mut self) {
+        let rhs = std::mem::replace(&mut self.rhs, MnaRhs::new());
+        self.previous_rhs.push(rhs);
+    }
+
+    /// Add an admittance between two terminals (group1)
+    pub fn add_admittance(&mut self, term_1: usize, term_2: usize, admittance: P) {
+        self.matrix
+            .add_symmetric_group1(term_1, term_2, admittance, -admittance);
+    }
+
+    /// Add an N-port admittance matrix (group1), given by row then
+    /// column, whose current into the positive node of each port is
+    /// the row of the matrix times the port voltages
+    pub fn add_admittance_matrix(&mut self, ports: &[(usize, usize)], admittance: &[P]) {
+        let n = ports.len();
+        for (i, output) in ports.iter().enumerate() {
+            for (j, control) in ports.iter().enumerate() {
+                self.add_voltage_controlled_current_source(
+                    *output,
+                    *control,
+                    admittance[i * n + j],
+                );
+            }
         }
     }
 
     pub fn add_resistor(
-	&mut self,
-	term_1: usize,
-	term_2: usize,
-	current_edge: Option<usize>,
-	resistance: P,
+        &mut self,
+        term_1: usize,
+        term_2: usize,
+        current_edge: Option<usize>,
+        resistance: P,
     ) {
-	let r = resistance;
+        let r = resistance;
         match current_edge {
             Some(e) => self
                 .matrix
@@ -41,13 +90,13 @@ impl<P: ValueType + ops::Neg<Output=P>> Mna<P> {
     }
 
     pub fn add_independent_voltage_source(
-	&mut self,
-	term_pos: usize,
-	term_neg: usize,
-	current_edge: usize,
-	voltage: P,
+        &mut self,
+        term_pos: usize,
+        term_neg: usize,
+        current_edge: usize,
+        voltage: P,
     ) {
-	let v = voltage;
+        let v = voltage;
         self.matrix.add_symmetric_group2(
             term_pos,
             term_neg,
@@ -58,91 +107,291 @@ impl<P: ValueType + ops::Neg<Output=P>> Mna<P> {
         );
         self.rhs.add_rhs_group2(current_edge, v);
     }
-    
-    /* Unclean!
-    pub fn add_element_stamp(&mut self, component: &Component) {
-        match component {
-            Component::VoltageControlledVoltageSource {
-                term_pos,
-                term_neg,
-		ctrl_pos,
-		ctrl_neg,
-                current_index,
-                voltage_scale: k,
-            } => {
-                self.matrix.add_symmetric_group2(
-                    *term_pos,
-                    *term_neg,
-                    *current_index,
-                    1.0,
-                    -1.0,
-                    0.0,
-                );
-		self.matrix.add_unsymmetric_bottom_group2(
-		    *ctrl_pos,
-		    *ctrl_neg,
-		    *current_index,
-		    -*k,
-		    *k,
-		    0.0,
-		);
-            },
-            Component::CurrentControlledVoltageSource {
-                term_pos,
-                term_neg,
-		ctrl_edge,
-                current_index,
-                voltage_scale: k,
-            } => {
-                self.matrix.add_symmetric_group2(
-                    *term_pos,
-                    *term_neg,
-                    *current_index,
-                    1.0,
-                    -1.0,
-                    0.0,
-                );
-		self.matrix.add_group2_value(*current_index, *ctrl_edge, -*k);
-            },
-	    Component::IndependentCurrentSource {
-                term_pos,
-                term_neg,
-                current_index,
-                current: i
-            } => {
-                match current_index {
-                    Some(edge) => {
-			self.matrix.add_unsymmetric_right_group2(
-			    *term_pos, *term_neg, *edge,
-			    1.0, -1.0, 1.0);
-			self.rhs.add_rhs_group2(*edge, *i);
-		    },
-                    None => {
-			self.rhs.add_rhs_group1(*term_pos, -*i);
-			self.rhs.add_rhs_group1(*term_neg, *i);
-		    }
-                }
-            },
-            _ => todo!("Not currently implemented"),
-        }
+
+    /// Add a voltage in series with a group 2 branch, so that the
+    /// branch equation of a resistance R becomes v1 - v2 = R i + voltage
+    pub fn add_series_voltage(&mut self, current_edge: usize, voltage: P) {
+        self.rhs.add_rhs_group2(current_edge, voltage);
+    }
+
+    /// Add an independent current source (group1). The current flows
+    /// out of term_pos, through the source, and into term_neg.
+    pub fn add_independent_current_source(&mut self, term_pos: usize, term_neg: usize, current: P) {
+        // Make sure both terminals are counted as voltage nodes
+        self.matrix
+            .add_symmetric_group1(term_pos, term_neg, P::zero(), P::zero());
+        self.rhs.add_rhs_group1(term_pos, -current);
+        self.rhs.add_rhs_group1(term_neg, current);
+    }
+
+    /// Add a voltage-controlled voltage source (group2), whose voltage
+    /// from term_pos to term_neg is the gain times the voltage from
+    /// ctrl_pos to ctrl_neg
+    pub fn add_voltage_controlled_voltage_source(
+        &mut self,
+        (term_pos, term_neg): (usize, usize),
+        (ctrl_pos, ctrl_neg): (usize, usize),
+        current_edge: usize,
+        gain: P,
+    ) {
+        self.matrix.add_symmetric_group2(
+            term_pos,
+            term_neg,
+            current_edge,
+            P::one(),
+            -P::one(),
+            P::zero(),
+        );
+        self.matrix
+            .add_unsymmetric_bottom_group2(ctrl_pos, ctrl_neg, current_edge, -gain, gain);
+    }
+
+    /// Add a voltage-controlled current source (group1), whose current
+    /// (out of term_pos, through the source, and into term_neg) is the
+    /// transconductance times the voltage from ctrl_pos to ctrl_neg
+    pub fn add_voltage_controlled_current_source(
+        &mut self,
+        (term_pos, term_neg): (usize, usize),
+        (ctrl_pos, ctrl_neg): (usize, usize),
+        transconductance: P,
+    ) {
+        self.matrix.add_unsymmetric_group1(
+            term_pos,
+            term_neg,
+            ctrl_pos,
+            ctrl_neg,
+            transconductance,
+            -transconductance,
+        );
+    }
+
+    /// Add a current-controlled current source (group1), whose current
+    /// (out of term_pos, through the source, and into term_neg) is the
+    /// gain times the current of ctrl_edge
+    pub fn add_current_controlled_current_source(
+        &mut self,
+        (term_pos, term_neg): (usize, usize),
+        ctrl_edge: usize,
+        gain: P,
+    ) {
+        self.matrix
+            .add_unsymmetric_right_group2(term_pos, term_neg, ctrl_edge, gain, -gain);
+    }
+
+    /// Add a current-controlled voltage source (group2), whose voltage
+    /// from term_pos to term_neg is the transresistance times the
+    /// current of ctrl_edge
+    pub fn add_current_controlled_voltage_source(
+        &mut self,
+        (term_pos, term_neg): (usize, usize),
+        ctrl_edge: usize,
+        current_edge: usize,
+        transresistance: P,
+    ) {
+        self.matrix.add_symmetric_group2(
+            term_pos,
+            term_neg,
+            current_edge,
+            P::one(),
+            -P::one(),
+            P::zero(),
+        );
+        self.matrix
+            .add_group2_value(current_edge, ctrl_edge, -transresistance);
+    }
+
+    /// Number of voltage nodes excluding ground, which are the first
+    /// unknowns (node n is unknown n-1), followed by the current edges
+    pub fn num_voltage_nodes(&self) -> usize {
+        self.matrix.num_voltage_nodes()
+    }
+
+    /// The entries of the matrix, by row and column
+    pub fn entries(&self) -> Vec<((usize, usize), P)> {
+        self.matrix.entries()
+    }
+
+    /// The right-hand side
+    pub fn rhs(&self) -> Vec<P> {
+        self.rhs.get_vector(
+            self.matrix.num_voltage_nodes(),
+            self.matrix.num_current_edges(),
+        )
     }
-     */
 
-    /// Returns node voltages, edge currents
+    /// Returns node voltages, edge currents. Panics if the system
+    /// cannot be solved (see [Mna::try_solve]).
     pub fn solve(self) -> (Vec<P>, Vec<P>) {
+        self.try_solve().unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Returns node voltages, edge currents, or an error if a stamp
+    /// could not be added or the system cannot be solved
+    pub fn try_solve(self) -> Result<(Vec<P>, Vec<P>), EsimError> {
+        self.try_solve_with(&mut CachedSolver::default())
+    }
+
+    /// Solve as for [Mna::try_solve] with a solver kept between
+    /// systems of the same structure, which refactorizes the matrix
+    pub fn try_solve_with(
+        self,
+        solver: &mut CachedSolver<P>,
+    ) -> Result<(Vec<P>, Vec<P>), EsimError> {
+        self.check()?;
         let num_voltage_nodes = self.matrix.num_voltage_nodes();
         let num_current_edges = self.matrix.num_current_edges();
         let matrix = self.matrix.get_matrix();
 
-	matrix.print_structure(num_voltage_nodes);
-	
-	let rhs = self.rhs.get_vector(num_voltage_nodes, num_current_edges);
+        let rhs = self.rhs.get_vector(num_voltage_nodes, num_current_edges);
+
+        let mut solution = solver.solve_columns(&matrix, vec![rhs])?.remove(0);
+        let currents: Vec<_> = solution.drain(num_voltage_nodes..).collect();
+        // Solution now contains the voltages
+        Ok((solution, currents))
+    }
+
+    /// The error of the first stamp that could not be added, if any
+    fn check(&self) -> Result<(), EsimError> {
+        match self.matrix.error() {
+            Some(message) => Err(EsimError::Assembly {
+                message: message.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Solve the adjoint system (the transposed matrix) for the
+    /// voltage from term_pos to term_neg. The solution for each node
+    /// is the transfer to that voltage from a unit current injected
+    /// into the node, and the solution for each edge the transfer from
+    /// a unit voltage in series with the edge, so the transfer from
+    /// every source is found from one solve. Returns the solutions for
+    /// the nodes and for the edges. Panics if the system cannot be
+    /// solved (see [Mna::try_solve_adjoint]).
+    pub fn solve_adjoint(self, term_pos: usize, term_neg: usize) -> (Vec<P>, Vec<P>) {
+        self.try_solve_adjoint(term_pos, term_neg)
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Solve the adjoint system as for [Mna::solve_adjoint], returning
+    /// an error if a stamp could not be added or the system cannot be
+    /// solved
+    pub fn try_solve_adjoint(
+        self,
+        term_pos: usize,
+        term_neg: usize,
+    ) -> Result<(Vec<P>, Vec<P>), EsimError> {
+        self.check()?;
+        let num_voltage_nodes = self.matrix.num_voltage_nodes();
+        let num_current_edges = self.matrix.num_current_edges();
+        let matrix = transpose(&self.matrix.get_matrix());
+        let mut rhs = vec![P::zero(); num_voltage_nodes + num_current_edges];
+        if term_pos != 0 {
+            rhs[term_pos - 1] = P::one();
+        }
+        if term_neg != 0 {
+            rhs[term_neg - 1] = -P::one();
+        }
+        let mut solution = try_solve(matrix, rhs)?;
+        let edges = solution.split_off(num_voltage_nodes);
+        Ok((solution, edges))
+    }
+
+    /// Solve for each right-hand side (see next_rhs) with a single
+    /// factorization. Returns node voltages and edge currents for
+    /// each, in the order they were started. Panics if the system
+    /// cannot be solved (see [Mna::try_solve_columns]).
+    pub fn solve_columns(self) -> Vec<(Vec<P>, Vec<P>)> {
+        self.try_solve_columns()
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Solve for each right-hand side as for [Mna::solve_columns],
+    /// returning an error if the system cannot be solved
+    pub fn try_solve_columns(self) -> Result<Vec<Solution<P>>, EsimError> {
+        self.try_solve_columns_with(&mut CachedSolver::default())
+    }
+
+    /// Solve for each right-hand side as for [Mna::try_solve_columns]
+    /// with a solver kept between systems of the same structure
+    pub fn try_solve_columns_with(
+        self,
+        solver: &mut CachedSolver<P>,
+    ) -> Result<Vec<Solution<P>>, EsimError> {
+        self.check()?;
+        let num_voltage_nodes = self.matrix.num_voltage_nodes();
+        let num_current_edges = self.matrix.num_current_edges();
+        let matrix = self.matrix.get_matrix();
+        let columns = self
+            .previous_rhs
+            .into_iter()
+            .chain([self.rhs])
+            .map(|rhs| rhs.get_vector(num_voltage_nodes, num_current_edges))
+            .collect();
+        Ok(solver
+            .solve_columns(&matrix, columns)?
+            .into_iter()
+            .map(|mut solution| {
+                let currents = solution.split_off(num_voltage_nodes);
+                (solution, currents)
+            })
+            .collect())
+    }
+}
 
-	let mut solution = solve(matrix, rhs);
-	let currents: Vec<_> = solution
-	    .drain(num_voltage_nodes..)
-	    .collect();
-	// Solution now contains the voltages
-	(solution, currents)
+impl<P: Scalar + From<f64>> Mna<P> {
+    /// Add a controlled source, which is linear, so it is stamped the
+    /// same way in every analysis. Panics if the component is not a
+    /// controlled source.
+    pub fn add_controlled_source(&mut self, component: &Component) {
+        match *component {
+            Component::VoltageControlledVoltageSource {
+                term_pos,
+                term_neg,
+                ctrl_pos,
+                ctrl_neg,
+                current_edge,
+                gain,
+            } => self.add_voltage_controlled_voltage_source(
+                (term_pos, term_neg),
+                (ctrl_pos, ctrl_neg),
+                current_edge,
+                gain.into(),
+            ),
+            Component::VoltageControlledCurrentSource {
+                term_pos,
+                term_neg,
+                ctrl_pos,
+                ctrl_neg,
+                transconductance,
+            } => self.add_voltage_controlled_current_source(
+                (term_pos, term_neg),
+                (ctrl_pos, ctrl_neg),
+                transconductance.into(),
+            ),
+            Component::CurrentControlledCurrentSource {
+                term_pos,
+                term_neg,
+                ctrl_edge,
+                gain,
+            } => self.add_current_controlled_current_source(
+                (term_pos, term_neg),
+                ctrl_edge,
+                gain.into(),
+            ),
+            Component::CurrentControlledVoltageSource {
+                term_pos,
+                term_neg,
+                ctrl_edge,
+                current_edge,
+                transresistance,
+            } => self.add_current_controlled_voltage_source(
+                (term_pos, term_neg),
+                ctrl_edge,
+                current_edge,
+                transresistance.into(),
+            ),
+            _ => panic!("Not a controlled source"),
+        }
     }
 }
@@ -1,6 +1,5 @@
+use crate::sparse::{concat_horizontal, concat_vertical, plus_equals, SparseMat, ValueType};
 use std::cmp;
-use csuperlu::{sparse_matrix::SparseMat, c::value_type::ValueType};
-use crate::sparse::{plus_equals, concat_horizontal, concat_vertical};
 
 /// Matrix for modified nodal analysis
 ///
@@ -23,6 +22,9 @@ pub struct MnaMatrix<P: ValueType> {
     top_right: SparseMat<P>,
     bottom_left: SparseMat<P>,
     bottom_right: SparseMat<P>,
+    /// The first stamp that could not be added, which makes the
+    /// matrix fail to solve
+    error: Option<String>,
 }
 
 impl<P: ValueType> MnaMatrix<P> {
@@ -34,6 +36,7 @@ impl<P: ValueType> MnaMatrix<P> {
             top_right: SparseMat::empty(),
             bottom_left: SparseMat::empty(),
             bottom_right: SparseMat::empty(),
+            error: None,
         }
     }
 
@@ -46,6 +49,36 @@ impl<P: ValueType> MnaMatrix<P> {
         self.num_current_edges
     }
 
+    /// The first stamp that could not be added, if there was one
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Record a stamp that could not be added, keeping the first
+    fn fail(&mut self, message: String) {
+        self.error.get_or_insert(message);
+    }
+
+    /// The entries that have been added, by row and column of the
+    /// whole matrix
+    pub fn entries(&self) -> Vec<((usize, usize), P)> {
+        let n = self.num_voltage_nodes;
+        [
+            (&self.top_left, 0, 0),
+            (&self.top_right, 0, n),
+            (&self.bottom_left, n, 0),
+            (&self.bottom_right, n, n),
+        ]
+        .into_iter()
+        .flat_map(|(block, row, column)| {
+            block
+                .non_zero_vals()
+                .iter()
+                .map(move |((r, c), value)| ((row + r, column + c), *value))
+        })
+        .collect()
+    }
+
     pub fn get_matrix(mut self) -> SparseMat<P> {
         self.top_left
             .resize(self.num_voltage_nodes, self.num_voltage_nodes);
@@ -79,14 +112,18 @@ impl<P: ValueType> MnaMatrix<P> {
     ///
     /// The two indices specified defines a group of four matrix entries $(n_1-1, n_1-1) =
     /// (n_2-1,n_2-1) = x_1$, and $(n_1-1,n_2-1) = (n_2-1,n_1-1) = x_2$ (i.e. a symmetric block).
-    /// Indices $n1$ and $n2$ are non-zero, and must be different. If either
+    /// Indices $n1$ and $n2$ must be different (otherwise nothing is
+    /// added, and the error is recorded for the solve). If either
     /// $n_1 = 0$ or $n_2 = 0$, then any elements where the matrix index would
     /// be negative are not written.
     ///
     /// This matrix block is added to the current matrix in the top left of the MNA matrix.
     pub fn add_symmetric_group1(&mut self, n1: usize, n2: usize, x1: P, x2: P) {
         if n1 == n2 {
-            panic!("Cannot set symmetric group 1 where n1 == n2");
+            self.fail(format!(
+                "Cannot set symmetric group 1 where n1 == n2 (node {n1})"
+            ));
+            return;
         }
         self.update_num_voltage_nodes(n1);
         self.update_num_voltage_nodes(n2);
@@ -108,19 +145,15 @@ impl<P: ValueType> MnaMatrix<P> {
     /// (bottom-left); $x_2$ to $(n_2-1, e)$ (top-right) and $(e, n_2-1)$
     /// (bottom-left); and $y$ to $(e, e)$ (bottom-right).
     ///
-    /// In all cases, if all cases, $n_1 != n_2$, and if $n_1 = 0$ or $n_2 = 0$, then
-    /// the corresponding matrix entries are not written.
-    pub fn add_symmetric_group2(
-        &mut self,
-        n1: usize,
-        n2: usize,
-        e: usize,
-        x1: P,
-        x2: P,
-        y: P,
-    ) {
+    /// In all cases, $n_1 != n_2$ (otherwise nothing is added, and the
+    /// error is recorded for the solve), and if $n_1 = 0$ or $n_2 = 0$,
+    /// then the corresponding matrix entries are not written.
+    pub fn add_symmetric_group2(&mut self, n1: usize, n2: usize, e: usize, x1: P, x2: P, y: P) {
         if n1 == n2 {
-            panic!("Cannot set symmetric group 2 where n1 == n2");
+            self.fail(format!(
+                "Cannot set symmetric group 2 where n1 == n2 (node {n1})"
+            ));
+            return;
         }
         self.update_num_voltage_nodes(n1);
         self.update_num_voltage_nodes(n2);
@@ -136,25 +169,12 @@ impl<P: ValueType> MnaMatrix<P> {
         }
     }
 
-    /*
     /// Same as symmetric version, but only adds values to the
     /// right-hand portion of the matrix (top and bottom)
-    pub fn add_unsymmetric_right_group2(
-        &mut self,
-        n1: usize,
-        n2: usize,
-        e: usize,
-        x1: f64,
-        x2: f64,
-        y: f64,
-    ) {
-        if n1 == n2 {
-            panic!("Cannot set unsymmetric group (right) 2 where n1 == n2");
-        }
+    pub fn add_unsymmetric_right_group2(&mut self, n1: usize, n2: usize, e: usize, x1: P, x2: P) {
         self.update_num_voltage_nodes(n1);
         self.update_num_voltage_nodes(n2);
         self.update_num_current_edges(e);
-        plus_equals(&mut self.bottom_right, e, e, y);
         if n1 != 0 {
             plus_equals(&mut self.top_right, n1 - 1, e, x1);
         }
@@ -162,27 +182,13 @@ impl<P: ValueType> MnaMatrix<P> {
             plus_equals(&mut self.top_right, n2 - 1, e, x2);
         }
     }
-     */
 
-    /*
     /// Same as symmetric version, but only adds values to the
     /// bottom portion of the matrix (left and right)
-    pub fn add_unsymmetric_bottom_group2(
-        &mut self,
-        n1: usize,
-        n2: usize,
-        e: usize,
-        x1: f64,
-        x2: f64,
-        y: f64,
-    ) {
-        if n1 == n2 {
-            panic!("Cannot set unsymmetric group (bottom) 2 where n1 == n2");
-        }
+    pub fn add_unsymmetric_bottom_group2(&mut self, n1: usize, n2: usize, e: usize, x1: P, x2: P) {
         self.update_num_voltage_nodes(n1);
         self.update_num_voltage_nodes(n2);
         self.update_num_current_edges(e);
-        plus_equals(&mut self.bottom_right, e, e, y);
         if n1 != 0 {
             plus_equals(&mut self.bottom_left, e, n1 - 1, x1);
         }
@@ -190,20 +196,36 @@ impl<P: ValueType> MnaMatrix<P> {
             plus_equals(&mut self.bottom_left, e, n2 - 1, x2);
         }
     }
-     */
 
-    /*
     /// Add a single value in the group2 (current-current, bottom-right) portion
     /// of the matrix
-    pub fn add_group2_value(
-        &mut self,
-        e1: usize,
-        e2: usize,
-        y: f64,
-    ) {
+    pub fn add_group2_value(&mut self, e1: usize, e2: usize, y: P) {
         self.update_num_current_edges(e1);
         self.update_num_current_edges(e2);
         plus_equals(&mut self.bottom_right, e1, e2, y);
     }
-     */
+
+    /// Add an unsymmetric block to the top-left matrix: $x_1$ to
+    /// $(n_1-1, c_1-1)$ and $(n_2-1, c_2-1)$, and $x_2$ to $(n_1-1, c_2-1)$
+    /// and $(n_2-1, c_1-1)$, so that the currents at $n_1$ and $n_2$
+    /// depend on the voltages of $c_1$ and $c_2$. Entries in a row or
+    /// column of ground are not written.
+    pub fn add_unsymmetric_group1(
+        &mut self,
+        n1: usize,
+        n2: usize,
+        c1: usize,
+        c2: usize,
+        x1: P,
+        x2: P,
+    ) {
+        for n in [n1, n2, c1, c2] {
+            self.update_num_voltage_nodes(n);
+        }
+        for (row, column, value) in [(n1, c1, x1), (n2, c2, x1), (n1, c2, x2), (n2, c1, x2)] {
+            if row != 0 && column != 0 {
+                plus_equals(&mut self.top_left, row - 1, column - 1, value);
+            }
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use csuperlu::{sparse_matrix::SparseMat, c::value_type::ValueType};
+use crate::sparse::{plus_equals, SparseMat, ValueType};
 
 /// Modified nodal analysis right-hand side
 ///
@@ -21,7 +21,7 @@ impl<P: ValueType> MnaRhs<P> {
         }
     }
 
-    pub fn get_vector(self, num_voltage_nodes: usize, num_current_edges: usize) -> Vec<P> {
+    pub fn get_vector(&self, num_voltage_nodes: usize, num_current_edges: usize) -> Vec<P> {
         let mut out = vec![P::zero(); num_voltage_nodes + num_current_edges];
         for ((row, _), value) in self.top.non_zero_vals().iter() {
             out[*row] = *value;
@@ -32,17 +32,17 @@ impl<P: ValueType> MnaRhs<P> {
         out
     }
 
-    /*
-    /// Add a RHS element in the group 1 matrix.
-    pub fn add_rhs_group1(&mut self, n: usize, x: f64) {
-	if n != 0 {
-            self.top.insert_unbounded(n - 1, 1, x);
-	}
+    /// Add a RHS element in the group 1 matrix. Values added to the
+    /// same node accumulate, and the ground node (n = 0) is ignored.
+    pub fn add_rhs_group1(&mut self, n: usize, x: P) {
+        if n != 0 {
+            plus_equals(&mut self.top, n - 1, 1, x);
+        }
     }
-     */
-    
-    /// Add a RHS element in the group 2 matrix
+
+    /// Add a RHS element in the group 2 matrix. Values added to the
+    /// same edge accumulate.
     pub fn add_rhs_group2(&mut self, e: usize, x: P) {
-        self.bottom.insert_unbounded(e, 1, x);
+        plus_equals(&mut self.bottom, e, 1, x);
     }
 }
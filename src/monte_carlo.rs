@@ -0,0 +1,155 @@
+//! Monte Carlo analysis
+//!
+//! Each sample is a copy of the circuit with the values of its
+//! resistors, capacitors and inductors varied at random, and the
+//! measurements are made on every sample. The variation of each value
+//! is normally distributed about the nominal value, with the
+//! tolerance as three standard deviations. The samples are repeatable
+//! for a given seed, and each sample has its own random number
+//! stream, so a range of samples can be run on its own and gives the
+//! same values as in a full run.
+
+use rand::rngs::StdRng;
+use rand_distr::{Distribution, StandardNormal};
+
+use crate::circuit::Circuit;
+use crate::component::Component;
+use crate::error::{invalid, EsimError};
+use crate::rng::{RngStreams, MONTE_CARLO};
+use crate::statistics::{SpecLimits, Statistics, YieldReport};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonteCarloOptions {
+    pub num_samples: usize,
+    /// Index of the first sample, to run a subset of the samples
+    pub first_sample: usize,
+    /// Master seed of the random number streams
+    pub seed: u64,
+    /// Relative tolerance of the component values (three standard
+    /// deviations)
+    pub tolerance: f64,
+}
+
+impl MonteCarloOptions {
+    pub fn new(num_samples: usize, seed: u64, tolerance: f64) -> Self {
+        Self {
+            num_samples,
+            first_sample: 0,
+            seed,
+            tolerance,
+        }
+    }
+}
+
+/// Values of each measurement for every sample
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonteCarloResult {
+    pub names: Vec<String>,
+    /// Values of each measurement (in the order of the names), with
+    /// one value per sample
+    pub values: Vec<Vec<f64>>,
+    /// Specification limits of each measurement (none by default)
+    pub limits: Vec<SpecLimits>,
+}
+
+impl MonteCarloResult {
+    /// Values of a measurement for every sample
+    pub fn measurement(&self, name: &str) -> Option<&[f64]> {
+        let index = self.names.iter().position(|n| n == name)?;
+        Some(&self.values[index])
+    }
+
+    /// Attach specification limits to a measurement
+    ///
+    /// Panics if there is no measurement with the name (see
+    /// [MonteCarloResult::try_set_limits]).
+    pub fn set_limits(&mut self, name: &str, limits: SpecLimits) {
+        self.try_set_limits(name, limits)
+            .unwrap_or_else(|error| panic!("{error}"));
+    }
+
+    /// Attach specification limits to a measurement, or return an
+    /// error if there is no measurement with the name
+    pub fn try_set_limits(&mut self, name: &str, limits: SpecLimits) -> Result<(), EsimError> {
+        let index = self
+            .names
+            .iter()
+            .position(|n| n == name)
+            .ok_or_else(|| invalid(format!("No measurement named {name}")))?;
+        self.limits[index] = limits;
+        Ok(())
+    }
+
+    /// Summary statistics of a measurement across the samples, with
+    /// the requested percentiles and number of histogram bins, and the
+    /// Cpk against the measurement's specification limits
+    pub fn statistics(
+        &self,
+        name: &str,
+        percentiles: &[f64],
+        num_bins: usize,
+    ) -> Option<Statistics> {
+        let index = self.names.iter().position(|n| n == name)?;
+        Some(Statistics::new(
+            &self.values[index],
+            percentiles,
+            self.limits[index],
+            num_bins,
+        ))
+    }
+
+    /// Pass/fail counts and yield of the measurements against their
+    /// specification limits
+    pub fn yield_report(&self) -> YieldReport {
+        let num_samples = self.values.first().map_or(0, Vec::len);
+        let checks = self
+            .names
+            .iter()
+            .zip(&self.values)
+            .zip(&self.limits)
+            .map(|((name, values), limits)| limits.check(name, values))
+            .collect();
+        YieldReport::new(num_samples, checks)
+    }
+}
+
+/// A copy of the circuit with its component values varied at random
+fn vary(circuit: &Circuit, tolerance: f64, rng: &mut StdRng) -> Circuit {
+    let mut circuit = circuit.clone();
+    for instance in circuit.instances_mut() {
+        let value = match &mut instance.component {
+            Component::Resistor { resistance, .. } => resistance,
+            Component::Capacitor { capacitance, .. } => capacitance,
+            Component::Inductor { inductance, .. } => inductance,
+            _ => continue,
+        };
+        let deviation: f64 = StandardNormal.sample(rng);
+        *value *= 1.0 + deviation * tolerance / 3.0;
+    }
+    circuit
+}
+
+/// Run a Monte Carlo analysis, making named measurements (which
+/// return their values in the order of the names) on every sample
+pub fn run_monte_carlo(
+    circuit: &Circuit,
+    options: &MonteCarloOptions,
+    names: &[&str],
+    measure: impl Fn(&Circuit) -> Vec<f64>,
+) -> MonteCarloResult {
+    let streams = RngStreams::new(options.seed);
+    let mut values = vec![Vec::with_capacity(options.num_samples); names.len()];
+    let first = options.first_sample;
+    for index in first..first + options.num_samples {
+        let mut rng = streams.substream(MONTE_CARLO, index as u64);
+        let sample = vary(circuit, options.tolerance, &mut rng);
+        for (values, value) in values.iter_mut().zip(measure(&sample)) {
+            values.push(value);
+        }
+    }
+    MonteCarloResult {
+        names: names.iter().map(|name| name.to_string()).collect(),
+        values,
+        limits: vec![SpecLimits::default(); names.len()],
+    }
+}
@@ -0,0 +1,1409 @@
+//! Netlist parsing
+//!
+//! A netlist has one component per line, in the form
+//!
+//! ```text
+//! R1 1 0 1k [G2]
+//! C1 2 0 100n
+//! R2 1 0 model L=l [W=w] [TEMP=t]
+//! C2 2 0 model L=l [W=w] [TEMP=t]
+//! L1 1 2 10u
+//! D1 2 0 [model] [TEMP=t]
+//! Q1 c b e [substrate] model [TEMP=t]
+//! M1 d g s b model [L=l] [W=w] [TEMP=t]
+//! V1 1 0 [DC] 5 [AC 1 [0]] [waveform]
+//! I1 0 2 [DC] 1m [AC 1 [0]] [waveform]
+//! E1 3 0 1 2 10
+//! G1 3 0 1 2 1m
+//! F1 3 0 V1 2
+//! H1 3 0 V1 1k
+//! B1 4 0 V={2*vref}
+//! S1 5 6 [ref] TSTONEFILE=filter.s2p
+//! ```
+//!
+//! The first letter of the name is the component type, nodes are
+//! numbers or names (0 and `gnd` are ground), and values are parsed by
+//! [parse_value](crate::value::parse_value). Named nodes are numbered
+//! after the highest numbered node, in the order they appear, and the
+//! names are kept in the circuit (see [crate::node]). A resistor marked `G2`
+//! keeps its current in the solution. The waveform of a source is in
+//! the form of [parse_waveform](crate::waveform::parse_waveform), such
+//! as `PULSE(0 5 0 1n 1n 1u 2u)`. The controlled sources are
+//! voltage-controlled (E and G, by the voltage between the last two
+//! nodes) or current-controlled (F and H, by the current of a named
+//! component with a current edge, such as a voltage source). A
+//! behavioral source (B) is a voltage (`V=`) or current (`I=`) source
+//! whose value is an expression of the parameters; expressions of node
+//! voltages and currents are not supported. An N-port (S) is measured
+//! data from a Touchstone file (see [TouchstoneData]), whose extension
+//! gives the number of ports; each port is between one of its nodes
+//! and the reference node (ground if it is not given), and the path is
+//! relative to the directory of the netlist file. A diode
+//! uses the model named on its line, or the default model, and is at
+//! the temperature (degrees Celsius) on its line, if it has one, or
+//! else that of the circuit, given by a `.TEMP` line (27 degrees Celsius
+//! if there is none). A diode of a `SCHOTTKY` or `TUNNEL` model is a
+//! Schottky or tunnel diode (see [SchottkyModel] and
+//! [TunnelDiodeModel]). A resistor or capacitor with a drawn length
+//! `L` is a semiconductor resistor or capacitor of the `R` or `C` model
+//! named on its line (see [SemiconductorResistorModel] and
+//! [SemiconductorCapacitorModel]), at its temperature as for a diode.
+//! A bipolar transistor (Q) uses an `NPN` or `PNP` model (see
+//! [BjtModel]), and a MOSFET (M) an `NMOS` or `PMOS` model (see
+//! [MosfetModel]), with its drawn length and width (100 µm if they are
+//! not given), each at its temperature as for a diode. The substrate of
+//! a bipolar transistor and the bulk of a MOSFET are not modelled (with
+//! a warning if the bulk is not at the source). Current edges are numbered in the order of the lines that need them.
+//!
+//! As in SPICE, a line starting with `+` continues the previous one,
+//! text after a `;` (or a `$` after a space) is a comment, and parsing
+//! stops at `.END`. The first line is the title, as in SPICE, if it
+//! is not a command and cannot be parsed as a component (with a
+//! warning if it starts with the letter of a component), so a netlist
+//! can start with either a title or its first component. Blank
+//! lines and lines starting with `*` or `#` are ignored, as are
+//! `.MEASURE` lines (which are parsed by
+//! [parse_measurements](crate::measure::parse_measurements)) and
+//! analysis and output commands (such as `.TRAN` and `.PRINT`), since
+//! analyses are run through the API. The analyses of `.OP`, `.DC`,
+//! `.AC` and `.TRAN` lines are parsed by [parse_analyses], and the
+//! solver settings of `.OPTIONS` lines by [parse_options]. Subcircuits are defined between `.SUBCKT` and `.ENDS`
+//! lines, with parameters used in braces, and used by X lines, which
+//! are expanded into their components, named after the instance (as in
+//! `X1.R1`), with their own internal nodes, except the global nodes
+//! named on `.GLOBAL` lines, which are shared with the netlist.
+//!
+//! Parameters are defined by `.PARAM` lines, as in `.PARAM r=1k
+//! gain={r/100}`, and used in expressions in braces (see
+//! [crate::expression]), as in `R1 1 2 {2*r + 50}`. The parameters are
+//! kept in the circuit, and the value of a resistor, capacitor,
+//! inductor or source (without a waveform) that is an expression is
+//! evaluated again when a parameter is set (as by
+//! [Circuit::set_parameter]). Functions are defined by `.FUNC` lines,
+//! as in `.FUNC db(x)=20*log10(x)` (or `.FUNC db(x) {20*log10(x)}`),
+//! and called in expressions, including those of behavioral sources.
+//!
+//! `.INCLUDE file` is replaced by the lines of the file, and `.LIB file
+//! section` by those of a section of a library file (between `.LIB
+//! section` and `.ENDL`). Relative paths are from the directory of the
+//! including file, so a netlist read from a file is best parsed by
+//! [parse_netlist_file].
+//!
+//! Lines between `.IF (condition)`, `.ELSEIF`, `.ELSE` and `.ENDIF`
+//! lines are kept or left out by conditions on the parameters, as in
+//! `.IF (load == 1)` (see [conditional]).
+//!
+//! Models are defined by `.MODEL` cards, as in `.MODEL dmod D(IS=1e-12
+//! N=1.8)`, anywhere in the netlist, and kept in the circuit, where the
+//! instances that use a model follow changes to it (as by
+//! [Circuit::set_model_parameter]). Diode (`D`) models are supported;
+//! cards of other types, and parameters the model does not have (such
+//! as `CJO`), are ignored with a warning (see [Circuit::warnings]), so
+//! that vendor model files can be included. Model names are not case
+//! sensitive. The diode models and subcircuits of the built-in [crate::library] (such as
+//! `1N4148` and `TL081`) can be used without being defined.
+//!
+//! Netlists written for ngspice or LTspice, where the first line is
+//! always a title and expressions can be in single quotes, are parsed by
+//! [parse_netlist_dialect] and [parse_netlist_file_dialect] (see
+//! [Dialect]).
+//!
+//! Parsing stops at the first error. To find all the errors of a
+//! netlist at once, with error codes, spans and suggestions, check it
+//! with [check_netlist] or [check_netlist_file] (see [Diagnostics]).
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::circuit::Circuit;
+use crate::component::{
+    AcSpec, BjtModel, Component, DiodeModel, Model, MosfetModel, Polarity, SchottkyModel,
+    SemiconductorCapacitorModel, SemiconductorResistorModel, TouchstoneData, TunnelDiodeModel,
+};
+use crate::library;
+use crate::measure::is_measure;
+use crate::value::{normalize, parse_spice_value, parse_value, ValueError};
+use crate::waveform::{parse_waveform_in, Waveform};
+
+pub(crate) use self::diagnostic::did_you_mean;
+pub use self::diagnostic::{
+    check_netlist, check_netlist_file, Diagnostic, Diagnostics, ErrorCode, Span,
+};
+use self::dialect::comment_title;
+pub use self::dialect::Dialect;
+pub use self::directive::{parse_analyses, parse_analyses_file, parse_options, parse_options_file};
+pub(crate) use self::subcircuit::{flatten, touchstone_file, Statement};
+pub use self::writer::{write_netlist, WriteError};
+
+mod conditional;
+mod diagnostic;
+mod dialect;
+mod directive;
+mod include;
+mod subcircuit;
+mod writer;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetlistError {
+    /// The included file the line is in, if it is not in the netlist
+    /// itself
+    pub file: Option<PathBuf>,
+    /// Line number (from 1)
+    pub line: usize,
+    pub message: String,
+    /// What kind of error it is
+    pub code: ErrorCode,
+    /// The token of the line the error is at, if it is at one
+    pub token: Option<String>,
+    /// How the line might be fixed, if there is a likely way
+    pub suggestion: Option<String>,
+}
+
+impl NetlistError {
+    pub(crate) fn new(file: Option<PathBuf>, line: usize, message: String) -> Self {
+        Self {
+            file,
+            line,
+            message,
+            code: ErrorCode::Invalid,
+            token: None,
+            suggestion: None,
+        }
+    }
+
+    pub(crate) fn with_code(self, code: ErrorCode) -> Self {
+        Self { code, ..self }
+    }
+
+    /// The error, at a token of its line
+    pub(crate) fn at(self, token: &str) -> Self {
+        Self {
+            token: Some(token.to_string()),
+            ..self
+        }
+    }
+
+    pub(crate) fn suggest(self, suggestion: Option<String>) -> Self {
+        Self { suggestion, ..self }
+    }
+}
+
+impl fmt::Display for NetlistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{} line {}: {}", file.display(), self.line, self.message),
+            None => write!(f, "Netlist line {}: {}", self.line, self.message),
+        }
+    }
+}
+
+impl std::error::Error for NetlistError {}
+
+/// Where a line of a netlist is
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Location {
+    /// The file the line is in, if the netlist is read from a file
+    pub file: Option<PathBuf>,
+    /// Line number (from 1)
+    pub line: usize,
+}
+
+impl Location {
+    pub fn error(&self, message: String) -> NetlistError {
+        NetlistError::new(self.file.clone(), self.line, message)
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{} line {}", file.display(), self.line),
+            None => write!(f, "netlist line {}", self.line),
+        }
+    }
+}
+
+/// Parser state for one line, with the warnings of the netlist
+struct Line<'a> {
+    location: &'a Location,
+    tokens: Vec<&'a str>,
+    warnings: &'a RefCell<Vec<String>>,
+}
+
+impl<'a> Line<'a> {
+    fn error(&self, message: &str) -> NetlistError {
+        self.location.error(message.to_string())
+    }
+
+    /// Add a warning at the line
+    fn warn(&self, message: &str) {
+        self.warnings
+            .borrow_mut()
+            .push(format!("{}: {message}", self.location));
+    }
+
+    /// Parse a value, warning of each change made to normalize it
+    fn parse(&self, text: &str) -> Result<f64, ValueError> {
+        for change in normalize(text).changes {
+            self.warn(&format!("value '{text}': {change}"));
+        }
+        parse_value(text)
+    }
+
+    fn token(&self, index: usize, what: &str) -> Result<&str, NetlistError> {
+        self.tokens.get(index).copied().ok_or_else(|| {
+            self.error(&format!("missing {what}"))
+                .with_code(ErrorCode::MissingToken)
+        })
+    }
+
+    fn node(&self, index: usize) -> Result<usize, NetlistError> {
+        let token = self.token(index, "node")?;
+        token.parse().map_err(|_| {
+            self.error(&format!("invalid node '{token}'"))
+                .with_code(ErrorCode::InvalidNode)
+                .at(token)
+        })
+    }
+
+    fn value(&self, index: usize) -> Result<f64, NetlistError> {
+        let token = self.token(index, "value")?;
+        self.parse(token).map_err(|error| {
+            self.error(&error.to_string())
+                .with_code(ErrorCode::InvalidValue)
+                .at(token)
+                .suggest(Some(String::from(
+                    "write a number with an optional scale suffix, as in 4.7k, 10u or 1meg",
+                )))
+        })
+    }
+
+    /// An error at an unexpected token
+    fn unexpected(&self, token: &str) -> NetlistError {
+        self.error(&format!("unexpected '{token}'"))
+            .with_code(ErrorCode::UnexpectedToken)
+            .at(token)
+    }
+
+    /// The DC value, AC specification and waveform of a source, from
+    /// the tokens after its nodes
+    fn source(&self) -> Result<(f64, AcSpec, Option<Waveform>), NetlistError> {
+        let mut index = 3;
+        let mut dc = 0.0;
+        let mut ac = AcSpec::default();
+        let mut waveform = None;
+        while index < self.tokens.len() {
+            match self.tokens[index].to_ascii_uppercase().as_str() {
+                "DC" => {
+                    dc = self.value(index + 1)?;
+                    index += 2;
+                }
+                "AC" => {
+                    let magnitude = self.value(index + 1)?;
+                    // A phase is a value, checked without the warnings
+                    // of parsing it, which come when it is parsed below
+                    let phase = match self.tokens.get(index + 2) {
+                        Some(token) if parse_spice_value(&normalize(token).text).is_ok() => {
+                            index += 1;
+                            self.value(index + 1)?
+                        }
+                        _ => 0.0,
+                    };
+                    ac = AcSpec::new(magnitude, phase);
+                    index += 2;
+                }
+                token if token.contains('(') => {
+                    waveform = Some(self.waveform(self.tokens[index])?);
+                    index += 1;
+                }
+                // `PWL FILE=...` without parentheses, up to its options
+                "PWL" => {
+                    let end = (index + 1..self.tokens.len())
+                        .find(|k| !self.tokens[*k].contains('='))
+                        .unwrap_or(self.tokens.len());
+                    waveform = Some(self.waveform(&self.tokens[index..end].join(" "))?);
+                    index = end;
+                }
+                _ if index == 3 => {
+                    dc = self.value(index)?;
+                    index += 1;
+                }
+                _ => return Err(self.unexpected(self.tokens[index])),
+            }
+        }
+        Ok((dc, ac, waveform))
+    }
+
+    /// Parse a waveform, with the relative paths of its files taken
+    /// from the directory of the netlist
+    fn waveform(&self, text: &str) -> Result<Waveform, NetlistError> {
+        parse_waveform_in(text, self.directory()).map_err(|error| self.error(&error.to_string()))
+    }
+
+    /// The directory of the netlist, which relative paths are taken
+    /// from
+    fn directory(&self) -> &Path {
+        self.location
+            .file
+            .as_deref()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new(""))
+    }
+
+    /// The instance parameters (as `TEMP=50`) at the end of the line,
+    /// from an index on, as (key in upper case, value), and the line
+    /// without them
+    fn instance_parameters(
+        &self,
+        from: usize,
+        keys: &[&str],
+    ) -> Result<(Self, Vec<(String, f64)>), NetlistError> {
+        let mut tokens = self.tokens.clone();
+        let mut parameters = Vec::new();
+        while tokens.len() > from {
+            let Some((key, value)) = tokens.last().and_then(|token| token.split_once('=')) else {
+                break;
+            };
+            let key = key.to_ascii_uppercase();
+            if !keys.contains(&key.as_str()) {
+                return Err(self.unexpected(tokens[tokens.len() - 1]));
+            }
+            let value = self
+                .parse(value)
+                .map_err(|error| self.error(&format!("{}: {error}", key.to_ascii_lowercase())))?;
+            parameters.push((key, value));
+            tokens.pop();
+        }
+        let line = Line {
+            location: self.location,
+            tokens,
+            warnings: self.warnings,
+        };
+        Ok((line, parameters))
+    }
+
+    /// Check that there are no tokens from an index on
+    fn end(&self, index: usize) -> Result<(), NetlistError> {
+        match self.tokens.get(index) {
+            Some(token) => Err(self.unexpected(token)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A model from a `.MODEL name type(parameter=value ...)` line (the
+/// parentheses and commas between the parameters are optional), or
+/// none if the type is not supported
+fn model_card(line: &Line) -> Result<Option<(String, Model)>, NetlistError> {
+    let name = line.token(1, "model name")?;
+    let parameters = line.tokens[2..].join(" ").replace(['(', ')', ','], " ");
+    // Spaces around the equals signs are allowed
+    let parameters = parameters
+        .split('=')
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("=");
+    let mut words = parameters.split_whitespace();
+    let kind = words
+        .next()
+        .ok_or_else(|| line.error("missing model type"))?;
+    let mut model = match kind.to_ascii_uppercase().as_str() {
+        "D" => Model::Diode(DiodeModel::default()),
+        "SCHOTTKY" => Model::Schottky(SchottkyModel::default()),
+        "TUNNEL" => Model::TunnelDiode(TunnelDiodeModel::default()),
+        "R" => Model::SemiconductorResistor(SemiconductorResistorModel::default()),
+        "C" => Model::SemiconductorCapacitor(SemiconductorCapacitorModel::default()),
+        "NPN" => Model::Bjt(BjtModel::default()),
+        "PNP" => Model::Bjt(BjtModel {
+            polarity: Polarity::P,
+            ..Default::default()
+        }),
+        "NMOS" => Model::Mosfet(MosfetModel::default()),
+        "PMOS" => Model::Mosfet(MosfetModel {
+            polarity: Polarity::P,
+            ..Default::default()
+        }),
+        _ => {
+            line.warn(&format!(
+                "model '{name}' of type '{kind}' is not supported, so it is ignored"
+            ));
+            return Ok(None);
+        }
+    };
+    for word in words {
+        let (parameter, value) = word
+            .split_once('=')
+            .ok_or_else(|| line.error(&format!("expected a parameter, found '{word}'")))?;
+        if let (Model::Mosfet(_), "LEVEL") = (model, parameter.to_ascii_uppercase().as_str()) {
+            if value != "1" {
+                line.warn(&format!(
+                    "level {value} of model '{name}' is not supported, so it is level 1"
+                ));
+            }
+            continue;
+        }
+        match model.parameter_mut(parameter) {
+            Some(slot) => {
+                *slot = line
+                    .parse(value)
+                    .map_err(|error| line.error(&format!("parameter '{parameter}': {error}")))?
+            }
+            None => line.warn(&format!(
+                "parameter '{parameter}' of model '{name}' is not supported, so it is ignored"
+            )),
+        }
+    }
+    Ok(Some((name.to_ascii_lowercase(), model)))
+}
+
+/// The name of the model a component uses by a name on its line: that
+/// of the innermost subcircuit instance the component is in with a
+/// model of the name, or else of the netlist, if it is defined
+pub(crate) fn find_model(
+    component: &str,
+    model: &str,
+    defined: impl Fn(&str) -> bool,
+) -> Option<String> {
+    component
+        .match_indices('.')
+        .rev()
+        .map(|(end, _)| format!("{}.{model}", &component[..end]))
+        .chain([model.to_string()])
+        .map(|name| name.to_ascii_lowercase())
+        .find(|name| defined(name))
+}
+
+/// The name and card of the model a component uses by a name on its
+/// line, defined by the netlist or else a part of the library (which
+/// is added to the circuit), or none if the card of the model has an
+/// error, which is reported already
+fn component_model(
+    circuit: &mut Circuit,
+    failed_models: &[String],
+    line: &Line,
+    component: &str,
+    model: &str,
+) -> Result<Option<(String, Model)>, NetlistError> {
+    let found = match find_model(component, model, |name| circuit.model(name).is_some()) {
+        Some(found) => found,
+        None if failed_models.contains(&model.to_ascii_lowercase()) => return Ok(None),
+        // A part of the library, unless the netlist defines a model of
+        // the same name
+        None => {
+            let part = library::model(model).ok_or_else(|| {
+                let models = circuit
+                    .models()
+                    .map(|(name, _)| name)
+                    .chain(library::PARTS.iter().map(|part| part.name));
+                line.error(&format!("no model named '{model}'"))
+                    .with_code(ErrorCode::UnknownName)
+                    .at(model)
+                    .suggest(did_you_mean(model, models))
+            })?;
+            let found = model.to_ascii_lowercase();
+            circuit.add_model(&found, part);
+            found
+        }
+    };
+    let card = *circuit.model(&found).expect("the model is defined");
+    Ok(Some((found, card)))
+}
+
+/// Analysis, output and options commands, which are ignored
+const IGNORED_COMMANDS: [&str; 16] = [
+    ".OP", ".DC", ".AC", ".TRAN", ".NOISE", ".TF", ".PZ", ".SENS", ".FOUR", ".PRINT", ".PLOT",
+    ".PROBE", ".SAVE", ".WIDTH", ".OPTIONS", ".OPTION",
+];
+
+/// The commands of a netlist, for suggestions of a command that is
+/// misspelt
+const COMMANDS: [&str; 28] = [
+    ".OP", ".DC", ".AC", ".TRAN", ".NOISE", ".TF", ".PZ", ".SENS", ".FOUR", ".PRINT", ".PLOT",
+    ".PROBE", ".SAVE", ".WIDTH", ".OPTIONS", ".PARAM", ".FUNC", ".SUBCKT", ".ENDS", ".GLOBAL",
+    ".MODEL", ".MEASURE", ".TEMP", ".INCLUDE", ".LIB", ".IF", ".ELSE", ".ENDIF",
+];
+
+/// Split a line into tokens at whitespace, keeping each parenthesised
+/// group (and the word before it) in one token, as in
+/// `PULSE(0 5 0 1n 1n 1u 2u)`, and each group in braces in one token
+fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut depth = 0usize;
+    for (k, c) in text.char_indices() {
+        match c {
+            '(' | '{' => {
+                depth += 1;
+                start.get_or_insert(k);
+            }
+            ')' | '}' => depth = depth.saturating_sub(1),
+            c if c.is_whitespace() && depth == 0 => {
+                // A word followed by a group is kept with it
+                let next = text[k..].trim_start();
+                if let Some(begin) = start {
+                    if !next.starts_with('(') {
+                        tokens.push(&text[begin..k]);
+                        start = None;
+                    }
+                }
+            }
+            _ => {
+                start.get_or_insert(k);
+            }
+        }
+    }
+    if let Some(begin) = start {
+        tokens.push(&text[begin..]);
+    }
+    tokens
+}
+
+/// The lines of a netlist with comments removed and continuation lines
+/// joined, up to `.END`, with the number of the line (from 1) each
+/// starts on. Blank lines and comment lines are left out.
+fn logical_lines(text: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, text) in text.lines().enumerate() {
+        let text = text.trim();
+        if text.starts_with(['*', '#']) {
+            continue;
+        }
+        let end = text
+            .find(';')
+            .into_iter()
+            .chain(text.find(" $").or_else(|| text.find("\t$")))
+            .min()
+            .unwrap_or(text.len());
+        let text = text[..end].trim();
+        if text.is_empty() {
+            continue;
+        }
+        if let Some(continued) = text.strip_prefix('+') {
+            if let Some((_, line)) = lines.last_mut() {
+                line.push(' ');
+                line.push_str(continued.trim());
+                continue;
+            }
+        }
+        if text.eq_ignore_ascii_case(".END") {
+            break;
+        }
+        lines.push((index + 1, text.to_string()));
+    }
+    lines
+}
+
+/// Parse a netlist into a circuit. Included files are found from the
+/// current directory.
+pub fn parse_netlist(text: &str) -> Result<Circuit, NetlistError> {
+    parse(text, None, Dialect::Esim)
+}
+
+/// Parse a netlist written for another simulator (see [Dialect]) into
+/// a circuit
+pub fn parse_netlist_dialect(text: &str, dialect: Dialect) -> Result<Circuit, NetlistError> {
+    parse(text, None, dialect)
+}
+
+/// Read a netlist from a file and parse it into a circuit. Included
+/// files are found from the directory of the file.
+pub fn parse_netlist_file(path: &Path) -> Result<Circuit, NetlistError> {
+    parse_netlist_file_dialect(path, Dialect::Esim)
+}
+
+/// Read a netlist written for another simulator (see [Dialect]) from
+/// a file and parse it into a circuit
+pub fn parse_netlist_file_dialect(path: &Path, dialect: Dialect) -> Result<Circuit, NetlistError> {
+    let text = fs::read_to_string(path).map_err(|error| {
+        NetlistError::new(
+            Some(path.to_path_buf()),
+            0,
+            format!("cannot read the netlist: {error}"),
+        )
+        .with_code(ErrorCode::Read)
+    })?;
+    parse(&text, Some(path), dialect)
+}
+
+/// Parse netlist text in a dialect, read from a file if it is
+pub(crate) fn parse(
+    text: &str,
+    file: Option<&Path>,
+    dialect: Dialect,
+) -> Result<Circuit, NetlistError> {
+    let (circuit, errors) = parse_collect(text, file, dialect)?;
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(circuit),
+    }
+}
+
+/// Parse netlist text in a dialect, going on past the lines with
+/// errors, and returning the circuit of the other lines with the
+/// errors, or the first error if the lines cannot be known (as in a
+/// subcircuit definition). In the format of this crate, a first line
+/// that is not a command and fails to parse is the title, with a
+/// warning if its first letter is that of a component (so that a
+/// broken first component is not dropped without notice).
+pub(crate) fn parse_collect(
+    text: &str,
+    file: Option<&Path>,
+    dialect: Dialect,
+) -> Result<(Circuit, Vec<NetlistError>), NetlistError> {
+    let parsed = parse_statements(text, file, dialect);
+    let first_line = |error: &&NetlistError| error.line == 1 && error.file.as_deref() == file;
+    let error = match &parsed {
+        Ok((_, errors)) => errors.iter().find(first_line),
+        Err(error) => Some(error).filter(first_line),
+    };
+    let Some(error) = error else {
+        return parsed;
+    };
+    let title = text.trim_start();
+    if dialect.has_title() || title.starts_with('.') {
+        return parsed;
+    }
+    let title = title.lines().next().unwrap_or_default().trim();
+    let component = title
+        .chars()
+        .next()
+        .is_some_and(|letter| COMPONENT_LETTERS.contains(letter.to_ascii_uppercase()));
+    let warning = component.then(|| {
+        let location = Location {
+            file: file.map(Path::to_path_buf),
+            line: 1,
+        };
+        format!(
+            "{location}: '{title}' is taken as the title, since it is not a component \
+             ({})",
+            error.message
+        )
+    });
+    let (mut circuit, errors) = parse_statements(&comment_title(text), file, dialect)?;
+    if let Some(warning) = warning {
+        let mut warnings = vec![warning];
+        warnings.extend_from_slice(circuit.warnings());
+        circuit.set_warnings(warnings);
+    }
+    Ok((circuit, errors))
+}
+
+/// The first letters of the names of components (see [parse_statements]
+/// and the subcircuit instances of [subcircuit])
+const COMPONENT_LETTERS: &str = "RCLDVIEGFHBSQMX";
+
+/// Parse netlist text as for [parse_collect], with its first line a
+/// statement unless the dialect has a title
+fn parse_statements(
+    text: &str,
+    file: Option<&Path>,
+    dialect: Dialect,
+) -> Result<(Circuit, Vec<NetlistError>), NetlistError> {
+    let mut circuit = Circuit::new();
+    let mut errors = Vec::new();
+    let warnings = RefCell::new(Vec::new());
+    // Names of the components and models whose lines have errors, whose
+    // uses are not reported again
+    let mut failed: Vec<String> = Vec::new();
+    let mut next_edge = 0;
+    let mut edge = || {
+        next_edge += 1;
+        next_edge - 1
+    };
+    // Current-controlled sources, with the name of the component that
+    // controls each, resolved once every component is known
+    let mut controlled = Vec::new();
+    // Values given by expressions, which follow the parameters
+    let mut expressions = Vec::new();
+    // Instances that use a model, with its name
+    let mut uses = Vec::new();
+    let (statements, parameters, nodes) = flatten(text, file, dialect)?;
+    // The models are defined first, since they can be used before
+    // their cards
+    for statement in &statements {
+        if !statement.name.eq_ignore_ascii_case(".MODEL") {
+            continue;
+        }
+        let line = Line {
+            location: &statement.location,
+            tokens: statement.tokens.iter().map(String::as_str).collect(),
+            warnings: &warnings,
+        };
+        match model_card(&line) {
+            Ok(Some((name, _))) if circuit.model(&name).is_some() => errors.push(
+                line.error(&format!("duplicate model name '{name}'"))
+                    .with_code(ErrorCode::DuplicateName)
+                    .at(line.tokens[1]),
+            ),
+            Ok(Some((name, model))) => circuit.add_model(&name, model),
+            Ok(None) => {}
+            Err(error) => {
+                failed.extend(line.tokens.get(1).map(|name| name.to_ascii_lowercase()));
+                errors.push(error);
+            }
+        }
+    }
+    let failed_models = failed.clone();
+    let mut add = |statement: &Statement| -> Result<(), NetlistError> {
+        let location = &statement.location;
+        let name = statement.name.as_str();
+        let line = Line {
+            location,
+            tokens: statement.tokens.iter().map(String::as_str).collect(),
+            warnings: &warnings,
+        };
+        if is_measure(name) || name.eq_ignore_ascii_case(".MODEL") {
+            return Ok(());
+        }
+        if IGNORED_COMMANDS
+            .iter()
+            .any(|c| name.eq_ignore_ascii_case(c))
+        {
+            // The warnings of analysis and options lines are kept with
+            // the circuit, and their errors left to parse_analyses and
+            // parse_options
+            directive::warn(&line);
+            return Ok(());
+        }
+        if name.eq_ignore_ascii_case(".TEMP") {
+            if line.tokens.len() > 2 {
+                return Err(line.error("only one temperature is supported"));
+            }
+            circuit.set_temperature(line.value(1)?);
+            return Ok(());
+        }
+        // The controlling component of a current-controlled source, and
+        // the model a component uses
+        let mut control = None;
+        let mut model_use = None;
+        let component = match statement.kind() {
+            'R' | 'C' => {
+                // A semiconductor resistor or capacitor has a model and
+                // a drawn length, and may have a width and temperature
+                let (line, parameters) = line.instance_parameters(3, &["L", "W", "TEMP"])?;
+                let parameter = |key: &str| {
+                    parameters
+                        .iter()
+                        .find(|(k, _)| k == key)
+                        .map(|(_, value)| *value)
+                };
+                if !parameters.is_empty() {
+                    line.end(4)?;
+                    let model = line.token(3, "model name")?;
+                    let Some((found, card)) =
+                        component_model(&mut circuit, &failed_models, &line, name, model)?
+                    else {
+                        return Ok(());
+                    };
+                    let length = parameter("L").ok_or_else(|| {
+                        line.error("missing L").with_code(ErrorCode::MissingToken)
+                    })?;
+                    let (term_1, term_2) = (line.node(1)?, line.node(2)?);
+                    let (width, temperature) = (parameter("W"), parameter("TEMP"));
+                    model_use = Some(found);
+                    match (statement.kind(), card) {
+                        ('R', Model::SemiconductorResistor(model)) => {
+                            Component::SemiconductorResistor {
+                                term_1,
+                                term_2,
+                                current_edge: None,
+                                model,
+                                length,
+                                width,
+                                temperature,
+                            }
+                        }
+                        ('C', Model::SemiconductorCapacitor(model)) => {
+                            Component::SemiconductorCapacitor {
+                                term_1,
+                                term_2,
+                                model,
+                                length,
+                                width,
+                                temperature,
+                            }
+                        }
+                        ('R', _) => {
+                            return Err(line
+                                .error(&format!("'{model}' is not a resistor model"))
+                                .at(model))
+                        }
+                        _ => {
+                            return Err(line
+                                .error(&format!("'{model}' is not a capacitor model"))
+                                .at(model))
+                        }
+                    }
+                } else if statement.kind() == 'R' {
+                    let group2 = match line.tokens.get(4) {
+                        None => false,
+                        Some(flag) if flag.eq_ignore_ascii_case("G2") => true,
+                        Some(flag) => return Err(line.unexpected(flag)),
+                    };
+                    line.end(5)?;
+                    Component::Resistor {
+                        term_1: line.node(1)?,
+                        term_2: line.node(2)?,
+                        current_edge: group2.then(&mut edge),
+                        resistance: line.value(3)?,
+                    }
+                } else {
+                    line.end(4)?;
+                    Component::Capacitor {
+                        term_1: line.node(1)?,
+                        term_2: line.node(2)?,
+                        capacitance: line.value(3)?,
+                    }
+                }
+            }
+            'L' => {
+                line.end(4)?;
+                Component::Inductor {
+                    term_1: line.node(1)?,
+                    term_2: line.node(2)?,
+                    current_edge: edge(),
+                    inductance: line.value(3)?,
+                }
+            }
+            'D' => {
+                // The temperature of the diode, if it has its own
+                let (line, parameters) = line.instance_parameters(3, &["TEMP"])?;
+                let temp = parameters.first().map(|(_, temp)| *temp);
+                line.end(4)?;
+                let (anode, cathode) = (line.node(1)?, line.node(2)?);
+                match line.tokens.get(3) {
+                    Some(model) => {
+                        let Some((found, card)) =
+                            component_model(&mut circuit, &failed_models, &line, name, model)?
+                        else {
+                            return Ok(());
+                        };
+                        model_use = Some(found);
+                        match card {
+                            Model::Diode(model) => Component::Diode {
+                                anode,
+                                cathode,
+                                model: DiodeModel { temp, ..model },
+                            },
+                            Model::Schottky(model) => Component::SchottkyDiode {
+                                anode,
+                                cathode,
+                                model: SchottkyModel { temp, ..model },
+                            },
+                            Model::TunnelDiode(model) => Component::TunnelDiode {
+                                anode,
+                                cathode,
+                                model: TunnelDiodeModel { temp, ..model },
+                            },
+                            _ => {
+                                return Err(line
+                                    .error(&format!("'{model}' is not a diode model"))
+                                    .at(model))
+                            }
+                        }
+                    }
+                    None => Component::Diode {
+                        anode,
+                        cathode,
+                        model: DiodeModel {
+                            temp,
+                            ..DiodeModel::default()
+                        },
+                    },
+                }
+            }
+            'V' => {
+                let (voltage, ac, waveform) = line.source()?;
+                Component::IndependentVoltageSource {
+                    term_pos: line.node(1)?,
+                    term_neg: line.node(2)?,
+                    current_edge: edge(),
+                    voltage,
+                    ac,
+                    waveform,
+                }
+            }
+            'I' => {
+                let (current, ac, waveform) = line.source()?;
+                Component::IndependentCurrentSource {
+                    term_pos: line.node(1)?,
+                    term_neg: line.node(2)?,
+                    current,
+                    ac,
+                    waveform,
+                }
+            }
+            'E' => {
+                line.end(6)?;
+                Component::VoltageControlledVoltageSource {
+                    term_pos: line.node(1)?,
+                    term_neg: line.node(2)?,
+                    ctrl_pos: line.node(3)?,
+                    ctrl_neg: line.node(4)?,
+                    current_edge: edge(),
+                    gain: line.value(5)?,
+                }
+            }
+            'G' => {
+                line.end(6)?;
+                Component::VoltageControlledCurrentSource {
+                    term_pos: line.node(1)?,
+                    term_neg: line.node(2)?,
+                    ctrl_pos: line.node(3)?,
+                    ctrl_neg: line.node(4)?,
+                    transconductance: line.value(5)?,
+                }
+            }
+            'F' => {
+                line.end(5)?;
+                control = Some(line.token(3, "controlling component")?.to_string());
+                Component::CurrentControlledCurrentSource {
+                    term_pos: line.node(1)?,
+                    term_neg: line.node(2)?,
+                    ctrl_edge: 0,
+                    gain: line.value(4)?,
+                }
+            }
+            'H' => {
+                line.end(5)?;
+                control = Some(line.token(3, "controlling component")?.to_string());
+                Component::CurrentControlledVoltageSource {
+                    term_pos: line.node(1)?,
+                    term_neg: line.node(2)?,
+                    ctrl_edge: 0,
+                    current_edge: edge(),
+                    transresistance: line.value(4)?,
+                }
+            }
+            'B' => {
+                line.end(4)?;
+                let token = line.token(3, "V= or I=")?;
+                let (quantity, value) = token.split_once('=').unwrap_or((token, ""));
+                let value = line
+                    .parse(value)
+                    .map_err(|error| line.error(&error.to_string()))?;
+                let (term_pos, term_neg) = (line.node(1)?, line.node(2)?);
+                match quantity.to_ascii_uppercase().as_str() {
+                    "V" => Component::IndependentVoltageSource {
+                        term_pos,
+                        term_neg,
+                        current_edge: edge(),
+                        voltage: value,
+                        ac: AcSpec::default(),
+                        waveform: None,
+                    },
+                    "I" => Component::IndependentCurrentSource {
+                        term_pos,
+                        term_neg,
+                        current: value,
+                        ac: AcSpec::default(),
+                        waveform: None,
+                    },
+                    _ => return Err(line.error(&format!("expected V= or I=, found '{token}'"))),
+                }
+            }
+            'S' => {
+                let file = touchstone_file(&statement.tokens);
+                line.end(file + 1)?;
+                let path = line.token(file, "TSTONEFILE=")?["TSTONEFILE=".len()..]
+                    .trim_matches(['"', '\'']);
+                let data = TouchstoneData::from_file(line.directory().join(path))
+                    .map_err(|error| line.error(&error.to_string()))?;
+                let nodes = (1..file)
+                    .map(|index| line.node(index))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let n = data.num_ports();
+                let reference = match nodes.len() {
+                    len if len == n => 0,
+                    len if len == n + 1 => nodes[n],
+                    _ => {
+                        return Err(line.error(&format!(
+                            "expected {n} nodes and an optional reference node"
+                        )))
+                    }
+                };
+                Component::NPort {
+                    ports: nodes[..n].iter().map(|node| (*node, reference)).collect(),
+                    data: Arc::new(data),
+                }
+            }
+            'Q' => {
+                // The temperature of the transistor, if it has its own
+                let (line, parameters) = line.instance_parameters(4, &["TEMP"])?;
+                let temp = parameters.first().map(|(_, temp)| *temp);
+                line.end(6)?;
+                let (collector, base, emitter) = (line.node(1)?, line.node(2)?, line.node(3)?);
+                let model = match line.tokens.get(5) {
+                    Some(model) => {
+                        line.node(4)?;
+                        line.warn(&format!(
+                            "the substrate of '{name}' is not modelled, so it is ignored"
+                        ));
+                        model
+                    }
+                    None => line.token(4, "model name")?,
+                };
+                let Some((found, card)) =
+                    component_model(&mut circuit, &failed_models, &line, name, model)?
+                else {
+                    return Ok(());
+                };
+                model_use = Some(found);
+                let Model::Bjt(card) = card else {
+                    return Err(line
+                        .error(&format!("'{model}' is not a bipolar transistor model"))
+                        .at(model));
+                };
+                Component::Bjt {
+                    collector,
+                    base,
+                    emitter,
+                    model: BjtModel { temp, ..card },
+                }
+            }
+            'M' => {
+                let (line, parameters) = line.instance_parameters(5, &["L", "W", "TEMP"])?;
+                let parameter = |key: &str| {
+                    parameters
+                        .iter()
+                        .find(|(name, _)| name == key)
+                        .map(|(_, value)| *value)
+                };
+                line.end(6)?;
+                let (drain, gate, source) = (line.node(1)?, line.node(2)?, line.node(3)?);
+                if line.node(4)? != source {
+                    line.warn(&format!(
+                        "the bulk of '{name}' is not modelled, so it is taken to be at the source"
+                    ));
+                }
+                let model = line.token(5, "model name")?;
+                let Some((found, card)) =
+                    component_model(&mut circuit, &failed_models, &line, name, model)?
+                else {
+                    return Ok(());
+                };
+                model_use = Some(found);
+                let Model::Mosfet(card) = card else {
+                    return Err(line
+                        .error(&format!("'{model}' is not a MOSFET model"))
+                        .at(model));
+                };
+                for (key, value) in &parameters {
+                    if key != "TEMP" && *value <= 0.0 {
+                        return Err(
+                            line.error(&format!("{} must be positive", key.to_ascii_lowercase()))
+                        );
+                    }
+                }
+                Component::Mosfet {
+                    drain,
+                    gate,
+                    source,
+                    model: MosfetModel {
+                        width: parameter("W").unwrap_or(card.width),
+                        length: parameter("L").unwrap_or(card.length),
+                        temp: parameter("TEMP"),
+                        ..card
+                    },
+                }
+            }
+            '.' => {
+                return Err(line
+                    .error(&format!("unsupported command '{name}'"))
+                    .with_code(ErrorCode::Unsupported)
+                    .at(line.tokens[0])
+                    .suggest(did_you_mean(name, COMMANDS.iter().copied())))
+            }
+            _ => {
+                return Err(line
+                    .error(&format!("unknown component type '{name}'"))
+                    .with_code(ErrorCode::UnknownComponent)
+                    .at(line.tokens[0])
+                    .suggest(Some(String::from(
+                        "the first letter of a name is the type of the component: \
+                         R, C, L, D, Q, M, V, I, E, G, F, H, B, S or X",
+                    ))))
+            }
+        };
+        if circuit.instances().iter().any(|i| i.name == name) {
+            return Err(line
+                .error(&format!("duplicate component name '{name}'"))
+                .with_code(ErrorCode::DuplicateName)
+                .at(line.tokens[0])
+                .suggest(Some(String::from("rename one of the components"))));
+        }
+        let value = match component {
+            Component::Resistor { .. }
+            | Component::Capacitor { .. }
+            | Component::Inductor { .. } => Some(3),
+            _ if statement.kind() == 'B' => Some(3),
+            Component::IndependentVoltageSource { waveform: None, .. }
+            | Component::IndependentCurrentSource { waveform: None, .. } => Some(
+                line.tokens
+                    .iter()
+                    .position(|t| t.eq_ignore_ascii_case("DC"))
+                    .map_or(3, |dc| dc + 1),
+            ),
+            _ => None,
+        };
+        if let Some((_, expression)) = statement
+            .expressions
+            .iter()
+            .find(|(position, _)| Some(*position) == value)
+        {
+            expressions.push((name.to_string(), expression.clone()));
+        }
+        if let Some(control) = control {
+            controlled.push((circuit.instances().len(), location.clone(), control));
+        }
+        uses.extend(model_use.map(|model| (name.to_string(), model)));
+        circuit.add_component(name, component);
+        Ok(())
+    };
+    for statement in &statements {
+        if let Err(error) = add(statement) {
+            failed.push(statement.name.to_ascii_lowercase());
+            errors.push(error);
+        }
+    }
+    for (instance, model) in uses {
+        circuit.use_model(&instance, &model);
+    }
+    circuit.set_parameters(parameters);
+    circuit.set_node_names(nodes);
+    for (name, expression) in expressions {
+        // The expressions have been evaluated while flattening
+        circuit.set_value_expression(&name, expression).unwrap();
+    }
+    for (index, location, control) in controlled {
+        // The controlling component's line has an error, which is
+        // reported already
+        if failed.contains(&control.to_ascii_lowercase()) {
+            continue;
+        }
+        let error = |message: String| location.error(message).at(&control);
+        let Some(instance) = circuit
+            .instances()
+            .iter()
+            .find(|i| i.name.eq_ignore_ascii_case(&control))
+        else {
+            let names = circuit.instances().iter().map(|i| i.name.as_str());
+            errors.push(
+                error(format!("no component named '{control}'"))
+                    .with_code(ErrorCode::UnknownName)
+                    .suggest(did_you_mean(&control, names)),
+            );
+            continue;
+        };
+        let Some(ctrl_edge) = instance.component.current_edge() else {
+            errors.push(error(format!("'{control}' has no current edge")));
+            continue;
+        };
+        *circuit.instances_mut()[index]
+            .component
+            .ctrl_edge_mut()
+            .unwrap() = ctrl_edge;
+    }
+    circuit.set_warnings(warnings.into_inner());
+    Ok((circuit, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dc::operating_point;
+
+    #[test]
+    fn first_line_is_a_title_if_it_is_not_a_statement() {
+        for title in ["RC low pass filter", "My amplifier", "Voltage divider"] {
+            let circuit = parse_netlist(&format!("{title}\nV1 in 0 1\nR1 in out 1k\n")).unwrap();
+            assert_eq!(circuit.instances().len(), 2, "{title}");
+        }
+        let circuit = parse_netlist("V1 in 0 1\nR1 in out 1k\n").unwrap();
+        assert_eq!(circuit.instances()[0].name, "V1");
+    }
+
+    /// The AC specification and whether there is a waveform of the
+    /// source of a one line netlist
+    fn source_ac(line: &str) -> (AcSpec, bool) {
+        let circuit = parse_netlist(&format!("{line}\nR1 1 0 1k\n")).unwrap();
+        match &circuit.instances()[0].component {
+            Component::IndependentVoltageSource { ac, waveform, .. } => (*ac, waveform.is_some()),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn ac_phase_is_optional() {
+        assert_eq!(source_ac("V1 1 0 AC 2 45"), (AcSpec::new(2.0, 45.0), false));
+        // A phase with a micro sign is still a phase
+        let (ac, _) = source_ac("V1 1 0 AC 2 45µ");
+        assert!((ac.phase - 45e-6).abs() < 1e-15);
+        assert_eq!(source_ac("V1 1 0 AC 2"), (AcSpec::new(2.0, 0.0), false));
+        assert_eq!(
+            source_ac("V1 1 0 AC 1 SIN(0 1 1k)"),
+            (AcSpec::new(1.0, 0.0), true)
+        );
+    }
+
+    #[test]
+    fn broken_first_component_is_not_dropped_silently() {
+        let circuit = parse_netlist("R1 in out\nV1 in 0 1\nR2 out 0 1k\n.op\n").unwrap();
+        assert_eq!(circuit.instances().len(), 2);
+        assert_eq!(circuit.warnings().len(), 1);
+        assert!(
+            circuit.warnings()[0].starts_with(
+                "netlist line 1: 'R1 in out' is taken as the title, since it is not a component"
+            ),
+            "{}",
+            circuit.warnings()[0]
+        );
+        // A title that cannot be a component is taken without a warning
+        let circuit = parse_netlist("2N2222 amplifier\nV1 in 0 1\nR1 in 0 1k\n").unwrap();
+        assert!(circuit.warnings().is_empty());
+    }
+
+    #[test]
+    fn pwl_files_are_found_next_to_the_netlist() {
+        let directory = std::env::temp_dir().join(format!("esim-{}-pwl", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("stim.csv"), "0,0\n1m,2\n").unwrap();
+        for source in [
+            "V1 1 0 PWL FILE=\"stim.csv\" INTERP=STEP",
+            "V1 1 0 PWL(FILE=\"stim.csv\" INTERP=STEP)",
+        ] {
+            let path = directory.join("deck.cir");
+            fs::write(&path, format!("{source}\nR1 1 0 1k\n")).unwrap();
+            let circuit = parse_netlist_file(&path).unwrap();
+            match &circuit.instances()[0].component {
+                Component::IndependentVoltageSource {
+                    waveform: Some(Waveform::PwlFile(file)),
+                    ..
+                } => {
+                    assert_eq!(file.path(), directory.join("stim.csv"), "{source}");
+                    // The value steps at the points of the file
+                    assert_eq!(file.value(0.5e-3), 0.0, "{source}");
+                }
+                _ => panic!("{source}: expected a PWL file"),
+            }
+        }
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn errors_after_a_title_keep_their_lines() {
+        let error = parse_netlist("Divider\nV1 in 0 1\nR1 in out\n").unwrap_err();
+        assert_eq!(error.line, 3);
+        let error = parse_netlist(".bogus\nV1 in 0 1\n").unwrap_err();
+        assert_eq!(error.line, 1);
+    }
+
+    #[test]
+    fn warnings_are_kept_with_the_circuit() {
+        let circuit = parse_netlist(
+            "V1 1 0 1\nR1 1 0 4k7\n.MODEL j1 NJF(BETA=1m)\n.MODEL d1 D(IS=1e-14 CJO=2p)\n\
+             .OPTIONS NOPAGE RELTOL=1e-4\n.TRAN 1µ 1m\n",
+        )
+        .unwrap();
+        assert_eq!(
+            circuit.warnings(),
+            [
+                "netlist line 3: model 'j1' of type 'NJF' is not supported, so it is ignored",
+                "netlist line 4: parameter 'CJO' of model 'd1' is not supported, so it is ignored",
+                "netlist line 2: value '4k7': expanded 4k7 to 4.7k",
+                "netlist line 5: option 'NOPAGE' is not supported, so it is ignored",
+                "netlist line 6: value '1µ': replaced micro sign with 'u'",
+            ]
+        );
+        assert!(parse_netlist("V1 1 0 1\nR1 1 0 1k\n")
+            .unwrap()
+            .warnings()
+            .is_empty());
+    }
+
+    #[test]
+    fn semiconductor_resistors_are_at_their_own_or_the_circuit_temperature() {
+        // 1k at 27 degrees, and 2k at 127
+        let out = |temp: &str| {
+            let circuit = parse_netlist(&format!(
+                "V1 in 0 1\nR1 in out 1k\nR2 out 0 rmod L=10u W=1u {temp}\n\
+                 .MODEL rmod R(RSH=100 TC1=0.01)\n.TEMP 127\n"
+            ))
+            .unwrap();
+            operating_point(&circuit).node_voltage("out").unwrap()
+        };
+        assert!((out("") - 2.0 / 3.0).abs() < 1e-9);
+        assert!((out("TEMP=27") - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn diodes_of_schottky_and_tunnel_models_have_their_temperatures() {
+        let circuit = parse_netlist(
+            "V1 a 0 0.2\nD1 a 0 sk TEMP=100\nD2 a 0 td\nC1 a 0 cmod L=20u\n\
+             .MODEL sk SCHOTTKY(PHI_B=0.5)\n.MODEL td TUNNEL(IP=2m)\n.MODEL cmod C(CJ=1m)\n",
+        )
+        .unwrap();
+        let components: Vec<_> = circuit.instances().iter().map(|i| &i.component).collect();
+        assert!(matches!(
+            components[1],
+            Component::SchottkyDiode { model, .. } if model.phi_b == 0.5 && model.temp == Some(100.0)
+        ));
+        assert!(matches!(
+            components[2],
+            Component::TunnelDiode { model, .. } if model.ip == 2e-3 && model.temp.is_none()
+        ));
+        assert!(matches!(
+            components[3],
+            Component::SemiconductorCapacitor { length, temperature: None, .. } if (length - 20e-6).abs() < 1e-18
+        ));
+        let error = parse_netlist("V1 a 0 1\nR1 a 0 sk L=1u\n.MODEL sk SCHOTTKY\n").unwrap_err();
+        assert!(
+            error.to_string().contains("'sk' is not a resistor model"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn transistors_use_their_models_and_are_written_back() {
+        let circuit = parse_netlist(
+            "V1 vcc 0 5\nRB vcc b 430k\nRC vcc c 1k\nQ1 c b 0 qmod\n\
+             VG g 0 3\nRD vcc d 500\nM1 d g 0 0 nmod L=10u W=20u TEMP=27\n\
+             .MODEL qmod NPN(BF=100)\n.MODEL nmod NMOS(LEVEL=1 VTO=1 KP=1m)\n",
+        )
+        .unwrap();
+        assert!(circuit.warnings().is_empty(), "{:?}", circuit.warnings());
+        let check = |circuit: &Circuit| {
+            let solution = operating_point(circuit);
+            let base = (5.0 - solution.node_voltage("b").unwrap()) / 430e3;
+            let collector = (5.0 - solution.node_voltage("c").unwrap()) / 1e3;
+            assert!((collector / base / 100.0 - 1.0).abs() < 1e-3);
+            // 1m * 20u / 10u / 2 * (3 - 1)^2 = 4 mA through 500 ohms
+            assert!((solution.node_voltage("d").unwrap() - 3.0).abs() < 1e-6);
+        };
+        check(&circuit);
+        let written = write_netlist(&circuit).unwrap();
+        assert!(written.contains("M1 d g 0 0 nmod L="), "{written}");
+        check(&parse_netlist(&written).unwrap());
+
+        let circuit = parse_netlist("V1 d 0 1\nM1 d d 0 d nmod\n.MODEL nmod NMOS\n").unwrap();
+        assert_eq!(
+            circuit.warnings(),
+            ["netlist line 2: the bulk of 'M1' is not modelled, so it is taken to be at the source"]
+        );
+        let circuit = parse_netlist("V1 c 0 1\nQ1 c c 0 c qmod\n.MODEL qmod NPN\n").unwrap();
+        assert_eq!(
+            circuit.warnings(),
+            ["netlist line 2: the substrate of 'Q1' is not modelled, so it is ignored"]
+        );
+        let error = parse_netlist("V1 c 0 1\nQ1 c c 0 dmod\n.MODEL dmod D\n").unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("'dmod' is not a bipolar transistor model"),
+            "{error}"
+        );
+    }
+}
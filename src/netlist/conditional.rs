@@ -0,0 +1,135 @@
+//! Conditional sections
+//!
+//! Lines between `.IF`, `.ELSEIF`, `.ELSE` and `.ENDIF` lines are kept
+//! or left out by conditions on the parameters, so that a test bench
+//! can switch between configurations with one parameter:
+//!
+//! ```text
+//! .PARAM load=1
+//! .IF (load == 1)
+//! RL out 0 1k
+//! .ELSEIF (load == 2)
+//! RL out 0 10k
+//! .ELSE
+//! CL out 0 1n
+//! .ENDIF
+//! ```
+//!
+//! The lines after the first condition that is true (not 0) are kept,
+//! or those after `.ELSE` if none is, up to the next `.ELSEIF`, `.ELSE`
+//! or `.ENDIF`. Conditions are expressions (see [crate::expression]),
+//! with or without parentheses, of the parameters of the netlist
+//! defined by `.PARAM` lines above them (outside subcircuits, and in
+//! sections that are kept). Sections can be nested, and can hold any
+//! lines, including `.PARAM` lines and subcircuit definitions. A
+//! condition inside a subcircuit is in terms of the parameters of the
+//! netlist, not those of the subcircuit. The sections are chosen when
+//! the netlist is parsed, so setting a parameter of the circuit later
+//! does not change them.
+
+use super::subcircuit::assignments;
+use super::{tokenize, Location, NetlistError};
+use crate::expression::{parse_expression, Parameters};
+
+/// A section being read
+struct Section {
+    /// Where its `.IF` line is
+    location: Location,
+    /// Whether the lines are kept
+    kept: bool,
+    /// Whether the lines of an earlier branch were kept
+    done: bool,
+    /// Whether an `.ELSE` line has been read
+    otherwise: bool,
+}
+
+/// The value of the condition of a line, as a truth
+fn condition(
+    location: &Location,
+    command: &str,
+    text: &str,
+    parameters: &Parameters,
+) -> Result<bool, NetlistError> {
+    if text.is_empty() {
+        return Err(location.error(format!("missing condition of {command}")));
+    }
+    let value = parse_expression(text)
+        .and_then(|expression| parameters.evaluate(&expression))
+        .map_err(|error| location.error(format!("condition '{text}': {error}")))?;
+    Ok(value != 0.0)
+}
+
+/// The lines of a netlist with those of the sections that are not kept
+/// left out, and without the lines of the conditions
+pub(crate) fn select(
+    lines: Vec<(Location, String)>,
+) -> Result<Vec<(Location, String)>, NetlistError> {
+    let mut parameters = Parameters::new();
+    let mut sections: Vec<Section> = Vec::new();
+    // Depth of the subcircuit definitions being read
+    let mut depth = 0usize;
+    let mut selected = Vec::new();
+    for (location, text) in lines {
+        // The command, which a condition in parentheses can follow
+        // without a space
+        let line = text.trim_start();
+        let end = line
+            .find(|c: char| c.is_whitespace() || c == '(')
+            .unwrap_or(line.len());
+        let command = line[..end].to_ascii_uppercase();
+        let rest = line[end..].trim();
+        let kept = sections.iter().all(|section| section.kept);
+        match command.as_str() {
+            ".IF" => {
+                let kept = kept && condition(&location, &command, rest, &parameters)?;
+                sections.push(Section {
+                    location,
+                    kept,
+                    done: kept,
+                    otherwise: false,
+                });
+                continue;
+            }
+            command @ (".ELSEIF" | ".ELSE" | ".ENDIF") => {
+                let outer = sections.iter().rev().skip(1).all(|section| section.kept);
+                let section = sections
+                    .last_mut()
+                    .ok_or_else(|| location.error(format!("{command} without .IF")))?;
+                if section.otherwise && command != ".ENDIF" {
+                    return Err(location.error(format!("{command} after .ELSE")));
+                }
+                match command {
+                    ".ELSEIF" => {
+                        section.kept = outer
+                            && !section.done
+                            && condition(&location, command, rest, &parameters)?;
+                        section.done |= section.kept;
+                    }
+                    ".ELSE" => {
+                        section.kept = outer && !section.done;
+                        section.otherwise = true;
+                    }
+                    _ => {
+                        sections.pop();
+                    }
+                }
+                continue;
+            }
+            _ if !kept => continue,
+            ".SUBCKT" => depth += 1,
+            ".ENDS" => depth = depth.saturating_sub(1),
+            ".PARAM" if depth == 0 => {
+                let tokens: Vec<String> = tokenize(rest).into_iter().map(String::from).collect();
+                for (name, expression) in assignments(&location, &tokens)? {
+                    parameters.define(&name, expression);
+                }
+            }
+            _ => {}
+        }
+        selected.push((location, text));
+    }
+    if let Some(section) = sections.last() {
+        return Err(section.location.error(String::from(".IF without .ENDIF")));
+    }
+    Ok(selected)
+}
@@ -0,0 +1,320 @@
+//! Netlist diagnostics
+//!
+//! Parsing a netlist stops at its first error, but checking it (with
+//! [check_netlist] or [check_netlist_file]) goes on to the end, so
+//! that every line that cannot be parsed is reported at once. Each
+//! [Diagnostic] has an error code (see [ErrorCode]), the span of the
+//! source it is at, a message and, where there is a likely fix, a
+//! suggestion, and the [Diagnostics] of a netlist print as a table:
+//!
+//! ```text
+//! LOCATION             CODE  MESSAGE                     SUGGESTION
+//! amp.cir:3:9-12       E006  invalid value '4.7q'        write a number with ...
+//! amp.cir:5:1-5        E008  unsupported command '.TRN'  did you mean '.TRAN'?
+//! ```
+//!
+//! Errors in the structure of the netlist (in its subcircuits,
+//! included files and conditional sections) still stop the check,
+//! since the lines after them cannot be known, as do those in the
+//! `.PARAM` lines. A line that uses a component or model whose own
+//! line has an error is not reported again. Spans are in characters
+//! (from 1) of the line of the file the error is in, and cover the
+//! whole line if the error is not at one token of it, or if the token
+//! is not in the text of the line (as for the nodes and values of the
+//! lines of subcircuits).
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{parse_collect, Dialect, NetlistError};
+
+/// What kind of error a netlist has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// A line that is not valid in another way
+    Invalid,
+    /// The netlist, or a file it uses, cannot be read
+    Read,
+    /// A line that is missing a token, such as a node or value
+    MissingToken,
+    /// A token that is not expected where it is
+    UnexpectedToken,
+    InvalidNode,
+    InvalidValue,
+    /// A line whose first letter is not a component type
+    UnknownComponent,
+    /// A command or component that is not supported
+    Unsupported,
+    /// A name of a model, subcircuit or component that is not defined
+    UnknownName,
+    /// A name that is defined twice
+    DuplicateName,
+}
+
+impl ErrorCode {
+    /// The code as text, as in `E006`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Invalid => "E001",
+            Self::Read => "E002",
+            Self::MissingToken => "E003",
+            Self::UnexpectedToken => "E004",
+            Self::InvalidNode => "E005",
+            Self::InvalidValue => "E006",
+            Self::UnknownComponent => "E007",
+            Self::Unsupported => "E008",
+            Self::UnknownName => "E009",
+            Self::DuplicateName => "E010",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// Where a diagnostic is in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// Line number (from 1), which is 0 if the error is not at a line
+    pub line: usize,
+    /// First column (from 1)
+    pub start: usize,
+    /// Column after the last
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: ErrorCode,
+    /// The included file the span is in, if it is not in the netlist
+    /// itself
+    pub file: Option<PathBuf>,
+    pub span: Span,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    /// The location of the diagnostic, as in `amp.cir:3:9-12`
+    pub fn location(&self) -> String {
+        let file = match &self.file {
+            Some(file) => file.display().to_string(),
+            None => String::from("netlist"),
+        };
+        let Span { line, start, end } = self.span;
+        if line == 0 {
+            file
+        } else {
+            format!("{file}:{line}:{start}-{end}")
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} {}", self.location(), self.code, self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " ({suggestion})")?;
+        }
+        Ok(())
+    }
+}
+
+/// The diagnostics of a netlist, in the order of its lines (with the
+/// uses of components and models that are resolved after the lines
+/// last)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diagnostics {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    /// Whether the netlist has no errors
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.diagnostics.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter()
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    /// The diagnostics as a table, with a header row, in columns as
+    /// wide as their widest entry
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<[String; 4]> = self
+            .iter()
+            .map(|diagnostic| {
+                [
+                    diagnostic.location(),
+                    diagnostic.code.to_string(),
+                    diagnostic.message.clone(),
+                    diagnostic.suggestion.clone().unwrap_or_default(),
+                ]
+            })
+            .collect();
+        let header = ["LOCATION", "CODE", "MESSAGE", "SUGGESTION"].map(String::from);
+        let mut widths = [0; 4];
+        for row in std::iter::once(&header).chain(&rows) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        for row in std::iter::once(&header).chain(&rows) {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Number of single-character edits between two names, ignoring case
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, y) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(x != y);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The candidate closest to a name, if one is close enough (within
+/// two edits, and less than half of the name) to be a likely misspelling
+pub(crate) fn closest<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2 && 2 * distance < name.chars().count())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// A suggestion of the candidate closest to a name, if there is one
+pub(crate) fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<String> {
+    closest(name, candidates).map(|candidate| format!("did you mean '{candidate}'?"))
+}
+
+/// The span of an error in the text of its file
+fn span(error: &NetlistError, text: &str) -> Span {
+    let lines: Vec<&str> = text.lines().collect();
+    let Some(first) = error.line.checked_sub(1).filter(|k| *k < lines.len()) else {
+        return Span {
+            line: error.line,
+            start: 1,
+            end: 1,
+        };
+    };
+    let whole = |line: usize| {
+        let text = lines[line - 1];
+        let indent = text.chars().take_while(|c| c.is_whitespace()).count();
+        Span {
+            line,
+            start: indent + 1,
+            end: text.trim_end().chars().count() + 1,
+        }
+    };
+    let Some(token) = &error.token else {
+        return whole(error.line);
+    };
+    // The token can be on a continuation line of the line
+    let continued = lines[first + 1..]
+        .iter()
+        .take_while(|line| line.trim_start().starts_with('+'))
+        .count();
+    for (index, line) in lines[first..=first + continued].iter().enumerate() {
+        let lower = line.to_lowercase();
+        if let Some(found) = lower.find(&token.to_lowercase()) {
+            let start = lower[..found].chars().count() + 1;
+            return Span {
+                line: error.line + index,
+                start,
+                end: start + token.chars().count(),
+            };
+        }
+    }
+    whole(error.line)
+}
+
+/// The diagnostic of an error, with the text of the netlist it is in
+/// (if it is not in an included file) and the file of the netlist
+fn diagnostic(error: NetlistError, text: &str, file: Option<&Path>) -> Diagnostic {
+    let span = match error.file.as_deref() {
+        Some(path) if Some(path) != file => match fs::read_to_string(path) {
+            Ok(text) => span(&error, &text),
+            Err(_) => span(&error, ""),
+        },
+        _ => span(&error, text),
+    };
+    Diagnostic {
+        code: error.code,
+        file: error.file.or(file.map(Path::to_path_buf)),
+        span,
+        message: error.message,
+        suggestion: error.suggestion,
+    }
+}
+
+fn check(text: &str, file: Option<&Path>, dialect: Dialect) -> Diagnostics {
+    let errors = match parse_collect(text, file, dialect) {
+        Ok((_, errors)) => errors,
+        Err(error) => vec![error],
+    };
+    Diagnostics {
+        diagnostics: errors
+            .into_iter()
+            .map(|error| diagnostic(error, text, file))
+            .collect(),
+    }
+}
+
+/// Check a netlist in a dialect, returning the diagnostics of all the
+/// errors that parsing it would stop at the first of
+pub fn check_netlist(text: &str, dialect: Dialect) -> Diagnostics {
+    check(text, None, dialect)
+}
+
+/// Read a netlist from a file and check it, as for [check_netlist]
+pub fn check_netlist_file(path: &Path, dialect: Dialect) -> Diagnostics {
+    match fs::read_to_string(path) {
+        Ok(text) => check(&text, Some(path), dialect),
+        Err(error) => Diagnostics {
+            diagnostics: vec![Diagnostic {
+                code: ErrorCode::Read,
+                file: Some(path.to_path_buf()),
+                span: Span {
+                    line: 0,
+                    start: 1,
+                    end: 1,
+                },
+                message: format!("cannot read the netlist: {error}"),
+                suggestion: None,
+            }],
+        },
+    }
+}
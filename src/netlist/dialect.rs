@@ -0,0 +1,140 @@
+//! Netlist dialects
+//!
+//! Netlists written for other simulators differ from the format of this
+//! crate in small ways, which are translated line by line before the
+//! lines are parsed, so the same deck can be run here and there. In the
+//! ngspice and LTspice dialects:
+//!
+//! - the first line of the netlist is its title, and is ignored (the
+//!   first line of an included file is not)
+//! - an expression can be in single quotes, as in `R1 1 2 '2*r'`, as
+//!   well as in braces
+//! - the micro sign (`µ`) is the `u` suffix, without a warning
+//! - the expression of a behavioral source can be written as `V=expr`
+//!   (running to the end of the line, as in LTspice) or `V = 'expr'`,
+//!   and is put in braces
+//! - `.control` to `.endc` blocks (ngspice) and `.backanno` lines
+//!   (LTspice) are left out
+//!
+//! Implicit ends are the same in all the dialects: a netlist without an
+//! `.END` line ends with its last line.
+
+/// The simulator a netlist is written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    /// The format of this crate, where the first line is a title only
+    /// if it is not a statement (see [crate::netlist])
+    #[default]
+    Esim,
+    Ngspice,
+    Ltspice,
+}
+
+impl Dialect {
+    /// Whether the first line of a netlist is its title
+    pub(crate) fn has_title(self) -> bool {
+        self != Self::Esim
+    }
+
+    /// Translate the lines of a netlist (see [super::logical_lines])
+    /// into the format of this crate
+    pub(crate) fn translate(self, lines: Vec<(usize, String)>) -> Vec<(usize, String)> {
+        if self == Self::Esim {
+            return lines;
+        }
+        let mut out = Vec::new();
+        let mut control = false;
+        for (number, line) in lines {
+            let command = line
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_ascii_uppercase();
+            match command.as_str() {
+                ".CONTROL" => control = true,
+                ".ENDC" => control = false,
+                ".BACKANNO" => {}
+                _ if control => {}
+                // The quotes around paths are not expressions
+                ".INCLUDE" | ".INC" | ".LIB" => out.push((number, line)),
+                _ => {
+                    let line = line.replace(['µ', 'μ'], "u");
+                    let line = if command.starts_with('B') {
+                        behavioral(&line)
+                    } else {
+                        line
+                    };
+                    out.push((number, quoted_expressions(&line)));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// A line with each expression in single quotes put in braces
+fn quoted_expressions(line: &str) -> String {
+    let mut out = String::new();
+    let mut open = false;
+    for c in line.chars() {
+        match c {
+            '\'' => {
+                out.push(if open { '}' } else { '{' });
+                open = !open;
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A behavioral source line with its expression as `V={expr}` (or
+/// `I={expr}`), or the line as it is if it is not in a form with an
+/// expression
+fn behavioral(line: &str) -> String {
+    let mut rest = line.trim();
+    let mut tokens = Vec::new();
+    for _ in 0..3 {
+        let Some((token, after)) = rest.split_once(char::is_whitespace) else {
+            return line.to_string();
+        };
+        tokens.push(token);
+        rest = after.trim_start();
+    }
+    let Some((quantity, expression)) = rest.split_once('=') else {
+        return line.to_string();
+    };
+    let quantity = quantity.trim();
+    if !quantity.eq_ignore_ascii_case("V") && !quantity.eq_ignore_ascii_case("I") {
+        return line.to_string();
+    }
+    let expression = expression.trim();
+    let expression = [('{', '}'), ('\'', '\'')]
+        .iter()
+        .find_map(|(open, close)| {
+            expression
+                .strip_prefix(*open)
+                .and_then(|e| e.strip_suffix(*close))
+        })
+        .unwrap_or(expression);
+    format!("{} {quantity}={{{expression}}}", tokens.join(" "))
+}
+
+/// The text of a netlist with its title line (if the dialect has one)
+/// made a comment, keeping the numbers of the other lines
+pub(crate) fn without_title(text: &str, dialect: Dialect) -> String {
+    if dialect.has_title() {
+        comment_title(text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// The text with its first line (its title) replaced by a comment, so
+/// that the lines keep their numbers
+pub(crate) fn comment_title(text: &str) -> String {
+    match text.split_once('\n') {
+        Some((_, rest)) => format!("*\n{rest}"),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,226 @@
+//! Analysis directives
+//!
+//! The analyses to run on a netlist are given by its `.OP`, `.DC`,
+//! `.AC` and `.TRAN` lines, which are ignored when the netlist is
+//! parsed into a circuit, and read here instead:
+//!
+//! ```text
+//! .OP
+//! .DC V1 0 5 0.1
+//! .AC DEC 10 1 1meg
+//! .TRAN 1u 1m
+//! ```
+//!
+//! A DC sweep sweeps one component (by name) from a start to a stop
+//! value in steps. An AC analysis has a number of points per decade
+//! (`DEC`) or octave (`OCT`), or in all (`LIN`), from a start to a
+//! stop frequency. A transient analysis has a time step and a stop
+//! time, which can be followed by a start time of zero, a largest time
+//! step (which is ignored, since the time step is fixed) and `UIC`, to
+//! start from the initial conditions.
+//!
+//! The settings of the solvers are given by `.OPTIONS` (or `.OPTION`)
+//! lines of `name=value` pairs, read into [SimOptions]. Options that
+//! are not supported are ignored, as are flags without a value (such
+//! as `NOPAGE`), so that netlists written for SPICE can be parsed; the
+//! circuit parsed from the netlist has a warning for each (see
+//! [Circuit::warnings](crate::circuit::Circuit::warnings)).
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+
+use super::include::source_lines;
+use super::{tokenize, Dialect, ErrorCode, Line, NetlistError};
+use crate::ac::{AcSweep, Variation};
+use crate::options::SimOptions;
+use crate::sweep::SweepRange;
+use crate::transient::TransientOptions;
+use crate::watch::Analysis;
+
+/// The analysis of a directive line, or None if it is not one
+fn analysis(line: &Line) -> Result<Option<Analysis>, NetlistError> {
+    let analysis = match line.tokens[0].to_ascii_uppercase().as_str() {
+        ".OP" => {
+            line.end(1)?;
+            Analysis::OperatingPoint
+        }
+        ".DC" => {
+            let instance = line.token(1, "swept component")?;
+            let (start, stop, step) = (line.value(2)?, line.value(3)?, line.value(4)?);
+            if line.tokens.len() > 5 {
+                return Err(line.error("only one swept component is supported"));
+            }
+            if step == 0.0 || (stop - start) * step < 0.0 {
+                return Err(line.error("the step must go from the start to the stop value"));
+            }
+            Analysis::DcSweep {
+                instance: instance.to_string(),
+                range: SweepRange::new(start, stop, step),
+            }
+        }
+        ".AC" => {
+            let variation = match line
+                .token(1, "DEC, OCT or LIN")?
+                .to_ascii_uppercase()
+                .as_str()
+            {
+                "DEC" => Variation::Decade,
+                "OCT" => Variation::Octave,
+                "LIN" => Variation::Linear,
+                token => {
+                    return Err(line.error(&format!("expected DEC, OCT or LIN, not '{token}'")))
+                }
+            };
+            let points = line.value(2)?;
+            if points < 1.0 || points.fract() != 0.0 {
+                return Err(line.error("the number of points must be a positive integer"));
+            }
+            let (start, stop) = (line.value(3)?, line.value(4)?);
+            if start <= 0.0 || stop < start {
+                return Err(line.error("the frequencies must be positive and increasing"));
+            }
+            line.end(5)?;
+            Analysis::Ac(AcSweep::new(variation, points as usize, start, stop))
+        }
+        ".TRAN" => {
+            let (time_step, stop_time) = (line.value(1)?, line.value(2)?);
+            if time_step <= 0.0 || stop_time <= 0.0 {
+                return Err(line.error("the time step and stop time must be positive"));
+            }
+            let mut options = TransientOptions::new(time_step, stop_time);
+            for (index, token) in line.tokens.iter().enumerate().skip(3) {
+                if token.eq_ignore_ascii_case("UIC") {
+                    options.use_initial_conditions = true;
+                } else if index > 4 {
+                    return Err(line.unexpected(token));
+                } else if index == 3 && line.value(index)? != 0.0 {
+                    return Err(line.error("a start time is not supported"));
+                } else {
+                    // The largest time step
+                    line.value(index)?;
+                }
+            }
+            Analysis::Transient(options)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(analysis))
+}
+
+/// Set the options of an options line, or do nothing if it is not one
+fn set_options(line: &Line, options: &mut SimOptions) -> Result<(), NetlistError> {
+    let name = line.tokens[0].to_ascii_uppercase();
+    if name != ".OPTIONS" && name != ".OPTION" {
+        return Ok(());
+    }
+    // Spaces around the equals signs are allowed
+    let assignments = line.tokens[1..]
+        .join(" ")
+        .split('=')
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("=");
+    for word in assignments.split_whitespace() {
+        let Some((option, value)) = word.split_once('=') else {
+            line.warn(&format!(
+                "option '{word}' is not supported, so it is ignored"
+            ));
+            continue;
+        };
+        let supported = options
+            .set(option, value)
+            .map_err(|error| line.error(&error.to_string()))?;
+        if !supported {
+            line.warn(&format!(
+                "option '{option}' is not supported, so it is ignored"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Add the warnings of an analysis or options line to those of its
+/// netlist, ignoring its errors, which are found when the directives
+/// are parsed
+pub(super) fn warn(line: &Line) {
+    let _ = analysis(line);
+    let _ = set_options(line, &mut SimOptions::default());
+}
+
+/// Parse the analysis directives of a netlist in a dialect, read from
+/// a file if it is, in the order they appear
+fn analyses(
+    text: &str,
+    file: Option<&Path>,
+    dialect: Dialect,
+) -> Result<Vec<Analysis>, NetlistError> {
+    let mut analyses = Vec::new();
+    // The warnings are kept with the circuit instead (see [warn])
+    let warnings = RefCell::new(Vec::new());
+    for (location, text) in source_lines(text, file, dialect)? {
+        let line = Line {
+            location: &location,
+            tokens: tokenize(&text),
+            warnings: &warnings,
+        };
+        if let Some(analysis) = analysis(&line)? {
+            analyses.push(analysis);
+        }
+    }
+    Ok(analyses)
+}
+
+/// Parse the analysis directives of a netlist in a dialect, ignoring
+/// the other lines
+pub fn parse_analyses(text: &str, dialect: Dialect) -> Result<Vec<Analysis>, NetlistError> {
+    analyses(text, None, dialect)
+}
+
+/// Read a netlist from a file and parse its analysis directives, as
+/// for [parse_analyses]
+pub fn parse_analyses_file(path: &Path, dialect: Dialect) -> Result<Vec<Analysis>, NetlistError> {
+    let text = read(path)?;
+    analyses(&text, Some(path), dialect)
+}
+
+fn read(path: &Path) -> Result<String, NetlistError> {
+    fs::read_to_string(path).map_err(|error| {
+        NetlistError::new(
+            Some(path.to_path_buf()),
+            0,
+            format!("cannot read the netlist: {error}"),
+        )
+        .with_code(ErrorCode::Read)
+    })
+}
+
+/// Parse the options lines of a netlist in a dialect into the options,
+/// starting from the defaults, with later lines setting an option again
+fn options(text: &str, file: Option<&Path>, dialect: Dialect) -> Result<SimOptions, NetlistError> {
+    let mut options = SimOptions::default();
+    // The warnings are kept with the circuit instead (see [warn])
+    let warnings = RefCell::new(Vec::new());
+    for (location, text) in source_lines(text, file, dialect)? {
+        let line = Line {
+            location: &location,
+            tokens: tokenize(&text),
+            warnings: &warnings,
+        };
+        set_options(&line, &mut options)?;
+    }
+    Ok(options)
+}
+
+/// Parse the `.OPTIONS` lines of a netlist in a dialect, ignoring the
+/// other lines
+pub fn parse_options(text: &str, dialect: Dialect) -> Result<SimOptions, NetlistError> {
+    options(text, None, dialect)
+}
+
+/// Read a netlist from a file and parse its `.OPTIONS` lines, as for
+/// [parse_options]
+pub fn parse_options_file(path: &Path, dialect: Dialect) -> Result<SimOptions, NetlistError> {
+    let text = read(path)?;
+    options(&text, Some(path), dialect)
+}
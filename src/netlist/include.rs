@@ -0,0 +1,166 @@
+//! Included files and libraries
+//!
+//! An `.INCLUDE` (or `.INC`) line is replaced by the lines of a file,
+//! and a `.LIB` line giving a file and a section by the lines of that
+//! section of the file, which are between `.LIB` (giving the name of
+//! the section) and `.ENDL` lines, so that vendor model files can be
+//! used as they are:
+//!
+//! ```text
+//! .INCLUDE "models/opamps.inc"
+//! .LIB 'diodes.lib' typical
+//! ```
+//!
+//! The sections of a file are left out when the whole file is
+//! included, as are the lines outside the section when a section is.
+//! A relative path is from the directory of the file with the line in
+//! it, or the current directory for a netlist that is not read from a
+//! file. Included files can include others, but not (directly or
+//! through others) themselves. Section names are not case sensitive.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::conditional::select;
+use super::dialect::{without_title, Dialect};
+use super::{logical_lines, tokenize, ErrorCode, Location, NetlistError};
+
+/// The path in a token, without quotes
+fn unquote(token: &str) -> &str {
+    token.trim_matches(['"', '\''])
+}
+
+/// Files (with the section, if one is selected) being read, from the
+/// netlist itself
+type Stack = Vec<(PathBuf, Option<String>)>;
+
+/// Read the lines of a file, or of a section of it, into the lines of
+/// a netlist, from an include line
+fn include(
+    location: &Location,
+    path: &str,
+    section: Option<&str>,
+    dialect: Dialect,
+    stack: &mut Stack,
+    lines: &mut Vec<(Location, String)>,
+) -> Result<(), NetlistError> {
+    let directory = location
+        .file
+        .as_deref()
+        .and_then(Path::parent)
+        .unwrap_or(Path::new(""));
+    let path = directory.join(unquote(path));
+    let read_error = |error: std::io::Error| {
+        location
+            .error(format!("cannot read '{}': {error}", path.display()))
+            .with_code(ErrorCode::Read)
+    };
+    let key = (
+        fs::canonicalize(&path).map_err(read_error)?,
+        section.map(str::to_ascii_lowercase),
+    );
+    if stack.contains(&key) {
+        return Err(location.error(format!("'{}' includes itself", path.display())));
+    }
+    let text = fs::read_to_string(&path).map_err(read_error)?;
+    stack.push(key);
+    let found = read(&text, Some(&path), section, dialect, stack, lines)?;
+    stack.pop();
+    match section {
+        Some(section) if !found => {
+            Err(location.error(format!("no section '{section}' in '{}'", path.display())))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Add the lines of netlist text (from a file, if it is read from one),
+/// or of a section of it, in a dialect, to the lines of a netlist,
+/// returning whether the section was found
+fn read(
+    text: &str,
+    file: Option<&Path>,
+    section: Option<&str>,
+    dialect: Dialect,
+    stack: &mut Stack,
+    lines: &mut Vec<(Location, String)>,
+) -> Result<bool, NetlistError> {
+    // The section the lines are in, with the location of its start
+    let mut current: Option<(String, Location)> = None;
+    let mut found = false;
+    for (number, text) in dialect.translate(logical_lines(text)) {
+        let location = Location {
+            file: file.map(Path::to_path_buf),
+            line: number,
+        };
+        let tokens = tokenize(&text);
+        let command = tokens[0].to_ascii_uppercase();
+        match (command.as_str(), &tokens[1..]) {
+            (".LIB", [name]) => {
+                if current.is_some() {
+                    return Err(location.error(String::from("section inside a section")));
+                }
+                found |= section.is_some_and(|section| section.eq_ignore_ascii_case(name));
+                current = Some((name.to_string(), location));
+                continue;
+            }
+            (".ENDL", _) => {
+                if current.take().is_none() {
+                    return Err(location.error(String::from(".ENDL outside a section")));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        let selected = match (&current, section) {
+            (Some((name, _)), Some(section)) => name.eq_ignore_ascii_case(section),
+            (None, None) => true,
+            _ => false,
+        };
+        if !selected {
+            continue;
+        }
+        match (command.as_str(), &tokens[1..]) {
+            (".INCLUDE" | ".INC", [path]) => include(&location, path, None, dialect, stack, lines)?,
+            (".LIB", [path, section]) => {
+                include(&location, path, Some(section), dialect, stack, lines)?
+            }
+            (".INCLUDE" | ".INC" | ".LIB", _) => {
+                return Err(location.error(format!("invalid {command} line")))
+            }
+            _ => lines.push((location, text)),
+        }
+    }
+    if let Some((name, location)) = current {
+        return Err(location.error(format!("section '{name}' has no .ENDL")));
+    }
+    Ok(found)
+}
+
+/// The lines of a netlist (see [logical_lines]) in a dialect, read
+/// from a file if it is, with each line saying where it is, the
+/// included files and sections in place of the lines including them,
+/// and only the conditional sections that are kept (see
+/// [super::conditional])
+pub(crate) fn source_lines(
+    text: &str,
+    file: Option<&Path>,
+    dialect: Dialect,
+) -> Result<Vec<(Location, String)>, NetlistError> {
+    let mut stack = Vec::new();
+    if let Some(file) = file {
+        if let Ok(path) = fs::canonicalize(file) {
+            stack.push((path, None));
+        }
+    }
+    let mut lines = Vec::new();
+    read(
+        &without_title(text, dialect),
+        file,
+        None,
+        dialect,
+        &mut stack,
+        &mut lines,
+    )?;
+    select(lines)
+}
@@ -0,0 +1,572 @@
+//! Subcircuits
+//!
+//! A subcircuit is defined between a `.SUBCKT` line, giving its name,
+//! its ports and the default values of its parameters, and an `.ENDS`
+//! line, and used by an `X` line, giving the nodes connected to its
+//! ports, its name and any parameters that differ from the defaults:
+//!
+//! ```text
+//! .SUBCKT follower 1 2 PARAMS: r=1k
+//! R1 1 3 {r}
+//! R2 3 0 1meg
+//! E1 2 0 3 0 1
+//! .ENDS follower
+//! X1 5 6 follower r=2k
+//! ```
+//!
+//! Each `X` line is replaced by the lines of its subcircuit, which can
+//! use other subcircuits (but not, directly or indirectly, itself). The
+//! components are named after the instance they are in, as in `X1.R1`,
+//! and so are the components controlling the current-controlled sources
+//! in the subcircuit. Ground (node 0) is shared with the rest of the
+//! netlist, and the other nodes that are not ports are internal to each
+//! instance, numbered (with the named nodes of the netlist) after the
+//! highest numbered node in the order they appear, and named after the
+//! instance, as in `x1.mid`.
+//!
+//! The parameters of a subcircuit, and `.PARAM` lines inside it, are
+//! used in expressions (see [crate::expression]) in braces, as in
+//! `{r}` or `{2*r}`, anywhere in a line of the subcircuit, and they
+//! are given by expressions too. Each instance has its own parameters,
+//! which become parameters of the netlist named after it, as in `x1.r`,
+//! so the values that depend on them follow the parameters of the
+//! netlist they depend on. Names that are not parameters of the
+//! subcircuit are parameters of the netlist. Subcircuit, port and
+//! parameter names are not case sensitive. A subcircuit can be defined
+//! anywhere in the netlist, including inside another, but its name is
+//! not local to it.
+//!
+//! Functions defined by `.FUNC` lines (see [crate::expression]) can be
+//! called in any expression of the netlist. Like subcircuit names, they
+//! are not local to a subcircuit they are defined in, and their bodies
+//! are in terms of the parameters of the netlist.
+//!
+//! A `.MODEL` card inside a subcircuit is local to it: it is named
+//! after each instance, as in `x1.dmod`, and its parameters can be
+//! expressions of the parameters of the instance.
+//!
+//! Nodes named on a `.GLOBAL` line, as in `.GLOBAL vdd vss`, are
+//! shared by the whole netlist, like ground: a global node used inside
+//! a subcircuit, at any depth, is the node of the same name at the top
+//! level, rather than internal to the instance, so supplies need not be
+//! passed down through ports. A global node takes the place of a port
+//! of the same name. The `.GLOBAL` lines apply wherever they are in the
+//! netlist.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+
+use super::dialect::Dialect;
+use super::include::source_lines;
+use super::{did_you_mean, tokenize, ErrorCode, Location, NetlistError};
+use crate::expression::{parse_expression, parse_function, Expression, Parameters};
+use crate::library;
+use crate::node::{is_ground, NodeNames};
+
+/// A line of a netlist with its subcircuits expanded
+pub(crate) struct Statement {
+    /// Where the line the statement comes from is
+    pub location: Location,
+    /// Name of the component (or the command), prefixed by the
+    /// instances of the subcircuits it is in
+    pub name: String,
+    /// The tokens of the line, with the nodes of the netlist and the
+    /// values of the expressions
+    pub tokens: Vec<String>,
+    /// The tokens that were an expression in braces, by position, with
+    /// the expression in terms of the parameters of the netlist
+    pub expressions: Vec<(usize, Expression)>,
+}
+
+impl Statement {
+    /// The type of the statement: the first letter of its name
+    /// (upper case), or `.` for a command
+    pub fn kind(&self) -> char {
+        kind(&self.tokens)
+    }
+}
+
+/// A subcircuit definition
+struct Definition {
+    /// Where the `.SUBCKT` line is
+    location: Location,
+    ports: Vec<String>,
+    /// Parameters, with their default values
+    params: Vec<(String, Expression)>,
+    lines: Vec<(Location, Vec<String>)>,
+}
+
+/// Where lines are being expanded
+struct Scope {
+    /// Name of the instance (empty at the top level)
+    path: String,
+    /// The node of the netlist connected to each port
+    ports: HashMap<String, String>,
+    /// The parameter of the netlist for each parameter of the
+    /// subcircuit
+    params: HashMap<String, String>,
+    /// Subcircuits being expanded, from the outermost
+    stack: Vec<String>,
+}
+
+fn kind(tokens: &[String]) -> char {
+    tokens[0].chars().next().unwrap().to_ascii_uppercase()
+}
+
+/// Index of the first parameter of a `.SUBCKT` or `X` line (or of
+/// `PARAMS:` before it), or the number of tokens if there are none
+fn params_start(tokens: &[String]) -> usize {
+    tokens
+        .iter()
+        .position(|t| t.eq_ignore_ascii_case("PARAMS:") || t.contains('='))
+        .unwrap_or(tokens.len())
+}
+
+/// Positions of the nodes in the tokens of a line
+pub(crate) fn node_positions(tokens: &[String]) -> Range<usize> {
+    let end = match kind(tokens) {
+        'E' | 'G' | 'M' => 5,
+        // The substrate is before the model, if there is one
+        'Q' if params_start(tokens) > 5 => 5,
+        'Q' => 4,
+        'S' => touchstone_file(tokens),
+        'X' => params_start(tokens).saturating_sub(1),
+        '.' => 1,
+        _ => 3,
+    };
+    1..end.clamp(1, tokens.len())
+}
+
+/// Index of the `TSTONEFILE=` token of an N-port line, or the number
+/// of tokens if there is none
+pub(crate) fn touchstone_file(tokens: &[String]) -> usize {
+    tokens
+        .iter()
+        .position(|token| {
+            token
+                .get(..11)
+                .is_some_and(|key| key.eq_ignore_ascii_case("TSTONEFILE="))
+        })
+        .unwrap_or(tokens.len())
+}
+
+/// Parameter assignments `name=expression` (with the expression in
+/// braces or not), with an optional `PARAMS:`
+pub(crate) fn assignments(
+    location: &Location,
+    tokens: &[String],
+) -> Result<Vec<(String, Expression)>, NetlistError> {
+    // Spaces around the equals signs are allowed
+    let joined = tokens.join(" ");
+    let joined = joined
+        .split('=')
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("=");
+    tokenize(&joined)
+        .into_iter()
+        .filter(|t| !t.eq_ignore_ascii_case("PARAMS:"))
+        .map(|t| {
+            let (name, value) = t
+                .split_once('=')
+                .ok_or_else(|| location.error(format!("expected a parameter, found '{t}'")))?;
+            let value = value
+                .strip_prefix('{')
+                .and_then(|v| v.strip_suffix('}'))
+                .unwrap_or(value);
+            let expression = parse_expression(value)
+                .map_err(|e| location.error(format!("parameter '{name}': {e}")))?;
+            Ok((name.to_ascii_lowercase(), expression))
+        })
+        .collect()
+}
+
+/// An expression in terms of the parameters of the netlist
+fn resolve(expression: &Expression, scope: &Scope) -> Expression {
+    expression.rename(&|name| {
+        scope
+            .params
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    })
+}
+
+/// Expands the subcircuit instances of a netlist
+struct Flattener {
+    definitions: HashMap<String, Definition>,
+    statements: Vec<Statement>,
+    /// Names of the named and internal nodes, in the order they appear
+    internal: Vec<String>,
+    parameters: Parameters,
+    /// Names of the global nodes (lower case)
+    globals: Vec<String>,
+}
+
+impl Flattener {
+    /// The node of the netlist for a node of a line
+    fn node(&mut self, scope: &Scope, node: &str) -> String {
+        let key = node.to_ascii_lowercase();
+        let global = self.globals.contains(&key);
+        if is_ground(node) {
+            String::from("0")
+        } else if (scope.path.is_empty() || global) && node.parse::<usize>().is_ok() {
+            node.to_string()
+        } else if let (false, Some(connected)) = (global, scope.ports.get(&key)) {
+            connected.clone()
+        } else {
+            let name = if global || scope.path.is_empty() {
+                key
+            } else {
+                format!("{}.{key}", scope.path)
+            };
+            if !self.internal.contains(&name) {
+                self.internal.push(name.clone());
+            }
+            name
+        }
+    }
+
+    /// Replace each expression in braces in a token by its value,
+    /// returning the expression if it is the whole token (or the whole
+    /// value of a `name={expression}` token)
+    fn substitute(
+        &self,
+        token: &str,
+        scope: &Scope,
+    ) -> Result<(String, Option<Expression>), String> {
+        let mut out = String::new();
+        let mut rest = token;
+        let mut whole = None;
+        while let Some(start) = rest.find('{') {
+            let end = start
+                + rest[start..]
+                    .find('}')
+                    .ok_or_else(|| format!("unclosed '{{' in '{token}'"))?;
+            let expression = parse_expression(&rest[start + 1..end])
+                .map(|expression| resolve(&expression, scope))
+                .map_err(|e| e.to_string())?;
+            let value = self
+                .parameters
+                .evaluate(&expression)
+                .map_err(|e| e.to_string())?;
+            let assigned = rest[..start]
+                .strip_suffix('=')
+                .is_some_and(|name| !name.is_empty() && name.chars().all(char::is_alphanumeric));
+            if rest.len() == token.len() && (start == 0 || assigned) && end == token.len() - 1 {
+                whole = Some(expression);
+            }
+            out.push_str(&rest[..start]);
+            out.push_str(&value.to_string());
+            rest = &rest[end + 1..];
+        }
+        out.push_str(rest);
+        Ok((out, whole))
+    }
+
+    fn expand(
+        &mut self,
+        lines: &[(Location, Vec<String>)],
+        scope: &Scope,
+    ) -> Result<(), NetlistError> {
+        for (location, tokens) in lines {
+            if tokens[0].eq_ignore_ascii_case(".PARAM") {
+                continue;
+            }
+            if kind(tokens) == '.' {
+                let mut tokens = tokens.clone();
+                if tokens[0].eq_ignore_ascii_case(".MODEL") {
+                    for token in &mut tokens[1..] {
+                        *token = self
+                            .substitute(token, scope)
+                            .map_err(|message| location.error(message))?
+                            .0;
+                    }
+                    if let (false, Some(model)) = (scope.path.is_empty(), tokens.get_mut(1)) {
+                        *model = format!("{}.{model}", scope.path);
+                    }
+                }
+                self.statements.push(Statement {
+                    location: location.clone(),
+                    name: tokens[0].clone(),
+                    tokens,
+                    expressions: Vec::new(),
+                });
+                continue;
+            }
+            let mut tokens = tokens.clone();
+            let mut expressions = Vec::new();
+            // The parameters of an instance are expanded with it
+            if kind(&tokens) != 'X' {
+                for (position, token) in tokens.iter_mut().enumerate() {
+                    let (value, expression) = self
+                        .substitute(token, scope)
+                        .map_err(|message| location.error(message))?;
+                    *token = value;
+                    if let Some(expression) = expression {
+                        expressions.push((position, expression));
+                    }
+                }
+            }
+            for position in node_positions(&tokens) {
+                tokens[position] = self.node(scope, &tokens[position]);
+            }
+            let name = if scope.path.is_empty() {
+                tokens[0].clone()
+            } else {
+                if matches!(kind(&tokens), 'F' | 'H') {
+                    if let Some(control) = tokens.get_mut(3) {
+                        *control = format!("{}.{control}", scope.path);
+                    }
+                }
+                format!("{}.{}", scope.path, tokens[0])
+            };
+            if kind(&tokens) == 'X' {
+                self.instance(location, name, &tokens, scope)?;
+            } else {
+                self.statements.push(Statement {
+                    location: location.clone(),
+                    name,
+                    tokens,
+                    expressions,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Expand an instance of a subcircuit, from its line
+    fn instance(
+        &mut self,
+        location: &Location,
+        name: String,
+        tokens: &[String],
+        scope: &Scope,
+    ) -> Result<(), NetlistError> {
+        let end = params_start(tokens);
+        if end < 2 {
+            return Err(location.error(String::from("missing subcircuit name")));
+        }
+        let subcircuit = tokens[end - 1].to_ascii_lowercase();
+        if scope.stack.contains(&subcircuit) {
+            return Err(location.error(format!("subcircuit '{subcircuit}' contains itself")));
+        }
+        let definition = self.definitions.get(&subcircuit).ok_or_else(|| {
+            location
+                .error(format!("no subcircuit named '{subcircuit}'"))
+                .with_code(ErrorCode::UnknownName)
+                .at(&subcircuit)
+                .suggest(did_you_mean(
+                    &subcircuit,
+                    self.definitions.keys().map(String::as_str),
+                ))
+        })?;
+        let nodes = &tokens[1..end - 1];
+        if nodes.len() != definition.ports.len() {
+            return Err(location.error(format!(
+                "{name} connects {} nodes, but subcircuit '{subcircuit}' has {} ports",
+                nodes.len(),
+                definition.ports.len()
+            )));
+        }
+        // The parameters of the subcircuit, and those defined inside
+        // it, with the parameter of the netlist for each
+        let mut locals = definition.params.clone();
+        for (location, line) in &definition.lines {
+            if line[0].eq_ignore_ascii_case(".PARAM") {
+                locals.extend(assignments(location, &line[1..])?);
+            }
+        }
+        let inner = Scope {
+            params: locals
+                .iter()
+                .map(|(param, _)| (param.clone(), format!("{name}.{param}").to_lowercase()))
+                .collect(),
+            path: name,
+            ports: definition
+                .ports
+                .iter()
+                .map(|port| port.to_ascii_lowercase())
+                .zip(nodes.iter().cloned())
+                .collect(),
+            stack: scope
+                .stack
+                .iter()
+                .cloned()
+                .chain([subcircuit.clone()])
+                .collect(),
+        };
+        for (param, expression) in &locals {
+            self.parameters
+                .define(&inner.params[param], resolve(expression, &inner));
+        }
+        for (param, expression) in assignments(location, &tokens[end..])? {
+            if !definition.params.iter().any(|(p, _)| *p == param) {
+                return Err(location.error(format!(
+                    "subcircuit '{subcircuit}' has no parameter '{param}'"
+                )));
+            }
+            self.parameters
+                .define(&inner.params[&param], resolve(&expression, scope));
+        }
+        // The definition is taken out while its lines are expanded
+        let definition = self.definitions.remove(&subcircuit).unwrap();
+        let result = self.expand(&definition.lines, &inner);
+        self.definitions.insert(subcircuit, definition);
+        result
+    }
+}
+
+/// Read the subcircuit definitions, the parameters and the global
+/// nodes from the lines of a netlist, returning the tokens of the other
+/// lines
+fn read_definitions(
+    lines: Vec<(Location, String)>,
+    parameters: &mut Parameters,
+    definitions: &mut HashMap<String, Definition>,
+    globals: &mut Vec<String>,
+) -> Result<Vec<(Location, Vec<String>)>, NetlistError> {
+    let mut open: Vec<(String, Definition)> = Vec::new();
+    let mut top = Vec::new();
+    for (location, text) in lines {
+        let tokens: Vec<String> = tokenize(&text).into_iter().map(String::from).collect();
+        match tokens[0].to_ascii_uppercase().as_str() {
+            ".SUBCKT" => {
+                let end = params_start(&tokens);
+                if end < 2 {
+                    return Err(location.error(String::from("missing subcircuit name")));
+                }
+                let definition = Definition {
+                    location: location.clone(),
+                    ports: tokens[2..end].to_vec(),
+                    params: assignments(&location, &tokens[end..])?,
+                    lines: Vec::new(),
+                };
+                open.push((tokens[1].to_ascii_lowercase(), definition));
+            }
+            ".ENDS" => {
+                let (name, definition) = open
+                    .pop()
+                    .ok_or_else(|| location.error(String::from(".ENDS without .SUBCKT")))?;
+                if let Some(ends) = tokens.get(1) {
+                    if !ends.eq_ignore_ascii_case(&name) {
+                        return Err(
+                            location.error(format!(".ENDS {ends} ends subcircuit '{name}'"))
+                        );
+                    }
+                }
+                let location = definition.location.clone();
+                if definitions.insert(name.clone(), definition).is_some() {
+                    return Err(location
+                        .error(format!("duplicate subcircuit name '{name}'"))
+                        .with_code(ErrorCode::DuplicateName));
+                }
+            }
+            ".GLOBAL" => {
+                if tokens.len() < 2 {
+                    return Err(location.error(String::from("missing global node")));
+                }
+                for node in &tokens[1..] {
+                    let node = node.to_ascii_lowercase();
+                    if !is_ground(&node) && !globals.contains(&node) {
+                        globals.push(node);
+                    }
+                }
+            }
+            ".FUNC" => {
+                let (name, function) = parse_function(&tokens[1..].join(" "))
+                    .map_err(|e| location.error(e.to_string()))?;
+                parameters
+                    .define_function(&name, function)
+                    .map_err(|e| location.error(e.to_string()))?;
+            }
+            ".PARAM" if open.is_empty() => {
+                for (name, expression) in assignments(&location, &tokens[1..])? {
+                    parameters.define(&name, expression);
+                }
+            }
+            _ => match open.last_mut() {
+                Some((_, definition)) => definition.lines.push((location, tokens)),
+                None => top.push((location, tokens)),
+            },
+        }
+    }
+    if let Some((name, definition)) = open.last() {
+        return Err(definition
+            .location
+            .error(format!("subcircuit '{name}' has no .ENDS")));
+    }
+    Ok(top)
+}
+
+/// The lines of a netlist (see [source_lines]) as statements, with
+/// the subcircuit definitions and `.PARAM` lines removed and each
+/// subcircuit instance replaced by the lines of the subcircuit, and
+/// the parameters and node names of the netlist
+pub(crate) fn flatten(
+    text: &str,
+    file: Option<&Path>,
+    dialect: Dialect,
+) -> Result<(Vec<Statement>, Parameters, NodeNames), NetlistError> {
+    let mut parameters = Parameters::new();
+    let mut definitions = HashMap::new();
+    let mut globals = Vec::new();
+    let top = read_definitions(
+        source_lines(text, file, dialect)?,
+        &mut parameters,
+        &mut definitions,
+        &mut globals,
+    )?;
+    // The subcircuits of the library are used unless the netlist
+    // defines its own
+    for (name, text) in library::subcircuits() {
+        if !definitions.contains_key(&name.to_ascii_lowercase()) {
+            let lines = source_lines(text, None, Dialect::Esim)?;
+            read_definitions(lines, &mut parameters, &mut definitions, &mut globals)?;
+        }
+    }
+
+    let mut flattener = Flattener {
+        definitions,
+        statements: Vec::new(),
+        internal: Vec::new(),
+        parameters,
+        globals,
+    };
+    let scope = Scope {
+        path: String::new(),
+        ports: HashMap::new(),
+        params: HashMap::new(),
+        stack: Vec::new(),
+    };
+    flattener.expand(&top, &scope)?;
+
+    // The named and internal nodes are numbered after the numbered
+    // nodes of the netlist
+    let mut statements = flattener.statements;
+    let highest = statements
+        .iter()
+        .flat_map(|s| node_positions(&s.tokens).map(|p| s.tokens[p].parse::<usize>()))
+        .filter_map(Result::ok)
+        .max()
+        .unwrap_or(0);
+    let internal: HashMap<String, usize> = flattener
+        .internal
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| (name, highest + index + 1))
+        .collect();
+    for statement in &mut statements {
+        for position in node_positions(&statement.tokens) {
+            if let Some(node) = internal.get(&statement.tokens[position]) {
+                statement.tokens[position] = node.to_string();
+            }
+        }
+    }
+    let mut nodes = NodeNames::new();
+    for (name, node) in internal {
+        nodes.insert(&name, node);
+    }
+    Ok((statements, flattener.parameters, nodes))
+}
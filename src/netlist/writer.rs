@@ -0,0 +1,626 @@
+//! Writing circuits as SPICE netlists
+//!
+//! The inverse of the parser: a circuit is written as a SPICE deck,
+//! which this crate and ngspice both read, so that a circuit built
+//! through the API can be saved, or cross-checked against ngspice. The
+//! deck starts with a comment (which SPICE takes as the title) and
+//! ends with `.END`, and has
+//!
+//! - a `.PARAM` line for each parameter, with the values that are
+//!   expressions of the parameters written in braces
+//! - a `.FUNC` line for each user-defined function
+//! - a `.TEMP` line, if the temperature of the circuit is not the
+//!   nominal one
+//! - a `.MODEL` card for each diode and transistor model of the
+//!   circuit, and one for each diode or transistor that does not use
+//!   one, named after the instance
+//! - a `.SUBCKT` definition for each macromodel instance (such as a
+//!   crystal), holding the primitive components it is elaborated into,
+//!   and an `X` line using it
+//! - a line for each other instance
+//!
+//! Nodes are written by their names in the circuit (see [crate::node]),
+//! or as numbers if they have none, so the nodes of ngspice are named
+//! as in the circuit. Read back, the numbered nodes keep their numbers
+//! and the named ones are numbered after them, as in any netlist. An
+//! instance whose name does not start with the letter
+//! of its SPICE type (such as `X1.R1`, from a subcircuit) is written
+//! with the letter in front (`RX1.R1`). The `G2` flag of resistors is
+//! not written, and the bulk of a MOSFET is written as its source. Components that SPICE does not have (such as relays
+//! and logic gates) and waveforms other than the SPICE primitives
+//! cannot be written. A current-controlled source is written with the
+//! name of the instance controlling it, which ngspice requires to be a
+//! voltage source.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::circuit::Circuit;
+use crate::component::{
+    AcSpec, BjtModel, Component, DiodeModel, Model, MosfetModel, Polarity, NOMINAL_TEMPERATURE,
+};
+use crate::value::format_number;
+use crate::waveform::Waveform;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteError {
+    /// Name of the instance that cannot be written
+    pub instance: String,
+    pub message: String,
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.instance, self.message)
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+/// A node as it is written: its name in the circuit, if it has one,
+/// or else its number
+fn node_name(circuit: &Circuit, node: usize) -> String {
+    circuit
+        .node_names()
+        .name(node)
+        .map_or_else(|| node.to_string(), str::to_string)
+}
+
+/// The SPICE letter of a component, if SPICE has it
+fn letter(component: &Component) -> Option<char> {
+    Some(match component {
+        Component::Resistor { .. } | Component::Thermistor { .. } => 'R',
+        Component::Capacitor { .. } => 'C',
+        Component::Inductor { .. } => 'L',
+        Component::Diode { .. } => 'D',
+        Component::Bjt { .. } => 'Q',
+        Component::Mosfet { .. } => 'M',
+        Component::IndependentVoltageSource { .. }
+        | Component::CurrentProbe { .. }
+        | Component::MeasurementProbe { .. } => 'V',
+        Component::IndependentCurrentSource { .. } => 'I',
+        Component::VoltageControlledVoltageSource { .. } => 'E',
+        Component::VoltageControlledCurrentSource { .. } => 'G',
+        Component::CurrentControlledCurrentSource { .. } => 'F',
+        Component::CurrentControlledVoltageSource { .. } => 'H',
+        component if is_macromodel(component) => 'X',
+        _ => return None,
+    })
+}
+
+/// Whether a component is written as a subcircuit of the components it
+/// is elaborated into
+fn is_macromodel(component: &Component) -> bool {
+    matches!(
+        component,
+        Component::Crystal { .. }
+            | Component::Supercapacitor { .. }
+            | Component::Urc { .. }
+            | Component::SemiconductorResistor { .. }
+            | Component::SemiconductorCapacitor { .. }
+            | Component::MeasurementProbe { .. }
+    )
+}
+
+/// The name of an instance in SPICE, starting with the letter of its
+/// type
+fn spice_name(letter: char, name: &str) -> String {
+    if name.starts_with(letter) || name.starts_with(letter.to_ascii_lowercase()) {
+        name.to_string()
+    } else {
+        format!("{letter}{name}")
+    }
+}
+
+/// A waveform as a SPICE source function, if it is a SPICE primitive
+fn spice_waveform(waveform: &Waveform) -> Option<String> {
+    let numbers = |values: &[f64]| {
+        values
+            .iter()
+            .map(|value| format_number(*value))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    Some(match waveform {
+        Waveform::Dc(value) => format!("PWL(0 {})", format_number(*value)),
+        Waveform::Pulse {
+            v1,
+            v2,
+            delay,
+            rise,
+            fall,
+            width,
+            period,
+        } => {
+            // Infinite widths and periods are left to their defaults
+            let mut values = vec![*v1, *v2, *delay, *rise, *fall, *width, *period];
+            while values.last().is_some_and(|value| value.is_infinite()) {
+                values.pop();
+            }
+            format!("PULSE({})", numbers(&values))
+        }
+        Waveform::Sin {
+            offset,
+            amplitude,
+            frequency,
+            delay,
+            damping,
+        } => format!(
+            "SIN({})",
+            numbers(&[*offset, *amplitude, *frequency, *delay, *damping])
+        ),
+        Waveform::Pwl(points) => {
+            let values: Vec<f64> = points.iter().flat_map(|(t, v)| [*t, *v]).collect();
+            format!("PWL({})", numbers(&values))
+        }
+        _ => return None,
+    })
+}
+
+/// The line of an instance with its temperature, if it has its own
+fn with_temperature(line: String, temp: Option<f64>) -> String {
+    match temp {
+        Some(temp) => format!("{line} TEMP={}", format_number(temp)),
+        None => line,
+    }
+}
+
+/// A diode model card
+fn diode_card(name: &str, model: &DiodeModel) -> String {
+    format!(
+        ".MODEL {name} D(IS={} N={} RS={} KF={} AF={} EG={} XTI={} TNOM={})",
+        format_number(model.is),
+        format_number(model.n),
+        format_number(model.rs),
+        format_number(model.kf),
+        format_number(model.af),
+        format_number(model.eg),
+        format_number(model.xti),
+        format_number(model.tnom),
+    )
+}
+
+/// A bipolar transistor model card
+fn bjt_card(name: &str, model: &BjtModel) -> String {
+    let kind = match model.polarity {
+        Polarity::N => "NPN",
+        Polarity::P => "PNP",
+    };
+    format!(
+        ".MODEL {name} {kind}(IS={} BF={} BR={} NF={} NR={} VAF={} RB={} RC={} RE={} CJE={} \
+         CJC={} EG={} XTI={} TNOM={})",
+        format_number(model.is),
+        format_number(model.bf),
+        format_number(model.br),
+        format_number(model.nf),
+        format_number(model.nr),
+        format_number(model.vaf),
+        format_number(model.rb),
+        format_number(model.rc),
+        format_number(model.re),
+        format_number(model.cje),
+        format_number(model.cjc),
+        format_number(model.eg),
+        format_number(model.xti),
+        format_number(model.tnom),
+    )
+}
+
+/// A MOSFET model card
+fn mosfet_card(name: &str, model: &MosfetModel) -> String {
+    let kind = match model.polarity {
+        Polarity::N => "NMOS",
+        Polarity::P => "PMOS",
+    };
+    format!(
+        ".MODEL {name} {kind}(LEVEL=1 VTO={} KP={} LAMBDA={} RD={} RS={} CGSO={} CGDO={} \
+         TNOM={})",
+        format_number(model.vto),
+        format_number(model.kp),
+        format_number(model.lambda),
+        format_number(model.rd),
+        format_number(model.rs),
+        format_number(model.cgso),
+        format_number(model.cgdo),
+        format_number(model.tnom),
+    )
+}
+
+/// Writes the lines of a deck
+struct Writer<'a> {
+    circuit: &'a Circuit,
+    /// Value expressions, by instance
+    expressions: HashMap<&'a str, String>,
+    /// SPICE names of the instances with current edges, by edge
+    edges: HashMap<usize, String>,
+    models: Vec<String>,
+    subcircuits: Vec<String>,
+}
+
+impl Writer<'_> {
+    /// A value of an instance, as its expression if it has one
+    fn value(&self, name: &str, value: f64) -> String {
+        match self.expressions.get(name) {
+            Some(expression) => format!("{{{expression}}}"),
+            None => format_number(value),
+        }
+    }
+
+    /// The DC value, AC specification and waveform of a source
+    fn source(
+        &self,
+        name: &str,
+        value: f64,
+        ac: &AcSpec,
+        waveform: &Option<Waveform>,
+    ) -> Result<String, WriteError> {
+        let mut out = format!("DC {}", self.value(name, value));
+        if *ac != AcSpec::default() {
+            out += &format!(
+                " AC {} {}",
+                format_number(ac.magnitude),
+                format_number(ac.phase)
+            );
+        }
+        if let Some(waveform) = waveform {
+            let waveform = spice_waveform(waveform).ok_or_else(|| WriteError {
+                instance: name.to_string(),
+                message: String::from("the waveform is not a SPICE source function"),
+            })?;
+            out += &format!(" {waveform}");
+        }
+        Ok(out)
+    }
+
+    /// The name of the instance with a current edge, which controls a
+    /// source
+    fn control(&self, name: &str, edge: usize) -> Result<&str, WriteError> {
+        self.edges
+            .get(&edge)
+            .map(String::as_str)
+            .ok_or_else(|| WriteError {
+                instance: name.to_string(),
+                message: format!("no instance has the controlling current edge {edge}"),
+            })
+    }
+
+    /// The name of the card of an instance: that of the model it uses,
+    /// or else one named after it, which is added with a card
+    fn card(
+        &mut self,
+        name: &str,
+        spice: &str,
+        top: bool,
+        card: impl Fn(&str) -> String,
+    ) -> String {
+        match self.circuit.instance_model(name) {
+            Some(model) if top => model.to_string(),
+            _ => {
+                let model = format!("{spice}_model");
+                self.models.push(card(&model));
+                model
+            }
+        }
+    }
+
+    /// The line of an instance, if it has one, adding the model cards
+    /// and subcircuit definitions it needs
+    fn line(
+        &mut self,
+        name: &str,
+        component: &Component,
+        top: bool,
+    ) -> Result<Option<String>, WriteError> {
+        let error = |message: &str| WriteError {
+            instance: name.to_string(),
+            message: message.to_string(),
+        };
+        let letter = letter(component).ok_or_else(|| error("SPICE has no such component"))?;
+        let spice = spice_name(letter, name);
+        let circuit = self.circuit;
+        let nodes = |nodes: &[usize]| {
+            nodes
+                .iter()
+                .map(|node| node_name(circuit, *node))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        if top && is_macromodel(component) {
+            return Ok(Some(self.subcircuit(name, &spice, component)?));
+        }
+        let rest = match *component {
+            Component::Resistor {
+                term_1,
+                term_2,
+                resistance,
+                ..
+            } => format!(
+                "{} {}",
+                nodes(&[term_1, term_2]),
+                self.value(name, resistance)
+            ),
+            Component::Thermistor {
+                term_1,
+                term_2,
+                model,
+                temperature,
+                ..
+            } => format!(
+                "{} {}",
+                nodes(&[term_1, term_2]),
+                format_number(model.resistance(temperature.unwrap_or(circuit.temperature())))
+            ),
+            Component::Capacitor {
+                term_1,
+                term_2,
+                capacitance,
+            } => format!(
+                "{} {}",
+                nodes(&[term_1, term_2]),
+                self.value(name, capacitance)
+            ),
+            Component::Inductor {
+                term_1,
+                term_2,
+                inductance,
+                ..
+            } => format!(
+                "{} {}",
+                nodes(&[term_1, term_2]),
+                self.value(name, inductance)
+            ),
+            Component::Diode {
+                anode,
+                cathode,
+                model,
+            } => {
+                let card = self.card(name, &spice, top, |card| diode_card(card, &model));
+                let line = format!("{} {card}", nodes(&[anode, cathode]));
+                with_temperature(line, model.temp)
+            }
+            Component::Bjt {
+                collector,
+                base,
+                emitter,
+                model,
+            } => {
+                let card = self.card(name, &spice, top, |card| bjt_card(card, &model));
+                let line = format!("{} {card}", nodes(&[collector, base, emitter]));
+                with_temperature(line, model.temp)
+            }
+            Component::Mosfet {
+                drain,
+                gate,
+                source,
+                model,
+            } => {
+                let card = self.card(name, &spice, top, |card| mosfet_card(card, &model));
+                let line = format!(
+                    "{} {card} L={} W={}",
+                    nodes(&[drain, gate, source, source]),
+                    format_number(model.length),
+                    format_number(model.width)
+                );
+                with_temperature(line, model.temp)
+            }
+            Component::IndependentVoltageSource {
+                term_pos,
+                term_neg,
+                voltage,
+                ac,
+                ref waveform,
+                ..
+            } => format!(
+                "{} {}",
+                nodes(&[term_pos, term_neg]),
+                self.source(name, voltage, &ac, waveform)?
+            ),
+            Component::IndependentCurrentSource {
+                term_pos,
+                term_neg,
+                current,
+                ac,
+                ref waveform,
+            } => format!(
+                "{} {}",
+                nodes(&[term_pos, term_neg]),
+                self.source(name, current, &ac, waveform)?
+            ),
+            Component::CurrentProbe {
+                term_pos, term_neg, ..
+            }
+            | Component::MeasurementProbe {
+                term_pos,
+                term_neg,
+                current_edge: Some(_),
+                ..
+            } => format!("{} 0", nodes(&[term_pos, term_neg])),
+            // A voltage probe only observes the circuit
+            Component::MeasurementProbe { .. } => return Ok(None),
+            Component::VoltageControlledVoltageSource {
+                term_pos,
+                term_neg,
+                ctrl_pos,
+                ctrl_neg,
+                gain,
+                ..
+            } => format!(
+                "{} {}",
+                nodes(&[term_pos, term_neg, ctrl_pos, ctrl_neg]),
+                format_number(gain)
+            ),
+            Component::VoltageControlledCurrentSource {
+                term_pos,
+                term_neg,
+                ctrl_pos,
+                ctrl_neg,
+                transconductance,
+            } => format!(
+                "{} {}",
+                nodes(&[term_pos, term_neg, ctrl_pos, ctrl_neg]),
+                format_number(transconductance)
+            ),
+            Component::CurrentControlledCurrentSource {
+                term_pos,
+                term_neg,
+                ctrl_edge,
+                gain,
+            } => format!(
+                "{} {} {}",
+                nodes(&[term_pos, term_neg]),
+                self.control(name, ctrl_edge)?,
+                format_number(gain)
+            ),
+            Component::CurrentControlledVoltageSource {
+                term_pos,
+                term_neg,
+                ctrl_edge,
+                transresistance,
+                ..
+            } => format!(
+                "{} {} {}",
+                nodes(&[term_pos, term_neg]),
+                self.control(name, ctrl_edge)?,
+                format_number(transresistance)
+            ),
+            _ => return Err(error("SPICE has no such component")),
+        };
+        Ok(Some(format!("{spice} {rest}")))
+    }
+
+    /// Define a subcircuit holding the primitive components a
+    /// macromodel instance is elaborated into, returning the line
+    /// using it
+    fn subcircuit(
+        &mut self,
+        name: &str,
+        spice: &str,
+        component: &Component,
+    ) -> Result<String, WriteError> {
+        let mut ports: Vec<usize> = Vec::new();
+        for terminal in component.terminals() {
+            if terminal != 0 && !ports.contains(&terminal) {
+                ports.push(terminal);
+            }
+        }
+        let ports: Vec<String> = ports
+            .iter()
+            .map(|node| node_name(self.circuit, *node))
+            .collect();
+        let definition = format!("{spice}_sub");
+        let mut lines = vec![format!(".SUBCKT {definition} {}", ports.join(" "))];
+        let mut single = Circuit::new();
+        single.add_component(name, component.clone());
+        let prefix = format!("{name}.");
+        for instance in single.elaborate().instances() {
+            let local = instance
+                .name
+                .strip_prefix(&prefix)
+                .unwrap_or(&instance.name);
+            if let Some(line) = self.line(local, &instance.component, false)? {
+                lines.push(line);
+            }
+        }
+        lines.push(format!(".ENDS {definition}"));
+        self.subcircuits.extend(lines);
+        Ok(format!("{spice} {} {definition}", ports.join(" ")))
+    }
+}
+
+/// Write a circuit as a SPICE netlist
+pub fn write_netlist(circuit: &Circuit) -> Result<String, WriteError> {
+    let mut edges = HashMap::new();
+    for instance in circuit.instances() {
+        if let (Some(edge), Some(letter)) = (
+            instance.component.current_edge(),
+            letter(&instance.component),
+        ) {
+            edges.insert(edge, spice_name(letter, &instance.name));
+        }
+    }
+    let mut writer = Writer {
+        circuit,
+        expressions: circuit
+            .value_expressions()
+            .iter()
+            .map(|(instance, expression)| (instance.as_str(), expression.to_string()))
+            .collect(),
+        edges,
+        models: Vec::new(),
+        subcircuits: Vec::new(),
+    };
+    for (name, model) in circuit.models() {
+        match model {
+            Model::Diode(model) => writer.models.push(diode_card(name, model)),
+            Model::Bjt(model) => writer.models.push(bjt_card(name, model)),
+            Model::Mosfet(model) => writer.models.push(mosfet_card(name, model)),
+            _ => {}
+        }
+    }
+    let mut elements = Vec::new();
+    for instance in circuit.instances() {
+        if let Some(line) = writer.line(&instance.name, &instance.component, true)? {
+            elements.push(line);
+        }
+    }
+
+    let mut lines = vec![String::from("* Netlist written by esim")];
+    lines.extend(
+        circuit
+            .parameters()
+            .iter()
+            .map(|(name, expression)| format!(".PARAM {name}={{{expression}}}")),
+    );
+    lines.extend(circuit.parameters().functions().map(|(name, function)| {
+        format!(
+            ".FUNC {name}({}) {{{}}}",
+            function.arguments.join(","),
+            function.body
+        )
+    }));
+    if circuit.temperature() != NOMINAL_TEMPERATURE {
+        lines.push(format!(".TEMP {}", format_number(circuit.temperature())));
+    }
+    lines.extend(writer.models);
+    lines.extend(writer.subcircuits);
+    lines.extend(elements);
+    lines.push(String::from(".END"));
+    Ok(lines.join("\n") + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CircuitBuilder;
+    use crate::dc::operating_point;
+    use crate::netlist::parse_netlist;
+
+    #[test]
+    fn nodes_are_written_by_name() {
+        let mut circuit = CircuitBuilder::new()
+            .vsource("V1", "in", "0", 10.0)
+            .resistor("R1", "in", "out", 1e3)
+            .resistor("R2", "out", "gnd", 3e3)
+            .build()
+            .unwrap();
+        let netlist = write_netlist(&circuit).unwrap();
+        assert!(netlist.contains("R1 in out 1e3"), "{netlist}");
+        let written = operating_point(&parse_netlist(&netlist).unwrap());
+        let output = written.node_voltage("out").unwrap();
+        assert!((output - 7.5).abs() < 1e-9);
+
+        // A node without a name is written as its number
+        let node = circuit.num_voltage_nodes() + 1;
+        circuit.add_component(
+            "R3",
+            Component::Resistor {
+                term_1: node,
+                term_2: 0,
+                current_edge: None,
+                resistance: 1e3,
+            },
+        );
+        let netlist = write_netlist(&circuit).unwrap();
+        assert!(netlist.contains(&format!("R3 {node} 0 1e3")), "{netlist}");
+    }
+}
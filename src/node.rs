@@ -0,0 +1,92 @@
+//! Node names
+//!
+//! The components of a circuit connect numbered nodes, but the nodes
+//! can also be named: a circuit keeps a table of names, in which each
+//! new name is given the next free node number (see
+//! [Circuit::node](crate::circuit::Circuit::node)), and the results of
+//! the analyses (such as the DC, AC, sweep and transient ones) look
+//! node voltages up by name.
+//!
+//! Names are not case sensitive. `0` and `gnd` are both ground (node
+//! 0), and a name that is a number is the node with that number, so
+//! numbered and named nodes can be used together, as in a netlist. A
+//! name is given a number after the highest node in use when it is
+//! first used, and that number is then reserved for the name: asking
+//! for the node by number is an error (see
+//! [Circuit::try_node](crate::circuit::Circuit::try_node)), rather than
+//! the two nodes being merged. The nodes of components added directly
+//! (by [Circuit::add_component](crate::circuit::Circuit::add_component))
+//! are not checked, so they should be numbers from `Circuit::node`.
+
+use std::collections::BTreeMap;
+
+/// Whether a node name is ground
+pub fn is_ground(name: &str) -> bool {
+    name == "0" || name.eq_ignore_ascii_case("gnd")
+}
+
+/// Node numbers by name
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NodeNames {
+    numbers: BTreeMap<String, usize>,
+    /// The first name given to each named node
+    names: BTreeMap<usize, String>,
+}
+
+impl NodeNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of a node by name: ground, a number, or a name in the
+    /// table
+    pub fn get(&self, name: &str) -> Option<usize> {
+        if is_ground(name) {
+            return Some(0);
+        }
+        self.numbers
+            .get(&name.to_ascii_lowercase())
+            .copied()
+            .or_else(|| name.parse().ok())
+    }
+
+    /// The name of a node, if it has one
+    pub fn name(&self, node: usize) -> Option<&str> {
+        self.names.get(&node).map(String::as_str)
+    }
+
+    /// Name a node, replacing any node with the name
+    pub fn insert(&mut self, name: &str, node: usize) {
+        let name = name.to_ascii_lowercase();
+        if let Some(old) = self
+            .numbers
+            .insert(name.clone(), node)
+            .filter(|old| *old != node)
+        {
+            if self.names.get(&old) == Some(&name) {
+                self.names.remove(&old);
+                // The node keeps any other name it has
+                if let Some(other) = self.numbers.iter().find(|(_, n)| **n == old) {
+                    self.names.insert(old, other.0.clone());
+                }
+            }
+        }
+        self.names.entry(node).or_insert(name);
+    }
+
+    /// Highest node with a name (zero if there are none)
+    pub fn highest(&self) -> usize {
+        self.names.keys().next_back().copied().unwrap_or(0)
+    }
+
+    /// The names (in lower case) with their nodes, in order of name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.numbers
+            .iter()
+            .map(|(name, number)| (name.as_str(), *number))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.numbers.is_empty()
+    }
+}
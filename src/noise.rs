@@ -0,0 +1,257 @@
+//! Noise analysis
+//!
+//! Small-signal noise of a circuit about its operating point, as for
+//! a SPICE `.NOISE` line: the spectral density of the noise voltage
+//! between two output nodes at each frequency, and the same referred
+//! to an input source (divided by the power gain from the source to
+//! the output).
+//!
+//! Each noisy device is a noise current source in parallel with it:
+//! resistors (and thermistors) have thermal noise $4kT/R$ at their own
+//! temperature, and junctions have shot noise $2qI$ at their operating
+//! point current (at the temperature of the junction), with flicker
+//! noise if their model has it (see
+//! [Junction::noise](crate::component::Junction::noise)). The other
+//! components are noiseless. The sources are uncorrelated, so their
+//! densities add at the output, each weighted by the squared magnitude
+//! of its transfer to the output.
+//!
+//! Rather than solving the circuit once for each noise source, the
+//! transfers from every node to the output are found from a single
+//! solve of the adjoint (transposed) AC system at each frequency.
+//! A resistor is at the temperature of the circuit (see
+//! [Circuit::temperature]), and a semiconductor resistor or thermistor
+//! at the temperature given for it.
+
+use crate::ac::{node_transfer, source_transfer, LinearAcAnalysis};
+use crate::circuit::Circuit;
+use std::collections::HashMap;
+
+use crate::component::{thermal_voltage, Component, ELECTRON_CHARGE};
+use crate::dc::{explain, DcOptions};
+use crate::error::EsimError;
+
+/// Noise densities of a circuit at each frequency
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseResult {
+    pub frequencies: Vec<f64>,
+    /// Density of the output noise voltage (V²/Hz)
+    pub output: Vec<f64>,
+    /// Density of the output noise referred to the input source (V²/Hz
+    /// for a voltage source, A²/Hz for a current source)
+    pub input: Vec<f64>,
+    /// Magnitude of the gain from the input source to the output
+    pub gain: Vec<f64>,
+    /// Contribution of each noisy instance to the output density, by
+    /// name
+    pub contributions: Vec<(String, Vec<f64>)>,
+}
+
+impl NoiseResult {
+    /// The RMS output noise voltage over the frequencies, integrating
+    /// the density by the trapezoidal rule
+    pub fn total_output(&self) -> f64 {
+        integrate(&self.frequencies, &self.output).sqrt()
+    }
+
+    /// The RMS input-referred noise over the frequencies
+    pub fn total_input(&self) -> f64 {
+        integrate(&self.frequencies, &self.input).sqrt()
+    }
+
+    /// The instances in order of their contribution to the total output
+    /// noise power, largest first, with their share of it
+    pub fn ranking(&self) -> Vec<(&str, f64)> {
+        let total = integrate(&self.frequencies, &self.output);
+        let mut ranking: Vec<(&str, f64)> = self
+            .contributions
+            .iter()
+            .map(|(name, density)| {
+                let power = integrate(&self.frequencies, density);
+                let share = if total > 0.0 { power / total } else { 0.0 };
+                (name.as_str(), share)
+            })
+            .collect();
+        ranking.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranking
+    }
+}
+
+/// Integral of samples over increasing abscissas by the trapezoidal
+/// rule
+fn integrate(x: &[f64], y: &[f64]) -> f64 {
+    x.windows(2)
+        .zip(y.windows(2))
+        .map(|(x, y)| 0.5 * (y[0] + y[1]) * (x[1] - x[0]))
+        .sum()
+}
+
+/// Temperatures of the semiconductor resistors of a circuit by name,
+/// which are plain resistors once it is elaborated
+fn resistor_temperatures(circuit: &Circuit) -> HashMap<String, f64> {
+    circuit
+        .instances()
+        .iter()
+        .filter_map(|instance| match instance.component {
+            Component::SemiconductorResistor { temperature, .. } => {
+                temperature.map(|temperature| (instance.name.clone(), temperature))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Terminals and density (A²/Hz) at a frequency of the noise current
+/// of each noisy instance of an elaborated circuit, by index, with the
+/// resistors at the temperature of the circuit unless they have their
+/// own
+fn noise_sources(
+    analysis: &LinearAcAnalysis,
+    temperatures: &HashMap<String, f64>,
+    frequency: f64,
+) -> Vec<(usize, usize, usize, f64)> {
+    let thermal = |resistance: f64, temperature: f64| {
+        4.0 * ELECTRON_CHARGE * thermal_voltage(temperature) / resistance
+    };
+    let circuit_temperature = analysis.circuit().temperature();
+    analysis
+        .circuit()
+        .instances()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, instance)| match instance.component {
+            Component::Resistor {
+                term_1,
+                term_2,
+                resistance,
+                ..
+            } => {
+                let temperature = temperatures
+                    .get(&instance.name)
+                    .copied()
+                    .unwrap_or(circuit_temperature);
+                Some((index, term_1, term_2, thermal(resistance, temperature)))
+            }
+            Component::Thermistor {
+                term_1,
+                term_2,
+                model,
+                temperature,
+                ..
+            } => {
+                let temperature = temperature.unwrap_or(circuit_temperature);
+                Some((
+                    index,
+                    term_1,
+                    term_2,
+                    thermal(model.resistance(temperature), temperature),
+                ))
+            }
+            _ => {
+                let (anode, cathode, junction) = instance.component.junction()?;
+                let voltage = analysis.dc_voltage(anode) - analysis.dc_voltage(cathode);
+                let (current, _) = junction.evaluate(voltage);
+                Some((index, anode, cathode, junction.noise(current, frequency)))
+            }
+        })
+        .collect()
+}
+
+/// Noise analysis of a circuit, with the output the voltage from
+/// output_pos to output_neg, referred to the named input source, at
+/// each frequency (in Hz). Panics if the input is not an independent
+/// source (see [try_noise]).
+pub fn noise(
+    circuit: &Circuit,
+    output: (usize, usize),
+    input: &str,
+    frequencies: &[f64],
+) -> NoiseResult {
+    let analysis = LinearAcAnalysis::new(circuit);
+    noise_with(circuit, &analysis, output, input, frequencies)
+        .unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Noise analysis as for [noise], returning an error if the circuit
+/// fails its check (see [Circuit::validate]), its operating point
+/// cannot be solved, the input is not an independent source, or an
+/// output node is not in the circuit
+pub fn try_noise(
+    circuit: &Circuit,
+    output: (usize, usize),
+    input: &str,
+    frequencies: &[f64],
+) -> Result<NoiseResult, EsimError> {
+    let analysis = LinearAcAnalysis::try_new(circuit, &DcOptions::default())?;
+    analysis.check_output(output)?;
+    noise_with(circuit, &analysis, output, input, frequencies)
+        .map_err(|error| explain(circuit, error))
+}
+
+fn noise_with(
+    circuit: &Circuit,
+    analysis: &LinearAcAnalysis,
+    (output_pos, output_neg): (usize, usize),
+    input: &str,
+    frequencies: &[f64],
+) -> Result<NoiseResult, EsimError> {
+    let input = analysis.input_source(input)?.clone();
+    let temperatures = resistor_temperatures(circuit);
+    let mut result = NoiseResult {
+        frequencies: frequencies.to_vec(),
+        output: Vec::new(),
+        input: Vec::new(),
+        gain: Vec::new(),
+        contributions: Vec::new(),
+    };
+    for (n, frequency) in frequencies.iter().enumerate() {
+        let adjoint = analysis
+            .assemble(*frequency, false)
+            .try_solve_adjoint(output_pos, output_neg)?;
+        let gain = source_transfer(&adjoint, &input)
+            .expect("The input is an independent source")
+            .norm();
+        let mut output = 0.0;
+        for (index, term_1, term_2, density) in noise_sources(analysis, &temperatures, *frequency) {
+            let transfer = node_transfer(&adjoint, term_1) - node_transfer(&adjoint, term_2);
+            let contribution = transfer.norm_sqr() * density;
+            output += contribution;
+            if n == 0 {
+                let name = analysis.circuit().instances()[index].name.clone();
+                result.contributions.push((name, Vec::new()));
+            }
+            let position = result
+                .contributions
+                .iter()
+                .position(|(name, _)| *name == analysis.circuit().instances()[index].name)
+                .expect("The noisy instances are the same at every frequency");
+            result.contributions[position].1.push(contribution);
+        }
+        result.output.push(output);
+        result.input.push(output / (gain * gain));
+        result.gain.push(gain);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CircuitBuilder;
+
+    #[test]
+    fn thermal_noise_follows_the_temperature_of_the_circuit() {
+        let mut circuit = CircuitBuilder::new()
+            .vsource("V1", "in", "0", 1.0)
+            .resistor("R1", "in", "out", 1e3)
+            .resistor("R2", "out", "0", 1e3)
+            .build()
+            .unwrap();
+        let out = circuit.node_names().get("out").unwrap();
+        let nominal = noise(&circuit, (out, 0), "V1", &[1e3]).output[0];
+        circuit.set_temperature(127.0);
+        let hot = noise(&circuit, (out, 0), "V1", &[1e3]).output[0];
+        let ratio = (127.0 + 273.15) / (27.0 + 273.15);
+        assert!((hot / nominal - ratio).abs() < 1e-9);
+    }
+}
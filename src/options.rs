@@ -0,0 +1,183 @@
+//! Simulator options
+//!
+//! The settings of the solvers, as given by the `.OPTIONS` lines of a
+//! netlist (see [crate::netlist::parse_options]), are kept in a
+//! [SimOptions], from which each analysis takes its own options:
+//!
+//! ```text
+//! .OPTIONS RELTOL=1e-4 ITL1=200 METHOD=TRAP
+//! .OPTIONS TEMP=85
+//! ```
+//!
+//! The options are
+//!
+//! - `RELTOL`: relative tolerance of the Newton iteration, and of the
+//!   truncation error if the time step is controlled
+//! - `ABSTOL`: absolute tolerance of currents (A) in the truncation
+//!   error, since the Newton iteration converges on the junction
+//!   voltages alone
+//! - `VNTOL`: absolute tolerance of voltages (V)
+//! - `GMIN`: the conductance gmin stepping ends at
+//! - `ITL1`: largest number of Newton iterations of an operating point
+//! - `ITL4`: largest number of Newton iterations at a time point
+//! - `TEMP`: temperature (degrees Celsius) of the circuit, instead of
+//!   that of its `.TEMP` line, and of the thermistors and semiconductor
+//!   resistors and capacitors, instead of their own (diodes with a
+//!   temperature of their own keep it)
+//! - `METHOD`: integration method of transient analyses, `EULER`,
+//!   `TRAP` or `GEAR` (of order 2), instead of that of the analysis
+//!
+//! The defaults are those of SPICE, except `ITL4`, which is 100 rather
+//! than 10, as for the operating point.
+
+use std::fmt;
+
+use crate::circuit::Circuit;
+use crate::component::Component;
+use crate::dc::{DcOptions, NewtonOptions};
+use crate::transient::{IntegrationMethod, StepControl, TransientOptions};
+use crate::value::parse_value;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimOptions {
+    pub reltol: f64,
+    /// Absolute tolerance of currents (A)
+    pub abstol: f64,
+    /// Absolute tolerance of voltages (V)
+    pub vntol: f64,
+    /// Conductance (S) gmin stepping ends at
+    pub gmin: f64,
+    /// Largest number of Newton iterations of an operating point
+    pub itl1: usize,
+    /// Largest number of Newton iterations at a time point
+    pub itl4: usize,
+    /// Temperature (degrees Celsius) of the circuit, if it is not its
+    /// own
+    pub temp: Option<f64>,
+    /// Integration method of transient analyses, if it is not their own
+    pub method: Option<IntegrationMethod>,
+}
+
+impl Default for SimOptions {
+    fn default() -> Self {
+        Self {
+            reltol: 1e-3,
+            abstol: 1e-12,
+            vntol: 1e-6,
+            gmin: 1e-12,
+            itl1: 100,
+            itl4: 100,
+            temp: None,
+            method: None,
+        }
+    }
+}
+
+/// An option that cannot be set from its value
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionError {
+    pub option: String,
+    pub message: String,
+}
+
+impl fmt::Display for OptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "option '{}': {}", self.option, self.message)
+    }
+}
+
+impl std::error::Error for OptionError {}
+
+impl SimOptions {
+    /// Set an option by its name (in any case) from the text of its
+    /// value, returning false if there is no such option
+    pub fn set(&mut self, name: &str, value: &str) -> Result<bool, OptionError> {
+        let error = |message: String| OptionError {
+            option: name.to_string(),
+            message,
+        };
+        let number = || parse_value(value).map_err(|e| error(e.to_string()));
+        let positive = || match number()? {
+            number if number > 0.0 => Ok(number),
+            _ => Err(error(String::from("the value must be positive"))),
+        };
+        let count = || match number()? {
+            number if number >= 1.0 && number.fract() == 0.0 => Ok(number as usize),
+            _ => Err(error(String::from("the value must be a positive integer"))),
+        };
+        match name.to_ascii_uppercase().as_str() {
+            "RELTOL" => self.reltol = positive()?,
+            "ABSTOL" => self.abstol = positive()?,
+            "VNTOL" => self.vntol = positive()?,
+            "GMIN" => self.gmin = positive()?,
+            "ITL1" => self.itl1 = count()?,
+            "ITL4" => self.itl4 = count()?,
+            "TEMP" => self.temp = Some(number()?),
+            "METHOD" => {
+                self.method = Some(match value.to_ascii_uppercase().as_str() {
+                    "EULER" => IntegrationMethod::BackwardEuler,
+                    "TRAP" | "TRAPEZOIDAL" => IntegrationMethod::Trapezoidal,
+                    "GEAR" => IntegrationMethod::Gear { order: 2 },
+                    _ => {
+                        return Err(error(format!(
+                            "expected EULER, TRAP or GEAR, not '{value}'"
+                        )))
+                    }
+                })
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Options of an operating point
+    pub fn dc_options(&self) -> DcOptions {
+        DcOptions {
+            gmin: self.gmin,
+            newton: NewtonOptions {
+                reltol: self.reltol,
+                vntol: self.vntol,
+                max_iterations: self.itl1,
+            },
+            ..DcOptions::default()
+        }
+    }
+
+    /// Options of a transient analysis, from those it is run with
+    pub fn transient_options(&self, options: TransientOptions) -> TransientOptions {
+        TransientOptions {
+            method: self.method.unwrap_or(options.method),
+            step_control: options.step_control.map(|control| StepControl {
+                reltol: self.reltol,
+                abstol: self.abstol,
+                vntol: self.vntol,
+                ..control
+            }),
+            dc: self.dc_options(),
+            newton: NewtonOptions {
+                max_iterations: self.itl4,
+                ..self.dc_options().newton
+            },
+            ..options
+        }
+    }
+
+    /// A circuit at the temperature of the options, if they have one
+    pub fn at_temperature(&self, circuit: &Circuit) -> Circuit {
+        let mut circuit = circuit.clone();
+        if let Some(temp) = self.temp {
+            circuit.set_temperature(temp);
+            for instance in circuit.instances_mut() {
+                match &mut instance.component {
+                    Component::Thermistor { temperature, .. }
+                    | Component::SemiconductorResistor { temperature, .. }
+                    | Component::SemiconductorCapacitor { temperature, .. } => {
+                        *temperature = Some(temp)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        circuit
+    }
+}
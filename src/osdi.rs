@@ -0,0 +1,681 @@
+//! Compact models compiled by OpenVAF (OSDI)
+//!
+//! OpenVAF compiles Verilog-A models into shared libraries with the
+//! OSDI (version 0.3) interface: each library holds descriptors of
+//! its models, giving their nodes, parameters and Jacobian entries,
+//! and functions to set up a model and an instance and to evaluate
+//! the residuals and Jacobian of an instance at a solution.
+//!
+//! Two-terminal models are loaded as [CompactModel]s, which are
+//! junctions. Internal nodes of the model (such as the node between a
+//! diode's junction and its series resistance) are solved for at each
+//! terminal voltage, by Newton iteration on the residuals of the
+//! internal nodes, and are then eliminated from the Jacobian to give
+//! the conductance seen at the terminals. Internal nodes that the
+//! model collapses (such as when a series resistance is zero) are
+//! merged.
+//!
+//! Models are evaluated for DC (resistive) residuals only, without
+//! the model's own voltage limiting. This module is only built with
+//! the `osdi` feature.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
+use libloading::Library;
+
+use crate::component::{CompactModel, Junction};
+
+/// Version of the interface this module implements
+const VERSION: (u32, u32) = (0, 3);
+
+const PARA_TY_MASK: u32 = 3;
+const PARA_TY_REAL: u32 = 0;
+const PARA_TY_INT: u32 = 1;
+
+const ACCESS_FLAG_SET: u32 = 1;
+
+const CALC_RESIST_RESIDUAL: u32 = 1;
+const CALC_RESIST_JACOBIAN: u32 = 4;
+const ANALYSIS_DC: u32 = 2048;
+const ANALYSIS_STATIC: u32 = 32768;
+
+const EVAL_RET_FLAG_FATAL: u32 = 2;
+
+/// Largest change in the terminal voltage between Newton iterations
+const MAX_STEP: f64 = 0.5;
+
+/// Largest change in an internal node voltage between iterations of
+/// the internal Newton iteration
+const MAX_INTERNAL_STEP: f64 = 0.1;
+
+/// Settings of the internal Newton iteration
+const MAX_INTERNAL_ITERATIONS: usize = 200;
+const INTERNAL_ABSTOL: f64 = 1e-12;
+const INTERNAL_VNTOL: f64 = 1e-9;
+
+#[repr(C)]
+struct OsdiSimParas {
+    names: *const *const c_char,
+    vals: *const f64,
+    names_str: *const *const c_char,
+    vals_str: *const *const c_char,
+}
+
+#[repr(C)]
+struct OsdiSimInfo {
+    paras: OsdiSimParas,
+    abstime: f64,
+    prev_solve: *const f64,
+    prev_state: *const f64,
+    next_state: *mut f64,
+    flags: u32,
+}
+
+#[repr(C)]
+struct OsdiInitError {
+    code: u32,
+    parameter_id: u32,
+}
+
+#[repr(C)]
+struct OsdiInitInfo {
+    flags: u32,
+    num_errors: u32,
+    errors: *mut OsdiInitError,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct OsdiNodePair {
+    node_1: u32,
+    node_2: u32,
+}
+
+#[repr(C)]
+struct OsdiJacobianEntry {
+    nodes: OsdiNodePair,
+    react_ptr_off: u32,
+    flags: u32,
+}
+
+#[repr(C)]
+struct OsdiNode {
+    name: *const c_char,
+    units: *const c_char,
+    residual_units: *const c_char,
+    resist_residual_off: u32,
+    react_residual_off: u32,
+    resist_limit_rhs_off: u32,
+    react_limit_rhs_off: u32,
+    is_flow: bool,
+}
+
+#[repr(C)]
+struct OsdiParamOpvar {
+    name: *const *const c_char,
+    num_alias: u32,
+    description: *const c_char,
+    units: *const c_char,
+    flags: u32,
+    len: u32,
+}
+
+#[repr(C)]
+struct OsdiNoiseSource {
+    name: *const c_char,
+    nodes: OsdiNodePair,
+}
+
+type AccessFn = unsafe extern "C" fn(*mut c_void, *mut c_void, u32, u32) -> *mut c_void;
+type SetupModelFn =
+    unsafe extern "C" fn(*mut c_void, *mut c_void, *const OsdiSimParas, *mut OsdiInitInfo);
+type SetupInstanceFn = unsafe extern "C" fn(
+    *mut c_void,
+    *mut c_void,
+    *mut c_void,
+    f64,
+    u32,
+    *const OsdiSimParas,
+    *mut OsdiInitInfo,
+);
+type EvalFn =
+    unsafe extern "C" fn(*mut c_void, *mut c_void, *mut c_void, *const OsdiSimInfo) -> u32;
+type LoadFn = unsafe extern "C" fn(*mut c_void, *mut c_void, *mut f64);
+type LoadJacobianFn = unsafe extern "C" fn(*mut c_void, *mut c_void);
+
+#[repr(C)]
+struct OsdiDescriptor {
+    name: *const c_char,
+    num_nodes: u32,
+    num_terminals: u32,
+    nodes: *const OsdiNode,
+    num_jacobian_entries: u32,
+    jacobian_entries: *const OsdiJacobianEntry,
+    num_collapsible: u32,
+    collapsible: *const OsdiNodePair,
+    collapsed_offset: u32,
+    noise_sources: *const OsdiNoiseSource,
+    num_noise_src: u32,
+    num_params: u32,
+    num_instance_params: u32,
+    num_opvars: u32,
+    param_opvar: *const OsdiParamOpvar,
+    node_mapping_offset: u32,
+    jacobian_ptr_resist_offset: u32,
+    num_states: u32,
+    state_idx_off: u32,
+    bound_step_offset: u32,
+    instance_size: u32,
+    model_size: u32,
+    access: AccessFn,
+    setup_model: SetupModelFn,
+    setup_instance: SetupInstanceFn,
+    eval: EvalFn,
+    load_noise: *const c_void,
+    load_residual_resist: LoadFn,
+    load_residual_react: LoadFn,
+    load_limit_rhs_resist: LoadFn,
+    load_limit_rhs_react: LoadFn,
+    load_spice_rhs_dc: *const c_void,
+    load_spice_rhs_tran: *const c_void,
+    load_jacobian_resist: LoadJacobianFn,
+    load_jacobian_react: *const c_void,
+    load_jacobian_tran: *const c_void,
+}
+
+type LogFn = unsafe extern "C" fn(*mut c_void, *const c_char, u32);
+
+/// Messages logged by the models, until they are taken by [take_log]
+static LOG: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Messages logged by models are kept in the log
+unsafe extern "C" fn log_message(_handle: *mut c_void, message: *const c_char, _level: u32) {
+    if !message.is_null() {
+        let message = CStr::from_ptr(message).to_string_lossy().into_owned();
+        LOG.lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .push(message);
+    }
+}
+
+/// Take the messages logged by the models of every library since they
+/// were last taken, oldest first
+pub fn take_log() -> Vec<String> {
+    std::mem::take(&mut *LOG.lock().unwrap_or_else(|error| error.into_inner()))
+}
+
+#[derive(Debug, Clone)]
+pub struct OsdiError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for OsdiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "OSDI library {}: {}", self.path.display(), self.message)
+    }
+}
+
+impl std::error::Error for OsdiError {}
+
+/// Zeroed memory with the alignment of a double, for the model and
+/// instance data
+fn zeroed(size: u32) -> Vec<f64> {
+    vec![0.0; (size as usize).div_ceil(8)]
+}
+
+fn name(text: *const c_char) -> String {
+    if text.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(text) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// A parameter of a model
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsdiParameter {
+    /// Name, and then any aliases
+    pub names: Vec<String>,
+    pub description: String,
+    pub units: String,
+    /// Whether the parameter can be given per instance
+    pub instance: bool,
+}
+
+/// A shared library of compiled models
+pub struct OsdiLibrary {
+    path: PathBuf,
+    library: Arc<Library>,
+    descriptors: &'static [OsdiDescriptor],
+}
+
+impl OsdiLibrary {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, OsdiError> {
+        let path = path.as_ref().to_path_buf();
+        let error = |message: String| OsdiError {
+            path: path.clone(),
+            message,
+        };
+        unsafe {
+            let library = Library::new(&path).map_err(|e| error(e.to_string()))?;
+            let symbol = |name: &[u8]| {
+                library
+                    .get::<*const u32>(name)
+                    .map(|symbol| **symbol)
+                    .map_err(|e| error(e.to_string()))
+            };
+            let version = (
+                symbol(b"OSDI_VERSION_MAJOR\0")?,
+                symbol(b"OSDI_VERSION_MINOR\0")?,
+            );
+            if version != VERSION {
+                return Err(error(format!(
+                    "OSDI version {}.{} is not supported (only {}.{})",
+                    version.0, version.1, VERSION.0, VERSION.1
+                )));
+            }
+            let num_descriptors = symbol(b"OSDI_NUM_DESCRIPTORS\0")?;
+            let descriptors = library
+                .get::<*const OsdiDescriptor>(b"OSDI_DESCRIPTORS\0")
+                .map_err(|e| error(e.to_string()))?;
+            let descriptors = std::slice::from_raw_parts(*descriptors, num_descriptors as usize);
+            if let Ok(log) = library.get::<*mut Option<LogFn>>(b"osdi_log\0") {
+                **log = Some(log_message);
+            }
+            Ok(Self {
+                path,
+                library: Arc::new(library),
+                descriptors,
+            })
+        }
+    }
+
+    /// Names of the models in the library
+    pub fn models(&self) -> Vec<String> {
+        self.descriptors.iter().map(|d| name(d.name)).collect()
+    }
+
+    fn descriptor(&self, model: &str) -> Result<&'static OsdiDescriptor, OsdiError> {
+        self.descriptors
+            .iter()
+            .find(|d| name(d.name) == model)
+            .ok_or_else(|| OsdiError {
+                path: self.path.clone(),
+                message: format!("No model named {model}"),
+            })
+    }
+
+    /// Names of the terminals of a model
+    pub fn terminals(&self, model: &str) -> Result<Vec<String>, OsdiError> {
+        let descriptor = self.descriptor(model)?;
+        Ok((0..descriptor.num_terminals as usize)
+            .map(|k| name(unsafe { (*descriptor.nodes.add(k)).name }))
+            .collect())
+    }
+
+    /// Parameters of a model (not including its operating point
+    /// variables)
+    pub fn parameters(&self, model: &str) -> Result<Vec<OsdiParameter>, OsdiError> {
+        let descriptor = self.descriptor(model)?;
+        Ok(parameters(descriptor)
+            .iter()
+            .enumerate()
+            .map(|(k, parameter)| OsdiParameter {
+                names: (0..=parameter.num_alias as usize)
+                    .map(|a| name(unsafe { *parameter.name.add(a) }))
+                    .collect(),
+                description: name(parameter.description),
+                units: name(parameter.units),
+                instance: k < descriptor.num_instance_params as usize,
+            })
+            .collect())
+    }
+
+    /// Load a two-terminal model as a compact model, with parameters
+    /// (by name or alias, case-insensitively) and the temperature in
+    /// degrees Celsius
+    pub fn load(
+        &self,
+        model: &str,
+        parameters: &[(&str, f64)],
+        temperature: f64,
+    ) -> Result<CompactModel, OsdiError> {
+        let instance =
+            OsdiInstance::new(self, model, parameters, temperature).map_err(|message| {
+                OsdiError {
+                    path: self.path.clone(),
+                    message: format!("{model}: {message}"),
+                }
+            })?;
+        Ok(CompactModel::new(model, instance))
+    }
+}
+
+/// The parameters (not operating point variables) of a descriptor
+fn parameters(descriptor: &OsdiDescriptor) -> &[OsdiParamOpvar] {
+    let all = unsafe {
+        std::slice::from_raw_parts(
+            descriptor.param_opvar,
+            (descriptor.num_params + descriptor.num_opvars) as usize,
+        )
+    };
+    &all[..descriptor.num_params as usize]
+}
+
+/// Empty simulator parameters
+const NO_NAMES: [*const c_char; 1] = [ptr::null()];
+
+fn sim_paras() -> OsdiSimParas {
+    OsdiSimParas {
+        names: NO_NAMES.as_ptr(),
+        vals: ptr::null(),
+        names_str: NO_NAMES.as_ptr(),
+        vals_str: ptr::null(),
+    }
+}
+
+/// Report the errors of a setup call
+fn check_setup(info: &OsdiInitInfo, what: &str) -> Result<(), String> {
+    if info.num_errors == 0 {
+        Ok(())
+    } else {
+        Err(format!("{} errors in {what} setup", info.num_errors))
+    }
+}
+
+/// Model and instance data, and the solution and Jacobian they read
+/// and write
+struct OsdiState {
+    model: Vec<f64>,
+    instance: Vec<f64>,
+    /// Solution, by solution index (0 is the cathode, as ground, and
+    /// 1 is the anode)
+    solution: Vec<f64>,
+    residual: Vec<f64>,
+    /// Dense Jacobian, by row and column solution index
+    jacobian: Vec<f64>,
+    prev_state: Vec<f64>,
+    next_state: Vec<f64>,
+}
+
+struct OsdiInstance {
+    descriptor: &'static OsdiDescriptor,
+    _library: Arc<Library>,
+    /// Number of solution indices
+    size: usize,
+    state: Mutex<OsdiState>,
+}
+
+// The descriptor is read-only data in the library, which is not
+// unloaded while an instance exists, and the model and instance data
+// are only reached through the mutex
+unsafe impl Send for OsdiInstance {}
+unsafe impl Sync for OsdiInstance {}
+
+impl OsdiInstance {
+    fn new(
+        library: &OsdiLibrary,
+        model: &str,
+        values: &[(&str, f64)],
+        temperature: f64,
+    ) -> Result<Self, String> {
+        let descriptor = library.descriptor(model).map_err(|e| e.message)?;
+        if descriptor.num_terminals != 2 {
+            return Err(format!(
+                "Only two-terminal models can be loaded (this has {})",
+                descriptor.num_terminals
+            ));
+        }
+        let known = library.parameters(model).map_err(|e| e.message)?;
+        let mut state = OsdiState {
+            model: zeroed(descriptor.model_size),
+            instance: zeroed(descriptor.instance_size),
+            solution: Vec::new(),
+            residual: Vec::new(),
+            jacobian: Vec::new(),
+            prev_state: vec![0.0; descriptor.num_states as usize],
+            next_state: vec![0.0; descriptor.num_states as usize],
+        };
+        let model_ptr = state.model.as_mut_ptr() as *mut c_void;
+        let instance_ptr = state.instance.as_mut_ptr() as *mut c_void;
+        let handle = ptr::null_mut();
+
+        // Parameters are set on the model, which gives the defaults
+        // of the instance parameters too
+        for (parameter_name, value) in values {
+            let id = known
+                .iter()
+                .position(|p| {
+                    p.names
+                        .iter()
+                        .any(|n| n.eq_ignore_ascii_case(parameter_name))
+                })
+                .ok_or_else(|| format!("No parameter named {parameter_name}"))?;
+            let flags = parameters(descriptor)[id].flags;
+            unsafe {
+                let target =
+                    (descriptor.access)(ptr::null_mut(), model_ptr, id as u32, ACCESS_FLAG_SET);
+                match flags & PARA_TY_MASK {
+                    PARA_TY_REAL => *(target as *mut f64) = *value,
+                    PARA_TY_INT => *(target as *mut i32) = value.round() as i32,
+                    _ => return Err(format!("{parameter_name} is not a number")),
+                }
+            }
+        }
+
+        let paras = sim_paras();
+        let mut info = OsdiInitInfo {
+            flags: 0,
+            num_errors: 0,
+            errors: ptr::null_mut(),
+        };
+        unsafe { (descriptor.setup_model)(handle, model_ptr, &paras, &mut info) };
+        check_setup(&info, "model")?;
+        let kelvin = temperature + 273.15;
+        unsafe {
+            (descriptor.setup_instance)(
+                handle,
+                instance_ptr,
+                model_ptr,
+                kelvin,
+                2,
+                &paras,
+                &mut info,
+            )
+        };
+        check_setup(&info, "instance")?;
+
+        // Solution index of each node: the terminals are 1 and 0, the
+        // internal nodes follow, and collapsed nodes are merged
+        let num_nodes = descriptor.num_nodes as usize;
+        let mut index: Vec<Option<usize>> = vec![None; num_nodes];
+        index[0] = Some(1);
+        index[1] = Some(0);
+        unsafe {
+            let collapsed = (state.instance.as_ptr() as *const u8)
+                .add(descriptor.collapsed_offset as usize)
+                as *const bool;
+            let mut merged: Vec<usize> = (0..num_nodes).collect();
+            let root = |merged: &Vec<usize>, mut node: usize| {
+                while merged[node] != node {
+                    node = merged[node];
+                }
+                node
+            };
+            for k in 0..descriptor.num_collapsible as usize {
+                if !*collapsed.add(k) {
+                    continue;
+                }
+                let pair = *descriptor.collapsible.add(k);
+                let node_1 = root(&merged, pair.node_1 as usize);
+                if pair.node_2 as usize >= num_nodes {
+                    // Collapsed to ground, which is the cathode here
+                    let cathode = root(&merged, 1);
+                    merged[node_1] = cathode;
+                } else {
+                    let node_2 = root(&merged, pair.node_2 as usize);
+                    // Keep the terminals as the roots
+                    if node_1 < node_2 {
+                        merged[node_2] = node_1;
+                    } else {
+                        merged[node_1] = node_2;
+                    }
+                }
+            }
+            let mut size = 2;
+            for node in 0..num_nodes {
+                let root = root(&merged, node);
+                if index[root].is_none() {
+                    index[root] = Some(size);
+                    size += 1;
+                }
+                index[node] = index[root];
+            }
+            let mapping = (state.instance.as_mut_ptr() as *mut u8)
+                .add(descriptor.node_mapping_offset as usize) as *mut u32;
+            for (node, index) in index.iter().enumerate() {
+                *mapping.add(node) = index.unwrap() as u32;
+            }
+            state.solution = vec![0.0; size];
+            state.residual = vec![0.0; size];
+            state.jacobian = vec![0.0; size * size];
+            let pointers = (state.instance.as_mut_ptr() as *mut u8)
+                .add(descriptor.jacobian_ptr_resist_offset as usize)
+                as *mut *mut f64;
+            for k in 0..descriptor.num_jacobian_entries as usize {
+                let entry = &*descriptor.jacobian_entries.add(k);
+                let row = index[entry.nodes.node_1 as usize].unwrap();
+                let column = index[entry.nodes.node_2 as usize].unwrap();
+                *pointers.add(k) = state.jacobian.as_mut_ptr().add(row * size + column);
+            }
+            Ok(Self {
+                descriptor,
+                _library: library.library.clone(),
+                size,
+                state: Mutex::new(state),
+            })
+        }
+    }
+}
+
+impl OsdiState {
+    /// Evaluate the residuals and Jacobian at the solution
+    fn eval(&mut self, descriptor: &OsdiDescriptor) {
+        let model = self.model.as_mut_ptr() as *mut c_void;
+        let instance = self.instance.as_mut_ptr() as *mut c_void;
+        let info = OsdiSimInfo {
+            paras: sim_paras(),
+            abstime: 0.0,
+            prev_solve: self.solution.as_ptr(),
+            prev_state: self.prev_state.as_ptr(),
+            next_state: self.next_state.as_mut_ptr(),
+            flags: CALC_RESIST_RESIDUAL | CALC_RESIST_JACOBIAN | ANALYSIS_DC | ANALYSIS_STATIC,
+        };
+        self.residual.fill(0.0);
+        self.jacobian.fill(0.0);
+        unsafe {
+            let flags = (descriptor.eval)(ptr::null_mut(), instance, model, &info);
+            if flags & EVAL_RET_FLAG_FATAL != 0 {
+                panic!("OSDI model {} failed to evaluate", name(descriptor.name));
+            }
+            (descriptor.load_residual_resist)(instance, model, self.residual.as_mut_ptr());
+            (descriptor.load_jacobian_resist)(instance, model);
+        }
+    }
+}
+
+/// Solve a dense linear system in place by Gaussian elimination with
+/// partial pivoting, returning the solution
+fn solve_dense(mut a: Vec<f64>, mut b: Vec<f64>) -> Vec<f64> {
+    let n = b.len();
+    for k in 0..n {
+        let pivot = (k..n)
+            .max_by(|i, j| a[i * n + k].abs().total_cmp(&a[j * n + k].abs()))
+            .unwrap();
+        if pivot != k {
+            for j in 0..n {
+                a.swap(k * n + j, pivot * n + j);
+            }
+            b.swap(k, pivot);
+        }
+        for i in k + 1..n {
+            let factor = a[i * n + k] / a[k * n + k];
+            for j in k..n {
+                a[i * n + j] -= factor * a[k * n + j];
+            }
+            b[i] -= factor * b[k];
+        }
+    }
+    for k in (0..n).rev() {
+        let sum: f64 = (k + 1..n).map(|j| a[k * n + j] * b[j]).sum();
+        b[k] = (b[k] - sum) / a[k * n + k];
+    }
+    b
+}
+
+impl Junction for OsdiInstance {
+    fn evaluate(&self, voltage: f64) -> (f64, f64) {
+        let n = self.size;
+        let mut state = self.state.lock().unwrap();
+        state.solution[1] = voltage;
+        // The internal nodes, from their values at the last
+        // evaluation
+        let internal = 2..n;
+        for _ in 0..MAX_INTERNAL_ITERATIONS {
+            state.eval(self.descriptor);
+            if internal.is_empty() {
+                break;
+            }
+            let a: Vec<f64> = internal
+                .clone()
+                .flat_map(|i| internal.clone().map(move |j| (i, j)))
+                .map(|(i, j)| state.jacobian[i * n + j])
+                .collect();
+            let b: Vec<f64> = internal.clone().map(|i| -state.residual[i]).collect();
+            let step = solve_dense(a, b);
+            let converged = internal.clone().zip(&step).all(|(i, dv)| {
+                state.residual[i].abs() < INTERNAL_ABSTOL || dv.abs() < INTERNAL_VNTOL
+            });
+            for (i, dv) in internal.clone().zip(step) {
+                state.solution[i] += dv.clamp(-MAX_INTERNAL_STEP, MAX_INTERNAL_STEP);
+            }
+            if converged {
+                state.eval(self.descriptor);
+                break;
+            }
+        }
+        // The current into the anode, and the conductance seen at the
+        // anode with the internal nodes eliminated
+        let current = state.residual[1];
+        let mut conductance = state.jacobian[n + 1];
+        if !internal.is_empty() {
+            let a: Vec<f64> = internal
+                .clone()
+                .flat_map(|i| internal.clone().map(move |j| (i, j)))
+                .map(|(i, j)| state.jacobian[i * n + j])
+                .collect();
+            let b: Vec<f64> = internal
+                .clone()
+                .map(|i| state.jacobian[i * n + 1])
+                .collect();
+            let x = solve_dense(a, b);
+            conductance -= internal
+                .clone()
+                .zip(x)
+                .map(|(j, x)| state.jacobian[n + j] * x)
+                .sum::<f64>();
+        }
+        (current, conductance)
+    }
+
+    fn limit_voltage(&self, new: f64, old: f64) -> f64 {
+        new.clamp(old - MAX_STEP, old + MAX_STEP)
+    }
+}
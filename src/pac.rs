@@ -0,0 +1,279 @@
+//! Periodic AC analysis
+//!
+//! Small-signal analysis about the periodic steady state of a circuit
+//! (see [crate::pss]), rather than about its DC operating point, which
+//! gives the conversion gain of a mixer or a switched-capacitor
+//! circuit: a small signal at one frequency comes out at that
+//! frequency shifted by each harmonic of the steady state.
+//!
+//! The junctions are linearised at each time point of a period of the
+//! steady state, and their conductance is a periodic function of
+//! time, whose Fourier coefficients couple the small signal at each
+//! sideband (the input frequency plus a harmonic of the fundamental)
+//! to the others, as in harmonic balance. The linear part of the
+//! circuit is solved at each sideband as in AC analysis, and the other
+//! nonlinear components are linearised about the DC operating point.
+//! The small signal is driven by the AC specification of the
+//! independent sources, at the input frequency only.
+//!
+//! The response at each sideband is a phasor, so the small signal is
+//! $x(t) = \operatorname{Re} \sum_k X_k e^{j (\omega + k \omega_0) t}$,
+//! with the time measured from the start of the period of the steady
+//! state. The sidebands below the fundamental can have a negative
+//! frequency, where the signal at the (positive) frequency is the
+//! conjugate of the phasor. The steady state should have at least four
+//! time steps in a period for each sideband, so that the conductances
+//! do not alias.
+
+use std::f64::consts::PI;
+
+use num::Complex;
+
+use crate::ac::LinearAcAnalysis;
+use crate::circuit::Circuit;
+use crate::harmonic_balance::add_admittance;
+use crate::node::NodeNames;
+use crate::pss::PssResult;
+use crate::sparse::{plus_equals, solve, SparseMat};
+
+/// Small-signal response of a circuit about its periodic steady state
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacResult {
+    /// Fundamental frequency of the steady state, in Hz
+    pub fundamental: f64,
+    /// Input frequencies, in Hz
+    pub frequencies: Vec<f64>,
+    /// Node voltages (from node 1) at each sideband (from the lowest),
+    /// at each input frequency
+    pub voltages: Vec<Vec<Vec<Complex<f64>>>>,
+    /// Edge currents at each sideband, at each input frequency
+    pub currents: Vec<Vec<Vec<Complex<f64>>>>,
+    nodes: NodeNames,
+}
+
+impl PacResult {
+    /// Number of sidebands above (and below) the input frequency
+    pub fn num_sidebands(&self) -> usize {
+        self.voltages.first().map_or(0, |v| v.len() / 2)
+    }
+
+    /// Frequency of a sideband at each input frequency, which is the
+    /// input frequency plus that many times the fundamental
+    pub fn sideband_frequencies(&self, sideband: isize) -> Vec<f64> {
+        self.frequencies
+            .iter()
+            .map(|f| f + sideband as f64 * self.fundamental)
+            .collect()
+    }
+
+    /// Index of a sideband. Panics if it is not in the result.
+    fn index(&self, sideband: isize) -> usize {
+        let max = self.num_sidebands() as isize;
+        assert!(
+            sideband.abs() <= max,
+            "Sideband {sideband} is not in the result (of {max} sidebands)"
+        );
+        (sideband + max) as usize
+    }
+
+    /// Voltage of a node at a sideband, at each input frequency
+    pub fn voltage(&self, node: usize, sideband: isize) -> Vec<Complex<f64>> {
+        let index = self.index(sideband);
+        self.voltages
+            .iter()
+            .map(|sidebands| match node {
+                0 => Complex::new(0.0, 0.0),
+                n => sidebands[index][n - 1],
+            })
+            .collect()
+    }
+
+    /// Voltage of a node by name (see [crate::node]) at a sideband, at
+    /// each input frequency, if there is a node with the name in the
+    /// circuit
+    pub fn node_voltage(&self, name: &str, sideband: isize) -> Option<Vec<Complex<f64>>> {
+        let num_nodes = self
+            .voltages
+            .first()
+            .and_then(|v| v.first())
+            .map_or(0, Vec::len);
+        self.nodes
+            .get(name)
+            .filter(|node| *node <= num_nodes)
+            .map(|node| self.voltage(node, sideband))
+    }
+
+    /// Current of an edge at a sideband, at each input frequency
+    pub fn current(&self, edge: usize, sideband: isize) -> Vec<Complex<f64>> {
+        let index = self.index(sideband);
+        self.currents
+            .iter()
+            .map(|sidebands| sidebands[index][edge])
+            .collect()
+    }
+
+    /// Conversion gain from the voltage of an input node at the input
+    /// frequency to the voltage of an output node at a sideband, at
+    /// each input frequency
+    pub fn conversion_gain(
+        &self,
+        output: usize,
+        input: usize,
+        sideband: isize,
+    ) -> Vec<Complex<f64>> {
+        self.voltage(output, sideband)
+            .iter()
+            .zip(self.voltage(input, 0))
+            .map(|(output, input)| output / input)
+            .collect()
+    }
+
+    /// Magnitude of the conversion gain in decibels
+    pub fn conversion_gain_db(&self, output: usize, input: usize, sideband: isize) -> Vec<f64> {
+        self.conversion_gain(output, input, sideband)
+            .iter()
+            .map(|gain| 20.0 * gain.norm().log10())
+            .collect()
+    }
+}
+
+/// Fourier coefficients (from harmonic -max to max) of a real waveform
+/// sampled at equally spaced times over a period
+fn fourier_coefficients(samples: &[f64], max: usize) -> Vec<Complex<f64>> {
+    let size = samples.len() as f64;
+    (-(max as isize)..=max as isize)
+        .map(|k| {
+            samples
+                .iter()
+                .enumerate()
+                .map(|(n, x)| {
+                    x * Complex::from_polar(1.0, -2.0 * PI * (k * n as isize) as f64 / size)
+                })
+                .sum::<Complex<f64>>()
+                / size
+        })
+        .collect()
+}
+
+/// Node voltages of a steady state interpolated linearly at as many
+/// equally spaced times over its period as it has time steps
+fn equally_spaced(steady_state: &PssResult) -> Vec<Vec<f64>> {
+    let result = &steady_state.result;
+    let time = &result.time;
+    let num_points = time.len() - 1;
+    (0..num_points)
+        .map(|n| {
+            let t = steady_state.period * n as f64 / num_points as f64;
+            let index = time.partition_point(|x| *x < t).clamp(1, num_points);
+            let (t0, t1) = (time[index - 1], time[index]);
+            let weight = (t - t0) / (t1 - t0);
+            result.voltages[index - 1]
+                .iter()
+                .zip(&result.voltages[index])
+                .map(|(v0, v1)| v0 + weight * (v1 - v0))
+                .collect()
+        })
+        .collect()
+}
+
+/// Small-signal analysis of a circuit about its periodic steady state
+/// (from [crate::pss::pss] with a fixed time step), with a number of
+/// sidebands above and below each input frequency (in Hz)
+pub fn pac(
+    circuit: &Circuit,
+    steady_state: &PssResult,
+    num_sidebands: usize,
+    frequencies: &[f64],
+) -> PacResult {
+    let analysis = LinearAcAnalysis::new(circuit);
+    let max = num_sidebands;
+    let fundamental = 1.0 / steady_state.period;
+    // The steady state at equally spaced times over one period, without
+    // the last (which is the start of the next), since a step shortened
+    // to land on a breakpoint of a source adds a time point off the grid
+    let states = equally_spaced(steady_state);
+    let node = |voltages: &[f64], node: usize| match node {
+        0 => 0.0,
+        n => voltages[n - 1],
+    };
+
+    // Terminals of each junction, and the Fourier coefficients of its
+    // conductance less the conductance stamped for it by the AC
+    // analysis
+    let junctions: Vec<_> = analysis
+        .circuit()
+        .instances()
+        .iter()
+        .filter_map(|instance| {
+            let (anode, cathode, model) = instance.component.junction()?;
+            let stamped = model
+                .linearise(analysis.dc_voltage(anode) - analysis.dc_voltage(cathode))
+                .0;
+            let conductances: Vec<f64> = states
+                .iter()
+                .map(|v| model.linearise(node(v, anode) - node(v, cathode)).0 - stamped)
+                .collect();
+            Some((
+                (anode, cathode),
+                fourier_coefficients(&conductances, 2 * max),
+            ))
+        })
+        .collect();
+
+    let blocks = 2 * max + 1;
+    let mut voltages = Vec::with_capacity(frequencies.len());
+    let mut currents = Vec::with_capacity(frequencies.len());
+    for frequency in frequencies {
+        // The linear system at each sideband (from -max), with the
+        // sources at the input frequency only
+        let linear: Vec<_> = (-(max as isize)..=max as isize)
+            .map(|k| {
+                let mna = analysis.assemble(frequency + k as f64 * fundamental, k == 0);
+                (mna.entries(), mna.rhs(), mna.num_voltage_nodes())
+            })
+            .collect();
+        let size = linear[0].1.len();
+        let num_voltage_nodes = linear[0].2;
+        let mut matrix = SparseMat::new(blocks * size, blocks * size);
+        let mut rhs = Vec::with_capacity(blocks * size);
+        for (block, (entries, block_rhs, _)) in linear.iter().enumerate() {
+            for ((row, column), value) in entries {
+                plus_equals(
+                    &mut matrix,
+                    block * size + row,
+                    block * size + column,
+                    *value,
+                );
+            }
+            rhs.extend_from_slice(block_rhs);
+        }
+        for (terminals, conductances) in &junctions {
+            for row in 0..blocks {
+                for column in 0..blocks {
+                    add_admittance(
+                        &mut matrix,
+                        (row * size, column * size),
+                        *terminals,
+                        conductances[2 * max + row - column],
+                    );
+                }
+            }
+        }
+        let (sideband_voltages, sideband_currents) = solve(matrix, rhs)
+            .chunks(size)
+            .map(|block| {
+                let (v, i) = block.split_at(num_voltage_nodes);
+                (v.to_vec(), i.to_vec())
+            })
+            .unzip();
+        voltages.push(sideband_voltages);
+        currents.push(sideband_currents);
+    }
+    PacResult {
+        fundamental,
+        frequencies: frequencies.to_vec(),
+        voltages,
+        currents,
+        nodes: circuit.node_names().clone(),
+    }
+}
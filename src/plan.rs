@@ -0,0 +1,292 @@
+//! Run plans and dry runs
+//!
+//! A run plan is a circuit with the analyses to run on it, which can
+//! be saved and loaded as a schema document (see
+//! [v1::RunPlan](crate::schema::v1::RunPlan)). A dry run estimates the
+//! cost of a plan without solving anything: the circuit is
+//! elaborated, the sparsity pattern of the MNA matrix is built from
+//! the terminals and current edges of each component, and a symbolic
+//! LU factorization in minimum degree order gives the fill-in and the
+//! number of floating point operations of each factorization.
+//!
+//! The estimate is of the least work the plan needs. The pattern is
+//! treated as structurally symmetric, and each component is assumed
+//! to couple all its terminals and edges, which is the pattern a
+//! sparse solver works with after symmetric ordering. A nonlinear
+//! circuit needs one factorization per Newton iteration, so its
+//! cost is a multiple of the estimate.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap};
+use std::fmt;
+
+use crate::circuit::Circuit;
+use crate::component::Component;
+use crate::schema::{from_json, to_json, v1, SchemaError};
+use crate::watch::Analysis;
+
+/// Bytes stored for each nonzero of a sparse matrix (value and row
+/// index)
+const BYTES_PER_NONZERO: usize = 12;
+
+#[derive(Debug, Clone, Default)]
+pub struct RunPlan {
+    pub circuit: Circuit,
+    pub analyses: Vec<Analysis>,
+}
+
+/// Estimated cost of one analysis of a run plan
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisCost {
+    pub analysis: Analysis,
+    /// Number of solution points stored (one for an operating point)
+    pub time_points: usize,
+    /// Number of factorizations if every solve is linear
+    pub factorizations: usize,
+    /// Floating point operations of the factorizations
+    pub flops: f64,
+    /// Bytes taken by the stored results
+    pub result_memory: usize,
+}
+
+/// Estimated size and cost of a run plan
+#[derive(Debug, Clone, PartialEq)]
+pub struct DryRun {
+    /// Voltage nodes (excluding ground) of the elaborated circuit
+    pub num_voltage_nodes: usize,
+    pub num_current_edges: usize,
+    /// Number of rows (and columns) of the MNA matrix
+    pub matrix_size: usize,
+    /// Nonzeros in the MNA matrix
+    pub nonzeros: usize,
+    /// Nonzeros in the L and U factors, including fill-in
+    pub factor_nonzeros: usize,
+    /// Floating point operations of one factorization
+    pub factorization_flops: f64,
+    /// Bytes taken by the matrix and its factors
+    pub matrix_memory: usize,
+    /// Whether the circuit has nonlinear components, in which case
+    /// each solve takes several factorizations
+    pub nonlinear: bool,
+    pub analyses: Vec<AnalysisCost>,
+}
+
+impl DryRun {
+    /// Floating point operations of all the analyses
+    pub fn total_flops(&self) -> f64 {
+        self.analyses.iter().map(|a| a.flops).sum()
+    }
+
+    /// Bytes taken by the matrix and the results of all the analyses
+    pub fn total_memory(&self) -> usize {
+        self.matrix_memory + self.analyses.iter().map(|a| a.result_memory).sum::<usize>()
+    }
+}
+
+impl fmt::Display for DryRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "matrix: {} x {} ({} nodes, {} edges), {} nonzeros, {} in factors",
+            self.matrix_size,
+            self.matrix_size,
+            self.num_voltage_nodes,
+            self.num_current_edges,
+            self.nonzeros,
+            self.factor_nonzeros
+        )?;
+        writeln!(
+            f,
+            "factorization: {:.3e} flops, {} bytes{}",
+            self.factorization_flops,
+            self.matrix_memory,
+            if self.nonlinear {
+                " (nonlinear: one per Newton iteration)"
+            } else {
+                ""
+            }
+        )?;
+        for cost in &self.analyses {
+            writeln!(
+                f,
+                "{:<6} {:>10} points {:>10} factorizations {:>12.3e} flops {:>12} bytes",
+                cost.analysis.name(),
+                cost.time_points,
+                cost.factorizations,
+                cost.flops,
+                cost.result_memory
+            )?;
+        }
+        write!(
+            f,
+            "total: {:.3e} flops, {} bytes",
+            self.total_flops(),
+            self.total_memory()
+        )
+    }
+}
+
+impl RunPlan {
+    pub fn new(circuit: Circuit, analyses: Vec<Analysis>) -> Self {
+        Self { circuit, analyses }
+    }
+
+    /// Serialize the plan as a JSON document
+    pub fn to_json(&self) -> Result<String, SchemaError> {
+        to_json(&v1::RunPlan::from(self))
+    }
+
+    /// Read a plan from a JSON document, with an error if its circuit
+    /// is not valid (see [v1::Circuit])
+    pub fn from_json(json: &str) -> Result<Self, SchemaError> {
+        Ok(from_json::<v1::RunPlan>(json)?.try_into()?)
+    }
+
+    /// Estimate the size and cost of the plan without solving
+    pub fn dry_run(&self) -> DryRun {
+        let circuit = self.circuit.elaborate();
+        let num_voltage_nodes = circuit.num_voltage_nodes();
+        let num_current_edges = circuit.num_current_edges();
+        let matrix_size = num_voltage_nodes + num_current_edges;
+        let mut pattern = sparsity_pattern(&circuit, num_voltage_nodes, matrix_size);
+        let nonzeros = pattern.iter().map(|row| row.len() + 1).sum();
+        let (factor_nonzeros, factorization_flops) = symbolic_factorization(&mut pattern);
+        let nonlinear = circuit.instances().iter().any(|instance| {
+            instance.component.junction().is_some()
+                || instance.component.transistor().is_some()
+                || matches!(
+                    instance.component,
+                    Component::SaturableInductor { .. }
+                        | Component::Thyristor { .. }
+                        | Component::Igbt { .. }
+                        | Component::Relay { .. }
+                        | Component::Fuse { .. }
+                )
+        });
+        let analyses = self
+            .analyses
+            .iter()
+            .map(|analysis| {
+                // A transient analysis starts from an operating point
+                let (time_points, factorizations) = match analysis {
+                    Analysis::OperatingPoint => (1, 1),
+                    // One solve at each point, after the operating
+                    // point of a nonlinear AC analysis
+                    Analysis::DcSweep { range, .. } => {
+                        let num_points = range.values().len();
+                        (num_points, num_points)
+                    }
+                    Analysis::Ac(sweep) => {
+                        let num_points = sweep.frequencies().len();
+                        (num_points, num_points + usize::from(nonlinear))
+                    }
+                    Analysis::Transient(options) => {
+                        // At least, since steps are added to land on the
+                        // breakpoints of the sources, and if the step is
+                        // controlled
+                        let num_steps = (options.stop_time / options.time_step).ceil() as usize;
+                        (num_steps + 1, num_steps + 1)
+                    }
+                };
+                AnalysisCost {
+                    analysis: analysis.clone(),
+                    time_points,
+                    factorizations,
+                    flops: factorizations as f64 * factorization_flops,
+                    // AC results are complex
+                    result_memory: time_points
+                        * (matrix_size + 1)
+                        * std::mem::size_of::<f64>()
+                        * if matches!(analysis, Analysis::Ac(_)) {
+                            2
+                        } else {
+                            1
+                        },
+                }
+            })
+            .collect();
+        DryRun {
+            num_voltage_nodes,
+            num_current_edges,
+            matrix_size,
+            nonzeros,
+            factor_nonzeros,
+            factorization_flops,
+            matrix_memory: (nonzeros + factor_nonzeros) * BYTES_PER_NONZERO,
+            nonlinear,
+            analyses,
+        }
+    }
+}
+
+/// Off-diagonal pattern of each row of the MNA matrix, made
+/// symmetric. Nodes are rows 0 to num_voltage_nodes - 1 (ground has no
+/// row), followed by the current edges.
+fn sparsity_pattern(
+    circuit: &Circuit,
+    num_voltage_nodes: usize,
+    matrix_size: usize,
+) -> Vec<BTreeSet<usize>> {
+    let mut pattern = vec![BTreeSet::new(); matrix_size];
+    for instance in circuit.instances() {
+        let component = &instance.component;
+        let rows: Vec<usize> = component
+            .terminals()
+            .into_iter()
+            .filter(|node| *node != 0)
+            .map(|node| node - 1)
+            .chain(
+                component
+                    .current_edges()
+                    .into_iter()
+                    .map(|edge| num_voltage_nodes + edge),
+            )
+            .collect();
+        for &row in &rows {
+            for &column in &rows {
+                if row != column {
+                    pattern[row].insert(column);
+                }
+            }
+        }
+    }
+    pattern
+}
+
+/// Eliminate the rows of a symmetric pattern in minimum degree order,
+/// returning the nonzeros of the factors and the floating point
+/// operations of the elimination
+fn symbolic_factorization(pattern: &mut [BTreeSet<usize>]) -> (usize, f64) {
+    let mut eliminated = vec![false; pattern.len()];
+    // Degrees can be out of date, in which case the row is pushed
+    // again with its new degree
+    let mut queue: BinaryHeap<Reverse<(usize, usize)>> = pattern
+        .iter()
+        .enumerate()
+        .map(|(row, neighbours)| Reverse((neighbours.len(), row)))
+        .collect();
+    let mut nonzeros = 0;
+    let mut flops = 0.0;
+    while let Some(Reverse((degree, row))) = queue.pop() {
+        if eliminated[row] || degree != pattern[row].len() {
+            continue;
+        }
+        eliminated[row] = true;
+        let neighbours = std::mem::take(&mut pattern[row]);
+        // The pivot, a column of L and a row of U, then a division for
+        // each entry of L and a multiply-add for each update
+        let d = neighbours.len();
+        nonzeros += 2 * d + 1;
+        flops += (d + 2 * d * d) as f64;
+        for &i in &neighbours {
+            pattern[i].remove(&row);
+            for &j in &neighbours {
+                if i != j {
+                    pattern[i].insert(j);
+                }
+            }
+            queue.push(Reverse((pattern[i].len(), i)));
+        }
+    }
+    (nonzeros, flops)
+}
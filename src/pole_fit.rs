@@ -0,0 +1,331 @@
+//! Pole and bandwidth estimation from AC data
+//!
+//! A low-order rational model $H(s) = N(s) / D(s)$ is fitted to a
+//! frequency response (such as a transfer function from a sweep of
+//! [LinearAcAnalysis](crate::ac::LinearAcAnalysis)), and its poles and
+//! zeros are found from the roots of the fitted polynomials. This
+//! gives the dominant pole and its Q without the eigenvalue analysis
+//! of the full circuit.
+//!
+//! The fit is the Sanathanan-Koerner iteration: the equation
+//! $N(s_k) - H_k D(s_k) = 0$ is solved in the least squares sense at
+//! the measured points, weighted by $1 / |D(s_k)|$ from the previous
+//! iteration, so that it converges to the fit of $H$ itself rather
+//! than of $H D$. The constant term of $D$ is one, so the model has no
+//! pole at zero frequency. The frequency is scaled by the geometric
+//! mean of the measured range for conditioning.
+//!
+//! The -3 dB bandwidth is read from the data itself (relative to the
+//! lowest frequency point), interpolated in log frequency.
+
+use std::f64::consts::PI;
+
+use num::Complex;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoleFitOptions {
+    pub num_poles: usize,
+    pub num_zeros: usize,
+    /// Number of reweighted least squares iterations
+    pub iterations: usize,
+}
+
+impl Default for PoleFitOptions {
+    fn default() -> Self {
+        Self {
+            num_poles: 2,
+            num_zeros: 0,
+            iterations: 20,
+        }
+    }
+}
+
+/// A rational model fitted to a frequency response
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoleFit {
+    /// Poles, in rad/s
+    pub poles: Vec<Complex<f64>>,
+    /// Zeros, in rad/s
+    pub zeros: Vec<Complex<f64>>,
+    /// Gain at zero frequency
+    pub dc_gain: f64,
+    /// Largest difference between the model and the data, relative to
+    /// the largest magnitude of the data
+    pub max_error: f64,
+    /// Angular frequency the polynomials are scaled by
+    omega_scale: f64,
+    /// Numerator and denominator coefficients in the scaled frequency,
+    /// lowest order first
+    numerator: Vec<f64>,
+    denominator: Vec<f64>,
+}
+
+impl PoleFit {
+    /// Response of the model at a frequency (in Hz)
+    pub fn response(&self, frequency: f64) -> Complex<f64> {
+        let s = Complex::new(0.0, 2.0 * PI * frequency / self.omega_scale);
+        polynomial(&self.numerator, s) / polynomial(&self.denominator, s)
+    }
+
+    /// The stable pole nearest the origin, if there is one
+    pub fn dominant_pole(&self) -> Option<Complex<f64>> {
+        self.poles
+            .iter()
+            .filter(|p| p.re < 0.0)
+            .min_by(|a, b| a.norm().total_cmp(&b.norm()))
+            .copied()
+    }
+
+    /// Natural frequency of the dominant pole (in Hz)
+    pub fn dominant_pole_frequency(&self) -> Option<f64> {
+        self.dominant_pole().map(|p| p.norm() / (2.0 * PI))
+    }
+
+    /// Quality factor of the dominant pole, if it is one of a complex
+    /// pair
+    pub fn q(&self) -> Option<f64> {
+        self.dominant_pole()
+            .filter(|p| p.im.abs() > 1e-9 * p.norm())
+            .map(|p| p.norm() / (2.0 * p.re.abs()))
+    }
+}
+
+/// Bandwidth and dominant pole of a frequency response
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandwidthEstimate {
+    /// Frequency (in Hz) at which the magnitude first falls 3 dB below
+    /// that at the lowest frequency, if it does within the data
+    pub f3db: Option<f64>,
+    /// Natural frequency of the dominant pole (in Hz)
+    pub dominant_pole_frequency: Option<f64>,
+    /// Quality factor of the dominant pole, if it is complex
+    pub q: Option<f64>,
+    pub fit: PoleFit,
+}
+
+/// Complex response from magnitudes and phases (in degrees)
+pub fn response_from_magnitude_phase(magnitude: &[f64], phase: &[f64]) -> Vec<Complex<f64>> {
+    magnitude
+        .iter()
+        .zip(phase)
+        .map(|(m, p)| Complex::from_polar(*m, p.to_radians()))
+        .collect()
+}
+
+/// Value of a polynomial (lowest order coefficient first)
+fn polynomial(coefficients: &[f64], s: Complex<f64>) -> Complex<f64> {
+    coefficients
+        .iter()
+        .rev()
+        .fold(Complex::new(0.0, 0.0), |sum, c| sum * s + c)
+}
+
+/// Least squares solution of a dense system with more rows than
+/// columns, by Householder QR. The matrix is row-major with the given
+/// number of columns. Columns are scaled to unit norm first.
+fn least_squares(mut a: Vec<f64>, mut b: Vec<f64>, num_columns: usize) -> Vec<f64> {
+    let num_rows = b.len();
+    let at = |row: usize, column: usize| row * num_columns + column;
+    let scales: Vec<f64> = (0..num_columns)
+        .map(|c| {
+            let norm = (0..num_rows)
+                .map(|r| a[at(r, c)].powi(2))
+                .sum::<f64>()
+                .sqrt();
+            if norm > 0.0 {
+                norm
+            } else {
+                1.0
+            }
+        })
+        .collect();
+    for r in 0..num_rows {
+        for c in 0..num_columns {
+            a[at(r, c)] /= scales[c];
+        }
+    }
+    for k in 0..num_columns {
+        let norm = (k..num_rows)
+            .map(|r| a[at(r, k)].powi(2))
+            .sum::<f64>()
+            .sqrt();
+        if norm == 0.0 {
+            continue;
+        }
+        let alpha = if a[at(k, k)] > 0.0 { -norm } else { norm };
+        let mut v: Vec<f64> = (k..num_rows).map(|r| a[at(r, k)]).collect();
+        v[0] -= alpha;
+        let vv: f64 = v.iter().map(|x| x * x).sum();
+        if vv == 0.0 {
+            continue;
+        }
+        for c in k..num_columns {
+            let dot: f64 = (k..num_rows).map(|r| v[r - k] * a[at(r, c)]).sum();
+            for r in k..num_rows {
+                a[at(r, c)] -= 2.0 * dot / vv * v[r - k];
+            }
+        }
+        let dot: f64 = (k..num_rows).map(|r| v[r - k] * b[r]).sum();
+        for r in k..num_rows {
+            b[r] -= 2.0 * dot / vv * v[r - k];
+        }
+    }
+    let mut x = vec![0.0; num_columns];
+    for k in (0..num_columns).rev() {
+        let sum: f64 = (k + 1..num_columns).map(|c| a[at(k, c)] * x[c]).sum();
+        x[k] = if a[at(k, k)] != 0.0 {
+            (b[k] - sum) / a[at(k, k)]
+        } else {
+            0.0
+        };
+    }
+    x.iter().zip(&scales).map(|(x, scale)| x / scale).collect()
+}
+
+/// Roots of a polynomial (lowest order coefficient first), by the
+/// Durand-Kerner iteration. Leading coefficients that are negligible
+/// are dropped.
+fn roots(coefficients: &[f64]) -> Vec<Complex<f64>> {
+    let largest = coefficients.iter().fold(0.0, |m: f64, c| m.max(c.abs()));
+    let degree = coefficients
+        .iter()
+        .rposition(|c| c.abs() > 1e-12 * largest)
+        .unwrap_or(0);
+    if degree == 0 {
+        return Vec::new();
+    }
+    let monic: Vec<f64> = coefficients[..=degree]
+        .iter()
+        .map(|c| c / coefficients[degree])
+        .collect();
+    let seed = Complex::new(0.4, 0.9);
+    let mut roots: Vec<Complex<f64>> = (0..degree).map(|k| seed.powu(k as u32)).collect();
+    for _ in 0..500 {
+        let mut change: f64 = 0.0;
+        for i in 0..degree {
+            let denominator: Complex<f64> = (0..degree)
+                .filter(|j| *j != i)
+                .map(|j| roots[i] - roots[j])
+                .product();
+            let step = polynomial(&monic, roots[i]) / denominator;
+            roots[i] -= step;
+            change = change.max(step.norm() / roots[i].norm().max(1.0));
+        }
+        if change < 1e-14 {
+            break;
+        }
+    }
+    roots
+}
+
+/// Fit a rational model to the response at each frequency (in Hz).
+/// Panics if there are fewer data points than model coefficients, or
+/// a frequency is not positive.
+pub fn fit_poles(
+    frequencies: &[f64],
+    response: &[Complex<f64>],
+    options: &PoleFitOptions,
+) -> PoleFit {
+    let PoleFitOptions {
+        num_poles,
+        num_zeros,
+        ..
+    } = *options;
+    let num_columns = num_zeros + 1 + num_poles;
+    assert!(
+        2 * frequencies.len() >= num_columns,
+        "Too few points to fit {num_poles} poles and {num_zeros} zeros"
+    );
+    assert!(
+        frequencies.iter().all(|f| *f > 0.0),
+        "Frequencies must be positive"
+    );
+    let (lowest, highest) = frequencies
+        .iter()
+        .fold((f64::INFINITY, 0.0_f64), |(lo, hi), f| {
+            (lo.min(*f), hi.max(*f))
+        });
+    let omega_scale = 2.0 * PI * (lowest * highest).sqrt();
+    let points: Vec<Complex<f64>> = frequencies
+        .iter()
+        .map(|f| Complex::new(0.0, 2.0 * PI * f / omega_scale))
+        .collect();
+
+    let mut numerator = vec![0.0; num_zeros + 1];
+    let mut denominator = vec![1.0];
+    denominator.resize(num_poles + 1, 0.0);
+    for _ in 0..options.iterations.max(1) {
+        let mut a = Vec::with_capacity(2 * points.len() * num_columns);
+        let mut b = Vec::with_capacity(2 * points.len());
+        for (s, h) in points.iter().zip(response) {
+            let weight = 1.0 / polynomial(&denominator, *s).norm();
+            let row: Vec<Complex<f64>> = (0..=num_zeros)
+                .map(|i| s.powu(i as u32))
+                .chain((1..=num_poles).map(|j| -h * s.powu(j as u32)))
+                .map(|x| x * weight)
+                .collect();
+            a.extend(row.iter().map(|x| x.re));
+            a.extend(row.iter().map(|x| x.im));
+            b.push(h.re * weight);
+            b.push(h.im * weight);
+        }
+        // Rows were pushed as a real row then an imaginary row, each of
+        // num_columns entries
+        let x = least_squares(a, b, num_columns);
+        numerator = x[..=num_zeros].to_vec();
+        denominator = [1.0]
+            .into_iter()
+            .chain(x[num_zeros + 1..].iter().copied())
+            .collect();
+    }
+
+    let largest = response.iter().fold(0.0, |m: f64, h| m.max(h.norm()));
+    let max_error = points
+        .iter()
+        .zip(response)
+        .map(|(s, h)| (polynomial(&numerator, *s) / polynomial(&denominator, *s) - h).norm())
+        .fold(0.0, f64::max)
+        / largest.max(f64::MIN_POSITIVE);
+    PoleFit {
+        poles: roots(&denominator)
+            .iter()
+            .map(|p| p * omega_scale)
+            .collect(),
+        zeros: roots(&numerator).iter().map(|z| z * omega_scale).collect(),
+        dc_gain: numerator[0],
+        max_error,
+        omega_scale,
+        numerator,
+        denominator,
+    }
+}
+
+/// Frequency at which the magnitude first falls 3 dB below that at the
+/// first point, interpolated in log frequency
+fn f3db(frequencies: &[f64], response: &[Complex<f64>]) -> Option<f64> {
+    let db: Vec<f64> = response.iter().map(|h| 20.0 * h.norm().log10()).collect();
+    let target = db.first()? - 10.0 * 2.0_f64.log10();
+    let k = db.iter().position(|level| *level < target)?;
+    if k == 0 {
+        return None;
+    }
+    let (f0, f1) = (frequencies[k - 1].ln(), frequencies[k].ln());
+    let fraction = (target - db[k - 1]) / (db[k] - db[k - 1]);
+    Some((f0 + fraction * (f1 - f0)).exp())
+}
+
+/// Estimate the bandwidth and dominant pole of a frequency response,
+/// given at increasing frequencies (in Hz)
+pub fn estimate_bandwidth(
+    frequencies: &[f64],
+    response: &[Complex<f64>],
+    options: &PoleFitOptions,
+) -> BandwidthEstimate {
+    let fit = fit_poles(frequencies, response, options);
+    BandwidthEstimate {
+        f3db: f3db(frequencies, response),
+        dominant_pole_frequency: fit.dominant_pole_frequency(),
+        q: fit.q(),
+        fit,
+    }
+}
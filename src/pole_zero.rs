@@ -0,0 +1,478 @@
+//! Pole-zero analysis
+//!
+//! The poles and zeros of the transfer function from an input source
+//! to an output voltage, as for a SPICE `.PZ` line, found from the MNA
+//! matrices of the circuit linearised about its operating point rather
+//! than fitted to sampled AC data (as in [pole_fit](crate::pole_fit)).
+//!
+//! After elaboration every component is stamped in the AC system as a
+//! conductance or a capacitance (or inductance), so the matrix at the
+//! complex frequency $s$ is $A(s) = G + sC$. The poles are the values
+//! of $s$ at which $A(s)$ is singular. Shifting to a frequency $s_0$ at
+//! which it is not, these are $s = s_0 - 1/\mu$ for the non-zero
+//! eigenvalues $\mu$ of $(G + s_0 C)^{-1} C$ (a zero eigenvalue is a
+//! pole at infinity). The zeros are found in the same way from the
+//! system with the input and output appended as a row and column,
+//!
+//! $$\begin{pmatrix} G + sC & b \\ c^T & 0 \end{pmatrix},$$
+//!
+//! which is singular where the transfer $c^T A(s)^{-1} b$ is zero. The
+//! eigenvalues are found by the shifted QR iteration on the dense
+//! matrix, so the analysis is for circuits of up to a few hundred
+//! unknowns.
+
+use std::f64::consts::PI;
+
+use num::Complex;
+
+use crate::ac::LinearAcAnalysis;
+use crate::circuit::Circuit;
+use crate::component::Component;
+use crate::dc::{explain, DcOptions};
+use crate::error::EsimError;
+use crate::mna::Mna;
+
+/// Dense complex matrix, by rows
+type Matrix = Vec<Vec<Complex<f64>>>;
+
+/// LU factors of a matrix (in place) and the row of each pivot
+type Factors = (Matrix, Vec<usize>);
+
+/// Multiples of the frequency scale of the circuit tried as the shift,
+/// in turn, until the shifted matrix is not singular
+const SHIFTS: [f64; 4] = [-1.0, -0.731, -1.377, -0.413];
+
+/// Size of an entry, relative to the largest, below which it is zero
+const NEGLIGIBLE: f64 = 1e-12;
+
+/// Limit on the QR iterations for each eigenvalue
+const MAX_QR_ITERATIONS: usize = 100;
+
+/// Poles and zeros of a transfer function
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoleZeroResult {
+    /// Poles, in rad/s, in order of magnitude
+    pub poles: Vec<Complex<f64>>,
+    /// Zeros, in rad/s, in order of magnitude
+    pub zeros: Vec<Complex<f64>>,
+    /// Gain $k$ of the transfer function
+    /// $k \prod (s - z_i) / \prod (s - p_i)$ (zero if the output does
+    /// not depend on the input)
+    pub gain: f64,
+}
+
+impl PoleZeroResult {
+    /// Transfer function at a frequency (in Hz)
+    pub fn response(&self, frequency: f64) -> Complex<f64> {
+        self.transfer(Complex::new(0.0, 2.0 * PI * frequency))
+    }
+
+    /// Transfer function at a complex frequency (in rad/s)
+    pub fn transfer(&self, s: Complex<f64>) -> Complex<f64> {
+        let numerator: Complex<f64> = self.zeros.iter().map(|z| s - z).product();
+        let denominator: Complex<f64> = self.poles.iter().map(|p| s - p).product();
+        self.gain * numerator / denominator
+    }
+
+    /// Whether every pole is in the left half plane
+    pub fn is_stable(&self) -> bool {
+        self.poles.iter().all(|p| p.re < 0.0)
+    }
+}
+
+/// Dense matrix of an MNA system
+fn dense(mna: &Mna<Complex<f64>>, size: usize) -> Matrix {
+    let mut matrix = vec![vec![Complex::new(0.0, 0.0); size]; size];
+    for ((row, column), value) in mna.entries() {
+        matrix[row][column] += value;
+    }
+    matrix
+}
+
+fn largest(matrix: &Matrix) -> f64 {
+    matrix
+        .iter()
+        .flatten()
+        .fold(0.0, |m: f64, x| m.max(x.norm()))
+}
+
+/// LU factorization with partial pivoting, or None if the matrix is
+/// singular
+fn factorize(mut a: Matrix) -> Option<Factors> {
+    let size = a.len();
+    let tolerance = NEGLIGIBLE * NEGLIGIBLE * largest(&a);
+    let mut pivots = Vec::with_capacity(size);
+    for k in 0..size {
+        let pivot = (k..size).max_by(|i, j| a[*i][k].norm().total_cmp(&a[*j][k].norm()))?;
+        if a[pivot][k].norm() <= tolerance {
+            return None;
+        }
+        a.swap(k, pivot);
+        pivots.push(pivot);
+        let (upper, lower) = a.split_at_mut(k + 1);
+        let pivot_row = &upper[k];
+        for row in lower.iter_mut() {
+            let factor = row[k] / pivot_row[k];
+            row[k] = factor;
+            for (x, p) in row[k + 1..].iter_mut().zip(&pivot_row[k + 1..]) {
+                *x -= factor * p;
+            }
+        }
+    }
+    Some((a, pivots))
+}
+
+/// Solve a factorized system
+fn substitute((lu, pivots): &Factors, mut b: Vec<Complex<f64>>) -> Vec<Complex<f64>> {
+    let size = lu.len();
+    for (k, pivot) in pivots.iter().enumerate() {
+        b.swap(k, *pivot);
+    }
+    for i in 0..size {
+        let sum: Complex<f64> = (0..i).map(|j| lu[i][j] * b[j]).sum();
+        b[i] -= sum;
+    }
+    for i in (0..size).rev() {
+        let sum: Complex<f64> = (i + 1..size).map(|j| lu[i][j] * b[j]).sum();
+        b[i] = (b[i] - sum) / lu[i][i];
+    }
+    b
+}
+
+/// Complex Givens rotation (c, s) taking (a, b) to (r, 0)
+fn givens(a: Complex<f64>, b: Complex<f64>) -> (f64, Complex<f64>) {
+    let r = a.norm().hypot(b.norm());
+    if r == 0.0 {
+        (1.0, Complex::new(0.0, 0.0))
+    } else if a.norm() == 0.0 {
+        (0.0, Complex::new(1.0, 0.0))
+    } else {
+        (a.norm() / r, a / a.norm() * b.conj() / r)
+    }
+}
+
+/// Eigenvalues of a dense matrix, by reduction to Hessenberg form
+/// with Householder reflections and the QR iteration with Wilkinson
+/// shifts
+fn eigenvalues(mut h: Matrix) -> Vec<Complex<f64>> {
+    let size = h.len();
+    for k in 0..size.saturating_sub(2) {
+        let norm = (k + 1..size)
+            .map(|i| h[i][k].norm_sqr())
+            .sum::<f64>()
+            .sqrt();
+        if norm == 0.0 {
+            continue;
+        }
+        let x = h[k + 1][k];
+        let phase = if x.norm() == 0.0 {
+            Complex::new(1.0, 0.0)
+        } else {
+            x / x.norm()
+        };
+        let mut v: Vec<Complex<f64>> = (k + 1..size).map(|i| h[i][k]).collect();
+        v[0] += phase * norm;
+        let v_norm = v.iter().map(|x| x.norm_sqr()).sum::<f64>().sqrt();
+        v.iter_mut().for_each(|x| *x /= v_norm);
+        // H = (I - 2vv*) H (I - 2vv*)
+        let dots: Vec<Complex<f64>> = (0..size)
+            .map(|j| (k + 1..size).map(|i| v[i - k - 1].conj() * h[i][j]).sum())
+            .collect();
+        for (row, v) in h[k + 1..].iter_mut().zip(&v) {
+            for (x, dot) in row.iter_mut().zip(&dots) {
+                *x -= 2.0 * v * dot;
+            }
+        }
+        for row in h.iter_mut() {
+            let dot: Complex<f64> = (k + 1..size).map(|j| row[j] * v[j - k - 1]).sum();
+            for j in k + 1..size {
+                row[j] -= 2.0 * dot * v[j - k - 1].conj();
+            }
+        }
+    }
+    let mut eigenvalues = Vec::with_capacity(size);
+    let mut hi = size;
+    let mut iterations = 0;
+    while hi > 0 {
+        let last = hi - 1;
+        // Start of the unreduced block ending at the last row
+        let mut lo = last;
+        while lo > 0 {
+            let scale = h[lo][lo].norm() + h[lo - 1][lo - 1].norm();
+            if h[lo][lo - 1].norm() <= f64::EPSILON * scale {
+                h[lo][lo - 1] = Complex::new(0.0, 0.0);
+                break;
+            }
+            lo -= 1;
+        }
+        if lo == last || iterations > MAX_QR_ITERATIONS {
+            eigenvalues.push(h[last][last]);
+            hi = last;
+            iterations = 0;
+            continue;
+        }
+        iterations += 1;
+        let (a, b, c, d) = (
+            h[last - 1][last - 1],
+            h[last - 1][last],
+            h[last][last - 1],
+            h[last][last],
+        );
+        let shift = if iterations % 10 == 0 {
+            // Exceptional shift, to break cycles
+            d + h[last][last - 1].norm()
+        } else {
+            let half = (a - d) / 2.0;
+            let root = (half * half + b * c).sqrt();
+            let (one, other) = ((a + d) / 2.0 + root, (a + d) / 2.0 - root);
+            if (one - d).norm() < (other - d).norm() {
+                one
+            } else {
+                other
+            }
+        };
+        for (k, row) in h.iter_mut().enumerate().take(hi).skip(lo) {
+            row[k] -= shift;
+        }
+        let mut rotations = Vec::with_capacity(last - lo);
+        for k in lo..last {
+            let (c, s) = givens(h[k][k], h[k + 1][k]);
+            let (upper, lower) = h.split_at_mut(k + 1);
+            for (x, y) in upper[k][k..hi].iter_mut().zip(&mut lower[0][k..hi]) {
+                (*x, *y) = (c * *x + s * *y, -s.conj() * *x + c * *y);
+            }
+            rotations.push((c, s));
+        }
+        for (k, (c, s)) in (lo..last).zip(rotations) {
+            for row in h[lo..=(k + 2).min(last)].iter_mut() {
+                let (x, y) = (row[k], row[k + 1]);
+                row[k] = x * c + y * s.conj();
+                row[k + 1] = -x * s + y * c;
+            }
+        }
+        for (k, row) in h.iter_mut().enumerate().take(hi).skip(lo) {
+            row[k] += shift;
+        }
+    }
+    eigenvalues
+}
+
+/// The finite values of s at which the pencil G + sC is singular,
+/// shifted to s0 (at which it is not, as factorized), in order of
+/// magnitude
+fn generalized_eigenvalues(factors: &Factors, c: &Matrix, s0: Complex<f64>) -> Vec<Complex<f64>> {
+    let size = c.len();
+    // Columns of (G + s0 C)^-1 C, which are zero where C is
+    let columns: Vec<Option<Vec<Complex<f64>>>> = (0..size)
+        .map(|j| {
+            let column: Vec<Complex<f64>> = c.iter().map(|row| row[j]).collect();
+            column
+                .iter()
+                .any(|x| x.norm() > 0.0)
+                .then(|| substitute(factors, column))
+        })
+        .collect();
+    let mut active: Vec<usize> = (0..size).filter(|j| columns[*j].is_some()).collect();
+    let entry = |i: usize, j: usize| columns[j].as_ref().map_or(Complex::new(0.0, 0.0), |c| c[i]);
+    let scale = active
+        .iter()
+        .flat_map(|j| active.iter().map(move |i| (*i, *j)))
+        .fold(0.0, |m: f64, (i, j)| m.max(entry(i, j).norm()));
+    // A zero row or column of the shifted matrix is a zero eigenvalue,
+    // so it is removed, which leaves the other eigenvalues and avoids
+    // the sensitivity of repeated zero eigenvalues to rounding
+    let negligible = |x: Complex<f64>| x.norm() <= NEGLIGIBLE * scale;
+    while let Some(position) = active.iter().position(|k| {
+        active.iter().all(|j| negligible(entry(*k, *j)))
+            || active.iter().all(|i| negligible(entry(*i, *k)))
+    }) {
+        active.remove(position);
+    }
+    let shifted: Matrix = active
+        .iter()
+        .map(|i| active.iter().map(|j| entry(*i, *j)).collect())
+        .collect();
+    let mut values: Vec<Complex<f64>> = eigenvalues(shifted)
+        .into_iter()
+        .filter(|mu| !negligible(*mu))
+        .map(|mu| s0 - 1.0 / mu)
+        .map(|s| {
+            // Round off a value at zero, or the imaginary part of a
+            // real value
+            if s.norm() <= 1e-9 * s0.norm() {
+                Complex::new(0.0, 0.0)
+            } else if s.im.abs() <= 1e-9 * s.norm() {
+                Complex::new(s.re, 0.0)
+            } else {
+                s
+            }
+        })
+        .collect();
+    values.sort_by(|a, b| a.norm().total_cmp(&b.norm()).then(a.im.total_cmp(&b.im)));
+    values
+}
+
+/// Find a shift at which the pencil G + sC is not singular, and
+/// factorize it there
+fn shift(g: &Matrix, c: &Matrix, scale: f64) -> Option<(Complex<f64>, Factors)> {
+    SHIFTS.iter().find_map(|multiple| {
+        let s0 = Complex::new(multiple * scale, 0.0);
+        let a: Matrix = g
+            .iter()
+            .zip(c)
+            .map(|(g, c)| g.iter().zip(c).map(|(g, c)| g + s0 * c).collect())
+            .collect();
+        factorize(a).map(|factors| (s0, factors))
+    })
+}
+
+/// Poles and zeros of the transfer function from an independent
+/// source (by name) to the voltage from output_pos to output_neg.
+/// Panics if the input is not an independent source, or the circuit
+/// is singular at every frequency (see [try_pole_zero]).
+pub fn pole_zero(circuit: &Circuit, output: (usize, usize), input: &str) -> PoleZeroResult {
+    pole_zero_with(&LinearAcAnalysis::new(circuit), output, input)
+        .unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Poles and zeros as for [pole_zero], returning an error if the
+/// circuit fails its check (see [Circuit::validate]), its operating
+/// point cannot be solved, the input is not an independent source, an
+/// output node is not in the circuit, or the circuit is singular at
+/// every frequency
+pub fn try_pole_zero(
+    circuit: &Circuit,
+    output: (usize, usize),
+    input: &str,
+) -> Result<PoleZeroResult, EsimError> {
+    let analysis = LinearAcAnalysis::try_new(circuit, &DcOptions::default())?;
+    analysis.check_output(output)?;
+    pole_zero_with(&analysis, output, input).map_err(|error| explain(circuit, error))
+}
+
+fn pole_zero_with(
+    analysis: &LinearAcAnalysis,
+    (output_pos, output_neg): (usize, usize),
+    input: &str,
+) -> Result<PoleZeroResult, EsimError> {
+    let input = analysis.input_source(input)?.clone();
+    // The matrix is G + j omega C, so G is the matrix at zero frequency
+    // and C the change at one radian per second
+    let zero = analysis.assemble(0.0, false);
+    let one = analysis.assemble(1.0 / (2.0 * PI), false);
+    let num_voltage_nodes = zero.num_voltage_nodes();
+    let size = zero.rhs().len();
+    let g = dense(&zero, size);
+    let c: Matrix = dense(&one, size)
+        .into_iter()
+        .zip(&g)
+        .map(|(a, g)| {
+            a.iter()
+                .zip(g)
+                .map(|(a, g)| (a - g) / Complex::new(0.0, 1.0))
+                .collect()
+        })
+        .collect();
+    let mut b = vec![Complex::new(0.0, 0.0); size];
+    match input {
+        Component::IndependentVoltageSource { current_edge, .. } => {
+            b[num_voltage_nodes + current_edge] = Complex::new(1.0, 0.0)
+        }
+        // The source current is injected into the negative terminal
+        Component::IndependentCurrentSource {
+            term_pos, term_neg, ..
+        } => {
+            if term_pos != 0 {
+                b[term_pos - 1] = Complex::new(-1.0, 0.0);
+            }
+            if term_neg != 0 {
+                b[term_neg - 1] = Complex::new(1.0, 0.0);
+            }
+        }
+        _ => unreachable!("The input is an independent source"),
+    }
+    let mut output = vec![Complex::new(0.0, 0.0); size];
+    if output_pos != 0 {
+        output[output_pos - 1] = Complex::new(1.0, 0.0);
+    }
+    if output_neg != 0 {
+        output[output_neg - 1] = Complex::new(-1.0, 0.0);
+    }
+    // Frequency at which the conductances and capacitances are
+    // comparable
+    let scale = match largest(&c) {
+        0.0 => 1.0,
+        c => largest(&g) / c,
+    };
+    let (s0, factors) = shift(&g, &c, scale).ok_or_else(|| EsimError::Solve {
+        message: "Circuit is singular at every frequency".to_string(),
+    })?;
+    let poles = generalized_eigenvalues(&factors, &c, s0);
+
+    // The system with the input and output appended, singular at the
+    // zeros
+    let append = |matrix: &Matrix, column: &[Complex<f64>], row: &[Complex<f64>]| -> Matrix {
+        matrix
+            .iter()
+            .zip(column)
+            .map(|(r, x)| r.iter().chain([x]).copied().collect())
+            .chain([row
+                .iter()
+                .copied()
+                .chain([Complex::new(0.0, 0.0)])
+                .collect()])
+            .collect()
+    };
+    let zeros_column = vec![Complex::new(0.0, 0.0); size];
+    let zeros_g = append(&g, &b, &output);
+    let zeros_c = append(&c, &zeros_column, &zeros_column);
+    // Singular at every frequency if the output does not depend on
+    // the input
+    let Some((z0, zero_factors)) = shift(&zeros_g, &zeros_c, scale) else {
+        return Ok(PoleZeroResult {
+            poles,
+            zeros: Vec::new(),
+            gain: 0.0,
+        });
+    };
+    let zeros = generalized_eigenvalues(&zero_factors, &zeros_c, z0);
+
+    // The gain from the transfer at the shift
+    let solution = substitute(&factors, b);
+    let transfer: Complex<f64> = output.iter().zip(&solution).map(|(c, x)| c * x).sum();
+    let numerator: Complex<f64> = zeros.iter().map(|z| s0 - z).product();
+    let denominator: Complex<f64> = poles.iter().map(|p| s0 - p).product();
+    Ok(PoleZeroResult {
+        poles,
+        zeros,
+        gain: (transfer * denominator / numerator).re,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CircuitBuilder;
+
+    #[test]
+    fn an_rc_filter_has_a_pole_at_its_time_constant() {
+        // 1k and 1u, so a pole at -1/RC = -1000 rad/s, no zeros and a
+        // gain of 1/RC
+        let circuit = CircuitBuilder::new()
+            .vsource("V1", "in", "0", 0.0)
+            .resistor("R1", "in", "out", 1e3)
+            .capacitor("C1", "out", "0", 1e-6)
+            .build()
+            .unwrap();
+        let out = circuit.node_names().get("out").unwrap();
+        let result = pole_zero(&circuit, (out, 0), "V1");
+        assert_eq!(result.poles.len(), 1);
+        assert!((result.poles[0] - Complex::new(-1e3, 0.0)).norm() < 1e-6);
+        assert!(result.zeros.is_empty());
+        assert!((result.gain - 1e3).abs() < 1e-6);
+        assert!(result.is_stable());
+        // Half power at the pole frequency
+        let corner = result.response(1e3 / (2.0 * PI));
+        assert!((corner.norm() - 0.5f64.sqrt()).abs() < 1e-9);
+    }
+}
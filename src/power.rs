@@ -0,0 +1,449 @@
+//! Power analysis
+//!
+//! The power absorbed by each instance, from a solution of the
+//! circuit: at the DC operating point, or at each time point of a
+//! transient analysis, from which follow the average powers over the
+//! analysis. The power of an instance is the sum over its terminals of
+//! the voltage times the current into the terminal, so it is positive
+//! when the instance dissipates or stores energy and negative when it
+//! delivers it.
+//!
+//! The independent sources, batteries, DAC bridges and controlled
+//! sources (whose power comes from outside the circuit) are the sources
+//! of the circuit, whose delivered power (the negative of the absorbed
+//! power) is the input for an efficiency: the efficiency of a set of
+//! loads is their average absorbed power as a fraction of the average
+//! power delivered by the sources.
+//!
+//! The powers are of the instances of the elaborated circuit, so a
+//! macromodel (such as a crystal) appears as the instances it expands
+//! to, whose names are prefixed by its own; these are summed when the
+//! power of the macromodel is asked for by name. The current of a
+//! capacitor in a transient analysis is from the change in its voltage
+//! since the previous time point, which is exact for backward Euler.
+//! The components take the values in the circuit, so the powers do not
+//! follow changes scheduled during the transient analysis.
+
+use std::fmt;
+
+use crate::circuit::Circuit;
+use crate::component::{Component, NOMINAL_TEMPERATURE};
+use crate::dc::DcSolution;
+use crate::error::{no_instance, EsimError};
+use crate::transient::TransientResult;
+
+/// Power absorbed by one instance
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstancePower {
+    /// Name of the instance in the elaborated circuit
+    pub name: String,
+    /// Whether the instance is a source of the circuit
+    pub source: bool,
+    /// Power absorbed at each time point
+    pub power: Vec<f64>,
+}
+
+/// Power absorbed by each instance of a circuit
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowerResult {
+    /// Time points (just zero at the operating point)
+    pub time: Vec<f64>,
+    pub instances: Vec<InstancePower>,
+}
+
+impl PowerResult {
+    /// Average of a quantity over the time points, by the trapezoidal
+    /// rule (the value itself if there is one time point)
+    fn average(&self, values: &[f64]) -> f64 {
+        let duration = self.time.last().unwrap_or(&0.0) - self.time.first().unwrap_or(&0.0);
+        if duration <= 0.0 {
+            return values.first().copied().unwrap_or(0.0);
+        }
+        let integral: f64 = self
+            .time
+            .windows(2)
+            .zip(values.windows(2))
+            .map(|(t, p)| 0.5 * (p[0] + p[1]) * (t[1] - t[0]))
+            .sum();
+        integral / duration
+    }
+
+    /// Power absorbed by an instance at each time point, summed over
+    /// the instances a macromodel expands to
+    pub fn power(&self, name: &str) -> Option<Vec<f64>> {
+        let prefix = format!("{name}.");
+        self.instances
+            .iter()
+            .filter(|i| i.name == name || i.name.starts_with(&prefix))
+            .map(|i| i.power.clone())
+            .reduce(|total, power| total.iter().zip(power).map(|(a, b)| a + b).collect())
+    }
+
+    /// Average power absorbed by an instance
+    pub fn average_power(&self, name: &str) -> Option<f64> {
+        self.power(name).map(|power| self.average(&power))
+    }
+
+    /// Average power delivered by the sources
+    pub fn source_power(&self) -> f64 {
+        self.instances
+            .iter()
+            .filter(|i| i.source)
+            .map(|i| -self.average(&i.power))
+            .sum()
+    }
+
+    /// Average power absorbed by the instances other than the sources
+    pub fn dissipated_power(&self) -> f64 {
+        self.instances
+            .iter()
+            .filter(|i| !i.source)
+            .map(|i| self.average(&i.power))
+            .sum()
+    }
+
+    /// Average power absorbed by a set of loads, as a fraction of the
+    /// average power delivered by the sources. Panics if there is no
+    /// instance with one of the names (see [PowerResult::try_efficiency]).
+    pub fn efficiency(&self, loads: &[&str]) -> f64 {
+        self.try_efficiency(loads)
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Efficiency as for [PowerResult::efficiency], or an error if
+    /// there is no instance with one of the names
+    pub fn try_efficiency(&self, loads: &[&str]) -> Result<f64, EsimError> {
+        let load = loads
+            .iter()
+            .map(|name| self.average_power(name).ok_or_else(|| no_instance(name)))
+            .sum::<Result<f64, _>>()?;
+        Ok(load / self.source_power())
+    }
+}
+
+impl fmt::Display for PowerResult {
+    /// The average power of each instance, then the totals
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<20} {:>14}", "Instance", "Power (W)")?;
+        for instance in &self.instances {
+            let power = self.average(&instance.power);
+            writeln!(f, "{:<20} {:>14.6e}", instance.name, power)?;
+        }
+        writeln!(
+            f,
+            "{:<20} {:>14.6e}",
+            "Sources delivered",
+            self.source_power()
+        )?;
+        writeln!(f, "{:<20} {:>14.6e}", "Dissipated", self.dissipated_power())
+    }
+}
+
+/// Whether a component is a source of the circuit
+fn is_source(component: &Component) -> bool {
+    matches!(
+        component,
+        Component::IndependentVoltageSource { .. }
+            | Component::IndependentCurrentSource { .. }
+            | Component::Battery { .. }
+            | Component::DacBridge { .. }
+            | Component::VoltageControlledVoltageSource { .. }
+            | Component::VoltageControlledCurrentSource { .. }
+            | Component::CurrentControlledCurrentSource { .. }
+            | Component::CurrentControlledVoltageSource { .. }
+    )
+}
+
+/// Power absorbed by a component of the elaborated circuit, from the
+/// node voltages and edge currents at a time (none at the operating
+/// point), and those at the previous time point (for the capacitors),
+/// if any
+fn absorbed(
+    component: &Component,
+    time: Option<f64>,
+    (voltages, currents): (&[f64], &[f64]),
+    previous: Option<(&[f64], f64)>,
+) -> f64 {
+    let v = |node: usize| match node {
+        0 => 0.0,
+        n => voltages[n - 1],
+    };
+    match *component {
+        Component::Resistor {
+            term_1,
+            term_2,
+            current_edge: None,
+            resistance,
+        } => (v(term_1) - v(term_2)).powi(2) / resistance,
+        Component::Thermistor {
+            term_1,
+            term_2,
+            current_edge: None,
+            model,
+            temperature,
+        } => {
+            (v(term_1) - v(term_2)).powi(2)
+                / model.resistance(temperature.unwrap_or(NOMINAL_TEMPERATURE))
+        }
+        Component::Resistor {
+            term_1,
+            term_2,
+            current_edge: Some(current_edge),
+            ..
+        }
+        | Component::Thermistor {
+            term_1,
+            term_2,
+            current_edge: Some(current_edge),
+            ..
+        }
+        | Component::Inductor {
+            term_1,
+            term_2,
+            current_edge,
+            ..
+        }
+        | Component::SaturableInductor {
+            term_1,
+            term_2,
+            current_edge,
+            ..
+        }
+        | Component::Fuse {
+            term_1,
+            term_2,
+            current_edge,
+            ..
+        } => (v(term_1) - v(term_2)) * currents[current_edge],
+        Component::Capacitor {
+            term_1,
+            term_2,
+            capacitance,
+        } => match previous {
+            Some((before, step)) => {
+                let voltage = |voltages: &[f64]| {
+                    let node = |node: usize| match node {
+                        0 => 0.0,
+                        n => voltages[n - 1],
+                    };
+                    node(term_1) - node(term_2)
+                };
+                let now = voltage(voltages);
+                now * capacitance * (now - voltage(before)) / step
+            }
+            None => 0.0,
+        },
+        Component::Diode { .. }
+        | Component::Photodiode { .. }
+        | Component::TunnelDiode { .. }
+        | Component::SchottkyDiode { .. }
+        | Component::Table { .. }
+        | Component::Compact { .. } => {
+            let (anode, cathode, junction) = component.junction().unwrap();
+            let voltage = v(anode) - v(cathode);
+            let mut current = junction.evaluate(voltage).0;
+            if let Component::Photodiode {
+                responsivity,
+                irradiance,
+                ..
+            } = *component
+            {
+                current -= responsivity * irradiance;
+            }
+            voltage * current
+        }
+        Component::Bjt { .. } | Component::Mosfet { .. } => {
+            let (nodes, transistor) = component.transistor().unwrap();
+            let voltage = |(pos, neg): (usize, usize)| v(nodes[pos]) - v(nodes[neg]);
+            transistor
+                .currents(transistor.controls().map(voltage))
+                .iter()
+                .map(|current| voltage(current.terminals) * current.current)
+                .sum()
+        }
+        Component::NPort {
+            ref ports,
+            ref data,
+        } => {
+            let conductance = data.conductance();
+            let port = |(pos, neg): (usize, usize)| v(pos) - v(neg);
+            ports
+                .iter()
+                .enumerate()
+                .map(|(i, output)| {
+                    let current: f64 = ports
+                        .iter()
+                        .enumerate()
+                        .map(|(j, control)| conductance[i * ports.len() + j] * port(*control))
+                        .sum();
+                    port(*output) * current
+                })
+                .sum()
+        }
+        Component::Relay {
+            coil_pos,
+            coil_neg,
+            contact_1,
+            contact_2,
+            coil_edge,
+            contact_edge,
+            ..
+        } => {
+            (v(coil_pos) - v(coil_neg)) * currents[coil_edge]
+                + (v(contact_1) - v(contact_2)) * currents[contact_edge]
+        }
+        Component::Thyristor {
+            anode,
+            cathode,
+            gate,
+            current_edge,
+            params,
+            ..
+        } => {
+            (v(anode) - v(cathode)) * currents[current_edge]
+                + (v(gate) - v(cathode)).powi(2) / params.r_gate
+        }
+        Component::Igbt {
+            collector,
+            emitter,
+            current_edge,
+            ..
+        } => (v(collector) - v(emitter)) * currents[current_edge],
+        Component::Battery {
+            term_pos,
+            term_neg,
+            current_edge,
+            ..
+        }
+        | Component::DacBridge {
+            term_pos,
+            term_neg,
+            current_edge,
+            ..
+        }
+        | Component::IndependentVoltageSource {
+            term_pos,
+            term_neg,
+            current_edge,
+            ..
+        }
+        | Component::CurrentProbe {
+            term_pos,
+            term_neg,
+            current_edge,
+        }
+        | Component::MeasurementProbe {
+            term_pos,
+            term_neg,
+            current_edge: Some(current_edge),
+            ..
+        }
+        | Component::VoltageControlledVoltageSource {
+            term_pos,
+            term_neg,
+            current_edge,
+            ..
+        }
+        | Component::CurrentControlledVoltageSource {
+            term_pos,
+            term_neg,
+            current_edge,
+            ..
+        } => (v(term_pos) - v(term_neg)) * currents[current_edge],
+        Component::VoltageControlledCurrentSource {
+            term_pos,
+            term_neg,
+            ctrl_pos,
+            ctrl_neg,
+            transconductance,
+        } => (v(term_pos) - v(term_neg)) * transconductance * (v(ctrl_pos) - v(ctrl_neg)),
+        Component::CurrentControlledCurrentSource {
+            term_pos,
+            term_neg,
+            ctrl_edge,
+            gain,
+        } => (v(term_pos) - v(term_neg)) * gain * currents[ctrl_edge],
+        Component::IndependentCurrentSource {
+            term_pos,
+            term_neg,
+            current,
+            ref waveform,
+            ..
+        } => {
+            let current = match (waveform, time) {
+                (Some(waveform), Some(time)) => waveform.value(time),
+                _ => current,
+            };
+            (v(term_pos) - v(term_neg)) * current
+        }
+        Component::LogicGate { .. } | Component::AdcBridge { .. } => 0.0,
+        Component::MeasurementProbe { .. }
+        | Component::Crystal { .. }
+        | Component::Urc { .. }
+        | Component::Supercapacitor { .. }
+        | Component::SemiconductorResistor { .. }
+        | Component::SemiconductorCapacitor { .. } => {
+            unreachable!("Macromodels are expanded by elaborate()")
+        }
+    }
+}
+
+/// Power of each instance at the DC operating point of a circuit
+pub fn operating_point_power(circuit: &Circuit, solution: &DcSolution) -> PowerResult {
+    let circuit = circuit.elaborate();
+    let instances = circuit
+        .instances()
+        .iter()
+        .map(|instance| InstancePower {
+            name: instance.name.clone(),
+            source: is_source(&instance.component),
+            power: vec![absorbed(
+                &instance.component,
+                None,
+                (&solution.voltages, &solution.currents),
+                None,
+            )],
+        })
+        .collect();
+    PowerResult {
+        time: vec![0.0],
+        instances,
+    }
+}
+
+/// Power of each instance at each time point of a transient analysis
+/// of a circuit
+pub fn transient_power(circuit: &Circuit, result: &TransientResult) -> PowerResult {
+    let circuit = circuit.elaborate();
+    let instances = circuit
+        .instances()
+        .iter()
+        .map(|instance| {
+            let power = (0..result.time.len())
+                .map(|point| {
+                    let previous = point.checked_sub(1).map(|before| {
+                        (
+                            result.voltages[before].as_slice(),
+                            result.time[point] - result.time[before],
+                        )
+                    });
+                    absorbed(
+                        &instance.component,
+                        Some(result.time[point]),
+                        (&result.voltages[point], &result.currents[point]),
+                        previous,
+                    )
+                })
+                .collect();
+            InstancePower {
+                name: instance.name.clone(),
+                source: is_source(&instance.component),
+                power,
+            }
+        })
+        .collect();
+    PowerResult {
+        time: result.time.clone(),
+        instances,
+    }
+}
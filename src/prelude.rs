@@ -0,0 +1,25 @@
+//! The common API in one import
+//!
+//! `use libesim::prelude::*;` brings in what is needed to build a
+//! circuit and run the usual analyses on it, without knowing which
+//! module each item lives in: the circuit and its components, the
+//! analyses and their options and results, and the error types. The
+//! traits are included too, so their methods are in scope. Less common
+//! items are left in their modules.
+
+pub use crate::{
+    dc_sweep, dc_sweep_nested, noise, operating_point, parse_netlist, parse_value, pole_zero,
+    transfer_function, try_dc_sweep, try_dc_sweep_nested, try_noise, try_operating_point,
+    try_pole_zero, try_transfer_function, write_netlist, AcSpec, BuildError, Circuit,
+    CircuitBuilder, Component, DcSolution, DcSweepResult, EsimError, IntegrationMethod,
+    LinearAcAnalysis, NetlistError, NoiseResult, PoleZeroResult, StepControl, SweepRange,
+    TransferFunction, TransientAnalysis, TransientOptions, TransientResult, ValueError, Waveform,
+    WriteError,
+};
+
+pub use crate::component::{DiodeModel, Junction, Model};
+pub use crate::debugger::Debugger;
+pub use crate::schema::SchemaError;
+pub use crate::solver::{NonlinearDevice, NonlinearSolver, SolverOptions};
+pub use crate::transient::Integrator;
+pub use crate::value::IntoValue;
@@ -0,0 +1,335 @@
+//! Periodic steady state analysis
+//!
+//! The steady state of a circuit driven by periodic sources, found by
+//! the shooting method rather than by a transient analysis run until
+//! the start-up transient has decayed (which takes many periods in a
+//! switching converter with a large output capacitor, or a tuned
+//! circuit with a high Q). A transient analysis over one period maps
+//! the solution at its start (every node voltage and edge current) to
+//! the solution at its end, and Newton iteration finds the solution
+//! that this maps to itself. The Jacobian of the map is found by
+//! finite differences, from one more period with each variable of the
+//! solution perturbed, so each iteration takes a period for each
+//! variable; the iteration usually converges in a handful of them.
+//!
+//! For an autonomous circuit (an oscillator, which has no periodic
+//! source) the period is unknown too, and is found along with the
+//! solution, starting from an estimate. The phase of the oscillation
+//! is then fixed by holding the node voltage that changes fastest at
+//! the start of the iteration.
+//!
+//! Each period starts from the initial conditions (see
+//! [TransientOptions::use_initial_conditions]) with a fixed number of
+//! steps, the first of which is by backward Euler, and the sources
+//! start from time zero in each, so their waveforms must be periodic
+//! from time zero. The Newton iteration starts after a number of
+//! periods from the operating point, which helps circuits that are far
+//! from steady state at the operating point.
+
+use crate::circuit::Circuit;
+use crate::error::{invalid, EsimError};
+use crate::sparse::{plus_equals, try_solve, SparseMat};
+use crate::transient::{IntegrationMethod, TransientAnalysis, TransientOptions, TransientResult};
+
+/// Newton iteration settings
+const MAX_NEWTON_ITERATIONS: usize = 50;
+const RELTOL: f64 = 1e-3;
+const VNTOL: f64 = 1e-6;
+const ABSTOL: f64 = 1e-12;
+
+/// Perturbation of each variable (and the period) relative to its
+/// size, for the finite difference Jacobian, and the smallest size
+/// assumed for a variable, relative to its absolute tolerance
+const PERTURBATION: f64 = 1e-6;
+const PERTURBATION_FLOOR: f64 = 1e3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PssOptions {
+    /// Period, or an estimate of it if the circuit is autonomous
+    pub period: f64,
+    /// Number of time steps in a period
+    pub steps_per_period: usize,
+    pub method: IntegrationMethod,
+    /// Whether the circuit is autonomous, so the period is found too
+    pub autonomous: bool,
+    /// Number of periods run from the operating point before the
+    /// Newton iteration
+    pub settling_periods: usize,
+    /// Voltages the nodes are held at for the operating point, before
+    /// the periods run from it, which start an oscillator
+    pub initial_voltages: Vec<(usize, f64)>,
+}
+
+impl PssOptions {
+    /// Options for a driven circuit, using backward Euler with 200
+    /// steps in a period, starting after one period
+    pub fn new(period: f64) -> Self {
+        Self {
+            period,
+            steps_per_period: 200,
+            method: IntegrationMethod::BackwardEuler,
+            autonomous: false,
+            settling_periods: 1,
+            initial_voltages: Vec::new(),
+        }
+    }
+
+    pub fn with_steps_per_period(self, steps_per_period: usize) -> Self {
+        Self {
+            steps_per_period,
+            ..self
+        }
+    }
+
+    pub fn with_method(self, method: IntegrationMethod) -> Self {
+        Self { method, ..self }
+    }
+
+    pub fn with_autonomous(self, autonomous: bool) -> Self {
+        Self { autonomous, ..self }
+    }
+
+    pub fn with_settling_periods(self, settling_periods: usize) -> Self {
+        Self {
+            settling_periods,
+            ..self
+        }
+    }
+
+    /// Hold a node at a voltage for the operating point (see
+    /// [TransientAnalysis::initial_voltage])
+    pub fn with_initial_voltage(mut self, node: usize, voltage: f64) -> Self {
+        self.initial_voltages.push((node, voltage));
+        self
+    }
+
+    /// Transient options for a number of periods
+    fn transient(&self, period: f64, periods: usize) -> TransientOptions {
+        let step = period / self.steps_per_period as f64;
+        // Half a step short of the end, so that rounding cannot add a
+        // step
+        let stop_time = (periods * self.steps_per_period) as f64 * step - 0.5 * step;
+        TransientOptions::new(step, stop_time).with_method(self.method)
+    }
+}
+
+/// The periodic steady state of a circuit
+#[derive(Debug, Clone)]
+pub struct PssResult {
+    /// The period (as given, unless the circuit is autonomous)
+    pub period: f64,
+    /// Transient analysis over one period of the steady state
+    pub result: TransientResult,
+    /// Number of Newton iterations taken
+    pub iterations: usize,
+    /// Warnings of the shooting method, such as a Newton iteration that
+    /// did not converge (those of the last period are in its result)
+    pub warnings: Vec<String>,
+}
+
+/// The solution at the last time point of a result, with the node
+/// voltages before the edge currents
+fn last_solution(result: &TransientResult) -> Result<Vec<f64>, EsimError> {
+    match (result.voltages.last(), result.currents.last()) {
+        (Some(voltages), Some(currents)) => Ok(voltages.iter().chain(currents).copied().collect()),
+        _ => Err(invalid("Period has no time points".to_string())),
+    }
+}
+
+/// Solve the periodic steady state of a circuit by the shooting
+/// method. Panics if a period or the Newton iteration cannot be
+/// solved (see [try_pss]).
+pub fn pss(circuit: &Circuit, options: &PssOptions) -> PssResult {
+    shooting(circuit, options, |analysis| Ok(analysis.run()))
+        .unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Solve the periodic steady state as for [pss], returning an error
+/// if the circuit fails its check (see [Circuit::validate]), a node
+/// of the initial voltages is not in the circuit, the circuit has no
+/// nodes, or a period or the Newton iteration cannot be solved
+pub fn try_pss(circuit: &Circuit, options: &PssOptions) -> Result<PssResult, EsimError> {
+    shooting(circuit, options, |analysis| analysis.try_run())
+}
+
+/// The shooting method, with each transient analysis run by run
+fn shooting(
+    circuit: &Circuit,
+    options: &PssOptions,
+    run: impl Fn(TransientAnalysis) -> Result<TransientResult, EsimError>,
+) -> Result<PssResult, EsimError> {
+    let settling = options.initial_voltages.iter().try_fold(
+        TransientAnalysis::new(
+            circuit,
+            options.transient(options.period, options.settling_periods.max(1)),
+        ),
+        |analysis, (node, voltage)| analysis.try_initial_voltage(*node, *voltage),
+    )?;
+    let settling = run(settling)?;
+    let num_voltage_nodes = settling.voltages[0].len();
+    let mut state = last_solution(&settling)?;
+    let mut period = options.period;
+    let size = state.len();
+
+    // One period of transient analysis from a state
+    let shoot = |state: &[f64], period: f64| -> Result<TransientResult, EsimError> {
+        let (voltages, currents) = state.split_at(num_voltage_nodes);
+        run(TransientAnalysis::new(
+            circuit,
+            options
+                .transient(period, 1)
+                .with_use_initial_conditions(true),
+        )
+        .initial_solution(voltages, currents))
+    };
+    let absolute = |index: usize| {
+        if index < num_voltage_nodes {
+            VNTOL
+        } else {
+            ABSTOL
+        }
+    };
+    let tolerance = |index: usize, a: f64, b: f64| RELTOL * a.abs().max(b.abs()) + absolute(index);
+
+    // The node voltage held to fix the phase of an oscillation, which
+    // is the one changing fastest
+    let phase_node = if options.autonomous {
+        let end = &settling.voltages[settling.voltages.len() - 1];
+        let before = &settling.voltages[settling.voltages.len() - 2];
+        let node = (0..num_voltage_nodes)
+            .max_by(|a, b| {
+                let rate = |i: usize| (end[i] - before[i]).abs();
+                rate(*a).total_cmp(&rate(*b))
+            })
+            .ok_or_else(|| invalid("An autonomous circuit must have a node".to_string()))?;
+        Some(node)
+    } else {
+        None
+    };
+
+    let mut result = shoot(&state, period)?;
+    let mut iterations = 0;
+    let mut converged = false;
+    while iterations < MAX_NEWTON_ITERATIONS {
+        let end = last_solution(&result)?;
+        converged = state
+            .iter()
+            .zip(&end)
+            .enumerate()
+            .all(|(index, (x, y))| (y - x).abs() <= tolerance(index, *x, *y));
+        if converged {
+            break;
+        }
+        iterations += 1;
+
+        // The Jacobian of the map, less the identity, by column, with
+        // a column for the period if it is unknown
+        let unknowns = size + usize::from(phase_node.is_some());
+        let mut matrix = SparseMat::new(unknowns, unknowns);
+        for column in 0..size {
+            let delta = PERTURBATION
+                * state[column]
+                    .abs()
+                    .max(PERTURBATION_FLOOR * absolute(column));
+            let mut perturbed = state.clone();
+            perturbed[column] += delta;
+            let perturbed_end = last_solution(&shoot(&perturbed, period)?)?;
+            for (row, (p, y)) in perturbed_end.iter().zip(&end).enumerate() {
+                let identity = if row == column { 1.0 } else { 0.0 };
+                let entry = (p - y) / delta - identity;
+                if entry != 0.0 {
+                    plus_equals(&mut matrix, row, column, entry);
+                }
+            }
+        }
+        if let Some(node) = phase_node {
+            let delta = PERTURBATION * period;
+            let perturbed_end = last_solution(&shoot(&state, period + delta)?)?;
+            for (row, (p, y)) in perturbed_end.iter().zip(&end).enumerate() {
+                plus_equals(&mut matrix, row, size, (p - y) / delta);
+            }
+            plus_equals(&mut matrix, size, node, 1.0);
+        }
+        let mut rhs: Vec<f64> = state.iter().zip(&end).map(|(x, y)| x - y).collect();
+        rhs.resize(unknowns, 0.0);
+        let step = try_solve(matrix, rhs)?;
+        for (x, dx) in state.iter_mut().zip(&step) {
+            *x += dx;
+        }
+        if phase_node.is_some() {
+            period += step[size];
+        }
+        result = shoot(&state, period)?;
+    }
+    let mut warnings = Vec::new();
+    if !converged {
+        warnings.push(format!(
+            "Periodic steady state did not converge after {MAX_NEWTON_ITERATIONS} iterations"
+        ));
+    }
+    if let Some(node) = phase_node {
+        let voltages = result.voltage(node + 1);
+        let (min, max) = voltages
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                (min.min(*v), max.max(*v))
+            });
+        if max - min <= tolerance(node, min, max) {
+            warnings.push(String::from(
+                "Periodic steady state is a constant solution, not an oscillation",
+            ));
+        }
+    }
+    Ok(PssResult {
+        period,
+        result,
+        iterations,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::builder::CircuitBuilder;
+    use crate::waveform::Waveform;
+
+    #[test]
+    fn a_driven_rc_settles_to_its_ac_response() {
+        // A 1 kHz sine into an RC low-pass filter with wRC = 1, whose
+        // steady state is the input scaled by 1/sqrt(2) and 45 degrees
+        // behind it
+        let frequency = 1e3;
+        let capacitance = 1.0 / (2.0 * PI * frequency * 1e3);
+        let circuit = CircuitBuilder::new()
+            .vsource("V1", "in", "0", 0.0)
+            .with_waveform(Waveform::Sin {
+                offset: 0.0,
+                amplitude: 1.0,
+                frequency,
+                delay: 0.0,
+                damping: 0.0,
+            })
+            .resistor("R1", "in", "out", 1e3)
+            .capacitor("C1", "out", "0", capacitance)
+            .build()
+            .unwrap();
+        // Enough steps that the backward Euler step starting each
+        // period barely moves the orbit
+        let options = PssOptions::new(1.0 / frequency)
+            .with_steps_per_period(2000)
+            .with_method(IntegrationMethod::Trapezoidal);
+        let pss = pss(&circuit, &options);
+        assert!(pss.warnings.is_empty(), "{:?}", pss.warnings);
+        assert_eq!(pss.period, 1.0 / frequency);
+        let output = pss.result.node_voltage("out").unwrap();
+        for (time, voltage) in pss.result.time.iter().zip(&output) {
+            let expected = (2.0 * PI * frequency * time - PI / 4.0).sin() / 2f64.sqrt();
+            assert!((voltage - expected).abs() < 1e-3, "{voltage} at {time}");
+        }
+        // The period ends where it started
+        assert!((output[0] - output[output.len() - 1]).abs() < 1e-3);
+    }
+}
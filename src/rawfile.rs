@@ -0,0 +1,540 @@
+//! Reading SPICE raw files
+//!
+//! ngspice and LTspice save their results as raw files, which are read
+//! here into [Dataset]s, so results from those simulators can be
+//! compared with those of esim (such as by the arithmetic on
+//! [Signal]s), or used as stimuli ([RawPlot::waveform]). A file holds
+//! one or more plots (one per analysis), each with a header
+//!
+//! ```text
+//! Title: ...
+//! Plotname: Transient Analysis
+//! Flags: real
+//! No. Variables: 3
+//! No. Points: 501
+//! Variables:
+//!     0   time    time
+//!     1   v(out)  voltage
+//!     2   i(v1)   current
+//! Binary:
+//! ```
+//!
+//! followed by the values of every variable at each point, as text
+//! (after `Values:`) or in binary (after `Binary:`). The first variable
+//! is the axis (time, frequency or the swept value), except in an
+//! operating point, which has none. The unit of each signal comes from
+//! the type of its variable.
+//!
+//! Binary values are little-endian doubles, or (in LTspice transient
+//! results) a double for the time and floats for the others, which are
+//! told apart by the size of the data. Complex values are pairs of
+//! doubles. LTspice writes its headers in UTF-16, and marks the time
+//! points where it compressed the data with a negative time, whose
+//! sign is removed.
+//!
+//! Plots are written as ASCII raw files by [write_raw].
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::schema::v1::{Dataset, Signal, Unit};
+use crate::waveform::Waveform;
+
+#[derive(Debug)]
+pub enum RawError {
+    /// The file could not be read
+    Io(io::Error),
+    /// The file is not a valid raw file
+    Format(String),
+}
+
+impl fmt::Display for RawError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "Cannot read the raw file: {error}"),
+            Self::Format(message) => write!(f, "Invalid raw file: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for RawError {}
+
+impl From<io::Error> for RawError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// One plot (the results of one analysis) of a raw file
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawPlot {
+    pub title: String,
+    /// Name of the analysis, such as `Transient Analysis`
+    pub plot_name: String,
+    pub dataset: Dataset,
+}
+
+impl RawPlot {
+    /// A signal by name (in any case)
+    pub fn signal(&self, name: &str) -> Option<&Signal> {
+        self.dataset
+            .signals
+            .iter()
+            .find(|signal| signal.name.eq_ignore_ascii_case(name))
+    }
+
+    /// A real signal as a piecewise-linear waveform of the axis (such
+    /// as time), if there is one
+    pub fn waveform(&self, name: &str) -> Option<Waveform> {
+        let axis = self.dataset.axis.as_ref()?;
+        let signal = self.signal(name)?;
+        let points = axis.real.iter().copied().zip(signal.real.iter().copied());
+        Some(Waveform::Pwl(points.collect()))
+    }
+}
+
+/// The header of a plot
+struct Header {
+    title: String,
+    plot_name: String,
+    complex: bool,
+    num_points: usize,
+    /// (name, type) of each variable
+    variables: Vec<(String, String)>,
+}
+
+fn format_error(message: impl Into<String>) -> RawError {
+    RawError::Format(message.into())
+}
+
+/// Read the header of a plot from its lines
+fn header(lines: &[String]) -> Result<Header, RawError> {
+    let field = |key: &str| {
+        lines.iter().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case(key)
+                .then(|| value.trim().to_string())
+        })
+    };
+    let number = |key: &str| {
+        field(key)
+            .and_then(|value| value.parse::<usize>().ok())
+            .ok_or_else(|| format_error(format!("missing '{key}'")))
+    };
+    let num_variables = number("No. Variables")?;
+    let start = lines
+        .iter()
+        .position(|line| line.trim().eq_ignore_ascii_case("Variables:"))
+        .ok_or_else(|| format_error("missing 'Variables:'"))?;
+    let variables: Vec<(String, String)> = lines[start + 1..]
+        .iter()
+        .take(num_variables)
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                [_, name, kind, ..] => Ok((name.to_string(), kind.to_ascii_lowercase())),
+                _ => Err(format_error(format!("invalid variable '{}'", line.trim()))),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+    if variables.len() != num_variables {
+        return Err(format_error("missing variables"));
+    }
+    Ok(Header {
+        title: field("Title").unwrap_or_default(),
+        plot_name: field("Plotname").unwrap_or_default(),
+        complex: field("Flags").is_some_and(|flags| flags.to_ascii_lowercase().contains("complex")),
+        num_points: number("No. Points")?,
+        variables,
+    })
+}
+
+/// The values of the variables of a plot, as (real, imaginary), by
+/// point then variable
+type Values = Vec<Vec<(f64, f64)>>;
+
+/// The dataset of a plot from the values of its variables
+fn dataset(header: &Header, values: Values) -> Dataset {
+    let signal = |index: usize, complex: bool| {
+        let (name, kind) = &header.variables[index];
+        let real = values.iter().map(|point| point[index].0).collect();
+        Signal {
+            name: name.clone(),
+            real,
+            imag: complex.then(|| values.iter().map(|point| point[index].1).collect()),
+            unit: Unit::of_quantity(kind),
+        }
+    };
+    let operating_point = header
+        .plot_name
+        .to_ascii_lowercase()
+        .contains("operating point");
+    let first = usize::from(!operating_point);
+    let mut axis = (!operating_point).then(|| signal(0, false));
+    if let Some(axis) = &mut axis {
+        // LTspice marks compressed points with a negative time
+        if axis.unit == Unit::Second {
+            axis.real.iter_mut().for_each(|time| *time = time.abs());
+        }
+    }
+    Dataset {
+        axis,
+        signals: (first..header.variables.len())
+            .map(|index| signal(index, header.complex))
+            .collect(),
+        warnings: Vec::new(),
+    }
+}
+
+/// Read text values, returning them and the number of bytes read
+fn text_values(header: &Header, text: &str) -> Result<(Values, usize), RawError> {
+    let number = |token: &str| {
+        token
+            .parse::<f64>()
+            .map_err(|_| format_error(format!("invalid value '{token}'")))
+    };
+    let value = |token: &str| -> Result<(f64, f64), RawError> {
+        match token.split_once(',') {
+            Some((re, im)) => Ok((number(re)?, number(im)?)),
+            None => Ok((number(token)?, 0.0)),
+        }
+    };
+    let mut tokens = text
+        .split_inclusive(char::is_whitespace)
+        .scan(0, |position, token| {
+            *position += token.len();
+            Some((*position, token.trim()))
+        })
+        .filter(|(_, token)| !token.is_empty());
+    let mut values = Vec::with_capacity(header.num_points);
+    let mut end = 0;
+    for _ in 0..header.num_points {
+        // Each point starts with its index
+        tokens
+            .next()
+            .ok_or_else(|| format_error("missing values"))?;
+        let mut point = Vec::with_capacity(header.variables.len());
+        for _ in 0..header.variables.len() {
+            let (position, token) = tokens
+                .next()
+                .ok_or_else(|| format_error("missing values"))?;
+            point.push(value(token)?);
+            end = position;
+        }
+        values.push(point);
+    }
+    Ok((values, end))
+}
+
+/// Read binary values, returning them and the number of bytes read
+fn binary_values(header: &Header, data: &[u8]) -> Result<(Values, usize), RawError> {
+    let (num_points, num_variables) = (header.num_points, header.variables.len());
+    let double = |bytes: &[u8]| f64::from_le_bytes(bytes.try_into().unwrap());
+    let float = |bytes: &[u8]| f32::from_le_bytes(bytes.try_into().unwrap()) as f64;
+    // Complex values and doubles, or a double for the time and floats
+    let (sizes, complex): (Vec<usize>, bool) = if header.complex {
+        (vec![16; num_variables], true)
+    } else if data.len() >= num_points * num_variables * 8 {
+        (vec![8; num_variables], false)
+    } else {
+        let mut sizes = vec![4; num_variables];
+        sizes[0] = 8;
+        (sizes, false)
+    };
+    let point_size: usize = sizes.iter().sum();
+    let length = point_size * num_points;
+    if data.len() < length {
+        return Err(format_error("missing values"));
+    }
+    let values = data[..length]
+        .chunks(point_size)
+        .map(|point| {
+            let mut offset = 0;
+            sizes
+                .iter()
+                .map(|size| {
+                    let bytes = &point[offset..offset + size];
+                    offset += size;
+                    match (complex, size) {
+                        (true, _) => (double(&bytes[..8]), double(&bytes[8..])),
+                        (false, 8) => (double(bytes), 0.0),
+                        _ => (float(bytes), 0.0),
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    Ok((values, length))
+}
+
+/// The header text of a plot up to its data, returning it, the kind of
+/// data (`binary` or `values`) and the number of bytes up to the data,
+/// from bytes in UTF-16 (little-endian) or not
+fn header_text(bytes: &[u8], utf16: bool) -> Option<(String, bool, usize)> {
+    let mut text = String::new();
+    let mut position = 0;
+    loop {
+        let (c, size) = if utf16 {
+            let unit = u16::from_le_bytes(bytes.get(position..position + 2)?.try_into().unwrap());
+            (char::from_u32(unit as u32).unwrap_or('?'), 2)
+        } else {
+            (*bytes.get(position)? as char, 1)
+        };
+        position += size;
+        text.push(c);
+        if c == '\n' {
+            let line = text.trim_end().rsplit('\n').next().unwrap_or_default();
+            if line.eq_ignore_ascii_case("Binary:") || line.eq_ignore_ascii_case("Values:") {
+                let binary = line.eq_ignore_ascii_case("Binary:");
+                return Some((text, binary, position));
+            }
+        }
+    }
+}
+
+/// Read the plots of a raw file from its contents
+pub fn read_raw(bytes: &[u8]) -> Result<Vec<RawPlot>, RawError> {
+    // UTF-16 text has a zero byte after each ASCII character
+    let utf16 = bytes.len() > 1 && bytes[0] != 0 && bytes[1] == 0;
+    let mut plots = Vec::new();
+    let mut rest = bytes;
+    loop {
+        let skipped = rest
+            .iter()
+            .position(|b| !b.is_ascii_whitespace() && *b != 0)
+            .unwrap_or(rest.len());
+        rest = &rest[skipped..];
+        if rest.is_empty() {
+            break;
+        }
+        let (text, binary, start) = header_text(rest, utf16)
+            .ok_or_else(|| format_error("no 'Binary:' or 'Values:' line"))?;
+        let lines: Vec<String> = text.lines().map(String::from).collect();
+        let header = header(&lines)?;
+        let data = &rest[start..];
+        let (values, length) = if binary {
+            binary_values(&header, data)?
+        } else {
+            let text = String::from_utf8_lossy(data);
+            text_values(&header, &text)?
+        };
+        plots.push(RawPlot {
+            title: header.title.clone(),
+            plot_name: header.plot_name.clone(),
+            dataset: dataset(&header, values),
+        });
+        rest = &data[length..];
+    }
+    if plots.is_empty() {
+        return Err(format_error("no plots"));
+    }
+    Ok(plots)
+}
+
+/// Read the plots of a raw file
+pub fn read_raw_file(path: impl AsRef<Path>) -> Result<Vec<RawPlot>, RawError> {
+    read_raw(&fs::read(path)?)
+}
+
+/// Write plots as an ASCII raw file, which ngspice and LTspice (and
+/// [read_raw]) can read. The axis, if there is one, is the first
+/// variable, and a plot is complex if any of its signals is.
+pub fn write_raw(plots: &[RawPlot]) -> String {
+    let mut text = String::new();
+    for plot in plots {
+        let dataset = &plot.dataset;
+        let variables: Vec<&Signal> = dataset.axis.iter().chain(&dataset.signals).collect();
+        let complex = variables.iter().any(|signal| signal.imag.is_some());
+        let num_points = variables.first().map_or(0, |signal| signal.real.len());
+        text += &format!("Title: {}\n", plot.title);
+        text += &format!("Plotname: {}\n", plot.plot_name);
+        text += &format!("Flags: {}\n", if complex { "complex" } else { "real" });
+        text += &format!("No. Variables: {}\n", variables.len());
+        text += &format!("No. Points: {num_points}\n");
+        text += "Variables:\n";
+        for (index, signal) in variables.iter().enumerate() {
+            let quantity = match signal.unit.quantity() {
+                "" => "notype",
+                quantity => quantity,
+            };
+            text += &format!("\t{index}\t{}\t{quantity}\n", signal.name);
+        }
+        text += "Values:\n";
+        for point in 0..num_points {
+            for (index, signal) in variables.iter().enumerate() {
+                let re = signal.real[point];
+                let value = match (complex, &signal.imag) {
+                    (false, _) => format!("{re:e}"),
+                    (true, Some(imag)) => format!("{re:e},{:e}", imag[point]),
+                    (true, None) => format!("{re:e},0"),
+                };
+                if index == 0 {
+                    text += &format!("{point}\t{value}\n");
+                } else {
+                    text += &format!("\t{value}\n");
+                }
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The header of a plot of time, v(out) and i(v1), up to its data
+    fn header_of(plot_name: &str, flags: &str, num_points: usize, data: &str) -> String {
+        format!(
+            "Title: test\nDate: today\nPlotname: {plot_name}\nFlags: {flags}\n\
+             No. Variables: 3\nNo. Points: {num_points}\nVariables:\n\
+             \t0\ttime\ttime\n\t1\tv(out)\tvoltage\n\t2\ti(v1)\tcurrent\n{data}:\n"
+        )
+    }
+
+    fn plot(title: &str, plot_name: &str, dataset: Dataset) -> RawPlot {
+        RawPlot {
+            title: title.to_string(),
+            plot_name: plot_name.to_string(),
+            dataset,
+        }
+    }
+
+    fn signal(name: &str, real: Vec<f64>, unit: Unit) -> Signal {
+        Signal {
+            unit,
+            ..Signal::real(name, real)
+        }
+    }
+
+    #[test]
+    fn plots_round_trip_through_an_ascii_raw_file() {
+        let transient = plot(
+            "rc",
+            "Transient Analysis",
+            Dataset {
+                axis: Some(signal("time", vec![0.0, 1e-3, 2.5e-3], Unit::Second)),
+                signals: vec![
+                    signal("v(out)", vec![0.0, 0.632, 0.1 + 0.2], Unit::Volt),
+                    signal("x", vec![1.0, -2.0, 3.0], Unit::None),
+                ],
+                warnings: Vec::new(),
+            },
+        );
+        let ac = plot(
+            "rc",
+            "AC Analysis",
+            Dataset {
+                axis: Some(signal("frequency", vec![1.0, 10.0], Unit::Hertz)),
+                signals: vec![Signal {
+                    imag: Some(vec![-0.5, -0.1]),
+                    ..signal("v(out)", vec![0.5, 0.99], Unit::Volt)
+                }],
+                warnings: Vec::new(),
+            },
+        );
+        let op = plot(
+            "rc",
+            "Operating Point",
+            Dataset {
+                axis: None,
+                signals: vec![signal("i(v1)", vec![-1e-3], Unit::Ampere)],
+                warnings: Vec::new(),
+            },
+        );
+        let plots = vec![transient, ac, op];
+        let text = write_raw(&plots);
+        assert!(text.contains("Flags: complex\n"));
+        assert!(text.contains("\t2\tx\tnotype\n"));
+        // The AC axis is written as complex with no imaginary part, and
+        // read back as real
+        assert_eq!(read_raw(text.as_bytes()).unwrap(), plots);
+    }
+
+    #[test]
+    fn ascii_values_are_read() {
+        let text = header_of("Transient Analysis", "real", 2, "Values")
+            + " 0\t0.000000e+00\n\t1.0e+00\n\t-2.5e-03\n\n 1\t1.0e-06\n\t2\n\t-1e-3\n";
+        let plots = read_raw(text.as_bytes()).unwrap();
+        assert_eq!(plots.len(), 1);
+        let plot = &plots[0];
+        assert_eq!(plot.title, "test");
+        assert_eq!(plot.plot_name, "Transient Analysis");
+        let axis = plot.dataset.axis.as_ref().unwrap();
+        assert_eq!((axis.name.as_str(), axis.unit), ("time", Unit::Second));
+        assert_eq!(axis.real, [0.0, 1e-6]);
+        let voltage = plot.signal("V(OUT)").unwrap();
+        assert_eq!(
+            (voltage.real.as_slice(), voltage.unit),
+            (&[1.0, 2.0][..], Unit::Volt)
+        );
+        assert_eq!(plot.signal("i(v1)").unwrap().unit, Unit::Ampere);
+        assert_eq!(
+            plot.waveform("v(out)"),
+            Some(Waveform::Pwl(vec![(0.0, 1.0), (1e-6, 2.0)]))
+        );
+    }
+
+    #[test]
+    fn binary_doubles_are_read() {
+        let mut bytes = header_of("Transient Analysis", "real", 2, "Binary").into_bytes();
+        for value in [0.0, 1.5, -1e-3, 1e-6, 2.5, -2e-3] {
+            bytes.extend(f64::to_le_bytes(value));
+        }
+        let plot = &read_raw(&bytes).unwrap()[0];
+        assert_eq!(plot.dataset.axis.as_ref().unwrap().real, [0.0, 1e-6]);
+        assert_eq!(plot.signal("v(out)").unwrap().real, [1.5, 2.5]);
+        assert_eq!(plot.signal("i(v1)").unwrap().real, [-1e-3, -2e-3]);
+    }
+
+    #[test]
+    fn ltspice_files_have_utf16_headers_and_float_values() {
+        // A double for the time, with the second point marked as
+        // compressed, and floats for the others
+        let header = header_of("Transient Analysis", "real forward", 2, "Binary");
+        let mut bytes: Vec<u8> = header.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        for (time, voltage, current) in [(0.0, 1.5f32, 0.25f32), (-1e-6, 2.5, 0.5)] {
+            bytes.extend(f64::to_le_bytes(time));
+            bytes.extend(f32::to_le_bytes(voltage));
+            bytes.extend(f32::to_le_bytes(current));
+        }
+        let plot = &read_raw(&bytes).unwrap()[0];
+        assert_eq!(plot.plot_name, "Transient Analysis");
+        assert_eq!(plot.dataset.axis.as_ref().unwrap().real, [0.0, 1e-6]);
+        assert_eq!(plot.signal("v(out)").unwrap().real, [1.5, 2.5]);
+        assert_eq!(plot.signal("i(v1)").unwrap().real, [0.25, 0.5]);
+    }
+
+    #[test]
+    fn complex_values_are_read() {
+        let mut bytes = header_of("AC Analysis", "complex", 1, "Binary").into_bytes();
+        for value in [1e3, 0.0, 0.5, -0.5, 1e-3, 2e-3] {
+            bytes.extend(f64::to_le_bytes(value));
+        }
+        let plot = &read_raw(&bytes).unwrap()[0];
+        // The axis is real, and the others complex
+        let axis = plot.dataset.axis.as_ref().unwrap();
+        assert_eq!((axis.real.as_slice(), &axis.imag), (&[1e3][..], &None));
+        let voltage = plot.signal("v(out)").unwrap();
+        assert_eq!(voltage.real, [0.5]);
+        assert_eq!(voltage.imag, Some(vec![-0.5]));
+        let text = header_of("AC Analysis", "complex", 1, "Values")
+            + "0\t1e3,0\n\t0.5,-0.5\n\t1e-3,2e-3\n";
+        assert_eq!(read_raw(text.as_bytes()).unwrap()[0], *plot);
+    }
+
+    #[test]
+    fn invalid_files_are_errors() {
+        let short = header_of("Transient Analysis", "real", 2, "Values") + "0\t0\n\t1\n";
+        for bytes in [&b""[..], b"Title: x\nNo. Points: 1\n", short.as_bytes()] {
+            assert!(matches!(read_raw(bytes), Err(RawError::Format(_))));
+        }
+        assert!(matches!(
+            read_raw_file("/nonexistent/file.raw"),
+            Err(RawError::Io(_))
+        ));
+    }
+}
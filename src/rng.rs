@@ -0,0 +1,63 @@
+//! Random number streams
+//!
+//! Each stochastic feature (such as Monte Carlo variation or random
+//! fault sampling) draws its random numbers from its own named
+//! stream. The streams are all derived from one master seed, but the
+//! seed of each stream depends only on the master seed and the
+//! stream's name, so turning one feature on or off does not change
+//! the numbers another one sees.
+//!
+//! A stream can also be split into numbered sub-streams (such as one
+//! per Monte Carlo sample), so any subset of the samples can be run
+//! again and gives exactly the same values as in the full run.
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Stream names used by the analyses
+pub const MONTE_CARLO: &str = "monte_carlo";
+pub const FAULT_CAMPAIGN: &str = "fault_campaign";
+pub const ANONYMIZE: &str = "anonymize";
+
+/// One step of the SplitMix64 generator, used to mix seeds
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// FNV-1a hash of a name, which (unlike the standard library hasher)
+/// is the same in every build
+fn hash_name(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Named random number streams derived from a master seed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RngStreams {
+    master_seed: u64,
+}
+
+impl RngStreams {
+    pub fn new(master_seed: u64) -> Self {
+        Self { master_seed }
+    }
+
+    /// Seed of a named stream
+    pub fn seed(&self, name: &str) -> u64 {
+        mix(self.master_seed ^ mix(hash_name(name)))
+    }
+
+    /// A named stream
+    pub fn stream(&self, name: &str) -> StdRng {
+        StdRng::seed_from_u64(self.seed(name))
+    }
+
+    /// A numbered sub-stream of a named stream
+    pub fn substream(&self, name: &str, index: u64) -> StdRng {
+        StdRng::seed_from_u64(mix(self.seed(name) ^ mix(index)))
+    }
+}
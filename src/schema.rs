@@ -0,0 +1,260 @@
+//! Versioned serialization schema
+//!
+//! The types in [v1] are a documented, stable description of
+//! circuits, analysis options and datasets for use by external
+//! tools. They are deliberately separate from the internal types
+//! (such as [Circuit](crate::circuit::Circuit)), which can change
+//! between releases; the schema types only change with the schema
+//! version.
+//!
+//! Documents are JSON (or YAML) objects with a `schema_version` field
+//! of the form `"major.minor"`, alongside a `content` field. Minor versions
+//! only add optional fields, so a document with any minor version of
+//! the current major version can be read (unknown fields are
+//! ignored). Documents with an older major version are upgraded by
+//! the migrations in [MIGRATIONS] before they are read, and documents
+//! with a newer major version are rejected.
+//!
+//! A [Circuit](crate::circuit::Circuit) is serialized as a
+//! [v1::Circuit], so it can be written and read directly, as in
+//! `to_json(&circuit)` and `from_yaml::<Circuit>(yaml)`.
+
+use std::fmt;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::EsimError;
+
+pub mod v1;
+
+/// Version of the schema written by this crate
+pub const SCHEMA_VERSION: Version = Version {
+    major: 1,
+    minor: 34,
+};
+
+/// Conversion of document contents from one major version to the next
+pub type Migration = fn(Value) -> Result<Value, SchemaError>;
+
+/// Migrations, as (major version converted from, migration). This is
+/// empty while there is only one major version.
+pub const MIGRATIONS: &[(u32, Migration)] = &[];
+
+/// Schema version, serialized as `"major.minor"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl From<Version> for String {
+    fn from(version: Version) -> String {
+        version.to_string()
+    }
+}
+
+impl TryFrom<String> for Version {
+    type Error = String;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (major, minor) = value
+            .split_once('.')
+            .ok_or_else(|| format!("Invalid schema version {value}"))?;
+        let parse = |s: &str| {
+            s.parse()
+                .map_err(|_| format!("Invalid schema version {value}"))
+        };
+        Ok(Self {
+            major: parse(major)?,
+            minor: parse(minor)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum SchemaError {
+    /// The document is not valid JSON, or does not match the schema
+    Json(serde_json::Error),
+    /// The document is not valid YAML
+    Yaml(serde_yaml::Error),
+    /// The content matches the schema, but is not valid (such as a
+    /// value expression of a parameter that is not defined)
+    Content(EsimError),
+    /// The document has no schema_version field
+    MissingVersion,
+    /// The document was written with a newer major version, or an
+    /// older one that cannot be upgraded
+    UnsupportedVersion(Version),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(error) => write!(f, "Invalid document: {error}"),
+            Self::Yaml(error) => write!(f, "Invalid document: {error}"),
+            Self::Content(error) => write!(f, "Invalid document content: {error}"),
+            Self::MissingVersion => write!(f, "Document has no schema_version"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported schema version {version} (this crate uses {SCHEMA_VERSION})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<serde_json::Error> for SchemaError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+impl From<serde_yaml::Error> for SchemaError {
+    fn from(error: serde_yaml::Error) -> Self {
+        Self::Yaml(error)
+    }
+}
+
+impl From<EsimError> for SchemaError {
+    fn from(error: EsimError) -> Self {
+        Self::Content(error)
+    }
+}
+
+/// A versioned document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Document<T> {
+    pub schema_version: Version,
+    pub content: T,
+}
+
+/// Serialize the content as a JSON document with the current schema
+/// version
+pub fn to_json<T: Serialize>(content: &T) -> Result<String, SchemaError> {
+    let document = Document {
+        schema_version: SCHEMA_VERSION,
+        content,
+    };
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+/// Serialize the content as a YAML document with the current schema
+/// version
+pub fn to_yaml<T: Serialize>(content: &T) -> Result<String, SchemaError> {
+    let document = Document {
+        schema_version: SCHEMA_VERSION,
+        content,
+    };
+    Ok(serde_yaml::to_string(&document)?)
+}
+
+/// Read a JSON document, upgrading it to the current schema version
+/// if it was written with an older major version
+pub fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, SchemaError> {
+    from_document(serde_json::from_str(json)?)
+}
+
+/// Read a YAML document, upgrading it as for [from_json]
+pub fn from_yaml<T: DeserializeOwned>(yaml: &str) -> Result<T, SchemaError> {
+    from_document(serde_yaml::from_str(yaml)?)
+}
+
+fn from_document<T: DeserializeOwned>(mut document: Value) -> Result<T, SchemaError> {
+    let version: Version = match document.get("schema_version") {
+        Some(version) => serde_json::from_value(version.clone())?,
+        None => return Err(SchemaError::MissingVersion),
+    };
+    if version.major > SCHEMA_VERSION.major {
+        return Err(SchemaError::UnsupportedVersion(version));
+    }
+    let mut content = document["content"].take();
+    for major in version.major..SCHEMA_VERSION.major {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|(from, _)| *from == major)
+            .ok_or(SchemaError::UnsupportedVersion(version))?;
+        content = (migration.1)(content)?;
+    }
+    Ok(serde_json::from_value(content)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::netlist::parse_netlist;
+
+    fn circuit() -> Circuit {
+        parse_netlist(".PARAM r=2k\nV1 in 0 5\nR1 in out {r}\nR2 out 0 1k\n").unwrap()
+    }
+
+    #[test]
+    fn circuits_round_trip_through_json_and_yaml() {
+        let circuit = circuit();
+        let expected = v1::Circuit::from(&circuit);
+        let json: Circuit = from_json(&to_json(&circuit).unwrap()).unwrap();
+        assert_eq!(v1::Circuit::from(&json), expected);
+        let yaml: Circuit = from_yaml(&to_yaml(&circuit).unwrap()).unwrap();
+        assert_eq!(v1::Circuit::from(&yaml), expected);
+    }
+
+    #[test]
+    fn invalid_content_is_an_error() {
+        let mut document = v1::Circuit::from(&circuit());
+        document.parameters.clear();
+        let json = to_json(&document).unwrap();
+        assert!(from_json::<Circuit>(&json).is_err());
+        let converted = Circuit::try_from(from_json::<v1::Circuit>(&json).unwrap());
+        assert!(matches!(converted, Err(EsimError::Invalid { .. })));
+
+        let table = v1::Circuit {
+            components: vec![v1::Component::Table {
+                name: String::from("T1"),
+                nodes: [1, 0],
+                quantity: v1::TableQuantity::Current,
+                points: vec![(0.0, 0.0)],
+            }],
+            ..v1::Circuit::default()
+        };
+        assert!(matches!(
+            Circuit::try_from(table),
+            Err(EsimError::Invalid { .. })
+        ));
+
+        let inductor = v1::Circuit {
+            components: vec![v1::Component::SaturableInductor {
+                name: String::from("L1"),
+                nodes: [1, 0],
+                current_edge: 0,
+                curve: v1::SaturationCurve::Pwl {
+                    points: vec![(0.0, 0.0)],
+                },
+            }],
+            ..v1::Circuit::default()
+        };
+        assert!(matches!(
+            Circuit::try_from(inductor),
+            Err(EsimError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_yaml_is_an_error() {
+        assert!(matches!(
+            from_yaml::<v1::Circuit>("schema_version: [1"),
+            Err(SchemaError::Yaml(_))
+        ));
+        assert!(matches!(
+            from_yaml::<v1::Circuit>("content: {}"),
+            Err(SchemaError::MissingVersion)
+        ));
+    }
+}
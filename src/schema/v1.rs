@@ -0,0 +1,3343 @@
+//! Schema version 1
+//!
+//! Nodes are numbered from 1, with 0 for ground, and current edges
+//! are numbered from 0, as in the rest of the crate. All values are
+//! in SI units, with temperatures in degrees Celsius and phases in
+//! degrees. A circuit also carries its node names, its parameters and
+//! the values that are expressions of them (as text), and its models
+//! with the instances that use them, so a parsed netlist is described
+//! in full.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops;
+use std::sync::Arc;
+
+use num::Complex;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::ac::{self, AcSweep, AcSweepResult};
+use crate::characterize::Curve;
+use crate::circuit;
+use crate::component::{
+    self, AcSpec, BatteryModel, CrystalParams, FuseParams, IgbtParams, ProbeParams, RelayParams,
+    SchottkyModel, SemiconductorCapacitorModel, SemiconductorResistorModel, SupercapParams,
+    TableModel, ThyristorKind, TouchstoneData, TunnelDiodeModel, UrcModel,
+};
+use crate::error::{invalid, EsimError};
+use crate::expression::{self, parse_expression, Parameters};
+use crate::node::NodeNames;
+use crate::plan;
+use crate::statistics::Histogram;
+use crate::sweep::{DcSweepFamily, DcSweepResult, SweepRange};
+use crate::tdr::{self, TdrResult};
+use crate::transient::{self, TransientOptions, TransientResult};
+use crate::watch;
+use crate::waveform;
+
+/// Small-signal source specification
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Ac {
+    pub magnitude: f64,
+    pub phase: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "equation", rename_all = "snake_case")]
+pub enum ThermistorModel {
+    Beta { r0: f64, t0: f64, beta: f64 },
+    SteinhartHart { a: f64, b: f64, c: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum SaturationCurve {
+    Arctan {
+        l0: f64,
+        l_sat: f64,
+        i_sat: f64,
+    },
+    /// (current, flux) points
+    Pwl {
+        points: Vec<(f64, f64)>,
+    },
+}
+
+/// Semiconductor resistor model card (since 1.1)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ResistorModel {
+    pub rsh: f64,
+    pub defw: f64,
+    pub narrow: f64,
+    pub short: f64,
+    pub tc1: f64,
+    pub tc2: f64,
+    pub tnom: f64,
+}
+
+/// Semiconductor capacitor model card (since 1.1)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CapacitorModel {
+    pub cj: f64,
+    pub cjsw: f64,
+    pub defw: f64,
+    pub narrow: f64,
+    pub short: f64,
+    pub tc1: f64,
+    pub tc2: f64,
+    pub tnom: f64,
+}
+
+/// Distributed RC line model card (since 1.2)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DistributedRcModel {
+    pub k: f64,
+    pub fmax: f64,
+    pub rperl: f64,
+    pub cperl: f64,
+}
+
+/// Junction diode model card (since 1.9)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DiodeModel {
+    pub is: f64,
+    pub n: f64,
+    #[serde(default)]
+    pub rs: f64,
+    /// Since 1.25
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kf: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub af: Option<f64>,
+    /// Since 1.31
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eg: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xti: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tnom: Option<f64>,
+    /// Temperature of the instance (degrees Celsius), if it has its
+    /// own; since 1.31
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temp: Option<f64>,
+}
+
+/// Polarity of a transistor (since 1.34)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Polarity {
+    /// NPN or N-channel
+    N,
+    /// PNP or P-channel
+    P,
+}
+
+/// Bipolar transistor model card (since 1.34)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BjtModel {
+    pub polarity: Polarity,
+    pub is: f64,
+    pub bf: f64,
+    pub br: f64,
+    pub nf: f64,
+    pub nr: f64,
+    pub vaf: f64,
+    pub rb: f64,
+    pub rc: f64,
+    pub re: f64,
+    pub cje: f64,
+    pub cjc: f64,
+    pub eg: f64,
+    pub xti: f64,
+    pub tnom: f64,
+    /// Temperature of the instance (degrees Celsius), if it has its
+    /// own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temp: Option<f64>,
+}
+
+/// MOSFET model card (since 1.34)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MosfetModel {
+    pub polarity: Polarity,
+    pub vto: f64,
+    pub kp: f64,
+    pub lambda: f64,
+    pub rd: f64,
+    pub rs: f64,
+    pub cgso: f64,
+    pub cgdo: f64,
+    pub tnom: f64,
+    /// Drawn width and length of the instance (m)
+    pub width: f64,
+    pub length: f64,
+    /// Temperature of the instance (degrees Celsius), if it has its
+    /// own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temp: Option<f64>,
+}
+
+/// Thresholds and levels of a logic family (since 1.16)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LogicFamily {
+    pub vil: f64,
+    pub vih: f64,
+    pub v_low: f64,
+    pub v_high: f64,
+    pub r_out: f64,
+}
+
+/// Logic function and logic nets of a gate (since 1.16)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "function", rename_all = "snake_case")]
+pub enum LogicGate {
+    Inverter { input: usize, output: usize },
+    Nand { inputs: Vec<usize>, output: usize },
+    Dff { d: usize, clock: usize, q: usize },
+}
+
+/// Thyristor parameters (since 1.6)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ThyristorParams {
+    pub igt: f64,
+    pub ih: f64,
+    pub r_on: f64,
+    pub r_off: f64,
+    pub r_gate: f64,
+}
+
+/// Transient source waveform (since 1.4)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum Waveform {
+    Dc {
+        value: f64,
+    },
+    Pulse {
+        v1: f64,
+        v2: f64,
+        delay: f64,
+        rise: f64,
+        fall: f64,
+        width: f64,
+        period: f64,
+    },
+    Sin {
+        offset: f64,
+        amplitude: f64,
+        frequency: f64,
+        delay: f64,
+        damping: f64,
+    },
+    /// (time, value) points
+    Pwl {
+        points: Vec<(f64, f64)>,
+    },
+    /// Points streamed from a file; since 1.5
+    PwlFile {
+        path: String,
+        #[serde(default)]
+        interpolation: Interpolation,
+        #[serde(default)]
+        out_of_range: OutOfRange,
+    },
+    Delay {
+        delay: f64,
+        waveform: Box<Waveform>,
+    },
+    /// Repeats forever if there is no count
+    Repeat {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        count: Option<usize>,
+        waveform: Box<Waveform>,
+    },
+    Concat {
+        waveforms: Vec<Waveform>,
+    },
+    Sum {
+        waveforms: Vec<Waveform>,
+    },
+}
+
+/// Interpolation between the points of a PWL file (since 1.5)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    Step,
+}
+
+/// Value outside the times of a PWL file (since 1.5)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutOfRange {
+    #[default]
+    Hold,
+    Zero,
+    Repeat,
+}
+
+/// Quantity given by the table of a table device (since 1.15)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TableQuantity {
+    #[default]
+    Current,
+    Resistance,
+}
+
+/// Network parameters of the data of an N-port (since 1.28)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkParameter {
+    #[default]
+    Scattering,
+    Admittance,
+    Impedance,
+}
+
+/// A named component. Fields that are optional default to zero (or
+/// to no current edge).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Component {
+    Resistor {
+        name: String,
+        nodes: [usize; 2],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        current_edge: Option<usize>,
+        resistance: f64,
+    },
+    Thermistor {
+        name: String,
+        nodes: [usize; 2],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        current_edge: Option<usize>,
+        model: ThermistorModel,
+        /// Optional (the temperature of the circuit) since 1.33
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        temperature: Option<f64>,
+    },
+    /// Since 1.1
+    SemiconductorResistor {
+        name: String,
+        nodes: [usize; 2],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        current_edge: Option<usize>,
+        model: ResistorModel,
+        length: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        width: Option<f64>,
+        /// Optional (the temperature of the circuit) since 1.33
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        temperature: Option<f64>,
+    },
+    Capacitor {
+        name: String,
+        nodes: [usize; 2],
+        capacitance: f64,
+    },
+    /// Since 1.1
+    SemiconductorCapacitor {
+        name: String,
+        nodes: [usize; 2],
+        model: CapacitorModel,
+        length: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        width: Option<f64>,
+        /// Optional (the temperature of the circuit) since 1.33
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        temperature: Option<f64>,
+    },
+    Inductor {
+        name: String,
+        nodes: [usize; 2],
+        current_edge: usize,
+        inductance: f64,
+    },
+    SaturableInductor {
+        name: String,
+        nodes: [usize; 2],
+        current_edge: usize,
+        curve: SaturationCurve,
+    },
+    Crystal {
+        name: String,
+        nodes: [usize; 2],
+        fs: f64,
+        q: f64,
+        c0: f64,
+        cm: f64,
+    },
+    /// Nodes are (ESR end, other end); since 1.11
+    Supercapacitor {
+        name: String,
+        nodes: [usize; 2],
+        capacitance: f64,
+        esr: f64,
+        rated_voltage: f64,
+        leakage_current: f64,
+    },
+    /// Nodes are (line end, line end, capacitance); since 1.2
+    Urc {
+        name: String,
+        nodes: [usize; 3],
+        model: DistributedRcModel,
+        length: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lumps: Option<usize>,
+    },
+    /// Nodes are (anode, cathode); since 1.9
+    Diode {
+        name: String,
+        nodes: [usize; 2],
+        model: DiodeModel,
+    },
+    /// Nodes are (anode, cathode); since 1.9
+    Photodiode {
+        name: String,
+        nodes: [usize; 2],
+        model: DiodeModel,
+        responsivity: f64,
+        irradiance: f64,
+    },
+    /// Nodes are (anode, cathode); since 1.18
+    SchottkyDiode {
+        name: String,
+        nodes: [usize; 2],
+        phi_b: f64,
+        area: f64,
+        richardson: f64,
+        n: f64,
+        lowering: f64,
+        #[serde(default)]
+        rs: f64,
+        /// Temperature of the instance (degrees Celsius), if it has its
+        /// own; since 1.33
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        temp: Option<f64>,
+    },
+    /// Nodes are (anode, cathode); since 1.14
+    TunnelDiode {
+        name: String,
+        nodes: [usize; 2],
+        ip: f64,
+        vp: f64,
+        is: f64,
+        n: f64,
+        /// Temperature of the instance (degrees Celsius), if it has its
+        /// own; since 1.33
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        temp: Option<f64>,
+    },
+    /// Points are (voltage, value); since 1.15
+    Table {
+        name: String,
+        nodes: [usize; 2],
+        quantity: TableQuantity,
+        points: Vec<(f64, f64)>,
+    },
+    /// Nodes are (anode, cathode), and the model is its name. Compact
+    /// models are loaded from libraries, so this is written for
+    /// reference but cannot be read back; since 1.17
+    Compact {
+        name: String,
+        nodes: [usize; 2],
+        model: String,
+    },
+    /// Ports are (positive, negative), and the points are (frequency,
+    /// matrix by row then column), with each value as (real,
+    /// imaginary); since 1.28
+    NPort {
+        name: String,
+        ports: Vec<[usize; 2]>,
+        parameter: NetworkParameter,
+        z0: f64,
+        points: Vec<(f64, Vec<(f64, f64)>)>,
+    },
+    /// Since 1.12
+    Fuse {
+        name: String,
+        nodes: [usize; 2],
+        current_edge: usize,
+        i2t: f64,
+        r_on: f64,
+        r_off: f64,
+    },
+    /// Nodes are (coil positive, coil negative, contact, contact);
+    /// since 1.13
+    Relay {
+        name: String,
+        nodes: [usize; 4],
+        coil_edge: usize,
+        contact_edge: usize,
+        r_coil: f64,
+        l_coil: f64,
+        pull_in: f64,
+        drop_out: f64,
+        operate_time: f64,
+        release_time: f64,
+        r_on: f64,
+        r_off: f64,
+    },
+    /// Nodes are (anode, cathode, gate); since 1.6
+    Scr {
+        name: String,
+        nodes: [usize; 3],
+        current_edge: usize,
+        params: ThyristorParams,
+    },
+    /// Nodes are (MT2, MT1, gate); since 1.6
+    Triac {
+        name: String,
+        nodes: [usize; 3],
+        current_edge: usize,
+        params: ThyristorParams,
+    },
+    /// Nodes are (collector, gate, emitter); since 1.8
+    Igbt {
+        name: String,
+        nodes: [usize; 3],
+        current_edge: usize,
+        vth: f64,
+        r_on: f64,
+        r_off: f64,
+        c_ge: f64,
+        tail_fraction: f64,
+        tail_time: f64,
+    },
+    /// Nodes are (collector, base, emitter); since 1.34
+    Bjt {
+        name: String,
+        nodes: [usize; 3],
+        model: BjtModel,
+    },
+    /// Nodes are (drain, gate, source); since 1.34
+    Mosfet {
+        name: String,
+        nodes: [usize; 3],
+        model: MosfetModel,
+    },
+    /// Nodes are (positive, negative); the capacity is in coulombs,
+    /// the OCV is (SOC, voltage) points, and soc is the initial state
+    /// of charge; since 1.10
+    Battery {
+        name: String,
+        nodes: [usize; 2],
+        current_edge: usize,
+        capacity: f64,
+        r_int: f64,
+        ocv: Vec<(f64, f64)>,
+        soc: f64,
+    },
+    /// Since 1.16
+    LogicGate {
+        name: String,
+        gate: LogicGate,
+        delay: f64,
+    },
+    /// Nodes are (positive, negative); since 1.16
+    AdcBridge {
+        name: String,
+        nodes: [usize; 2],
+        net: usize,
+        family: LogicFamily,
+    },
+    /// Nodes are (positive, negative); since 1.16
+    DacBridge {
+        name: String,
+        nodes: [usize; 2],
+        current_edge: usize,
+        net: usize,
+        family: LogicFamily,
+    },
+    /// Nodes are (positive, negative)
+    VoltageSource {
+        name: String,
+        nodes: [usize; 2],
+        current_edge: usize,
+        #[serde(default)]
+        dc: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ac: Option<Ac>,
+        /// Since 1.4
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        waveform: Option<Waveform>,
+    },
+    /// Nodes are (positive, negative); since 1.3
+    CurrentProbe {
+        name: String,
+        nodes: [usize; 2],
+        current_edge: usize,
+    },
+    /// Nodes are (positive, negative). A current probe has a current
+    /// edge, and a voltage probe does not; since 1.7
+    Probe {
+        name: String,
+        nodes: [usize; 2],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        current_edge: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        r_in: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        c_in: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bandwidth: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        averaging_window: Option<f64>,
+    },
+    /// Nodes are (positive, negative); current flows from the positive
+    /// node, through the source, to the negative node
+    CurrentSource {
+        name: String,
+        nodes: [usize; 2],
+        #[serde(default)]
+        dc: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ac: Option<Ac>,
+        /// Since 1.4
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        waveform: Option<Waveform>,
+    },
+    /// Nodes and control nodes are (positive, negative); since 1.26
+    VoltageControlledVoltageSource {
+        name: String,
+        nodes: [usize; 2],
+        control_nodes: [usize; 2],
+        current_edge: usize,
+        gain: f64,
+    },
+    /// Nodes and control nodes are (positive, negative); current flows
+    /// from the positive node, through the source, to the negative
+    /// node; since 1.26
+    VoltageControlledCurrentSource {
+        name: String,
+        nodes: [usize; 2],
+        control_nodes: [usize; 2],
+        transconductance: f64,
+    },
+    /// Nodes are (positive, negative); current flows from the positive
+    /// node, through the source, to the negative node; since 1.26
+    CurrentControlledCurrentSource {
+        name: String,
+        nodes: [usize; 2],
+        control_edge: usize,
+        gain: f64,
+    },
+    /// Nodes are (positive, negative); since 1.26
+    CurrentControlledVoltageSource {
+        name: String,
+        nodes: [usize; 2],
+        control_edge: usize,
+        current_edge: usize,
+        transresistance: f64,
+    },
+}
+
+/// A parameter expression, serialized as its text, as in `2*r + 50`
+/// (see [crate::expression]); since 1.27
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Expression(pub expression::Expression);
+
+impl From<Expression> for String {
+    fn from(expression: Expression) -> String {
+        expression.0.to_string()
+    }
+}
+
+impl TryFrom<String> for Expression {
+    type Error = String;
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        parse_expression(&text)
+            .map(Self)
+            .map_err(|error| format!("Invalid expression '{text}': {error}"))
+    }
+}
+
+/// A model shared by instances; since 1.27
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Model {
+    Diode(DiodeModel),
+    Schottky {
+        phi_b: f64,
+        area: f64,
+        richardson: f64,
+        n: f64,
+        lowering: f64,
+        #[serde(default)]
+        rs: f64,
+    },
+    TunnelDiode {
+        ip: f64,
+        vp: f64,
+        is: f64,
+        n: f64,
+    },
+    /// Since 1.33
+    SemiconductorResistor(ResistorModel),
+    /// Since 1.33
+    SemiconductorCapacitor(CapacitorModel),
+    /// Since 1.34
+    Bjt(BjtModel),
+    /// Since 1.34
+    Mosfet(MosfetModel),
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Circuit {
+    pub components: Vec<Component>,
+    /// Node numbers by name (in lower case); since 1.27
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub nodes: BTreeMap<String, usize>,
+    /// Parameters by name (in lower case); since 1.27
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub parameters: BTreeMap<String, Expression>,
+    /// Expressions of the parameters giving the values of instances,
+    /// by instance name; since 1.27
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub values: BTreeMap<String, Expression>,
+    /// Models by name (in lower case); since 1.27
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub models: BTreeMap<String, Model>,
+    /// The name of the model each instance that uses one uses, by
+    /// instance name; since 1.27
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub model_instances: BTreeMap<String, String>,
+    /// User-defined functions by name (in lower case); since 1.30
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub functions: BTreeMap<String, Function>,
+    /// Temperature (degrees Celsius), if it is not the nominal one;
+    /// since 1.31
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+}
+
+/// A user-defined function of parameter expressions (see
+/// [crate::expression::Function]); since 1.30
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Function {
+    pub arguments: Vec<String>,
+    pub body: Expression,
+}
+
+/// Options for a TDR analysis
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TdrOptions {
+    pub reference_impedance: f64,
+    pub rise_time: f64,
+    pub velocity: f64,
+    pub time_step: f64,
+    pub num_points: usize,
+}
+
+/// Integration method of a transient analysis; since 1.21
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrationMethod {
+    #[default]
+    BackwardEuler,
+    Trapezoidal,
+    /// Since 1.22
+    Gear {
+        order: usize,
+    },
+}
+
+/// Tolerances of the time step control of a transient analysis;
+/// since 1.23
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StepControl {
+    pub reltol: f64,
+    pub abstol: f64,
+    pub vntol: f64,
+    pub chgtol: f64,
+    pub fluxtol: f64,
+    pub trtol: f64,
+    pub min_step: f64,
+}
+
+/// Spacing of the frequencies of an AC sweep; since 1.29
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Variation {
+    #[default]
+    Decade,
+    Octave,
+    Linear,
+}
+
+/// An analysis in a run plan; since 1.19
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Analysis {
+    OperatingPoint,
+    /// Since 1.29
+    DcSweep {
+        instance: String,
+        start: f64,
+        stop: f64,
+        step: f64,
+    },
+    /// Since 1.29
+    Ac {
+        variation: Variation,
+        points: usize,
+        start: f64,
+        stop: f64,
+    },
+    Transient {
+        time_step: f64,
+        stop_time: f64,
+        /// Since 1.21
+        #[serde(default)]
+        method: IntegrationMethod,
+        /// Since 1.23
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        step_control: Option<StepControl>,
+        /// Since 1.24
+        #[serde(default)]
+        use_initial_conditions: bool,
+    },
+}
+
+/// A circuit and the analyses to run on it; since 1.19
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RunPlan {
+    pub circuit: Circuit,
+    pub analyses: Vec<Analysis>,
+}
+
+/// Unit of a signal, serialized as its symbol (an empty string for a
+/// dimensionless or unknown quantity); since 1.20
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Unit {
+    #[default]
+    #[serde(rename = "")]
+    None,
+    #[serde(rename = "V")]
+    Volt,
+    #[serde(rename = "A")]
+    Ampere,
+    #[serde(rename = "W")]
+    Watt,
+    #[serde(rename = "Ω")]
+    Ohm,
+    #[serde(rename = "S")]
+    Siemens,
+    #[serde(rename = "F")]
+    Farad,
+    #[serde(rename = "H")]
+    Henry,
+    #[serde(rename = "s")]
+    Second,
+    #[serde(rename = "Hz")]
+    Hertz,
+    #[serde(rename = "m")]
+    Metre,
+    #[serde(rename = "°C")]
+    Celsius,
+    #[serde(rename = "dB")]
+    Decibel,
+    #[serde(rename = "rad")]
+    Radian,
+}
+
+/// Units that multiply and divide, with their exponents of kg, m, s
+/// and A. Temperatures, levels and angles only scale.
+const DIMENSIONS: &[(Unit, [i8; 4])] = &[
+    (Unit::None, [0, 0, 0, 0]),
+    (Unit::Volt, [1, 2, -3, -1]),
+    (Unit::Ampere, [0, 0, 0, 1]),
+    (Unit::Watt, [1, 2, -3, 0]),
+    (Unit::Ohm, [1, 2, -3, -2]),
+    (Unit::Siemens, [-1, -2, 3, 2]),
+    (Unit::Farad, [-1, -2, 4, 2]),
+    (Unit::Henry, [1, 2, -2, -2]),
+    (Unit::Second, [0, 0, 1, 0]),
+    (Unit::Hertz, [0, 0, -1, 0]),
+    (Unit::Metre, [0, 1, 0, 0]),
+];
+
+impl Unit {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::None => "",
+            Self::Volt => "V",
+            Self::Ampere => "A",
+            Self::Watt => "W",
+            Self::Ohm => "Ω",
+            Self::Siemens => "S",
+            Self::Farad => "F",
+            Self::Henry => "H",
+            Self::Second => "s",
+            Self::Hertz => "Hz",
+            Self::Metre => "m",
+            Self::Celsius => "°C",
+            Self::Decibel => "dB",
+            Self::Radian => "rad",
+        }
+    }
+
+    /// The quantity measured in the unit, named as in a [Curve]
+    pub fn quantity(&self) -> &'static str {
+        match self {
+            Self::None => "",
+            Self::Volt => "voltage",
+            Self::Ampere => "current",
+            Self::Watt => "power",
+            Self::Ohm => "resistance",
+            Self::Siemens => "conductance",
+            Self::Farad => "capacitance",
+            Self::Henry => "inductance",
+            Self::Second => "time",
+            Self::Hertz => "frequency",
+            Self::Metre => "length",
+            Self::Celsius => "temperature",
+            Self::Decibel => "level",
+            Self::Radian => "phase",
+        }
+    }
+
+    /// The unit of a named quantity (None if it is not known)
+    pub fn of_quantity(quantity: &str) -> Self {
+        match quantity {
+            "impedance" => Self::Ohm,
+            "distance" => Self::Metre,
+            _ => [
+                Self::Volt,
+                Self::Ampere,
+                Self::Watt,
+                Self::Ohm,
+                Self::Siemens,
+                Self::Farad,
+                Self::Henry,
+                Self::Second,
+                Self::Hertz,
+                Self::Metre,
+                Self::Celsius,
+                Self::Decibel,
+                Self::Radian,
+            ]
+            .into_iter()
+            .find(|unit| unit.quantity() == quantity)
+            .unwrap_or(Self::None),
+        }
+    }
+
+    /// Unit of the product (sign 1) or quotient (sign -1) of two
+    /// quantities, if there is one
+    fn combine(self, other: Self, sign: i8) -> Option<Self> {
+        if other == Self::None {
+            return Some(self);
+        }
+        if self == Self::None && sign == 1 {
+            return Some(other);
+        }
+        let dimension = |unit| DIMENSIONS.iter().find(|(u, _)| *u == unit).map(|(_, d)| *d);
+        let (a, b) = (dimension(self)?, dimension(other)?);
+        let product = [0, 1, 2, 3].map(|k| a[k] + sign * b[k]);
+        DIMENSIONS
+            .iter()
+            .find(|(_, d)| *d == product)
+            .map(|(unit, _)| *unit)
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "dimensionless"),
+            _ => write!(f, "{}", self.symbol()),
+        }
+    }
+}
+
+/// An error in arithmetic on signals
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignalError {
+    /// The units of the operands do not allow the operation
+    Units {
+        operation: &'static str,
+        left: Unit,
+        right: Unit,
+    },
+    /// The unit of the operand does not allow the operation
+    Unit { operation: &'static str, unit: Unit },
+    /// The operands have different numbers of points
+    Lengths { left: usize, right: usize },
+}
+
+impl fmt::Display for SignalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Units {
+                operation,
+                left,
+                right,
+            } => write!(f, "Cannot {operation} signals in {left} and {right}"),
+            Self::Unit { operation, unit } => {
+                write!(f, "Cannot {operation} a signal in {unit}")
+            }
+            Self::Lengths { left, right } => {
+                write!(f, "Cannot combine signals of {left} and {right} points")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SignalError {}
+
+/// A named signal, with its unit. Complex signals have an imaginary
+/// part.
+///
+/// Signals can be combined by arithmetic (on references, giving a
+/// result), which checks and propagates their units: sums and
+/// differences need the same unit, and products and quotients need a
+/// unit for the result (so volts times amps are watts, but volts
+/// times seconds are an error).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Signal {
+    pub name: String,
+    pub real: Vec<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imag: Option<Vec<f64>>,
+    /// Since 1.20
+    #[serde(default, skip_serializing_if = "is_dimensionless")]
+    pub unit: Unit,
+}
+
+fn is_dimensionless(unit: &Unit) -> bool {
+    *unit == Unit::None
+}
+
+/// A set of signals sampled at the points of an independent variable
+/// (such as time or frequency). An operating point has no axis.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Dataset {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub axis: Option<Signal>,
+    pub signals: Vec<Signal>,
+    /// Warnings of the analysis that gave the signals; since 1.32
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl Signal {
+    pub fn real(name: &str, real: Vec<f64>) -> Self {
+        Self {
+            name: name.to_string(),
+            real,
+            imag: None,
+            unit: Unit::None,
+        }
+    }
+
+    pub fn with_unit(self, unit: Unit) -> Self {
+        Self { unit, ..self }
+    }
+
+    /// Name and unit, for labelling an axis, such as `v(2) (V)`
+    pub fn label(&self) -> String {
+        match self.unit {
+            Unit::None => self.name.clone(),
+            unit => format!("{} ({})", self.name, unit.symbol()),
+        }
+    }
+
+    fn points(&self) -> Vec<Complex<f64>> {
+        match &self.imag {
+            Some(imag) => self
+                .real
+                .iter()
+                .zip(imag)
+                .map(|(re, im)| Complex::new(*re, *im))
+                .collect(),
+            None => self.real.iter().map(|re| Complex::new(*re, 0.0)).collect(),
+        }
+    }
+
+    fn from_points(name: String, unit: Unit, points: &[Complex<f64>], complex: bool) -> Self {
+        Self {
+            name,
+            real: points.iter().map(|p| p.re).collect(),
+            imag: complex.then(|| points.iter().map(|p| p.im).collect()),
+            unit,
+        }
+    }
+
+    /// Name as an operand, in brackets if it is an expression
+    fn operand(&self) -> String {
+        if self.name.contains(' ') {
+            format!("({})", self.name)
+        } else {
+            self.name.clone()
+        }
+    }
+
+    fn combine(
+        &self,
+        other: &Signal,
+        (operation, symbol): (&'static str, &str),
+        unit: Option<Unit>,
+        f: impl Fn(Complex<f64>, Complex<f64>) -> Complex<f64>,
+    ) -> Result<Signal, SignalError> {
+        let unit = unit.ok_or(SignalError::Units {
+            operation,
+            left: self.unit,
+            right: other.unit,
+        })?;
+        if self.real.len() != other.real.len() {
+            return Err(SignalError::Lengths {
+                left: self.real.len(),
+                right: other.real.len(),
+            });
+        }
+        let points: Vec<_> = self
+            .points()
+            .into_iter()
+            .zip(other.points())
+            .map(|(a, b)| f(a, b))
+            .collect();
+        Ok(Self::from_points(
+            format!("{} {symbol} {}", self.operand(), other.operand()),
+            unit,
+            &points,
+            self.imag.is_some() || other.imag.is_some(),
+        ))
+    }
+
+    /// The signal multiplied by a dimensionless constant
+    pub fn scale(&self, factor: f64) -> Signal {
+        let points: Vec<_> = self.points().into_iter().map(|p| p * factor).collect();
+        Self::from_points(
+            format!("{factor} * {}", self.operand()),
+            self.unit,
+            &points,
+            self.imag.is_some(),
+        )
+    }
+
+    /// Magnitude of each point
+    pub fn magnitude(&self) -> Signal {
+        let real = self.points().iter().map(|p| p.norm()).collect();
+        Signal::real(&format!("mag({})", self.name), real).with_unit(self.unit)
+    }
+
+    /// Phase of each point, in radians
+    pub fn phase(&self) -> Signal {
+        let real = self.points().iter().map(|p| p.arg()).collect();
+        Signal::real(&format!("ph({})", self.name), real).with_unit(Unit::Radian)
+    }
+
+    /// Level of each point in decibels: 20 log10 of the magnitude for a
+    /// voltage, current or ratio, and 10 log10 for a power
+    pub fn db(&self) -> Result<Signal, SignalError> {
+        let factor = match self.unit {
+            Unit::Volt | Unit::Ampere | Unit::None => 20.0,
+            Unit::Watt => 10.0,
+            unit => {
+                return Err(SignalError::Unit {
+                    operation: "take the level of",
+                    unit,
+                })
+            }
+        };
+        let real = self
+            .points()
+            .iter()
+            .map(|p| factor * p.norm().log10())
+            .collect();
+        Ok(Signal::real(&format!("db({})", self.name), real).with_unit(Unit::Decibel))
+    }
+}
+
+impl ops::Add for &Signal {
+    type Output = Result<Signal, SignalError>;
+    fn add(self, other: &Signal) -> Self::Output {
+        let unit = (self.unit == other.unit).then_some(self.unit);
+        self.combine(other, ("add", "+"), unit, |a, b| a + b)
+    }
+}
+
+impl ops::Sub for &Signal {
+    type Output = Result<Signal, SignalError>;
+    fn sub(self, other: &Signal) -> Self::Output {
+        let unit = (self.unit == other.unit).then_some(self.unit);
+        self.combine(other, ("subtract", "-"), unit, |a, b| a - b)
+    }
+}
+
+impl ops::Mul for &Signal {
+    type Output = Result<Signal, SignalError>;
+    fn mul(self, other: &Signal) -> Self::Output {
+        let unit = self.unit.combine(other.unit, 1);
+        self.combine(other, ("multiply", "*"), unit, |a, b| a * b)
+    }
+}
+
+impl ops::Div for &Signal {
+    type Output = Result<Signal, SignalError>;
+    fn div(self, other: &Signal) -> Self::Output {
+        let unit = self.unit.combine(other.unit, -1);
+        self.combine(other, ("divide", "/"), unit, |a, b| a / b)
+    }
+}
+
+fn ac_to_schema(ac: AcSpec) -> Option<Ac> {
+    if ac == AcSpec::default() {
+        None
+    } else {
+        Some(Ac {
+            magnitude: ac.magnitude,
+            phase: ac.phase,
+        })
+    }
+}
+
+fn ac_from_schema(ac: Option<Ac>) -> AcSpec {
+    ac.map(|ac| AcSpec::new(ac.magnitude, ac.phase))
+        .unwrap_or_default()
+}
+
+impl From<component::DiodeModel> for DiodeModel {
+    fn from(model: component::DiodeModel) -> Self {
+        let defaults = component::DiodeModel::default();
+        Self {
+            is: model.is,
+            n: model.n,
+            rs: model.rs,
+            kf: (model.kf != 0.0).then_some(model.kf),
+            af: (model.af != 1.0).then_some(model.af),
+            eg: (model.eg != defaults.eg).then_some(model.eg),
+            xti: (model.xti != defaults.xti).then_some(model.xti),
+            tnom: (model.tnom != defaults.tnom).then_some(model.tnom),
+            temp: model.temp,
+        }
+    }
+}
+
+impl From<DiodeModel> for component::DiodeModel {
+    fn from(model: DiodeModel) -> Self {
+        let defaults = component::DiodeModel::default();
+        Self {
+            is: model.is,
+            n: model.n,
+            rs: model.rs,
+            kf: model.kf.unwrap_or(defaults.kf),
+            af: model.af.unwrap_or(defaults.af),
+            eg: model.eg.unwrap_or(defaults.eg),
+            xti: model.xti.unwrap_or(defaults.xti),
+            tnom: model.tnom.unwrap_or(defaults.tnom),
+            temp: model.temp,
+        }
+    }
+}
+
+impl From<component::Polarity> for Polarity {
+    fn from(polarity: component::Polarity) -> Self {
+        match polarity {
+            component::Polarity::N => Self::N,
+            component::Polarity::P => Self::P,
+        }
+    }
+}
+
+impl From<Polarity> for component::Polarity {
+    fn from(polarity: Polarity) -> Self {
+        match polarity {
+            Polarity::N => Self::N,
+            Polarity::P => Self::P,
+        }
+    }
+}
+
+impl From<component::BjtModel> for BjtModel {
+    fn from(model: component::BjtModel) -> Self {
+        Self {
+            polarity: model.polarity.into(),
+            is: model.is,
+            bf: model.bf,
+            br: model.br,
+            nf: model.nf,
+            nr: model.nr,
+            vaf: model.vaf,
+            rb: model.rb,
+            rc: model.rc,
+            re: model.re,
+            cje: model.cje,
+            cjc: model.cjc,
+            eg: model.eg,
+            xti: model.xti,
+            tnom: model.tnom,
+            temp: model.temp,
+        }
+    }
+}
+
+impl From<BjtModel> for component::BjtModel {
+    fn from(model: BjtModel) -> Self {
+        Self {
+            polarity: model.polarity.into(),
+            is: model.is,
+            bf: model.bf,
+            br: model.br,
+            nf: model.nf,
+            nr: model.nr,
+            vaf: model.vaf,
+            rb: model.rb,
+            rc: model.rc,
+            re: model.re,
+            cje: model.cje,
+            cjc: model.cjc,
+            eg: model.eg,
+            xti: model.xti,
+            tnom: model.tnom,
+            temp: model.temp,
+        }
+    }
+}
+
+impl From<component::MosfetModel> for MosfetModel {
+    fn from(model: component::MosfetModel) -> Self {
+        Self {
+            polarity: model.polarity.into(),
+            vto: model.vto,
+            kp: model.kp,
+            lambda: model.lambda,
+            rd: model.rd,
+            rs: model.rs,
+            cgso: model.cgso,
+            cgdo: model.cgdo,
+            tnom: model.tnom,
+            width: model.width,
+            length: model.length,
+            temp: model.temp,
+        }
+    }
+}
+
+impl From<MosfetModel> for component::MosfetModel {
+    fn from(model: MosfetModel) -> Self {
+        Self {
+            polarity: model.polarity.into(),
+            vto: model.vto,
+            kp: model.kp,
+            lambda: model.lambda,
+            rd: model.rd,
+            rs: model.rs,
+            cgso: model.cgso,
+            cgdo: model.cgdo,
+            tnom: model.tnom,
+            width: model.width,
+            length: model.length,
+            temp: model.temp,
+        }
+    }
+}
+
+impl From<SemiconductorResistorModel> for ResistorModel {
+    fn from(model: SemiconductorResistorModel) -> Self {
+        Self {
+            rsh: model.rsh,
+            defw: model.defw,
+            narrow: model.narrow,
+            short: model.short,
+            tc1: model.tc1,
+            tc2: model.tc2,
+            tnom: model.tnom,
+        }
+    }
+}
+
+impl From<ResistorModel> for SemiconductorResistorModel {
+    fn from(model: ResistorModel) -> Self {
+        Self {
+            rsh: model.rsh,
+            defw: model.defw,
+            narrow: model.narrow,
+            short: model.short,
+            tc1: model.tc1,
+            tc2: model.tc2,
+            tnom: model.tnom,
+        }
+    }
+}
+
+impl From<SemiconductorCapacitorModel> for CapacitorModel {
+    fn from(model: SemiconductorCapacitorModel) -> Self {
+        Self {
+            cj: model.cj,
+            cjsw: model.cjsw,
+            defw: model.defw,
+            narrow: model.narrow,
+            short: model.short,
+            tc1: model.tc1,
+            tc2: model.tc2,
+            tnom: model.tnom,
+        }
+    }
+}
+
+impl From<CapacitorModel> for SemiconductorCapacitorModel {
+    fn from(model: CapacitorModel) -> Self {
+        Self {
+            cj: model.cj,
+            cjsw: model.cjsw,
+            defw: model.defw,
+            narrow: model.narrow,
+            short: model.short,
+            tc1: model.tc1,
+            tc2: model.tc2,
+            tnom: model.tnom,
+        }
+    }
+}
+
+impl From<component::LogicFamily> for LogicFamily {
+    fn from(family: component::LogicFamily) -> Self {
+        Self {
+            vil: family.vil,
+            vih: family.vih,
+            v_low: family.v_low,
+            v_high: family.v_high,
+            r_out: family.r_out,
+        }
+    }
+}
+
+impl From<LogicFamily> for component::LogicFamily {
+    fn from(family: LogicFamily) -> Self {
+        Self {
+            vil: family.vil,
+            vih: family.vih,
+            v_low: family.v_low,
+            v_high: family.v_high,
+            r_out: family.r_out,
+        }
+    }
+}
+
+impl From<&component::LogicGate> for LogicGate {
+    fn from(gate: &component::LogicGate) -> Self {
+        use component::LogicGate as G;
+        match gate.clone() {
+            G::Inverter { input, output } => Self::Inverter { input, output },
+            G::Nand { inputs, output } => Self::Nand { inputs, output },
+            G::Dff { d, clock, q } => Self::Dff { d, clock, q },
+        }
+    }
+}
+
+impl From<LogicGate> for component::LogicGate {
+    fn from(gate: LogicGate) -> Self {
+        match gate {
+            LogicGate::Inverter { input, output } => Self::Inverter { input, output },
+            LogicGate::Nand { inputs, output } => Self::Nand { inputs, output },
+            LogicGate::Dff { d, clock, q } => Self::Dff { d, clock, q },
+        }
+    }
+}
+
+fn thyristor_from_schema(
+    [anode, cathode, gate]: [usize; 3],
+    current_edge: usize,
+    kind: ThyristorKind,
+    params: ThyristorParams,
+) -> component::Component {
+    component::Component::Thyristor {
+        anode,
+        cathode,
+        gate,
+        current_edge,
+        kind,
+        params: component::ThyristorParams {
+            igt: params.igt,
+            ih: params.ih,
+            r_on: params.r_on,
+            r_off: params.r_off,
+            r_gate: params.r_gate,
+        },
+    }
+}
+
+impl From<&waveform::Waveform> for Waveform {
+    fn from(waveform: &waveform::Waveform) -> Self {
+        use waveform::Waveform as W;
+        match *waveform {
+            W::Dc(value) => Self::Dc { value },
+            W::Pulse {
+                v1,
+                v2,
+                delay,
+                rise,
+                fall,
+                width,
+                period,
+            } => Self::Pulse {
+                v1,
+                v2,
+                delay,
+                rise,
+                fall,
+                width,
+                period,
+            },
+            W::Sin {
+                offset,
+                amplitude,
+                frequency,
+                delay,
+                damping,
+            } => Self::Sin {
+                offset,
+                amplitude,
+                frequency,
+                delay,
+                damping,
+            },
+            W::Pwl(ref points) => Self::Pwl {
+                points: points.clone(),
+            },
+            W::PwlFile(ref file) => Self::PwlFile {
+                path: file.path().to_string_lossy().into_owned(),
+                interpolation: match file.interpolation {
+                    waveform::Interpolation::Linear => Interpolation::Linear,
+                    waveform::Interpolation::Step => Interpolation::Step,
+                },
+                out_of_range: match file.out_of_range {
+                    waveform::OutOfRange::Hold => OutOfRange::Hold,
+                    waveform::OutOfRange::Zero => OutOfRange::Zero,
+                    waveform::OutOfRange::Repeat => OutOfRange::Repeat,
+                },
+            },
+            W::Delay {
+                delay,
+                ref waveform,
+            } => Self::Delay {
+                delay,
+                waveform: Box::new(waveform.as_ref().into()),
+            },
+            W::Repeat {
+                count,
+                ref waveform,
+            } => Self::Repeat {
+                count,
+                waveform: Box::new(waveform.as_ref().into()),
+            },
+            W::Concat(ref waveforms) => Self::Concat {
+                waveforms: waveforms.iter().map(Self::from).collect(),
+            },
+            W::Sum(ref waveforms) => Self::Sum {
+                waveforms: waveforms.iter().map(Self::from).collect(),
+            },
+        }
+    }
+}
+
+impl TryFrom<Waveform> for waveform::Waveform {
+    type Error = EsimError;
+
+    /// Fails if a PWL file cannot be read or parsed
+    fn try_from(waveform: Waveform) -> Result<Self, EsimError> {
+        Ok(match waveform {
+            Waveform::Dc { value } => Self::Dc(value),
+            Waveform::Pulse {
+                v1,
+                v2,
+                delay,
+                rise,
+                fall,
+                width,
+                period,
+            } => Self::Pulse {
+                v1,
+                v2,
+                delay,
+                rise,
+                fall,
+                width,
+                period,
+            },
+            Waveform::Sin {
+                offset,
+                amplitude,
+                frequency,
+                delay,
+                damping,
+            } => Self::Sin {
+                offset,
+                amplitude,
+                frequency,
+                delay,
+                damping,
+            },
+            Waveform::Pwl { points } => Self::Pwl(points),
+            Waveform::PwlFile {
+                path,
+                interpolation,
+                out_of_range,
+            } => Self::PwlFile(waveform::PwlFile::try_new(
+                path,
+                match interpolation {
+                    Interpolation::Linear => waveform::Interpolation::Linear,
+                    Interpolation::Step => waveform::Interpolation::Step,
+                },
+                match out_of_range {
+                    OutOfRange::Hold => waveform::OutOfRange::Hold,
+                    OutOfRange::Zero => waveform::OutOfRange::Zero,
+                    OutOfRange::Repeat => waveform::OutOfRange::Repeat,
+                },
+            )?),
+            Waveform::Delay { delay, waveform } => Self::Delay {
+                delay,
+                waveform: Box::new((*waveform).try_into()?),
+            },
+            Waveform::Repeat { count, waveform } => Self::Repeat {
+                count,
+                waveform: Box::new((*waveform).try_into()?),
+            },
+            Waveform::Concat { waveforms } => Self::Concat(
+                waveforms
+                    .into_iter()
+                    .map(Self::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Waveform::Sum { waveforms } => Self::Sum(
+                waveforms
+                    .into_iter()
+                    .map(Self::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+}
+
+impl From<&circuit::Instance> for Component {
+    fn from(instance: &circuit::Instance) -> Self {
+        use component::Component as C;
+        let name = instance.name.clone();
+        match instance.component {
+            C::Resistor {
+                term_1,
+                term_2,
+                current_edge,
+                resistance,
+            } => Self::Resistor {
+                name,
+                nodes: [term_1, term_2],
+                current_edge,
+                resistance,
+            },
+            C::Thermistor {
+                term_1,
+                term_2,
+                current_edge,
+                model,
+                temperature,
+            } => Self::Thermistor {
+                name,
+                nodes: [term_1, term_2],
+                current_edge,
+                model: match model {
+                    component::ThermistorModel::Beta { r0, t0, beta } => {
+                        ThermistorModel::Beta { r0, t0, beta }
+                    }
+                    component::ThermistorModel::SteinhartHart { a, b, c } => {
+                        ThermistorModel::SteinhartHart { a, b, c }
+                    }
+                },
+                temperature,
+            },
+            C::SemiconductorResistor {
+                term_1,
+                term_2,
+                current_edge,
+                model,
+                length,
+                width,
+                temperature,
+            } => Self::SemiconductorResistor {
+                name,
+                nodes: [term_1, term_2],
+                current_edge,
+                model: model.into(),
+                length,
+                width,
+                temperature,
+            },
+            C::Capacitor {
+                term_1,
+                term_2,
+                capacitance,
+            } => Self::Capacitor {
+                name,
+                nodes: [term_1, term_2],
+                capacitance,
+            },
+            C::SemiconductorCapacitor {
+                term_1,
+                term_2,
+                model,
+                length,
+                width,
+                temperature,
+            } => Self::SemiconductorCapacitor {
+                name,
+                nodes: [term_1, term_2],
+                model: model.into(),
+                length,
+                width,
+                temperature,
+            },
+            C::Inductor {
+                term_1,
+                term_2,
+                current_edge,
+                inductance,
+            } => Self::Inductor {
+                name,
+                nodes: [term_1, term_2],
+                current_edge,
+                inductance,
+            },
+            C::SaturableInductor {
+                term_1,
+                term_2,
+                current_edge,
+                ref curve,
+            } => Self::SaturableInductor {
+                name,
+                nodes: [term_1, term_2],
+                current_edge,
+                curve: match curve {
+                    component::SaturationCurve::Arctan { l0, l_sat, i_sat } => {
+                        SaturationCurve::Arctan {
+                            l0: *l0,
+                            l_sat: *l_sat,
+                            i_sat: *i_sat,
+                        }
+                    }
+                    component::SaturationCurve::Pwl { points } => SaturationCurve::Pwl {
+                        points: points.clone(),
+                    },
+                },
+            },
+            C::Crystal {
+                term_1,
+                term_2,
+                params,
+            } => Self::Crystal {
+                name,
+                nodes: [term_1, term_2],
+                fs: params.fs,
+                q: params.q,
+                c0: params.c0,
+                cm: params.cm,
+            },
+            C::Supercapacitor {
+                term_1,
+                term_2,
+                params,
+            } => Self::Supercapacitor {
+                name,
+                nodes: [term_1, term_2],
+                capacitance: params.capacitance,
+                esr: params.esr,
+                rated_voltage: params.rated_voltage,
+                leakage_current: params.leakage_current,
+            },
+            C::Urc {
+                term_1,
+                term_2,
+                term_cap,
+                model,
+                length,
+                lumps,
+            } => Self::Urc {
+                name,
+                nodes: [term_1, term_2, term_cap],
+                model: DistributedRcModel {
+                    k: model.k,
+                    fmax: model.fmax,
+                    rperl: model.rperl,
+                    cperl: model.cperl,
+                },
+                length,
+                lumps,
+            },
+            C::Diode {
+                anode,
+                cathode,
+                model,
+            } => Self::Diode {
+                name,
+                nodes: [anode, cathode],
+                model: model.into(),
+            },
+            C::Photodiode {
+                anode,
+                cathode,
+                model,
+                responsivity,
+                irradiance,
+            } => Self::Photodiode {
+                name,
+                nodes: [anode, cathode],
+                model: model.into(),
+                responsivity,
+                irradiance,
+            },
+            C::Thyristor {
+                anode,
+                cathode,
+                gate,
+                current_edge,
+                kind,
+                params,
+            } => {
+                let nodes = [anode, cathode, gate];
+                let params = ThyristorParams {
+                    igt: params.igt,
+                    ih: params.ih,
+                    r_on: params.r_on,
+                    r_off: params.r_off,
+                    r_gate: params.r_gate,
+                };
+                match kind {
+                    ThyristorKind::Scr => Self::Scr {
+                        name,
+                        nodes,
+                        current_edge,
+                        params,
+                    },
+                    ThyristorKind::Triac => Self::Triac {
+                        name,
+                        nodes,
+                        current_edge,
+                        params,
+                    },
+                }
+            }
+            C::TunnelDiode {
+                anode,
+                cathode,
+                model,
+            } => Self::TunnelDiode {
+                name,
+                nodes: [anode, cathode],
+                ip: model.ip,
+                vp: model.vp,
+                is: model.is,
+                n: model.n,
+                temp: model.temp,
+            },
+            C::SchottkyDiode {
+                anode,
+                cathode,
+                model,
+            } => Self::SchottkyDiode {
+                name,
+                nodes: [anode, cathode],
+                phi_b: model.phi_b,
+                area: model.area,
+                richardson: model.richardson,
+                n: model.n,
+                lowering: model.lowering,
+                rs: model.rs,
+                temp: model.temp,
+            },
+            C::Table {
+                term_1,
+                term_2,
+                ref model,
+            } => Self::Table {
+                name,
+                nodes: [term_1, term_2],
+                quantity: match model.quantity {
+                    component::TableQuantity::Current => TableQuantity::Current,
+                    component::TableQuantity::Resistance => TableQuantity::Resistance,
+                },
+                points: model.points.clone(),
+            },
+            C::Compact {
+                anode,
+                cathode,
+                ref model,
+            } => Self::Compact {
+                name,
+                nodes: [anode, cathode],
+                model: model.name.clone(),
+            },
+            C::NPort {
+                ref ports,
+                ref data,
+            } => Self::NPort {
+                name,
+                ports: ports.iter().map(|(pos, neg)| [*pos, *neg]).collect(),
+                parameter: match data.parameter {
+                    component::NetworkParameter::Scattering => NetworkParameter::Scattering,
+                    component::NetworkParameter::Admittance => NetworkParameter::Admittance,
+                    component::NetworkParameter::Impedance => NetworkParameter::Impedance,
+                },
+                z0: data.z0,
+                points: data
+                    .points
+                    .iter()
+                    .map(|(frequency, matrix)| {
+                        (*frequency, matrix.iter().map(|v| (v.re, v.im)).collect())
+                    })
+                    .collect(),
+            },
+            C::Fuse {
+                term_1,
+                term_2,
+                current_edge,
+                params,
+            } => Self::Fuse {
+                name,
+                nodes: [term_1, term_2],
+                current_edge,
+                i2t: params.i2t,
+                r_on: params.r_on,
+                r_off: params.r_off,
+            },
+            C::Relay {
+                coil_pos,
+                coil_neg,
+                contact_1,
+                contact_2,
+                coil_edge,
+                contact_edge,
+                params,
+            } => Self::Relay {
+                name,
+                nodes: [coil_pos, coil_neg, contact_1, contact_2],
+                coil_edge,
+                contact_edge,
+                r_coil: params.r_coil,
+                l_coil: params.l_coil,
+                pull_in: params.pull_in,
+                drop_out: params.drop_out,
+                operate_time: params.operate_time,
+                release_time: params.release_time,
+                r_on: params.r_on,
+                r_off: params.r_off,
+            },
+            C::Igbt {
+                collector,
+                gate,
+                emitter,
+                current_edge,
+                params,
+            } => Self::Igbt {
+                name,
+                nodes: [collector, gate, emitter],
+                current_edge,
+                vth: params.vth,
+                r_on: params.r_on,
+                r_off: params.r_off,
+                c_ge: params.c_ge,
+                tail_fraction: params.tail_fraction,
+                tail_time: params.tail_time,
+            },
+            C::Bjt {
+                collector,
+                base,
+                emitter,
+                model,
+            } => Self::Bjt {
+                name,
+                nodes: [collector, base, emitter],
+                model: model.into(),
+            },
+            C::Mosfet {
+                drain,
+                gate,
+                source,
+                model,
+            } => Self::Mosfet {
+                name,
+                nodes: [drain, gate, source],
+                model: model.into(),
+            },
+            C::Battery {
+                term_pos,
+                term_neg,
+                current_edge,
+                ref model,
+                soc,
+            } => Self::Battery {
+                name,
+                nodes: [term_pos, term_neg],
+                current_edge,
+                capacity: model.capacity,
+                r_int: model.r_int,
+                ocv: model.ocv.clone(),
+                soc,
+            },
+            C::LogicGate { ref gate, delay } => Self::LogicGate {
+                name,
+                gate: gate.into(),
+                delay,
+            },
+            C::AdcBridge {
+                term_pos,
+                term_neg,
+                net,
+                family,
+            } => Self::AdcBridge {
+                name,
+                nodes: [term_pos, term_neg],
+                net,
+                family: family.into(),
+            },
+            C::DacBridge {
+                term_pos,
+                term_neg,
+                current_edge,
+                net,
+                family,
+            } => Self::DacBridge {
+                name,
+                nodes: [term_pos, term_neg],
+                current_edge,
+                net,
+                family: family.into(),
+            },
+            C::IndependentVoltageSource {
+                term_pos,
+                term_neg,
+                current_edge,
+                voltage,
+                ac,
+                ref waveform,
+            } => Self::VoltageSource {
+                name,
+                nodes: [term_pos, term_neg],
+                current_edge,
+                dc: voltage,
+                ac: ac_to_schema(ac),
+                waveform: waveform.as_ref().map(Waveform::from),
+            },
+            C::CurrentProbe {
+                term_pos,
+                term_neg,
+                current_edge,
+            } => Self::CurrentProbe {
+                name,
+                nodes: [term_pos, term_neg],
+                current_edge,
+            },
+            C::MeasurementProbe {
+                term_pos,
+                term_neg,
+                current_edge,
+                params,
+            } => Self::Probe {
+                name,
+                nodes: [term_pos, term_neg],
+                current_edge,
+                r_in: params.r_in,
+                c_in: params.c_in,
+                bandwidth: params.bandwidth,
+                averaging_window: params.averaging_window,
+            },
+            C::IndependentCurrentSource {
+                term_pos,
+                term_neg,
+                current,
+                ac,
+                ref waveform,
+            } => Self::CurrentSource {
+                name,
+                nodes: [term_pos, term_neg],
+                dc: current,
+                ac: ac_to_schema(ac),
+                waveform: waveform.as_ref().map(Waveform::from),
+            },
+            C::VoltageControlledVoltageSource {
+                term_pos,
+                term_neg,
+                ctrl_pos,
+                ctrl_neg,
+                current_edge,
+                gain,
+            } => Self::VoltageControlledVoltageSource {
+                name,
+                nodes: [term_pos, term_neg],
+                control_nodes: [ctrl_pos, ctrl_neg],
+                current_edge,
+                gain,
+            },
+            C::VoltageControlledCurrentSource {
+                term_pos,
+                term_neg,
+                ctrl_pos,
+                ctrl_neg,
+                transconductance,
+            } => Self::VoltageControlledCurrentSource {
+                name,
+                nodes: [term_pos, term_neg],
+                control_nodes: [ctrl_pos, ctrl_neg],
+                transconductance,
+            },
+            C::CurrentControlledCurrentSource {
+                term_pos,
+                term_neg,
+                ctrl_edge,
+                gain,
+            } => Self::CurrentControlledCurrentSource {
+                name,
+                nodes: [term_pos, term_neg],
+                control_edge: ctrl_edge,
+                gain,
+            },
+            C::CurrentControlledVoltageSource {
+                term_pos,
+                term_neg,
+                ctrl_edge,
+                current_edge,
+                transresistance,
+            } => Self::CurrentControlledVoltageSource {
+                name,
+                nodes: [term_pos, term_neg],
+                control_edge: ctrl_edge,
+                current_edge,
+                transresistance,
+            },
+        }
+    }
+}
+
+impl TryFrom<SaturationCurve> for component::SaturationCurve {
+    type Error = EsimError;
+
+    /// Fails if the curve is not valid (such as a PWL curve with fewer
+    /// than two points)
+    fn try_from(curve: SaturationCurve) -> Result<Self, EsimError> {
+        let curve = match curve {
+            SaturationCurve::Arctan { l0, l_sat, i_sat } => {
+                component::SaturationCurve::Arctan { l0, l_sat, i_sat }
+            }
+            SaturationCurve::Pwl { points } => component::SaturationCurve::Pwl { points },
+        };
+        curve.check()?;
+        Ok(curve)
+    }
+}
+
+impl TryFrom<Component> for circuit::Instance {
+    type Error = EsimError;
+
+    /// Fails if the data of a component is not valid (such as a table
+    /// or a saturation curve with fewer than two points), its waveform
+    /// file cannot be read, or it is a compact model, which is loaded
+    /// from a library
+    fn try_from(component: Component) -> Result<Self, EsimError> {
+        use component::Component as C;
+        let (name, component) = match component {
+            Component::Resistor {
+                name,
+                nodes: [term_1, term_2],
+                current_edge,
+                resistance,
+            } => (
+                name,
+                C::Resistor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    resistance,
+                },
+            ),
+            Component::Thermistor {
+                name,
+                nodes: [term_1, term_2],
+                current_edge,
+                model,
+                temperature,
+            } => (
+                name,
+                C::Thermistor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    model: match model {
+                        ThermistorModel::Beta { r0, t0, beta } => {
+                            component::ThermistorModel::Beta { r0, t0, beta }
+                        }
+                        ThermistorModel::SteinhartHart { a, b, c } => {
+                            component::ThermistorModel::SteinhartHart { a, b, c }
+                        }
+                    },
+                    temperature,
+                },
+            ),
+            Component::SemiconductorResistor {
+                name,
+                nodes: [term_1, term_2],
+                current_edge,
+                model,
+                length,
+                width,
+                temperature,
+            } => (
+                name,
+                C::SemiconductorResistor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    model: model.into(),
+                    length,
+                    width,
+                    temperature,
+                },
+            ),
+            Component::SemiconductorCapacitor {
+                name,
+                nodes: [term_1, term_2],
+                model,
+                length,
+                width,
+                temperature,
+            } => (
+                name,
+                C::SemiconductorCapacitor {
+                    term_1,
+                    term_2,
+                    model: model.into(),
+                    length,
+                    width,
+                    temperature,
+                },
+            ),
+            Component::Capacitor {
+                name,
+                nodes: [term_1, term_2],
+                capacitance,
+            } => (
+                name,
+                C::Capacitor {
+                    term_1,
+                    term_2,
+                    capacitance,
+                },
+            ),
+            Component::Inductor {
+                name,
+                nodes: [term_1, term_2],
+                current_edge,
+                inductance,
+            } => (
+                name,
+                C::Inductor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    inductance,
+                },
+            ),
+            Component::SaturableInductor {
+                name,
+                nodes: [term_1, term_2],
+                current_edge,
+                curve,
+            } => (
+                name,
+                C::SaturableInductor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    curve: curve.try_into()?,
+                },
+            ),
+            Component::Crystal {
+                name,
+                nodes: [term_1, term_2],
+                fs,
+                q,
+                c0,
+                cm,
+            } => (
+                name,
+                C::Crystal {
+                    term_1,
+                    term_2,
+                    params: CrystalParams { fs, q, c0, cm },
+                },
+            ),
+            Component::Supercapacitor {
+                name,
+                nodes: [term_1, term_2],
+                capacitance,
+                esr,
+                rated_voltage,
+                leakage_current,
+            } => (
+                name,
+                C::Supercapacitor {
+                    term_1,
+                    term_2,
+                    params: SupercapParams {
+                        capacitance,
+                        esr,
+                        rated_voltage,
+                        leakage_current,
+                    },
+                },
+            ),
+            Component::Urc {
+                name,
+                nodes: [term_1, term_2, term_cap],
+                model,
+                length,
+                lumps,
+            } => (
+                name,
+                C::Urc {
+                    term_1,
+                    term_2,
+                    term_cap,
+                    model: UrcModel {
+                        k: model.k,
+                        fmax: model.fmax,
+                        rperl: model.rperl,
+                        cperl: model.cperl,
+                    },
+                    length,
+                    lumps,
+                },
+            ),
+            Component::Diode {
+                name,
+                nodes: [anode, cathode],
+                model,
+            } => (
+                name,
+                C::Diode {
+                    anode,
+                    cathode,
+                    model: model.into(),
+                },
+            ),
+            Component::Photodiode {
+                name,
+                nodes: [anode, cathode],
+                model,
+                responsivity,
+                irradiance,
+            } => (
+                name,
+                C::Photodiode {
+                    anode,
+                    cathode,
+                    model: model.into(),
+                    responsivity,
+                    irradiance,
+                },
+            ),
+            Component::Scr {
+                name,
+                nodes,
+                current_edge,
+                params,
+            } => (
+                name,
+                thyristor_from_schema(nodes, current_edge, ThyristorKind::Scr, params),
+            ),
+            Component::Triac {
+                name,
+                nodes,
+                current_edge,
+                params,
+            } => (
+                name,
+                thyristor_from_schema(nodes, current_edge, ThyristorKind::Triac, params),
+            ),
+            Component::TunnelDiode {
+                name,
+                nodes: [anode, cathode],
+                ip,
+                vp,
+                is,
+                n,
+                temp,
+            } => (
+                name,
+                C::TunnelDiode {
+                    anode,
+                    cathode,
+                    model: TunnelDiodeModel {
+                        ip,
+                        vp,
+                        is,
+                        n,
+                        temp,
+                    },
+                },
+            ),
+            Component::SchottkyDiode {
+                name,
+                nodes: [anode, cathode],
+                phi_b,
+                area,
+                richardson,
+                n,
+                lowering,
+                rs,
+                temp,
+            } => (
+                name,
+                C::SchottkyDiode {
+                    anode,
+                    cathode,
+                    model: SchottkyModel {
+                        phi_b,
+                        area,
+                        richardson,
+                        n,
+                        lowering,
+                        rs,
+                        temp,
+                    },
+                },
+            ),
+            Component::Table {
+                name,
+                nodes: [term_1, term_2],
+                quantity,
+                points,
+            } => {
+                let quantity = match quantity {
+                    TableQuantity::Current => component::TableQuantity::Current,
+                    TableQuantity::Resistance => component::TableQuantity::Resistance,
+                };
+                (
+                    name,
+                    C::Table {
+                        term_1,
+                        term_2,
+                        model: TableModel::try_new(points, quantity)?,
+                    },
+                )
+            }
+            Component::Compact { name, model, .. } => {
+                return Err(invalid(format!(
+                    "Compact model {model} of {name} cannot be read from a document"
+                )))
+            }
+            Component::NPort {
+                name,
+                ports,
+                parameter,
+                z0,
+                points,
+            } => {
+                let parameter = match parameter {
+                    NetworkParameter::Scattering => component::NetworkParameter::Scattering,
+                    NetworkParameter::Admittance => component::NetworkParameter::Admittance,
+                    NetworkParameter::Impedance => component::NetworkParameter::Impedance,
+                };
+                let points = points
+                    .into_iter()
+                    .map(|(frequency, matrix)| {
+                        let matrix = matrix
+                            .into_iter()
+                            .map(|(re, im)| Complex::new(re, im))
+                            .collect();
+                        (frequency, matrix)
+                    })
+                    .collect();
+                (
+                    name,
+                    C::NPort {
+                        ports: ports.into_iter().map(|[pos, neg]| (pos, neg)).collect(),
+                        data: Arc::new(TouchstoneData::try_new(parameter, z0, points)?),
+                    },
+                )
+            }
+            Component::Fuse {
+                name,
+                nodes: [term_1, term_2],
+                current_edge,
+                i2t,
+                r_on,
+                r_off,
+            } => (
+                name,
+                C::Fuse {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    params: FuseParams { i2t, r_on, r_off },
+                },
+            ),
+            Component::Relay {
+                name,
+                nodes: [coil_pos, coil_neg, contact_1, contact_2],
+                coil_edge,
+                contact_edge,
+                r_coil,
+                l_coil,
+                pull_in,
+                drop_out,
+                operate_time,
+                release_time,
+                r_on,
+                r_off,
+            } => (
+                name,
+                C::Relay {
+                    coil_pos,
+                    coil_neg,
+                    contact_1,
+                    contact_2,
+                    coil_edge,
+                    contact_edge,
+                    params: RelayParams {
+                        r_coil,
+                        l_coil,
+                        pull_in,
+                        drop_out,
+                        operate_time,
+                        release_time,
+                        r_on,
+                        r_off,
+                    },
+                },
+            ),
+            Component::Igbt {
+                name,
+                nodes: [collector, gate, emitter],
+                current_edge,
+                vth,
+                r_on,
+                r_off,
+                c_ge,
+                tail_fraction,
+                tail_time,
+            } => (
+                name,
+                C::Igbt {
+                    collector,
+                    gate,
+                    emitter,
+                    current_edge,
+                    params: IgbtParams {
+                        vth,
+                        r_on,
+                        r_off,
+                        c_ge,
+                        tail_fraction,
+                        tail_time,
+                    },
+                },
+            ),
+            Component::Bjt {
+                name,
+                nodes: [collector, base, emitter],
+                model,
+            } => (
+                name,
+                C::Bjt {
+                    collector,
+                    base,
+                    emitter,
+                    model: model.into(),
+                },
+            ),
+            Component::Mosfet {
+                name,
+                nodes: [drain, gate, source],
+                model,
+            } => (
+                name,
+                C::Mosfet {
+                    drain,
+                    gate,
+                    source,
+                    model: model.into(),
+                },
+            ),
+            Component::Battery {
+                name,
+                nodes: [term_pos, term_neg],
+                current_edge,
+                capacity,
+                r_int,
+                ocv,
+                soc,
+            } => (
+                name,
+                C::Battery {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    model: BatteryModel {
+                        capacity,
+                        r_int,
+                        ocv,
+                    },
+                    soc,
+                },
+            ),
+            Component::LogicGate { name, gate, delay } => (
+                name,
+                C::LogicGate {
+                    gate: gate.into(),
+                    delay,
+                },
+            ),
+            Component::AdcBridge {
+                name,
+                nodes: [term_pos, term_neg],
+                net,
+                family,
+            } => (
+                name,
+                C::AdcBridge {
+                    term_pos,
+                    term_neg,
+                    net,
+                    family: family.into(),
+                },
+            ),
+            Component::DacBridge {
+                name,
+                nodes: [term_pos, term_neg],
+                current_edge,
+                net,
+                family,
+            } => (
+                name,
+                C::DacBridge {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    net,
+                    family: family.into(),
+                },
+            ),
+            Component::VoltageSource {
+                name,
+                nodes: [term_pos, term_neg],
+                current_edge,
+                dc,
+                ac,
+                waveform,
+            } => (
+                name,
+                C::IndependentVoltageSource {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    voltage: dc,
+                    ac: ac_from_schema(ac),
+                    waveform: waveform.map(TryInto::try_into).transpose()?,
+                },
+            ),
+            Component::CurrentProbe {
+                name,
+                nodes: [term_pos, term_neg],
+                current_edge,
+            } => (
+                name,
+                C::CurrentProbe {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                },
+            ),
+            Component::Probe {
+                name,
+                nodes: [term_pos, term_neg],
+                current_edge,
+                r_in,
+                c_in,
+                bandwidth,
+                averaging_window,
+            } => (
+                name,
+                C::MeasurementProbe {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    params: ProbeParams {
+                        r_in,
+                        c_in,
+                        bandwidth,
+                        averaging_window,
+                    },
+                },
+            ),
+            Component::CurrentSource {
+                name,
+                nodes: [term_pos, term_neg],
+                dc,
+                ac,
+                waveform,
+            } => (
+                name,
+                C::IndependentCurrentSource {
+                    term_pos,
+                    term_neg,
+                    current: dc,
+                    ac: ac_from_schema(ac),
+                    waveform: waveform.map(TryInto::try_into).transpose()?,
+                },
+            ),
+            Component::VoltageControlledVoltageSource {
+                name,
+                nodes: [term_pos, term_neg],
+                control_nodes: [ctrl_pos, ctrl_neg],
+                current_edge,
+                gain,
+            } => (
+                name,
+                C::VoltageControlledVoltageSource {
+                    term_pos,
+                    term_neg,
+                    ctrl_pos,
+                    ctrl_neg,
+                    current_edge,
+                    gain,
+                },
+            ),
+            Component::VoltageControlledCurrentSource {
+                name,
+                nodes: [term_pos, term_neg],
+                control_nodes: [ctrl_pos, ctrl_neg],
+                transconductance,
+            } => (
+                name,
+                C::VoltageControlledCurrentSource {
+                    term_pos,
+                    term_neg,
+                    ctrl_pos,
+                    ctrl_neg,
+                    transconductance,
+                },
+            ),
+            Component::CurrentControlledCurrentSource {
+                name,
+                nodes: [term_pos, term_neg],
+                control_edge,
+                gain,
+            } => (
+                name,
+                C::CurrentControlledCurrentSource {
+                    term_pos,
+                    term_neg,
+                    ctrl_edge: control_edge,
+                    gain,
+                },
+            ),
+            Component::CurrentControlledVoltageSource {
+                name,
+                nodes: [term_pos, term_neg],
+                control_edge,
+                current_edge,
+                transresistance,
+            } => (
+                name,
+                C::CurrentControlledVoltageSource {
+                    term_pos,
+                    term_neg,
+                    ctrl_edge: control_edge,
+                    current_edge,
+                    transresistance,
+                },
+            ),
+        };
+        Ok(Self { name, component })
+    }
+}
+
+impl From<&component::Model> for Model {
+    fn from(model: &component::Model) -> Self {
+        match *model {
+            component::Model::Diode(model) => Self::Diode(model.into()),
+            component::Model::Schottky(model) => Self::Schottky {
+                phi_b: model.phi_b,
+                area: model.area,
+                richardson: model.richardson,
+                n: model.n,
+                lowering: model.lowering,
+                rs: model.rs,
+            },
+            component::Model::TunnelDiode(model) => Self::TunnelDiode {
+                ip: model.ip,
+                vp: model.vp,
+                is: model.is,
+                n: model.n,
+            },
+            component::Model::SemiconductorResistor(model) => {
+                Self::SemiconductorResistor(model.into())
+            }
+            component::Model::SemiconductorCapacitor(model) => {
+                Self::SemiconductorCapacitor(model.into())
+            }
+            component::Model::Bjt(model) => Self::Bjt(model.into()),
+            component::Model::Mosfet(model) => Self::Mosfet(model.into()),
+        }
+    }
+}
+
+impl From<Model> for component::Model {
+    fn from(model: Model) -> Self {
+        match model {
+            Model::Diode(model) => Self::Diode(model.into()),
+            Model::Schottky {
+                phi_b,
+                area,
+                richardson,
+                n,
+                lowering,
+                rs,
+            } => Self::Schottky(SchottkyModel {
+                phi_b,
+                area,
+                richardson,
+                n,
+                lowering,
+                rs,
+                temp: None,
+            }),
+            Model::TunnelDiode { ip, vp, is, n } => Self::TunnelDiode(TunnelDiodeModel {
+                ip,
+                vp,
+                is,
+                n,
+                temp: None,
+            }),
+            Model::SemiconductorResistor(model) => Self::SemiconductorResistor(model.into()),
+            Model::SemiconductorCapacitor(model) => Self::SemiconductorCapacitor(model.into()),
+            Model::Bjt(model) => Self::Bjt(model.into()),
+            Model::Mosfet(model) => Self::Mosfet(model.into()),
+        }
+    }
+}
+
+impl From<&circuit::Circuit> for Circuit {
+    fn from(circuit: &circuit::Circuit) -> Self {
+        let expression = |expression: &expression::Expression| Expression(expression.clone());
+        Self {
+            components: circuit.instances().iter().map(Component::from).collect(),
+            nodes: circuit
+                .node_names()
+                .iter()
+                .map(|(name, node)| (name.to_string(), node))
+                .collect(),
+            parameters: circuit
+                .parameters()
+                .iter()
+                .map(|(name, value)| (name.to_string(), expression(value)))
+                .collect(),
+            values: circuit
+                .value_expressions()
+                .iter()
+                .map(|(instance, value)| (instance.clone(), expression(value)))
+                .collect(),
+            models: circuit
+                .models()
+                .map(|(name, model)| (name.to_string(), model.into()))
+                .collect(),
+            model_instances: circuit
+                .instances()
+                .iter()
+                .filter_map(|instance| {
+                    let model = circuit.instance_model(&instance.name)?;
+                    Some((instance.name.clone(), model.to_string()))
+                })
+                .collect(),
+            functions: circuit
+                .parameters()
+                .functions()
+                .map(|(name, function)| {
+                    let function = Function {
+                        arguments: function.arguments.clone(),
+                        body: expression(&function.body),
+                    };
+                    (name.to_string(), function)
+                })
+                .collect(),
+            temperature: (circuit.temperature() != component::NOMINAL_TEMPERATURE)
+                .then_some(circuit.temperature()),
+        }
+    }
+}
+
+impl TryFrom<Circuit> for circuit::Circuit {
+    type Error = EsimError;
+
+    /// Fails if a component cannot be converted, an instance uses a
+    /// model it cannot, a value cannot be evaluated from the
+    /// parameters, or a function is built in
+    fn try_from(circuit: Circuit) -> Result<Self, EsimError> {
+        let mut out = circuit::Circuit::new();
+        for component in circuit.components {
+            let instance = circuit::Instance::try_from(component)?;
+            out.add_component(&instance.name, instance.component);
+        }
+        let mut nodes = NodeNames::new();
+        for (name, node) in &circuit.nodes {
+            nodes.insert(name, *node);
+        }
+        out.set_node_names(nodes);
+        let mut parameters = Parameters::new();
+        for (name, expression) in circuit.parameters {
+            parameters.define(&name, expression.0);
+        }
+        for (name, function) in circuit.functions {
+            let function = expression::Function {
+                arguments: function.arguments,
+                body: function.body.0,
+            };
+            parameters
+                .define_function(&name, function)
+                .map_err(|error| invalid(error.to_string()))?;
+        }
+        out.set_parameters(parameters);
+        if let Some(temperature) = circuit.temperature {
+            out.set_temperature(temperature);
+        }
+        for (instance, expression) in circuit.values {
+            out.set_value_expression(&instance, expression.0)
+                .map_err(|error| invalid(format!("Value of {instance}: {error}")))?;
+        }
+        for (name, model) in circuit.models {
+            out.add_model(&name, model.into());
+        }
+        for (instance, model) in &circuit.model_instances {
+            out.try_use_model(instance, model)?;
+        }
+        Ok(out)
+    }
+}
+
+/// A circuit is serialized as a [Circuit] of this schema
+impl Serialize for circuit::Circuit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Circuit::from(self).serialize(serializer)
+    }
+}
+
+/// A circuit is deserialized from a [Circuit] of this schema, with an
+/// error if it cannot be converted
+impl<'de> Deserialize<'de> for circuit::Circuit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Circuit::deserialize(deserializer)?
+            .try_into()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl From<&tdr::TdrOptions> for TdrOptions {
+    fn from(options: &tdr::TdrOptions) -> Self {
+        Self {
+            reference_impedance: options.reference_impedance,
+            rise_time: options.rise_time,
+            velocity: options.velocity,
+            time_step: options.time_step,
+            num_points: options.num_points,
+        }
+    }
+}
+
+impl From<TdrOptions> for tdr::TdrOptions {
+    fn from(options: TdrOptions) -> Self {
+        Self {
+            reference_impedance: options.reference_impedance,
+            rise_time: options.rise_time,
+            velocity: options.velocity,
+            time_step: options.time_step,
+            num_points: options.num_points,
+        }
+    }
+}
+
+impl From<&watch::Analysis> for Analysis {
+    fn from(analysis: &watch::Analysis) -> Self {
+        match analysis {
+            watch::Analysis::OperatingPoint => Self::OperatingPoint,
+            watch::Analysis::DcSweep { instance, range } => Self::DcSweep {
+                instance: instance.clone(),
+                start: range.start,
+                stop: range.stop,
+                step: range.step,
+            },
+            watch::Analysis::Ac(sweep) => Self::Ac {
+                variation: sweep.variation.into(),
+                points: sweep.points,
+                start: sweep.start,
+                stop: sweep.stop,
+            },
+            watch::Analysis::Transient(options) => Self::Transient {
+                time_step: options.time_step,
+                stop_time: options.stop_time,
+                method: options.method.into(),
+                step_control: options.step_control.map(Into::into),
+                use_initial_conditions: options.use_initial_conditions,
+            },
+        }
+    }
+}
+
+impl From<Analysis> for watch::Analysis {
+    fn from(analysis: Analysis) -> Self {
+        match analysis {
+            Analysis::OperatingPoint => Self::OperatingPoint,
+            Analysis::DcSweep {
+                instance,
+                start,
+                stop,
+                step,
+            } => Self::DcSweep {
+                instance,
+                range: SweepRange::new(start, stop, step),
+            },
+            Analysis::Ac {
+                variation,
+                points,
+                start,
+                stop,
+            } => Self::Ac(AcSweep::new(variation.into(), points, start, stop)),
+            Analysis::Transient {
+                time_step,
+                stop_time,
+                method,
+                step_control,
+                use_initial_conditions,
+            } => Self::Transient(TransientOptions {
+                step_control: step_control.map(Into::into),
+                use_initial_conditions,
+                ..TransientOptions::new(time_step, stop_time).with_method(method.into())
+            }),
+        }
+    }
+}
+
+impl From<transient::StepControl> for StepControl {
+    fn from(control: transient::StepControl) -> Self {
+        Self {
+            reltol: control.reltol,
+            abstol: control.abstol,
+            vntol: control.vntol,
+            chgtol: control.chgtol,
+            fluxtol: control.fluxtol,
+            trtol: control.trtol,
+            min_step: control.min_step,
+        }
+    }
+}
+
+impl From<StepControl> for transient::StepControl {
+    fn from(control: StepControl) -> Self {
+        Self {
+            reltol: control.reltol,
+            abstol: control.abstol,
+            vntol: control.vntol,
+            chgtol: control.chgtol,
+            fluxtol: control.fluxtol,
+            trtol: control.trtol,
+            min_step: control.min_step,
+        }
+    }
+}
+
+impl From<ac::Variation> for Variation {
+    fn from(variation: ac::Variation) -> Self {
+        match variation {
+            ac::Variation::Decade => Self::Decade,
+            ac::Variation::Octave => Self::Octave,
+            ac::Variation::Linear => Self::Linear,
+        }
+    }
+}
+
+impl From<Variation> for ac::Variation {
+    fn from(variation: Variation) -> Self {
+        match variation {
+            Variation::Decade => Self::Decade,
+            Variation::Octave => Self::Octave,
+            Variation::Linear => Self::Linear,
+        }
+    }
+}
+
+impl From<transient::IntegrationMethod> for IntegrationMethod {
+    fn from(method: transient::IntegrationMethod) -> Self {
+        match method {
+            transient::IntegrationMethod::BackwardEuler => Self::BackwardEuler,
+            transient::IntegrationMethod::Trapezoidal => Self::Trapezoidal,
+            transient::IntegrationMethod::Gear { order } => Self::Gear { order },
+        }
+    }
+}
+
+impl From<IntegrationMethod> for transient::IntegrationMethod {
+    fn from(method: IntegrationMethod) -> Self {
+        match method {
+            IntegrationMethod::BackwardEuler => Self::BackwardEuler,
+            IntegrationMethod::Trapezoidal => Self::Trapezoidal,
+            IntegrationMethod::Gear { order } => Self::Gear { order },
+        }
+    }
+}
+
+impl From<&plan::RunPlan> for RunPlan {
+    fn from(plan: &plan::RunPlan) -> Self {
+        Self {
+            circuit: Circuit::from(&plan.circuit),
+            analyses: plan.analyses.iter().map(Analysis::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<RunPlan> for plan::RunPlan {
+    type Error = EsimError;
+
+    /// Fails if the circuit cannot be converted
+    fn try_from(plan: RunPlan) -> Result<Self, EsimError> {
+        Ok(Self {
+            circuit: circuit::Circuit::try_from(plan.circuit)?,
+            analyses: plan.analyses.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
+impl Dataset {
+    /// The signal with a name, if there is one
+    pub fn signal(&self, name: &str) -> Option<&Signal> {
+        self.signals.iter().find(|s| s.name == name)
+    }
+
+    /// Name the signals `v(n)` and `i(e)` (as for an operating point)
+    /// after the names of the nodes of a circuit, and the instances
+    /// with the current edges, where there are some, as in `v(out)`
+    /// and `i(v1)`
+    pub fn name_signals(&mut self, circuit: &circuit::Circuit) {
+        let index = |name: &str, prefix: &str| -> Option<usize> {
+            name.strip_prefix(prefix)?.strip_suffix(')')?.parse().ok()
+        };
+        for signal in &mut self.signals {
+            let name = if let Some(node) = index(&signal.name, "v(") {
+                circuit
+                    .node_names()
+                    .name(node)
+                    .map(|name| format!("v({name})"))
+            } else if let Some(edge) = index(&signal.name, "i(") {
+                circuit
+                    .instances()
+                    .iter()
+                    .find(|instance| instance.component.current_edge() == Some(edge))
+                    .map(|instance| format!("i({})", instance.name.to_ascii_lowercase()))
+            } else {
+                None
+            };
+            if let Some(name) = name {
+                signal.name = name;
+            }
+        }
+    }
+
+    /// The dataset as comma-separated values, with a heading row of
+    /// the labels (see [Signal::label]) of the axis and the signals,
+    /// then a row for each point. A complex signal has a column for
+    /// each part, labelled as in `re(v(2)) (V)` and `im(v(2)) (V)`.
+    pub fn to_csv(&self) -> String {
+        let signals: Vec<&Signal> = self.axis.iter().chain(&self.signals).collect();
+        let mut columns: Vec<(String, &[f64])> = Vec::new();
+        for signal in &signals {
+            match &signal.imag {
+                Some(imag) => {
+                    let part = |name: &str| Signal::real(name, Vec::new()).with_unit(signal.unit);
+                    let (re, im) = (
+                        format!("re({})", signal.name),
+                        format!("im({})", signal.name),
+                    );
+                    columns.push((part(&re).label(), &signal.real));
+                    columns.push((part(&im).label(), imag));
+                }
+                None => columns.push((signal.label(), &signal.real)),
+            }
+        }
+        let quote = |label: &str| {
+            if label.contains([',', '"']) {
+                format!("\"{}\"", label.replace('"', "\"\""))
+            } else {
+                label.to_string()
+            }
+        };
+        let mut csv = columns
+            .iter()
+            .map(|(label, _)| quote(label))
+            .collect::<Vec<_>>()
+            .join(",");
+        csv.push('\n');
+        let num_points = columns.first().map_or(0, |(_, values)| values.len());
+        for point in 0..num_points {
+            let row: Vec<String> = columns
+                .iter()
+                .map(|(_, values)| format!("{:e}", values[point]))
+                .collect();
+            csv += &row.join(",");
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Dataset for a DC operating point, with signals named v(n) for
+    /// the node voltages and i(e) for the edge currents
+    pub fn operating_point(voltages: &[f64], currents: &[f64]) -> Self {
+        let voltages = voltages
+            .iter()
+            .enumerate()
+            .map(|(n, v)| Signal::real(&format!("v({})", n + 1), vec![*v]).with_unit(Unit::Volt));
+        let currents = currents
+            .iter()
+            .enumerate()
+            .map(|(e, i)| Signal::real(&format!("i({e})"), vec![*i]).with_unit(Unit::Ampere));
+        Self {
+            axis: None,
+            signals: voltages.chain(currents).collect(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl From<&Curve> for Dataset {
+    fn from(curve: &Curve) -> Self {
+        Self {
+            axis: Some(
+                Signal::real(&curve.x_name, curve.x.clone())
+                    .with_unit(Unit::of_quantity(&curve.x_name)),
+            ),
+            signals: vec![Signal::real(&curve.y_name, curve.y.clone())
+                .with_unit(Unit::of_quantity(&curve.y_name))],
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl From<&TransientResult> for Dataset {
+    /// Signals are named v(n) for the node voltages and i(e) for the
+    /// edge currents, as for an operating point
+    fn from(result: &TransientResult) -> Self {
+        let num_nodes = result.voltages.first().map_or(0, Vec::len);
+        let num_edges = result.currents.first().map_or(0, Vec::len);
+        let voltages = (1..=num_nodes)
+            .map(|n| Signal::real(&format!("v({n})"), result.voltage(n)).with_unit(Unit::Volt));
+        let currents = (0..num_edges)
+            .map(|e| Signal::real(&format!("i({e})"), result.current(e)).with_unit(Unit::Ampere));
+        Self {
+            axis: Some(Signal::real("time", result.time.clone()).with_unit(Unit::Second)),
+            signals: voltages.chain(currents).collect(),
+            warnings: result.warnings.clone(),
+        }
+    }
+}
+
+impl From<&DcSweepResult> for Dataset {
+    /// The axis is named after the swept instance, and the signals
+    /// are named as for an operating point
+    fn from(result: &DcSweepResult) -> Self {
+        let num_nodes = result.solutions.first().map_or(0, |s| s.voltages.len());
+        let num_edges = result.solutions.first().map_or(0, |s| s.currents.len());
+        let voltages = (1..=num_nodes)
+            .map(|n| Signal::real(&format!("v({n})"), result.voltage(n)).with_unit(Unit::Volt));
+        let currents = (0..num_edges)
+            .map(|e| Signal::real(&format!("i({e})"), result.current(e)).with_unit(Unit::Ampere));
+        Self {
+            axis: Some(
+                Signal::real(&result.instance, result.values.clone())
+                    .with_unit(Unit::of_quantity(result.quantity)),
+            ),
+            signals: voltages.chain(currents).collect(),
+            warnings: result.warnings(),
+        }
+    }
+}
+
+impl From<&AcSweepResult> for Dataset {
+    /// The signals are complex, and named as for an operating point
+    fn from(result: &AcSweepResult) -> Self {
+        let complex = |name: String, points: Vec<Complex<f64>>, unit| Signal {
+            name,
+            real: points.iter().map(|p| p.re).collect(),
+            imag: Some(points.iter().map(|p| p.im).collect()),
+            unit,
+        };
+        let num_nodes = result.voltages.first().map_or(0, Vec::len);
+        let num_edges = result.currents.first().map_or(0, Vec::len);
+        let voltages =
+            (1..=num_nodes).map(|n| complex(format!("v({n})"), result.voltage(n), Unit::Volt));
+        let currents =
+            (0..num_edges).map(|e| complex(format!("i({e})"), result.current(e), Unit::Ampere));
+        Self {
+            axis: Some(
+                Signal::real("frequency", result.frequencies.clone()).with_unit(Unit::Hertz),
+            ),
+            signals: voltages.chain(currents).collect(),
+            warnings: result.warnings.clone(),
+        }
+    }
+}
+
+impl From<&DcSweepFamily> for Dataset {
+    /// The axis is the inner sweep, and each signal of each curve is
+    /// named with the outer value, such as `v(2) ib=1e-5`
+    fn from(family: &DcSweepFamily) -> Self {
+        let axis = family.curves.first().and_then(|c| Dataset::from(c).axis);
+        let signals = family
+            .curves
+            .iter()
+            .zip(&family.values)
+            .flat_map(|(curve, value)| {
+                Dataset::from(curve)
+                    .signals
+                    .into_iter()
+                    .map(move |mut signal| {
+                        signal.name = format!("{} {}={value:e}", signal.name, family.instance);
+                        signal
+                    })
+            })
+            .collect();
+        let warnings = family.curves.iter().flat_map(|c| c.warnings()).collect();
+        Self {
+            axis,
+            signals,
+            warnings,
+        }
+    }
+}
+
+impl From<&Histogram> for Dataset {
+    fn from(histogram: &Histogram) -> Self {
+        let counts = histogram.counts.iter().map(|c| *c as f64).collect();
+        Self {
+            axis: Some(Signal::real("bin", histogram.centres())),
+            signals: vec![Signal::real("count", counts)],
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl From<&TdrResult> for Dataset {
+    fn from(result: &TdrResult) -> Self {
+        Self {
+            axis: Some(Signal::real("time", result.time.clone()).with_unit(Unit::Second)),
+            signals: vec![
+                Signal::real("distance", result.distance.clone())
+                    .with_unit(Unit::of_quantity("distance")),
+                Signal::real("reflection", result.reflection.clone()),
+                Signal::real("impedance", result.impedance.clone())
+                    .with_unit(Unit::of_quantity("impedance")),
+            ],
+            warnings: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Sensitivity analysis
+//!
+//! The derivative of an output voltage with respect to the value of
+//! each component, as for a SPICE `.SENS` line, so the components
+//! whose tolerances matter most can be found. The sensitivity of the
+//! DC operating point is to each resistance and each independent
+//! source value, with the circuit linearised about the operating
+//! point. The sensitivity of the AC response is to each resistance,
+//! capacitance and inductance at each frequency, from which follows
+//! the sensitivity of the magnitude (such as that of the passband of a
+//! filter).
+//!
+//! The derivatives are found by the adjoint method, from one solve of
+//! the adjoint system for the output (see [noise](mod@crate::noise))
+//! rather than one solve for each component. A change $dR$ in a
+//! resistance carrying a current $I$ changes the voltage across it by
+//! $I\,dR$, as a source in series with it would, which is the same as
+//! a current $I\,dR/R$ driven into its first terminal and out of its
+//! second, and the adjoint solution is the transfer to the output
+//! from a current driven into each node. A change in a capacitance
+//! (or any admittance) is likewise a current driven between its
+//! terminals, and a change in an inductance carrying a current $I$ is a
+//! voltage $j\omega I\,dL$ in series with it, which is weighted by the
+//! transfer from a voltage in series with its edge. A change in a
+//! source value is weighted by the transfer from the source.
+
+use std::f64::consts::{LN_10, PI};
+use std::fmt;
+
+use num::Complex;
+
+use crate::ac::{node_transfer, source_transfer, LinearAcAnalysis};
+use crate::circuit::Circuit;
+use crate::component::Component;
+use crate::dc::solve_elaborated;
+
+/// Sensitivity of an output to the value of one component
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sensitivity {
+    /// Name of the instance
+    pub instance: String,
+    /// Quantity of the value, such as "resistance" or "voltage"
+    pub quantity: &'static str,
+    pub value: f64,
+    /// Derivative of the output with respect to the value
+    pub absolute: f64,
+}
+
+impl Sensitivity {
+    /// Change in the output per unit relative change in the value (so
+    /// a hundredth of it is the change for a one percent tolerance)
+    pub fn relative(&self) -> f64 {
+        self.absolute * self.value
+    }
+}
+
+/// Sensitivities of an output voltage to the component values
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensitivityResult {
+    /// Output voltage at the operating point
+    pub output: f64,
+    pub sensitivities: Vec<Sensitivity>,
+    /// Warnings of solving the operating point
+    pub warnings: Vec<String>,
+}
+
+impl SensitivityResult {
+    /// Sensitivity to an instance, if it has one
+    pub fn get(&self, instance: &str) -> Option<&Sensitivity> {
+        self.sensitivities.iter().find(|s| s.instance == instance)
+    }
+
+    /// The sensitivities in order of the magnitude of their relative
+    /// sensitivity, largest first
+    pub fn ranking(&self) -> Vec<&Sensitivity> {
+        let mut ranking: Vec<&Sensitivity> = self.sensitivities.iter().collect();
+        ranking.sort_by(|a, b| b.relative().abs().total_cmp(&a.relative().abs()));
+        ranking
+    }
+}
+
+impl fmt::Display for SensitivityResult {
+    /// A table of the sensitivities, largest relative sensitivity
+    /// first, with the change in the output for a one percent change in
+    /// each value
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Output: {:.6e}", self.output)?;
+        writeln!(
+            f,
+            "{:<12} {:<12} {:>12} {:>12} {:>12}",
+            "instance", "quantity", "value", "absolute", "per percent"
+        )?;
+        for sensitivity in self.ranking() {
+            writeln!(
+                f,
+                "{:<12} {:<12} {:>12.4e} {:>12.4e} {:>12.4e}",
+                sensitivity.instance,
+                sensitivity.quantity,
+                sensitivity.value,
+                sensitivity.absolute,
+                sensitivity.relative() / 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Sensitivity of the DC voltage from output_pos to output_neg to the
+/// resistances and independent source values of a circuit
+pub fn dc_sensitivity(
+    circuit: &Circuit,
+    (output_pos, output_neg): (usize, usize),
+) -> SensitivityResult {
+    let analysis = LinearAcAnalysis::new(circuit);
+    let mut warnings = Vec::new();
+    let (voltages, _) = solve_elaborated(analysis.circuit(), &mut warnings);
+    let node_voltage = |node: usize| match node {
+        0 => 0.0,
+        n => voltages[n - 1],
+    };
+    let adjoint = analysis
+        .assemble(0.0, false)
+        .solve_adjoint(output_pos, output_neg);
+    let transfer = |node: usize| node_transfer(&adjoint, node).re;
+    let sensitivities = analysis
+        .circuit()
+        .instances()
+        .iter()
+        .filter_map(|instance| {
+            let (quantity, value, absolute) = match instance.component {
+                Component::Resistor {
+                    term_1,
+                    term_2,
+                    resistance,
+                    ..
+                } => {
+                    let current = (node_voltage(term_1) - node_voltage(term_2)) / resistance;
+                    let absolute = (transfer(term_1) - transfer(term_2)) * current / resistance;
+                    ("resistance", resistance, absolute)
+                }
+                Component::IndependentVoltageSource { voltage, .. } => (
+                    "voltage",
+                    voltage,
+                    source_transfer(&adjoint, &instance.component)?.re,
+                ),
+                Component::IndependentCurrentSource { current, .. } => (
+                    "current",
+                    current,
+                    source_transfer(&adjoint, &instance.component)?.re,
+                ),
+                _ => return None,
+            };
+            Some(Sensitivity {
+                instance: instance.name.clone(),
+                quantity,
+                value,
+                absolute,
+            })
+        })
+        .collect();
+    SensitivityResult {
+        output: node_voltage(output_pos) - node_voltage(output_neg),
+        sensitivities,
+        warnings,
+    }
+}
+
+/// Sensitivity of a complex output to the value of one component, at
+/// each frequency
+#[derive(Debug, Clone, PartialEq)]
+pub struct AcSensitivity {
+    /// Name of the instance
+    pub instance: String,
+    /// Quantity of the value, such as "resistance" or "capacitance"
+    pub quantity: &'static str,
+    pub value: f64,
+    /// Derivative of the output with respect to the value
+    pub absolute: Vec<Complex<f64>>,
+}
+
+/// Sensitivities of an AC output voltage to the component values at
+/// each frequency
+#[derive(Debug, Clone, PartialEq)]
+pub struct AcSensitivityResult {
+    pub frequencies: Vec<f64>,
+    /// Output voltage at each frequency
+    pub output: Vec<Complex<f64>>,
+    pub sensitivities: Vec<AcSensitivity>,
+}
+
+impl AcSensitivityResult {
+    /// Sensitivity to an instance, if it has one
+    pub fn get(&self, instance: &str) -> Option<&AcSensitivity> {
+        self.sensitivities.iter().find(|s| s.instance == instance)
+    }
+
+    /// Derivative of the magnitude of the output with respect to the
+    /// value of an instance, at each frequency
+    pub fn magnitude(&self, instance: &str) -> Option<Vec<f64>> {
+        let sensitivity = self.get(instance)?;
+        Some(
+            self.output
+                .iter()
+                .zip(&sensitivity.absolute)
+                .map(|(output, absolute)| (output.conj() * absolute).re / output.norm())
+                .collect(),
+        )
+    }
+
+    /// Change in the magnitude of the output in decibels per unit
+    /// relative change in the value of an instance, at each frequency
+    pub fn magnitude_db(&self, instance: &str) -> Option<Vec<f64>> {
+        let value = self.get(instance)?.value;
+        Some(
+            self.magnitude(instance)?
+                .iter()
+                .zip(&self.output)
+                .map(|(magnitude, output)| 20.0 / LN_10 * magnitude * value / output.norm())
+                .collect(),
+        )
+    }
+
+    /// The instances in order of the largest magnitude of their
+    /// relative sensitivity in decibels over the frequencies, largest
+    /// first, with that magnitude
+    pub fn ranking(&self) -> Vec<(&str, f64)> {
+        let mut ranking: Vec<(&str, f64)> = self
+            .sensitivities
+            .iter()
+            .map(|s| {
+                let largest = self
+                    .magnitude_db(&s.instance)
+                    .unwrap_or_default()
+                    .iter()
+                    .fold(0.0, |m: f64, x| m.max(x.abs()));
+                (s.instance.as_str(), largest)
+            })
+            .collect();
+        ranking.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranking
+    }
+}
+
+/// Sensitivity of the AC voltage from output_pos to output_neg to the
+/// resistances, capacitances and inductances of a circuit, at each
+/// frequency (in Hz)
+pub fn ac_sensitivity(
+    circuit: &Circuit,
+    (output_pos, output_neg): (usize, usize),
+    frequencies: &[f64],
+) -> AcSensitivityResult {
+    let analysis = LinearAcAnalysis::new(circuit);
+    let instances = analysis.circuit().instances();
+    let mut sensitivities: Vec<AcSensitivity> = instances
+        .iter()
+        .filter_map(|instance| {
+            let (quantity, value) = match instance.component {
+                Component::Resistor { resistance, .. } => ("resistance", resistance),
+                Component::Capacitor { capacitance, .. } => ("capacitance", capacitance),
+                Component::Inductor { inductance, .. } => ("inductance", inductance),
+                _ => return None,
+            };
+            Some(AcSensitivity {
+                instance: instance.name.clone(),
+                quantity,
+                value,
+                absolute: Vec::with_capacity(frequencies.len()),
+            })
+        })
+        .collect();
+    let mut output = Vec::with_capacity(frequencies.len());
+    for frequency in frequencies {
+        let omega = Complex::new(0.0, 2.0 * PI * frequency);
+        let (voltages, currents) = analysis.assemble(*frequency, true).solve();
+        let node_voltage = |node: usize| match node {
+            0 => Complex::new(0.0, 0.0),
+            n => voltages[n - 1],
+        };
+        let adjoint = analysis
+            .assemble(*frequency, false)
+            .solve_adjoint(output_pos, output_neg);
+        let transfer = |term_1: usize, term_2: usize| {
+            node_transfer(&adjoint, term_1) - node_transfer(&adjoint, term_2)
+        };
+        output.push(node_voltage(output_pos) - node_voltage(output_neg));
+        let derivatives = instances
+            .iter()
+            .filter_map(|instance| match instance.component {
+                Component::Resistor {
+                    term_1,
+                    term_2,
+                    resistance,
+                    ..
+                } => {
+                    let voltage = node_voltage(term_1) - node_voltage(term_2);
+                    Some(transfer(term_1, term_2) * voltage / (resistance * resistance))
+                }
+                Component::Capacitor { term_1, term_2, .. } => {
+                    let voltage = node_voltage(term_1) - node_voltage(term_2);
+                    Some(-transfer(term_1, term_2) * omega * voltage)
+                }
+                Component::Inductor { current_edge, .. } => {
+                    Some(adjoint.1[current_edge] * omega * currents[current_edge])
+                }
+                _ => None,
+            });
+        for (sensitivity, derivative) in sensitivities.iter_mut().zip(derivatives) {
+            sensitivity.absolute.push(derivative);
+        }
+    }
+    AcSensitivityResult {
+        frequencies: frequencies.to_vec(),
+        output,
+        sensitivities,
+    }
+}
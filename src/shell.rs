@@ -0,0 +1,281 @@
+//! Interactive command shell
+//!
+//! A [Shell] keeps a circuit parsed from a netlist in memory, and runs
+//! commands on it one line at a time, as in the ngspice shell:
+//!
+//! ```text
+//! source amp.cir
+//! op
+//! print v(out)
+//! alter R1=2k
+//! tran 1u 1m
+//! print v(in) v(out)
+//! reset
+//! ```
+//!
+//! The commands are
+//!
+//! - `source <file>`: parse a netlist, with its analysis directives
+//! - `run`: run the analyses of the netlist (an operating point if it
+//!   has none)
+//! - `op`, `dc`, `ac` and `tran`: run an analysis, with the arguments
+//!   of its directive (see [crate::netlist::parse_analyses])
+//! - `print <signal> ...` or `print all`: the signals of the analysis
+//!   run last, such as `v(out)` or `i(v1)`
+//! - `alter <instance>=<value>`: set the value of a resistor, capacitor,
+//!   inductor or independent source
+//! - `alterparam <parameter>=<value>`: set a parameter of the netlist
+//! - `option <option>=<value>`: set a simulator option, as in an
+//!   `.OPTIONS` line (see [crate::options])
+//! - `reset`: parse the netlist again, undoing the changes (and those
+//!   of the options)
+//! - `show`: list the instances of the circuit
+//! - `help`: list the commands
+//!
+//! Names are not case sensitive, and values can be in engineering
+//! notation (see [crate::value]). Each command gives its output as
+//! text, or an error, which leaves the circuit as it was.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::circuit::Circuit;
+use crate::netlist::{
+    parse_analyses, parse_analyses_file, parse_netlist_file_dialect, parse_options_file, Dialect,
+};
+use crate::options::SimOptions;
+use crate::schema::v1::Dataset;
+use crate::transient::ComponentChange;
+use crate::value::parse_value;
+use crate::watch::Analysis;
+
+const HELP: &str = "\
+source <file>              parse a netlist
+run                        run the analyses of the netlist
+op | dc | ac | tran ...    run an analysis, as in its directive
+print <signal> ... | all   print signals of the last analysis
+alter <instance>=<value>   set the value of an instance
+alterparam <name>=<value>  set a parameter
+option <name>=<value>      set a simulator option
+reset                      parse the netlist again
+show                       list the instances
+help                       list the commands
+quit                       leave the shell";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShellError {
+    pub message: String,
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ShellError {}
+
+fn error(message: impl fmt::Display) -> ShellError {
+    ShellError {
+        message: message.to_string(),
+    }
+}
+
+/// A circuit in memory, with the commands run on it
+#[derive(Debug, Clone, Default)]
+pub struct Shell {
+    dialect: Dialect,
+    /// The netlist sourced last, if one was
+    netlist: Option<PathBuf>,
+    circuit: Circuit,
+    /// The analyses of the netlist
+    analyses: Vec<Analysis>,
+    /// The simulator options of the netlist, as changed by the commands
+    options: SimOptions,
+    /// The results of the analysis run last
+    result: Option<Dataset>,
+}
+
+impl Shell {
+    /// A shell that sources netlists in a dialect
+    pub fn new(dialect: Dialect) -> Self {
+        Self {
+            dialect,
+            ..Self::default()
+        }
+    }
+
+    /// The circuit, as changed by the commands
+    pub fn circuit(&self) -> &Circuit {
+        &self.circuit
+    }
+
+    /// The simulator options, as changed by the commands
+    pub fn options(&self) -> &SimOptions {
+        &self.options
+    }
+
+    /// The results of the analysis run last, if one was
+    pub fn result(&self) -> Option<&Dataset> {
+        self.result.as_ref()
+    }
+
+    /// Run a command line, returning its output
+    pub fn execute(&mut self, line: &str) -> Result<String, ShellError> {
+        let line = line.trim();
+        let (command, arguments) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let arguments = arguments.trim();
+        match command.to_ascii_lowercase().as_str() {
+            "" => Ok(String::new()),
+            "source" if !arguments.is_empty() => self.source(Path::new(arguments)),
+            "reset" => {
+                let netlist = self
+                    .netlist
+                    .clone()
+                    .ok_or_else(|| error("no netlist sourced"))?;
+                self.source(&netlist)
+            }
+            "run" => {
+                let mut analyses = self.analyses.clone();
+                if analyses.is_empty() {
+                    analyses.push(Analysis::OperatingPoint);
+                }
+                analyses
+                    .iter()
+                    .map(|analysis| self.run(analysis))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(|outputs| outputs.join("\n"))
+            }
+            "op" | "dc" | "ac" | "tran" => {
+                let analyses = parse_analyses(&format!(".{line}"), Dialect::Esim)
+                    .map_err(|e| error(e.message))?;
+                self.run(&analyses[0])
+            }
+            "print" if !arguments.is_empty() => self.print(arguments),
+            "alter" => {
+                let (name, value) = assignment(arguments)?;
+                self.alter(name, value)
+            }
+            "alterparam" => {
+                let (name, value) = assignment(arguments)?;
+                self.circuit
+                    .set_parameter(&name.to_ascii_lowercase(), value)
+                    .map_err(error)?;
+                Ok(String::new())
+            }
+            "option" => {
+                let (name, value) = arguments
+                    .split_once('=')
+                    .ok_or_else(|| error("expected <name>=<value>"))?;
+                if !self.options.set(name.trim(), value.trim()).map_err(error)? {
+                    return Err(error(format!("no option named '{}'", name.trim())));
+                }
+                Ok(String::new())
+            }
+            "show" => Ok(self
+                .circuit
+                .instances()
+                .iter()
+                .map(|instance| format!("{} {:?}", instance.name, instance.component))
+                .collect::<Vec<_>>()
+                .join("\n")),
+            "help" => Ok(HELP.to_string()),
+            _ => Err(error(format!(
+                "unknown command '{line}' (help lists the commands)"
+            ))),
+        }
+    }
+
+    /// Parse a netlist and its analyses, replacing the circuit
+    fn source(&mut self, path: &Path) -> Result<String, ShellError> {
+        let circuit = parse_netlist_file_dialect(path, self.dialect).map_err(error)?;
+        let analyses = parse_analyses_file(path, self.dialect).map_err(error)?;
+        self.options = parse_options_file(path, self.dialect).map_err(error)?;
+        self.analyses = analyses;
+        self.circuit = circuit;
+        self.netlist = Some(path.to_path_buf());
+        self.result = None;
+        Ok(format!(
+            "{}: {} instances, {} analyses",
+            path.display(),
+            self.circuit.instances().len(),
+            self.analyses.len()
+        ))
+    }
+
+    /// Run an analysis, keeping its results, and returning its name
+    fn run(&mut self, analysis: &Analysis) -> Result<String, ShellError> {
+        if self.netlist.is_none() {
+            return Err(error("no netlist sourced"));
+        }
+        let analysis = analysis.for_circuit(&self.circuit).map_err(error)?;
+        let mut dataset = analysis.run_with(&self.circuit, &self.options);
+        dataset.name_signals(&self.circuit);
+        self.result = Some(dataset);
+        Ok(format!("{} done", analysis.name()))
+    }
+
+    /// Print signals of the last results, by name or `all`
+    fn print(&self, arguments: &str) -> Result<String, ShellError> {
+        let result = self
+            .result
+            .as_ref()
+            .ok_or_else(|| error("no analysis has been run"))?;
+        let signals = if arguments.eq_ignore_ascii_case("all") {
+            result.signals.clone()
+        } else {
+            arguments
+                .split_whitespace()
+                .map(|name| {
+                    result
+                        .signals
+                        .iter()
+                        .find(|signal| signal.name.eq_ignore_ascii_case(name))
+                        .cloned()
+                        .ok_or_else(|| error(format!("no signal named '{name}'")))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        Ok(match &result.axis {
+            // An operating point
+            None => signals
+                .iter()
+                .map(|signal| format!("{} = {:e}", signal.label(), signal.real[0]))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Some(axis) => {
+                let dataset = Dataset {
+                    axis: Some(axis.clone()),
+                    signals,
+                    warnings: Vec::new(),
+                };
+                dataset.to_csv().trim_end().to_string()
+            }
+        })
+    }
+
+    /// Set the value of an instance (named in any case)
+    fn alter(&mut self, name: &str, value: f64) -> Result<String, ShellError> {
+        let instance = self
+            .circuit
+            .instances_mut()
+            .iter_mut()
+            .find(|i| i.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| error(format!("no instance named '{name}'")))?;
+        instance.component = ComponentChange::Value(value)
+            .apply(&instance.component)
+            .ok_or_else(|| error(format!("the value of '{}' cannot be set", instance.name)))?;
+        Ok(String::new())
+    }
+}
+
+/// The name and value of `name=value` (or `name = value`, or
+/// `name value`)
+fn assignment(arguments: &str) -> Result<(&str, f64), ShellError> {
+    let (name, value) = arguments
+        .split_once('=')
+        .or_else(|| arguments.split_once(char::is_whitespace))
+        .ok_or_else(|| error("expected <name>=<value>"))?;
+    let value = parse_value(value.trim()).map_err(error)?;
+    Ok((name.trim(), value))
+}
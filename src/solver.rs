@@ -0,0 +1,603 @@
+//! Nonlinear solver
+//!
+//! The [NonlinearSolver] finds the DC operating point of a circuit
+//! with nonlinear devices by damped Newton-Raphson iteration. Each
+//! device implements [NonlinearDevice], whose `load` gives the
+//! [Stamp]s of the device at a [State] of the circuit: the current of
+//! each of its branches and the derivative of that current by the
+//! voltage controlling it. Each iteration solves the linear part of
+//! the circuit with every device replaced by its stamps (a
+//! transconductance in parallel with a current source), so a device
+//! needs no knowledge of the MNA matrix.
+//!
+//! The junctions of the circuit (such as diodes) are linearised at
+//! their junction voltages, each limited from the one before (see
+//! [crate::component::pnjlim]), and bypassed while they do not change
+//! (see [crate::evaluation]). The transistors are linearised in the
+//! same way at their control voltages, each limited by the model (see
+//! [crate::component::Transistor]), and each current becomes a stamp
+//! for each control voltage. A circuit of the usual components is
+//! therefore solved with no devices given, and devices the
+//! [Component]s do not describe can be added alongside them. The same iteration solves
+//! the operating point, each point of a DC sweep and each time point
+//! of a transient analysis, with the linear system of each analysis.
+//!
+//! The change in the node voltages in each iteration can be scaled
+//! down so that none changes by more than a maximum step, which keeps
+//! the exponentials of the devices in range from a poor starting
+//! point (the analyses take full steps, relying on the junction
+//! limiting instead). The iteration has converged when the junction
+//! voltages and the control voltages of the transistors and devices
+//! settle, and the current of each transistor and device branch is
+//! that predicted by its linearisation from the iteration before (the
+//! residual of the linearisation is small).
+//! An iteration that does not converge within the iteration limit is
+//! reported as an [EsimError::Convergence].
+
+use crate::circuit::Circuit;
+use crate::component::Transistor;
+use crate::dc::{
+    dc_solution, ClampActivity, DcSolution, LinearDcAnalysis, NewtonOptions, NodeClamp,
+};
+use crate::error::EsimError;
+use crate::evaluation::JunctionCache;
+use crate::mna::Mna;
+use crate::sparse::CachedSolver;
+
+/// Node voltages and edge currents of a circuit during the iteration
+#[derive(Debug, Clone, Copy)]
+pub struct State<'a> {
+    /// Node voltages, excluding ground (node n is at index n-1)
+    pub voltages: &'a [f64],
+    pub currents: &'a [f64],
+}
+
+impl State<'_> {
+    /// Voltage of a node (zero for ground)
+    pub fn voltage(&self, node: usize) -> f64 {
+        match node {
+            0 => 0.0,
+            n => self.voltages.get(n - 1).copied().unwrap_or(0.0),
+        }
+    }
+
+    /// Voltage from the first node of a pair to the second
+    pub fn difference(&self, (pos, neg): (usize, usize)) -> f64 {
+        self.voltage(pos) - self.voltage(neg)
+    }
+}
+
+/// One branch of a nonlinear device at a state: the current (out of
+/// the positive output node, through the device, and into the
+/// negative one) and its derivative by the voltage of the control
+/// nodes, which are the output nodes for a two-terminal device
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stamp {
+    pub output: (usize, usize),
+    pub control: (usize, usize),
+    pub current: f64,
+    pub conductance: f64,
+}
+
+impl Stamp {
+    /// The stamp of a two-terminal branch, controlled by its own voltage
+    pub fn branch(pos: usize, neg: usize, current: f64, conductance: f64) -> Self {
+        Self {
+            output: (pos, neg),
+            control: (pos, neg),
+            current,
+            conductance,
+        }
+    }
+
+    /// Current predicted by the stamp at a control voltage
+    fn predict(&self, voltage: f64, at: f64) -> f64 {
+        self.current + self.conductance * (voltage - at)
+    }
+}
+
+/// A current of a transistor of an elaborated circuit, linearised at
+/// its control voltages: the current between the output nodes, and its
+/// derivative by the voltage between each pair of control nodes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TransistorBranch {
+    pub output: (usize, usize),
+    pub controls: [(usize, usize); 2],
+    pub at: [f64; 2],
+    pub current: f64,
+    pub derivatives: [f64; 2],
+}
+
+impl TransistorBranch {
+    /// Current predicted at control voltages
+    fn predict(&self, voltages: [f64; 2]) -> f64 {
+        self.current
+            + self.derivatives[0] * (voltages[0] - self.at[0])
+            + self.derivatives[1] * (voltages[1] - self.at[1])
+    }
+
+    /// A stamp for each control voltage, with the currents they
+    /// predict at zero volts, which add up to that of the branch
+    fn stamps(&self) -> [(Stamp, f64); 2] {
+        let stamp = |control: usize, current| Stamp {
+            output: self.output,
+            control: self.controls[control],
+            current,
+            conductance: self.derivatives[control],
+        };
+        [
+            (stamp(0, self.current), self.predict([0.0, 0.0])),
+            (stamp(1, 0.0), 0.0),
+        ]
+    }
+}
+
+/// The currents of a transistor with terminals at nodes, linearised at
+/// control voltages
+pub(crate) fn linearise_transistor(
+    nodes: [usize; 3],
+    transistor: &dyn Transistor,
+    at: [f64; 2],
+) -> Vec<TransistorBranch> {
+    let pair = |(pos, neg): (usize, usize)| (nodes[pos], nodes[neg]);
+    let controls = transistor.controls().map(pair);
+    transistor
+        .currents(at)
+        .into_iter()
+        .map(|current| TransistorBranch {
+            output: pair(current.terminals),
+            controls,
+            at,
+            current: current.current,
+            derivatives: current.derivatives,
+        })
+        .collect()
+}
+
+/// The currents of the transistors of an elaborated circuit, each
+/// linearised at its control voltages (indexed by instance, and zero
+/// if missing)
+pub(crate) fn transistor_branches(
+    circuit: &Circuit,
+    controls: &[[f64; 2]],
+) -> Vec<TransistorBranch> {
+    circuit
+        .instances()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, instance)| {
+            let (nodes, transistor) = instance.component.transistor()?;
+            let at = controls.get(index).copied().unwrap_or([0.0; 2]);
+            Some(linearise_transistor(nodes, transistor, at))
+        })
+        .flatten()
+        .collect()
+}
+
+/// A device solved by the [NonlinearSolver]
+pub trait NonlinearDevice {
+    /// The stamps of the device at a state, which are the same number
+    /// (in the same order) at every state
+    fn load(&self, state: &State) -> Vec<Stamp>;
+}
+
+/// Settings of the [NonlinearSolver]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolverOptions {
+    /// Tolerances of the junction and control voltages, and the
+    /// iteration limit
+    pub newton: NewtonOptions,
+    /// Absolute tolerance of the device currents (A)
+    pub abstol: f64,
+    /// Largest change in any node voltage in one iteration (V)
+    pub max_step: f64,
+}
+
+impl Default for SolverOptions {
+    /// The SPICE tolerances, with at most 100 iterations of at most
+    /// half a volt
+    fn default() -> Self {
+        Self {
+            newton: NewtonOptions::default(),
+            abstol: 1e-12,
+            max_step: 0.5,
+        }
+    }
+}
+
+impl SolverOptions {
+    fn converged(&self, new: f64, old: f64, tolerance: f64) -> bool {
+        (new - old).abs() <= self.newton.reltol * new.abs().max(old.abs()) + tolerance
+    }
+}
+
+/// Solution of the [NonlinearSolver]
+#[derive(Debug, Clone)]
+pub struct NonlinearSolution {
+    pub solution: DcSolution,
+    /// Number of linear solves it took to converge
+    pub iterations: usize,
+}
+
+/// The last iterate of a Newton iteration (node voltages and edge
+/// currents), whether it converged, and the number of linear solves
+/// it took
+#[derive(Debug, Clone)]
+pub(crate) struct Iterate {
+    pub solution: (Vec<f64>, Vec<f64>),
+    pub converged: bool,
+    pub iterations: usize,
+}
+
+/// The nonlinear parts of a circuit linearised at an iterate: the
+/// conductance and current of each junction (by instance, and None
+/// for the other instances), and the stamp of each device and
+/// transistor branch with the current it predicts at zero volts
+pub(crate) struct Linearisation<'a> {
+    pub junctions: &'a [Option<(f64, f64)>],
+    pub devices: Vec<(Stamp, f64)>,
+}
+
+impl<'a> Linearisation<'a> {
+    /// The linearisation with the stamps of the device branches, each
+    /// loaded at a control voltage, and of the transistor branches
+    pub(crate) fn new(
+        junctions: &'a [Option<(f64, f64)>],
+        stamps: &[(Stamp, f64)],
+        transistors: &[TransistorBranch],
+    ) -> Self {
+        let devices = stamps
+            .iter()
+            .map(|(stamp, control)| (*stamp, stamp.predict(0.0, *control)));
+        Self {
+            junctions,
+            devices: devices
+                .chain(transistors.iter().flat_map(TransistorBranch::stamps))
+                .collect(),
+        }
+    }
+
+    /// The linear DC system of an elaborated circuit with this
+    /// linearisation
+    pub(crate) fn dc(&self, circuit: &Circuit) -> LinearDcAnalysis<f64> {
+        let mut dc = LinearDcAnalysis::linearised(circuit, self.junctions);
+        self.add_devices(dc.mna_mut());
+        dc
+    }
+
+    /// Add the stamps of the device and transistor branches to a
+    /// linear system
+    pub(crate) fn add_devices(&self, mna: &mut Mna<f64>) {
+        for (stamp, current) in &self.devices {
+            add_stamp(mna, stamp, *current);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NonlinearSolver {
+    pub options: SolverOptions,
+}
+
+impl From<NewtonOptions> for NonlinearSolver {
+    /// The solver of the analyses, which take full Newton steps and
+    /// rely on the junctions limiting their own voltages
+    fn from(newton: NewtonOptions) -> Self {
+        Self::new(SolverOptions {
+            newton,
+            max_step: f64::INFINITY,
+            ..Default::default()
+        })
+    }
+}
+
+impl NonlinearSolver {
+    pub fn new(options: SolverOptions) -> Self {
+        Self { options }
+    }
+
+    /// Solve the DC operating point of a circuit, with devices added
+    /// to its own junctions, starting from zero volts
+    pub fn solve(
+        &self,
+        circuit: &Circuit,
+        devices: &[&dyn NonlinearDevice],
+    ) -> Result<NonlinearSolution, EsimError> {
+        let circuit = circuit.elaborate();
+        let mut linear = CachedSolver::default();
+        let iterate = self.iterate(
+            &circuit,
+            devices,
+            &mut Vec::new(),
+            &mut JunctionCache::default(),
+            &mut [],
+            |linearisation| linearisation.dc(&circuit).try_solve_with(&mut linear),
+        )?;
+        if !iterate.converged {
+            return Err(EsimError::Convergence {
+                iterations: self.options.newton.max_iterations,
+            });
+        }
+        Ok(NonlinearSolution {
+            solution: dc_solution(&circuit, iterate.solution, Vec::new()),
+            iterations: iterate.iterations,
+        })
+    }
+
+    /// Newton iteration of an elaborated circuit, in which solve
+    /// returns the solution of the linear part of the circuit with the
+    /// nonlinear parts replaced by their linearisation. The junctions
+    /// are linearised at the junction voltages of the iteration (each
+    /// limited from the one before), or bypassed using the cache (see
+    /// [crate::evaluation]), and the transistors at their control
+    /// voltages (each limited from the one before). The junction
+    /// voltages start from their values on entry, and the transistor
+    /// control voltages from those kept in the cache, and both are
+    /// left at their final values; the devices start from zero volts.
+    ///
+    /// The node voltages of each iterate are damped to the largest
+    /// step, then clamped, before the junction voltages and device
+    /// stamps are found from them. The number of iterations in which
+    /// each clamp was active is added to its activity. The iteration
+    /// has converged when it is not damped, the junction voltages and
+    /// the control voltages of the transistors and devices settle, and
+    /// the transistor and device currents are those predicted by their
+    /// linearisations from the iteration before, so a circuit with no
+    /// junctions, transistors or devices is solved once.
+    pub(crate) fn iterate(
+        &self,
+        circuit: &Circuit,
+        devices: &[&dyn NonlinearDevice],
+        junctions: &mut Vec<f64>,
+        cache: &mut JunctionCache,
+        clamps: &mut [ClampActivity],
+        mut solve: impl FnMut(&Linearisation) -> Result<(Vec<f64>, Vec<f64>), EsimError>,
+    ) -> Result<Iterate, EsimError> {
+        let options = &self.options;
+        junctions.resize(circuit.instances().len(), 0.0);
+        cache
+            .transistors
+            .resize(circuit.instances().len(), [0.0; 2]);
+        let load = |voltages: &[f64], currents: &[f64]| -> Vec<(Stamp, f64)> {
+            let state = State { voltages, currents };
+            devices
+                .iter()
+                .flat_map(|device| device.load(&state))
+                .map(|stamp| (stamp, state.difference(stamp.control)))
+                .collect()
+        };
+        let mut voltages = vec![0.0; circuit.num_voltage_nodes()];
+        let mut stamps = load(&voltages, &[]);
+        let mut transistors = transistor_branches(circuit, &cache.transistors);
+        let mut solution = solve(&Linearisation::new(
+            cache.linearise(circuit, junctions),
+            &stamps,
+            &transistors,
+        ))?;
+        for iteration in 1..=options.newton.max_iterations {
+            let mut next = solution.0.clone();
+            let step = next
+                .iter()
+                .zip(&voltages)
+                .map(|(new, old)| (new - old).abs())
+                .fold(0.0, f64::max);
+            let damped = step > options.max_step;
+            if damped {
+                let scale = options.max_step / step;
+                for (new, old) in next.iter_mut().zip(&voltages) {
+                    *new = old + scale * (*new - old);
+                }
+            }
+            for activity in clamps.iter_mut() {
+                let NodeClamp { node, min, max } = activity.clamp;
+                let clamped = next[node - 1].clamp(min, max);
+                if clamped != next[node - 1] {
+                    next[node - 1] = clamped;
+                    activity.iterations += 1;
+                }
+            }
+            let state = State {
+                voltages: &next,
+                currents: &solution.1,
+            };
+            let mut converged = !damped;
+            for (index, instance) in circuit.instances().iter().enumerate() {
+                if let Some((anode, cathode, model)) = instance.component.junction() {
+                    let old = junctions[index];
+                    let new = model.limit_voltage(state.difference((anode, cathode)), old);
+                    converged &= options.newton.converged(new, old);
+                    junctions[index] = new;
+                }
+                if let Some((nodes, transistor)) = instance.component.transistor() {
+                    let voltages = transistor
+                        .controls()
+                        .map(|(pos, neg)| state.difference((nodes[pos], nodes[neg])));
+                    cache.transistors[index] = transistor.limit(voltages, cache.transistors[index]);
+                }
+            }
+            let next_transistors = transistor_branches(circuit, &cache.transistors);
+            converged &= transistors.iter().zip(&next_transistors).all(|(old, new)| {
+                let predicted = old.predict(new.at);
+                (0..2).all(|k| options.newton.converged(new.at[k], old.at[k]))
+                    && options.converged(new.current, predicted, options.abstol)
+            });
+            let next_stamps = load(&next, &solution.1);
+            converged &= stamps
+                .iter()
+                .zip(&next_stamps)
+                .all(|((old, at), (new, control))| {
+                    let predicted = old.predict(*control, *at);
+                    options.newton.converged(*control, *at)
+                        && options.converged(new.current, predicted, options.abstol)
+                });
+            voltages = next;
+            stamps = next_stamps;
+            transistors = next_transistors;
+            if converged {
+                return Ok(Iterate {
+                    solution,
+                    converged,
+                    iterations: iteration,
+                });
+            }
+            solution = solve(&Linearisation::new(
+                cache.linearise(circuit, junctions),
+                &stamps,
+                &transistors,
+            ))?;
+        }
+        Ok(Iterate {
+            solution,
+            converged: false,
+            iterations: options.newton.max_iterations + 1,
+        })
+    }
+}
+
+/// Add a stamp to the linear system, as its conductance in parallel
+/// with a current source of the current it predicts at zero volts
+fn add_stamp(mna: &mut Mna<f64>, stamp: &Stamp, current: f64) {
+    if stamp.output == stamp.control {
+        let (pos, neg) = stamp.output;
+        mna.add_admittance(pos, neg, stamp.conductance);
+    } else {
+        mna.add_voltage_controlled_current_source(stamp.output, stamp.control, stamp.conductance);
+    }
+    mna.add_independent_current_source(stamp.output.0, stamp.output.1, current);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CircuitBuilder;
+    use crate::component::{BjtModel, Model, MosfetModel, Polarity, THERMAL_VOLTAGE};
+    use crate::dc::operating_point;
+
+    fn diode_circuit() -> Circuit {
+        CircuitBuilder::new()
+            .vsource("V1", "in", "0", 5.0)
+            .resistor("R1", "in", "out", 1e3)
+            .diode("D1", "out", "0")
+            .build()
+            .unwrap()
+    }
+
+    /// A conductance whose current is the square of its voltage (in
+    /// mA per volt squared)
+    struct Square {
+        pos: usize,
+        neg: usize,
+    }
+
+    impl NonlinearDevice for Square {
+        fn load(&self, state: &State) -> Vec<Stamp> {
+            let voltage = state.difference((self.pos, self.neg));
+            vec![Stamp::branch(
+                self.pos,
+                self.neg,
+                1e-3 * voltage * voltage,
+                2e-3 * voltage,
+            )]
+        }
+    }
+
+    #[test]
+    fn junctions_solve_as_the_operating_point() {
+        let circuit = diode_circuit();
+        let solution = NonlinearSolver::default().solve(&circuit, &[]).unwrap();
+        let expected = operating_point(&circuit);
+        let out = circuit.node_names().get("out").unwrap();
+        let voltage = solution.solution.voltage(out);
+        assert!((voltage - expected.voltage(out)).abs() < 1e-3);
+        assert!(voltage > 0.5 && voltage < 0.9);
+    }
+
+    #[test]
+    fn device_stamps_are_solved() {
+        let circuit = CircuitBuilder::new()
+            .vsource("V1", "in", "0", 2.0)
+            .resistor("R1", "in", "out", 1e3)
+            .build()
+            .unwrap();
+        let out = circuit.node_names().get("out").unwrap();
+        let device = Square { pos: out, neg: 0 };
+        let solution = NonlinearSolver::default()
+            .solve(&circuit, &[&device])
+            .unwrap();
+        // (2 - v) / 1k = 1m v^2 at v = 1
+        assert!((solution.solution.voltage(out) - 1.0).abs() < 1e-6);
+        assert!(solution.iterations > 1);
+    }
+
+    #[test]
+    fn bipolar_transistors_are_biased_by_their_base_current() {
+        let circuit = CircuitBuilder::new()
+            .model("q", Model::Bjt(BjtModel::default()))
+            .vsource("V1", "vcc", "0", 5.0)
+            .resistor("RB", "vcc", "b", 430e3)
+            .resistor("RC", "vcc", "c", 1e3)
+            .bjt("Q1", "c", "b", "0", "q")
+            .build()
+            .unwrap();
+        let solution = operating_point(&circuit);
+        assert!(solution.warnings.is_empty());
+        let vbe = solution.node_voltage("b").unwrap();
+        let base = (5.0 - vbe) / 430e3;
+        let collector = (5.0 - solution.node_voltage("c").unwrap()) / 1e3;
+        assert!((collector / base / 100.0 - 1.0).abs() < 1e-3);
+        // The base-emitter voltage is that of the collector current
+        let expected = THERMAL_VOLTAGE * (collector / 1e-16).ln();
+        assert!((vbe - expected).abs() < 1e-3);
+        assert!(vbe > 0.7 && vbe < 0.8);
+    }
+
+    #[test]
+    fn mosfets_are_biased_in_saturation() {
+        let nmos = MosfetModel {
+            vto: 1.0,
+            kp: 1e-3,
+            ..Default::default()
+        };
+        let pmos = MosfetModel {
+            polarity: Polarity::P,
+            vto: -1.0,
+            ..nmos
+        };
+        let circuit = CircuitBuilder::new()
+            .model("n", Model::Mosfet(nmos))
+            .model("p", Model::Mosfet(pmos))
+            .vsource("V1", "vdd", "0", 10.0)
+            .vsource("VG", "g", "0", 3.0)
+            .resistor("RD", "vdd", "d", 1e3)
+            .mosfet("M1", "d", "g", "0", "n")
+            // The P-channel transistor is a mirror image of the first
+            .vsource("VGP", "vdd", "gp", 3.0)
+            .resistor("RDP", "dp", "0", 1e3)
+            .mosfet("M2", "dp", "gp", "vdd", "p")
+            .build()
+            .unwrap();
+        let solution = operating_point(&circuit);
+        assert!(solution.warnings.is_empty());
+        // 1m / 2 (3 - 1)^2 = 2 mA through 1k
+        let drain = solution.node_voltage("d").unwrap();
+        assert!((drain - 8.0).abs() < 1e-6);
+        let drain = solution.node_voltage("dp").unwrap();
+        assert!((drain - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn iteration_limit_is_a_convergence_error() {
+        let options = SolverOptions {
+            newton: NewtonOptions {
+                max_iterations: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let result = NonlinearSolver::new(options).solve(&diode_circuit(), &[]);
+        assert!(matches!(
+            result,
+            Err(EsimError::Convergence { iterations: 1 })
+        ));
+    }
+}
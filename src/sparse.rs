@@ -1,10 +1,165 @@
 //! Sparse matrix utilities
 //!
-//! This file acts as an interface between this application and
-//! csuperlu 
+//! This file acts as an interface between this application and the
+//! linear solver. The MNA systems are built as [SparseMat]s of a
+//! [ValueType] (f64 or Complex<f64>), and solved by a [LinearSolver],
+//! which factorizes the matrix and then solves it for any number of
+//! right-hand sides, and can refactorize a matrix of the same
+//! structure with new values. An analysis that solves many systems
+//! keeps one [CachedSolver], which refactorizes when only the values
+//! have changed.
 //!
+//! The backend is chosen by feature flags: with the `superlu`
+//! feature (on by default) it is SuperLU, through csuperlu, and
+//! without it a dense LU factorization in Rust, which needs no system
+//! library (so the tests can be run without SuperLU installed), but
+//! is only suitable for small circuits.
 
-use csuperlu::{sparse_matrix::SparseMat, dense::DenseMatrix, simple_driver::{SimpleSystem, SimpleSolution}, c::{stat::CSuperluStat, options::ColumnPermPolicy, value_type::ValueType}};
+use std::collections::HashMap;
+use std::ops;
+
+use num::Complex;
+
+use crate::error::EsimError;
+
+mod dense;
+#[cfg(feature = "superlu")]
+mod superlu;
+
+pub use self::dense::DenseLu;
+#[cfg(feature = "superlu")]
+pub use self::superlu::SuperLu;
+
+/// The solver of the backend chosen by the feature flags
+#[cfg(feature = "superlu")]
+pub type DefaultSolver<P> = SuperLu<P>;
+#[cfg(not(feature = "superlu"))]
+pub type DefaultSolver<P> = DenseLu<P>;
+
+/// The values of the types that the backend of the feature flags
+/// can solve
+#[cfg(feature = "superlu")]
+pub trait BackendValue: csuperlu::c::value_type::ValueType {}
+#[cfg(feature = "superlu")]
+impl<P: csuperlu::c::value_type::ValueType> BackendValue for P {}
+#[cfg(not(feature = "superlu"))]
+pub trait BackendValue {}
+#[cfg(not(feature = "superlu"))]
+impl<P> BackendValue for P {}
+
+/// A value of a matrix
+pub trait ValueType: BackendValue + Copy + num::Num + ops::Neg<Output = Self> {
+    /// Magnitude of the value, for choosing pivots
+    fn magnitude(&self) -> f64;
+}
+
+impl ValueType for f64 {
+    fn magnitude(&self) -> f64 {
+        self.abs()
+    }
+}
+
+impl ValueType for Complex<f64> {
+    fn magnitude(&self) -> f64 {
+        self.norm()
+    }
+}
+
+/// Sparse matrix of the values at their row and column, which are
+/// zero if they are not stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseMat<P: ValueType> {
+    num_rows: usize,
+    num_cols: usize,
+    values: HashMap<(usize, usize), P>,
+}
+
+impl<P: ValueType> SparseMat<P> {
+    /// A matrix with no rows or columns
+    pub fn empty() -> Self {
+        Self::new(0, 0)
+    }
+
+    /// A zero matrix of a size
+    pub fn new(num_rows: usize, num_cols: usize) -> Self {
+        Self {
+            num_rows,
+            num_cols,
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    pub fn num_cols(&self) -> usize {
+        self.num_cols
+    }
+
+    /// The value at a row and column, which is zero outside the
+    /// matrix
+    pub fn get_unbounded(&self, row: usize, col: usize) -> P {
+        self.values.get(&(row, col)).copied().unwrap_or(P::zero())
+    }
+
+    /// Set a value, growing the matrix if it is outside it
+    pub fn insert_unbounded(&mut self, row: usize, col: usize, value: P) {
+        self.num_rows = self.num_rows.max(row + 1);
+        self.num_cols = self.num_cols.max(col + 1);
+        self.values.insert((row, col), value);
+    }
+
+    /// Set a value. Panics if it is outside the matrix.
+    pub fn insert(&mut self, row: usize, col: usize, value: P) {
+        assert!(
+            row < self.num_rows && col < self.num_cols,
+            "Cannot insert at ({row}, {col}) in a {}x{} matrix",
+            self.num_rows,
+            self.num_cols
+        );
+        self.values.insert((row, col), value);
+    }
+
+    /// Change the size of the matrix, dropping the values outside it
+    pub fn resize(&mut self, num_rows: usize, num_cols: usize) {
+        self.resize_rows(num_rows);
+        self.resize_cols(num_cols);
+    }
+
+    pub fn resize_rows(&mut self, num_rows: usize) {
+        self.num_rows = num_rows;
+        self.values.retain(|(row, _), _| *row < num_rows);
+    }
+
+    pub fn resize_cols(&mut self, num_cols: usize) {
+        self.num_cols = num_cols;
+        self.values.retain(|(_, col), _| *col < num_cols);
+    }
+
+    /// The stored values, by row and column
+    pub fn non_zero_vals(&self) -> &HashMap<(usize, usize), P> {
+        &self.values
+    }
+}
+
+/// A backend that solves square linear systems
+pub trait LinearSolver<P: ValueType> {
+    /// Factorize a matrix, which the next solves are with. Fails if the
+    /// matrix is not square, or is singular.
+    fn factorize(&mut self, a: &SparseMat<P>) -> Result<(), EsimError>;
+
+    /// Solve the factorized matrix for several right-hand sides (each
+    /// column of b)
+    fn solve(&self, b: Vec<Vec<P>>) -> Result<Vec<Vec<P>>, EsimError>;
+
+    /// Factorize a matrix with the same structure (the positions of
+    /// its values) as the last, with new values, reusing what a
+    /// backend can of the last factorization
+    fn refactorize(&mut self, a: &SparseMat<P>) -> Result<(), EsimError> {
+        self.factorize(a)
+    }
+}
 
 /// Assumes the matrix is square
 pub fn plus_equals<P: ValueType>(mat: &mut SparseMat<P>, row: usize, col: usize, val: P) {
@@ -28,6 +183,15 @@ pub fn concat_horizontal<P: ValueType>(mut a: SparseMat<P>, b: &SparseMat<P>) ->
     a
 }
 
+/// The transpose of a matrix
+pub fn transpose<P: ValueType>(a: &SparseMat<P>) -> SparseMat<P> {
+    let mut t = SparseMat::new(a.num_cols(), a.num_rows());
+    for ((row, col), value) in a.non_zero_vals().iter() {
+        t.insert(*col, *row, *value);
+    }
+    t
+}
+
 pub fn concat_vertical<P: ValueType>(mut a: SparseMat<P>, b: &SparseMat<P>) -> SparseMat<P> {
     if a.num_cols() != b.num_cols() {
         panic!(
@@ -45,19 +209,187 @@ pub fn concat_vertical<P: ValueType>(mut a: SparseMat<P>, b: &SparseMat<P>) -> S
 }
 
 pub fn solve<P: ValueType>(a: SparseMat<P>, b: Vec<P>) -> Vec<P> {
-    if a.num_rows() != b.len() {
-        panic!("Cannot solve system; incompatible dimensions");
-    }
-    let a = a.compressed_column_format();
-    a.print("a");
-    let b = DenseMatrix::from_vectors(b.len(), 1, b);
-    let system = SimpleSystem { a, b };
-    let mut stat = CSuperluStat::new();
-    let SimpleSolution {
-	mut x,
-	..
-    }= system.solve(&mut stat, ColumnPermPolicy::ColAMD)
-        .expect("Failed to solve system");
-
-    x.column_major_values().to_vec()
+    try_solve(a, b).unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Solve the system, returning an error if it cannot be solved (such
+/// as when the matrix is singular) instead of panicking
+pub fn try_solve<P: ValueType>(a: SparseMat<P>, b: Vec<P>) -> Result<Vec<P>, EsimError> {
+    Ok(try_solve_columns(a, vec![b])?.remove(0))
+}
+
+/// Solve the system for several right-hand sides at once (each
+/// column of b) with the solver of the backend, factorizing the
+/// matrix only once, returning an error if it cannot be solved
+pub fn try_solve_columns<P: ValueType>(
+    a: SparseMat<P>,
+    b: Vec<Vec<P>>,
+) -> Result<Vec<Vec<P>>, EsimError> {
+    CachedSolver::default().solve_columns(&a, b)
+}
+
+/// The solver of the backend, kept through an analysis that solves
+/// a sequence of matrices (such as the iterations of a Newton solve,
+/// the time points of a transient analysis, or the frequencies of an
+/// AC sweep). A matrix with the structure of the last one factorized
+/// is refactorized, reusing what the backend can of its factorization,
+/// and any other is factorized from the start.
+pub struct CachedSolver<P: ValueType> {
+    solver: DefaultSolver<P>,
+    /// Size and stored positions (sorted) of the matrix last
+    /// factorized, if the factorization succeeded
+    structure: Option<(usize, Vec<(usize, usize)>)>,
+}
+
+impl<P: ValueType> Default for CachedSolver<P> {
+    fn default() -> Self {
+        Self {
+            solver: DefaultSolver::default(),
+            structure: None,
+        }
+    }
+}
+
+impl<P: ValueType> CachedSolver<P> {
+    /// Solve a system for several right-hand sides (each column of b),
+    /// returning an error if it cannot be solved
+    pub fn solve_columns(
+        &mut self,
+        a: &SparseMat<P>,
+        b: Vec<Vec<P>>,
+    ) -> Result<Vec<Vec<P>>, EsimError> {
+        if b.iter().any(|column| column.len() != a.num_rows()) {
+            return Err(incompatible());
+        }
+        if b.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut positions: Vec<(usize, usize)> = a.non_zero_vals().keys().copied().collect();
+        positions.sort_unstable();
+        let structure = Some((a.num_rows(), positions));
+        let result = if structure == self.structure {
+            self.solver.refactorize(a)
+        } else {
+            self.solver.factorize(a)
+        };
+        self.structure = result.is_ok().then_some(structure).flatten();
+        result?;
+        self.solver.solve(b)
+    }
+}
+
+fn incompatible() -> EsimError {
+    EsimError::Solve {
+        message: String::from("incompatible dimensions"),
+    }
+}
+
+fn singular() -> EsimError {
+    EsimError::Solve {
+        message: String::from("the matrix is singular"),
+    }
+}
+
+#[cfg(feature = "superlu")]
+fn unfactorized() -> EsimError {
+    EsimError::Solve {
+        message: String::from("the matrix has not been factorized"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mna::Mna;
+
+    /// The matrix and right-hand side of a 1 V source driving a
+    /// divider of 1 and 2 ohms, with a capacitive admittance at the
+    /// output, whose solution is 1 V, 2/3 V and -1/3 A at DC
+    fn divider<P: ValueType + From<f64>>(admittance: P) -> (SparseMat<P>, Vec<P>) {
+        let mut mna = Mna::new();
+        mna.add_independent_voltage_source(1, 0, 0, 1.0.into());
+        mna.add_resistor(1, 2, None, 1.0.into());
+        mna.add_resistor(2, 0, None, 2.0.into());
+        mna.add_admittance(2, 0, admittance);
+        let mut a = SparseMat::empty();
+        for ((row, col), value) in mna.entries() {
+            a.insert_unbounded(row, col, value);
+        }
+        (a, mna.rhs())
+    }
+
+    fn assert_close<P: ValueType>(x: &[P], y: &[P]) {
+        assert_eq!(x.len(), y.len());
+        for (x, y) in x.iter().zip(y) {
+            assert!((*x - *y).magnitude() < 1e-12);
+        }
+    }
+
+    fn solve_with<S: LinearSolver<P> + Default, P: ValueType>(
+        a: &SparseMat<P>,
+        b: Vec<P>,
+    ) -> Result<Vec<P>, EsimError> {
+        let mut solver = S::default();
+        solver.factorize(a)?;
+        Ok(solver.solve(vec![b])?.remove(0))
+    }
+
+    #[test]
+    fn dense_lu_solves_an_mna_system() {
+        let (a, b) = divider(0.0);
+        let x = solve_with::<DenseLu<f64>, _>(&a, b).unwrap();
+        assert_close(&x, &[1.0, 2.0 / 3.0, -1.0 / 3.0]);
+    }
+
+    #[test]
+    fn singular_matrix_fails_to_factorize() {
+        // The output node has no connection to anything but ground
+        let mut a = SparseMat::new(2, 2);
+        a.insert(0, 0, 1.0);
+        let result = DenseLu::default().factorize(&a);
+        assert!(matches!(result, Err(EsimError::Solve { .. })));
+        let result = try_solve(a, vec![1.0, 0.0]);
+        assert!(matches!(result, Err(EsimError::Solve { .. })));
+    }
+
+    #[test]
+    fn cached_solver_follows_new_values_and_structures() {
+        let mut solver = CachedSolver::default();
+        let (a, b) = divider(0.0);
+        let x = solver.solve_columns(&a, vec![b.clone()]).unwrap();
+        assert_close(&x[0], &[1.0, 2.0 / 3.0, -1.0 / 3.0]);
+        // Same structure, new values: 1 V across 1 ohm and 2 || 2 ohms
+        let (a, b) = divider(0.5);
+        let x = solver.solve_columns(&a, vec![b]).unwrap();
+        assert_close(&x[0], &[1.0, 0.5, -0.5]);
+        // A different structure is factorized from the start
+        let mut a = SparseMat::new(1, 1);
+        a.insert(0, 0, 4.0);
+        let x = solver.solve_columns(&a, vec![vec![2.0]]).unwrap();
+        assert_close(&x[0], &[0.5]);
+    }
+
+    #[cfg(feature = "superlu")]
+    #[test]
+    fn superlu_matches_dense_lu() {
+        let (a, b) = divider(0.25);
+        let dense = solve_with::<DenseLu<f64>, _>(&a, b.clone()).unwrap();
+        let superlu = solve_with::<SuperLu<f64>, _>(&a, b).unwrap();
+        assert_close(&dense, &superlu);
+
+        let (a, b) = divider(Complex::new(0.0, 0.5));
+        let dense = solve_with::<DenseLu<Complex<f64>>, _>(&a, b.clone()).unwrap();
+        let superlu = solve_with::<SuperLu<Complex<f64>>, _>(&a, b).unwrap();
+        assert_close(&dense, &superlu);
+    }
+
+    #[cfg(feature = "superlu")]
+    #[test]
+    fn superlu_reports_singular_and_unfactorized_matrices() {
+        let mut a = SparseMat::new(2, 2);
+        a.insert(0, 0, 1.0);
+        let mut solver = SuperLu::default();
+        assert!(solver.factorize(&a).is_err());
+        assert!(solver.solve(vec![vec![1.0, 0.0]]).is_err());
+    }
 }
@@ -0,0 +1,102 @@
+//! Dense LU backend
+//!
+//! LU factorization with partial pivoting of the matrix stored
+//! densely, in Rust, which needs no system library. It takes time
+//! cubic in the size of the matrix, so it is for small circuits and
+//! the tests, and for builds without the `superlu` feature.
+
+use super::{incompatible, singular, LinearSolver, SparseMat, ValueType};
+use crate::error::EsimError;
+
+/// The dense LU factorization of a matrix
+#[derive(Debug, Clone, PartialEq)]
+pub struct DenseLu<P: ValueType> {
+    size: usize,
+    /// The factors, by row: L below the diagonal (with its unit
+    /// diagonal not stored) and U on and above it
+    factors: Vec<P>,
+    /// The row of the matrix in each row of the factors
+    pivots: Vec<usize>,
+}
+
+impl<P: ValueType> Default for DenseLu<P> {
+    fn default() -> Self {
+        Self {
+            size: 0,
+            factors: Vec::new(),
+            pivots: Vec::new(),
+        }
+    }
+}
+
+impl<P: ValueType> LinearSolver<P> for DenseLu<P> {
+    fn factorize(&mut self, a: &SparseMat<P>) -> Result<(), EsimError> {
+        let n = a.num_rows();
+        if a.num_cols() != n {
+            return Err(incompatible());
+        }
+        let mut lu = vec![P::zero(); n * n];
+        for ((row, col), value) in a.non_zero_vals().iter() {
+            lu[row * n + col] = *value;
+        }
+        let mut pivots: Vec<usize> = (0..n).collect();
+        for k in 0..n {
+            let (pivot, largest) = (k..n).map(|row| (row, lu[row * n + k].magnitude())).fold(
+                (k, 0.0),
+                |best, next| if next.1 > best.1 { next } else { best },
+            );
+            if largest == 0.0 || !largest.is_finite() {
+                return Err(singular());
+            }
+            if pivot != k {
+                for col in 0..n {
+                    lu.swap(k * n + col, pivot * n + col);
+                }
+                pivots.swap(k, pivot);
+            }
+            let diagonal = lu[k * n + k];
+            for row in k + 1..n {
+                let factor = lu[row * n + k] / diagonal;
+                lu[row * n + k] = factor;
+                if factor == P::zero() {
+                    continue;
+                }
+                for col in k + 1..n {
+                    lu[row * n + col] = lu[row * n + col] - factor * lu[k * n + col];
+                }
+            }
+        }
+        *self = Self {
+            size: n,
+            factors: lu,
+            pivots,
+        };
+        Ok(())
+    }
+
+    fn solve(&self, b: Vec<Vec<P>>) -> Result<Vec<Vec<P>>, EsimError> {
+        let n = self.size;
+        let lu = &self.factors;
+        b.into_iter()
+            .map(|column| {
+                if column.len() != n {
+                    return Err(incompatible());
+                }
+                // Forward substitution with L, then back with U
+                let mut x: Vec<P> = self.pivots.iter().map(|row| column[*row]).collect();
+                for row in 0..n {
+                    for col in 0..row {
+                        x[row] = x[row] - lu[row * n + col] * x[col];
+                    }
+                }
+                for row in (0..n).rev() {
+                    for col in row + 1..n {
+                        x[row] = x[row] - lu[row * n + col] * x[col];
+                    }
+                    x[row] = x[row] / lu[row * n + row];
+                }
+                Ok(x)
+            })
+            .collect()
+    }
+}
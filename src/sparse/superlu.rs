@@ -0,0 +1,101 @@
+//! SuperLU backend
+//!
+//! Factorizes the matrix with SuperLU (`gstrf`, through csuperlu),
+//! keeping the L and U factors with the row and column permutations,
+//! so a singular matrix is found when it is factorized, and each
+//! solve is only the triangular solves (`gstrs`) for its right-hand
+//! sides. The columns are ordered by COLAMD when a matrix is first
+//! factorized, and a refactorization of a matrix with the same
+//! structure reuses that ordering.
+
+use std::fmt;
+
+use csuperlu::{
+    c::{options::ColumnPermPolicy, stat::CSuperluStat},
+    dense::DenseMatrix,
+    lu_decomp::LUDecomp,
+    sparse_matrix::{self, CompColMatrix},
+};
+
+use super::{incompatible, unfactorized, LinearSolver, SparseMat, ValueType};
+use crate::error::EsimError;
+
+/// The SuperLU factorization of a matrix
+pub struct SuperLu<P: ValueType> {
+    size: usize,
+    factors: Option<LUDecomp<P>>,
+}
+
+impl<P: ValueType> Default for SuperLu<P> {
+    fn default() -> Self {
+        Self {
+            size: 0,
+            factors: None,
+        }
+    }
+}
+
+fn failed(error: impl fmt::Debug) -> EsimError {
+    EsimError::Solve {
+        message: format!("{error:?}"),
+    }
+}
+
+/// The matrix in the compressed column format of SuperLU
+fn compressed<P: ValueType>(a: &SparseMat<P>) -> Result<CompColMatrix<P>, EsimError> {
+    if a.num_cols() != a.num_rows() {
+        return Err(incompatible());
+    }
+    let mut matrix = sparse_matrix::SparseMat::new(a.num_rows(), a.num_cols());
+    for ((row, col), value) in a.non_zero_vals().iter() {
+        matrix.insert(*row, *col, *value);
+    }
+    Ok(matrix.compressed_column_format())
+}
+
+impl<P: ValueType> LinearSolver<P> for SuperLu<P> {
+    fn factorize(&mut self, a: &SparseMat<P>) -> Result<(), EsimError> {
+        self.factors = None;
+        let matrix = compressed(a)?;
+        let mut stat = CSuperluStat::new();
+        let factors =
+            LUDecomp::factorize(matrix, ColumnPermPolicy::ColAMD, &mut stat).map_err(failed)?;
+        self.size = a.num_rows();
+        self.factors = Some(factors);
+        Ok(())
+    }
+
+    fn refactorize(&mut self, a: &SparseMat<P>) -> Result<(), EsimError> {
+        match self.factors.as_mut() {
+            Some(factors) if a.num_rows() == self.size => {
+                let matrix = compressed(a)?;
+                let mut stat = CSuperluStat::new();
+                let result = factors.refactorize(matrix, &mut stat).map_err(failed);
+                if result.is_err() {
+                    self.factors = None;
+                }
+                result
+            }
+            _ => self.factorize(a),
+        }
+    }
+
+    fn solve(&self, b: Vec<Vec<P>>) -> Result<Vec<Vec<P>>, EsimError> {
+        let factors = self.factors.as_ref().ok_or_else(unfactorized)?;
+        let num_rows = self.size;
+        if b.iter().any(|column| column.len() != num_rows) {
+            return Err(incompatible());
+        }
+        let num_columns = b.len();
+        if num_columns == 0 {
+            return Ok(Vec::new());
+        }
+        let mut x = DenseMatrix::from_vectors(num_rows, num_columns, b.concat());
+        let mut stat = CSuperluStat::new();
+        factors.solve(&mut x, &mut stat).map_err(failed)?;
+        Ok(x.column_major_values()
+            .chunks(num_rows)
+            .map(|column| column.to_vec())
+            .collect())
+    }
+}
@@ -0,0 +1,298 @@
+//! Summary statistics of measurements over many samples
+//!
+//! Used to summarise the values of a measurement across the samples
+//! of a Monte Carlo run: the mean and standard deviation, chosen
+//! percentiles, the process capability index (Cpk) against
+//! specification limits, and a histogram.
+//!
+//! Measurements can also be checked against their specification
+//! limits, giving the number of samples that fail each limit, the
+//! yield (the fraction of samples that pass every limit), and the
+//! indices of the failing samples so they can be looked at.
+
+/// Lower and upper specification limits of a measurement (either can
+/// be absent for a one-sided specification)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SpecLimits {
+    pub lower: Option<f64>,
+    pub upper: Option<f64>,
+}
+
+impl SpecLimits {
+    pub fn new(lower: Option<f64>, upper: Option<f64>) -> Self {
+        Self { lower, upper }
+    }
+
+    /// Whether a value is within the limits (a value that is not a
+    /// number is not)
+    pub fn contains(&self, value: f64) -> bool {
+        !value.is_nan()
+            && !self.lower.is_some_and(|lower| value < lower)
+            && !self.upper.is_some_and(|upper| value > upper)
+    }
+
+    /// Check the samples of a measurement against the limits
+    pub fn check(&self, name: &str, samples: &[f64]) -> LimitCheck {
+        let below = |x: &f64| self.lower.is_some_and(|lower| *x < lower);
+        let above = |x: &f64| self.upper.is_some_and(|upper| *x > upper);
+        LimitCheck {
+            name: name.to_string(),
+            below_lower: samples.iter().filter(|x| below(x)).count(),
+            above_upper: samples.iter().filter(|x| above(x)).count(),
+            failing: (0..samples.len())
+                .filter(|k| !self.contains(samples[*k]))
+                .collect(),
+        }
+    }
+}
+
+/// Samples of a measurement that fail its specification limits
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitCheck {
+    pub name: String,
+    /// Number of samples below the lower limit
+    pub below_lower: usize,
+    /// Number of samples above the upper limit
+    pub above_upper: usize,
+    /// Indices of the failing samples (including any that are not a
+    /// number)
+    pub failing: Vec<usize>,
+}
+
+/// Pass/fail counts of a set of measurements against their limits
+#[derive(Debug, Clone, PartialEq)]
+pub struct YieldReport {
+    pub num_samples: usize,
+    pub checks: Vec<LimitCheck>,
+    /// Indices of the samples that fail any limit
+    pub failing: Vec<usize>,
+}
+
+impl YieldReport {
+    pub fn new(num_samples: usize, checks: Vec<LimitCheck>) -> Self {
+        let mut failing: Vec<usize> = checks
+            .iter()
+            .flat_map(|check| check.failing.iter().copied())
+            .collect();
+        failing.sort_unstable();
+        failing.dedup();
+        Self {
+            num_samples,
+            checks,
+            failing,
+        }
+    }
+
+    /// Number of samples that pass every limit
+    pub fn passed(&self) -> usize {
+        self.num_samples - self.failing.len()
+    }
+
+    /// Fraction of the samples that pass every limit
+    pub fn yield_fraction(&self) -> f64 {
+        self.passed() as f64 / self.num_samples as f64
+    }
+
+    /// The limit check of a measurement
+    pub fn check(&self, name: &str) -> Option<&LimitCheck> {
+        self.checks.iter().find(|check| check.name == name)
+    }
+}
+
+/// Counts of samples in equal-width bins between the smallest and
+/// largest sample. Samples that are not a number are not counted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// Bin edges (one more than the number of bins)
+    pub edges: Vec<f64>,
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    pub fn new(samples: &[f64], num_bins: usize) -> Self {
+        let samples: Vec<f64> = samples.iter().copied().filter(|x| !x.is_nan()).collect();
+        if samples.is_empty() || num_bins == 0 {
+            return Self {
+                edges: Vec::new(),
+                counts: Vec::new(),
+            };
+        }
+        let min = samples.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = samples.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        // All the samples go in one bin if they are the same
+        let width = if max > min {
+            (max - min) / num_bins as f64
+        } else {
+            1.0
+        };
+        let edges = (0..=num_bins).map(|k| min + k as f64 * width).collect();
+        let mut counts = vec![0; num_bins];
+        for sample in &samples {
+            let bin = ((sample - min) / width) as usize;
+            counts[bin.min(num_bins - 1)] += 1;
+        }
+        Self { edges, counts }
+    }
+
+    /// Centre of each bin
+    pub fn centres(&self) -> Vec<f64> {
+        self.edges.windows(2).map(|e| 0.5 * (e[0] + e[1])).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statistics {
+    pub num_samples: usize,
+    pub mean: f64,
+    /// Sample standard deviation (zero for a single sample)
+    pub std_dev: f64,
+    /// (percentile, value) for each requested percentile
+    pub percentiles: Vec<(f64, f64)>,
+    /// Process capability index against the specification limits, if
+    /// there are any. With no spread in the samples, it is infinite:
+    /// positive if the mean is within the limits and negative if not.
+    pub cpk: Option<f64>,
+    pub histogram: Histogram,
+}
+
+/// Value below which a percentage of the samples lie, interpolating
+/// linearly between the sorted samples
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let position = (percent / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+    sorted[below] + (position - below as f64) * (sorted[above] - sorted[below])
+}
+
+impl Statistics {
+    /// Statistics of a measurement, with the percentiles (from 0 to
+    /// 100) and the number of histogram bins to report, and the Cpk
+    /// against the specification limits
+    pub fn new(samples: &[f64], percentiles: &[f64], limits: SpecLimits, num_bins: usize) -> Self {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        // A single sample has no spread, rather than an undefined one
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0).max(1.0);
+        let std_dev = variance.sqrt();
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let percentiles = if sorted.is_empty() {
+            Vec::new()
+        } else {
+            percentiles
+                .iter()
+                .map(|p| (*p, percentile(&sorted, *p)))
+                .collect()
+        };
+
+        // The margin to a limit in units of three standard deviations
+        let capability = |margin: f64| {
+            if std_dev > 0.0 {
+                margin / (3.0 * std_dev)
+            } else if margin >= 0.0 {
+                f64::INFINITY
+            } else {
+                f64::NEG_INFINITY
+            }
+        };
+        let upper = limits.upper.map(|usl| capability(usl - mean));
+        let lower = limits.lower.map(|lsl| capability(mean - lsl));
+        let cpk = match (lower, upper) {
+            (Some(lower), Some(upper)) => Some(lower.min(upper)),
+            (cpk, None) | (None, cpk) => cpk,
+        };
+
+        Self {
+            num_samples: samples.len(),
+            mean,
+            std_dev,
+            percentiles,
+            cpk,
+            histogram: Histogram::new(samples, num_bins),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_interpolate_between_the_samples() {
+        let samples = [4.0, 1.0, 3.0, 2.0, 5.0];
+        let statistics = Statistics::new(
+            &samples,
+            &[0.0, 50.0, 90.0, 100.0],
+            SpecLimits::default(),
+            0,
+        );
+        assert_eq!(statistics.mean, 3.0);
+        assert!((statistics.std_dev - 2.5f64.sqrt()).abs() < 1e-12);
+        assert_eq!(
+            statistics.percentiles,
+            [(0.0, 1.0), (50.0, 3.0), (90.0, 4.6), (100.0, 5.0)]
+        );
+        assert_eq!(statistics.cpk, None);
+    }
+
+    #[test]
+    fn cpk_is_the_margin_to_the_nearer_limit() {
+        let samples = [9.0, 10.0, 11.0];
+        // A standard deviation of one, with the mean 6 above the lower
+        // limit and 3 below the upper
+        let cpk =
+            |lower, upper| Statistics::new(&samples, &[], SpecLimits::new(lower, upper), 0).cpk;
+        assert_eq!(cpk(Some(4.0), Some(13.0)), Some(1.0));
+        assert_eq!(cpk(Some(4.0), None), Some(2.0));
+        assert_eq!(cpk(None, Some(13.0)), Some(1.0));
+        assert_eq!(cpk(None, Some(7.0)), Some(-1.0));
+    }
+
+    #[test]
+    fn a_single_sample_has_no_spread() {
+        let limits = SpecLimits::new(Some(0.0), Some(2.0));
+        let statistics = Statistics::new(&[1.0], &[50.0], limits, 2);
+        assert_eq!(statistics.std_dev, 0.0);
+        assert_eq!(statistics.percentiles, [(50.0, 1.0)]);
+        assert_eq!(statistics.cpk, Some(f64::INFINITY));
+        let outside = Statistics::new(&[3.0], &[], limits, 0);
+        assert_eq!(outside.cpk, Some(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn histogram_bins_span_the_samples() {
+        let histogram = Histogram::new(&[0.0, 0.5, 1.0, 2.5, 3.0, 4.0], 4);
+        assert_eq!(histogram.edges, [0.0, 1.0, 2.0, 3.0, 4.0]);
+        // The largest sample is in the last bin
+        assert_eq!(histogram.counts, [2, 1, 1, 2]);
+        assert_eq!(histogram.centres(), [0.5, 1.5, 2.5, 3.5]);
+        let same = Histogram::new(&[2.0, 2.0], 3);
+        assert_eq!(same.counts, [2, 0, 0]);
+        assert!(Histogram::new(&[], 3).counts.is_empty());
+    }
+
+    #[test]
+    fn histograms_leave_out_samples_that_are_not_a_number() {
+        let histogram = Histogram::new(&[f64::NAN, 1.0, 3.0, f64::NAN], 2);
+        assert_eq!(histogram.edges, [1.0, 2.0, 3.0]);
+        assert_eq!(histogram.counts, [1, 1]);
+        assert!(Histogram::new(&[f64::NAN], 2).counts.is_empty());
+    }
+
+    #[test]
+    fn yield_counts_the_samples_that_pass_every_limit() {
+        let limits = SpecLimits::new(Some(0.0), Some(1.0));
+        let gain = limits.check("gain", &[0.5, -1.0, 2.0, f64::NAN]);
+        assert_eq!((gain.below_lower, gain.above_upper), (1, 1));
+        assert_eq!(gain.failing, [1, 2, 3]);
+        let offset = SpecLimits::new(None, Some(0.1)).check("offset", &[0.2, 0.0, 0.0, 0.0]);
+        let report = YieldReport::new(4, vec![gain, offset]);
+        assert_eq!(report.failing, [0, 1, 2, 3]);
+        assert_eq!((report.passed(), report.yield_fraction()), (0, 0.0));
+        assert_eq!(
+            report.check("offset").map(|check| check.above_upper),
+            Some(1)
+        );
+    }
+}
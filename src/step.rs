@@ -0,0 +1,171 @@
+//! Parameter stepping
+//!
+//! Any analysis is repeated with a parameter of the circuit stepped
+//! over a list of values, as for a SPICE `.STEP` line, giving one run
+//! for each value. The parameter is the value of an instance (as in a
+//! [DC sweep](crate::sweep)) or a parameter of the model of an
+//! instance, such as the saturation current of a diode, or of a model
+//! shared by instances (from a `.MODEL` card), or a parameter
+//! of the circuit (from a `.PARAM` line), whose dependent values are
+//! evaluated again at each step. The analysis
+//! is a function of the circuit, so it can be an operating point, an
+//! AC sweep, a transient analysis or measurements made on one, and a
+//! step can be nested inside another by stepping in the analysis.
+//!
+//! The values are given as a list; [SweepRange](crate::sweep::SweepRange)
+//! gives linear steps.
+
+use std::fmt;
+
+use crate::circuit::Circuit;
+use crate::error::{invalid, EsimError};
+use crate::transient::ComponentChange;
+
+/// A stepped parameter of a circuit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepParameter {
+    /// The value of an instance: a resistance, capacitance or
+    /// inductance, or the value of an independent source
+    Value(String),
+    /// A parameter of the model of an instance, by name (such as `is`
+    /// for a diode)
+    Model { instance: String, parameter: String },
+    /// A parameter of a model of the circuit by name, which changes it
+    /// for every instance that uses the model
+    Card { model: String, parameter: String },
+    /// A parameter of the circuit, by name
+    Parameter(String),
+}
+
+impl StepParameter {
+    /// Set the parameter of a circuit, or return an error message if
+    /// it cannot be set
+    fn apply(&self, circuit: &mut Circuit, value: f64) -> Result<(), String> {
+        let (name, parameter) = match self {
+            Self::Value(instance) => (instance, None),
+            Self::Model {
+                instance,
+                parameter,
+            } => (instance, Some(parameter)),
+            Self::Parameter(name) => {
+                if !circuit.parameters().contains(name) {
+                    return Err(format!("No parameter named {name}"));
+                }
+                return circuit
+                    .set_parameter(name, value)
+                    .map_err(|error| error.to_string());
+            }
+            Self::Card { model, parameter } => {
+                if circuit.model(model).is_none() {
+                    return Err(format!("No model named {model}"));
+                }
+                if !circuit.set_model_parameter(model, parameter, value) {
+                    return Err(format!("Model {model} has no parameter {parameter}"));
+                }
+                return Ok(());
+            }
+        };
+        let instance = circuit
+            .instances_mut()
+            .iter_mut()
+            .find(|i| i.name == *name)
+            .ok_or_else(|| format!("No instance named {name}"))?;
+        match parameter {
+            None => {
+                instance.component = ComponentChange::Value(value)
+                    .apply(&instance.component)
+                    .ok_or_else(|| format!("Cannot step the value of instance {}", instance.name))?
+            }
+            Some(parameter) => {
+                *instance
+                    .component
+                    .model_parameter_mut(parameter)
+                    .ok_or_else(|| {
+                        format!("Instance {} has no parameter {parameter}", instance.name)
+                    })? = value
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for StepParameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(instance) | Self::Parameter(instance) => write!(f, "{instance}"),
+            Self::Model {
+                instance,
+                parameter,
+            } => write!(f, "{instance}.{parameter}"),
+            Self::Card { model, parameter } => write!(f, "{model}({parameter})"),
+        }
+    }
+}
+
+/// The result of an analysis at each value of a stepped parameter
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepResult<T> {
+    pub parameter: StepParameter,
+    pub values: Vec<f64>,
+    /// The result of the analysis at each value
+    pub runs: Vec<T>,
+}
+
+impl<T> StepResult<T> {
+    /// The run at a stepped value, if there is one within a small
+    /// relative tolerance of it
+    pub fn get(&self, value: f64) -> Option<&T> {
+        self.values
+            .iter()
+            .position(|v| (v - value).abs() <= 1e-9 * v.abs().max(value.abs()))
+            .map(|index| &self.runs[index])
+    }
+
+    /// The stepped values with their runs
+    pub fn iter(&self) -> impl Iterator<Item = (f64, &T)> {
+        self.values.iter().copied().zip(&self.runs)
+    }
+
+    /// A quantity of each run (such as a measurement), by stepped value
+    pub fn map<U>(&self, f: impl Fn(&T) -> U) -> Vec<(f64, U)> {
+        self.iter().map(|(value, run)| (value, f(run))).collect()
+    }
+}
+
+/// Run an analysis of a circuit with a parameter set to each value in
+/// turn. Panics if there is no instance or parameter with the name, or
+/// the parameter cannot be set (see [try_step]).
+pub fn step<T>(
+    circuit: &Circuit,
+    parameter: &StepParameter,
+    values: &[f64],
+    analysis: impl Fn(&Circuit) -> T,
+) -> StepResult<T> {
+    try_step(circuit, parameter, values, |circuit| Ok(analysis(circuit)))
+        .unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Run an analysis that can fail (such as a `try_` analysis) with a
+/// parameter set to each value in turn as for [step], returning an
+/// error if the parameter cannot be set, or the first error of the
+/// analysis
+pub fn try_step<T>(
+    circuit: &Circuit,
+    parameter: &StepParameter,
+    values: &[f64],
+    analysis: impl Fn(&Circuit) -> Result<T, EsimError>,
+) -> Result<StepResult<T>, EsimError> {
+    let runs = values
+        .iter()
+        .map(|value| {
+            let mut circuit = circuit.clone();
+            parameter.apply(&mut circuit, *value).map_err(invalid)?;
+            analysis(&circuit)
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(StepResult {
+        parameter: parameter.clone(),
+        values: values.to_vec(),
+        runs,
+    })
+}
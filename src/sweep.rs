@@ -0,0 +1,320 @@
+//! DC sweep analysis
+//!
+//! The value of one component (an independent source, or a resistor,
+//! capacitor or inductor) is stepped over a range, and the operating
+//! point is solved at each value, as for a SPICE `.DC` line. The
+//! circuit is elaborated once, and only the swept component changes
+//! between points. Each point starts its Newton iteration from the
+//! junction voltages of the previous point, which follows a nonlinear
+//! characteristic (such as a diode IV curve) in small steps rather
+//! than solving each point from zero.
+//!
+//! A nested sweep repeats the sweep at each value of a second
+//! component, as a `.DC` line with two sources does, giving a family
+//! of curves.
+
+use crate::circuit::Circuit;
+use crate::component::Component;
+use crate::dc::{dc_solution, explain, not_converged, DcSolution, NewtonOptions};
+use crate::error::{invalid, no_instance, EsimError};
+use crate::evaluation::JunctionCache;
+use crate::solver::NonlinearSolver;
+use crate::sparse::CachedSolver;
+use crate::transient::ComponentChange;
+
+/// Values from start to stop (inclusive) in equal steps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweepRange {
+    pub start: f64,
+    pub stop: f64,
+    pub step: f64,
+}
+
+impl SweepRange {
+    /// Panics if the step is zero, or goes away from the stop value
+    pub fn new(start: f64, stop: f64, step: f64) -> Self {
+        assert!(step != 0.0, "Sweep step must not be zero");
+        assert!(
+            (stop - start) * step >= 0.0,
+            "Sweep step must go from start towards stop"
+        );
+        Self { start, stop, step }
+    }
+
+    /// The swept values. The stop value is included if it is within
+    /// a small fraction of a step of the last step.
+    pub fn values(&self) -> Vec<f64> {
+        let num_steps = ((self.stop - self.start) / self.step + 1e-9).floor() as usize;
+        (0..=num_steps)
+            .map(|n| self.start + n as f64 * self.step)
+            .collect()
+    }
+}
+
+/// Operating points at each value of a swept component
+#[derive(Debug, Clone)]
+pub struct DcSweepResult {
+    /// Name of the swept instance
+    pub instance: String,
+    /// Quantity swept, such as "voltage" or "resistance"
+    pub quantity: &'static str,
+    pub values: Vec<f64>,
+    pub solutions: Vec<DcSolution>,
+}
+
+impl DcSweepResult {
+    /// Voltage of a node at each swept value (zero for ground)
+    pub fn voltage(&self, node: usize) -> Vec<f64> {
+        self.solutions.iter().map(|s| s.voltage(node)).collect()
+    }
+
+    /// Voltage of a node by name (see [crate::node]) at each swept
+    /// value, if there is a node with the name in the circuit
+    pub fn node_voltage(&self, name: &str) -> Option<Vec<f64>> {
+        self.solutions
+            .iter()
+            .map(|s| s.node_voltage(name))
+            .collect()
+    }
+
+    /// Current in an edge at each swept value
+    pub fn current(&self, edge: usize) -> Vec<f64> {
+        self.solutions.iter().map(|s| s.currents[edge]).collect()
+    }
+
+    /// Current through a current probe at each swept value
+    pub fn probe_current(&self, name: &str) -> Option<Vec<f64>> {
+        self.solutions
+            .iter()
+            .map(|s| s.probe_current(name))
+            .collect()
+    }
+
+    /// Warnings of the points of the sweep, each with its swept value
+    pub fn warnings(&self) -> Vec<String> {
+        self.values
+            .iter()
+            .zip(&self.solutions)
+            .flat_map(|(value, solution)| {
+                solution
+                    .warnings
+                    .iter()
+                    .map(move |warning| format!("{} = {value:e}: {warning}", self.instance))
+            })
+            .collect()
+    }
+}
+
+/// Operating points of a DC sweep at each value of a second swept
+/// component, giving a family of curves (such as the collector
+/// characteristics of a transistor for several base currents)
+#[derive(Debug, Clone)]
+pub struct DcSweepFamily {
+    /// Name of the outer swept instance
+    pub instance: String,
+    pub values: Vec<f64>,
+    /// The inner sweep at each outer value
+    pub curves: Vec<DcSweepResult>,
+}
+
+impl DcSweepFamily {
+    /// Voltage of a node along each curve
+    pub fn voltage(&self, node: usize) -> Vec<Vec<f64>> {
+        self.curves.iter().map(|c| c.voltage(node)).collect()
+    }
+
+    /// Voltage of a node by name (see [crate::node]) along each curve,
+    /// if there is a node with the name in the circuit
+    pub fn node_voltage(&self, name: &str) -> Option<Vec<Vec<f64>>> {
+        self.curves.iter().map(|c| c.node_voltage(name)).collect()
+    }
+
+    /// Current in an edge along each curve
+    pub fn current(&self, edge: usize) -> Vec<Vec<f64>> {
+        self.curves.iter().map(|c| c.current(edge)).collect()
+    }
+}
+
+/// Index of a swept instance in an elaborated circuit, or an error if
+/// there is none
+fn swept_index(circuit: &Circuit, instance: &str) -> Result<usize, EsimError> {
+    circuit
+        .instances()
+        .iter()
+        .position(|i| i.name == instance)
+        .ok_or_else(|| no_instance(instance))
+}
+
+/// Set the value of a swept instance, or return an error if the value
+/// cannot be set
+fn set_value(circuit: &mut Circuit, index: usize, value: f64) -> Result<(), EsimError> {
+    let instance = &mut circuit.instances_mut()[index];
+    instance.component = ComponentChange::Value(value)
+        .apply(&instance.component)
+        .ok_or_else(|| {
+            invalid(format!(
+                "Cannot sweep the value of instance {}",
+                instance.name
+            ))
+        })?;
+    Ok(())
+}
+
+/// Sweep an instance of an elaborated circuit, with the Newton
+/// iteration of the first point starting from the junction voltages,
+/// which are left at those of the first point. The junction cache and
+/// the linear solver are kept between points.
+fn sweep_elaborated(
+    circuit: &mut Circuit,
+    index: usize,
+    range: &SweepRange,
+    junctions: &mut Vec<f64>,
+    cache: &mut JunctionCache,
+    linear: &mut CachedSolver<f64>,
+    options: &NewtonOptions,
+) -> Result<DcSweepResult, EsimError> {
+    let values = range.values();
+    let solver = NonlinearSolver::from(*options);
+    let mut first = None;
+    let mut solutions = Vec::new();
+    for value in &values {
+        set_value(circuit, index, *value)?;
+        let circuit = &*circuit;
+        let iterate = solver.iterate(circuit, &[], junctions, cache, &mut [], |linearisation| {
+            linearisation.dc(circuit).try_solve_with(linear)
+        })?;
+        let warnings = if iterate.converged {
+            Vec::new()
+        } else {
+            vec![not_converged(options)]
+        };
+        first.get_or_insert_with(|| junctions.clone());
+        solutions.push(dc_solution(circuit, iterate.solution, warnings));
+    }
+    if let Some(first) = first {
+        *junctions = first;
+    }
+    let instance = &circuit.instances()[index];
+    let quantity = match instance.component {
+        Component::IndependentVoltageSource { .. } => "voltage",
+        Component::IndependentCurrentSource { .. } => "current",
+        Component::Resistor { .. } => "resistance",
+        Component::Capacitor { .. } => "capacitance",
+        Component::Inductor { .. } => "inductance",
+        _ => unreachable!("Only the values of sources and passive components are swept"),
+    };
+    Ok(DcSweepResult {
+        instance: instance.name.clone(),
+        quantity,
+        values,
+        solutions,
+    })
+}
+
+/// Solve the operating point of a circuit at each value of a swept
+/// component. Panics if there is no instance with the name, or if its
+/// value cannot be set (see [try_dc_sweep]).
+pub fn dc_sweep(circuit: &Circuit, instance: &str, range: &SweepRange) -> DcSweepResult {
+    dc_sweep_options(circuit, instance, range, &NewtonOptions::default())
+}
+
+/// Sweep a component as for [dc_sweep], converging the Newton
+/// iteration at each point by the settings
+pub fn dc_sweep_options(
+    circuit: &Circuit,
+    instance: &str,
+    range: &SweepRange,
+    options: &NewtonOptions,
+) -> DcSweepResult {
+    sweep_options(circuit, instance, range, options).unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Sweep a component as for [dc_sweep_options], returning an error if
+/// the circuit fails its check (see [Circuit::validate]), there is no
+/// instance with the name, its value cannot be set, or the system at
+/// a point cannot be solved
+pub fn try_dc_sweep(
+    circuit: &Circuit,
+    instance: &str,
+    range: &SweepRange,
+    options: &NewtonOptions,
+) -> Result<DcSweepResult, EsimError> {
+    circuit.validate()?;
+    sweep_options(circuit, instance, range, options).map_err(|error| explain(circuit, error))
+}
+
+fn sweep_options(
+    circuit: &Circuit,
+    instance: &str,
+    range: &SweepRange,
+    options: &NewtonOptions,
+) -> Result<DcSweepResult, EsimError> {
+    let mut elaborated = circuit.elaborate();
+    let index = swept_index(&elaborated, instance)?;
+    sweep_elaborated(
+        &mut elaborated,
+        index,
+        range,
+        &mut Vec::new(),
+        &mut JunctionCache::default(),
+        &mut CachedSolver::default(),
+        options,
+    )
+}
+
+/// Sweep the inner component at each value of the outer component.
+/// Each curve starts from the junction voltages of the first point of
+/// the previous curve. Panics as for [dc_sweep] (see
+/// [try_dc_sweep_nested]).
+pub fn dc_sweep_nested(
+    circuit: &Circuit,
+    inner: (&str, &SweepRange),
+    outer: (&str, &SweepRange),
+) -> DcSweepFamily {
+    sweep_nested(circuit, inner, outer).unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Sweep the inner component at each value of the outer component as
+/// for [dc_sweep_nested], returning an error as for [try_dc_sweep]
+pub fn try_dc_sweep_nested(
+    circuit: &Circuit,
+    inner: (&str, &SweepRange),
+    outer: (&str, &SweepRange),
+) -> Result<DcSweepFamily, EsimError> {
+    circuit.validate()?;
+    sweep_nested(circuit, inner, outer).map_err(|error| explain(circuit, error))
+}
+
+fn sweep_nested(
+    circuit: &Circuit,
+    (inner, inner_range): (&str, &SweepRange),
+    (outer, outer_range): (&str, &SweepRange),
+) -> Result<DcSweepFamily, EsimError> {
+    let mut elaborated = circuit.elaborate();
+    let inner_index = swept_index(&elaborated, inner)?;
+    let outer_index = swept_index(&elaborated, outer)?;
+    let values = outer_range.values();
+    let mut junctions = Vec::new();
+    let mut cache = JunctionCache::default();
+    let mut linear = CachedSolver::default();
+    let curves = values
+        .iter()
+        .map(|value| {
+            set_value(&mut elaborated, outer_index, *value)?;
+            sweep_elaborated(
+                &mut elaborated,
+                inner_index,
+                inner_range,
+                &mut junctions,
+                &mut cache,
+                &mut linear,
+                &NewtonOptions::default(),
+            )
+        })
+        .collect::<Result<_, EsimError>>()?;
+    Ok(DcSweepFamily {
+        instance: outer.to_string(),
+        values,
+        curves,
+    })
+}
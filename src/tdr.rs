@@ -0,0 +1,200 @@
+//! Time-domain reflectometry (TDR)
+//!
+//! A TDR instrument launches a step with a finite rise time into a
+//! port through its reference impedance, and records the reflected
+//! wave. Here the reflection coefficient of the port is computed by
+//! AC analysis over a uniform frequency grid, and the reflected step
+//! is obtained by inverse Fourier transform, using a Gaussian edge
+//! with the requested 10-90% rise time. The reflected waveform is
+//! then converted into impedance versus distance along the line,
+//! using the propagation velocity (the wave travels out and back, so
+//! distance is half the velocity times the time).
+
+use std::f64::consts::PI;
+
+use num::Complex;
+
+use crate::ac::LinearAcAnalysis;
+use crate::circuit::Circuit;
+use crate::component::{AcSpec, Component};
+
+/// Ratio between the 10-90% rise time of a Gaussian edge and its
+/// standard deviation
+const GAUSSIAN_RISE_TIME_RATIO: f64 = 2.563;
+
+/// Settings for a TDR analysis
+#[derive(Debug, Clone)]
+pub struct TdrOptions {
+    /// Impedance of the source (and of the reference line)
+    pub reference_impedance: f64,
+    /// 10-90% rise time of the incident step (s)
+    pub rise_time: f64,
+    /// Propagation velocity along the line (m/s)
+    pub velocity: f64,
+    /// Time between output samples (s)
+    pub time_step: f64,
+    /// Number of output samples
+    pub num_points: usize,
+}
+
+impl Default for TdrOptions {
+    /// A 50 Ohm, 35 ps instrument looking into FR4 (about half the
+    /// speed of light)
+    fn default() -> Self {
+        Self {
+            reference_impedance: 50.0,
+            rise_time: 35e-12,
+            velocity: 1.5e8,
+            time_step: 5e-12,
+            num_points: 1024,
+        }
+    }
+}
+
+/// Reflected waveform and the impedance profile derived from it
+#[derive(Debug, Clone)]
+pub struct TdrResult {
+    /// Time after the incident edge reaches the port (s)
+    pub time: Vec<f64>,
+    /// Distance along the line (m)
+    pub distance: Vec<f64>,
+    /// Reflected voltage divided by the incident step amplitude
+    pub reflection: Vec<f64>,
+    /// Impedance seen at each distance (Ohm)
+    pub impedance: Vec<f64>,
+}
+
+pub struct TdrAnalysis<'a> {
+    circuit: &'a Circuit,
+    term_pos: usize,
+    term_neg: usize,
+    options: TdrOptions,
+}
+
+impl<'a> TdrAnalysis<'a> {
+    /// Make a TDR analysis looking into the port between term_pos and
+    /// term_neg. The AC specifications of the sources in the circuit
+    /// are ignored.
+    pub fn new(
+        circuit: &'a Circuit,
+        term_pos: usize,
+        term_neg: usize,
+        options: TdrOptions,
+    ) -> Self {
+        Self {
+            circuit,
+            term_pos,
+            term_neg,
+            options,
+        }
+    }
+
+    /// The circuit driven by the instrument: a unit AC source in series
+    /// with the reference impedance, connected across the port.
+    fn test_circuit(&self) -> Circuit {
+        let mut circuit = self.circuit.clone();
+        for instance in circuit.instances_mut() {
+            match &mut instance.component {
+                Component::IndependentVoltageSource { ac, .. }
+                | Component::IndependentCurrentSource { ac, .. } => *ac = AcSpec::default(),
+                _ => {}
+            }
+        }
+        let source_node = circuit.num_voltage_nodes() + 1;
+        let current_edge = circuit.num_current_edges();
+        circuit.add_component(
+            "vtdr",
+            Component::IndependentVoltageSource {
+                term_pos: source_node,
+                term_neg: self.term_neg,
+                current_edge,
+                voltage: 0.0,
+                ac: AcSpec::new(1.0, 0.0),
+                waveform: None,
+            },
+        );
+        circuit.add_component(
+            "rtdr",
+            Component::Resistor {
+                term_1: source_node,
+                term_2: self.term_pos,
+                current_edge: None,
+                resistance: self.options.reference_impedance,
+            },
+        );
+        circuit
+    }
+
+    /// Reflection coefficient of the port at the frequency
+    fn reflection_coefficient(&self, ac: &LinearAcAnalysis, frequency: f64) -> Complex<f64> {
+        let (voltages, _) = ac.solve(frequency);
+        let voltage = |n: usize| {
+            if n == 0 {
+                Complex::new(0.0, 0.0)
+            } else {
+                voltages[n - 1]
+            }
+        };
+        // The port voltage is half the source voltage when matched
+        2.0 * (voltage(self.term_pos) - voltage(self.term_neg)) - 1.0
+    }
+
+    pub fn run(&self) -> TdrResult {
+        let TdrOptions {
+            reference_impedance,
+            rise_time,
+            velocity,
+            time_step,
+            num_points: n,
+        } = self.options;
+        let circuit = self.test_circuit();
+        let ac = LinearAcAnalysis::new(&circuit);
+
+        // Delay the edge so that the Gaussian smoothing does not wrap
+        // around to the end of the record
+        let delay = 3.0 * rise_time;
+        let sigma = rise_time / GAUSSIAN_RISE_TIME_RATIO;
+        let df = 1.0 / (n as f64 * time_step);
+
+        // Spectrum of the impulse response of the reflection, for the
+        // non-negative frequencies. The DC point is evaluated at a very
+        // low frequency instead, where capacitors still have a finite
+        // impedance.
+        let spectrum: Vec<Complex<f64>> = (0..=n / 2)
+            .map(|k| {
+                let f = if k == 0 { 1e-6 * df } else { k as f64 * df };
+                let edge = (-2.0 * (PI * sigma * f).powi(2)).exp();
+                let shift = Complex::from_polar(1.0, -2.0 * PI * f * delay);
+                self.reflection_coefficient(&ac, f) * edge * shift
+            })
+            .collect();
+
+        // Inverse DFT of a real signal, followed by a running sum to
+        // turn the impulse response into the step response
+        let mut reflection = Vec::with_capacity(n);
+        let mut step = 0.0;
+        for m in 0..n {
+            let mut sample = spectrum[0].re;
+            for (k, value) in spectrum.iter().enumerate().skip(1) {
+                let weight = if 2 * k == n { 1.0 } else { 2.0 };
+                let phase = 2.0 * PI * (k * m) as f64 / n as f64;
+                sample += weight * (value * Complex::from_polar(1.0, phase)).re;
+            }
+            step += sample / n as f64;
+            reflection.push(step);
+        }
+
+        let time: Vec<f64> = (0..n).map(|m| m as f64 * time_step - delay).collect();
+        let distance = time.iter().map(|t| velocity * t / 2.0).collect();
+        let impedance = reflection
+            .iter()
+            .map(|rho| reference_impedance * (1.0 + rho) / (1.0 - rho))
+            .collect();
+        TdrResult {
+            time,
+            distance,
+            reflection,
+            impedance,
+        }
+    }
+}
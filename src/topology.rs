@@ -0,0 +1,343 @@
+//! Circuit topology checks
+//!
+//! Checks on the connectivity of a circuit which would otherwise
+//! show up as a singular MNA matrix at solve time. The electrical rule
+//! check ([check], or [Circuit::check]) runs all of them, and returns
+//! each problem it finds as a [Violation].
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+
+use crate::circuit::Circuit;
+use crate::component::Component;
+
+/// Whether the component fixes the voltage between its terminals at
+/// DC (ideal and controlled voltage sources and current probes, and
+/// inductors, which are short circuits)
+fn is_voltage_branch(component: &Component) -> bool {
+    matches!(
+        component,
+        Component::IndependentVoltageSource { .. }
+            | Component::VoltageControlledVoltageSource { .. }
+            | Component::CurrentControlledVoltageSource { .. }
+            | Component::CurrentProbe { .. }
+            | Component::MeasurementProbe {
+                current_edge: Some(_),
+                ..
+            }
+            | Component::Inductor { .. }
+            | Component::SaturableInductor { .. }
+    )
+}
+
+/// Spanning forest of the voltage branches added so far
+struct Forest {
+    /// For each node, the (neighbour node, instance index) of each tree edge
+    adjacency: Vec<Vec<(usize, usize)>>,
+}
+
+impl Forest {
+    /// Path of instance indices from n1 to n2 through the forest, if
+    /// the nodes are connected
+    fn path(&self, n1: usize, n2: usize) -> Option<Vec<usize>> {
+        let mut previous = vec![None; self.adjacency.len()];
+        let mut visited = vec![false; self.adjacency.len()];
+        let mut queue = VecDeque::from([n1]);
+        visited[n1] = true;
+        while let Some(n) = queue.pop_front() {
+            if n == n2 {
+                let mut path = Vec::new();
+                let mut node = n2;
+                while let Some((from, index)) = previous[node] {
+                    path.push(index);
+                    node = from;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &(next, index) in &self.adjacency[n] {
+                if !visited[next] {
+                    visited[next] = true;
+                    previous[next] = Some((n, index));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Find loops made only of voltage branches (ideal voltage sources
+/// and inductors). Two voltage sources in parallel are a loop of two
+/// branches. Each loop is a list of instance indices into the circuit,
+/// where the last instance is the one that closes the loop.
+pub fn voltage_loops(circuit: &Circuit) -> Vec<Vec<usize>> {
+    let mut forest = Forest {
+        adjacency: vec![Vec::new(); circuit.num_voltage_nodes() + 1],
+    };
+    let mut loops = Vec::new();
+    for (index, instance) in circuit.instances().iter().enumerate() {
+        if !is_voltage_branch(&instance.component) {
+            continue;
+        }
+        let terminals = instance.component.terminals();
+        let (n1, n2) = (terminals[0], terminals[1]);
+        match forest.path(n1, n2) {
+            Some(mut path) => {
+                path.push(index);
+                loops.push(path);
+            }
+            None => {
+                forest.adjacency[n1].push((n2, index));
+                forest.adjacency[n2].push((n1, index));
+            }
+        }
+    }
+    loops
+}
+
+/// A problem found by the electrical rule check
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// A node (by name, or number if it has none) connected to fewer
+    /// than two terminals
+    FloatingNode { node: String, terminals: usize },
+    /// Nodes connected to each other but to ground only through
+    /// capacitors, current sources or controlling inputs
+    NoDcPath { nodes: Vec<String> },
+    /// A voltage source (or inductor) with both terminals on one node
+    ShortedSource { instance: String },
+    /// A loop of voltage sources and inductors, listed in the order of
+    /// the circuit
+    VoltageLoop { instances: Vec<String> },
+    /// Current sources which are the only branches between a group of
+    /// nodes and the rest of the circuit
+    CurrentCutset {
+        instances: Vec<String>,
+        nodes: Vec<String>,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |nodes: &[String]| nodes.join(", ");
+        match self {
+            Self::FloatingNode { node, terminals } => {
+                let terminals = match terminals {
+                    0 => "no terminals",
+                    _ => "only one terminal",
+                };
+                write!(f, "node {node} is floating (connected to {terminals})")
+            }
+            Self::NoDcPath { nodes } => {
+                write!(f, "no DC path to ground from nodes {}", list(nodes))
+            }
+            Self::ShortedSource { instance } => write!(f, "{instance} is shorted"),
+            Self::VoltageLoop { instances } => write!(
+                f,
+                "loop of voltage sources and inductors: {}",
+                instances.join(", ")
+            ),
+            Self::CurrentCutset { instances, nodes } => write!(
+                f,
+                "current sources {} are the only path to nodes {}",
+                instances.join(", "),
+                list(nodes)
+            ),
+        }
+    }
+}
+
+/// Whether the component is a current source, whose branch does not
+/// fix the voltage across it or conduct according to it
+fn is_current_source(component: &Component) -> bool {
+    matches!(
+        component,
+        Component::IndependentCurrentSource { .. }
+            | Component::VoltageControlledCurrentSource { .. }
+            | Component::CurrentControlledCurrentSource { .. }
+    )
+}
+
+/// The pairs of terminals a component connects, through a DC path or
+/// (if dc is false) through any branch. Controlling inputs and the
+/// inputs of bridges are not connected to anything, and devices that
+/// are off in DC analysis are taken to conduct.
+fn connections(component: &Component, dc: bool) -> Vec<(usize, usize)> {
+    let terminals = component.terminals();
+    let chain = || {
+        terminals
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .collect()
+    };
+    match *component {
+        Component::Capacitor { .. }
+        | Component::SemiconductorCapacitor { .. }
+        | Component::Crystal { .. } => {
+            if dc {
+                Vec::new()
+            } else {
+                chain()
+            }
+        }
+        Component::Urc {
+            term_1,
+            term_2,
+            term_cap,
+            ..
+        } => {
+            if dc {
+                vec![(term_1, term_2)]
+            } else {
+                vec![(term_1, term_2), (term_2, term_cap)]
+            }
+        }
+        Component::Relay {
+            coil_pos,
+            coil_neg,
+            contact_1,
+            contact_2,
+            ..
+        } => vec![(coil_pos, coil_neg), (contact_1, contact_2)],
+        Component::MeasurementProbe {
+            current_edge: None, ..
+        }
+        | Component::AdcBridge { .. }
+        | Component::LogicGate { .. } => Vec::new(),
+        Component::VoltageControlledVoltageSource {
+            term_pos, term_neg, ..
+        }
+        | Component::CurrentControlledVoltageSource {
+            term_pos, term_neg, ..
+        } => vec![(term_pos, term_neg)],
+        _ if is_current_source(component) => Vec::new(),
+        _ => chain(),
+    }
+}
+
+/// Groups of nodes connected by the given pairs, other than the group
+/// with ground, each in order of node
+fn isolated_groups(num_nodes: usize, pairs: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    let mut group: Vec<usize> = (0..=num_nodes).collect();
+    fn root(group: &mut [usize], mut node: usize) -> usize {
+        while group[node] != node {
+            group[node] = group[group[node]];
+            node = group[node];
+        }
+        node
+    }
+    for &(n1, n2) in pairs {
+        let (r1, r2) = (root(&mut group, n1), root(&mut group, n2));
+        // Keep ground as the root of its group
+        group[r1.max(r2)] = r1.min(r2);
+    }
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for node in 1..=num_nodes {
+        let r = root(&mut group, node);
+        if r != 0 {
+            groups.entry(r).or_default().push(node);
+        }
+    }
+    groups.into_values().collect()
+}
+
+/// Check the connectivity of a circuit for the problems that would
+/// make its MNA matrix singular
+///
+/// Every node must be connected to at least two terminals, and have a
+/// DC path to ground. A voltage source (or inductor) must not be
+/// shorted, or be part of a loop of them, and a group of nodes must
+/// not be connected to the rest of the circuit only by current
+/// sources. The circuit should already be elaborated. Nodes with no
+/// terminals at all are reported as floating rather than as having no
+/// DC path, and groups of nodes cut off by current sources are not
+/// also reported as having no DC path. Nodes are reported by their
+/// names in the circuit, or by number if they have none (such as the
+/// internal nodes of the elaborated components).
+pub fn check(circuit: &Circuit) -> Vec<Violation> {
+    let num_nodes = circuit.num_voltage_nodes();
+    let instances = circuit.instances();
+    let mut violations = Vec::new();
+    let name = |node: usize| {
+        circuit
+            .node_names()
+            .name(node)
+            .map_or_else(|| node.to_string(), str::to_string)
+    };
+    let names = |nodes: &[usize]| nodes.iter().map(|node| name(*node)).collect();
+
+    let mut terminals = vec![0; num_nodes + 1];
+    for instance in instances {
+        for node in instance.component.terminals() {
+            terminals[node] += 1;
+        }
+    }
+    for (node, &count) in terminals.iter().enumerate().skip(1) {
+        if count < 2 {
+            violations.push(Violation::FloatingNode {
+                node: name(node),
+                terminals: count,
+            });
+        }
+    }
+
+    for path in voltage_loops(circuit) {
+        let names: Vec<String> = path
+            .iter()
+            .map(|index| instances[*index].name.clone())
+            .collect();
+        if let [instance] = names.as_slice() {
+            violations.push(Violation::ShortedSource {
+                instance: instance.clone(),
+            });
+        } else {
+            violations.push(Violation::VoltageLoop { instances: names });
+        }
+    }
+
+    // Cutsets of current sources, from the groups of nodes cut off
+    // from ground when the current sources are removed
+    let pairs: Vec<(usize, usize)> = instances
+        .iter()
+        .flat_map(|instance| connections(&instance.component, false))
+        .collect();
+    let mut cut = vec![false; num_nodes + 1];
+    for nodes in isolated_groups(num_nodes, &pairs) {
+        let sources: Vec<String> = instances
+            .iter()
+            .filter(|instance| is_current_source(&instance.component))
+            .filter(|instance| {
+                let terminals = instance.component.terminals();
+                nodes.contains(&terminals[0]) != nodes.contains(&terminals[1])
+            })
+            .map(|instance| instance.name.clone())
+            .collect();
+        if !sources.is_empty() {
+            for &node in &nodes {
+                cut[node] = true;
+            }
+            violations.push(Violation::CurrentCutset {
+                instances: sources,
+                nodes: names(&nodes),
+            });
+        }
+    }
+
+    let pairs: Vec<(usize, usize)> = instances
+        .iter()
+        .flat_map(|instance| connections(&instance.component, true))
+        .collect();
+    for nodes in isolated_groups(num_nodes, &pairs) {
+        let nodes: Vec<usize> = nodes
+            .into_iter()
+            .filter(|node| terminals[*node] > 0)
+            .collect();
+        if !nodes.is_empty() && !nodes.iter().all(|node| cut[*node]) {
+            violations.push(Violation::NoDcPath {
+                nodes: names(&nodes),
+            });
+        }
+    }
+    violations
+}
@@ -0,0 +1,114 @@
+//! DC transfer function analysis
+//!
+//! The small-signal DC gain from an input source to an output voltage,
+//! with the resistance seen by the source and the resistance at the
+//! output, as for a SPICE `.TF` line. The circuit is linearised about
+//! its operating point, with capacitors open and inductors shorted.
+//!
+//! The gain and the output resistance both come from one solve of the
+//! adjoint system for the output (see [noise](mod@crate::noise)): the gain
+//! is the transfer from the value of the source, and the output
+//! resistance is the transfer from a unit current driven into the
+//! output, with the input source set to zero. The input resistance is
+//! found by driving the source with a unit value.
+
+use std::fmt;
+
+use crate::ac::{node_transfer, source_transfer, LinearAcAnalysis};
+use crate::circuit::Circuit;
+use crate::component::Component;
+use crate::dc::{explain, DcOptions};
+use crate::error::EsimError;
+
+/// Small-signal DC transfer function from a source to an output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransferFunction {
+    /// Change in the output voltage per unit change in the source
+    /// (a voltage gain, or a transresistance for a current source)
+    pub gain: f64,
+    /// Resistance seen by the source, looking into the circuit from its
+    /// terminals (infinite if it drives no current)
+    pub input_resistance: f64,
+    /// Resistance between the output nodes, with the source set to
+    /// zero
+    pub output_resistance: f64,
+}
+
+impl fmt::Display for TransferFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Transfer function: {:.6e}", self.gain)?;
+        writeln!(f, "Input resistance: {:.6e} ohm", self.input_resistance)?;
+        write!(f, "Output resistance: {:.6e} ohm", self.output_resistance)
+    }
+}
+
+/// Small-signal DC transfer function from an independent source (by
+/// name) to the voltage from output_pos to output_neg. Panics if the
+/// input is not an independent source (see [try_transfer_function]).
+pub fn transfer_function(
+    circuit: &Circuit,
+    output: (usize, usize),
+    input: &str,
+) -> TransferFunction {
+    transfer_function_with(&LinearAcAnalysis::new(circuit), output, input)
+        .unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Small-signal DC transfer function as for [transfer_function],
+/// returning an error if the circuit fails its check (see
+/// [Circuit::validate]), its operating point cannot be solved, the
+/// input is not an independent source, or an output node is not in
+/// the circuit
+pub fn try_transfer_function(
+    circuit: &Circuit,
+    output: (usize, usize),
+    input: &str,
+) -> Result<TransferFunction, EsimError> {
+    let analysis = LinearAcAnalysis::try_new(circuit, &DcOptions::default())?;
+    analysis.check_output(output)?;
+    transfer_function_with(&analysis, output, input).map_err(|error| explain(circuit, error))
+}
+
+fn transfer_function_with(
+    analysis: &LinearAcAnalysis,
+    (output_pos, output_neg): (usize, usize),
+    input: &str,
+) -> Result<TransferFunction, EsimError> {
+    let input = analysis.input_source(input)?.clone();
+    let adjoint = analysis
+        .assemble(0.0, false)
+        .try_solve_adjoint(output_pos, output_neg)?;
+    let gain = source_transfer(&adjoint, &input)
+        .expect("The input is an independent source")
+        .re;
+    let output_resistance =
+        (node_transfer(&adjoint, output_pos) - node_transfer(&adjoint, output_neg)).re;
+
+    let mut mna = analysis.assemble(0.0, false);
+    let input_resistance = match input {
+        Component::IndependentVoltageSource { current_edge, .. } => {
+            mna.add_series_voltage(current_edge, 1.0.into());
+            let (_, currents) = mna.try_solve()?;
+            // The edge current flows into the positive terminal, so the
+            // current the source drives into the circuit is its negative
+            -1.0 / currents[current_edge].re
+        }
+        Component::IndependentCurrentSource {
+            term_pos, term_neg, ..
+        } => {
+            mna.add_independent_current_source(term_pos, term_neg, 1.0.into());
+            let (voltages, _) = mna.try_solve()?;
+            let voltage = |node: usize| match node {
+                0 => 0.0,
+                n => voltages[n - 1].re,
+            };
+            voltage(term_neg) - voltage(term_pos)
+        }
+        _ => unreachable!("The input is an independent source"),
+    };
+    Ok(TransferFunction {
+        gain,
+        input_resistance,
+        output_resistance,
+    })
+}
@@ -0,0 +1,1992 @@
+//! Transient analysis
+//!
+//! The circuit is stepped in time from its DC operating point with a
+//! fixed time step (unless it is controlled, see below), using (by default) backward Euler companion
+//! models for the reactive components: a capacitor becomes a conductance C/h in
+//! parallel with a current source, and an inductor becomes a
+//! resistance L/h in series with a voltage source, both set from the
+//! solution at the previous time point. Sources follow their
+//! waveforms, and the operating point uses their values at time zero.
+//! Circuits with junctions are solved by Newton iteration at each time
+//! point, starting from the junction voltages of the previous one.
+//!
+//! The time points of a fixed step are the multiples of the step, with
+//! a shorter step inserted to land on each breakpoint of the sources
+//! that falls between two of them (such as a corner of a PULSE or PWL
+//! waveform), so that no corner is stepped over. Each time point is
+//! taken as the multiple or the breakpoint itself, rather than as the
+//! sum of the steps before it, so that rounding does not accumulate.
+//!
+//! Switching components (thyristors, IGBTs and relay contacts) change
+//! state as events.
+//! After each time point is solved, the state of every switch is
+//! checked against the solution; if any changes, the event is
+//! recorded and the time point is solved again with the new states.
+//! Batteries integrate their state of charge, and fuses their i²t,
+//! from their current at each time point.
+//!
+//! Logic gates are simulated by events (see [crate::digital]), with
+//! the logic advanced to each time point along with the switches.
+//! A change in the level of a DAC bridge is handled like a switching
+//! event.
+//!
+//! Changes to components can be scheduled up front, to simulate a
+//! scenario in segments (such as a load resistor changing value at
+//! 1 ms, then a component failing open at 2 ms). Each change is made
+//! at the time point nearest its time, before that time point is
+//! solved, and the states of the other components carry on across it.
+//!
+//! Initial conditions (as SPICE `.IC`) can be given for node voltages,
+//! which are held at them while the operating point is solved, and
+//! then released. With the option to use the initial conditions
+//! (SPICE `UIC`), the operating point is skipped altogether: the
+//! analysis starts from the initial node voltages and inductor
+//! currents, with every other voltage and current zero, which is how
+//! an oscillator is started away from its (unstable) operating point.
+//!
+//! The Newton iteration of each time point can be inspected under a
+//! debugger (see [crate::debugger]).
+//!
+//! The signals of measurement probes are computed at the end, through
+//! the bandwidth and averaging of each probe.
+//!
+//! The trapezoidal rule can be chosen instead of backward Euler. It is
+//! second order, so it follows LC resonances without the artificial
+//! damping of backward Euler, but it does not damp anything: after a
+//! discontinuity it rings from one time point to the next. The first
+//! time point, and each one after an event or a scheduled change, is
+//! therefore taken by backward Euler. Ringing is also detected as a
+//! node voltage whose change alternates in sign over several time
+//! points, and is damped by taking the next time point by backward
+//! Euler; the times of these are in the result.
+//!
+//! Gear's method (the backward differentiation formula) of order 2
+//! is also second order, but damps high frequencies like backward
+//! Euler, so it suits stiff circuits such as switching converters,
+//! whose fast switching transients would otherwise need a small time
+//! step. It is started by backward Euler (Gear's method of order 1)
+//! in the same way.
+//!
+//! Each method is an [Integrator], which gives the coefficients of a
+//! linear multistep formula for the derivative of a capacitor voltage
+//! or an inductor current, from which the companion models are
+//! built. Other schemes can be added by implementing the trait, and
+//! used with [TransientAnalysis::with_integrator].
+//!
+//! The time step can instead be controlled by the local truncation
+//! error, as in SPICE, up to the time step of the options. The error
+//! in the charge of each capacitor, and the flux of each inductor, is
+//! estimated from a divided difference over the latest time points,
+//! and the next step is the largest that keeps every error within its
+//! tolerance; a step that turns out too long is rejected, and taken
+//! again with the shorter step. The tolerances are per quantity (see
+//! [StepControl]): charge against a current tolerance, with its own
+//! floor `chgtol`, and flux against a voltage tolerance, with `fluxtol`,
+//! so a femtoamp branch is not held to the tolerance of an amp branch,
+//! or the reverse. The step is shortened to land on each scheduled
+//! change, which is then made at its exact time, and on each
+//! breakpoint of the sources (such as the edges of a pulse and the
+//! points of a PWL waveform), so that no edge is stepped over; the step
+//! after a breakpoint is taken by backward Euler.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::circuit::{Circuit, Instance};
+use crate::component::{Component, NOMINAL_TEMPERATURE};
+use crate::dc::{explain, hold_nodes, not_converged, solve_nodesets, DcOptions, NewtonOptions};
+use crate::debugger::{Breakpoint, DebugSession, Debugger, NewtonState, Stamp};
+use crate::digital::LogicSimulator;
+use crate::error::{invalid, no_instance, EsimError};
+use crate::evaluation::JunctionCache;
+use crate::fault::{faulty_component, FaultKind};
+use crate::fourier::{fourier, try_fourier, FourierOptions, FourierResult};
+use crate::mna::Mna;
+use crate::node::NodeNames;
+use crate::solver::{Linearisation, NonlinearSolver};
+use crate::sparse::CachedSolver;
+
+/// Number of times a time point is solved again after switching
+/// events before the states are accepted as they are
+const MAX_EVENT_ITERATIONS: usize = 10;
+
+/// Number of successive time points at which the change in a node
+/// voltage alternates in sign before it is taken as trapezoidal
+/// ringing
+const RINGING_STEPS: usize = 3;
+
+/// Fraction of the time step within which a scheduled change is made
+/// at a time point when the step is controlled (the step is shortened
+/// to reach each change)
+const CHANGE_RESOLUTION: f64 = 1e-6;
+
+/// First controlled time step, as a fraction of the largest
+const INITIAL_STEP: f64 = 0.1;
+
+/// Most a controlled time step grows from one time point to the next
+const MAX_STEP_GROWTH: f64 = 2.0;
+
+/// A controlled time step is rejected if the truncation error allows
+/// less than this fraction of it
+const STEP_REJECTION: f64 = 0.9;
+
+/// Coefficients of a linear multistep formula, which gives the
+/// derivative of a quantity x at time point n from its value there,
+/// its values at the two time points before, and its derivative at
+/// the previous time point:
+///
+/// x'(n) = (a\[0\] x(n) - a\[1\] x(n-1) - a\[2\] x(n-2)) / h - b x'(n-1)
+///
+/// where h is the time step from time point n-1 to n.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MultistepCoefficients {
+    pub a: [f64; 3],
+    pub b: f64,
+}
+
+/// A scheme for integrating the reactive components over a time step
+pub trait Integrator: fmt::Debug + Send + Sync {
+    /// Coefficients of a time step, given its ratio to the previous
+    /// time step (which is one unless the step is controlled)
+    fn coefficients(&self, ratio: f64) -> MultistepCoefficients;
+
+    /// Order of accuracy
+    fn order(&self) -> usize;
+
+    /// Constant of the local truncation error, relative to the
+    /// divided difference of order one more than the scheme (as used
+    /// by SPICE)
+    fn error_constant(&self) -> f64;
+
+    /// Whether the scheme damps high frequencies. A scheme that does
+    /// not is checked for ringing.
+    fn damped(&self) -> bool;
+}
+
+/// Backward Euler, which is first order, and damps high frequencies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BackwardEuler;
+
+impl Integrator for BackwardEuler {
+    fn coefficients(&self, _ratio: f64) -> MultistepCoefficients {
+        MultistepCoefficients {
+            a: [1.0, 1.0, 0.0],
+            b: 0.0,
+        }
+    }
+
+    fn order(&self) -> usize {
+        1
+    }
+
+    fn error_constant(&self) -> f64 {
+        0.5
+    }
+
+    fn damped(&self) -> bool {
+        true
+    }
+}
+
+/// The trapezoidal rule, which is second order, without damping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Trapezoidal;
+
+impl Integrator for Trapezoidal {
+    fn coefficients(&self, _ratio: f64) -> MultistepCoefficients {
+        MultistepCoefficients {
+            a: [2.0, 2.0, 0.0],
+            b: 1.0,
+        }
+    }
+
+    fn order(&self) -> usize {
+        2
+    }
+
+    fn error_constant(&self) -> f64 {
+        1.0 / 12.0
+    }
+
+    fn damped(&self) -> bool {
+        false
+    }
+}
+
+/// Gear's method (the backward differentiation formula) of order 1
+/// (which is backward Euler) or 2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gear {
+    order: usize,
+}
+
+impl Gear {
+    /// Panics if the order is not 1 or 2
+    pub fn new(order: usize) -> Self {
+        assert!(
+            (1..=2).contains(&order),
+            "Gear's method is only of order 1 or 2"
+        );
+        Self { order }
+    }
+}
+
+impl Integrator for Gear {
+    fn coefficients(&self, ratio: f64) -> MultistepCoefficients {
+        let a = match self.order {
+            1 => [1.0, 1.0, 0.0],
+            // The second order formula through the three time points,
+            // which are equally spaced if the ratio is one
+            _ => [
+                (1.0 + 2.0 * ratio) / (1.0 + ratio),
+                1.0 + ratio,
+                -ratio * ratio / (1.0 + ratio),
+            ],
+        };
+        MultistepCoefficients { a, b: 0.0 }
+    }
+
+    fn order(&self) -> usize {
+        self.order
+    }
+
+    fn error_constant(&self) -> f64 {
+        match self.order {
+            1 => 0.5,
+            _ => 2.0 / 9.0,
+        }
+    }
+
+    fn damped(&self) -> bool {
+        true
+    }
+}
+
+/// Integration method of the reactive components
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegrationMethod {
+    /// First order, and damps high frequencies
+    #[default]
+    BackwardEuler,
+    /// Second order, without damping
+    Trapezoidal,
+    /// Gear's method of order 1 or 2, which damps high frequencies
+    Gear { order: usize },
+}
+
+impl IntegrationMethod {
+    /// The scheme of the method. Panics if the order of Gear's method
+    /// is not 1 or 2.
+    pub fn integrator(self) -> Arc<dyn Integrator> {
+        match self {
+            Self::BackwardEuler => Arc::new(BackwardEuler),
+            Self::Trapezoidal => Arc::new(Trapezoidal),
+            Self::Gear { order } => Arc::new(Gear::new(order)),
+        }
+    }
+}
+
+/// Tolerances of the local truncation error, which control the time
+/// step. Each quantity is weighted by its own tolerances: the error
+/// in the charge of a capacitor is compared with the current
+/// tolerances, and that in the flux of an inductor with the voltage
+/// tolerances, so that neither sets the step for the other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepControl {
+    /// Relative tolerance of each quantity
+    pub reltol: f64,
+    /// Absolute tolerance of capacitor currents (A)
+    pub abstol: f64,
+    /// Absolute tolerance of inductor voltages (V)
+    pub vntol: f64,
+    /// Charge (C) below which the tolerance of a capacitor charge no
+    /// longer falls with the charge
+    pub chgtol: f64,
+    /// Flux (Wb) below which the tolerance of an inductor flux no
+    /// longer falls with the flux
+    pub fluxtol: f64,
+    /// Factor by which the truncation error is taken to be
+    /// overestimated
+    pub trtol: f64,
+    /// Smallest time step, which is taken whatever the error
+    pub min_step: f64,
+}
+
+impl Default for StepControl {
+    /// The SPICE defaults
+    fn default() -> Self {
+        Self {
+            reltol: 1e-3,
+            abstol: 1e-12,
+            vntol: 1e-6,
+            chgtol: 1e-14,
+            fluxtol: 1e-14,
+            trtol: 7.0,
+            min_step: 1e-18,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransientOptions {
+    /// Fixed time step, or the largest time step if it is controlled
+    pub time_step: f64,
+    pub stop_time: f64,
+    pub method: IntegrationMethod,
+    /// Control of the time step by the truncation error, if the step
+    /// is not fixed
+    pub step_control: Option<StepControl>,
+    /// Whether to start from the initial conditions instead of the
+    /// operating point (SPICE `UIC`)
+    pub use_initial_conditions: bool,
+    /// Options for solving the operating point the analysis starts from
+    pub dc: DcOptions,
+    /// Convergence settings of the Newton iteration at each time point
+    pub newton: NewtonOptions,
+}
+
+impl TransientOptions {
+    /// Options using backward Euler with a fixed time step
+    pub fn new(time_step: f64, stop_time: f64) -> Self {
+        Self {
+            time_step,
+            stop_time,
+            method: IntegrationMethod::BackwardEuler,
+            step_control: None,
+            use_initial_conditions: false,
+            dc: DcOptions::default(),
+            newton: NewtonOptions::default(),
+        }
+    }
+
+    pub fn with_method(self, method: IntegrationMethod) -> Self {
+        Self { method, ..self }
+    }
+
+    /// Control the time step, up to the time step of the options
+    pub fn with_step_control(self, step_control: StepControl) -> Self {
+        Self {
+            step_control: Some(step_control),
+            ..self
+        }
+    }
+
+    pub fn with_use_initial_conditions(self, use_initial_conditions: bool) -> Self {
+        Self {
+            use_initial_conditions,
+            ..self
+        }
+    }
+
+    /// Solve the operating point with DC options
+    pub fn with_dc_options(self, dc: DcOptions) -> Self {
+        Self { dc, ..self }
+    }
+
+    /// Converge the Newton iteration at each time point by other
+    /// settings
+    pub fn with_newton_options(self, newton: NewtonOptions) -> Self {
+        Self { newton, ..self }
+    }
+}
+
+/// A change of state of a component during the analysis
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransientEvent {
+    pub time: f64,
+    pub instance: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TransientResult {
+    pub time: Vec<f64>,
+    /// Node voltages at each time point, excluding ground (node n is
+    /// at index n-1)
+    pub voltages: Vec<Vec<f64>>,
+    /// Edge currents at each time point
+    pub currents: Vec<Vec<f64>>,
+    pub events: Vec<TransientEvent>,
+    /// Time points at which trapezoidal ringing was detected, so that
+    /// the next time point was taken by backward Euler
+    pub damped: Vec<f64>,
+    /// Number of time steps rejected by the step control, and taken
+    /// again with a smaller step
+    pub rejected: usize,
+    /// Measured signals of the measurement probes, by name
+    probes: HashMap<String, Vec<f64>>,
+    /// Internal states of components, by instance and state name
+    states: HashMap<String, Vec<f64>>,
+    nodes: NodeNames,
+    /// Warnings of the analysis, such as a time point whose Newton
+    /// iteration did not converge
+    pub warnings: Vec<String>,
+}
+
+impl TransientResult {
+    /// Voltage of a node at every time point (zero for ground)
+    pub fn voltage(&self, node: usize) -> Vec<f64> {
+        self.voltages
+            .iter()
+            .map(|voltages| node_voltage(voltages, node))
+            .collect()
+    }
+
+    /// Voltage of a node by name (see [crate::node]) at every time
+    /// point, if there is a node with the name in the circuit
+    pub fn node_voltage(&self, name: &str) -> Option<Vec<f64>> {
+        self.nodes
+            .get(name)
+            .filter(|node| *node <= self.voltages.first().map_or(0, Vec::len))
+            .map(|node| self.voltage(node))
+    }
+
+    /// Current of an edge at every time point
+    pub fn current(&self, edge: usize) -> Vec<f64> {
+        self.currents
+            .iter()
+            .map(|currents| currents[edge])
+            .collect()
+    }
+
+    /// Signal measured by a measurement probe at every time point
+    pub fn probe(&self, name: &str) -> Option<&[f64]> {
+        self.probes.get(name).map(Vec::as_slice)
+    }
+
+    /// Internal state of a component at every time point, by instance
+    /// and state name (such as `b1.soc` for the state of charge of
+    /// battery b1, or `f1.i2t` for the i²t of fuse f1)
+    pub fn state(&self, name: &str) -> Option<&[f64]> {
+        self.states.get(name).map(Vec::as_slice)
+    }
+
+    /// Fourier analysis of the voltage of a node over its last period
+    /// at a fundamental frequency (in Hz)
+    pub fn fourier(
+        &self,
+        node: usize,
+        fundamental: f64,
+        options: &FourierOptions,
+    ) -> FourierResult {
+        fourier(
+            &format!("v({node})"),
+            &self.time,
+            &self.voltage(node),
+            fundamental,
+            options,
+        )
+    }
+
+    /// Fourier analysis of the voltage of a node as for
+    /// [TransientResult::fourier], returning an error if the node is
+    /// not in the circuit or the result is shorter than a period (see
+    /// [try_fourier])
+    pub fn try_fourier(
+        &self,
+        node: usize,
+        fundamental: f64,
+        options: &FourierOptions,
+    ) -> Result<FourierResult, EsimError> {
+        if node > self.voltages.first().map_or(0, Vec::len) {
+            return Err(invalid(format!("No node {node} in the result")));
+        }
+        try_fourier(
+            &format!("v({node})"),
+            &self.time,
+            &self.voltage(node),
+            fundamental,
+            options,
+        )
+    }
+}
+
+/// A change to a component during the analysis
+#[derive(Debug, Clone, PartialEq)]
+pub enum ComponentChange {
+    /// Set the value of a resistor, capacitor or inductor, or the
+    /// value of an independent source (which then holds the value,
+    /// rather than following its waveform)
+    Value(f64),
+    /// Replace a two-terminal component with an open circuit
+    Open,
+    /// Replace a two-terminal component with a short circuit
+    Short,
+    /// Replace the component with another, which must have the same
+    /// terminals and current edges
+    Replace(Box<Component>),
+}
+
+impl ComponentChange {
+    /// The changed component, or None if the change cannot be made to
+    /// it
+    pub(crate) fn apply(&self, component: &Component) -> Option<Component> {
+        match self {
+            Self::Value(value) => {
+                let mut component = component.clone();
+                match component {
+                    Component::Resistor {
+                        ref mut resistance, ..
+                    } => *resistance = *value,
+                    Component::Capacitor {
+                        ref mut capacitance,
+                        ..
+                    } => *capacitance = *value,
+                    Component::Inductor {
+                        ref mut inductance, ..
+                    } => *inductance = *value,
+                    Component::IndependentVoltageSource {
+                        voltage: ref mut source,
+                        ref mut waveform,
+                        ..
+                    }
+                    | Component::IndependentCurrentSource {
+                        current: ref mut source,
+                        ref mut waveform,
+                        ..
+                    } => {
+                        *source = *value;
+                        *waveform = None;
+                    }
+                    _ => return None,
+                }
+                Some(component)
+            }
+            Self::Open => faulty_component(component, FaultKind::Open),
+            Self::Short => faulty_component(component, FaultKind::Short),
+            Self::Replace(replacement) => (replacement.terminals() == component.terminals()
+                && replacement.current_edges() == component.current_edges())
+            .then(|| (**replacement).clone()),
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Self::Value(_) => "value changed",
+            Self::Open => "opened",
+            Self::Short => "shorted",
+            Self::Replace(_) => "replaced",
+        }
+    }
+}
+
+/// A change to a named instance, scheduled at a time
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledChange {
+    pub time: f64,
+    pub instance: String,
+    pub change: ComponentChange,
+}
+
+/// State of a component that changes during the analysis
+#[derive(Debug, Clone, Copy, Default)]
+struct DeviceState {
+    /// Whether a switch is on, or a DAC bridge is high
+    on: bool,
+    /// Time of the last turn-off of a switch, and the current before it
+    turned_off: Option<(f64, f64)>,
+    /// State of charge of a battery
+    soc: f64,
+    /// Integral of the square of the current through a fuse
+    i2t: f64,
+    /// Whether a relay coil is energized, and the time it last
+    /// changed
+    energized: bool,
+    energized_at: f64,
+    /// Current of a capacitor at the last time point, for formulas
+    /// that use the previous derivative
+    current: f64,
+}
+
+/// Append the internal state of a battery or fuse to the recorded
+/// states
+fn record_state(
+    recorded: &mut HashMap<String, Vec<f64>>,
+    instance: &Instance,
+    state: &DeviceState,
+) {
+    let (name, value) = match instance.component {
+        Component::Battery { .. } => ("soc", state.soc),
+        Component::Fuse { .. } => ("i2t", state.i2t),
+        _ => return,
+    };
+    recorded
+        .entry(format!("{}.{name}", instance.name))
+        .or_default()
+        .push(value);
+}
+
+fn node_voltage(voltages: &[f64], node: usize) -> f64 {
+    if node == 0 {
+        0.0
+    } else {
+        voltages[node - 1]
+    }
+}
+
+/// Check the change in each node voltage between two time points
+/// against the previous change, counting the successive time points
+/// at which the sign alternates. Returns whether any node has
+/// alternated for long enough to be ringing, in which case the counts
+/// are reset.
+fn detect_ringing(
+    previous: &[f64],
+    voltages: &[f64],
+    changes: &mut Vec<f64>,
+    alternations: &mut Vec<usize>,
+) -> bool {
+    changes.resize(voltages.len(), 0.0);
+    alternations.resize(voltages.len(), 0);
+    let mut ringing = false;
+    for (node, (v, old)) in voltages.iter().zip(previous).enumerate() {
+        let change = v - old;
+        let tolerance = 1e-6 + 1e-3 * v.abs();
+        if change * changes[node] < 0.0 && change.abs() > tolerance {
+            alternations[node] += 1;
+        } else {
+            alternations[node] = 0;
+        }
+        changes[node] = change;
+        ringing |= alternations[node] >= RINGING_STEPS;
+    }
+    if ringing {
+        alternations.fill(0);
+    }
+    ringing
+}
+
+/// Divided difference of the highest order of values at times,
+/// computed in place
+fn divided_difference(times: &[f64], values: &mut [f64]) -> f64 {
+    for order in 1..values.len() {
+        for j in 0..values.len() - order {
+            values[j] = (values[j] - values[j + 1]) / (times[j] - times[j + order]);
+        }
+    }
+    values[0]
+}
+
+/// The state of the Newton iteration, carried from one time point to
+/// the next
+struct NewtonContext<'a> {
+    junctions: Vec<f64>,
+    cache: JunctionCache,
+    /// The linear solver, which refactorizes the matrix of each time
+    /// point with the structure of the last
+    linear: CachedSolver<f64>,
+    debug: Option<DebugSession<'a>>,
+    /// Warnings of the time points solved so far
+    warnings: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct TransientAnalysis {
+    /// The elaborated circuit
+    circuit: Circuit,
+    options: TransientOptions,
+    /// Scheme of the integration method of the options, unless
+    /// another has been given
+    integrator: Arc<dyn Integrator>,
+    /// Scheduled changes, in order of time
+    changes: Vec<ScheduledChange>,
+    /// Initial node voltages, and currents of inductive edges
+    initial_voltages: Vec<(usize, f64)>,
+    initial_currents: Vec<(usize, f64)>,
+    /// Approximate node voltages for the operating point
+    nodesets: Vec<(usize, f64)>,
+    /// The time step being taken, and its ratio to the previous one
+    step: f64,
+    ratio: f64,
+}
+
+impl TransientAnalysis {
+    pub fn new(circuit: &Circuit, options: TransientOptions) -> Self {
+        Self {
+            circuit: circuit.elaborate(),
+            options,
+            integrator: options.method.integrator(),
+            changes: Vec::new(),
+            initial_voltages: Vec::new(),
+            initial_currents: Vec::new(),
+            nodesets: Vec::new(),
+            step: options.time_step,
+            ratio: 1.0,
+        }
+    }
+
+    /// Integrate with a scheme other than those of [IntegrationMethod]
+    pub fn with_integrator(self, integrator: impl Integrator + 'static) -> Self {
+        Self {
+            integrator: Arc::new(integrator),
+            ..self
+        }
+    }
+
+    /// Schedule a change to a named instance at a time
+    ///
+    /// Panics if there is no such instance, or the change cannot be
+    /// made to it (see [TransientAnalysis::try_schedule]).
+    pub fn schedule(self, time: f64, instance: &str, change: ComponentChange) -> Self {
+        self.try_schedule(time, instance, change)
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Schedule a change as for [TransientAnalysis::schedule],
+    /// returning an error if there is no such instance, or the change
+    /// cannot be made to it
+    pub fn try_schedule(
+        mut self,
+        time: f64,
+        instance: &str,
+        change: ComponentChange,
+    ) -> Result<Self, EsimError> {
+        let component = self
+            .circuit
+            .instances()
+            .iter()
+            .find(|i| i.name == instance)
+            .map(|i| &i.component)
+            .ok_or_else(|| no_instance(instance))?;
+        if change.apply(component).is_none() {
+            return Err(invalid(format!(
+                "Cannot make change {change:?} to instance {instance}"
+            )));
+        }
+        let position = self.changes.partition_point(|c| c.time <= time);
+        self.changes.insert(
+            position,
+            ScheduledChange {
+                time,
+                instance: instance.to_string(),
+                change,
+            },
+        );
+        Ok(self)
+    }
+
+    /// The node of an initial voltage or nodeset, which must not be
+    /// ground and must be in the circuit
+    fn check_node(&self, node: usize, purpose: &str) -> Result<(), EsimError> {
+        if node == 0 || node > self.circuit.num_voltage_nodes() {
+            return Err(invalid(format!("No node {node} {purpose}")));
+        }
+        Ok(())
+    }
+
+    /// Set the initial voltage of a node (SPICE `.IC`), replacing any
+    /// given before. Panics if the node is ground or not in the
+    /// circuit (see [TransientAnalysis::try_initial_voltage]).
+    pub fn initial_voltage(self, node: usize, voltage: f64) -> Self {
+        self.try_initial_voltage(node, voltage)
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Set the initial voltage of a node as for
+    /// [TransientAnalysis::initial_voltage], returning an error if the
+    /// node is ground or not in the circuit
+    pub fn try_initial_voltage(mut self, node: usize, voltage: f64) -> Result<Self, EsimError> {
+        self.check_node(node, "to set the initial voltage of")?;
+        self.initial_voltages.retain(|(n, _)| *n != node);
+        self.initial_voltages.push((node, voltage));
+        Ok(self)
+    }
+
+    /// Set the approximate voltage of a node, from which the operating
+    /// point is solved (SPICE `.NODESET`; see [crate::dc]), replacing
+    /// any given before. Panics if the node is ground or not in the
+    /// circuit (see [TransientAnalysis::try_nodeset]).
+    pub fn nodeset(self, node: usize, voltage: f64) -> Self {
+        self.try_nodeset(node, voltage)
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Set the approximate voltage of a node as for
+    /// [TransientAnalysis::nodeset], returning an error if the node is
+    /// ground or not in the circuit
+    pub fn try_nodeset(mut self, node: usize, voltage: f64) -> Result<Self, EsimError> {
+        self.check_node(node, "for a nodeset")?;
+        self.nodesets.retain(|(n, _)| *n != node);
+        self.nodesets.push((node, voltage));
+        Ok(self)
+    }
+
+    /// Set the initial current of an inductor, saturable inductor or
+    /// relay coil, by instance name, replacing any given before. The
+    /// current is only used when starting from the initial conditions.
+    /// Panics if there is no such instance (see
+    /// [TransientAnalysis::try_initial_current]).
+    pub fn initial_current(self, instance: &str, current: f64) -> Self {
+        self.try_initial_current(instance, current)
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Set the initial current of an inductor as for
+    /// [TransientAnalysis::initial_current], returning an error if
+    /// there is no such instance
+    pub fn try_initial_current(mut self, instance: &str, current: f64) -> Result<Self, EsimError> {
+        let edge = self
+            .circuit
+            .instances()
+            .iter()
+            .find(|i| i.name == instance)
+            .and_then(|i| match i.component {
+                Component::Inductor { current_edge, .. }
+                | Component::SaturableInductor { current_edge, .. } => Some(current_edge),
+                Component::Relay { coil_edge, .. } => Some(coil_edge),
+                _ => None,
+            })
+            .ok_or_else(|| invalid(format!("No inductor named {instance}")))?;
+        self.initial_currents.retain(|(e, _)| *e != edge);
+        self.initial_currents.push((edge, current));
+        Ok(self)
+    }
+
+    /// Set the initial voltage of every node and current of every
+    /// edge, as the solution to start from when the initial
+    /// conditions are used
+    pub(crate) fn initial_solution(mut self, voltages: &[f64], currents: &[f64]) -> Self {
+        self.initial_voltages = (1..).zip(voltages.iter().copied()).collect();
+        self.initial_currents = (0..).zip(currents.iter().copied()).collect();
+        self
+    }
+
+    /// The first breakpoint of the source waveforms after a time (see
+    /// [Waveform::next_breakpoint](crate::waveform::Waveform::next_breakpoint)),
+    /// which a step lands on
+    fn next_breakpoint(&self, t: f64) -> Option<f64> {
+        self.circuit
+            .instances()
+            .iter()
+            .filter_map(|instance| match &instance.component {
+                Component::IndependentVoltageSource {
+                    waveform: Some(waveform),
+                    ..
+                }
+                | Component::IndependentCurrentSource {
+                    waveform: Some(waveform),
+                    ..
+                } => waveform.next_breakpoint(t),
+                _ => None,
+            })
+            .min_by(f64::total_cmp)
+    }
+
+    /// Make the scheduled changes nearest a time point (or at it, if
+    /// the step is controlled), recording an event for each. The first
+    /// of the changes is at the position next, which is advanced past
+    /// the changes made. Fails if a change cannot be made to the
+    /// instance as an earlier change left it.
+    fn make_changes(
+        &mut self,
+        t: f64,
+        next: &mut usize,
+        events: &mut Vec<TransientEvent>,
+    ) -> Result<(), EsimError> {
+        let until = match self.options.step_control {
+            Some(_) => t + CHANGE_RESOLUTION * self.step,
+            None => t + 0.5 * self.step,
+        };
+        while let Some(scheduled) = self.changes.get(*next).filter(|c| c.time < until) {
+            let instance = self
+                .circuit
+                .instances_mut()
+                .iter_mut()
+                .find(|i| i.name == scheduled.instance)
+                .ok_or_else(|| no_instance(&scheduled.instance))?;
+            instance.component = scheduled.change.apply(&instance.component).ok_or_else(|| {
+                invalid(format!(
+                    "Cannot make change {:?} to instance {}",
+                    scheduled.change, scheduled.instance
+                ))
+            })?;
+            events.push(TransientEvent {
+                time: t,
+                instance: scheduled.instance.clone(),
+                description: String::from(scheduled.change.description()),
+            });
+            *next += 1;
+        }
+        Ok(())
+    }
+
+    /// Solve the operating point with the sources at their time zero
+    /// values, from the nodesets, and with the nodes with initial
+    /// voltages held at them; or,
+    /// if the initial conditions are used, take the initial voltages
+    /// and currents (and zero for the rest) as the solution
+    fn operating_point(
+        &self,
+        junctions: &mut Vec<f64>,
+        warnings: &mut Vec<String>,
+    ) -> Result<(Vec<f64>, Vec<f64>), EsimError> {
+        if self.options.use_initial_conditions {
+            let mut voltages = vec![0.0; self.circuit.num_voltage_nodes()];
+            let mut currents = vec![0.0; self.circuit.num_current_edges()];
+            for (node, voltage) in &self.initial_voltages {
+                voltages[node - 1] = *voltage;
+            }
+            for (edge, current) in &self.initial_currents {
+                currents[*edge] = *current;
+            }
+            return Ok((voltages, currents));
+        }
+        let mut circuit = self.circuit.clone();
+        hold_nodes(&mut circuit, &self.initial_voltages);
+        for instance in circuit.instances_mut() {
+            match &mut instance.component {
+                Component::IndependentVoltageSource {
+                    voltage: value,
+                    waveform: Some(waveform),
+                    ..
+                }
+                | Component::IndependentCurrentSource {
+                    current: value,
+                    waveform: Some(waveform),
+                    ..
+                } => *value = waveform.value(0.0),
+                _ => {}
+            }
+        }
+        solve_nodesets(
+            &circuit,
+            junctions,
+            &self.nodesets,
+            &self.options.dc,
+            warnings,
+        )
+    }
+
+    /// Solve a time point by an integration scheme, from the solutions
+    /// at the previous two time points, the component states, and the
+    /// state of the Newton iteration
+    fn solve(
+        &self,
+        t: f64,
+        integrator: &dyn Integrator,
+        history: [&(Vec<f64>, Vec<f64>); 2],
+        states: &[DeviceState],
+        iteration: &mut NewtonContext,
+    ) -> Result<(Vec<f64>, Vec<f64>), EsimError> {
+        let coefficients = integrator.coefficients(self.ratio);
+        let instances = 0..self.circuit.instances().len();
+        let NewtonContext {
+            junctions,
+            cache,
+            linear,
+            debug,
+            warnings,
+        } = iteration;
+        let mut count = 0;
+        let mut previous = history[0].0.clone();
+        let options = &self.options.newton;
+        let solver = NonlinearSolver::from(*options);
+        let solve = |linearisation: &Linearisation| {
+            let linearised = linearisation.junctions;
+            let mut mna = self.assemble(
+                t,
+                &coefficients,
+                history,
+                states,
+                linearised,
+                instances.clone(),
+            );
+            linearisation.add_devices(&mut mna);
+            let Some(debug) = debug else {
+                return mna.try_solve_with(linear);
+            };
+            let system = Stamp::from_mna(&mna);
+            let solution = mna.try_solve_with(linear)?;
+            count += 1;
+            let stamp = |name: &str| {
+                let index = self
+                    .circuit
+                    .instances()
+                    .iter()
+                    .position(|i| i.name == name)?;
+                let mna = self.assemble(
+                    t,
+                    &coefficients,
+                    history,
+                    states,
+                    linearised,
+                    index..index + 1,
+                );
+                Some(Stamp::from_mna(&mna))
+            };
+            debug.iteration(NewtonState {
+                time: t,
+                iteration: count,
+                breakpoint: None,
+                voltages: &solution.0,
+                currents: &solution.1,
+                previous: &previous,
+                system: &system,
+                stamp: &stamp,
+            });
+            previous.clone_from(&solution.0);
+            Ok(solution)
+        };
+        let iterate = solver.iterate(&self.circuit, &[], junctions, cache, &mut [], solve)?;
+        if !iterate.converged {
+            warnings.push(format!("time {t}: {}", not_converged(options)));
+        }
+        Ok(iterate.solution)
+    }
+
+    /// Assemble the MNA system at a time point, from the solutions at
+    /// the previous two time points (the latest first) and the
+    /// component states, with the given linearisation of each junction.
+    /// Only the stamps of a range of the instances are added.
+    fn assemble(
+        &self,
+        t: f64,
+        coefficients: &MultistepCoefficients,
+        history: [&(Vec<f64>, Vec<f64>); 2],
+        states: &[DeviceState],
+        linearised: &[Option<(f64, f64)>],
+        instances: Range<usize>,
+    ) -> Mna<f64> {
+        let h = self.step;
+        let MultistepCoefficients { a, b } = *coefficients;
+        // The companion conductance of a capacitor is a[0] C/h, and the
+        // companion resistance of an inductor a[0] L/h. Each is in
+        // parallel (or series) with a source from the history.
+        let [(voltages, currents), (older_voltages, older_currents)] = history;
+        let previous_voltage =
+            |term_1, term_2| node_voltage(voltages, term_1) - node_voltage(voltages, term_2);
+        let older_voltage = |term_1, term_2| {
+            node_voltage(older_voltages, term_1) - node_voltage(older_voltages, term_2)
+        };
+        // The flux of an inductance L from the history, over h
+        let flux = |inductance: f64, edge: usize| {
+            inductance / h * (a[1] * currents[edge] + a[2] * older_currents[edge])
+        };
+        let mut mna = Mna::new();
+        for index in instances {
+            let instance = &self.circuit.instances()[index];
+            match instance.component {
+                Component::Resistor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    resistance,
+                } => mna.add_resistor(term_1, term_2, current_edge, resistance),
+                Component::Thermistor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    model,
+                    temperature,
+                } => {
+                    let resistance = model.resistance(temperature.unwrap_or(NOMINAL_TEMPERATURE));
+                    mna.add_resistor(term_1, term_2, current_edge, resistance)
+                }
+                Component::Capacitor {
+                    term_1,
+                    term_2,
+                    capacitance,
+                } => {
+                    let g = a[0] * capacitance / h;
+                    let current = capacitance / h
+                        * (a[1] * previous_voltage(term_1, term_2)
+                            + a[2] * older_voltage(term_1, term_2))
+                        + b * states[index].current;
+                    mna.add_admittance(term_1, term_2, g);
+                    mna.add_independent_current_source(term_2, term_1, current);
+                }
+                Component::Inductor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    inductance,
+                } => {
+                    let r = a[0] * inductance / h;
+                    let voltage =
+                        -flux(inductance, current_edge) - b * previous_voltage(term_1, term_2);
+                    mna.add_resistor(term_1, term_2, Some(current_edge), r);
+                    mna.add_series_voltage(current_edge, voltage);
+                }
+                Component::SaturableInductor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    ref curve,
+                } => {
+                    // The inductance is taken at the previous current
+                    let inductance = curve.inductance(currents[current_edge]);
+                    let r = a[0] * inductance / h;
+                    let voltage =
+                        -flux(inductance, current_edge) - b * previous_voltage(term_1, term_2);
+                    mna.add_resistor(term_1, term_2, Some(current_edge), r);
+                    mna.add_series_voltage(current_edge, voltage);
+                }
+                Component::Crystal { .. }
+                | Component::Urc { .. }
+                | Component::Supercapacitor { .. }
+                | Component::SemiconductorResistor { .. }
+                | Component::SemiconductorCapacitor { .. } => {
+                    unreachable!("Macromodels are expanded by elaborate()")
+                }
+                Component::Diode { .. }
+                | Component::Photodiode { .. }
+                | Component::TunnelDiode { .. }
+                | Component::SchottkyDiode { .. }
+                | Component::Table { .. }
+                | Component::Compact { .. } => {
+                    let (anode, cathode, _) = instance.component.junction().unwrap();
+                    let (conductance, current) = linearised[index].unwrap();
+                    mna.add_admittance(anode, cathode, conductance);
+                    mna.add_independent_current_source(anode, cathode, current);
+                    if let Component::Photodiode {
+                        responsivity,
+                        irradiance,
+                        ..
+                    } = instance.component
+                    {
+                        mna.add_independent_current_source(
+                            cathode,
+                            anode,
+                            responsivity * irradiance,
+                        );
+                    }
+                }
+                // The transistors are added with the device stamps (see
+                // crate::solver)
+                Component::Bjt { .. } | Component::Mosfet { .. } => {}
+                Component::Battery {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    ref model,
+                    ..
+                } => {
+                    mna.add_resistor(term_pos, term_neg, Some(current_edge), model.r_int);
+                    let ocv = model.open_circuit_voltage(states[index].soc);
+                    mna.add_series_voltage(current_edge, ocv);
+                }
+                Component::LogicGate { .. } | Component::AdcBridge { .. } => {}
+                Component::DacBridge {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    family,
+                    ..
+                } => {
+                    mna.add_resistor(term_pos, term_neg, Some(current_edge), family.r_out);
+                    let voltage = family.output_voltage(states[index].on);
+                    mna.add_series_voltage(current_edge, voltage);
+                }
+                Component::NPort {
+                    ref ports,
+                    ref data,
+                } => mna.add_admittance_matrix(ports, &data.conductance()),
+                Component::Fuse {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    params,
+                } => {
+                    let open = states[index].i2t >= params.i2t;
+                    mna.add_resistor(term_1, term_2, Some(current_edge), params.resistance(open));
+                }
+                Component::Relay {
+                    coil_pos,
+                    coil_neg,
+                    contact_1,
+                    contact_2,
+                    coil_edge,
+                    contact_edge,
+                    params,
+                } => {
+                    // The coil is stamped like an inductor, with its resistance added
+                    let r = params.r_coil + a[0] * params.l_coil / h;
+                    // The previous voltage across the inductance alone
+                    let inductance_voltage =
+                        previous_voltage(coil_pos, coil_neg) - params.r_coil * currents[coil_edge];
+                    let voltage = -flux(params.l_coil, coil_edge) - b * inductance_voltage;
+                    mna.add_resistor(coil_pos, coil_neg, Some(coil_edge), r);
+                    mna.add_series_voltage(coil_edge, voltage);
+                    let r = params.resistance(states[index].on);
+                    mna.add_resistor(contact_1, contact_2, Some(contact_edge), r);
+                }
+                Component::Thyristor {
+                    anode,
+                    cathode,
+                    gate,
+                    current_edge,
+                    params,
+                    ..
+                } => {
+                    let r = params.resistance(states[index].on);
+                    mna.add_resistor(anode, cathode, Some(current_edge), r);
+                    mna.add_resistor(gate, cathode, None, params.r_gate);
+                }
+                Component::Igbt {
+                    collector,
+                    emitter,
+                    current_edge,
+                    params,
+                    ..
+                } => {
+                    let state = states[index];
+                    let r = params.resistance(state.on);
+                    mna.add_resistor(collector, emitter, Some(current_edge), r);
+                    if let (false, Some((time, current))) = (state.on, state.turned_off) {
+                        // The tail current is forced through the off resistance
+                        let tail = params.tail_current(current, t - time);
+                        mna.add_series_voltage(current_edge, -r * tail);
+                    }
+                }
+                Component::IndependentVoltageSource {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    voltage,
+                    ref waveform,
+                    ..
+                } => {
+                    let voltage = waveform.as_ref().map_or(voltage, |w| w.value(t));
+                    mna.add_independent_voltage_source(term_pos, term_neg, current_edge, voltage)
+                }
+                Component::CurrentProbe {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                } => mna.add_independent_voltage_source(term_pos, term_neg, current_edge, 0.0),
+                Component::MeasurementProbe {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    ..
+                } => {
+                    if let Some(current_edge) = current_edge {
+                        mna.add_independent_voltage_source(term_pos, term_neg, current_edge, 0.0)
+                    }
+                }
+                Component::IndependentCurrentSource {
+                    term_pos,
+                    term_neg,
+                    current,
+                    ref waveform,
+                    ..
+                } => {
+                    let current = waveform.as_ref().map_or(current, |w| w.value(t));
+                    mna.add_independent_current_source(term_pos, term_neg, current)
+                }
+                Component::VoltageControlledVoltageSource { .. }
+                | Component::VoltageControlledCurrentSource { .. }
+                | Component::CurrentControlledCurrentSource { .. }
+                | Component::CurrentControlledVoltageSource { .. } => {
+                    mna.add_controlled_source(&instance.component)
+                }
+            }
+        }
+        mna
+    }
+
+    /// Update the states of the switches from a solution at a time
+    /// point, recording an event for each change. Returns whether any
+    /// switch changed state.
+    fn update_switches(
+        &self,
+        t: f64,
+        solution: &(Vec<f64>, Vec<f64>),
+        states: &mut [DeviceState],
+        events: &mut Vec<TransientEvent>,
+    ) -> bool {
+        let (voltages, currents) = solution;
+        let mut changed = false;
+        for (index, instance) in self.circuit.instances().iter().enumerate() {
+            let state = &mut states[index];
+            let (on, current) = match instance.component {
+                Component::Thyristor {
+                    anode,
+                    cathode,
+                    gate,
+                    current_edge,
+                    kind,
+                    params,
+                } => {
+                    let voltage = node_voltage(voltages, anode) - node_voltage(voltages, cathode);
+                    let gate_current = (node_voltage(voltages, gate)
+                        - node_voltage(voltages, cathode))
+                        / params.r_gate;
+                    let current = currents[current_edge];
+                    let on = params.next_state(kind, state.on, voltage, current, gate_current);
+                    (on, current)
+                }
+                Component::Igbt {
+                    gate,
+                    emitter,
+                    current_edge,
+                    params,
+                    ..
+                } => {
+                    let gate_voltage =
+                        node_voltage(voltages, gate) - node_voltage(voltages, emitter);
+                    (gate_voltage >= params.vth, currents[current_edge])
+                }
+                Component::Relay {
+                    coil_edge,
+                    contact_edge,
+                    params,
+                    ..
+                } => {
+                    let energized = params.energized(state.energized, currents[coil_edge]);
+                    if energized != state.energized {
+                        state.energized = energized;
+                        state.energized_at = t;
+                    }
+                    // The delay is rounded to the nearest time step
+                    let elapsed = t - state.energized_at + 0.5 * self.step;
+                    let on = if elapsed >= params.delay(energized) {
+                        energized
+                    } else {
+                        state.on
+                    };
+                    (on, currents[contact_edge])
+                }
+                _ => continue,
+            };
+            if on != state.on {
+                state.on = on;
+                if !on {
+                    state.turned_off = Some((t, current));
+                }
+                changed = true;
+                events.push(TransientEvent {
+                    time: t,
+                    instance: instance.name.clone(),
+                    description: String::from(if on { "turned on" } else { "turned off" }),
+                });
+            }
+        }
+        changed
+    }
+
+    /// Advance the logic to a time point: drive the nets of the ADC
+    /// bridges from the solution, process the logic events up to the
+    /// time point (recording an event for each change of a gate or
+    /// bridge output), and set the levels of the DAC bridges. Returns
+    /// whether any DAC bridge changed level.
+    fn update_logic(
+        &self,
+        t: f64,
+        solution: &(Vec<f64>, Vec<f64>),
+        logic: &mut LogicSimulator,
+        states: &mut [DeviceState],
+        events: &mut Vec<TransientEvent>,
+    ) -> bool {
+        if logic.is_empty() {
+            return false;
+        }
+        let voltages = &solution.0;
+        for (index, instance) in self.circuit.instances().iter().enumerate() {
+            if let Component::AdcBridge {
+                term_pos,
+                term_neg,
+                net,
+                family,
+            } = instance.component
+            {
+                let voltage = node_voltage(voltages, term_pos) - node_voltage(voltages, term_neg);
+                let level = family.input_level(states[index].on, voltage);
+                states[index].on = level;
+                logic.schedule(t, net, level, index);
+            }
+        }
+        // Events are resolved to the nearest time point
+        let until = t + 0.5 * self.step;
+        for (_, driver, level) in logic.advance(&self.circuit, until) {
+            events.push(TransientEvent {
+                time: t,
+                instance: self.circuit.instances()[driver].name.clone(),
+                description: String::from(if level { "went high" } else { "went low" }),
+            });
+        }
+        let mut changed = false;
+        for (index, instance) in self.circuit.instances().iter().enumerate() {
+            if let Component::DacBridge { net, .. } = instance.component {
+                let level = logic.level(net);
+                if level != states[index].on {
+                    states[index].on = level;
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Update the currents of the capacitors from the solution at a
+    /// time point, solved by a scheme from the solutions at the
+    /// previous two time points
+    fn update_capacitor_currents(
+        &self,
+        integrator: &dyn Integrator,
+        history: [&(Vec<f64>, Vec<f64>); 2],
+        solution: &(Vec<f64>, Vec<f64>),
+        states: &mut [DeviceState],
+    ) {
+        let h = self.step;
+        let MultistepCoefficients { a, b } = integrator.coefficients(self.ratio);
+        for (index, instance) in self.circuit.instances().iter().enumerate() {
+            if let Component::Capacitor {
+                term_1,
+                term_2,
+                capacitance,
+            } = instance.component
+            {
+                let voltage = |voltages: &[f64]| {
+                    node_voltage(voltages, term_1) - node_voltage(voltages, term_2)
+                };
+                let state = &mut states[index];
+                state.current = capacitance / h
+                    * (a[0] * voltage(&solution.0)
+                        - a[1] * voltage(&history[0].0)
+                        - a[2] * voltage(&history[1].0))
+                    - b * state.current;
+            }
+        }
+    }
+
+    /// Integrate the states of the batteries and fuses over the time
+    /// step ending at a time point, from the currents at the time
+    /// point, and record them. An event is recorded when a battery
+    /// becomes full or empty, or a fuse opens.
+    fn integrate_states(
+        &self,
+        t: f64,
+        solution: &(Vec<f64>, Vec<f64>),
+        states: &mut [DeviceState],
+        events: &mut Vec<TransientEvent>,
+        recorded: &mut HashMap<String, Vec<f64>>,
+    ) {
+        let h = self.step;
+        for (index, instance) in self.circuit.instances().iter().enumerate() {
+            let state = &mut states[index];
+            let description = match instance.component {
+                Component::Battery {
+                    current_edge,
+                    ref model,
+                    ..
+                } => {
+                    let old = state.soc;
+                    state.soc = model.integrate(old, solution.1[current_edge], h);
+                    if state.soc == old {
+                        None
+                    } else if state.soc == 0.0 {
+                        Some("fully discharged")
+                    } else if state.soc == 1.0 {
+                        Some("fully charged")
+                    } else {
+                        None
+                    }
+                }
+                Component::Fuse {
+                    current_edge,
+                    params,
+                    ..
+                } => {
+                    let was_open = state.i2t >= params.i2t;
+                    state.i2t += solution.1[current_edge].powi(2) * h;
+                    (!was_open && state.i2t >= params.i2t).then_some("opened")
+                }
+                _ => continue,
+            };
+            if let Some(description) = description {
+                events.push(TransientEvent {
+                    time: t,
+                    instance: instance.name.clone(),
+                    description: String::from(description),
+                });
+            }
+            record_state(recorded, instance, state);
+        }
+    }
+
+    /// The largest time step the truncation error allows, from the
+    /// solution at a time point and the solutions before it (the
+    /// latest first), with the states of the components at the time
+    /// point and the previous one. The error of each capacitor charge
+    /// and inductor flux is estimated from its divided difference of
+    /// order one more than that of the scheme, as in SPICE. Infinite if
+    /// there are too few time points.
+    fn truncation_step(
+        &self,
+        control: &StepControl,
+        integrator: &dyn Integrator,
+        points: &[(f64, &[f64], &[f64])],
+        states: &[DeviceState],
+        previous_states: &[DeviceState],
+    ) -> f64 {
+        let order = integrator.order();
+        let Some(points) = points.get(..order + 2) else {
+            return f64::INFINITY;
+        };
+        let times: Vec<f64> = points.iter().map(|(t, _, _)| *t).collect();
+        let h = times[0] - times[1];
+        let voltage = |term_1, term_2, voltages: &[f64]| {
+            node_voltage(voltages, term_1) - node_voltage(voltages, term_2)
+        };
+        let mut step = f64::INFINITY;
+        for (index, instance) in self.circuit.instances().iter().enumerate() {
+            // The charge (or flux) at each point, the largest current
+            // (or voltage) over the step, and the tolerances of each
+            let (mut values, rate, absolute, smallest) = match instance.component {
+                Component::Capacitor {
+                    term_1,
+                    term_2,
+                    capacitance,
+                } => {
+                    let values = points
+                        .iter()
+                        .map(|(_, v, _)| capacitance * voltage(term_1, term_2, v))
+                        .collect::<Vec<f64>>();
+                    let rate = states[index]
+                        .current
+                        .abs()
+                        .max(previous_states[index].current.abs());
+                    (values, rate, control.abstol, control.chgtol)
+                }
+                Component::Inductor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    inductance,
+                } => {
+                    let values = points
+                        .iter()
+                        .map(|(_, _, i)| inductance * i[current_edge])
+                        .collect();
+                    let rate = points[..2]
+                        .iter()
+                        .map(|(_, v, _)| voltage(term_1, term_2, v).abs())
+                        .fold(0.0, f64::max);
+                    (values, rate, control.vntol, control.fluxtol)
+                }
+                Component::SaturableInductor {
+                    term_1,
+                    term_2,
+                    current_edge,
+                    ref curve,
+                } => {
+                    let values = points
+                        .iter()
+                        .map(|(_, _, i)| curve.flux(i[current_edge]))
+                        .collect();
+                    let rate = points[..2]
+                        .iter()
+                        .map(|(_, v, _)| voltage(term_1, term_2, v).abs())
+                        .fold(0.0, f64::max);
+                    (values, rate, control.vntol, control.fluxtol)
+                }
+                Component::Relay {
+                    coil_pos,
+                    coil_neg,
+                    coil_edge,
+                    params,
+                    ..
+                } => {
+                    let values = points
+                        .iter()
+                        .map(|(_, _, i)| params.l_coil * i[coil_edge])
+                        .collect();
+                    // The voltage across the inductance alone
+                    let rate = points[..2]
+                        .iter()
+                        .map(|(_, v, i)| {
+                            (voltage(coil_pos, coil_neg, v) - params.r_coil * i[coil_edge]).abs()
+                        })
+                        .fold(0.0, f64::max);
+                    (values, rate, control.vntol, control.fluxtol)
+                }
+                _ => continue,
+            };
+            let largest = values[..2].iter().fold(0.0, |m: f64, q| m.max(q.abs()));
+            let tolerance =
+                (absolute + control.reltol * rate).max(control.reltol * largest.max(smallest) / h);
+            let error = integrator.error_constant() * divided_difference(&times, &mut values).abs();
+            let bound = control.trtol * tolerance / error.max(absolute);
+            step = step.min(bound.powf(1.0 / order as f64));
+        }
+        step
+    }
+
+    /// The signals seen through the measurement probes
+    fn measure_probes(&self, result: &TransientResult) -> HashMap<String, Vec<f64>> {
+        self.circuit
+            .instances()
+            .iter()
+            .filter_map(|instance| match instance.component {
+                Component::MeasurementProbe {
+                    term_pos,
+                    term_neg,
+                    current_edge,
+                    params,
+                } => {
+                    let signal: Vec<f64> = match current_edge {
+                        Some(edge) => result.current(edge),
+                        None => result
+                            .voltage(term_pos)
+                            .iter()
+                            .zip(result.voltage(term_neg))
+                            .map(|(v1, v2)| v1 - v2)
+                            .collect(),
+                    };
+                    Some((instance.name.clone(), params.measure(&result.time, &signal)))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Run the analysis. Panics if the circuit cannot be solved (see
+    /// [TransientAnalysis::try_run]).
+    pub fn run(&self) -> TransientResult {
+        self.run_debug(None)
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Run the analysis, returning an error if the circuit fails its
+    /// check (see [Circuit::validate]), or the system of a time point
+    /// cannot be solved. Newton iterations that do not converge give
+    /// warnings (see [TransientResult::warnings]), as they do for
+    /// [TransientAnalysis::run].
+    pub fn try_run(&self) -> Result<TransientResult, EsimError> {
+        self.circuit.validate()?;
+        self.run_debug(None)
+            .map_err(|error| explain(&self.circuit, error))
+    }
+
+    /// Run the analysis under a debugger, which is paused at the
+    /// breakpoints (see [crate::debugger]). Panics as for
+    /// [TransientAnalysis::run].
+    pub fn debug(
+        &self,
+        breakpoints: &[Breakpoint],
+        debugger: &mut dyn Debugger,
+    ) -> TransientResult {
+        self.run_debug(Some(DebugSession::new(breakpoints, debugger)))
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    fn run_debug(&self, debug: Option<DebugSession>) -> Result<TransientResult, EsimError> {
+        let TransientOptions {
+            time_step,
+            stop_time,
+            step_control,
+            ..
+        } = self.options;
+        // The tolerance keeps a stop time that is a multiple of the
+        // step from gaining a step through rounding (as `10u` is just
+        // under 1e-5)
+        let num_steps = ((stop_time / time_step) - 1e-9).ceil() as usize;
+        let mut states: Vec<DeviceState> = self
+            .circuit
+            .instances()
+            .iter()
+            .map(|instance| match instance.component {
+                Component::Battery { soc, .. } => DeviceState {
+                    soc,
+                    ..Default::default()
+                },
+                _ => DeviceState::default(),
+            })
+            .collect();
+        let mut events = Vec::new();
+        let mut recorded = HashMap::new();
+        let mut logic = LogicSimulator::new(&self.circuit);
+
+        // The circuit as changed so far
+        let mut segment = self.clone();
+        let mut next_step = match step_control {
+            Some(_) => INITIAL_STEP * time_step,
+            None => time_step,
+        };
+        segment.step = next_step;
+        let mut next_change = 0;
+        segment.make_changes(0.0, &mut next_change, &mut events)?;
+
+        let mut iteration = NewtonContext {
+            junctions: Vec::new(),
+            cache: JunctionCache::default(),
+            linear: CachedSolver::default(),
+            debug,
+            warnings: Vec::new(),
+        };
+        let mut solution =
+            segment.operating_point(&mut iteration.junctions, &mut iteration.warnings)?;
+        // The solution at the time point before the previous one
+        let mut older = solution.clone();
+        let mut result = TransientResult {
+            time: vec![0.0],
+            voltages: vec![solution.0.clone()],
+            currents: vec![solution.1.clone()],
+            events: Vec::new(),
+            damped: Vec::new(),
+            rejected: 0,
+            probes: HashMap::new(),
+            states: HashMap::new(),
+            nodes: self.circuit.node_names().clone(),
+            warnings: Vec::new(),
+        };
+        for (instance, state) in self.circuit.instances().iter().zip(&states) {
+            record_state(&mut recorded, instance, state);
+        }
+        // Backward Euler is used for the first time point, and after
+        // events, scheduled changes and ringing
+        let integrator = &*self.integrator;
+        let mut damp = true;
+        let mut changes = vec![0.0; solution.0.len()];
+        let mut alternations = vec![0; solution.0.len()];
+        let mut previous_t = 0.0;
+        let mut previous_step = next_step;
+        // The next point of the fixed time step grid
+        let mut point = 1;
+        loop {
+            let breakpoint = segment.next_breakpoint(previous_t + CHANGE_RESOLUTION * next_step);
+            // The time the step lands on, if it is shortened to reach
+            // one, which is taken exactly rather than as the sum of the
+            // steps (so rounding does not accumulate)
+            let mut landing = None;
+            let mut h = match step_control {
+                None if point > num_steps => break,
+                None => {
+                    // The step is shortened to reach a breakpoint before
+                    // the next point of the grid,
+                    // the last of which is the stop time
+                    let grid = if point == num_steps {
+                        stop_time
+                    } else {
+                        point as f64 * time_step
+                    };
+                    let until = match breakpoint {
+                        Some(b) if b < grid - CHANGE_RESOLUTION * time_step => b,
+                        _ => {
+                            point += 1;
+                            grid
+                        }
+                    };
+                    landing = Some(until);
+                    until - previous_t
+                }
+                Some(_) if previous_t >= stop_time - CHANGE_RESOLUTION * next_step => break,
+                Some(_) => {
+                    // The step is shortened to reach the next scheduled
+                    // change, the next breakpoint, and the stop time
+                    let until = self.changes.get(next_change).map_or(stop_time, |c| {
+                        if c.time > previous_t {
+                            c.time.min(stop_time)
+                        } else {
+                            stop_time
+                        }
+                    });
+                    let until = breakpoint.map_or(until, |b| b.min(until));
+                    if until - previous_t <= next_step {
+                        landing = Some(until);
+                    }
+                    next_step.min(until - previous_t)
+                }
+            };
+            // The states before the time point, which are restored if
+            // the time step is rejected
+            let saved =
+                step_control.map(|_| (states.clone(), logic.clone(), iteration.junctions.clone()));
+            let (t, next, restart, num_events) = loop {
+                let t = landing.unwrap_or(previous_t + h);
+                segment.step = h;
+                segment.ratio = h / previous_step;
+                let num_events = events.len();
+                segment.make_changes(t, &mut next_change, &mut events)?;
+                if events.len() > num_events {
+                    // A junction may have been changed
+                    iteration.cache.clear();
+                }
+                let restart = damp || events.len() > num_events;
+                let method: &dyn Integrator = if restart { &BackwardEuler } else { integrator };
+                let history = [&solution, &older];
+                let mut next = segment.solve(t, method, history, &states, &mut iteration)?;
+                let mut iterations = 0;
+                while segment.update_switches(t, &next, &mut states, &mut events)
+                    | segment.update_logic(t, &next, &mut logic, &mut states, &mut events)
+                {
+                    iterations += 1;
+                    if iterations > MAX_EVENT_ITERATIONS {
+                        iteration
+                            .warnings
+                            .push(format!("time {t}: switching events did not settle"));
+                        break;
+                    }
+                    next = segment.solve(t, method, history, &states, &mut iteration)?;
+                }
+                segment.update_capacitor_currents(method, history, &next, &mut states);
+                let (Some(control), Some((saved_states, saved_logic, saved_junctions))) =
+                    (step_control, &saved)
+                else {
+                    break (t, next, restart, num_events);
+                };
+                let points: Vec<(f64, &[f64], &[f64])> =
+                    std::iter::once((t, &next.0[..], &next.1[..]))
+                        .chain(
+                            result
+                                .time
+                                .iter()
+                                .zip(&result.voltages)
+                                .zip(&result.currents)
+                                .rev()
+                                .take(method.order() + 1)
+                                .map(|((t, v), i)| (*t, &v[..], &i[..])),
+                        )
+                        .collect();
+                let allowed =
+                    segment.truncation_step(&control, method, &points, &states, saved_states);
+                // Steps that start after a discontinuity are kept, since
+                // the error estimate spans it
+                if allowed < STEP_REJECTION * h
+                    && h > control.min_step
+                    && !restart
+                    && events.len() == num_events
+                {
+                    states.clone_from(saved_states);
+                    logic.clone_from(saved_logic);
+                    iteration.junctions.clone_from(saved_junctions);
+                    result.rejected += 1;
+                    h = allowed.max(control.min_step);
+                    landing = None;
+                    continue;
+                }
+                next_step = allowed
+                    .min(MAX_STEP_GROWTH * h)
+                    .min(time_step)
+                    .max(control.min_step);
+                break (t, next, restart, num_events);
+            };
+            // The step after a breakpoint is taken by backward Euler, as
+            // after an event
+            let at_breakpoint = breakpoint.is_some_and(|b| t >= b - CHANGE_RESOLUTION * h);
+            damp = events.len() > num_events || at_breakpoint;
+            if !integrator.damped() {
+                let ringing = detect_ringing(&solution.0, &next.0, &mut changes, &mut alternations);
+                if ringing && !restart {
+                    result.damped.push(t);
+                    damp = true;
+                }
+            }
+            older = std::mem::replace(&mut solution, next);
+            previous_step = h;
+            previous_t = t;
+            segment.integrate_states(t, &solution, &mut states, &mut events, &mut recorded);
+            result.time.push(t);
+            result.voltages.push(solution.0.clone());
+            result.currents.push(solution.1.clone());
+        }
+        result.events = events;
+        result.probes = self.measure_probes(&result);
+        result.states = recorded;
+        result.warnings = iteration.warnings;
+        result.warnings.extend_from_slice(logic.warnings());
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::CircuitBuilder;
+    use crate::value::parse_value;
+    use crate::waveform::Waveform;
+
+    fn rc_circuit() -> Circuit {
+        CircuitBuilder::new()
+            .vsource("V1", "in", "0", 1.0)
+            .resistor("R1", "in", "out", 1e3)
+            .capacitor("C1", "out", "0", 1e-6)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn try_run_rejects_an_invalid_circuit() {
+        let mut circuit = rc_circuit();
+        circuit.add_component(
+            "R2",
+            Component::Resistor {
+                term_1: 1,
+                term_2: 1,
+                current_edge: None,
+                resistance: 1e3,
+            },
+        );
+        let result = TransientAnalysis::new(&circuit, TransientOptions::new(1e-5, 1e-3)).try_run();
+        assert!(matches!(result, Err(EsimError::Assembly { .. })));
+    }
+
+    #[test]
+    fn try_run_solves_a_valid_circuit() {
+        let circuit = rc_circuit();
+        let options = TransientOptions::new(1e-5, 1e-3);
+        let result = TransientAnalysis::new(&circuit, options).try_run().unwrap();
+        let expected = TransientAnalysis::new(&circuit, options).run();
+        assert_eq!(result.time, expected.time);
+        assert_eq!(result.voltages, expected.voltages);
+    }
+
+    #[test]
+    fn fixed_steps_land_on_the_breakpoints() {
+        let pulse = Waveform::Pulse {
+            v1: 0.0,
+            v2: 1.0,
+            delay: 15e-6,
+            rise: 1e-6,
+            fall: 1e-6,
+            width: 20e-6,
+            period: 1.0,
+        };
+        let circuit = CircuitBuilder::new()
+            .vsource("V1", "in", "0", 0.0)
+            .with_waveform(pulse)
+            .resistor("R1", "in", "0", 1e3)
+            .build()
+            .unwrap();
+        let result = TransientAnalysis::new(&circuit, TransientOptions::new(1e-5, 1e-4)).run();
+        let input = result.node_voltage("in").unwrap();
+        for corner in [15e-6, 16e-6, 36e-6, 37e-6] {
+            let index = result.time.iter().position(|t| (t - corner).abs() < 1e-15);
+            let index = index.unwrap_or_else(|| panic!("{:?}", result.time));
+            let expected = if corner == 15e-6 || corner == 37e-6 {
+                0.0
+            } else {
+                1.0
+            };
+            assert!((input[index] - expected).abs() < 1e-9);
+        }
+        // The other time points are exact multiples of the step
+        let grid: Vec<f64> = (0..=10).map(|n| n as f64 * 1e-5).collect();
+        assert!(grid.iter().all(|t| result.time.contains(t)));
+        assert_eq!(result.time.len(), grid.len() + 4);
+    }
+
+    #[test]
+    fn fixed_steps_end_at_the_stop_time() {
+        let circuit = rc_circuit();
+        let time_step = parse_value("10u").unwrap();
+        let result = TransientAnalysis::new(&circuit, TransientOptions::new(time_step, 2e-3)).run();
+        assert_eq!(result.time.len(), 201);
+        assert_eq!(result.time.last(), Some(&2e-3));
+    }
+
+    #[test]
+    fn unknown_instances_and_nodes_are_errors() {
+        let circuit = rc_circuit();
+        let analysis = || TransientAnalysis::new(&circuit, TransientOptions::new(1e-5, 1e-3));
+        let schedule = analysis().try_schedule(1e-4, "R9", ComponentChange::Value(2e3));
+        assert!(matches!(schedule, Err(EsimError::Invalid { .. })));
+        let ground = analysis().try_initial_voltage(0, 1.0);
+        assert!(matches!(ground, Err(EsimError::Invalid { .. })));
+        let missing = analysis().try_nodeset(9, 1.0);
+        assert!(matches!(missing, Err(EsimError::Invalid { .. })));
+        assert!(analysis().try_initial_voltage(2, 0.5).is_ok());
+    }
+}
@@ -0,0 +1,188 @@
+//! Two-port parameters
+//!
+//! The small-signal parameters of a circuit seen between two ports,
+//! each a pair of nodes, at each frequency. The ports are driven by
+//! voltage sources (as in AC analysis, with the other independent
+//! sources set to zero), one at a time with the other shorted, which
+//! gives the admittance (Y) parameters from the currents of the
+//! sources. These are converted to the impedance (Z), hybrid (h) and
+//! transmission (ABCD) parameters.
+//!
+//! The current of each port flows into the circuit at its positive
+//! node, and out at its negative node. The transmission parameters
+//! take the current of port 2 the same way, so that
+//! $V_1 = A V_2 - B I_2$ and $I_1 = C V_2 - D I_2$.
+//!
+//! Not every network has every representation: the Z parameters of a
+//! series impedance, and the transmission parameters of a network with
+//! no transmission from port 1 to port 2, do not exist, and come out
+//! infinite or NaN.
+
+use num::Complex;
+
+use crate::ac::LinearAcAnalysis;
+use crate::circuit::Circuit;
+
+/// The matrix of two-port parameters, by row then column
+pub type ParameterMatrix = [[Complex<f64>; 2]; 2];
+
+/// The representations of a two-port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Representation {
+    /// Z parameters: the port voltages from the port currents
+    Impedance,
+    /// Y parameters: the port currents from the port voltages
+    Admittance,
+    /// h parameters: the voltage of port 1 and current of port 2 from
+    /// the current of port 1 and voltage of port 2
+    Hybrid,
+    /// ABCD parameters: port 1 from port 2
+    Transmission,
+}
+
+/// Two-port parameters in one representation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TwoPortParameters {
+    pub representation: Representation,
+    pub matrix: ParameterMatrix,
+}
+
+fn determinant(m: &ParameterMatrix) -> Complex<f64> {
+    m[0][0] * m[1][1] - m[0][1] * m[1][0]
+}
+
+/// The matrix of a representation that is the same function of its
+/// partial inverse as the h parameters are of the Y parameters (and
+/// the reverse)
+fn hybrid_inverse(m: &ParameterMatrix) -> ParameterMatrix {
+    [
+        [1.0 / m[0][0], -m[0][1] / m[0][0]],
+        [m[1][0] / m[0][0], determinant(m) / m[0][0]],
+    ]
+}
+
+impl TwoPortParameters {
+    /// The Y parameters
+    fn admittance(&self) -> ParameterMatrix {
+        let m = &self.matrix;
+        match self.representation {
+            Representation::Admittance => *m,
+            Representation::Impedance => {
+                let det = determinant(m);
+                [
+                    [m[1][1] / det, -m[0][1] / det],
+                    [-m[1][0] / det, m[0][0] / det],
+                ]
+            }
+            Representation::Hybrid => hybrid_inverse(m),
+            Representation::Transmission => {
+                let b = m[0][1];
+                [[m[1][1] / b, -determinant(m) / b], [-1.0 / b, m[0][0] / b]]
+            }
+        }
+    }
+
+    /// The same parameters in another representation
+    pub fn convert(&self, representation: Representation) -> Self {
+        let y = self.admittance();
+        let matrix = match representation {
+            Representation::Admittance => y,
+            Representation::Impedance => {
+                let det = determinant(&y);
+                [
+                    [y[1][1] / det, -y[0][1] / det],
+                    [-y[1][0] / det, y[0][0] / det],
+                ]
+            }
+            Representation::Hybrid => hybrid_inverse(&y),
+            Representation::Transmission => {
+                let y21 = y[1][0];
+                [
+                    [-y[1][1] / y21, -1.0 / y21],
+                    [-determinant(&y) / y21, -y[0][0] / y21],
+                ]
+            }
+        };
+        Self {
+            representation,
+            matrix,
+        }
+    }
+}
+
+/// Two-port parameters of a circuit at each frequency
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoPortResult {
+    pub frequencies: Vec<f64>,
+    /// The Y parameters at each frequency
+    pub parameters: Vec<TwoPortParameters>,
+}
+
+impl TwoPortResult {
+    /// The matrix of a representation at each frequency
+    pub fn matrices(&self, representation: Representation) -> Vec<ParameterMatrix> {
+        self.parameters
+            .iter()
+            .map(|p| p.convert(representation).matrix)
+            .collect()
+    }
+
+    pub fn z(&self) -> Vec<ParameterMatrix> {
+        self.matrices(Representation::Impedance)
+    }
+
+    pub fn y(&self) -> Vec<ParameterMatrix> {
+        self.matrices(Representation::Admittance)
+    }
+
+    pub fn h(&self) -> Vec<ParameterMatrix> {
+        self.matrices(Representation::Hybrid)
+    }
+
+    pub fn abcd(&self) -> Vec<ParameterMatrix> {
+        self.matrices(Representation::Transmission)
+    }
+}
+
+/// Two-port parameters of a circuit between two ports, each given as
+/// its (positive, negative) nodes, at each frequency (in Hz)
+pub fn two_port(
+    circuit: &Circuit,
+    port_1: (usize, usize),
+    port_2: (usize, usize),
+    frequencies: &[f64],
+) -> TwoPortResult {
+    let analysis = LinearAcAnalysis::new(circuit);
+    let parameters = frequencies
+        .iter()
+        .map(|frequency| {
+            let mut mna = analysis.assemble(*frequency, false);
+            // The port sources take the edges after those of the circuit
+            let edge = mna.rhs().len() - mna.num_voltage_nodes();
+            let zero = Complex::new(0.0, 0.0);
+            mna.add_independent_voltage_source(port_1.0, port_1.1, edge, zero);
+            mna.add_independent_voltage_source(port_2.0, port_2.1, edge + 1, zero);
+            mna.next_rhs();
+            mna.add_series_voltage(edge, Complex::new(1.0, 0.0));
+            mna.next_rhs();
+            mna.add_series_voltage(edge + 1, Complex::new(1.0, 0.0));
+            // The first right-hand side is from the assembly, and is zero
+            let solutions = mna.solve_columns();
+            // The edge current of a source flows out of the circuit at its
+            // positive node
+            let mut matrix = [[zero; 2]; 2];
+            for (column, (_, currents)) in solutions[1..].iter().enumerate() {
+                matrix[0][column] = -currents[edge];
+                matrix[1][column] = -currents[edge + 1];
+            }
+            TwoPortParameters {
+                representation: Representation::Admittance,
+                matrix,
+            }
+        })
+        .collect();
+    TwoPortResult {
+        frequencies: frequencies.to_vec(),
+        parameters,
+    }
+}
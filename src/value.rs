@@ -0,0 +1,226 @@
+//! Parsing of component values
+//!
+//! Values are numbers in engineering notation, such as `4.7k` or
+//! `100n`, optionally followed by a unit that is ignored (as in
+//! SPICE, so `1F` is one femto, not one farad). The APIs that build a
+//! circuit from strings take their values through [IntoValue], so a
+//! value can be given as a number or in the same notation as in a
+//! netlist.
+//!
+//! Values copied from tools using other conventions are normalized
+//! first (see [normalize], which describes each change):
+//!
+//! - the micro sign (`µ`) or Greek mu (`μ`) becomes `u`
+//! - a comma decimal separator (`4,7k`) becomes a point; the last of
+//!   the commas and points is the decimal separator (`1.000,5` and
+//!   `1,000.5`), and the others, or all of them if the last is
+//!   repeated (`1.000.000`), are thousands separators and are removed
+//! - spaces inside the value (`4.7 k`, `1 000`) are removed
+//! - the ohm sign (`Ω`) is removed
+//! - the letter or `R` in place of the decimal point (`4k7`, `2R2`)
+//!   is expanded to `4.7k` and `2.2`
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueError {
+    pub text: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to parse value '{}': {}", self.text, self.message)
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+/// A value string in the form accepted by the suffix parser, along
+/// with a description of each change made to the original
+#[derive(Debug, Clone, PartialEq)]
+pub struct Normalized {
+    pub text: String,
+    pub changes: Vec<String>,
+}
+
+/// Multipliers that can follow a number, in the order they are
+/// matched (longest first)
+const SCALE_SUFFIXES: [(&str, f64); 11] = [
+    ("meg", 1e6),
+    ("mil", 25.4e-6),
+    ("f", 1e-15),
+    ("p", 1e-12),
+    ("n", 1e-9),
+    ("u", 1e-6),
+    ("m", 1e-3),
+    ("k", 1e3),
+    ("g", 1e9),
+    ("t", 1e12),
+    ("r", 1.0),
+];
+
+/// Expand letter-as-decimal-point notation (`4k7`, `2R2`, `1u5`)
+fn expand_rkm(text: &str) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let position = chars.iter().position(|c| c.is_ascii_alphabetic())?;
+    let letter = chars[position].to_ascii_lowercase();
+    let (before, after) = (&chars[..position], &chars[position + 1..]);
+    let all_digits = |s: &[char]| !s.is_empty() && s.iter().all(|c| c.is_ascii_digit());
+    if !all_digits(before) || !all_digits(after) || !"fpnumkgtr".contains(letter) {
+        return None;
+    }
+    let before: String = before.iter().collect();
+    let after: String = after.iter().collect();
+    if letter == 'r' {
+        Some(format!("{before}.{after}"))
+    } else {
+        Some(format!("{before}.{after}{letter}"))
+    }
+}
+
+/// Convert a value from other conventions into SPICE notation
+pub fn normalize(text: &str) -> Normalized {
+    let mut changes = Vec::new();
+    let mut out = text.trim().to_string();
+
+    if out.contains(['µ', 'μ']) {
+        out = out.replace(['µ', 'μ'], "u");
+        changes.push(String::from("replaced micro sign with 'u'"));
+    }
+    if out.contains('Ω') {
+        out = out.replace('Ω', "");
+        changes.push(String::from("removed ohm sign"));
+    }
+    if out.contains(char::is_whitespace) {
+        out.retain(|c| !c.is_whitespace());
+        changes.push(String::from("removed spaces"));
+    }
+    // The decimal mark is the last separator, unless it is repeated
+    // (as in `1.000.000`); the other separators are thousands separators
+    let decimal = match (out.rfind(','), out.rfind('.')) {
+        (Some(comma), Some(point)) => Some(if comma > point { ',' } else { '.' }),
+        (Some(_), None) => Some(','),
+        (None, Some(_)) => Some('.'),
+        (None, None) => None,
+    }
+    .filter(|mark| out.matches(*mark).count() == 1);
+    if out.contains(|c| matches!(c, ',' | '.') && Some(c) != decimal) {
+        out.retain(|c| !matches!(c, ',' | '.') || Some(c) == decimal);
+        changes.push(String::from("removed thousands separators"));
+    }
+    if decimal == Some(',') {
+        out = out.replace(',', ".");
+        changes.push(String::from("replaced decimal comma with a point"));
+    }
+    if let Some(expanded) = expand_rkm(&out) {
+        changes.push(format!("expanded {out} to {expanded}"));
+        out = expanded;
+    }
+    Normalized { text: out, changes }
+}
+
+/// Parse a value in SPICE notation, without normalization
+pub fn parse_spice_value(text: &str) -> Result<f64, ValueError> {
+    let error = |message: &str| ValueError {
+        text: text.to_string(),
+        message: message.to_string(),
+    };
+    // The number is the longest prefix that parses as a float
+    let number_end = (1..=text.len())
+        .rev()
+        .filter(|end| text.is_char_boundary(*end))
+        .find(|end| {
+            let prefix = &text[..*end];
+            // Exclude forms Rust accepts but SPICE does not
+            !prefix.ends_with(['e', 'E']) && prefix.parse::<f64>().is_ok()
+        })
+        .filter(|end| {
+            let prefix = text[..*end].to_ascii_lowercase();
+            !prefix.contains("inf") && !prefix.contains("nan")
+        })
+        .ok_or_else(|| error("expected a number"))?;
+    let number: f64 = text[..number_end].parse().unwrap();
+    let rest = text[number_end..].to_ascii_lowercase();
+    if !rest.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(error("unexpected characters after the number"));
+    }
+    let scale = SCALE_SUFFIXES
+        .iter()
+        .find(|(suffix, _)| rest.starts_with(suffix))
+        .map_or(1.0, |(_, scale)| *scale);
+    Ok(number * scale)
+}
+
+/// A number as text that [parse_spice_value] reads back exactly: the
+/// shorter of its plain and scientific notations
+pub(crate) fn format_number(value: f64) -> String {
+    let (plain, scientific) = (value.to_string(), format!("{value:e}"));
+    if plain.len() <= scientific.len() {
+        plain
+    } else {
+        scientific
+    }
+}
+
+/// Parse a value, normalizing it first. The changes made are not
+/// reported; a caller that warns about them finds them by [normalize].
+pub fn parse_value(text: &str) -> Result<f64, ValueError> {
+    let normalized = normalize(text);
+    parse_spice_value(&normalized.text).map_err(|error| ValueError {
+        text: text.to_string(),
+        message: error.message,
+    })
+}
+
+/// A value given as a number, or as a string parsed by [parse_value]
+pub trait IntoValue {
+    fn into_value(self) -> Result<f64, ValueError>;
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Result<f64, ValueError> {
+        Ok(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Result<f64, ValueError> {
+        parse_value(self)
+    }
+}
+
+impl IntoValue for &String {
+    fn into_value(self) -> Result<f64, ValueError> {
+        parse_value(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Result<f64, ValueError> {
+        parse_value(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_separator_is_the_decimal_mark() {
+        assert_eq!(parse_value("1.000,5").unwrap(), 1000.5);
+        assert_eq!(parse_value("1,000.5").unwrap(), 1000.5);
+        assert_eq!(parse_value("4,7k").unwrap(), 4700.0);
+        assert_eq!(parse_value("1.000.000").unwrap(), 1e6);
+        assert_eq!(parse_value("1,000,000").unwrap(), 1e6);
+        assert_eq!(parse_value("4.7k").unwrap(), 4700.0);
+        assert_eq!(
+            normalize("1.000,5").changes,
+            [
+                "removed thousands separators",
+                "replaced decimal comma with a point"
+            ]
+        );
+        assert!(normalize("4.7k").changes.is_empty());
+    }
+}
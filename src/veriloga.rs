@@ -0,0 +1,642 @@
+//! Behavioral devices from a subset of Verilog-A
+//!
+//! A two-terminal Verilog-A module whose analog block is a list of
+//! contributions is compiled into a [CompactModel], without an
+//! external compiler (compare [crate::osdi]):
+//!
+//! ```text
+//! `include "disciplines.vams"
+//! module vdiode(a, c);
+//!     inout a, c;
+//!     electrical a, c;
+//!     parameter real is = 1e-14;
+//!     parameter real n = 1 from (0:inf);
+//!     parameter real cj = 1p;
+//!     real vd;
+//!     analog begin
+//!         vd = V(a, c);
+//!         I(a, c) <+ is*(limexp(vd/(n*$vt)) - 1);
+//!         I(a, c) <+ ddt(cj*vd);
+//!     end
+//! endmodule
+//! ```
+//!
+//! The subset is
+//!
+//! - a single module, with two ports, which are the anode and the
+//!   cathode of the junction (port directions and disciplines are
+//!   ignored, and there can be no internal nodes)
+//! - `parameter` declarations (`real` or `integer`), whose defaults are
+//!   expressions of the parameters declared before them (ranges given
+//!   by `from` and `exclude` are not checked)
+//! - `real` (or `integer`) variables, assigned by `x = ...;` before
+//!   they are used, whose expressions are substituted into the
+//!   statements after the assignment
+//! - contributions `I(a, c) <+ ...;` (or of `I(c, a)`, which are
+//!   negated), of the current as a function of `V(a, c)`, or
+//!   contributions `V(a, c) <+ ...;` of the voltage as a function of
+//!   `I(a, c)`, but not both. Several contributions to a branch are
+//!   added.
+//! - expressions as in [crate::expression], with `limexp` (as `exp`),
+//!   `$vt` (the thermal voltage at the temperature of the model) and
+//!   `$temperature` (in Kelvin), and `ddt` of a charge that is linear
+//!   in the voltage, which is a capacitance in parallel with the
+//!   junction (current contributions only)
+//!
+//! Conditional statements, the `?:` operator, analog functions,
+//! noise sources, macros (lines starting with a backtick are ignored)
+//! and other system functions are not supported.
+//!
+//! The conductance of the junction is the derivative of the current
+//! contribution with respect to the voltage, found by differentiating
+//! its expression when the model is loaded. For a voltage contribution,
+//! the current is found from the voltage by Newton iteration on the
+//! contribution, and the conductance is the inverse of its derivative
+//! with respect to the current. The change in the junction voltage
+//! between Newton iterations of the circuit is limited, and the devices
+//! are noiseless.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use regex::Regex;
+
+use crate::component::{thermal_voltage, CompactModel, Junction};
+use crate::expression::{is_builtin, parse_expression, Expression, Operator, Parameters};
+
+/// Largest change in the junction voltage between Newton iterations
+const MAX_STEP: f64 = 0.5;
+
+/// Settings of the Newton iteration for the current of a voltage
+/// contribution
+const MAX_ITERATIONS: usize = 200;
+const RELTOL: f64 = 1e-9;
+const ABSTOL: f64 = 1e-15;
+
+/// Names standing for the branch voltage and current in the compiled
+/// expressions, which cannot be the names of parameters
+const VOLTAGE: &str = "$v";
+const CURRENT: &str = "$i";
+
+/// Names standing for `$vt` and `$temperature`, which the source is
+/// rewritten to use, since the expressions have no system functions
+const VT: &str = "__vt";
+const TEMPERATURE: &str = "__temperature";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerilogAError {
+    pub message: String,
+}
+
+impl fmt::Display for VerilogAError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Verilog-A: {}", self.message)
+    }
+}
+
+impl std::error::Error for VerilogAError {}
+
+fn error(message: impl fmt::Display) -> VerilogAError {
+    VerilogAError {
+        message: message.to_string(),
+    }
+}
+
+/// What the contributions of a module give
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Contribution {
+    /// The current, as a function of the voltage
+    Current,
+    /// The voltage, as a function of the current
+    Voltage,
+}
+
+/// A compiled module
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerilogAModule {
+    pub name: String,
+    /// The ports, as (anode, cathode), in lower case
+    pub ports: (String, String),
+    pub contribution: Contribution,
+    /// The parameters, in lower case, with their default expressions
+    parameters: Vec<(String, Expression)>,
+    /// Sum of the contributions that are not charges
+    resistive: Expression,
+    /// Sum of the charges of the contributions (the arguments of `ddt`)
+    charge: Expression,
+}
+
+/// Compile the module of a Verilog-A source
+pub fn compile(text: &str) -> Result<VerilogAModule, VerilogAError> {
+    let text = Regex::new(r"(?s)/\*.*?\*/").unwrap().replace_all(text, " ");
+    let text: String = text
+        .lines()
+        .map(|line| line.split("//").next().unwrap())
+        .filter(|line| !line.trim_start().starts_with('`'))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .replace("$vt", VT)
+        .replace("$temperature", TEMPERATURE);
+    let module =
+        Regex::new(r"(?s)\bmodule\s+(\w+)\s*(?:\(([^)]*)\))?\s*;(.*?)\bendmodule\b").unwrap();
+    let mut modules = module.captures_iter(&text);
+    let captures = modules
+        .next()
+        .ok_or_else(|| error("no module ... endmodule"))?;
+    if modules.next().is_some() {
+        return Err(error("only one module in a source is supported"));
+    }
+    let name = captures[1].to_string();
+    let ports: Vec<String> = captures
+        .get(2)
+        .map_or("", |ports| ports.as_str())
+        .split(',')
+        .map(|port| port.trim().to_lowercase())
+        .filter(|port| !port.is_empty())
+        .collect();
+    let [anode, cathode] = <[String; 2]>::try_from(ports).map_err(|ports| {
+        error(format!(
+            "module {name} has {} ports, but only two-terminal modules are supported",
+            ports.len()
+        ))
+    })?;
+    let mut compiler = Compiler {
+        ports: (anode, cathode),
+        parameters: Vec::new(),
+        variables: Vec::new(),
+        contribution: None,
+        resistive: Expression::Number(0.0),
+        charge: Expression::Number(0.0),
+    };
+    // Blocks are flattened, since there are no conditional statements
+    let body = Regex::new(r"\b(begin|end)\b")
+        .unwrap()
+        .replace_all(&captures[3], ";");
+    for statement in body.split(';').map(str::trim) {
+        compiler
+            .statement(statement)
+            .map_err(|e| error(format!("module {name}: {}", e.message)))?;
+    }
+    let contribution = compiler
+        .contribution
+        .ok_or_else(|| error(format!("module {name} has no contributions")))?;
+    Ok(VerilogAModule {
+        name,
+        ports: compiler.ports,
+        contribution,
+        parameters: compiler.parameters,
+        resistive: compiler.resistive,
+        charge: compiler.charge,
+    })
+}
+
+/// Read a Verilog-A source from a file and compile its module
+pub fn compile_file(path: impl AsRef<Path>) -> Result<VerilogAModule, VerilogAError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)
+        .map_err(|e| error(format!("cannot read {}: {e}", path.display())))?;
+    compile(&text)
+}
+
+/// The state of a module being compiled
+struct Compiler {
+    ports: (String, String),
+    parameters: Vec<(String, Expression)>,
+    /// The variables assigned so far, with their expressions
+    variables: Vec<(String, Option<Expression>)>,
+    contribution: Option<Contribution>,
+    resistive: Expression,
+    charge: Expression,
+}
+
+impl Compiler {
+    fn statement(&mut self, statement: &str) -> Result<(), VerilogAError> {
+        let (keyword, rest) = statement
+            .split_once(char::is_whitespace)
+            .unwrap_or((statement, ""));
+        let rest = rest.trim();
+        match keyword {
+            "" => {}
+            "analog" => self.statement(rest)?,
+            "inout" | "input" | "output" | "electrical" => {
+                for node in names(rest) {
+                    if node != self.ports.0 && node != self.ports.1 {
+                        return Err(error(format!("internal node '{node}' is not supported")));
+                    }
+                }
+            }
+            "parameter" => {
+                let rest = match rest.split_once(char::is_whitespace) {
+                    Some(("real" | "integer", rest)) => rest,
+                    _ => rest,
+                };
+                // The range of a parameter is not checked
+                let range = Regex::new(r"\b(from|exclude)\b").unwrap();
+                for declaration in split_arguments(rest) {
+                    let declaration = range.split(declaration).next().unwrap();
+                    let (name, default) = declaration.split_once('=').ok_or_else(|| {
+                        error(format!("parameter '{}' has no default", declaration.trim()))
+                    })?;
+                    let default = self.expression(default)?;
+                    self.parameters.push((name.trim().to_lowercase(), default));
+                }
+            }
+            "real" | "integer" => {
+                for variable in names(rest) {
+                    self.variables.push((variable, None));
+                }
+            }
+            "if" | "else" | "case" | "for" | "while" | "repeat" => {
+                return Err(error(format!("'{keyword}' statements are not supported")))
+            }
+            _ => {
+                if let Some((branch, value)) = statement.split_once("<+") {
+                    self.contribute(branch.trim(), value)?;
+                } else if let Some((variable, value)) = statement.split_once('=') {
+                    let variable = variable.trim().to_lowercase();
+                    let value = self.expression(value)?;
+                    match self
+                        .variables
+                        .iter_mut()
+                        .find(|(name, _)| *name == variable)
+                    {
+                        Some((_, expression)) => *expression = Some(value),
+                        None => {
+                            return Err(error(format!("variable '{variable}' is not declared")))
+                        }
+                    }
+                } else {
+                    return Err(error(format!("unsupported statement '{statement}'")));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Add a contribution to the branch between the ports
+    fn contribute(&mut self, branch: &str, value: &str) -> Result<(), VerilogAError> {
+        let access = Regex::new(r"^([IV])\s*\(\s*(\w+)\s*(?:,\s*(\w+)\s*)?\)$").unwrap();
+        let captures = access
+            .captures(branch)
+            .ok_or_else(|| error(format!("expected I(a, c) or V(a, c), not '{branch}'")))?;
+        let contribution = match &captures[1] {
+            "I" => Contribution::Current,
+            _ => Contribution::Voltage,
+        };
+        if self.contribution.is_some_and(|c| c != contribution) {
+            return Err(error(
+                "current and voltage contributions to a branch are not supported together",
+            ));
+        }
+        self.contribution = Some(contribution);
+        let sign = self.direction(&captures[2], captures.get(3).map(|c| c.as_str()))?;
+        let (resistive, charge) = split(&self.expression(value)?)?;
+        let (unknown, name) = match contribution {
+            Contribution::Current => (CURRENT, "I"),
+            Contribution::Voltage => (VOLTAGE, "V"),
+        };
+        if resistive.depends_on(unknown) || charge.depends_on(unknown) {
+            return Err(error(format!(
+                "a contribution to {branch} cannot use {name}"
+            )));
+        }
+        if contribution == Contribution::Voltage && charge != Expression::Number(0.0) {
+            return Err(error("ddt is only supported in current contributions"));
+        }
+        let operator = if sign > 0.0 {
+            Operator::Add
+        } else {
+            Operator::Subtract
+        };
+        self.resistive = Expression::Binary(
+            operator,
+            Box::new(self.resistive.clone()),
+            Box::new(resistive),
+        );
+        self.charge = Expression::Binary(operator, Box::new(self.charge.clone()), Box::new(charge));
+        Ok(())
+    }
+
+    /// The sign of a branch between two nodes, which is 1 from the
+    /// anode to the cathode, and -1 the other way
+    fn direction(&self, a: &str, c: Option<&str>) -> Result<f64, VerilogAError> {
+        let (a, c) = (a.to_lowercase(), c.map(str::to_lowercase));
+        let Some(c) = c else {
+            return Err(error(format!(
+                "the potential of node '{a}' is not supported, only that between the ports"
+            )));
+        };
+        if (&a, &c) == (&self.ports.0, &self.ports.1) {
+            Ok(1.0)
+        } else if (&c, &a) == (&self.ports.0, &self.ports.1) {
+            Ok(-1.0)
+        } else {
+            Err(error(format!("branch ({a}, {c}) is not between the ports")))
+        }
+    }
+
+    /// Parse an expression, in terms of the parameters and the branch
+    /// voltage and current, with the variables substituted
+    fn expression(&self, text: &str) -> Result<Expression, VerilogAError> {
+        let expression = parse_expression(text.trim()).map_err(error)?;
+        self.resolve(&expression)
+    }
+
+    fn resolve(&self, expression: &Expression) -> Result<Expression, VerilogAError> {
+        Ok(match expression {
+            Expression::Number(_) => expression.clone(),
+            Expression::Parameter(name) => {
+                if let Some((_, value)) = self.variables.iter().find(|(n, _)| n == name) {
+                    value.clone().ok_or_else(|| {
+                        error(format!("variable '{name}' is used before it is assigned"))
+                    })?
+                } else if self.parameters.iter().any(|(n, _)| n == name)
+                    || name == VT
+                    || name == TEMPERATURE
+                {
+                    expression.clone()
+                } else {
+                    return Err(error(format!("unknown name '{name}'")));
+                }
+            }
+            Expression::Negate(inner) => Expression::Negate(Box::new(self.resolve(inner)?)),
+            Expression::Binary(operator, lhs, rhs) => Expression::Binary(
+                *operator,
+                Box::new(self.resolve(lhs)?),
+                Box::new(self.resolve(rhs)?),
+            ),
+            Expression::Call(function, args) if function == "v" || function == "i" => {
+                let nodes: Vec<&str> = args
+                    .iter()
+                    .map(|arg| match arg {
+                        Expression::Parameter(node) => Ok(node.as_str()),
+                        _ => Err(error(format!(
+                            "expected nodes in {}(...)",
+                            function.to_uppercase()
+                        ))),
+                    })
+                    .collect::<Result<_, _>>()?;
+                if nodes.len() > 2 {
+                    return Err(error(format!(
+                        "{}(...) has more than two nodes",
+                        function.to_uppercase()
+                    )));
+                }
+                let sign = self.direction(nodes[0], nodes.get(1).copied())?;
+                let name = match function.as_str() {
+                    "v" => VOLTAGE,
+                    _ => CURRENT,
+                };
+                let access = Expression::Parameter(name.to_string());
+                if sign > 0.0 {
+                    access
+                } else {
+                    Expression::Negate(Box::new(access))
+                }
+            }
+            Expression::Call(function, args) => {
+                let function = match function.as_str() {
+                    "limexp" => "exp",
+                    "ddt" => "ddt",
+                    function if is_builtin(function) => function,
+                    function => return Err(error(format!("unsupported function '{function}'"))),
+                };
+                let args = args
+                    .iter()
+                    .map(|arg| self.resolve(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if function == "ddt" && args.len() != 1 {
+                    return Err(error("ddt takes 1 argument"));
+                }
+                Expression::Call(function.to_string(), args)
+            }
+        })
+    }
+}
+
+/// The names of a declaration, in lower case
+fn names(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Split a list at the commas that are not inside parentheses
+fn split_arguments(list: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0i32, 0);
+    for (k, c) in list.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&list[start..k]);
+                start = k + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&list[start..]);
+    parts
+}
+
+fn has_ddt(expression: &Expression) -> bool {
+    match expression {
+        Expression::Number(_) | Expression::Parameter(_) => false,
+        Expression::Negate(inner) => has_ddt(inner),
+        Expression::Binary(_, lhs, rhs) => has_ddt(lhs) || has_ddt(rhs),
+        Expression::Call(function, args) => function == "ddt" || args.iter().any(has_ddt),
+    }
+}
+
+/// Split a contribution into the part that is not a charge, and the
+/// charge whose time derivative it adds, or an error if `ddt` is not
+/// applied to a term of the contribution
+fn split(expression: &Expression) -> Result<(Expression, Expression), VerilogAError> {
+    let zero = Expression::Number(0.0);
+    if !has_ddt(expression) {
+        return Ok((expression.clone(), zero));
+    }
+    let binary = |operator, lhs: Expression, rhs: Expression| {
+        Expression::Binary(operator, Box::new(lhs), Box::new(rhs))
+    };
+    Ok(match expression {
+        Expression::Call(function, args) if function == "ddt" && !has_ddt(&args[0]) => {
+            (zero, args[0].clone())
+        }
+        Expression::Negate(inner) => {
+            let (resistive, charge) = split(inner)?;
+            (
+                Expression::Negate(Box::new(resistive)),
+                Expression::Negate(Box::new(charge)),
+            )
+        }
+        Expression::Binary(operator @ (Operator::Add | Operator::Subtract), lhs, rhs) => {
+            let ((r1, q1), (r2, q2)) = (split(lhs)?, split(rhs)?);
+            (binary(*operator, r1, r2), binary(*operator, q1, q2))
+        }
+        Expression::Binary(Operator::Multiply, factor, term)
+        | Expression::Binary(Operator::Multiply, term, factor)
+            if !has_ddt(factor) =>
+        {
+            let (resistive, charge) = split(term)?;
+            (
+                binary(Operator::Multiply, *factor.clone(), resistive),
+                binary(Operator::Multiply, *factor.clone(), charge),
+            )
+        }
+        Expression::Binary(Operator::Divide, term, divisor) if !has_ddt(divisor) => {
+            let (resistive, charge) = split(term)?;
+            (
+                binary(Operator::Divide, resistive, *divisor.clone()),
+                binary(Operator::Divide, charge, *divisor.clone()),
+            )
+        }
+        _ => return Err(error("ddt must be applied to a term of a contribution")),
+    })
+}
+
+impl VerilogAModule {
+    /// Names of the parameters, in the order they are declared
+    pub fn parameters(&self) -> impl Iterator<Item = &str> {
+        self.parameters.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Load the module as a compact model, with parameters (by name,
+    /// case-insensitively) and the temperature in degrees Celsius
+    pub fn load(
+        &self,
+        parameters: &[(&str, f64)],
+        temperature: f64,
+    ) -> Result<CompactModel, VerilogAError> {
+        let fail = |message: String| error(format!("{}: {message}", self.name));
+        let mut table = Parameters::new();
+        for (name, default) in &self.parameters {
+            table.define(name, default.clone());
+        }
+        for (name, value) in parameters {
+            if !self
+                .parameters
+                .iter()
+                .any(|(n, _)| n.eq_ignore_ascii_case(name))
+            {
+                return Err(fail(format!("no parameter named '{name}'")));
+            }
+            table.set(name, *value);
+        }
+        table.set(VT, thermal_voltage(temperature));
+        table.set(TEMPERATURE, temperature + 273.15);
+        let names: Vec<String> = table.names().map(String::from).collect();
+        let values = names
+            .iter()
+            .map(|name| table.value(name))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| fail(e.message))?;
+        let resistive = self.resistive.bind(&names, &values);
+        let unknown = match self.contribution {
+            Contribution::Current => VOLTAGE,
+            Contribution::Voltage => CURRENT,
+        };
+        let derivative = resistive.derivative(unknown).map_err(|e| fail(e.message))?;
+        if self.contribution == Contribution::Voltage && !resistive.depends_on(CURRENT) {
+            return Err(fail(String::from(
+                "the voltage must depend on the current, since a source is not a junction",
+            )));
+        }
+        let capacitance = self
+            .charge
+            .bind(&names, &values)
+            .derivative(VOLTAGE)
+            .map_err(|e| fail(e.message))?;
+        if capacitance.depends_on(VOLTAGE) {
+            return Err(fail(String::from(
+                "only charges that are linear in the voltage are supported",
+            )));
+        }
+        let capacitance = Parameters::new()
+            .evaluate(&capacitance)
+            .map_err(|e| fail(e.message))?;
+        Ok(CompactModel::new(
+            &self.name,
+            Behavioral {
+                contribution: self.contribution,
+                unknown,
+                resistive,
+                derivative,
+                capacitance,
+                current: Mutex::new(0.0),
+            },
+        ))
+    }
+}
+
+/// A compiled module with its parameters, as a junction
+struct Behavioral {
+    contribution: Contribution,
+    /// The name of the variable of the contribution
+    unknown: &'static str,
+    resistive: Expression,
+    /// Derivative of the contribution with respect to its variable
+    derivative: Expression,
+    capacitance: f64,
+    /// The current at the last evaluation, from which the Newton
+    /// iteration of a voltage contribution starts
+    current: Mutex<f64>,
+}
+
+impl Behavioral {
+    /// The contribution and its derivative at a value of its variable,
+    /// which are NaN where they cannot be evaluated
+    fn contribution(&self, value: f64) -> (f64, f64) {
+        let mut table = Parameters::new();
+        table.set(self.unknown, value);
+        let evaluate = |expression: &Expression| table.evaluate(expression).unwrap_or(f64::NAN);
+        (evaluate(&self.resistive), evaluate(&self.derivative))
+    }
+}
+
+impl Junction for Behavioral {
+    fn evaluate(&self, voltage: f64) -> (f64, f64) {
+        if self.contribution == Contribution::Current {
+            return self.contribution(voltage);
+        }
+        // Solve for the current giving the voltage, halving steps that
+        // leave the domain of the contribution
+        let mut last = self.current.lock().unwrap();
+        let mut current = if last.is_finite() { *last } else { 0.0 };
+        let (mut value, mut slope) = self.contribution(current);
+        for _ in 0..MAX_ITERATIONS {
+            let mut step = (value - voltage) / slope;
+            if !step.is_finite() {
+                break;
+            }
+            let mut next = self.contribution(current - step);
+            while !(next.0.is_finite() && next.1.is_finite()) && step.abs() > ABSTOL {
+                step /= 2.0;
+                next = self.contribution(current - step);
+            }
+            current -= step;
+            (value, slope) = next;
+            if step.abs() < ABSTOL + RELTOL * current.abs() {
+                break;
+            }
+        }
+        *last = current;
+        (current, 1.0 / slope)
+    }
+
+    fn limit_voltage(&self, new: f64, old: f64) -> f64 {
+        old + (new - old).clamp(-MAX_STEP, MAX_STEP)
+    }
+
+    fn noise(&self, _current: f64, _frequency: f64) -> f64 {
+        0.0
+    }
+
+    fn capacitance(&self) -> f64 {
+        self.capacitance
+    }
+}
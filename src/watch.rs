@@ -0,0 +1,210 @@
+//! Re-running analyses when a netlist changes
+//!
+//! The watcher polls the modification time of a netlist file. When
+//! it changes, the netlist is reloaded into the circuit (see
+//! [Circuit::reload_file]), and if any component changed, the analyses are
+//! run again and their results are written next to the netlist as
+//! JSON datasets (for `amp.cir`, `amp.op.json`, `amp.dc.json`,
+//! `amp.ac.json` and `amp.tran.json`). They are also run again when
+//! the `.OPTIONS` lines of the netlist change (see [SimOptions]).
+//! If the netlist cannot be parsed, the error is printed and the last
+//! good circuit is kept until the netlist changes again. Only the
+//! netlist itself is polled, not the files it includes.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::ac::{ac_sweep_options, AcSweep};
+use crate::circuit::Circuit;
+use crate::dc::operating_point_options;
+use crate::evaluation;
+use crate::measure::{measure_all, MeasureError, Measurement};
+use crate::netlist::{parse_options_file, Dialect};
+use crate::options::SimOptions;
+use crate::schema::{to_json, v1::Dataset};
+use crate::sweep::{dc_sweep_options, SweepRange};
+use crate::transient::{ComponentChange, TransientAnalysis, TransientOptions};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Analysis {
+    OperatingPoint,
+    /// Operating points at each value of a component, by name
+    DcSweep {
+        instance: String,
+        range: SweepRange,
+    },
+    Ac(AcSweep),
+    Transient(TransientOptions),
+}
+
+impl Analysis {
+    /// Short name of the analysis, such as `tran`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::OperatingPoint => "op",
+            Self::DcSweep { .. } => "dc",
+            Self::Ac(_) => "ac",
+            Self::Transient(_) => "tran",
+        }
+    }
+
+    /// Extension of the file the results are written to
+    fn extension(&self) -> String {
+        format!("{}.json", self.name())
+    }
+
+    /// The analysis with the instance of a DC sweep (which can be given
+    /// in any case) named as in a circuit, or an error if the circuit
+    /// has no such instance, or its value cannot be swept
+    pub fn for_circuit(&self, circuit: &Circuit) -> Result<Self, String> {
+        let Self::DcSweep { instance, range } = self else {
+            return Ok(self.clone());
+        };
+        let found = circuit
+            .instances()
+            .iter()
+            .find(|i| i.name.eq_ignore_ascii_case(instance))
+            .ok_or_else(|| format!("no component named '{instance}'"))?;
+        if ComponentChange::Value(0.0)
+            .apply(&found.component)
+            .is_none()
+        {
+            return Err(format!("the value of '{}' cannot be swept", found.name));
+        }
+        Ok(Self::DcSweep {
+            instance: found.name.clone(),
+            range: *range,
+        })
+    }
+
+    /// Run the analysis on a circuit. Panics if the instance of a DC
+    /// sweep is not in the circuit (see [Analysis::for_circuit]).
+    pub fn run(&self, circuit: &Circuit) -> Dataset {
+        self.run_with(circuit, &SimOptions::default())
+    }
+
+    /// Run the analysis on a circuit with the simulator options, as
+    /// given by the `.OPTIONS` lines of a netlist. Panics as for
+    /// [Analysis::run].
+    pub fn run_with(&self, circuit: &Circuit, options: &SimOptions) -> Dataset {
+        self.run_measured(circuit, options, &[]).0
+    }
+
+    /// Run the analysis as for [Analysis::run_with], making the
+    /// measurements (see [crate::measure]) on the result if it is a
+    /// transient analysis (and none if it is not)
+    pub fn run_measured(
+        &self,
+        circuit: &Circuit,
+        options: &SimOptions,
+        measurements: &[Measurement],
+    ) -> (Dataset, Vec<Result<f64, MeasureError>>) {
+        let circuit = &options.at_temperature(circuit);
+        let dc = options.dc_options();
+        let dataset = match self {
+            Self::OperatingPoint => {
+                let solution = operating_point_options(circuit, &dc);
+                let mut dataset = Dataset::operating_point(&solution.voltages, &solution.currents);
+                dataset.warnings = solution.warnings;
+                dataset
+            }
+            Self::DcSweep { instance, range } => {
+                Dataset::from(&dc_sweep_options(circuit, instance, range, &dc.newton))
+            }
+            Self::Ac(sweep) => Dataset::from(&ac_sweep_options(circuit, sweep, &dc)),
+            Self::Transient(transient) => {
+                let result =
+                    TransientAnalysis::new(circuit, options.transient_options(*transient)).run();
+                return (Dataset::from(&result), measure_all(measurements, &result));
+            }
+        };
+        (dataset, Vec::new())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchOptions {
+    pub analyses: Vec<Analysis>,
+    /// Time between checks of the netlist
+    pub poll_interval: Duration,
+    /// Whether to print the device evaluation profile (see
+    /// [crate::evaluation::Profile]) of each run
+    pub profile: bool,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            analyses: vec![Analysis::OperatingPoint],
+            poll_interval: Duration::from_millis(500),
+            profile: false,
+        }
+    }
+}
+
+/// Run the analyses on a circuit with the simulator options and write
+/// their results next to the netlist. Returns the paths of the files
+/// written.
+pub fn run_analyses(
+    circuit: &Circuit,
+    netlist: &Path,
+    analyses: &[Analysis],
+    sim_options: &SimOptions,
+) -> io::Result<Vec<PathBuf>> {
+    analyses
+        .iter()
+        .map(|analysis| {
+            let path = netlist.with_extension(analysis.extension());
+            let json =
+                to_json(&analysis.run_with(circuit, sim_options)).map_err(io::Error::other)?;
+            fs::write(&path, json)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+fn modified(path: &Path) -> io::Result<SystemTime> {
+    fs::metadata(path)?.modified()
+}
+
+/// Watch a netlist, running the analyses each time it changes (and
+/// once at the start). This only returns if the netlist cannot be
+/// read or the results cannot be written.
+pub fn watch(netlist: &Path, options: &WatchOptions) -> io::Result<()> {
+    let mut circuit = Circuit::new();
+    let mut sim_options = None;
+    let mut last_modified = None;
+    loop {
+        let time = modified(netlist)?;
+        if last_modified != Some(time) {
+            last_modified = Some(time);
+            let reloaded = parse_options_file(netlist, Dialect::Esim)
+                .and_then(|sim| Ok((sim, circuit.reload_file(netlist)?)));
+            match reloaded {
+                Ok((sim, diff)) if diff.is_empty() && sim_options == Some(sim) => {}
+                Ok((sim, diff)) => {
+                    sim_options = Some(sim);
+                    eprintln!(
+                        "{}: {} added, {} removed, {} changed",
+                        netlist.display(),
+                        diff.added.len(),
+                        diff.removed.len(),
+                        diff.changed.len()
+                    );
+                    evaluation::reset_profile();
+                    for path in run_analyses(&circuit, netlist, &options.analyses, &sim)? {
+                        eprintln!("Wrote {}", path.display());
+                    }
+                    if options.profile {
+                        eprintln!("{}", evaluation::profile());
+                    }
+                }
+                Err(error) => eprintln!("{}: {error}", netlist.display()),
+            }
+        }
+        thread::sleep(options.poll_interval);
+    }
+}
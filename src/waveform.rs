@@ -0,0 +1,700 @@
+//! Time-domain source waveforms
+//!
+//! Waveforms are built from the SPICE primitives (pulse, sine and
+//! piecewise-linear) and composed with combinators that delay,
+//! repeat, concatenate or sum other waveforms, so that stimuli such
+//! as bursts and staircases do not need long PWL lists.
+//!
+//! The same waveforms can be written as text, using the SPICE syntax
+//! for the primitives and function-like syntax for the combinators,
+//! for example:
+//!
+//! ```text
+//! CONCAT(PWL(0 0 1u 1), REPEAT(3, PULSE(0 5 0 1n 1n 1u 2u)))
+//! SUM(SIN(0 1 1k), DELAY(2m, PWL(0 0 1u 0.5)))
+//! ```
+//!
+//! Primitive arguments are separated by spaces or commas, and
+//! combinator arguments by commas.
+//!
+//! Long stimuli can be streamed from a file of (time, value) points
+//! with `PWL FILE="stim.csv"`, optionally followed by `INTERP=LINEAR`
+//! or `INTERP=STEP`, and by `OUT=HOLD`, `OUT=ZERO` or `OUT=REPEAT`
+//! for the value outside the times in the file. A relative path is
+//! taken from the directory of the netlist (see [parse_waveform_in]).
+
+use std::path::Path;
+
+use crate::error::EsimError;
+use crate::value::{parse_spice_value, ValueError};
+
+pub use self::pwl_file::{Interpolation, OutOfRange, PwlFile};
+
+mod pwl_file;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Waveform {
+    /// Constant value
+    Dc(f64),
+    /// SPICE pulse: v1 until the delay, then ramps to v2 over the rise
+    /// time, holds for the width, ramps back over the fall time, and
+    /// repeats with the period
+    Pulse {
+        v1: f64,
+        v2: f64,
+        delay: f64,
+        rise: f64,
+        fall: f64,
+        width: f64,
+        period: f64,
+    },
+    /// SPICE damped sine: the offset until the delay, then
+    /// $V_o + V_a e^{-\theta (t - t_d)} \sin(2 \pi f (t - t_d))$
+    Sin {
+        offset: f64,
+        amplitude: f64,
+        frequency: f64,
+        delay: f64,
+        damping: f64,
+    },
+    /// Piecewise-linear (time, value) points, sorted by time. The
+    /// first and last values are held outside the points.
+    Pwl(Vec<(f64, f64)>),
+    /// Piecewise-linear points streamed from a file
+    PwlFile(PwlFile),
+    /// The waveform shifted later in time. Before the delay, it holds
+    /// its initial value.
+    Delay { delay: f64, waveform: Box<Waveform> },
+    /// The waveform repeated every duration of the waveform, a number
+    /// of times (or forever if there is no count). After the last
+    /// repetition, it holds its final value.
+    Repeat {
+        count: Option<usize>,
+        waveform: Box<Waveform>,
+    },
+    /// Each waveform in turn, for its duration, starting from its own
+    /// time zero. The last waveform continues after its duration.
+    Concat(Vec<Waveform>),
+    /// Sum of the waveforms
+    Sum(Vec<Waveform>),
+}
+
+impl Waveform {
+    /// Delay the waveform by a time
+    pub fn delay(self, delay: f64) -> Self {
+        Self::Delay {
+            delay,
+            waveform: Box::new(self),
+        }
+    }
+
+    /// Repeat the waveform a number of times. Panics if the waveform
+    /// has no finite duration.
+    pub fn repeat(self, count: usize) -> Self {
+        if self.duration().is_none() {
+            panic!("Cannot repeat a waveform with no finite duration");
+        }
+        Self::Repeat {
+            count: Some(count),
+            waveform: Box::new(self),
+        }
+    }
+
+    /// Follow this waveform with another
+    pub fn then(self, next: Waveform) -> Self {
+        match self {
+            Self::Concat(mut waveforms) => {
+                waveforms.push(next);
+                Self::Concat(waveforms)
+            }
+            waveform => Self::Concat(vec![waveform, next]),
+        }
+    }
+
+    /// Add another waveform to this one
+    pub fn plus(self, other: Waveform) -> Self {
+        match self {
+            Self::Sum(mut waveforms) => {
+                waveforms.push(other);
+                Self::Sum(waveforms)
+            }
+            waveform => Self::Sum(vec![waveform, other]),
+        }
+    }
+
+    /// Read through the files of the waveform, returning an error if
+    /// one cannot be read or parsed (see [PwlFile::check])
+    pub fn check(&self) -> Result<(), EsimError> {
+        match self {
+            Self::PwlFile(file) => file.check(),
+            Self::Delay { waveform, .. } | Self::Repeat { waveform, .. } => waveform.check(),
+            Self::Concat(waveforms) | Self::Sum(waveforms) => {
+                waveforms.iter().try_for_each(Waveform::check)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Length of one cycle or segment of the waveform, which is used
+    /// when it is repeated or concatenated. This is one period for
+    /// periodic primitives (including the delay), the time of the last
+    /// point for PWL, and none for constant or endless waveforms.
+    pub fn duration(&self) -> Option<f64> {
+        match self {
+            Self::Dc(_) => None,
+            Self::Pulse { delay, period, .. } => period.is_finite().then_some(delay + period),
+            Self::Sin {
+                frequency, delay, ..
+            } => (*frequency > 0.0).then(|| delay + 1.0 / frequency),
+            Self::Pwl(points) => points.last().map(|(t, _)| *t),
+            Self::PwlFile(file) => Some(file.duration()),
+            Self::Delay { delay, waveform } => waveform.duration().map(|d| d + delay),
+            Self::Repeat { count, waveform } => {
+                count.and_then(|n| waveform.duration().map(|d| d * n as f64))
+            }
+            Self::Concat(waveforms) | Self::Sum(waveforms) => {
+                waveforms.iter().map(|w| w.duration()).sum()
+            }
+        }
+    }
+
+    /// The first breakpoint of the waveform after a time: a time at
+    /// which its value or slope changes abruptly, such as an edge of a
+    /// pulse or a point of a PWL waveform, or none if there are no more.
+    /// A transient analysis with a controlled step lands on these.
+    pub fn next_breakpoint(&self, t: f64) -> Option<f64> {
+        match self {
+            Self::Dc(_) => None,
+            Self::Pulse {
+                delay,
+                rise,
+                fall,
+                width,
+                period,
+                ..
+            } => {
+                if t < *delay {
+                    return Some(*delay);
+                }
+                let periodic = period.is_finite() && *period > 0.0;
+                let (start, local) = if periodic {
+                    let n = ((t - delay) / period).floor();
+                    (delay + n * period, t - delay - n * period)
+                } else {
+                    (*delay, t - delay)
+                };
+                let corners = [*rise, rise + width, rise + width + fall];
+                match corners.into_iter().find(|c| *c > local) {
+                    Some(corner) => Some(start + corner),
+                    None => periodic.then(|| start + period),
+                }
+            }
+            Self::Sin { delay, .. } => (t < *delay).then_some(*delay),
+            Self::Pwl(points) => points.iter().map(|(time, _)| *time).find(|time| *time > t),
+            Self::PwlFile(file) => file.next_point_time(t),
+            Self::Delay { delay, waveform } => {
+                if t < *delay {
+                    Some(*delay)
+                } else {
+                    waveform.next_breakpoint(t - delay).map(|b| b + delay)
+                }
+            }
+            Self::Repeat { count, waveform } => {
+                let duration = waveform
+                    .duration()
+                    .expect("Repeated waveform has no finite duration");
+                let n = (t / duration).floor();
+                if count.is_some_and(|count| n >= count as f64) {
+                    return None;
+                }
+                let next = waveform
+                    .next_breakpoint(t - n * duration)
+                    .filter(|b| *b < duration)
+                    .unwrap_or(duration);
+                Some(n * duration + next)
+            }
+            Self::Concat(waveforms) => {
+                let mut start = 0.0;
+                for (k, waveform) in waveforms.iter().enumerate() {
+                    let last = k == waveforms.len() - 1;
+                    match waveform.duration() {
+                        Some(d) if !last && t >= start + d => start += d,
+                        Some(d) if !last => {
+                            let next = waveform
+                                .next_breakpoint(t - start)
+                                .filter(|b| *b < d)
+                                .unwrap_or(d);
+                            return Some(start + next);
+                        }
+                        _ => return waveform.next_breakpoint(t - start).map(|b| b + start),
+                    }
+                }
+                None
+            }
+            Self::Sum(waveforms) => waveforms
+                .iter()
+                .filter_map(|w| w.next_breakpoint(t))
+                .min_by(f64::total_cmp),
+        }
+    }
+
+    /// Value of the waveform at the time
+    pub fn value(&self, t: f64) -> f64 {
+        match self {
+            Self::Dc(value) => *value,
+            Self::Pulse {
+                v1,
+                v2,
+                delay,
+                rise,
+                fall,
+                width,
+                period,
+            } => {
+                if t < *delay {
+                    return *v1;
+                }
+                let mut t = t - delay;
+                if period.is_finite() && *period > 0.0 {
+                    t %= period;
+                }
+                if t < *rise {
+                    v1 + (v2 - v1) * t / rise
+                } else if t < rise + width {
+                    *v2
+                } else if t < rise + width + fall {
+                    v2 + (v1 - v2) * (t - rise - width) / fall
+                } else {
+                    *v1
+                }
+            }
+            Self::Sin {
+                offset,
+                amplitude,
+                frequency,
+                delay,
+                damping,
+            } => {
+                if t < *delay {
+                    *offset
+                } else {
+                    let t = t - delay;
+                    offset
+                        + amplitude
+                            * (-damping * t).exp()
+                            * (2.0 * std::f64::consts::PI * frequency * t).sin()
+                }
+            }
+            Self::Pwl(points) => pwl_value(points, t),
+            Self::PwlFile(file) => file.value(t),
+            Self::Delay { delay, waveform } => waveform.value((t - delay).max(0.0)),
+            Self::Repeat { count, waveform } => {
+                let duration = waveform
+                    .duration()
+                    .expect("Repeated waveform has no finite duration");
+                let n = (t / duration).floor();
+                match count {
+                    Some(count) if n >= *count as f64 => waveform.value(duration),
+                    _ => waveform.value(t - n * duration),
+                }
+            }
+            Self::Concat(waveforms) => {
+                let mut start = 0.0;
+                for (k, waveform) in waveforms.iter().enumerate() {
+                    let last = k == waveforms.len() - 1;
+                    match waveform.duration() {
+                        Some(d) if !last && t >= start + d => start += d,
+                        _ => return waveform.value(t - start),
+                    }
+                }
+                0.0
+            }
+            Self::Sum(waveforms) => waveforms.iter().map(|w| w.value(t)).sum(),
+        }
+    }
+}
+
+/// Linear interpolation of sorted (x, y) points, holding the end
+/// values outside the points
+pub(crate) fn pwl_value(points: &[(f64, f64)], x: f64) -> f64 {
+    match points.iter().position(|(px, _)| *px > x) {
+        None => points.last().map_or(0.0, |(_, y)| *y),
+        Some(0) => points[0].1,
+        Some(k) => {
+            let ((x1, y1), (x2, y2)) = (points[k - 1], points[k]);
+            y1 + (y2 - y1) * (x - x1) / (x2 - x1)
+        }
+    }
+}
+
+fn error(text: &str, message: &str) -> ValueError {
+    ValueError {
+        text: text.to_string(),
+        message: message.to_string(),
+    }
+}
+
+/// Split a string at the commas that are not inside parentheses
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (k, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(text[start..k].trim());
+                start = k + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(text[start..].trim());
+    parts
+}
+
+/// Parse the numbers of a primitive, separated by spaces or commas
+fn parse_numbers(text: &str) -> Result<Vec<f64>, ValueError> {
+    text.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|s| !s.is_empty())
+        .map(parse_spice_value)
+        .collect()
+}
+
+/// Parse a waveform from its text form (see the module documentation).
+/// A plain number, or `DC` followed by a number, is a constant.
+pub fn parse_waveform(text: &str) -> Result<Waveform, ValueError> {
+    parse_waveform_in(text, Path::new(""))
+}
+
+/// Parse a waveform as for [parse_waveform], with the relative paths
+/// of PWL files taken from a directory (such as that of a netlist)
+pub fn parse_waveform_in(text: &str, directory: &Path) -> Result<Waveform, ValueError> {
+    let text = text.trim();
+    if let Some(options) = pwl_file_options(text) {
+        return parse_pwl_file(text, options, directory);
+    }
+    let Some(open) = text.find('(') else {
+        let value = text
+            .strip_prefix("DC")
+            .or_else(|| text.strip_prefix("dc"))
+            .unwrap_or(text);
+        return Ok(Waveform::Dc(parse_spice_value(value.trim())?));
+    };
+    if !text.ends_with(')') {
+        return Err(error(text, "expected ')' at the end of the waveform"));
+    }
+    let name = text[..open].trim().to_ascii_uppercase();
+    let args = &text[open + 1..text.len() - 1];
+    match name.as_str() {
+        "PULSE" => {
+            let v = parse_numbers(args)?;
+            if v.len() < 2 || v.len() > 7 {
+                return Err(error(text, "PULSE takes between 2 and 7 values"));
+            }
+            let get = |k: usize, default: f64| v.get(k).copied().unwrap_or(default);
+            Ok(Waveform::Pulse {
+                v1: v[0],
+                v2: v[1],
+                delay: get(2, 0.0),
+                rise: get(3, 0.0),
+                fall: get(4, 0.0),
+                width: get(5, f64::INFINITY),
+                period: get(6, f64::INFINITY),
+            })
+        }
+        "SIN" => {
+            let v = parse_numbers(args)?;
+            if v.len() < 3 || v.len() > 5 {
+                return Err(error(text, "SIN takes between 3 and 5 values"));
+            }
+            let get = |k: usize| v.get(k).copied().unwrap_or(0.0);
+            Ok(Waveform::Sin {
+                offset: v[0],
+                amplitude: v[1],
+                frequency: v[2],
+                delay: get(3),
+                damping: get(4),
+            })
+        }
+        "PWL" => {
+            let v = parse_numbers(args)?;
+            if v.is_empty() || v.len() % 2 != 0 {
+                return Err(error(text, "PWL takes pairs of time and value"));
+            }
+            let points: Vec<(f64, f64)> = v.chunks(2).map(|p| (p[0], p[1])).collect();
+            if points.windows(2).any(|p| p[1].0 < p[0].0) {
+                return Err(error(text, "PWL times must be increasing"));
+            }
+            Ok(Waveform::Pwl(points))
+        }
+        "DELAY" | "REPEAT" => {
+            let parts = split_top_level(args);
+            if parts.len() != 2 {
+                return Err(error(text, "expected two arguments"));
+            }
+            let waveform = parse_waveform_in(parts[1], directory)?;
+            if name == "DELAY" {
+                return Ok(waveform.delay(parse_spice_value(parts[0])?));
+            }
+            if waveform.duration().is_none() {
+                return Err(error(
+                    text,
+                    "cannot repeat a waveform with no finite duration",
+                ));
+            }
+            let count = if parts[0].eq_ignore_ascii_case("inf") {
+                None
+            } else {
+                Some(
+                    parts[0]
+                        .parse()
+                        .map_err(|_| error(text, "expected a whole number of repeats"))?,
+                )
+            };
+            Ok(Waveform::Repeat {
+                count,
+                waveform: Box::new(waveform),
+            })
+        }
+        "CONCAT" | "SUM" => {
+            let waveforms = split_top_level(args)
+                .into_iter()
+                .map(|part| parse_waveform_in(part, directory))
+                .collect::<Result<Vec<_>, _>>()?;
+            if name == "CONCAT" {
+                Ok(Waveform::Concat(waveforms))
+            } else {
+                Ok(Waveform::Sum(waveforms))
+            }
+        }
+        _ => Err(error(text, "unknown waveform")),
+    }
+}
+
+/// The options of a `PWL FILE=...` waveform, without the surrounding
+/// parentheses (if any)
+fn pwl_file_options(text: &str) -> Option<&str> {
+    let rest = text
+        .get(..3)?
+        .eq_ignore_ascii_case("PWL")
+        .then(|| text[3..].trim())?;
+    let rest = match rest.strip_prefix('(') {
+        Some(inner) => inner.strip_suffix(')')?.trim(),
+        None => rest,
+    };
+    rest.get(..5)?.eq_ignore_ascii_case("FILE=").then_some(rest)
+}
+
+/// Split `KEY=value` options at spaces outside quotes
+fn split_options(text: &str) -> Vec<(String, String)> {
+    let mut options = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in text.chars().chain(std::iter::once(' ')) {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    let (key, value) = current.split_once('=').unwrap_or((&current, ""));
+                    options.push((key.to_ascii_uppercase(), value.to_string()));
+                    current.clear();
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    options
+}
+
+fn parse_pwl_file(text: &str, options: &str, directory: &Path) -> Result<Waveform, ValueError> {
+    let mut path = None;
+    let mut interpolation = Interpolation::default();
+    let mut out_of_range = OutOfRange::default();
+    for (key, value) in split_options(options) {
+        match (key.as_str(), value.to_ascii_uppercase().as_str()) {
+            ("FILE", _) => path = Some(value),
+            ("INTERP", "LINEAR") => interpolation = Interpolation::Linear,
+            ("INTERP", "STEP") => interpolation = Interpolation::Step,
+            ("OUT", "HOLD") => out_of_range = OutOfRange::Hold,
+            ("OUT", "ZERO") => out_of_range = OutOfRange::Zero,
+            ("OUT", "REPEAT") => out_of_range = OutOfRange::Repeat,
+            _ => return Err(error(text, &format!("unknown option '{key}={value}'"))),
+        }
+    }
+    let path = directory.join(path.ok_or_else(|| error(text, "expected FILE=<path>"))?);
+    if !path.is_file() {
+        return Err(error(
+            text,
+            &format!("PWL file '{}' does not exist", path.display()),
+        ));
+    }
+    PwlFile::try_new(path, interpolation, out_of_range)
+        .map(Waveform::PwlFile)
+        .map_err(|file_error| error(text, &file_error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-9 * b.abs().max(1.0)
+    }
+
+    fn ramp() -> Waveform {
+        Waveform::Pwl(vec![(0.0, 0.0), (1.0, 2.0)])
+    }
+
+    #[test]
+    fn combinators_shift_repeat_join_and_add() {
+        // Before the delay, the initial value is held
+        let delayed = ramp().delay(2.0);
+        assert_eq!(delayed.value(1.0), 0.0);
+        assert_eq!(delayed.value(2.5), 1.0);
+        assert_eq!(delayed.duration(), Some(3.0));
+
+        // After the last repetition, the final value is held
+        let repeated = ramp().repeat(2);
+        assert_eq!(repeated.value(1.5), 1.0);
+        assert_eq!(repeated.value(5.0), 2.0);
+        assert_eq!(repeated.duration(), Some(2.0));
+
+        // The last waveform continues after its duration
+        let joined = ramp().then(Waveform::Dc(5.0)).then(ramp());
+        assert!(matches!(&joined, Waveform::Concat(waveforms) if waveforms.len() == 3));
+        assert_eq!(joined.value(0.5), 1.0);
+        assert_eq!(joined.value(1.5), 5.0);
+
+        let summed = ramp().plus(Waveform::Dc(1.0)).plus(ramp().delay(1.0));
+        assert!(matches!(&summed, Waveform::Sum(waveforms) if waveforms.len() == 3));
+        assert_eq!(summed.value(0.5), 2.0);
+        assert_eq!(summed.value(1.5), 4.0);
+    }
+
+    #[test]
+    fn breakpoints_are_found_through_the_combinators() {
+        let pulse = Waveform::Pulse {
+            v1: 0.0,
+            v2: 1.0,
+            delay: 1.0,
+            rise: 0.1,
+            fall: 0.2,
+            width: 0.5,
+            period: 2.0,
+        };
+        let breakpoints = |waveform: &Waveform, times: &[f64]| -> Vec<Option<f64>> {
+            times.iter().map(|t| waveform.next_breakpoint(*t)).collect()
+        };
+        assert_eq!(
+            breakpoints(&pulse, &[0.0, 1.0, 1.2, 1.7, 2.0]),
+            [Some(1.0), Some(1.1), Some(1.6), Some(1.8), Some(3.0)]
+        );
+        assert_eq!(
+            breakpoints(&ramp(), &[-1.0, 0.0, 1.0]),
+            [Some(0.0), Some(1.0), None]
+        );
+        let delayed = ramp().delay(2.0);
+        assert_eq!(
+            breakpoints(&delayed, &[0.0, 2.0, 3.0]),
+            [Some(2.0), Some(3.0), None]
+        );
+        // The end of each repetition is a breakpoint, up to the last
+        let repeated = ramp().repeat(2);
+        assert_eq!(
+            breakpoints(&repeated, &[0.5, 1.0, 2.0]),
+            [Some(1.0), Some(2.0), None]
+        );
+        let joined = ramp().then(Waveform::Dc(5.0)).then(ramp());
+        assert_eq!(breakpoints(&joined, &[0.5, 1.0]), [Some(1.0), None]);
+        let summed = ramp().plus(ramp().delay(0.5));
+        assert_eq!(breakpoints(&summed, &[0.0, 0.6]), [Some(0.5), Some(1.0)]);
+        assert_eq!(Waveform::Dc(1.0).next_breakpoint(0.0), None);
+    }
+
+    #[test]
+    fn the_concatenated_example_is_a_ramp_then_three_pulses() {
+        let waveform =
+            parse_waveform("CONCAT(PWL(0 0 1u 1), REPEAT(3, PULSE(0 5 0 1n 1n 1u 2u)))").unwrap();
+        assert_eq!(waveform.duration(), Some(7e-6));
+        for (t, expected) in [
+            (0.5e-6, 0.5),
+            (1e-6 + 0.5e-9, 2.5),
+            (1.5e-6, 5.0),
+            (2.5e-6, 0.0),
+            (3.5e-6, 5.0),
+            (5.5e-6, 5.0),
+            (8e-6, 0.0),
+        ] {
+            assert!(close(waveform.value(t), expected), "value at {t}");
+        }
+        for (t, expected) in [
+            (0.0, Some(1e-6)),
+            (1e-6, Some(1e-6 + 1e-9)),
+            (1.5e-6, Some(2.001e-6)),
+            (2.5e-6, Some(3e-6)),
+        ] {
+            let next = waveform.next_breakpoint(t);
+            assert!(
+                next.zip(expected).is_some_and(|(a, b)| close(a, b)),
+                "{next:?} after {t}"
+            );
+        }
+        assert_eq!(waveform.next_breakpoint(7.5e-6), None);
+    }
+
+    #[test]
+    fn the_summed_example_is_a_sine_plus_a_delayed_step() {
+        let waveform = parse_waveform("SUM(SIN(0 1 1k), DELAY(2m, PWL(0 0 1u 0.5)))").unwrap();
+        assert!(close(waveform.value(0.25e-3), 1.0));
+        assert!(close(
+            waveform.value(2e-3 + 0.5e-6),
+            0.25 + (PI * 1e-3).sin()
+        ));
+        assert!(close(waveform.value(3e-3), 0.5));
+        assert_eq!(waveform.next_breakpoint(0.0), Some(2e-3));
+        assert_eq!(waveform.next_breakpoint(2e-3), Some(2e-3 + 1e-6));
+        assert_eq!(waveform.next_breakpoint(2.5e-3), None);
+    }
+
+    #[test]
+    fn waveforms_are_parsed_from_text() {
+        assert_eq!(parse_waveform("5").unwrap(), Waveform::Dc(5.0));
+        assert_eq!(parse_waveform("dc 1m").unwrap(), Waveform::Dc(1e-3));
+        assert_eq!(
+            parse_waveform("pulse(0, 1)").unwrap(),
+            Waveform::Pulse {
+                v1: 0.0,
+                v2: 1.0,
+                delay: 0.0,
+                rise: 0.0,
+                fall: 0.0,
+                width: f64::INFINITY,
+                period: f64::INFINITY,
+            }
+        );
+        assert_eq!(
+            parse_waveform("REPEAT(inf, DELAY(1, PWL(0 0 1 1)))").unwrap(),
+            Waveform::Repeat {
+                count: None,
+                waveform: Box::new(Waveform::Pwl(vec![(0.0, 0.0), (1.0, 1.0)]).delay(1.0)),
+            }
+        );
+        for text in [
+            "PULSE(0)",
+            "SIN(0 1)",
+            "PWL(0 0 1)",
+            "PWL(1 0 0 1)",
+            "REPEAT(2, SIN(0 1 0))",
+            "REPEAT(1.5, PWL(0 0 1 1))",
+            "DELAY(1m)",
+            "PWL(0 0",
+            "RAMP(0 1)",
+        ] {
+            assert!(parse_waveform(text).is_err(), "{text}");
+        }
+    }
+}
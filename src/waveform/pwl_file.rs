@@ -0,0 +1,365 @@
+//! Piecewise-linear waveforms read from a file
+//!
+//! Stimulus files can hold millions of points, so they are not loaded
+//! into memory. Instead, the points are read as the waveform is
+//! evaluated, keeping a window of recent points. Evaluating at
+//! increasing times (as in a transient analysis) reads the file once;
+//! going back before the window (after a rejected time step, for
+//! example) is handled from the window, or by reading the file again
+//! from the start.
+//!
+//! Each line of the file holds a time and a value, separated by a
+//! comma, semicolon or spaces (so CSV files can be used directly).
+//! Blank lines, comment lines starting with `#`, `*` or `;`, and a
+//! header line before the first point are skipped.
+//!
+//! The file is checked when the waveform is made with
+//! [PwlFile::try_new] (or by [PwlFile::check]), which reads it through
+//! once, so that a missing or malformed file is reported as an
+//! [EsimError::Io] or [EsimError::Parse] rather than found part way
+//! through an analysis.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::error::EsimError;
+use crate::value::parse_spice_value;
+
+/// Number of points kept behind the current time
+const HISTORY: usize = 256;
+
+/// A time and a value
+type Point = (f64, f64);
+
+/// How the value between two points is computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    /// Straight line between the points
+    #[default]
+    Linear,
+    /// Value of the earlier point, up to the next point
+    Step,
+}
+
+/// Value of the waveform outside the times in the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutOfRange {
+    /// Hold the first value before, and the last value after
+    #[default]
+    Hold,
+    /// Zero outside the points
+    Zero,
+    /// Repeat the file with a period of its last time (and hold the
+    /// first value before the first point)
+    Repeat,
+}
+
+/// Points streamed from the file
+struct Stream {
+    lines: Lines<BufReader<File>>,
+    line_number: usize,
+    num_points: usize,
+    window: VecDeque<(f64, f64)>,
+}
+
+/// A piecewise-linear waveform whose points are in a file
+pub struct PwlFile {
+    path: PathBuf,
+    pub interpolation: Interpolation,
+    pub out_of_range: OutOfRange,
+    /// First and last points in the file, found on first use
+    span: OnceLock<((f64, f64), (f64, f64))>,
+    stream: Mutex<Option<Stream>>,
+}
+
+impl PwlFile {
+    /// The file is not read until the waveform is evaluated, so errors
+    /// in the file cause a panic then (see [PwlFile::try_new]).
+    pub fn new(
+        path: impl AsRef<Path>,
+        interpolation: Interpolation,
+        out_of_range: OutOfRange,
+    ) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            interpolation,
+            out_of_range,
+            span: OnceLock::new(),
+            stream: Mutex::new(None),
+        }
+    }
+
+    /// The waveform of a file, which is read through to check it,
+    /// returning an error if it cannot be read, a line after the first
+    /// point is not a time and a value, the times decrease, or it has
+    /// no points
+    pub fn try_new(
+        path: impl AsRef<Path>,
+        interpolation: Interpolation,
+        out_of_range: OutOfRange,
+    ) -> Result<Self, EsimError> {
+        let file = Self::new(path, interpolation, out_of_range);
+        file.check()?;
+        Ok(file)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the file through (once), returning an error as for
+    /// [PwlFile::try_new]
+    pub fn check(&self) -> Result<(), EsimError> {
+        self.try_span().map(|_| ())
+    }
+
+    fn io_error(&self, error: std::io::Error) -> EsimError {
+        EsimError::Io {
+            path: self.path.display().to_string(),
+            message: error.to_string(),
+        }
+    }
+
+    fn parse_error(&self, line_number: usize, message: &str) -> EsimError {
+        EsimError::Parse {
+            message: format!(
+                "PWL file {} line {line_number}: {message}",
+                self.path.display()
+            ),
+        }
+    }
+
+    fn start(&self) -> Result<Stream, EsimError> {
+        let file = File::open(&self.path).map_err(|error| self.io_error(error))?;
+        Ok(Stream {
+            lines: BufReader::new(file).lines(),
+            line_number: 0,
+            num_points: 0,
+            window: VecDeque::new(),
+        })
+    }
+
+    /// Read the next point from the stream, or none at the end of the file
+    fn next_point(&self, stream: &mut Stream) -> Result<Option<(f64, f64)>, EsimError> {
+        for line in stream.lines.by_ref() {
+            stream.line_number += 1;
+            let line = line.map_err(|error| self.io_error(error))?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(['#', '*', ';']) {
+                continue;
+            }
+            let fields: Vec<&str> = line
+                .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+                .filter(|s| !s.is_empty())
+                .collect();
+            let point = match fields[..] {
+                [time, value, ..] => parse_spice_value(time)
+                    .and_then(|time| Ok((time, parse_spice_value(value)?)))
+                    .map_err(|error| error.to_string()),
+                _ => Err(String::from("expected a time and a value")),
+            };
+            match point {
+                Ok(point) => {
+                    if let Some(previous) = stream.window.back() {
+                        if point.0 < previous.0 {
+                            return Err(
+                                self.parse_error(stream.line_number, "times must be increasing")
+                            );
+                        }
+                    }
+                    stream.num_points += 1;
+                    return Ok(Some(point));
+                }
+                // A header line before the first point
+                Err(_) if stream.num_points == 0 => continue,
+                Err(error) => return Err(self.parse_error(stream.line_number, &error)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// First and last points in the file. Panics if the file cannot be
+    /// read (see [PwlFile::check]).
+    fn span(&self) -> ((f64, f64), (f64, f64)) {
+        self.try_span().unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// First and last points in the file, found by reading it through
+    /// the first time
+    fn try_span(&self) -> Result<(Point, Point), EsimError> {
+        if let Some(span) = self.span.get() {
+            return Ok(*span);
+        }
+        let mut stream = self.start()?;
+        let first = self
+            .next_point(&mut stream)?
+            .ok_or_else(|| EsimError::Parse {
+                message: format!("PWL file {} has no points", self.path.display()),
+            })?;
+        let mut last = first;
+        while let Some(point) = self.next_point(&mut stream)? {
+            // Keep the last point in the window, so the times are
+            // checked to increase
+            stream.window.clear();
+            stream.window.push_back(point);
+            last = point;
+        }
+        Ok(*self.span.get_or_init(|| (first, last)))
+    }
+
+    /// Time of the last point in the file
+    pub fn duration(&self) -> f64 {
+        self.span().1 .0
+    }
+
+    /// The points on either side of a time inside the file. Panics if
+    /// the file cannot be read again, or has changed since its span
+    /// was found.
+    fn interval(&self, t: f64) -> ((f64, f64), (f64, f64)) {
+        let mut guard = self.stream.lock().unwrap();
+        let restart = match guard.as_ref() {
+            Some(stream) => !stream.window.front().is_some_and(|p| p.0 <= t),
+            None => true,
+        };
+        if restart {
+            *guard = Some(self.start().unwrap_or_else(|error| panic!("{error}")));
+        }
+        let stream = guard.as_mut().unwrap();
+        while stream.window.len() < 2 || stream.window.back().unwrap().0 <= t {
+            let point = self
+                .next_point(stream)
+                .unwrap_or_else(|error| panic!("{error}"))
+                .expect("PWL file ended before the last point");
+            stream.window.push_back(point);
+            if stream.window.len() > HISTORY {
+                stream.window.pop_front();
+            }
+        }
+        let k = stream.window.partition_point(|p| p.0 <= t);
+        (stream.window[k - 1], stream.window[k])
+    }
+
+    /// Time of the first point after a time (repeating the points if
+    /// they repeat out of range), or none if there are no more
+    pub fn next_point_time(&self, t: f64) -> Option<f64> {
+        let (first, last) = self.span();
+        let (offset, t) = match self.out_of_range {
+            OutOfRange::Repeat if t >= last.0 && last.0 > 0.0 => {
+                let n = (t / last.0).floor();
+                (n * last.0, t - n * last.0)
+            }
+            _ => (0.0, t),
+        };
+        if t < first.0 {
+            Some(offset + first.0)
+        } else if t >= last.0 {
+            None
+        } else {
+            Some(offset + self.interval(t).1 .0)
+        }
+    }
+
+    /// Value of the waveform at the time
+    pub fn value(&self, t: f64) -> f64 {
+        let (first, last) = self.span();
+        let t = match self.out_of_range {
+            OutOfRange::Repeat if t > last.0 && last.0 > 0.0 => t % last.0,
+            _ => t,
+        };
+        let outside = match self.out_of_range {
+            OutOfRange::Zero => 0.0,
+            _ if t < first.0 => first.1,
+            _ => last.1,
+        };
+        if t < first.0 || t > last.0 {
+            return outside;
+        } else if t == last.0 {
+            return last.1;
+        }
+        let ((t1, v1), (t2, v2)) = self.interval(t);
+        match self.interpolation {
+            Interpolation::Linear => v1 + (v2 - v1) * (t - t1) / (t2 - t1),
+            Interpolation::Step => v1,
+        }
+    }
+}
+
+impl Clone for PwlFile {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            interpolation: self.interpolation,
+            out_of_range: self.out_of_range,
+            span: self.span.clone(),
+            stream: Mutex::new(None),
+        }
+    }
+}
+
+impl PartialEq for PwlFile {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+            && self.interpolation == other.interpolation
+            && self.out_of_range == other.out_of_range
+    }
+}
+
+impl fmt::Debug for PwlFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PwlFile")
+            .field("path", &self.path)
+            .field("interpolation", &self.interpolation)
+            .field("out_of_range", &self.out_of_range)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PWL file of the lines in the temporary directory
+    fn write(name: &str, lines: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("esim-{}-{name}.csv", std::process::id()));
+        std::fs::write(&path, lines).unwrap();
+        path
+    }
+
+    fn open(path: &Path) -> Result<PwlFile, EsimError> {
+        PwlFile::try_new(path, Interpolation::Linear, OutOfRange::Hold)
+    }
+
+    #[test]
+    fn points_are_read_after_a_header() {
+        let path = write("points", "time,value\n0,0\n1m,2\n# comment\n2m;4\n");
+        let file = open(&path).unwrap();
+        assert_eq!(file.duration(), 2e-3);
+        assert!((file.value(0.5e-3) - 1.0).abs() < 1e-12);
+        assert_eq!(file.next_point_time(1.5e-3), Some(2e-3));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn a_missing_file_is_an_io_error() {
+        let result = open(Path::new("no/such/stimulus.csv"));
+        assert!(matches!(result, Err(EsimError::Io { .. })));
+    }
+
+    #[test]
+    fn malformed_files_are_parse_errors() {
+        for (name, lines) in [
+            ("decreasing", "0 0\n2 1\n1 2\n"),
+            ("malformed", "0 0\n1 x\n"),
+            ("empty", "time value\n"),
+        ] {
+            let path = write(name, lines);
+            let result = open(&path);
+            assert!(matches!(result, Err(EsimError::Parse { .. })), "{name}");
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
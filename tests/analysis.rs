@@ -0,0 +1,18 @@
+//! The analyses through the `analysis` module
+
+use libesim::analysis::pole_zero;
+use libesim::CircuitBuilder;
+
+#[test]
+fn analyses_are_reached_through_the_analysis_module() {
+    let circuit = CircuitBuilder::new()
+        .vsource("V1", "in", "0", 1.0)
+        .resistor("R1", "in", "out", 1e3)
+        .capacitor("C1", "out", "0", 1e-6)
+        .build()
+        .unwrap();
+    let out = circuit.node_names().get("out").unwrap();
+    let result = pole_zero::pole_zero(&circuit, (out, 0), "V1");
+    assert_eq!(result.poles.len(), 1);
+    assert!((result.poles[0].re + 1e3).abs() < 1e-6);
+}
@@ -0,0 +1,109 @@
+//! The esim command line
+
+use std::env;
+use std::fs;
+use std::process::{Command, Output};
+
+/// Run an esim command on a deck written to the temporary directory
+fn esim(command: &str, name: &str, deck: &str) -> Output {
+    let path = env::temp_dir().join(format!("esim-cli-{}-{name}.cir", std::process::id()));
+    fs::write(&path, deck).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_esim"))
+        .arg(command)
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    output
+}
+
+/// Run esim on a deck, returning its standard output and standard
+/// error
+fn run(name: &str, deck: &str) -> (String, String) {
+    let output = esim("run", name, deck);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn run_skips_the_title_of_a_spice_deck() {
+    let (output, _) = run(
+        "title",
+        "Voltage divider\nV1 in 0 10\nR1 in out 1k\nR2 out 0 1k\n.op\n.end\n",
+    );
+    assert!(output.contains("v(out) (V) = 5e0"), "{output}");
+}
+
+#[test]
+fn run_parses_a_deck_without_a_title() {
+    let (output, _) = run("untitled", "V1 in 0 10\nR1 in out 1k\nR2 out 0 3k\n.op\n");
+    assert!(output.contains("v(out) (V) = 7.5e0"), "{output}");
+}
+
+#[test]
+fn run_prints_the_warnings_of_the_netlist() {
+    let (output, warnings) = run(
+        "warnings",
+        "V1 in 0 10\nR1 in 0 4k7\n.options nopage\n.op\n",
+    );
+    assert!(output.contains("v(in) (V) = 1e1"), "{output}");
+    assert!(
+        warnings.contains("line 2: value '4k7': expanded 4k7 to 4.7k"),
+        "{warnings}"
+    );
+    assert!(
+        warnings.contains("option 'nopage' is not supported"),
+        "{warnings}"
+    );
+}
+
+#[test]
+fn check_runs_the_electrical_rule_check() {
+    let output = esim("check", "parallel", "V1 a 0 1\nV2 a 0 2\nR1 a 0 1k\n");
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(!output.status.success(), "{report}");
+    assert!(
+        report.contains("loop of voltage sources and inductors: V1, V2"),
+        "{report}"
+    );
+    let output = esim("check", "floating", "I1 a b 1m\nR1 b 0 1k\n");
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(!output.status.success(), "{report}");
+    assert!(report.contains("node a is floating"), "{report}");
+    let output = esim("check", "divider", "V1 in 0 1\nR1 in out 1k\nR2 out 0 1k\n");
+    assert!(output.status.success());
+}
+
+#[test]
+fn run_prints_the_measurements_of_a_transient_analysis() {
+    let (output, _) = run(
+        "measure",
+        "V1 in 0 PWL(0 0 1m 1)\nR1 in out 1k\nR2 out 0 1k\n.tran 10u 1m\n\
+         .meas tran half FIND v(out) AT=1m\n",
+    );
+    assert!(output.ends_with("half = 5e-1\n"), "{output}");
+    let output = esim(
+        "run",
+        "unmeasured",
+        "V1 in 0 1\nR1 in 0 1k\n.tran 10u 1m\n.meas tran x WHEN v(in)=2\n",
+    );
+    let errors = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(
+        errors.contains("Measurement x: v(1) does not reach 2"),
+        "{errors}"
+    );
+    let output = esim(
+        "run",
+        "no-tran",
+        "V1 in 0 1\nR1 in 0 1k\n.meas tran x AVG v(in)\n",
+    );
+    assert!(!output.status.success());
+}
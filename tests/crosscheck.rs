@@ -0,0 +1,60 @@
+//! Cross-checks against ngspice, which must be installed. They are
+//! ignored by default; run them with `cargo test -- --ignored`.
+
+use libesim::ac::{AcSweep, Variation};
+use libesim::crosscheck::{cross_check, cross_check_circuit, CrossCheckOptions};
+use libesim::transient::TransientOptions;
+use libesim::watch::Analysis;
+use libesim::CircuitBuilder;
+
+const FILTER: &str = "\
+.SUBCKT rc a b
+R1 a mid 500
+R2 mid b 500
+.ENDS
+V1 in 0 SIN(0 1 1k) AC 1
+X1 in out rc
+C1 out 0 100n
+.END
+";
+
+#[test]
+#[ignore = "needs ngspice"]
+fn netlist_matches_ngspice() {
+    let analyses = [
+        Analysis::OperatingPoint,
+        Analysis::Ac(AcSweep::new(Variation::Decade, 10, 10.0, 1e5)),
+        Analysis::Transient(TransientOptions::new(1e-6, 2e-3)),
+    ];
+    let options = CrossCheckOptions {
+        reltol: 1e-2,
+        abstol: 1e-3,
+        ..Default::default()
+    };
+    let report = cross_check(FILTER, &analyses, &[], &options).unwrap();
+    // The internal node of the subcircuit is compared by its name
+    assert!(report
+        .divergences
+        .iter()
+        .any(|divergence| divergence.signal == "v(x1.mid)"));
+    assert!(report.passed(), "{report}");
+}
+
+#[test]
+#[ignore = "needs ngspice"]
+fn built_circuit_matches_ngspice() {
+    let circuit = CircuitBuilder::new()
+        .vsource("V1", "in", "0", 5.0)
+        .resistor("R1", "in", "out", 1e3)
+        .diode("D1", "out", "0")
+        .build()
+        .unwrap();
+    let report = cross_check_circuit(
+        &circuit,
+        &[Analysis::OperatingPoint],
+        &[],
+        &CrossCheckOptions::default(),
+    )
+    .unwrap();
+    assert!(report.passed(), "{report}");
+}